@@ -17,6 +17,11 @@ const idl = JSON.parse(
 const PROGRAM_ID = new PublicKey("7KoXq7yEB7HccYeCKu9559v38bArHYpKmnp42gYAUpnc");
 const GLOBAL_CONFIG_SEED = Buffer.from("global-config");
 const CLIENT_BUCKET_SEED = Buffer.from("client-bucket");
+const BPF_LOADER_UPGRADEABLE = new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
+
+// Mirrors the *_FLAG_* constants in constants.rs
+const CONFIG_FLAG_PAUSED = 1 << 0;
+const BUCKET_FLAG_BLOCKED = 1 << 0;
 
 function loadWallet(): Keypair {
   const walletPath = path.join(os.homedir(), ".config/solana/id.json");
@@ -51,6 +56,10 @@ async function initialize(maxRequests: number, windowSeconds: number, burstLimit
   const program = await getProgram();
   const wallet = loadWallet();
   const { globalConfigPda } = getPdas(wallet.publicKey, PROGRAM_ID);
+  const [programDataPda] = PublicKey.findProgramAddressSync(
+    [PROGRAM_ID.toBuffer()],
+    BPF_LOADER_UPGRADEABLE
+  );
 
   const tx = await program.methods
     .initialize({
@@ -61,6 +70,7 @@ async function initialize(maxRequests: number, windowSeconds: number, burstLimit
     .accounts({
       globalConfig: globalConfigPda,
       admin: wallet.publicKey,
+      programData: programDataPda,
       systemProgram: SystemProgram.programId,
     })
     .rpc();
@@ -136,7 +146,7 @@ async function status() {
   console.log(`   Admin: ${config.admin.toBase58()}`);
   console.log(`   Max requests: ${config.maxRequests.toNumber()} per ${config.windowSeconds.toNumber()}s`);
   console.log(`   Burst limit: ${config.burstLimit.toNumber()}`);
-  console.log(`   Paused: ${config.isPaused}`);
+  console.log(`   Paused: ${(config.flags & CONFIG_FLAG_PAUSED) !== 0}`);
 
   try {
     const bucket = await program.account.clientBucket.fetch(clientBucketPda);
@@ -148,7 +158,7 @@ async function status() {
     console.log(`   Used: ${bucket.requestCount.toNumber()}/${config.maxRequests.toNumber()}`);
     console.log(`   Window resets in: ${remaining}s`);
     console.log(`   Total lifetime requests: ${bucket.totalRequests.toNumber()}`);
-    console.log(`   Blocked: ${bucket.isBlocked}`);
+    console.log(`   Blocked: ${(bucket.flags & BUCKET_FLAG_BLOCKED) !== 0}`);
   } catch {
     console.log("\n👤 Not registered yet. Run: ts-node cli/index.ts register");
   }
@@ -192,7 +202,7 @@ async function pause() {
     .rpc();
 
   const config = await program.account.globalConfig.fetch(globalConfigPda);
-  console.log(`✅ Program paused: ${config.isPaused}`);
+  console.log(`✅ Program paused: ${(config.flags & CONFIG_FLAG_PAUSED) !== 0}`);
   console.log(`   Tx: ${tx}`);
 }
 
@@ -0,0 +1,181 @@
+use std::fmt;
+
+use solana_client::client_error::{ClientError as RpcError, ClientErrorKind};
+use solana_client::rpc_request::{RpcError as RpcRequestError, RpcResponseErrorData};
+use solana_rate_limiter::client::rpc::ClientError;
+use solana_rate_limiter::constants::MAX_AUTO_UNPAUSE_DELAY;
+use solana_rate_limiter::errors::RateLimiterError;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::transaction::TransactionError;
+
+// What a command failed with. Errors the program returns are reported as what an
+// operator can do about them; anything else keeps the SDK's or the node's words.
+#[derive(Debug)]
+pub enum CliError {
+    // The Solana CLI config or the keypair file
+    Setup(String),
+    // Turned down before anything was sent, e.g. unblock on a wallet not blocked
+    Refused(String),
+    Client(ClientError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Setup(message) | CliError::Refused(message) => f.write_str(message),
+            CliError::Client(ClientError::Rpc(err)) => f.write_str(&describe(err)),
+            CliError::Client(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<ClientError> for CliError {
+    fn from(err: ClientError) -> Self {
+        CliError::Client(err)
+    }
+}
+
+impl From<RpcError> for CliError {
+    fn from(err: RpcError) -> Self {
+        CliError::Client(ClientError::from(err))
+    }
+}
+
+fn describe(err: &RpcError) -> String {
+    let Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) =
+        err.get_transaction_error()
+    else {
+        return err.to_string();
+    };
+    hint(code)
+        .or_else(|| anchor_message(err))
+        .unwrap_or_else(|| format!("transaction failed with custom error {}", code))
+}
+
+// The program errors these commands run into, and what to do next
+fn hint(code: u32) -> Option<String> {
+    // Below Anchor's offset: the system program refusing an `init`
+    if code == 0 {
+        return Some(
+            "an account this creates already exists; see show-config or show-bucket".into(),
+        );
+    }
+    let hints = [
+        (
+            RateLimiterError::Unauthorized,
+            "the keypair is not this limiter's admin; pass the admin's with --keypair".to_string(),
+        ),
+        (
+            RateLimiterError::NotUpgradeAuthority,
+            "init must be signed by the program's upgrade authority".to_string(),
+        ),
+        (
+            RateLimiterError::CouncilApprovalRequired,
+            "this limiter is run by a council: propose the change with propose_action and collect approvals"
+                .to_string(),
+        ),
+        (
+            RateLimiterError::InvalidConfig,
+            "limits rejected: max-requests and window-seconds must be above zero, and burst-limit at least max-requests"
+                .to_string(),
+        ),
+        (
+            RateLimiterError::InvalidSchedule,
+            format!(
+                "a pause can end itself at most {}s from now",
+                MAX_AUTO_UNPAUSE_DELAY
+            ),
+        ),
+        (
+            RateLimiterError::ProgramPaused,
+            "the limiter is paused; an admin lifts it with `unpause`".to_string(),
+        ),
+        (
+            RateLimiterError::CircuitBreakerTripped,
+            "the circuit breaker tripped on a run of denials; an admin resets it with set_circuit_breaker"
+                .to_string(),
+        ),
+        (
+            RateLimiterError::ClientBlocked,
+            "the wallet is blocked; an admin lifts it with `unblock`, or file_appeal if the limiter takes appeals"
+                .to_string(),
+        ),
+        (
+            RateLimiterError::ClientNotRegistered,
+            "the wallet has no bucket; run `rate-limiter register` first".to_string(),
+        ),
+        (
+            RateLimiterError::ClientFrozen,
+            "the wallet is frozen; an admin lifts it with thaw_client".to_string(),
+        ),
+        (
+            RateLimiterError::RateLimitExceeded,
+            "the wallet has used this window's allowance; show-bucket says when it resets".to_string(),
+        ),
+        (
+            RateLimiterError::BurstLimitExceeded,
+            "the wallet is at its burst limit for this window; show-bucket says when it resets"
+                .to_string(),
+        ),
+    ];
+    hints
+        .into_iter()
+        .find(|(err, _)| u32::from(*err) == code)
+        .map(|(_, hint)| hint)
+}
+
+// For the program's other errors, the message Anchor logs with them, when the
+// node refused the transaction at preflight and sent the logs back
+fn anchor_message(err: &RpcError) -> Option<String> {
+    let ClientErrorKind::RpcError(RpcRequestError::RpcResponseError {
+        data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+        ..
+    }) = err.kind()
+    else {
+        return None;
+    };
+    result.logs.as_ref()?.iter().find_map(|line| {
+        let (_, message) = line.split_once("Error Message: ")?;
+        Some(message.trim_end_matches('.').to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed(code: u32) -> CliError {
+        RpcError::from(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code),
+        ))
+        .into()
+    }
+
+    #[test]
+    fn program_errors_say_what_to_do() {
+        let blocked = failed(u32::from(RateLimiterError::ClientBlocked)).to_string();
+        assert!(blocked.contains("`unblock`"), "{}", blocked);
+        let init_twice = failed(0).to_string();
+        assert!(init_twice.contains("already exists"), "{}", init_twice);
+        // Without preflight logs an unlisted code is reported as it is
+        let other = u32::from(RateLimiterError::ArithmeticOverflow);
+        assert_eq!(
+            failed(other).to_string(),
+            format!("transaction failed with custom error {}", other)
+        );
+    }
+
+    #[test]
+    fn other_failures_keep_their_wording() {
+        let err: CliError = RpcError::from(TransactionError::AccountNotFound).into();
+        assert_eq!(
+            err.to_string(),
+            RpcError::from(TransactionError::AccountNotFound).to_string()
+        );
+        let refused = CliError::Refused("not blocked".into());
+        assert_eq!(refused.to_string(), "not blocked");
+    }
+}
@@ -0,0 +1,734 @@
+use std::path::Path;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use solana_client::rpc_client::RpcClient;
+use solana_rate_limiter::client as sdk;
+use solana_rate_limiter::client::rpc::{self, ClientError};
+use solana_rate_limiter::constants::*;
+use solana_rate_limiter::state::{ClientBucket, GlobalConfig};
+use solana_rate_limiter::{logic, pda, RateLimiterConfig};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature, Signer};
+use solana_sdk::transaction::Transaction;
+
+use crate::errors::CliError;
+
+mod errors;
+
+// Operates a deployed limiter with the keypair and RPC URL the Solana CLI is set
+// up with. Every instruction comes from the program crate's client builders, so
+// the account lists follow the program rather than this file.
+
+#[derive(Parser)]
+#[command(
+    name = "rate-limiter",
+    version,
+    about = "Operate a deployed solana-rate-limiter"
+)]
+struct Cli {
+    #[arg(
+        long,
+        short = 'C',
+        global = true,
+        help = "Solana CLI config file [default: the Solana CLI's own]"
+    )]
+    config: Option<String>,
+    #[arg(
+        long,
+        short = 'u',
+        global = true,
+        help = "RPC URL or moniker (mainnet-beta, testnet, devnet, localhost)"
+    )]
+    url: Option<String>,
+    #[arg(long, short = 'k', global = true, help = "Keypair that signs and pays")]
+    keypair: Option<String>,
+    #[arg(long, global = true, help = "Print one JSON object instead of text")]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Create the config and stats accounts; the keypair becomes admin")]
+    Init {
+        #[arg(long)]
+        max_requests: u64,
+        #[arg(long)]
+        window_seconds: i64,
+        #[arg(
+            long,
+            help = "Per-window ceiling when spending burst credits [default: max-requests]"
+        )]
+        burst_limit: Option<u64>,
+        #[arg(long, default_value_t = 3600)]
+        snapshot_interval_seconds: i64,
+    },
+    #[command(about = "Change the limits; unset ones keep their current value")]
+    UpdateConfig {
+        #[arg(long)]
+        max_requests: Option<u64>,
+        #[arg(long)]
+        window_seconds: Option<i64>,
+        #[arg(long)]
+        burst_limit: Option<u64>,
+        #[arg(long, help = "Restart every bucket's window on its next consume")]
+        reset_windows: bool,
+    },
+    #[command(about = "Emergency pause: consumes and registrations are refused")]
+    Pause {
+        #[arg(long, value_enum, default_value_t = PauseReason::Unspecified)]
+        reason: PauseReason,
+        #[arg(
+            long = "for",
+            value_name = "SECONDS",
+            help = "End the pause by itself after this long"
+        )]
+        duration: Option<i64>,
+    },
+    #[command(about = "Lift the emergency pause")]
+    Unpause,
+    #[command(about = "Block a wallet's consumes until it is unblocked")]
+    Block { wallet: Pubkey },
+    #[command(about = "Lift a block; this also resets the wallet's window")]
+    Unblock { wallet: Pubkey },
+    #[command(about = "Clear a wallet's window, and any block")]
+    Reset { wallet: Pubkey },
+    #[command(about = "Register the keypair's own wallet, paying for its bucket")]
+    Register,
+    #[command(about = "Consume requests as the keypair's wallet")]
+    Consume {
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+        amount: u64,
+    },
+    #[command(about = "Close the keypair's own bucket and get its rent back")]
+    Close,
+    #[command(about = "The keypair's quota: what is left, when the window ends, any block")]
+    Status {
+        #[arg(long, help = "Poll and redraw until interrupted")]
+        watch: bool,
+        #[arg(long, value_name = "SECONDS", default_value_t = 5, requires = "watch")]
+        interval: u64,
+    },
+    #[command(about = "Print the limiter's config")]
+    ShowConfig,
+    #[command(about = "Print a wallet's bucket and what it has left")]
+    ShowBucket { wallet: Pubkey },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PauseReason {
+    Unspecified,
+    Maintenance,
+    Incident,
+    Migration,
+}
+
+impl PauseReason {
+    fn code(self) -> u8 {
+        match self {
+            PauseReason::Unspecified => PAUSE_REASON_UNSPECIFIED,
+            PauseReason::Maintenance => PAUSE_REASON_MAINTENANCE,
+            PauseReason::Incident => PAUSE_REASON_INCIDENT,
+            PauseReason::Migration => PAUSE_REASON_MIGRATION,
+        }
+    }
+}
+
+fn pause_reason_name(code: u8) -> &'static str {
+    match code {
+        PAUSE_REASON_MAINTENANCE => "maintenance",
+        PAUSE_REASON_INCIDENT => "incident",
+        PAUSE_REASON_MIGRATION => "migration",
+        _ => "unspecified",
+    }
+}
+
+// A command's result: its text, and the object --json prints instead
+struct Report {
+    text: String,
+    json: Value,
+}
+
+struct Context {
+    rpc: RpcClient,
+    signer: Keypair,
+}
+
+impl Context {
+    // Flags first, then the Solana CLI config, then the Solana CLI's defaults
+    fn load(cli: &Cli) -> Result<Self, CliError> {
+        let path = cli
+            .config
+            .clone()
+            .or_else(|| (*solana_cli_config::CONFIG_FILE).clone());
+        let config = match path {
+            Some(path) if Path::new(&path).exists() => solana_cli_config::Config::load(&path)
+                .map_err(|err| CliError::Setup(format!("reading {}: {}", path, err)))?,
+            // An explicit --config that is not there is a mistake; the default may
+            // simply never have been written
+            Some(path) if cli.config.is_some() => {
+                return Err(CliError::Setup(format!("no Solana CLI config at {}", path)))
+            }
+            _ => solana_cli_config::Config::default(),
+        };
+        let url = normalize_url(cli.url.as_deref().unwrap_or(&config.json_rpc_url));
+        let keypair_path = cli.keypair.clone().unwrap_or(config.keypair_path);
+        let signer = read_keypair_file(&keypair_path)
+            .map_err(|err| CliError::Setup(format!("reading keypair {}: {}", keypair_path, err)))?;
+        let commitment = CommitmentConfig::from_str(&config.commitment)
+            .unwrap_or_else(|_| CommitmentConfig::confirmed());
+        Ok(Context {
+            rpc: RpcClient::new_with_commitment(url, commitment),
+            signer,
+        })
+    }
+
+    fn me(&self) -> Pubkey {
+        self.signer.pubkey()
+    }
+
+    fn send(&self, instructions: &[Instruction]) -> Result<Signature, CliError> {
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.me()),
+            &[&self.signer],
+            blockhash,
+        );
+        Ok(self.rpc.send_and_confirm_transaction(&tx)?)
+    }
+
+    fn config(&self) -> Result<GlobalConfig, CliError> {
+        rpc::fetch_global_config(&self.rpc).map_err(|err| match err {
+            ClientError::NotRegistered(address) => CliError::Refused(format!(
+                "no limiter config at {}; run `init` first",
+                address
+            )),
+            err => err.into(),
+        })
+    }
+
+    fn bucket(&self, wallet: &Pubkey) -> Result<ClientBucket, CliError> {
+        rpc::fetch_client_bucket(&self.rpc, wallet).map_err(|err| match err {
+            ClientError::NotRegistered(_) if *wallet == self.me() => CliError::Refused(format!(
+                "{} has not registered a bucket; run `rate-limiter register` first",
+                wallet
+            )),
+            ClientError::NotRegistered(_) => {
+                CliError::Refused(format!("{} has not registered a bucket", wallet))
+            }
+            err => err.into(),
+        })
+    }
+}
+
+// The Solana CLI's monikers, so `-u devnet` means what it does there
+fn normalize_url(url: &str) -> String {
+    match url {
+        "m" | "mainnet-beta" => "https://api.mainnet-beta.solana.com",
+        "t" | "testnet" => "https://api.testnet.solana.com",
+        "d" | "devnet" => "https://api.devnet.solana.com",
+        "l" | "localhost" => "http://localhost:8899",
+        url => url,
+    }
+    .to_string()
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+fn sent(text: String, signature: Signature, mut json: Value) -> Report {
+    json["signature"] = json!(signature.to_string());
+    Report {
+        text: format!("{}\nSignature: {}", text, signature),
+        json,
+    }
+}
+
+fn print(report: &Report, json: bool) {
+    if json {
+        println!("{}", report.json);
+    } else {
+        println!("{}", report.text);
+    }
+}
+
+fn print_error(err: &CliError, json: bool) {
+    if json {
+        println!("{}", json!({ "error": err.to_string() }));
+    } else {
+        eprintln!("error: {}", err);
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let json = cli.json;
+    let result = Context::load(&cli).and_then(|ctx| match cli.command {
+        Command::Status {
+            watch: true,
+            interval,
+        } => watch(&ctx, json, Duration::from_secs(interval.max(1))),
+        command => run(&ctx, command).map(|report| print(&report, json)),
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            print_error(&err, json);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(ctx: &Context, command: Command) -> Result<Report, CliError> {
+    match command {
+        Command::Init {
+            max_requests,
+            window_seconds,
+            burst_limit,
+            snapshot_interval_seconds,
+        } => init(
+            ctx,
+            RateLimiterConfig {
+                max_requests,
+                window_seconds,
+                burst_limit: burst_limit.unwrap_or(max_requests),
+            },
+            snapshot_interval_seconds,
+        ),
+        Command::UpdateConfig {
+            max_requests,
+            window_seconds,
+            burst_limit,
+            reset_windows,
+        } => {
+            let current = ctx.config()?;
+            let limits = RateLimiterConfig {
+                max_requests: max_requests.unwrap_or(current.max_requests),
+                window_seconds: window_seconds.unwrap_or(current.window_seconds),
+                burst_limit: burst_limit.unwrap_or(current.burst_limit),
+            };
+            update_config(ctx, limits, reset_windows)
+        }
+        Command::Pause { reason, duration } => pause(ctx, reason, duration),
+        Command::Unpause => unpause(ctx),
+        Command::Block { wallet } => block(ctx, &wallet),
+        Command::Unblock { wallet } => unblock(ctx, &wallet),
+        Command::Reset { wallet } => reset(ctx, &wallet),
+        Command::Register => register(ctx),
+        Command::Consume { amount } => consume(ctx, amount),
+        Command::Close => close(ctx),
+        Command::Status { .. } => show_bucket(ctx, &ctx.me()),
+        Command::ShowConfig => show_config(ctx),
+        Command::ShowBucket { wallet } => show_bucket(ctx, &wallet),
+    }
+}
+
+// Stats go in the same transaction: registrations, blocks and resets all write
+// to them, so a limiter without them is not usable yet
+fn init(
+    ctx: &Context,
+    limits: RateLimiterConfig,
+    snapshot_interval_seconds: i64,
+) -> Result<Report, CliError> {
+    let admin = ctx.me();
+    let text = format!(
+        "Initialized: {} requests per {}s, burst limit {}. Admin: {}",
+        limits.max_requests, limits.window_seconds, limits.burst_limit, admin
+    );
+    let json = json!({
+        "admin": admin.to_string(),
+        "max_requests": limits.max_requests,
+        "window_seconds": limits.window_seconds,
+        "burst_limit": limits.burst_limit,
+    });
+    let signature = ctx.send(&[
+        sdk::build_initialize_ix(&admin, limits),
+        sdk::build_initialize_stats_ix(&admin, &admin, snapshot_interval_seconds),
+    ])?;
+    Ok(sent(text, signature, json))
+}
+
+fn update_config(
+    ctx: &Context,
+    limits: RateLimiterConfig,
+    reset_windows: bool,
+) -> Result<Report, CliError> {
+    let text = format!(
+        "Config updated: {} requests per {}s, burst limit {}{}",
+        limits.max_requests,
+        limits.window_seconds,
+        limits.burst_limit,
+        if reset_windows {
+            ". Every window restarts on its next consume"
+        } else {
+            ""
+        }
+    );
+    let json = json!({
+        "max_requests": limits.max_requests,
+        "window_seconds": limits.window_seconds,
+        "burst_limit": limits.burst_limit,
+        "reset_windows": reset_windows,
+    });
+    let signature = ctx.send(&[sdk::build_update_config_ix(
+        &ctx.me(),
+        limits,
+        reset_windows,
+    )])?;
+    Ok(sent(text, signature, json))
+}
+
+fn pause(ctx: &Context, reason: PauseReason, duration: Option<i64>) -> Result<Report, CliError> {
+    let auto_unpause_at = duration.map_or(0, |seconds| unix_now() + seconds);
+    let signature = ctx.send(&[sdk::build_pause_ix(
+        &ctx.me(),
+        reason.code(),
+        auto_unpause_at,
+    )])?;
+    let until = match auto_unpause_at {
+        0 => "until unpaused".to_string(),
+        at => format!("until {} (unix time)", at),
+    };
+    Ok(sent(
+        format!("Paused ({}), {}", pause_reason_name(reason.code()), until),
+        signature,
+        json!({
+            "paused": true,
+            "reason": pause_reason_name(reason.code()),
+            "auto_unpause_at": auto_unpause_at,
+        }),
+    ))
+}
+
+// toggle_pause is the only way back, and it would pause a limiter that is not;
+// the check here is against the state just fetched
+fn unpause(ctx: &Context) -> Result<Report, CliError> {
+    let config = ctx.config()?;
+    let now = unix_now();
+    if !config.emergency_pause_active(now) {
+        return Err(CliError::Refused(if config.pause_active(now) {
+            format!(
+                "only a scheduled pause is on, until {} (unix time); clear_schedule ends it early",
+                config.scheduled_pause_end
+            )
+        } else {
+            "the limiter is not paused".to_string()
+        }));
+    }
+    let signature = ctx.send(&[sdk::build_toggle_pause_ix(&ctx.me())])?;
+    Ok(sent(
+        "Unpaused".into(),
+        signature,
+        json!({ "paused": false }),
+    ))
+}
+
+fn block(ctx: &Context, wallet: &Pubkey) -> Result<Report, CliError> {
+    let config = ctx.config()?;
+    if ctx.bucket(wallet)?.is_blocked() {
+        return Err(CliError::Refused(format!("{} is already blocked", wallet)));
+    }
+    let signature = ctx.send(&[sdk::build_block_client_ix(&config, &ctx.me(), wallet)])?;
+    Ok(sent(
+        format!("Blocked {}", wallet),
+        signature,
+        json!({ "wallet": wallet.to_string(), "blocked": true }),
+    ))
+}
+
+// There is no instruction that only lifts a block: reset_client lifts it with
+// the window, the same as a council's Unblock action
+fn unblock(ctx: &Context, wallet: &Pubkey) -> Result<Report, CliError> {
+    if !ctx.bucket(wallet)?.is_blocked() {
+        return Err(CliError::Refused(format!("{} is not blocked", wallet)));
+    }
+    let signature = ctx.send(&[sdk::build_reset_client_ix(&ctx.me(), wallet)])?;
+    Ok(sent(
+        format!("Unblocked {}; its window starts over", wallet),
+        signature,
+        json!({ "wallet": wallet.to_string(), "blocked": false }),
+    ))
+}
+
+fn reset(ctx: &Context, wallet: &Pubkey) -> Result<Report, CliError> {
+    let signature = ctx.send(&[sdk::build_reset_client_ix(&ctx.me(), wallet)])?;
+    Ok(sent(
+        format!("Reset {}", wallet),
+        signature,
+        json!({ "wallet": wallet.to_string() }),
+    ))
+}
+
+fn register(ctx: &Context) -> Result<Report, CliError> {
+    let config = ctx.config()?;
+    let me = ctx.me();
+    let signature = ctx.send(&[sdk::build_register_client_ix(&config, &me, &me)])?;
+    Ok(sent(
+        format!("Registered {}", me),
+        signature,
+        json!({
+            "wallet": me.to_string(),
+            "bucket": pda::client_bucket_address(&me).0.to_string(),
+        }),
+    ))
+}
+
+// One unit is a plain consume_request; more go through consume_amount, which
+// the program caps at the burst limit
+fn consume(ctx: &Context, amount: u64) -> Result<Report, CliError> {
+    let config = ctx.config()?;
+    if amount > config.burst_limit {
+        return Err(CliError::Refused(format!(
+            "--amount can be at most the burst limit, {}",
+            config.burst_limit
+        )));
+    }
+    let me = ctx.me();
+    // Fetched first so a missing bucket is reported as such, not as the account
+    // error the program would fail with
+    ctx.bucket(&me)?;
+    let ix = match amount {
+        1 => sdk::build_consume_ix(&config, &me),
+        amount => sdk::build_consume_amount_ix(&config, &me, amount),
+    };
+    let signature = ctx.send(&[ix])?;
+    let bucket = ctx.bucket(&me)?;
+    Ok(sent(
+        format!(
+            "Consumed {}. Used this window: {}",
+            amount, bucket.request_count
+        ),
+        signature,
+        json!({
+            "wallet": me.to_string(),
+            "amount": amount,
+            "request_count": bucket.request_count,
+        }),
+    ))
+}
+
+// The program keeps a blocked bucket, since closing it would drop the block
+fn close(ctx: &Context) -> Result<Report, CliError> {
+    let me = ctx.me();
+    let bucket = ctx.bucket(&me)?;
+    if bucket.is_blocked() {
+        return Err(CliError::Refused(
+            "a blocked bucket cannot be closed; it can once the block is lifted".into(),
+        ));
+    }
+    let signature = ctx.send(&[sdk::build_close_bucket_ix(&bucket)])?;
+    let refunded_to = if bucket.is_sponsored() {
+        "the sponsor pool"
+    } else {
+        "the wallet"
+    };
+    Ok(sent(
+        format!(
+            "Closed the bucket of {}; its rent went to {}",
+            me, refunded_to
+        ),
+        signature,
+        json!({ "wallet": me.to_string(), "sponsored": bucket.is_sponsored() }),
+    ))
+}
+
+// Redraws the status in place, or under --json prints one object per line. A
+// failed fetch is shown and polling carries on, so a flaky node does not end it;
+// anything else, such as the bucket being closed, does.
+fn watch(ctx: &Context, json: bool, interval: Duration) -> Result<(), CliError> {
+    loop {
+        let status = show_bucket(ctx, &ctx.me());
+        if !json {
+            print!("\x1b[2J\x1b[H");
+        }
+        match status {
+            Ok(report) => print(&report, json),
+            Err(err @ CliError::Client(ClientError::Rpc(_))) => print_error(&err, json),
+            Err(err) => return Err(err),
+        }
+        if !json {
+            println!("\nEvery {}s; Ctrl-C to stop", interval.as_secs());
+        }
+        sleep(interval);
+    }
+}
+
+fn show_config(ctx: &Context) -> Result<Report, CliError> {
+    let config = ctx.config()?;
+    let now = unix_now();
+    let paused = config.pause_active(now);
+    let json = json!({
+        "address": pda::global_config_address().0.to_string(),
+        "admin": config.admin.to_string(),
+        "max_requests": config.max_requests,
+        "window_seconds": config.window_seconds,
+        "burst_limit": config.burst_limit,
+        "paused": paused,
+        "pause_reason": pause_reason_name(config.pause_reason),
+        "auto_unpause_at": config.auto_unpause_at,
+        "scheduled_pause_start": config.scheduled_pause_start,
+        "scheduled_pause_end": config.scheduled_pause_end,
+        "config_epoch": config.config_epoch,
+        "council_size": config.council_size,
+        "council_threshold": config.council_threshold,
+        "hook_program": config.hook_program.to_string(),
+        "registration_fee_lamports": config.registration_fee_lamports,
+    });
+    let mut text = format!(
+        "Admin:        {}\nLimits:       {} requests per {}s, burst limit {}\nPaused:       {}",
+        config.admin,
+        config.max_requests,
+        config.window_seconds,
+        config.burst_limit,
+        if config.emergency_pause_active(now) {
+            format!("yes ({})", pause_reason_name(config.pause_reason))
+        } else if paused {
+            format!("scheduled, until {}", config.scheduled_pause_end)
+        } else {
+            "no".to_string()
+        },
+    );
+    if config.has_council() {
+        text.push_str(&format!(
+            "\nCouncil:      {} members, {} to approve",
+            config.council_size, config.council_threshold
+        ));
+    }
+    if config.hook_program != Pubkey::default() {
+        text.push_str(&format!("\nHook:         {}", config.hook_program));
+    }
+    if config.registration_fee_lamports > 0 {
+        text.push_str(&format!(
+            "\nFee:          {} lamports per registration",
+            config.registration_fee_lamports
+        ));
+    }
+    Ok(Report { text, json })
+}
+
+// What is left is worked out the way the program would, at the local clock
+fn show_bucket(ctx: &Context, wallet: &Pubkey) -> Result<Report, CliError> {
+    let config = ctx.config()?;
+    let bucket = ctx.bucket(wallet)?;
+    let schedule = if config.scheduled() {
+        Some(rpc::fetch_schedule(&ctx.rpc)?)
+    } else {
+        None
+    };
+    let entries = schedule.as_ref().map_or(&[][..], |s| s.entries());
+    let now = unix_now();
+    let limits = logic::wallet_config(&config, entries, now);
+    let quota = logic::simulate_scheduled_consume(&config, entries, &bucket, now);
+    let (violations, _) = logic::decayed_violations(&bucket, &config, now);
+    let json = json!({
+        "address": pda::client_bucket_address(wallet).0.to_string(),
+        "owner": bucket.owner.to_string(),
+        "label": bucket.label_text(),
+        "request_count": bucket.request_count,
+        "max_requests": limits.max_requests,
+        "remaining": quota.remaining,
+        "resets_in": quota.resets_in,
+        "window_ends_at": now + quota.resets_in,
+        "burst_credits": bucket.burst_credits,
+        "total_requests": bucket.total_requests,
+        "violations": violations,
+        "blocked": bucket.is_blocked(),
+        "frozen": bucket.is_frozen(),
+    });
+    let mut text = format!(
+        "Wallet:       {}\nUsed:         {}/{} this window, {} left\nWindow ends:  in {}s, at {} (unix time)\nLifetime:     {} requests\nViolations:   {}",
+        wallet,
+        bucket.request_count,
+        limits.max_requests,
+        quota.remaining,
+        quota.resets_in,
+        now + quota.resets_in,
+        bucket.total_requests,
+        violations,
+    );
+    if !bucket.label_text().is_empty() {
+        text.push_str(&format!("\nLabel:        {}", bucket.label_text()));
+    }
+    if bucket.is_blocked() {
+        text.push_str(
+            "\nBlocked:      yes; file_appeal, if the limiter takes appeals, or ask its admin",
+        );
+    }
+    if bucket.is_frozen() {
+        text.push_str("\nFrozen:       yes");
+    }
+    Ok(Report { text, json })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monikers_expand_like_the_solana_cli() {
+        assert_eq!(normalize_url("devnet"), "https://api.devnet.solana.com");
+        assert_eq!(normalize_url("l"), "http://localhost:8899");
+        assert_eq!(
+            normalize_url("http://10.0.0.2:8899"),
+            "http://10.0.0.2:8899"
+        );
+    }
+
+    #[test]
+    fn wallets_parse_as_positional_keys() {
+        let wallet = Pubkey::new_unique();
+        let cli = Cli::try_parse_from(["rate-limiter", "block", &wallet.to_string()]).unwrap();
+        assert!(matches!(cli.command, Command::Block { wallet: w } if w == wallet));
+        assert!(Cli::try_parse_from(["rate-limiter", "block", "not-a-key"]).is_err());
+        let cli = Cli::try_parse_from([
+            "rate-limiter",
+            "pause",
+            "--reason",
+            "incident",
+            "--for",
+            "600",
+            "--json",
+        ])
+        .unwrap();
+        assert!(cli.json);
+        assert!(matches!(
+            cli.command,
+            Command::Pause {
+                reason: PauseReason::Incident,
+                duration: Some(600)
+            }
+        ));
+    }
+
+    #[test]
+    fn end_user_flags() {
+        let cli = Cli::try_parse_from(["rate-limiter", "consume", "--amount", "4"]).unwrap();
+        assert!(matches!(cli.command, Command::Consume { amount: 4 }));
+        let cli = Cli::try_parse_from(["rate-limiter", "consume"]).unwrap();
+        assert!(matches!(cli.command, Command::Consume { amount: 1 }));
+        assert!(Cli::try_parse_from(["rate-limiter", "consume", "--amount", "0"]).is_err());
+
+        let cli = Cli::try_parse_from(["rate-limiter", "status", "--watch"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Status {
+                watch: true,
+                interval: 5
+            }
+        ));
+        // An interval means nothing without --watch
+        assert!(Cli::try_parse_from(["rate-limiter", "status", "--interval", "2"]).is_err());
+    }
+}
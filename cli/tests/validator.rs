@@ -0,0 +1,416 @@
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use futures_util::{Stream, StreamExt};
+use serde_json::{json, Value};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_rate_limiter::client::build_consume_ix;
+use solana_rate_limiter::client::program::{Cluster, RateLimiterClientError, RateLimiterProgram};
+use solana_rate_limiter::client::rpc::fetch_global_config;
+use solana_rate_limiter::client::subscribe::{bucket_events, bucket_updates, BucketEvent};
+use solana_rate_limiter::errors::RateLimiterError;
+use solana_rate_limiter::{pda, RateLimiterConfig};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{write_keypair_file, Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+
+// Runs the built binary, the SDK's subscriptions and its program handle against
+// a local solana-test-validator with the program
+// deployed and the test keypair as its upgrade authority. Needs
+// `solana-test-validator` on PATH and `anchor build` output in target/deploy, so
+// it is left out of a plain `cargo test`; run it with
+// `cargo test -p rate-limiter-cli -- --ignored`.
+
+struct Validator {
+    process: Child,
+    url: String,
+    ws_url: String,
+    _ledger: tempfile::TempDir,
+}
+
+impl Drop for Validator {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn program_so() -> PathBuf {
+    let path =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../target/deploy/solana_rate_limiter.so");
+    assert!(
+        path.exists(),
+        "run `anchor build` first: {} is missing",
+        path.display()
+    );
+    path
+}
+
+fn start_validator(authority: &Path, admin: &Keypair) -> Validator {
+    let ledger = tempfile::tempdir().unwrap();
+    let rpc_port = free_port();
+    let process = Command::new("solana-test-validator")
+        .arg("--reset")
+        .arg("--quiet")
+        .arg("--ledger")
+        .arg(ledger.path())
+        .args(["--rpc-port", &rpc_port.to_string()])
+        .args(["--faucet-port", &free_port().to_string()])
+        // Funds the admin from genesis, so nothing waits on an airdrop
+        .args(["--mint", &admin.pubkey().to_string()])
+        .arg("--upgradeable-program")
+        .arg(solana_rate_limiter::ID.to_string())
+        .arg(program_so())
+        .arg(authority)
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("solana-test-validator on PATH");
+    let validator = Validator {
+        process,
+        url: format!("http://127.0.0.1:{}", rpc_port),
+        // The validator serves pubsub one port up
+        ws_url: format!("ws://127.0.0.1:{}", rpc_port + 1),
+        _ledger: ledger,
+    };
+
+    let rpc = RpcClient::new(validator.url.clone());
+    let started = Instant::now();
+    while rpc.get_health().is_err() {
+        assert!(
+            started.elapsed() < Duration::from_secs(60),
+            "validator never came up"
+        );
+        sleep(Duration::from_millis(250));
+    }
+    validator
+}
+
+struct Cli<'a> {
+    url: &'a str,
+    keypair: &'a Path,
+}
+
+impl Cli<'_> {
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_rate-limiter"))
+            .args(["--url", self.url])
+            .arg("--keypair")
+            .arg(self.keypair)
+            .arg("--json")
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    fn ok(&self, args: &[&str]) -> Value {
+        let output = self.run(args);
+        let json = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(output.status.success(), "{:?} failed: {}", args, json);
+        json
+    }
+
+    fn failure(&self, args: &[&str]) -> Value {
+        let output = self.run(args);
+        assert!(!output.status.success(), "{:?} succeeded", args);
+        serde_json::from_slice(&output.stdout).unwrap()
+    }
+
+    fn fails(&self, args: &[&str]) -> String {
+        self.failure(args)["error"].as_str().unwrap().to_string()
+    }
+}
+
+#[test]
+#[ignore = "needs solana-test-validator and anchor build output"]
+fn init_consume_and_block_against_a_validator() {
+    let dir = tempfile::tempdir().unwrap();
+    let admin = Keypair::new();
+    let keypair = dir.path().join("admin.json");
+    write_keypair_file(&admin, &keypair).unwrap();
+    let validator = start_validator(&keypair, &admin);
+    let cli = Cli {
+        url: &validator.url,
+        keypair: &keypair,
+    };
+    let me = admin.pubkey().to_string();
+
+    cli.ok(&["init", "--max-requests", "2", "--window-seconds", "600"]);
+    let config = cli.ok(&["show-config"]);
+    assert_eq!(config["admin"], me);
+    assert_eq!(config["max_requests"], 2);
+    assert_eq!(config["burst_limit"], 2);
+    assert!(cli
+        .fails(&["init", "--max-requests", "2", "--window-seconds", "600"])
+        .contains("already exists"));
+
+    cli.ok(&["register"]);
+    assert_eq!(cli.ok(&["consume"])["request_count"], 1);
+    cli.ok(&["consume"]);
+    assert!(cli.fails(&["consume"]).contains("this window's allowance"));
+    assert_eq!(cli.ok(&["show-bucket", &me])["remaining"], 0);
+
+    cli.ok(&["block", &me]);
+    assert_eq!(cli.ok(&["show-bucket", &me])["blocked"], true);
+    assert!(cli.fails(&["block", &me]).contains("already blocked"));
+    assert!(cli.fails(&["consume"]).contains("`unblock`"));
+
+    cli.ok(&["unblock", &me]);
+    assert!(cli.fails(&["unblock", &me]).contains("not blocked"));
+    assert_eq!(cli.ok(&["consume"])["request_count"], 1);
+
+    cli.ok(&["pause", "--reason", "maintenance"]);
+    assert!(cli.fails(&["consume"]).contains("`unpause`"));
+    cli.ok(&["unpause"]);
+    assert!(cli.fails(&["unpause"]).contains("not paused"));
+
+    // Someone else's keypair is told it is not the admin
+    let other = Keypair::new();
+    let other_path = dir.path().join("other.json");
+    write_keypair_file(&other, &other_path).unwrap();
+    let rpc = RpcClient::new(validator.url.clone());
+    rpc.request_airdrop(&other.pubkey(), 1_000_000_000).unwrap();
+    while rpc.get_balance(&other.pubkey()).unwrap() == 0 {
+        sleep(Duration::from_millis(250));
+    }
+    let outsider = Cli {
+        url: &validator.url,
+        keypair: &other_path,
+    };
+    assert!(outsider
+        .fails(&["reset", &me])
+        .contains("not this limiter's admin"));
+}
+
+// The --json output with what changes from run to run swapped for placeholders:
+// this run's wallet and bucket address, signatures and clock-derived fields
+fn normalized(output: Value, wallet: &Pubkey) -> Value {
+    let text = output
+        .to_string()
+        .replace(
+            &pda::client_bucket_address(wallet).0.to_string(),
+            "<bucket>",
+        )
+        .replace(&wallet.to_string(), "<wallet>");
+    let mut value: Value = serde_json::from_str(&text).unwrap();
+    for (key, placeholder) in [
+        ("signature", "<signature>"),
+        ("resets_in", "<time>"),
+        ("window_ends_at", "<time>"),
+    ] {
+        if value.get(key).is_some() {
+            value[key] = json!(placeholder);
+        }
+    }
+    value
+}
+
+fn assert_golden(name: &str, output: Value, wallet: &Pubkey) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.json", name));
+    let golden: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(normalized(output, wallet), golden, "{}", path.display());
+}
+
+#[test]
+#[ignore = "needs solana-test-validator and anchor build output"]
+fn end_user_commands_match_golden_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let admin = Keypair::new();
+    let keypair = dir.path().join("admin.json");
+    write_keypair_file(&admin, &keypair).unwrap();
+    let validator = start_validator(&keypair, &admin);
+    let cli = Cli {
+        url: &validator.url,
+        keypair: &keypair,
+    };
+    let me = admin.pubkey();
+    let wallet = me.to_string();
+
+    cli.ok(&[
+        "init",
+        "--max-requests",
+        "5",
+        "--window-seconds",
+        "600",
+        "--burst-limit",
+        "10",
+    ]);
+    assert_golden("status_unregistered", cli.failure(&["status"]), &me);
+    assert_golden("register", cli.ok(&["register"]), &me);
+    assert_golden("consume", cli.ok(&["consume", "--amount", "3"]), &me);
+    assert_golden("status", cli.ok(&["status"]), &me);
+    assert_golden(
+        "consume_over_burst",
+        cli.failure(&["consume", "--amount", "11"]),
+        &me,
+    );
+
+    cli.ok(&["block", &wallet]);
+    assert_golden("status_blocked", cli.ok(&["status"]), &me);
+    assert_golden("consume_blocked", cli.failure(&["consume"]), &me);
+    assert_golden("close_blocked", cli.failure(&["close"]), &me);
+
+    cli.ok(&["unblock", &wallet]);
+    assert_golden("close", cli.ok(&["close"]), &me);
+    assert_golden("consume_unregistered", cli.failure(&["consume"]), &me);
+}
+
+async fn next<T>(stream: &mut (impl Stream<Item = T> + Unpin)) -> T {
+    tokio::time::timeout(Duration::from_secs(30), stream.next())
+        .await
+        .expect("no update within 30s")
+        .expect("subscription ended")
+}
+
+#[test]
+#[ignore = "needs solana-test-validator and anchor build output"]
+fn bucket_subscriptions_follow_consumes_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let admin = Keypair::new();
+    let keypair = dir.path().join("admin.json");
+    write_keypair_file(&admin, &keypair).unwrap();
+    let validator = start_validator(&keypair, &admin);
+    let cli = Cli {
+        url: &validator.url,
+        keypair: &keypair,
+    };
+    let me = admin.pubkey();
+    cli.ok(&["init", "--max-requests", "5", "--window-seconds", "600"]);
+    cli.ok(&["register"]);
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let mut updates = bucket_updates(&validator.url, &validator.ws_url, me);
+        let mut events = bucket_events(&validator.url, &validator.ws_url, me);
+        // Both start from the state read when they subscribed
+        assert_eq!(next(&mut updates).await.bucket.request_count, 0);
+        assert!(matches!(next(&mut events).await, BucketEvent::Updated(_)));
+
+        let mut slot = 0;
+        for used in 1..=3 {
+            cli.ok(&["consume"]);
+            let update = next(&mut updates).await;
+            assert_eq!(update.bucket.request_count, used);
+            assert_eq!(update.quota.remaining, 5 - used);
+            assert!(update.slot >= slot);
+            slot = update.slot;
+            match next(&mut events).await {
+                BucketEvent::Updated(update) => assert_eq!(update.bucket.request_count, used),
+                other => panic!("expected an update, got {:?}", other),
+            }
+        }
+
+        // A paused consume leaves the account alone; only the logs show it. Sent
+        // past preflight, which would otherwise keep it off the chain.
+        cli.ok(&["pause"]);
+        let rpc =
+            RpcClient::new_with_commitment(validator.url.clone(), CommitmentConfig::confirmed());
+        let config = fetch_global_config(&rpc).unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[build_consume_ix(&config, &me)],
+            Some(&me),
+            &[&admin],
+            rpc.get_latest_blockhash().unwrap(),
+        );
+        rpc.send_transaction_with_config(
+            &tx,
+            RpcSendTransactionConfig {
+                skip_preflight: true,
+                ..RpcSendTransactionConfig::default()
+            },
+        )
+        .unwrap();
+        match next(&mut events).await {
+            BucketEvent::Rejected { code, event, .. } => {
+                assert_eq!(code, u32::from(RateLimiterError::ProgramPaused));
+                assert_eq!(event, None);
+            }
+            other => panic!("expected a rejection, got {:?}", other),
+        }
+    });
+}
+
+#[test]
+#[ignore = "needs solana-test-validator and anchor build output"]
+fn typed_program_handle_against_a_validator() {
+    let dir = tempfile::tempdir().unwrap();
+    let admin = Keypair::new();
+    let keypair = dir.path().join("admin.json");
+    write_keypair_file(&admin, &keypair).unwrap();
+    let validator = start_validator(&keypair, &admin);
+    let cli = Cli {
+        url: &validator.url,
+        keypair: &keypair,
+    };
+    let client = Keypair::new();
+    let wallet = client.pubkey();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let cluster = Cluster::Custom(validator.url.clone(), validator.ws_url.clone());
+        let limiter = RateLimiterProgram::new(cluster, Arc::new(admin)).unwrap();
+        let limits = RateLimiterConfig {
+            max_requests: 2,
+            window_seconds: 600,
+            burst_limit: 2,
+        };
+        limiter.initialize(limits.clone()).await.unwrap();
+        assert_eq!(limiter.config().await.unwrap().max_requests, 2);
+        // The config account is already there, which the system program refuses
+        assert!(matches!(
+            limiter.initialize(limits).await,
+            Err(RateLimiterClientError::Program(0))
+        ));
+
+        assert!(matches!(
+            limiter.quota(&wallet).await,
+            Err(RateLimiterClientError::NotRegistered(owner)) if owner == wallet
+        ));
+        assert!(matches!(
+            limiter.consume(&client).await,
+            Err(RateLimiterClientError::NotRegistered(owner)) if owner == wallet
+        ));
+        limiter.register(&client).await.unwrap();
+        assert_eq!(limiter.quota(&wallet).await.unwrap().remaining, 2);
+
+        limiter.consume(&client).await.unwrap();
+        assert_eq!(limiter.quota(&wallet).await.unwrap().remaining, 1);
+        limiter.consume_amount(&client, 1).await.unwrap();
+        assert!(matches!(
+            limiter.consume(&client).await,
+            Err(RateLimiterClientError::RateLimitExceeded)
+        ));
+
+        limiter.block(&wallet).await.unwrap();
+        assert!(limiter.bucket(&wallet).await.unwrap().is_blocked());
+        assert!(matches!(
+            limiter.consume(&client).await,
+            Err(RateLimiterClientError::ClientBlocked)
+        ));
+        limiter.reset(&wallet).await.unwrap();
+        assert_eq!(limiter.bucket(&wallet).await.unwrap().request_count, 0);
+        limiter.consume(&client).await.unwrap();
+
+        cli.ok(&["pause"]);
+        assert!(matches!(
+            limiter.consume(&client).await,
+            Err(RateLimiterClientError::ProgramPaused)
+        ));
+    });
+}
@@ -0,0 +1,97 @@
+use anchor_lang::prelude::*;
+use solana_rate_limiter::constants::HOOK_AUTHORITY_SEED;
+
+declare_id!("BxfApsv3FRjqSgnGAT4HJWKxwmaALPjFMffrwxNBBGQ9");
+
+// Reference hook for the limiter's block/rejection notifications (see hook.rs in
+// the limiter). It only records what it was told; a real hook would freeze an
+// escrow or similar, with those accounts passed after the log. `set_refuse` makes
+// the hook fail so tests can show that a failing hook fails the limiter's call.
+
+pub const HOOK_LOG_SEED: &[u8] = b"hook-log";
+
+#[program]
+pub mod block_hook {
+    use super::*;
+
+    pub fn initialize_log(ctx: Context<InitializeLog>) -> Result<()> {
+        let log = &mut ctx.accounts.hook_log;
+        log.events = 0;
+        log.last_client = Pubkey::default();
+        log.last_reason = 0;
+        log.refuse = false;
+        log.bump = ctx.bumps.hook_log;
+        Ok(())
+    }
+
+    // Test knob; a real hook would not let anyone fail its notifications
+    pub fn set_refuse(ctx: Context<SetRefuse>, refuse: bool) -> Result<()> {
+        ctx.accounts.hook_log.refuse = refuse;
+        Ok(())
+    }
+
+    // Called by the limiter, signed with its hook authority PDA
+    pub fn on_rate_limit_event(
+        ctx: Context<OnRateLimitEvent>,
+        client: Pubkey,
+        reason_code: u8,
+    ) -> Result<()> {
+        let log = &mut ctx.accounts.hook_log;
+        require!(!log.refuse, HookError::Refused);
+        log.events = log.events.saturating_add(1);
+        log.last_client = client;
+        log.last_reason = reason_code;
+        msg!("Hook: client {} reason {}", client, reason_code);
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct HookLog {
+    pub events: u64,            // notifications received
+    pub last_client: Pubkey,
+    pub last_reason: u8,        // limiter reason code of the last notification
+    pub refuse: bool,           // fail every notification
+    pub bump: u8,
+}
+
+#[error_code]
+pub enum HookError {
+    #[msg("Hook refused the notification.")]
+    Refused,
+}
+
+#[derive(Accounts)]
+pub struct InitializeLog<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + HookLog::INIT_SPACE,
+        seeds = [HOOK_LOG_SEED],
+        bump
+    )]
+    pub hook_log: Account<'info, HookLog>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetRefuse<'info> {
+    #[account(mut, seeds = [HOOK_LOG_SEED], bump = hook_log.bump)]
+    pub hook_log: Account<'info, HookLog>,
+}
+
+// Account order is fixed by the limiter: its authority, the bucket, then extras
+#[derive(Accounts)]
+pub struct OnRateLimitEvent<'info> {
+    // Only the limiter can sign for this, so direct calls are refused
+    #[account(seeds = [HOOK_AUTHORITY_SEED], bump, seeds::program = solana_rate_limiter::ID)]
+    pub hook_authority: Signer<'info>,
+    /// CHECK: read-only; the owner check is all this hook needs
+    #[account(owner = solana_rate_limiter::ID)]
+    pub bucket: UncheckedAccount<'info>,
+    #[account(mut, seeds = [HOOK_LOG_SEED], bump = hook_log.bump)]
+    pub hook_log: Account<'info, HookLog>,
+}
@@ -0,0 +1,178 @@
+use anchor_lang::prelude::*;
+use solana_rate_limiter::constants::CALLER_AUTHORITY_SEED;
+use solana_rate_limiter::cpi::accounts::{ConsumeAsProgram, ConsumeRequest};
+use solana_rate_limiter::gate::read_gate_result;
+use solana_rate_limiter::program::SolanaRateLimiter;
+use solana_rate_limiter::state::{CallerAllowlist, ClientBucket, GlobalConfig, GlobalStats, ProgramBucket};
+
+declare_id!("FAX2X8J527nvBvfrSHZ9fWoYfbj4SvSwx7ndQzgYVaJw");
+
+// A per-user counter that only moves when the user's rate limiter bucket allows
+// it. The limiter does all the checking. `increment` makes the CPI first and lets
+// any rejection abort the whole transaction; `try_increment` asks gate_check
+// instead and records the denial itself. `increment_pooled` draws on this
+// program's own allowance, shared by every user.
+
+pub const COUNTER_SEED: &[u8] = b"counter";
+
+#[program]
+pub mod gated_counter {
+    use super::*;
+
+    pub fn initialize_counter(ctx: Context<InitializeCounter>) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        counter.authority = ctx.accounts.user.key();
+        counter.count = 0;
+        counter.denied = 0;
+        counter.bump = ctx.bumps.counter;
+        Ok(())
+    }
+
+    pub fn increment(ctx: Context<Increment>) -> Result<()> {
+        // The user's signature on this transaction carries through the CPI
+        solana_rate_limiter::cpi::consume_request(CpiContext::new(
+            ctx.accounts.rate_limiter_program.to_account_info(),
+            ConsumeRequest {
+                global_config: ctx.accounts.global_config.to_account_info(),
+                global_stats: Some(ctx.accounts.global_stats.to_account_info()),
+                client_bucket: ctx.accounts.client_bucket.to_account_info(),
+                client: ctx.accounts.user.to_account_info(),
+                caller_entry: Some(ctx.accounts.caller_entry.to_account_info()),
+                instructions: Some(ctx.accounts.instructions.to_account_info()),
+                schedule: None,
+                owner_denylist: None,
+                pause_grace: None,
+            },
+        ))?;
+
+        let counter = &mut ctx.accounts.counter;
+        counter.count = counter.count.saturating_add(1);
+        msg!("Counter: {}", counter.count);
+        Ok(())
+    }
+
+    // Degrades instead of failing: an over-limit user's transaction still lands,
+    // it just counts a denial instead of an increment
+    pub fn try_increment(ctx: Context<Increment>) -> Result<()> {
+        solana_rate_limiter::cpi::gate_check(CpiContext::new(
+            ctx.accounts.rate_limiter_program.to_account_info(),
+            ConsumeRequest {
+                global_config: ctx.accounts.global_config.to_account_info(),
+                global_stats: Some(ctx.accounts.global_stats.to_account_info()),
+                client_bucket: ctx.accounts.client_bucket.to_account_info(),
+                client: ctx.accounts.user.to_account_info(),
+                caller_entry: Some(ctx.accounts.caller_entry.to_account_info()),
+                instructions: Some(ctx.accounts.instructions.to_account_info()),
+                schedule: None,
+                owner_denylist: None,
+                pause_grace: None,
+            },
+        ))?;
+        let gate = read_gate_result()?;
+
+        let counter = &mut ctx.accounts.counter;
+        if gate.allowed {
+            counter.count = counter.count.saturating_add(1);
+            msg!("Counter: {} ({} left this window)", counter.count, gate.remaining);
+        } else {
+            counter.denied = counter.denied.saturating_add(1);
+            msg!("Denied, retry in {}s", gate.retry_after);
+        }
+        Ok(())
+    }
+
+    // Charged to the program bucket rather than the user's. The limiter knows the
+    // call is ours because we sign with our caller authority PDA.
+    pub fn increment_pooled(ctx: Context<IncrementPooled>) -> Result<()> {
+        solana_rate_limiter::cpi::consume_as_program(CpiContext::new_with_signer(
+            ctx.accounts.rate_limiter_program.to_account_info(),
+            ConsumeAsProgram {
+                global_config: ctx.accounts.global_config.to_account_info(),
+                global_stats: Some(ctx.accounts.global_stats.to_account_info()),
+                program_bucket: ctx.accounts.program_bucket.to_account_info(),
+                caller_authority: ctx.accounts.caller_authority.to_account_info(),
+            },
+            &[&[CALLER_AUTHORITY_SEED, &[ctx.bumps.caller_authority]]],
+        ))?;
+
+        let counter = &mut ctx.accounts.counter;
+        counter.count = counter.count.saturating_add(1);
+        msg!("Counter: {}", counter.count);
+        Ok(())
+    }
+}
+
+#[account]
+pub struct Counter {
+    pub authority: Pubkey,      // user whose bucket gates this counter
+    pub count: u64,
+    pub denied: u64,            // try_increment calls the gate refused
+    pub bump: u8,
+}
+
+impl Counter {
+    pub const LEN: usize = 8    // discriminator
+        + 32                    // authority
+        + 8                     // count
+        + 8                     // denied
+        + 1;                    // bump
+}
+
+#[derive(Accounts)]
+pub struct InitializeCounter<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = Counter::LEN,
+        seeds = [COUNTER_SEED, user.key().as_ref()],
+        bump
+    )]
+    pub counter: Account<'info, Counter>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+// The limiter accounts are checked again by the limiter itself; typing them here
+// just fails faster and keeps the IDL self-describing
+#[derive(Accounts)]
+pub struct Increment<'info> {
+    #[account(
+        mut,
+        seeds = [COUNTER_SEED, user.key().as_ref()],
+        bump = counter.bump,
+    )]
+    pub counter: Account<'info, Counter>,
+    pub user: Signer<'info>,
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(mut)]
+    pub client_bucket: Account<'info, ClientBucket>,
+    // This program's allowlist entry; the limiter refuses the CPI without it
+    pub caller_entry: Account<'info, CallerAllowlist>,
+    /// CHECK: the limiter reads it to confirm we are the caller
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    pub rate_limiter_program: Program<'info, SolanaRateLimiter>,
+}
+
+#[derive(Accounts)]
+pub struct IncrementPooled<'info> {
+    #[account(
+        mut,
+        seeds = [COUNTER_SEED, user.key().as_ref()],
+        bump = counter.bump,
+    )]
+    pub counter: Account<'info, Counter>,
+    pub user: Signer<'info>,
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(mut)]
+    pub program_bucket: Account<'info, ProgramBucket>,
+    /// CHECK: signs the CPI as this program; holds no data
+    #[account(seeds = [CALLER_AUTHORITY_SEED], bump)]
+    pub caller_authority: UncheckedAccount<'info>,
+    pub rate_limiter_program: Program<'info, SolanaRateLimiter>,
+}
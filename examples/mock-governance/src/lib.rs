@@ -0,0 +1,108 @@
+use anchor_lang::prelude::*;
+use solana_rate_limiter::cpi::accounts::{BlockClient, TogglePause, UpdateConfig};
+use solana_rate_limiter::program::SolanaRateLimiter;
+use solana_rate_limiter::state::{ClientBucket, GlobalConfig, GlobalStats};
+use solana_rate_limiter::RateLimiterConfig;
+
+declare_id!("8u1TS6rSEfEEzAdd9Zab2GVLxmmVDb197sURqgsEWbNd");
+
+// Stand-in for a Realms or Squads executor. Its ["governance"] PDA is the limiter
+// admin, and each instruction "signs" an admin instruction for it with
+// invoke_signed. There are no proposals or votes: anyone can execute, which is
+// fine for tests and nothing else. Nothing here pays rent, since the PDA holds no
+// lamports.
+
+pub const GOVERNANCE_SEED: &[u8] = b"governance";
+
+#[program]
+pub mod mock_governance {
+    use super::*;
+
+    pub fn execute_update_config(
+        ctx: Context<ExecuteConfig>,
+        config: RateLimiterConfig,
+        reset_windows: bool,
+    ) -> Result<()> {
+        let signer: &[&[&[u8]]] = &[&[GOVERNANCE_SEED, &[ctx.bumps.governance]]];
+        solana_rate_limiter::cpi::update_config(
+            CpiContext::new_with_signer(
+                ctx.accounts.rate_limiter_program.to_account_info(),
+                UpdateConfig {
+                    global_config: ctx.accounts.global_config.to_account_info(),
+                    admin: ctx.accounts.governance.to_account_info(),
+                },
+                signer,
+            ),
+            config,
+            reset_windows,
+        )
+    }
+
+    pub fn execute_toggle_pause(ctx: Context<ExecuteConfig>) -> Result<()> {
+        let signer: &[&[&[u8]]] = &[&[GOVERNANCE_SEED, &[ctx.bumps.governance]]];
+        solana_rate_limiter::cpi::toggle_pause(CpiContext::new_with_signer(
+            ctx.accounts.rate_limiter_program.to_account_info(),
+            TogglePause {
+                global_config: ctx.accounts.global_config.to_account_info(),
+                admin: ctx.accounts.governance.to_account_info(),
+            },
+            signer,
+        ))
+    }
+
+    pub fn execute_block_client(ctx: Context<ExecuteBlock>) -> Result<()> {
+        let signer: &[&[&[u8]]] = &[&[GOVERNANCE_SEED, &[ctx.bumps.governance]]];
+        solana_rate_limiter::cpi::block_client(CpiContext::new_with_signer(
+            ctx.accounts.rate_limiter_program.to_account_info(),
+            BlockClient {
+                global_config: ctx.accounts.global_config.to_account_info(),
+                global_stats: ctx.accounts.global_stats.to_account_info(),
+                client_bucket: ctx.accounts.client_bucket.to_account_info(),
+                admin: ctx.accounts.governance.to_account_info(),
+                client_wallet: ctx.accounts.client_wallet.to_account_info(),
+            },
+            signer,
+        ))
+    }
+
+    // Hands the admin role on, e.g. back to a keypair once the tests are done
+    pub fn execute_transfer_admin(ctx: Context<ExecuteConfig>, new_admin: Pubkey) -> Result<()> {
+        let signer: &[&[&[u8]]] = &[&[GOVERNANCE_SEED, &[ctx.bumps.governance]]];
+        solana_rate_limiter::cpi::transfer_admin(
+            CpiContext::new_with_signer(
+                ctx.accounts.rate_limiter_program.to_account_info(),
+                UpdateConfig {
+                    global_config: ctx.accounts.global_config.to_account_info(),
+                    admin: ctx.accounts.governance.to_account_info(),
+                },
+                signer,
+            ),
+            new_admin,
+        )
+    }
+}
+
+#[derive(Accounts)]
+pub struct ExecuteConfig<'info> {
+    #[account(mut)]
+    pub global_config: Account<'info, GlobalConfig>,
+    /// CHECK: signs as the limiter admin; holds no data
+    #[account(seeds = [GOVERNANCE_SEED], bump)]
+    pub governance: UncheckedAccount<'info>,
+    pub rate_limiter_program: Program<'info, SolanaRateLimiter>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteBlock<'info> {
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(mut)]
+    pub client_bucket: Account<'info, ClientBucket>,
+    /// CHECK: signs as the limiter admin; holds no data
+    #[account(seeds = [GOVERNANCE_SEED], bump)]
+    pub governance: UncheckedAccount<'info>,
+    /// CHECK: passed through as the bucket's seed
+    pub client_wallet: UncheckedAccount<'info>,
+    pub rate_limiter_program: Program<'info, SolanaRateLimiter>,
+}
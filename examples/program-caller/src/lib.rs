@@ -0,0 +1,189 @@
+use anchor_lang::prelude::*;
+use solana_rate_limiter::constants::CALLER_AUTHORITY_SEED;
+use solana_rate_limiter::cpi::accounts::{ConsumeAsProgram, ConsumeRequest, RegisterClient};
+use solana_rate_limiter::gate::read_consume_report;
+use solana_rate_limiter::logic;
+use solana_rate_limiter::program::SolanaRateLimiter;
+use solana_rate_limiter::state::{ClientBucket, GlobalConfig, GlobalStats, ProgramBucket};
+
+declare_id!("8knL1cyaNRGG9pSVsf9NjnP7Y2GXMS8TdV9unzwu4thu");
+
+// The smallest possible integrator, so the tests have a second caller program
+// next to gated-counter. `ping` spends a unit of this program's own allowance;
+// `relay_consume` forwards a user's consume_request with whatever allowlist entry
+// it is given, including none. `relay_consume_and_report` does the same through
+// consume_and_report and checks the report against the reloaded bucket.
+// `register_vault` and `relay_vault_consume` do the same for a bucket owned by this
+// program's ["vault", user] PDA, which only this program can sign for.
+
+const VAULT_SEED: &[u8] = b"vault";
+
+#[program]
+pub mod program_caller {
+    use super::*;
+
+    pub fn ping(ctx: Context<Ping>) -> Result<()> {
+        solana_rate_limiter::cpi::consume_as_program(CpiContext::new_with_signer(
+            ctx.accounts.rate_limiter_program.to_account_info(),
+            ConsumeAsProgram {
+                global_config: ctx.accounts.global_config.to_account_info(),
+                global_stats: Some(ctx.accounts.global_stats.to_account_info()),
+                program_bucket: ctx.accounts.program_bucket.to_account_info(),
+                caller_authority: ctx.accounts.caller_authority.to_account_info(),
+            },
+            &[&[CALLER_AUTHORITY_SEED, &[ctx.bumps.caller_authority]]],
+        ))
+    }
+
+    pub fn relay_consume(ctx: Context<RelayConsume>) -> Result<()> {
+        solana_rate_limiter::cpi::consume_request(ctx.accounts.consume_context())
+    }
+
+    pub fn relay_consume_and_report(ctx: Context<RelayConsume>) -> Result<()> {
+        solana_rate_limiter::cpi::consume_and_report(ctx.accounts.consume_context())?;
+        let report = read_consume_report()?;
+
+        // What an integrator would otherwise do: reload and work it out
+        ctx.accounts.global_config.reload()?;
+        ctx.accounts.client_bucket.reload()?;
+        let bucket = &ctx.accounts.client_bucket;
+        require_eq!(report.used, bucket.request_count);
+        require_eq!(report.remaining, logic::remaining_capacity(bucket, &ctx.accounts.global_config));
+        msg!("Used {} of {}, {} left until {}", report.used, report.max, report.remaining, report.window_end);
+        Ok(())
+    }
+
+    pub fn register_vault(ctx: Context<RegisterVault>) -> Result<()> {
+        let user = ctx.accounts.user.key();
+        solana_rate_limiter::cpi::register_client(CpiContext::new_with_signer(
+            ctx.accounts.rate_limiter_program.to_account_info(),
+            RegisterClient {
+                global_config: ctx.accounts.global_config.to_account_info(),
+                global_stats: ctx.accounts.global_stats.to_account_info(),
+                client_bucket: ctx.accounts.client_bucket.to_account_info(),
+                fast_bucket: ctx.accounts.fast_bucket.to_account_info(),
+                shard_parent: ctx.accounts.shard_parent.to_account_info(),
+                payer_meta: ctx.accounts.payer_meta.to_account_info(),
+                treasury: None,
+                client: ctx.accounts.vault.to_account_info(),
+                payer: ctx.accounts.user.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+            },
+            &[&[VAULT_SEED, user.as_ref(), &[ctx.bumps.vault]]],
+        ))
+    }
+
+    pub fn relay_vault_consume(ctx: Context<RelayVaultConsume>) -> Result<()> {
+        let user = ctx.accounts.user.key();
+        solana_rate_limiter::cpi::consume_request(CpiContext::new_with_signer(
+            ctx.accounts.rate_limiter_program.to_account_info(),
+            ConsumeRequest {
+                global_config: ctx.accounts.global_config.to_account_info(),
+                global_stats: Some(ctx.accounts.global_stats.to_account_info()),
+                client_bucket: ctx.accounts.client_bucket.to_account_info(),
+                client: ctx.accounts.vault.to_account_info(),
+                caller_entry: ctx.accounts.caller_entry.as_ref().map(|entry| entry.to_account_info()),
+                instructions: Some(ctx.accounts.instructions.to_account_info()),
+                schedule: None,
+                owner_denylist: ctx.accounts.owner_denylist.as_ref().map(|entry| entry.to_account_info()),
+                pause_grace: None,
+            },
+            &[&[VAULT_SEED, user.as_ref(), &[ctx.bumps.vault]]],
+        ))
+    }
+}
+
+#[derive(Accounts)]
+pub struct Ping<'info> {
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(mut)]
+    pub program_bucket: Account<'info, ProgramBucket>,
+    /// CHECK: signs the CPI as this program; holds no data
+    #[account(seeds = [CALLER_AUTHORITY_SEED], bump)]
+    pub caller_authority: UncheckedAccount<'info>,
+    pub rate_limiter_program: Program<'info, SolanaRateLimiter>,
+}
+
+#[derive(Accounts)]
+pub struct RelayConsume<'info> {
+    pub user: Signer<'info>,
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(mut)]
+    pub client_bucket: Account<'info, ClientBucket>,
+    /// CHECK: passed through untouched; the limiter decides whether it counts
+    pub caller_entry: Option<UncheckedAccount<'info>>,
+    /// CHECK: the limiter reads it to identify us
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    pub rate_limiter_program: Program<'info, SolanaRateLimiter>,
+}
+
+impl<'info> RelayConsume<'info> {
+    fn consume_context(&self) -> CpiContext<'_, '_, '_, 'info, ConsumeRequest<'info>> {
+        CpiContext::new(
+            self.rate_limiter_program.to_account_info(),
+            ConsumeRequest {
+                global_config: self.global_config.to_account_info(),
+                global_stats: Some(self.global_stats.to_account_info()),
+                client_bucket: self.client_bucket.to_account_info(),
+                client: self.user.to_account_info(),
+                caller_entry: self.caller_entry.as_ref().map(|entry| entry.to_account_info()),
+                instructions: Some(self.instructions.to_account_info()),
+                schedule: None,
+                owner_denylist: None,
+                pause_grace: None,
+            },
+        )
+    }
+}
+
+#[derive(Accounts)]
+pub struct RegisterVault<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: this program's PDA, signing as the bucket's owner
+    #[account(seeds = [VAULT_SEED, user.key().as_ref()], bump)]
+    pub vault: UncheckedAccount<'info>,
+    /// CHECK: validated by the limiter
+    pub global_config: UncheckedAccount<'info>,
+    /// CHECK: validated by the limiter
+    #[account(mut)]
+    pub global_stats: UncheckedAccount<'info>,
+    /// CHECK: created by the limiter
+    #[account(mut)]
+    pub client_bucket: UncheckedAccount<'info>,
+    /// CHECK: validated by the limiter
+    pub fast_bucket: UncheckedAccount<'info>,
+    /// CHECK: validated by the limiter
+    pub shard_parent: UncheckedAccount<'info>,
+    /// CHECK: created by the limiter if needed
+    #[account(mut)]
+    pub payer_meta: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    pub rate_limiter_program: Program<'info, SolanaRateLimiter>,
+}
+
+#[derive(Accounts)]
+pub struct RelayVaultConsume<'info> {
+    pub user: Signer<'info>,
+    /// CHECK: this program's PDA, signing as the bucket's owner
+    #[account(seeds = [VAULT_SEED, user.key().as_ref()], bump)]
+    pub vault: UncheckedAccount<'info>,
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(mut)]
+    pub client_bucket: Account<'info, ClientBucket>,
+    /// CHECK: passed through untouched; the limiter decides whether it counts
+    pub caller_entry: Option<UncheckedAccount<'info>>,
+    /// CHECK: the limiter reads it to identify us
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    /// CHECK: passed through untouched, like caller_entry
+    pub owner_denylist: Option<UncheckedAccount<'info>>,
+    pub rate_limiter_program: Program<'info, SolanaRateLimiter>,
+}
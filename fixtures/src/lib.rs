@@ -0,0 +1,376 @@
+use std::fmt;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use solana_client::rpc_client::RpcClient;
+use solana_rate_limiter::client as sdk;
+use solana_rate_limiter::state::{ClientBucket, GlobalConfig};
+use solana_rate_limiter::{logic, pda, ConfigPreset, RateLimiterConfig};
+use solana_sdk::account::{from_account, Account};
+use solana_sdk::clock::Clock;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair, Keypair, Signer};
+use solana_sdk::sysvar;
+use solana_sdk::transaction::Transaction;
+
+// Puts a limiter on a local cluster into a state worth testing an app against:
+// the config from a preset, a set of demo clients with buckets part-used in a
+// spread of amounts, and optionally a few of them blocked. Every step checks
+// what is already on chain first, so a second run only adds what is missing.
+
+const SNAPSHOT_INTERVAL_SECONDS: i64 = 3600;
+// getMultipleAccounts takes at most this many addresses
+const ACCOUNTS_PER_REQUEST: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    Strict,
+    Standard,
+    Lenient,
+}
+
+impl Preset {
+    // The program's preset of the same name
+    pub fn config_preset(self) -> ConfigPreset {
+        match self {
+            Preset::Strict => ConfigPreset::Strict,
+            Preset::Standard => ConfigPreset::Standard,
+            Preset::Lenient => ConfigPreset::Lenient,
+        }
+    }
+
+    pub fn limits(self) -> RateLimiterConfig {
+        self.config_preset().limits()
+    }
+}
+
+#[derive(Debug)]
+pub enum SeedError {
+    // An RPC failure or a refused transaction
+    Chain(String),
+    // The limiter is in a state the seeder leaves alone
+    Refused(String),
+    // The client keypairs file
+    File(String),
+}
+
+impl fmt::Display for SeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeedError::Chain(message) | SeedError::Refused(message) | SeedError::File(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SeedError {}
+
+// Where the seeder reads accounts and sends transactions: an RPC node here, a
+// program-test bank in the tests. The first signer pays the fees.
+pub trait Ledger {
+    fn accounts(&mut self, addresses: &[Pubkey]) -> Result<Vec<Option<Account>>, SeedError>;
+    fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair])
+        -> Result<(), SeedError>;
+}
+
+pub struct RpcLedger(pub RpcClient);
+
+impl Ledger for RpcLedger {
+    fn accounts(&mut self, addresses: &[Pubkey]) -> Result<Vec<Option<Account>>, SeedError> {
+        self.0
+            .get_multiple_accounts(addresses)
+            .map_err(|err| SeedError::Chain(err.to_string()))
+    }
+
+    fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), SeedError> {
+        let blockhash = self
+            .0
+            .get_latest_blockhash()
+            .map_err(|err| SeedError::Chain(err.to_string()))?;
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&signers[0].pubkey()),
+            signers,
+            blockhash,
+        );
+        self.0
+            .send_and_confirm_transaction(&tx)
+            .map(|_| ())
+            .map_err(|err| SeedError::Chain(err.to_string()))
+    }
+}
+
+pub struct SeedOptions {
+    pub preset: Preset,
+    // Share of max_requests each client's window is filled to, in percent, handed
+    // out to the clients in turn
+    pub usage: Vec<u64>,
+    // The first `block` clients end up blocked
+    pub block: usize,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SeedReport {
+    pub initialized: bool,
+    pub reconfigured: bool,
+    pub registered: usize,
+    pub consumed: u64,
+    pub blocked: usize,
+}
+
+impl SeedReport {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "initialized": self.initialized,
+            "reconfigured": self.reconfigured,
+            "registered": self.registered,
+            "consumed": self.consumed,
+            "blocked": self.blocked,
+        })
+    }
+}
+
+// Requests a client ends its current window at, before any block
+pub fn target_usage(options: &SeedOptions, limits: &RateLimiterConfig, index: usize) -> u64 {
+    if options.usage.is_empty() {
+        return 0;
+    }
+    let percent = options.usage[index % options.usage.len()].min(100);
+    limits.max_requests * percent / 100
+}
+
+pub fn seed(
+    ledger: &mut impl Ledger,
+    admin: &Keypair,
+    clients: &[Keypair],
+    options: &SeedOptions,
+) -> Result<SeedReport, SeedError> {
+    let mut report = SeedReport::default();
+    let me = admin.pubkey();
+    let limits = options.preset.limits();
+
+    let [config_account, stats_account, clock] = ledger
+        .accounts(&[
+            pda::global_config_address().0,
+            pda::global_stats_address().0,
+            sysvar::clock::ID,
+        ])?
+        .try_into()
+        .expect("one account per address");
+    let mut setup = Vec::new();
+    if config_account.is_none() {
+        setup.push(sdk::build_initialize_with_preset_ix(
+            &me,
+            options.preset.config_preset(),
+        ));
+    }
+    if stats_account.is_none() {
+        setup.push(sdk::build_initialize_stats_ix(
+            &me,
+            &me,
+            SNAPSHOT_INTERVAL_SECONDS,
+        ));
+    }
+    if !setup.is_empty() {
+        ledger.send(&setup, &[admin])?;
+        report.initialized = config_account.is_none();
+    }
+    let now = clock
+        .and_then(|clock| from_account::<Clock, _>(&clock))
+        .ok_or_else(|| SeedError::Chain("the clock sysvar did not decode".into()))?
+        .unix_timestamp;
+
+    let config_account = match config_account {
+        Some(account) => account,
+        None => ledger
+            .accounts(&[pda::global_config_address().0])?
+            .pop()
+            .flatten()
+            .ok_or_else(|| SeedError::Chain("initialize left no config account".into()))?,
+    };
+    let mut config =
+        GlobalConfig::try_from_account_data(&config_account.data).map_err(undecodable)?;
+    if config.admin != me {
+        return Err(SeedError::Refused(format!(
+            "the limiter's admin is {}; seed it with that keypair",
+            config.admin
+        )));
+    }
+    if config.pause_active(now) {
+        return Err(SeedError::Refused(
+            "the limiter is paused; unpause it before seeding".into(),
+        ));
+    }
+    if (
+        config.max_requests,
+        config.window_seconds,
+        config.burst_limit,
+    ) != (
+        limits.max_requests,
+        limits.window_seconds,
+        limits.burst_limit,
+    ) {
+        ledger.send(
+            &[sdk::build_update_config_ix(&me, limits.clone(), false)],
+            &[admin],
+        )?;
+        config.max_requests = limits.max_requests;
+        config.window_seconds = limits.window_seconds;
+        config.burst_limit = limits.burst_limit;
+        report.reconfigured = true;
+    }
+
+    let owners: Vec<Pubkey> = clients.iter().map(Signer::pubkey).collect();
+    for (client, bucket) in clients.iter().zip(buckets(ledger, &owners)?) {
+        if bucket.is_none() {
+            // The admin pays the rent so demo keypairs need no SOL
+            let ix = sdk::build_register_client_ix(&config, &client.pubkey(), &me);
+            ledger.send(&[ix], &[admin, client])?;
+            report.registered += 1;
+        }
+    }
+
+    let buckets = buckets(ledger, &owners)?;
+    for (index, (client, bucket)) in clients.iter().zip(buckets).enumerate() {
+        let bucket = bucket.ok_or_else(|| {
+            SeedError::Chain(format!(
+                "{} has no bucket after registering",
+                client.pubkey()
+            ))
+        })?;
+        // What the window holds once a consume opens it, since an expired one
+        // starts over
+        let used = match logic::apply_consume(&bucket, &config, now, 0) {
+            Ok(consumed) => consumed.bucket.request_count,
+            Err(_) => continue,
+        };
+        let target = target_usage(options, &limits, index);
+        if target > used {
+            let ix = sdk::build_consume_amount_ix(&config, &client.pubkey(), target - used);
+            ledger.send(&[ix], &[admin, client])?;
+            report.consumed += target - used;
+        }
+        if index < options.block && !bucket.is_blocked() {
+            let ix = sdk::build_block_client_ix(&config, &me, &client.pubkey());
+            ledger.send(&[ix], &[admin])?;
+            report.blocked += 1;
+        }
+    }
+    Ok(report)
+}
+
+fn undecodable(err: impl fmt::Display) -> SeedError {
+    SeedError::Chain(format!("decoding account: {}", err))
+}
+
+pub fn buckets(
+    ledger: &mut impl Ledger,
+    owners: &[Pubkey],
+) -> Result<Vec<Option<ClientBucket>>, SeedError> {
+    let addresses: Vec<Pubkey> = owners
+        .iter()
+        .map(|owner| pda::client_bucket_address(owner).0)
+        .collect();
+    let mut buckets = Vec::with_capacity(owners.len());
+    for chunk in addresses.chunks(ACCOUNTS_PER_REQUEST) {
+        for account in ledger.accounts(chunk)? {
+            let bucket = account
+                .map(|account| ClientBucket::try_from_account_data(&account.data))
+                .transpose()
+                .map_err(undecodable)?;
+            buckets.push(bucket);
+        }
+    }
+    Ok(buckets)
+}
+
+// The clients in `path`, then new keypairs up to `count`. An existing file with
+// more clients keeps them all.
+pub fn load_clients(path: &Path, count: usize) -> Result<Vec<Keypair>, SeedError> {
+    let mut clients = Vec::new();
+    if path.exists() {
+        let unreadable = |err: String| SeedError::File(format!("{}: {}", path.display(), err));
+        let text = fs::read_to_string(path).map_err(|err| unreadable(err.to_string()))?;
+        let entries: Vec<Value> =
+            serde_json::from_str(&text).map_err(|err| unreadable(err.to_string()))?;
+        for entry in entries {
+            // Each keypair is kept in the Solana CLI's own format
+            let bytes = entry["keypair"].to_string();
+            let keypair =
+                read_keypair(&mut Cursor::new(bytes)).map_err(|err| unreadable(err.to_string()))?;
+            clients.push(keypair);
+        }
+    }
+    while clients.len() < count {
+        clients.push(Keypair::new());
+    }
+    Ok(clients)
+}
+
+pub fn save_clients(path: &Path, clients: &[Keypair]) -> Result<(), SeedError> {
+    let entries: Vec<Value> = clients
+        .iter()
+        .map(|client| {
+            json!({
+                "wallet": client.pubkey().to_string(),
+                "keypair": client.to_bytes().to_vec(),
+            })
+        })
+        .collect();
+    let text = serde_json::to_string_pretty(&entries).expect("JSON values serialize");
+    fs::write(path, text).map_err(|err| SeedError::File(format!("{}: {}", path.display(), err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_pass_the_programs_checks() {
+        for preset in [Preset::Strict, Preset::Standard, Preset::Lenient] {
+            let limits = preset.limits();
+            assert!(limits.max_requests > 0 && limits.window_seconds > 0);
+            assert!(limits.burst_limit >= limits.max_requests, "{:?}", preset);
+        }
+    }
+
+    #[test]
+    fn usage_is_handed_out_in_turn() {
+        let options = SeedOptions {
+            preset: Preset::Standard,
+            usage: vec![0, 50, 100, 250],
+            block: 0,
+        };
+        let limits = options.preset.limits();
+        let targets: Vec<u64> = (0..5)
+            .map(|index| target_usage(&options, &limits, index))
+            .collect();
+        // Past 100% is capped at the window's allowance
+        assert_eq!(targets, [0, 50, 100, 100, 0]);
+    }
+
+    #[test]
+    fn clients_file_round_trips_and_tops_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clients.json");
+        let first = load_clients(&path, 2).unwrap();
+        save_clients(&path, &first).unwrap();
+
+        let again = load_clients(&path, 3).unwrap();
+        assert_eq!(again.len(), 3);
+        for (before, after) in first.iter().zip(&again) {
+            assert_eq!(before.to_bytes(), after.to_bytes());
+        }
+        // Asking for fewer keeps what is there
+        assert_eq!(load_clients(&path, 1).unwrap().len(), 3);
+    }
+}
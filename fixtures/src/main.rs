@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+use rate_limiter_fixtures::{load_clients, save_clients, seed, Preset, RpcLedger, SeedOptions};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::read_keypair_file;
+
+#[derive(Parser)]
+#[command(
+    name = "fixtures",
+    version,
+    about = "Seed a local solana-rate-limiter with demo clients; safe to run again"
+)]
+struct Args {
+    #[arg(
+        long,
+        short = 'u',
+        default_value = "http://127.0.0.1:8899",
+        help = "RPC URL of the cluster to seed"
+    )]
+    url: String,
+    #[arg(
+        long,
+        short = 'k',
+        help = "Admin keypair; must be the program's upgrade authority to initialize [default: the Solana CLI's]"
+    )]
+    keypair: Option<String>,
+    #[arg(long, value_enum, default_value_t = Preset::Standard)]
+    preset: Preset,
+    #[arg(long, default_value_t = 5, help = "Demo clients to have registered")]
+    clients: usize,
+    #[arg(
+        long,
+        default_value = "fixture-clients.json",
+        help = "Where the demo keypairs are kept; existing ones are reused"
+    )]
+    out: PathBuf,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "0,25,50,100",
+        help = "Percent of max_requests each client's window is filled to, in turn"
+    )]
+    usage: Vec<u64>,
+    #[arg(long, default_value_t = 0, help = "Block this many of the clients")]
+    block: usize,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let keypair = args
+        .keypair
+        .clone()
+        .unwrap_or_else(|| solana_cli_config::Config::default().keypair_path);
+    let admin = match read_keypair_file(&keypair) {
+        Ok(admin) => admin,
+        Err(err) => {
+            eprintln!("reading keypair {}: {}", keypair, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // Written before anything is sent, so a failed run does not lose the keys of
+    // clients it already registered
+    let clients = match load_clients(&args.out, args.clients)
+        .and_then(|clients| save_clients(&args.out, &clients).map(|()| clients))
+    {
+        Ok(clients) => clients,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut ledger = RpcLedger(RpcClient::new_with_commitment(
+        args.url,
+        CommitmentConfig::confirmed(),
+    ));
+    let options = SeedOptions {
+        preset: args.preset,
+        usage: args.usage,
+        block: args.block,
+    };
+    match seed(&mut ledger, &admin, &clients, &options) {
+        Ok(report) => {
+            println!("{}", report.to_json());
+            eprintln!(
+                "{} client keypairs in {}",
+                clients.len(),
+                args.out.display()
+            );
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::path::Path;
+
+use rate_limiter_fixtures::{
+    buckets, seed, target_usage, Ledger, Preset, SeedError, SeedOptions, SeedReport,
+};
+use solana_program_test::{BanksClient, ProgramTest};
+use solana_rate_limiter::pda;
+use solana_rate_limiter::state::{GlobalConfig, GlobalStats};
+use solana_sdk::account::Account;
+use solana_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_program;
+use solana_sdk::transaction::Transaction;
+use tokio::runtime::Runtime;
+
+// Runs the seeder against an in-process bank with the built program deployed and
+// the test admin as its upgrade authority. Needs `anchor build` output in
+// target/deploy, so it is left out of a plain `cargo test`; run it with
+// `cargo test -p rate-limiter-fixtures -- --ignored`.
+
+struct Bank {
+    runtime: Runtime,
+    banks: BanksClient,
+}
+
+impl Ledger for Bank {
+    fn accounts(&mut self, addresses: &[Pubkey]) -> Result<Vec<Option<Account>>, SeedError> {
+        addresses
+            .iter()
+            .map(|address| {
+                self.runtime
+                    .block_on(self.banks.get_account(*address))
+                    .map_err(|err| SeedError::Chain(err.to_string()))
+            })
+            .collect()
+    }
+
+    fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), SeedError> {
+        let blockhash = self
+            .runtime
+            .block_on(self.banks.get_latest_blockhash())
+            .map_err(|err| SeedError::Chain(err.to_string()))?;
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&signers[0].pubkey()),
+            signers,
+            blockhash,
+        );
+        self.runtime
+            .block_on(self.banks.process_transaction(tx))
+            .map_err(|err| SeedError::Chain(err.to_string()))
+    }
+}
+
+// The loader's accounts as a deploy with `authority` as upgrade authority leaves them
+fn add_upgradeable_program(program_test: &mut ProgramTest, authority: &Pubkey) {
+    let path =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../target/deploy/solana_rate_limiter.so");
+    let elf = std::fs::read(&path)
+        .unwrap_or_else(|err| panic!("{}: {}; run `anchor build` first", path.display(), err));
+    let rent = Rent::default();
+    let program_data = Pubkey::find_program_address(
+        &[solana_rate_limiter::ID.as_ref()],
+        &bpf_loader_upgradeable::ID,
+    )
+    .0;
+
+    let metadata = UpgradeableLoaderState::size_of_programdata_metadata();
+    let mut data_account = Account::new_data_with_space(
+        rent.minimum_balance(metadata + elf.len()),
+        &UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: Some(*authority),
+        },
+        metadata + elf.len(),
+        &bpf_loader_upgradeable::ID,
+    )
+    .unwrap();
+    data_account.data[metadata..].copy_from_slice(&elf);
+    program_test.add_account(program_data, data_account);
+
+    let mut program_account = Account::new_data(
+        rent.minimum_balance(UpgradeableLoaderState::size_of_program()),
+        &UpgradeableLoaderState::Program {
+            programdata_address: program_data,
+        },
+        &bpf_loader_upgradeable::ID,
+    )
+    .unwrap();
+    program_account.executable = true;
+    program_test.add_account(solana_rate_limiter::ID, program_account);
+}
+
+fn start(admin: &Keypair) -> Bank {
+    let mut program_test = ProgramTest::default();
+    add_upgradeable_program(&mut program_test, &admin.pubkey());
+    program_test.add_account(
+        admin.pubkey(),
+        Account::new(100 * LAMPORTS_PER_SOL, 0, &system_program::ID),
+    );
+    let runtime = Runtime::new().unwrap();
+    let (banks, _, _) = runtime.block_on(program_test.start());
+    Bank { runtime, banks }
+}
+
+fn config(bank: &mut Bank) -> GlobalConfig {
+    let account = bank
+        .accounts(&[pda::global_config_address().0])
+        .unwrap()
+        .pop()
+        .flatten();
+    GlobalConfig::try_from_account_data(&account.unwrap().data).unwrap()
+}
+
+fn stats(bank: &mut Bank) -> GlobalStats {
+    let account = bank
+        .accounts(&[pda::global_stats_address().0])
+        .unwrap()
+        .pop()
+        .flatten();
+    GlobalStats::try_from_account_data(&account.unwrap().data).unwrap()
+}
+
+fn options() -> SeedOptions {
+    SeedOptions {
+        preset: Preset::Strict,
+        usage: vec![0, 40, 100],
+        block: 1,
+    }
+}
+
+#[test]
+#[ignore = "needs anchor build output"]
+fn seeds_a_fresh_limiter_and_tops_it_up() {
+    let admin = Keypair::new();
+    let mut bank = start(&admin);
+    let options = options();
+    let limits = options.preset.limits();
+    let mut clients: Vec<Keypair> = (0..4).map(|_| Keypair::new()).collect();
+
+    let report = seed(&mut bank, &admin, &clients, &options).unwrap();
+    assert_eq!(
+        report,
+        SeedReport {
+            initialized: true,
+            reconfigured: false,
+            registered: 4,
+            // 0%, 40% and 100% of the strict preset's 5, then 0% again
+            consumed: 2 + 5,
+            blocked: 1,
+        }
+    );
+    let config = config(&mut bank);
+    assert_eq!(config.admin, admin.pubkey());
+    assert_eq!(
+        (
+            config.max_requests,
+            config.window_seconds,
+            config.burst_limit
+        ),
+        (
+            limits.max_requests,
+            limits.window_seconds,
+            limits.burst_limit
+        )
+    );
+    let stats = stats(&mut bank);
+    assert_eq!(
+        (
+            stats.total_clients,
+            stats.blocked_clients,
+            stats.total_requests
+        ),
+        (4, 1, 7)
+    );
+
+    // Two more clients: only they are registered and filled, nobody else changes
+    clients.extend((0..2).map(|_| Keypair::new()));
+    let report = seed(&mut bank, &admin, &clients, &options).unwrap();
+    assert_eq!(
+        report,
+        SeedReport {
+            registered: 2,
+            consumed: 2 + 5,
+            ..SeedReport::default()
+        }
+    );
+    let owners: Vec<Pubkey> = clients.iter().map(Signer::pubkey).collect();
+    for (index, bucket) in buckets(&mut bank, &owners).unwrap().into_iter().enumerate() {
+        let bucket = bucket.unwrap();
+        assert_eq!(
+            bucket.request_count,
+            target_usage(&options, &limits, index),
+            "client {}",
+            index
+        );
+        assert_eq!(
+            bucket.is_blocked(),
+            index < options.block,
+            "client {}",
+            index
+        );
+    }
+
+    // A third run finds nothing to do
+    assert_eq!(
+        seed(&mut bank, &admin, &clients, &options).unwrap(),
+        SeedReport::default()
+    );
+}
+
+#[test]
+#[ignore = "needs anchor build output"]
+fn leaves_someone_elses_limiter_alone() {
+    let admin = Keypair::new();
+    let mut bank = start(&admin);
+    let clients = vec![Keypair::new()];
+    seed(&mut bank, &admin, &clients, &options()).unwrap();
+
+    let stranger = Keypair::new();
+    let err = seed(&mut bank, &stranger, &clients, &options()).unwrap_err();
+    assert!(matches!(err, SeedError::Refused(_)), "{}", err);
+}
@@ -0,0 +1,419 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anchor_lang::prelude::*;
+use prometheus::{Encoder, IntCounter, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use super::rpc::{ClientError, Result};
+use crate::logic;
+use crate::pda;
+use crate::state::{ClientBucket, GlobalConfig, GlobalStats, Schedule, ScheduleEntry};
+
+// Prometheus gauges for one limiter, behind the `metrics` feature. `run` reads
+// the config, the stats and a fixed list of buckets every interval and `serve`
+// answers scrapes with the text format. A failed read leaves the last values in
+// place and sets `rate_limiter_stale`, so a flaky RPC node shows up as stale
+// data on the dashboard rather than as the exporter going away.
+//
+// The chain's totals are absolute, so they are exported as gauges, even
+// total_requests; rate() over them works the same as over a counter.
+
+// getMultipleAccounts takes at most this many addresses
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+pub struct Exporter {
+    registry: Registry,
+    paused: IntGauge,
+    total_requests: IntGauge,
+    total_clients: IntGauge,
+    blocked_clients: IntGauge,
+    remaining_quota: IntGaugeVec,
+    stale: IntGauge,
+    last_refresh: IntGauge,
+    refresh_errors: IntCounter,
+}
+
+impl Exporter {
+    pub fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new();
+        let gauge = |name: &str, help: &str| -> prometheus::Result<IntGauge> {
+            let gauge = IntGauge::new(name, help)?;
+            registry.register(Box::new(gauge.clone()))?;
+            Ok(gauge)
+        };
+        let exporter = Exporter {
+            paused: gauge(
+                "rate_limiter_paused",
+                "1 while an emergency or scheduled pause is in effect",
+            )?,
+            total_requests: gauge(
+                "rate_limiter_total_requests",
+                "Accepted consumes across all clients",
+            )?,
+            total_clients: gauge("rate_limiter_total_clients", "Buckets ever registered")?,
+            blocked_clients: gauge("rate_limiter_blocked_clients", "Buckets currently blocked")?,
+            stale: gauge(
+                "rate_limiter_stale",
+                "1 when the last refresh failed and the other series are older",
+            )?,
+            last_refresh: gauge(
+                "rate_limiter_last_refresh_timestamp_seconds",
+                "Unix time of the last successful refresh",
+            )?,
+            remaining_quota: IntGaugeVec::new(
+                Opts::new(
+                    "rate_limiter_remaining_quota",
+                    "Requests a watched bucket has left in its window",
+                ),
+                &["owner"],
+            )?,
+            refresh_errors: IntCounter::new(
+                "rate_limiter_refresh_errors_total",
+                "Refreshes that failed",
+            )?,
+            registry,
+        };
+        exporter
+            .registry
+            .register(Box::new(exporter.remaining_quota.clone()))?;
+        exporter
+            .registry
+            .register(Box::new(exporter.refresh_errors.clone()))?;
+        Ok(exporter)
+    }
+
+    // For adding the caller's own series next to these
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    // Sets every series from one read of the accounts. A watched bucket that does
+    // not exist, never registered or since closed, drops its series.
+    pub fn record(
+        &self,
+        config: &GlobalConfig,
+        schedule: &[ScheduleEntry],
+        stats: &GlobalStats,
+        buckets: &[(Pubkey, Option<ClientBucket>)],
+        now: i64,
+    ) {
+        self.paused.set(config.pause_active(now) as i64);
+        self.total_requests.set(clamp(stats.total_requests));
+        self.total_clients.set(clamp(stats.total_clients));
+        self.blocked_clients.set(clamp(stats.blocked_clients));
+        for (owner, bucket) in buckets {
+            let label = owner.to_string();
+            match bucket {
+                Some(bucket) => {
+                    let quota = logic::simulate_scheduled_consume(config, schedule, bucket, now);
+                    self.remaining_quota
+                        .with_label_values(&[label.as_str()])
+                        .set(clamp(quota.remaining));
+                }
+                None => {
+                    let _ = self.remaining_quota.remove_label_values(&[label.as_str()]);
+                }
+            }
+        }
+        self.stale.set(0);
+        self.last_refresh.set(now);
+    }
+
+    pub fn record_failure(&self) {
+        self.stale.set(1);
+        self.refresh_errors.inc();
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = Vec::new();
+        // Only fails on a writer error, and a Vec does not have them
+        let _ = TextEncoder::new().encode(&self.registry.gather(), &mut text);
+        String::from_utf8(text).unwrap_or_default()
+    }
+
+    // One getMultipleAccounts per hundred addresses, so the config, the stats
+    // and the first buckets come from the same slot
+    pub async fn refresh(&self, rpc: &RpcClient, buckets: &[Pubkey]) -> Result<()> {
+        let mut addresses = vec![
+            pda::global_config_address().0,
+            pda::global_stats_address().0,
+            pda::schedule_address().0,
+        ];
+        addresses.extend(
+            buckets
+                .iter()
+                .map(|owner| pda::client_bucket_address(owner).0),
+        );
+        let mut accounts = Vec::with_capacity(addresses.len());
+        for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            accounts.extend(rpc.get_multiple_accounts(chunk).await?);
+        }
+        let data = |index: usize| accounts[index].as_ref().map(|a| a.data.as_slice());
+
+        let decode_error = |address: Pubkey| {
+            move |source: anchor_lang::error::Error| ClientError::Decode { address, source }
+        };
+        let config = data(0).ok_or(ClientError::NotRegistered(addresses[0]))?;
+        let config =
+            GlobalConfig::try_from_account_data(config).map_err(decode_error(addresses[0]))?;
+        let stats = data(1).ok_or(ClientError::NotRegistered(addresses[1]))?;
+        let stats =
+            GlobalStats::try_from_account_data(stats).map_err(decode_error(addresses[1]))?;
+        let schedule = match data(2) {
+            Some(schedule) if config.scheduled() => Schedule::try_from_account_data(schedule)
+                .map_err(decode_error(addresses[2]))?
+                .entries()
+                .to_vec(),
+            _ => Vec::new(),
+        };
+        let buckets = buckets
+            .iter()
+            .enumerate()
+            .map(|(i, owner)| {
+                let bucket = data(3 + i)
+                    .map(ClientBucket::try_from_account_data)
+                    .transpose()
+                    .map_err(decode_error(addresses[3 + i]))?;
+                Ok((*owner, bucket))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.record(&config, &schedule, &stats, &buckets, unix_now());
+        Ok(())
+    }
+}
+
+fn clamp(value: u64) -> i64 {
+    value.min(i64::MAX as u64) as i64
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+// Refreshes every `interval` until the task is dropped
+pub async fn run(
+    rpc: RpcClient,
+    exporter: Arc<Exporter>,
+    buckets: Vec<Pubkey>,
+    interval: Duration,
+) {
+    let mut ticks = tokio::time::interval(interval);
+    loop {
+        ticks.tick().await;
+        if exporter.refresh(&rpc, &buckets).await.is_err() {
+            exporter.record_failure();
+        }
+    }
+}
+
+// Answers GET /metrics on every connection `listener` accepts, and 404 to the
+// rest. Enough HTTP for a scraper; put a real server in front for anything more.
+pub async fn serve(listener: TcpListener, exporter: Arc<Exporter>) -> io::Result<()> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let exporter = exporter.clone();
+        tokio::spawn(async move {
+            let mut request = [0u8; 1024];
+            let read = stream.read(&mut request).await.unwrap_or(0);
+            let response = respond(&request[..read], &exporter);
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        });
+    }
+}
+
+// The whole exporter: refreshes in the background and serves on `listen`
+pub async fn export(
+    rpc: RpcClient,
+    listen: SocketAddr,
+    buckets: Vec<Pubkey>,
+    interval: Duration,
+) -> io::Result<()> {
+    let exporter = Arc::new(Exporter::new().map_err(io::Error::other)?);
+    let listener = TcpListener::bind(listen).await?;
+    let refresher = tokio::spawn(run(rpc, exporter.clone(), buckets, interval));
+    let served = serve(listener, exporter).await;
+    refresher.abort();
+    served
+}
+
+fn respond(request: &[u8], exporter: &Exporter) -> String {
+    let (status, body) = if request.starts_with(b"GET /metrics ") {
+        ("200 OK", exporter.render())
+    } else {
+        ("404 Not Found", String::new())
+    };
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{CLIENT_BUCKET_VERSION, USAGE_HISTOGRAM_BUCKETS};
+
+    // program-test is not a dependency, so the accounts are built here as a
+    // refresh would decode them; refresh itself only adds the fetch
+
+    fn stats() -> GlobalStats {
+        GlobalStats {
+            total_clients: 12,
+            blocked_clients: 2,
+            total_requests: 4_321,
+            total_saturated: false,
+            window_rollovers: 0,
+            snapshot_interval_seconds: 3_600,
+            last_snapshot_at: 0,
+            bump: 255,
+            circuit_breaker_threshold: 0,
+            circuit_breaker_window_seconds: 0,
+            rejection_window_start: 0,
+            rejection_count: 0,
+            total_denials: 0,
+            usage_histogram: [0; USAGE_HISTOGRAM_BUCKETS],
+        }
+    }
+
+    fn bucket(owner: Pubkey, request_count: u64, now: i64) -> ClientBucket {
+        ClientBucket {
+            version: CLIENT_BUCKET_VERSION,
+            owner,
+            request_count,
+            window_start: now,
+            total_requests: request_count,
+            flags: 0,
+            violations: 0,
+            violations_updated_at: 0,
+            config_epoch: 0,
+            burst_credits: 0,
+            priority_count: 0,
+            escrowed_count: 0,
+            bonus_requests: 0,
+            last_nonce: 0,
+            nonce_bitmap: 0,
+            last_refund_nonce: 0,
+            refunded_count: 0,
+            registered_by: Pubkey::default(),
+            recovery_delegate: Pubkey::default(),
+            last_request_ts: now,
+            bump: 255,
+            label: [0; 32],
+            event_tag: [0; 16],
+        }
+    }
+
+    #[test]
+    fn renders_the_expected_series() {
+        let exporter = Exporter::new().unwrap();
+        let config = super::super::tests::config();
+        let owner = Pubkey::new_unique();
+        let closed = Pubkey::new_unique();
+        let now = 1_700_000_000;
+        let watched = bucket(owner, 3, now);
+        let remaining = logic::simulate_consume(&config, &watched, now).remaining;
+
+        exporter.record(
+            &config,
+            &[],
+            &stats(),
+            &[(owner, Some(watched.clone())), (closed, Some(watched))],
+            now,
+        );
+        exporter.record(
+            &config,
+            &[],
+            &stats(),
+            &[(owner, Some(bucket(owner, 3, now))), (closed, None)],
+            now,
+        );
+        let text = exporter.render();
+        for line in [
+            "rate_limiter_paused 0".to_string(),
+            "rate_limiter_total_requests 4321".to_string(),
+            "rate_limiter_blocked_clients 2".to_string(),
+            "rate_limiter_stale 0".to_string(),
+            format!("rate_limiter_last_refresh_timestamp_seconds {}", now),
+            format!(
+                "rate_limiter_remaining_quota{{owner=\"{}\"}} {}",
+                owner, remaining
+            ),
+            "# TYPE rate_limiter_refresh_errors_total counter".to_string(),
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing {:?} in\n{}",
+                line,
+                text
+            );
+        }
+        // The closed bucket's series is gone rather than stuck at its last value
+        assert!(!text.contains(&closed.to_string()), "{}", text);
+    }
+
+    #[test]
+    fn failures_mark_the_data_stale_and_keep_it() {
+        let exporter = Exporter::new().unwrap();
+        let config = super::super::tests::config();
+        exporter.record(&config, &[], &stats(), &[], 1_000);
+        exporter.record_failure();
+        exporter.record_failure();
+        let text = exporter.render();
+        assert!(
+            text.lines().any(|l| l == "rate_limiter_stale 1"),
+            "{}",
+            text
+        );
+        assert!(
+            text.lines()
+                .any(|l| l == "rate_limiter_refresh_errors_total 2"),
+            "{}",
+            text
+        );
+        assert!(
+            text.lines()
+                .any(|l| l == "rate_limiter_total_requests 4321"),
+            "{}",
+            text
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_the_exposition_over_http() {
+        let exporter = Arc::new(Exporter::new().unwrap());
+        exporter.record(&super::super::tests::config(), &[], &stats(), &[], 1_000);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, exporter));
+
+        let get = |path: &'static str| async move {
+            let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+            stream
+                .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        let metrics = get("/metrics").await;
+        assert!(metrics.starts_with("HTTP/1.1 200 OK\r\n"), "{}", metrics);
+        assert!(
+            metrics.contains("\nrate_limiter_blocked_clients 2\n"),
+            "{}",
+            metrics
+        );
+        assert!(get("/").await.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
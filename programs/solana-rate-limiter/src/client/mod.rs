@@ -0,0 +1,1887 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::bpf_loader_upgradeable;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{system_program, Discriminator, InstructionData};
+
+#[cfg(feature = "snapshot-import")]
+use crate::constants::SNAPSHOT_SOURCE_PROGRAM_ID;
+use crate::constants::{EVENT_TAG_LEN, LIMIT_ALGORITHM_FIXED_WINDOW};
+use crate::errors::RateLimiterError;
+use crate::gate::DeploymentInfo;
+use crate::pda;
+#[cfg(feature = "snapshot-import")]
+use crate::snapshot::BucketSnapshot;
+use crate::state::{
+    AdminAction, Appeal, CallerAllowlist, ClientBucket, ClientNote, DiscountTier, FastBucket,
+    GlobalConfig, GlobalStats, Offer, PayerMeta, ProgramBucket, Proposal, RebalanceParams,
+    Schedule, ScheduleEntry, ShardParent, SponsorPool,
+};
+use crate::{accounts, instruction, ConfigPreset, ConsumeArgs, RateLimiterConfig};
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod parser;
+pub mod program;
+pub mod rpc;
+pub mod sender;
+pub mod subscribe;
+
+// Instruction builders for off-chain Rust clients, behind the `client` feature.
+// Each derives its PDAs through `pda` and lists accounts in the order the
+// program's `#[derive(Accounts)]` struct declares them, so a new account in a
+// context reaches callers with the crate update.
+//
+// Optional accounts that the program requires depending on its state, such as
+// the schedule while one is set or the treasury while a registration fee is
+// charged, are picked from the decoded account passed in; fetch it first. Only
+// top-level calls are covered: CPI callers pass `caller_entry` and the
+// instructions sysvar themselves.
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn global_config() -> Pubkey {
+    pda::global_config_address().0
+}
+
+fn global_stats() -> Pubkey {
+    pda::global_stats_address().0
+}
+
+fn bucket(owner: &Pubkey) -> Pubkey {
+    pda::client_bucket_address(owner).0
+}
+
+fn schedule(config: &GlobalConfig) -> Option<Pubkey> {
+    config.scheduled().then(|| pda::schedule_address().0)
+}
+
+// While an emergency pause with a grace period is on; without it a consume gets
+// no grace
+fn pause_grace(config: &GlobalConfig) -> Option<Pubkey> {
+    (config.is_paused() && config.pause_grace_seconds > 0).then(|| pda::pause_grace_address().0)
+}
+
+// Only while the circuit breaker is on, which refuses consumes that leave it out
+fn stats(config: &GlobalConfig) -> Option<Pubkey> {
+    config.circuit_breaker_on().then(global_stats)
+}
+
+fn treasury(config: &GlobalConfig) -> Option<Pubkey> {
+    (config.registration_fee_lamports > 0).then_some(config.treasury)
+}
+
+fn sponsor_pool(bucket: &ClientBucket) -> Option<Pubkey> {
+    bucket.is_sponsored().then(|| pda::sponsor_pool_address().0)
+}
+
+// The upgradeable loader's record of our upgrade authority
+pub fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[crate::ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+// The remaining accounts hook.rs expects: the hook program and our hook
+// authority, then whatever the hook itself reads. Empty while no hook is set.
+pub fn hook_accounts(config: &GlobalConfig, extra: &[AccountMeta]) -> Vec<AccountMeta> {
+    if !config.has_hook() {
+        return Vec::new();
+    }
+    let mut metas = vec![
+        AccountMeta::new_readonly(config.hook_program, false),
+        AccountMeta::new_readonly(pda::hook_authority_address().0, false),
+    ];
+    metas.extend_from_slice(extra);
+    metas
+}
+
+pub fn build_initialize_ix(admin: &Pubkey, config: RateLimiterConfig) -> Instruction {
+    build(
+        accounts::Initialize {
+            global_config: global_config(),
+            admin: *admin,
+            program: crate::ID,
+            program_data: program_data_address(),
+            system_program: system_program::ID,
+        },
+        instruction::Initialize { config },
+    )
+}
+
+pub fn build_initialize_with_preset_ix(admin: &Pubkey, preset: ConfigPreset) -> Instruction {
+    build(
+        accounts::Initialize {
+            global_config: global_config(),
+            admin: *admin,
+            program: crate::ID,
+            program_data: program_data_address(),
+            system_program: system_program::ID,
+        },
+        instruction::InitializeWithPreset { preset },
+    )
+}
+
+pub fn build_initialize_stats_ix(
+    admin: &Pubkey,
+    payer: &Pubkey,
+    snapshot_interval_seconds: i64,
+) -> Instruction {
+    build(
+        accounts::InitializeStats {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            admin: *admin,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeStats {
+            snapshot_interval_seconds,
+        },
+    )
+}
+
+pub fn build_register_client_ix(
+    config: &GlobalConfig,
+    client: &Pubkey,
+    payer: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::RegisterClient {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(client),
+            fast_bucket: pda::fast_bucket_address(client).0,
+            shard_parent: pda::shard_parent_address(client).0,
+            payer_meta: pda::payer_meta_address(payer).0,
+            treasury: treasury(config),
+            client: *client,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::RegisterClient {},
+    )
+}
+
+pub fn build_register_fast_client_ix(
+    config: &GlobalConfig,
+    client: &Pubkey,
+    payer: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::RegisterFastClient {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            fast_bucket: pda::fast_bucket_address(client).0,
+            client_bucket: bucket(client),
+            shard_parent: pda::shard_parent_address(client).0,
+            payer_meta: pda::payer_meta_address(payer).0,
+            treasury: treasury(config),
+            client: *client,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::RegisterFastClient {},
+    )
+}
+
+// Appends the shard buckets, which the handler creates, as remaining accounts
+pub fn build_register_sharded_ix(
+    config: &GlobalConfig,
+    client: &Pubkey,
+    payer: &Pubkey,
+    shard_count: u8,
+) -> Instruction {
+    let mut ix = build(
+        accounts::RegisterSharded {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            shard_parent: pda::shard_parent_address(client).0,
+            client_bucket: bucket(client),
+            fast_bucket: pda::fast_bucket_address(client).0,
+            payer_meta: pda::payer_meta_address(payer).0,
+            treasury: treasury(config),
+            client: *client,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::RegisterSharded { shard_count },
+    );
+    ix.accounts.extend(
+        (0..shard_count)
+            .map(|index| AccountMeta::new(pda::client_shard_address(client, index).0, false)),
+    );
+    ix
+}
+
+pub fn build_register_sponsored_ix(client: &Pubkey) -> Instruction {
+    build(
+        accounts::RegisterSponsored {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(client),
+            fast_bucket: pda::fast_bucket_address(client).0,
+            shard_parent: pda::shard_parent_address(client).0,
+            sponsor_pool: pda::sponsor_pool_address().0,
+            client: *client,
+            system_program: system_program::ID,
+        },
+        instruction::RegisterSponsored {},
+    )
+}
+
+pub fn build_register_program_ix(
+    admin: &Pubkey,
+    payer: &Pubkey,
+    caller_program: &Pubkey,
+    limits: RateLimiterConfig,
+) -> Instruction {
+    build(
+        accounts::RegisterProgram {
+            global_config: global_config(),
+            program_bucket: pda::program_bucket_address(caller_program).0,
+            caller_program: *caller_program,
+            admin: *admin,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::RegisterProgram { limits },
+    )
+}
+
+// The consume builders leave the stats account out unless the circuit breaker needs
+// it, so consumes by different clients do not queue behind each other; the totals
+// then miss them
+fn consume_accounts(config: &GlobalConfig, client: &Pubkey) -> accounts::ConsumeRequest {
+    accounts::ConsumeRequest {
+        global_config: global_config(),
+        global_stats: stats(config),
+        client_bucket: bucket(client),
+        client: *client,
+        caller_entry: None,
+        instructions: None,
+        schedule: schedule(config),
+        owner_denylist: None,
+        pause_grace: pause_grace(config),
+    }
+}
+
+pub fn build_consume_ix(config: &GlobalConfig, client: &Pubkey) -> Instruction {
+    build(
+        consume_accounts(config, client),
+        instruction::ConsumeRequest {},
+    )
+}
+
+pub fn build_consume_and_report_ix(config: &GlobalConfig, client: &Pubkey) -> Instruction {
+    build(
+        consume_accounts(config, client),
+        instruction::ConsumeAndReport {},
+    )
+}
+
+pub fn build_consume_amount_ix(config: &GlobalConfig, client: &Pubkey, amount: u64) -> Instruction {
+    build(
+        consume_accounts(config, client),
+        instruction::ConsumeAmount { amount },
+    )
+}
+
+pub fn build_consume_priority_ix(
+    config: &GlobalConfig,
+    client: &Pubkey,
+    priority: bool,
+) -> Instruction {
+    build(
+        consume_accounts(config, client),
+        instruction::ConsumePriority { priority },
+    )
+}
+
+pub fn build_consume_v2_ix(
+    config: &GlobalConfig,
+    client: &Pubkey,
+    args: ConsumeArgs,
+) -> Instruction {
+    build(
+        consume_accounts(config, client),
+        instruction::ConsumeV2 { args },
+    )
+}
+
+// Appends the hook accounts when a hook is set, for hooks that read nothing else
+pub fn build_gate_check_ix(config: &GlobalConfig, client: &Pubkey) -> Instruction {
+    let mut ix = build(consume_accounts(config, client), instruction::GateCheck {});
+    ix.accounts.extend(hook_accounts(config, &[]));
+    ix
+}
+
+pub fn build_consume_fast_ix(config: &GlobalConfig, client: &Pubkey) -> Instruction {
+    build(
+        accounts::ConsumeRequestFast {
+            global_config: global_config(),
+            global_stats: stats(config),
+            fast_bucket: pda::fast_bucket_address(client).0,
+            client: *client,
+            schedule: schedule(config),
+        },
+        instruction::ConsumeRequestFast {},
+    )
+}
+
+pub fn build_consume_sharded_ix(config: &GlobalConfig, client: &Pubkey, shard: u8) -> Instruction {
+    build(
+        accounts::ConsumeSharded {
+            global_config: global_config(),
+            shard_parent: pda::shard_parent_address(client).0,
+            shard_bucket: pda::client_shard_address(client, shard).0,
+            client: *client,
+            schedule: schedule(config),
+            global_stats: stats(config),
+        },
+        instruction::ConsumeSharded { shard },
+    )
+}
+
+// The handler wants every shard, in order
+pub fn build_aggregate_shards_ix(parent: &ShardParent) -> Instruction {
+    let mut ix = build(
+        accounts::AggregateShards {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            shard_parent: pda::shard_parent_address(&parent.owner).0,
+        },
+        instruction::AggregateShards {},
+    );
+    ix.accounts.extend((0..parent.shard_count).map(|index| {
+        AccountMeta::new_readonly(pda::client_shard_address(&parent.owner, index).0, false)
+    }));
+    ix
+}
+
+pub fn build_try_consume_ix(config: &GlobalConfig, client: &Pubkey) -> Instruction {
+    build(
+        accounts::TryConsume {
+            global_config: global_config(),
+            global_stats: stats(config),
+            client_bucket: bucket(client),
+            client: *client,
+            schedule: schedule(config),
+        },
+        instruction::TryConsume {},
+    )
+}
+
+// `caller_authority` is the caller program's PDA and signs through its CPI, so
+// this is for callers building the CPI by hand rather than a transaction
+pub fn build_consume_as_program_ix(config: &GlobalConfig, caller_program: &Pubkey) -> Instruction {
+    build(
+        accounts::ConsumeAsProgram {
+            global_config: global_config(),
+            global_stats: stats(config),
+            program_bucket: pda::program_bucket_address(caller_program).0,
+            caller_authority: pda::caller_authority_address(caller_program).0,
+        },
+        instruction::ConsumeAsProgram {},
+    )
+}
+
+pub fn build_allow_caller_ix(
+    admin: &Pubkey,
+    payer: &Pubkey,
+    caller_program: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::AllowCaller {
+            global_config: global_config(),
+            caller_entry: pda::caller_allowlist_address(caller_program).0,
+            caller_program: *caller_program,
+            admin: *admin,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::AllowCaller {},
+    )
+}
+
+pub fn build_revoke_caller_ix(admin: &Pubkey, caller_program: &Pubkey) -> Instruction {
+    build(
+        accounts::RevokeCaller {
+            global_config: global_config(),
+            caller_entry: pda::caller_allowlist_address(caller_program).0,
+            admin: *admin,
+        },
+        instruction::RevokeCaller {},
+    )
+}
+
+pub fn build_block_program_owners_ix(
+    admin: &Pubkey,
+    payer: &Pubkey,
+    program_id: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::BlockProgramOwners {
+            global_config: global_config(),
+            denylist_entry: pda::program_denylist_address(program_id).0,
+            admin: *admin,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::BlockProgramOwners {
+            program_id: *program_id,
+        },
+    )
+}
+
+pub fn build_unblock_program_owners_ix(admin: &Pubkey, program_id: &Pubkey) -> Instruction {
+    build(
+        accounts::UnblockProgramOwners {
+            global_config: global_config(),
+            denylist_entry: pda::program_denylist_address(program_id).0,
+            admin: *admin,
+        },
+        instruction::UnblockProgramOwners {},
+    )
+}
+
+pub fn build_transfer_bucket_ix(
+    current: &ClientBucket,
+    new_wallet: &Pubkey,
+    payer: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::TransferBucket {
+            global_config: global_config(),
+            client_bucket: bucket(&current.owner),
+            new_bucket: bucket(new_wallet),
+            new_fast_bucket: pda::fast_bucket_address(new_wallet).0,
+            new_shard_parent: pda::shard_parent_address(new_wallet).0,
+            owner: current.owner,
+            new_wallet: *new_wallet,
+            sponsor_pool: sponsor_pool(current),
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::TransferBucket {},
+    )
+}
+
+pub fn build_set_label_ix(owner: &Pubkey, label: String) -> Instruction {
+    build(
+        accounts::SetLabel {
+            global_config: global_config(),
+            client_bucket: bucket(owner),
+            owner: *owner,
+        },
+        instruction::SetLabel { label },
+    )
+}
+
+// All zeros clears the tag, unless the config holds tags to the first one set
+pub fn build_set_event_tag_ix(owner: &Pubkey, event_tag: [u8; EVENT_TAG_LEN]) -> Instruction {
+    build(
+        accounts::SetLabel {
+            global_config: global_config(),
+            client_bucket: bucket(owner),
+            owner: *owner,
+        },
+        instruction::SetEventTag { event_tag },
+    )
+}
+
+pub fn build_close_bucket_ix(current: &ClientBucket) -> Instruction {
+    build(
+        accounts::CloseBucket {
+            global_stats: global_stats(),
+            client_bucket: bucket(&current.owner),
+            sponsor_pool: sponsor_pool(current),
+            owner: current.owner,
+        },
+        instruction::CloseBucket {},
+    )
+}
+
+// `delegate: None` clears the owner's recovery delegate
+pub fn build_set_recovery_delegate_ix(owner: &Pubkey, delegate: Option<Pubkey>) -> Instruction {
+    build(
+        accounts::SetRecoveryDelegate {
+            global_config: global_config(),
+            client_bucket: bucket(owner),
+            owner: *owner,
+        },
+        instruction::SetRecoveryDelegate { delegate },
+    )
+}
+
+// Signed by `lost.recovery_delegate`, who receives the bucket's lamports
+pub fn build_recover_bucket_ix(lost: &ClientBucket) -> Instruction {
+    build(
+        accounts::RecoverBucket {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(&lost.owner),
+            sponsor_pool: sponsor_pool(lost),
+            delegate: lost.recovery_delegate,
+        },
+        instruction::RecoverBucket {},
+    )
+}
+
+pub fn build_gc_bucket_ix(stale: &ClientBucket, cranker: &Pubkey) -> Instruction {
+    build(
+        accounts::GcBucket {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(&stale.owner),
+            owner: stale.owner,
+            sponsor_pool: sponsor_pool(stale),
+            cranker: *cranker,
+        },
+        instruction::GcBucket {},
+    )
+}
+
+pub fn build_file_appeal_ix(client: &Pubkey) -> Instruction {
+    build(
+        accounts::FileAppeal {
+            global_config: global_config(),
+            client_bucket: bucket(client),
+            appeal: pda::appeal_address(client).0,
+            client: *client,
+            system_program: system_program::ID,
+        },
+        instruction::FileAppeal {},
+    )
+}
+
+pub fn build_offer_quota_ix(
+    config: &GlobalConfig,
+    seller: &Pubkey,
+    amount: u64,
+    price_lamports: u64,
+) -> Instruction {
+    build(
+        accounts::OfferQuota {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            seller_bucket: bucket(seller),
+            offer: pda::offer_address(seller).0,
+            seller: *seller,
+            system_program: system_program::ID,
+            schedule: schedule(config),
+        },
+        instruction::OfferQuota {
+            amount,
+            price_lamports,
+        },
+    )
+}
+
+pub fn build_accept_offer_ix(
+    config: &GlobalConfig,
+    seller: &Pubkey,
+    buyer: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::AcceptOffer {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            offer: pda::offer_address(seller).0,
+            seller_bucket: bucket(seller),
+            buyer_bucket: bucket(buyer),
+            seller: *seller,
+            buyer: *buyer,
+            system_program: system_program::ID,
+            schedule: schedule(config),
+        },
+        instruction::AcceptOffer {},
+    )
+}
+
+// Passes the seller's bucket so the escrow is released; a seller who has since
+// closed it can drop that account from the returned instruction
+pub fn build_cancel_offer_ix(config: &GlobalConfig, seller: &Pubkey) -> Instruction {
+    build(
+        accounts::CancelOffer {
+            global_config: global_config(),
+            offer: pda::offer_address(seller).0,
+            seller_bucket: Some(bucket(seller)),
+            seller: *seller,
+            schedule: schedule(config),
+        },
+        instruction::CancelOffer {},
+    )
+}
+
+pub fn build_fund_sponsor_pool_ix(admin: &Pubkey, payer: &Pubkey, lamports: u64) -> Instruction {
+    build(
+        accounts::FundSponsorPool {
+            global_config: global_config(),
+            sponsor_pool: pda::sponsor_pool_address().0,
+            admin: *admin,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::FundSponsorPool { lamports },
+    )
+}
+
+pub fn build_withdraw_sponsor_pool_ix(admin: &Pubkey) -> Instruction {
+    build(
+        accounts::WithdrawSponsorPool {
+            global_config: global_config(),
+            sponsor_pool: pda::sponsor_pool_address().0,
+            admin: *admin,
+        },
+        instruction::WithdrawSponsorPool {},
+    )
+}
+
+fn update_config_accounts(admin: &Pubkey) -> accounts::UpdateConfig {
+    accounts::UpdateConfig {
+        global_config: global_config(),
+        admin: *admin,
+    }
+}
+
+pub fn build_update_config_ix(
+    admin: &Pubkey,
+    config: RateLimiterConfig,
+    reset_windows: bool,
+) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::UpdateConfig {
+            config,
+            reset_windows,
+        },
+    )
+}
+
+pub fn build_set_caller_mode_ix(
+    admin: &Pubkey,
+    program_keyed: bool,
+    wallet_fallback: bool,
+) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetCallerMode {
+            program_keyed,
+            wallet_fallback,
+        },
+    )
+}
+
+pub fn build_set_event_tag_policy_ix(admin: &Pubkey, set_once: bool) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetEventTagPolicy { set_once },
+    )
+}
+
+pub fn build_transfer_admin_ix(admin: &Pubkey, new_admin: Pubkey) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::TransferAdmin { new_admin },
+    )
+}
+
+pub fn build_cancel_admin_recovery_ix(admin: &Pubkey) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::CancelAdminRecovery {},
+    )
+}
+
+pub fn build_schedule_pause_ix(admin: &Pubkey, start: i64, end: i64) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SchedulePause { start, end },
+    )
+}
+
+pub fn build_clear_schedule_ix(admin: &Pubkey) -> Instruction {
+    build(update_config_accounts(admin), instruction::ClearSchedule {})
+}
+
+pub fn build_add_council_member_ix(admin: &Pubkey, member: Pubkey, threshold: u8) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::AddCouncilMember { member, threshold },
+    )
+}
+
+pub fn build_set_hook_ix(
+    admin: &Pubkey,
+    hook_program: Pubkey,
+    on_reject: bool,
+    propagate: bool,
+) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetHook {
+            hook_program,
+            on_reject,
+            propagate,
+        },
+    )
+}
+
+pub fn build_set_limit_feeder_ix(
+    admin: &Pubkey,
+    feeder: Pubkey,
+    floor: u64,
+    ceiling: u64,
+) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetLimitFeeder {
+            feeder,
+            floor,
+            ceiling,
+        },
+    )
+}
+
+pub fn build_set_registration_policy_ix(
+    admin: &Pubkey,
+    fee_lamports: u64,
+    cooldown_seconds: i64,
+    treasury: Pubkey,
+) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetRegistrationPolicy {
+            fee_lamports,
+            cooldown_seconds,
+            treasury,
+        },
+    )
+}
+
+pub fn build_set_max_buckets_per_payer_ix(admin: &Pubkey, max_buckets: u32) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetMaxBucketsPerPayer { max_buckets },
+    )
+}
+
+pub fn build_set_pause_grace_ix(admin: &Pubkey, grace_seconds: i64, budget: u64) -> Instruction {
+    build(
+        accounts::SetPauseGrace {
+            global_config: global_config(),
+            pause_grace: pda::pause_grace_address().0,
+            admin: *admin,
+            system_program: system_program::ID,
+        },
+        instruction::SetPauseGrace {
+            grace_seconds,
+            budget,
+        },
+    )
+}
+
+pub fn build_set_appeal_policy_ix(
+    admin: &Pubkey,
+    bond_lamports: u64,
+    cooldown_seconds: i64,
+) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetAppealPolicy {
+            bond_lamports,
+            cooldown_seconds,
+        },
+    )
+}
+
+pub fn build_set_violation_halflife_ix(admin: &Pubkey, halflife_seconds: u32) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetViolationHalflife { halflife_seconds },
+    )
+}
+
+pub fn build_set_min_client_balance_ix(admin: &Pubkey, lamports: u64) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetMinClientBalance { lamports },
+    )
+}
+
+pub fn build_set_inactivity_forgiveness_ix(admin: &Pubkey, seconds: i64) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetInactivityForgiveness { seconds },
+    )
+}
+
+pub fn build_set_recovery_inactivity_ix(admin: &Pubkey, seconds: i64) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetRecoveryInactivity { seconds },
+    )
+}
+
+// `mode` is one of the PRORATION_* constants
+pub fn build_set_proration_mode_ix(admin: &Pubkey, mode: u8) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetProrationMode { mode },
+    )
+}
+
+pub fn build_set_gc_policy_ix(
+    admin: &Pubkey,
+    stale_after_seconds: i64,
+    gc_bounty_bps: u16,
+) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetGcPolicy {
+            stale_after_seconds,
+            gc_bounty_bps,
+        },
+    )
+}
+
+pub fn build_set_discount_tiers_ix(admin: &Pubkey, tiers: Vec<DiscountTier>) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetDiscountTiers { tiers },
+    )
+}
+
+pub fn build_toggle_pause_ix(admin: &Pubkey) -> Instruction {
+    build(
+        accounts::TogglePause {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::TogglePause {},
+    )
+}
+
+pub fn build_pause_ix(admin: &Pubkey, reason: u8, auto_unpause_at: i64) -> Instruction {
+    build(
+        accounts::TogglePause {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::Pause {
+            reason,
+            auto_unpause_at,
+        },
+    )
+}
+
+pub fn build_set_schedule_ix(
+    admin: &Pubkey,
+    payer: &Pubkey,
+    entries: Vec<ScheduleEntry>,
+) -> Instruction {
+    build(
+        accounts::SetSchedule {
+            global_config: global_config(),
+            schedule: pda::schedule_address().0,
+            admin: *admin,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::SetSchedule { entries },
+    )
+}
+
+pub fn build_push_dynamic_limit_ix(
+    limit_feeder: &Pubkey,
+    new_max: u64,
+    valid_until: i64,
+) -> Instruction {
+    build(
+        accounts::PushDynamicLimit {
+            global_config: global_config(),
+            limit_feeder: *limit_feeder,
+        },
+        instruction::PushDynamicLimit {
+            new_max,
+            valid_until,
+        },
+    )
+}
+
+pub fn build_set_refund_authority_ix(
+    admin: &Pubkey,
+    authority: Pubkey,
+    max_per_window: u64,
+    lower_totals: bool,
+) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetRefundAuthority {
+            authority,
+            max_per_window,
+            lower_totals,
+        },
+    )
+}
+
+// `kind` is one of the AUTHORITY_* constants. The current key keeps signing until
+// `effective_at`, and the new one only from then on.
+pub fn build_set_authority_ix(
+    admin: &Pubkey,
+    kind: u8,
+    new_key: Pubkey,
+    effective_at: i64,
+) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::SetAuthority {
+            kind,
+            new_key,
+            effective_at,
+        },
+    )
+}
+
+pub fn build_cancel_authority_rotation_ix(admin: &Pubkey, kind: u8) -> Instruction {
+    build(
+        update_config_accounts(admin),
+        instruction::CancelAuthorityRotation { kind },
+    )
+}
+
+// Nonces must rise per bucket; a backend can use a counter or a timestamp
+pub fn build_refund_request_ix(
+    refund_authority: &Pubkey,
+    client: &Pubkey,
+    amount: u64,
+    attestation_nonce: u64,
+) -> Instruction {
+    build(
+        accounts::RefundRequest {
+            global_config: global_config(),
+            client_bucket: bucket(client),
+            refund_authority: *refund_authority,
+            client_wallet: *client,
+        },
+        instruction::RefundRequest {
+            amount,
+            attestation_nonce,
+        },
+    )
+}
+
+pub fn build_sweep_excess_lamports_ix(
+    admin: &Pubkey,
+    treasury: &Pubkey,
+    targets: &[Pubkey],
+) -> Instruction {
+    let mut ix = build(
+        accounts::SweepExcess {
+            global_config: global_config(),
+            treasury: *treasury,
+            admin: *admin,
+        },
+        instruction::SweepExcessLamports {},
+    );
+    ix.accounts.extend(
+        targets
+            .iter()
+            .map(|target| AccountMeta::new(*target, false)),
+    );
+    ix
+}
+
+pub fn build_set_circuit_breaker_ix(
+    admin: &Pubkey,
+    threshold: u64,
+    window_seconds: i64,
+) -> Instruction {
+    build(
+        accounts::ConfigureStats {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            admin: *admin,
+        },
+        instruction::SetCircuitBreaker {
+            threshold,
+            window_seconds,
+        },
+    )
+}
+
+pub fn build_set_rebalance_policy_ix(
+    admin: &Pubkey,
+    payer: &Pubkey,
+    params: RebalanceParams,
+) -> Instruction {
+    build(
+        accounts::SetRebalancePolicy {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            rebalance_policy: pda::rebalance_policy_address().0,
+            admin: *admin,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::SetRebalancePolicy { params },
+    )
+}
+
+// Permissionless; anyone may pay for the crank
+pub fn build_rebalance_limits_ix() -> Instruction {
+    build(
+        accounts::RebalanceLimits {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            rebalance_policy: pda::rebalance_policy_address().0,
+        },
+        instruction::RebalanceLimits {},
+    )
+}
+
+// Permissionless; `payer` covers the board's rent on the first submission
+pub fn build_submit_to_leaderboard_ix(owner: &Pubkey, payer: &Pubkey) -> Instruction {
+    build(
+        accounts::SubmitToLeaderboard {
+            leaderboard: pda::leaderboard_address().0,
+            client_bucket: bucket(owner),
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::SubmitToLeaderboard {},
+    )
+}
+
+// `authority` is the program's upgrade authority
+pub fn build_begin_admin_recovery_ix(authority: &Pubkey, new_admin: Pubkey) -> Instruction {
+    build(
+        accounts::BeginAdminRecovery {
+            global_config: global_config(),
+            authority: *authority,
+            program: crate::ID,
+            program_data: program_data_address(),
+        },
+        instruction::BeginAdminRecovery { new_admin },
+    )
+}
+
+// Also signed by the upgrade authority; zeros turn the throttle off
+pub fn build_set_admin_throttle_ix(
+    authority: &Pubkey,
+    window_seconds: i64,
+    max_actions: u32,
+) -> Instruction {
+    build(
+        accounts::BeginAdminRecovery {
+            global_config: global_config(),
+            authority: *authority,
+            program: crate::ID,
+            program_data: program_data_address(),
+        },
+        instruction::SetAdminThrottle {
+            window_seconds,
+            max_actions,
+        },
+    )
+}
+
+pub fn build_finalize_admin_recovery_ix() -> Instruction {
+    build(
+        accounts::FinalizeAdminRecovery {
+            global_config: global_config(),
+        },
+        instruction::FinalizeAdminRecovery {},
+    )
+}
+
+pub fn build_reset_client_ix(admin: &Pubkey, client: &Pubkey) -> Instruction {
+    build(
+        accounts::ResetClient {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(client),
+            admin: *admin,
+            client_wallet: *client,
+        },
+        instruction::ResetClient {},
+    )
+}
+
+// Appends the hook accounts when a hook is set, for hooks that read nothing else
+pub fn build_block_client_ix(
+    config: &GlobalConfig,
+    admin: &Pubkey,
+    client: &Pubkey,
+) -> Instruction {
+    let mut ix = build(
+        accounts::BlockClient {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(client),
+            admin: *admin,
+            client_wallet: *client,
+        },
+        instruction::BlockClient {},
+    );
+    ix.accounts.extend(hook_accounts(config, &[]));
+    ix
+}
+
+fn freeze_accounts(admin: &Pubkey, client: &Pubkey) -> accounts::FreezeClient {
+    accounts::FreezeClient {
+        global_config: global_config(),
+        client_bucket: bucket(client),
+        admin: *admin,
+        client_wallet: *client,
+    }
+}
+
+pub fn build_freeze_client_ix(admin: &Pubkey, client: &Pubkey) -> Instruction {
+    build(freeze_accounts(admin, client), instruction::FreezeClient {})
+}
+
+pub fn build_thaw_client_ix(admin: &Pubkey, client: &Pubkey) -> Instruction {
+    build(freeze_accounts(admin, client), instruction::ThawClient {})
+}
+
+pub fn build_set_balance_exempt_ix(admin: &Pubkey, client: &Pubkey, exempt: bool) -> Instruction {
+    build(
+        freeze_accounts(admin, client),
+        instruction::SetBalanceExempt { exempt },
+    )
+}
+
+fn admin_fast_accounts(admin: &Pubkey, client: &Pubkey) -> accounts::AdminFastClient {
+    accounts::AdminFastClient {
+        global_config: global_config(),
+        global_stats: global_stats(),
+        fast_bucket: pda::fast_bucket_address(client).0,
+        admin: *admin,
+        client_wallet: *client,
+    }
+}
+
+pub fn build_reset_fast_client_ix(admin: &Pubkey, client: &Pubkey) -> Instruction {
+    build(
+        admin_fast_accounts(admin, client),
+        instruction::ResetFastClient {},
+    )
+}
+
+pub fn build_block_fast_client_ix(admin: &Pubkey, client: &Pubkey) -> Instruction {
+    build(
+        admin_fast_accounts(admin, client),
+        instruction::BlockFastClient {},
+    )
+}
+
+pub fn build_adjust_client_usage_ix(
+    admin: &Pubkey,
+    client: &Pubkey,
+    total_requests: u64,
+) -> Instruction {
+    build(
+        accounts::AdjustClientUsage {
+            global_config: global_config(),
+            client_bucket: bucket(client),
+            admin: *admin,
+            client_wallet: *client,
+        },
+        instruction::AdjustClientUsage { total_requests },
+    )
+}
+
+pub fn build_set_client_note_ix(
+    admin: &Pubkey,
+    payer: &Pubkey,
+    client: &Pubkey,
+    note: String,
+) -> Instruction {
+    build(
+        accounts::SetClientNote {
+            global_config: global_config(),
+            client_note: pda::client_note_address(client).0,
+            admin: *admin,
+            payer: *payer,
+            client_wallet: *client,
+            system_program: system_program::ID,
+        },
+        instruction::SetClientNote { note },
+    )
+}
+
+pub fn build_clear_client_note_ix(admin: &Pubkey, client: &Pubkey) -> Instruction {
+    build(
+        accounts::ClearClientNote {
+            global_config: global_config(),
+            client_note: pda::client_note_address(client).0,
+            admin: *admin,
+        },
+        instruction::ClearClientNote {},
+    )
+}
+
+pub fn build_approve_appeal_ix(admin: &Pubkey, client: &Pubkey) -> Instruction {
+    build(
+        accounts::ApproveAppeal {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(client),
+            appeal: pda::appeal_address(client).0,
+            admin: *admin,
+            client_wallet: *client,
+        },
+        instruction::ApproveAppeal {},
+    )
+}
+
+pub fn build_deny_appeal_ix(config: &GlobalConfig, admin: &Pubkey, client: &Pubkey) -> Instruction {
+    build(
+        accounts::DenyAppeal {
+            global_config: global_config(),
+            appeal: pda::appeal_address(client).0,
+            treasury: config.treasury,
+            admin: *admin,
+            client_wallet: *client,
+        },
+        instruction::DenyAppeal {},
+    )
+}
+
+// Opens proposal number `config.proposal_count`, so build it from a fresh fetch
+pub fn build_propose_action_ix(
+    config: &GlobalConfig,
+    proposer: &Pubkey,
+    action: AdminAction,
+) -> Instruction {
+    build(
+        accounts::ProposeAction {
+            global_config: global_config(),
+            proposal: pda::proposal_address(config.proposal_count).0,
+            proposer: *proposer,
+            system_program: system_program::ID,
+        },
+        instruction::ProposeAction { action },
+    )
+}
+
+pub fn build_approve_action_ix(proposal_id: u64, member: &Pubkey) -> Instruction {
+    build(
+        accounts::ApproveAction {
+            global_config: global_config(),
+            proposal: pda::proposal_address(proposal_id).0,
+            member: *member,
+        },
+        instruction::ApproveAction {},
+    )
+}
+
+// Passes the bucket that block, unblock and freeze actions act on, and the
+// hook accounts a block reaches
+pub fn build_execute_action_ix(config: &GlobalConfig, proposal: &Proposal) -> Instruction {
+    let client = match proposal.action {
+        AdminAction::Block { client }
+        | AdminAction::Unblock { client }
+        | AdminAction::Freeze { client, .. } => Some(client),
+        _ => None,
+    };
+    let mut ix = build(
+        accounts::ExecuteAction {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            proposal: pda::proposal_address(proposal.id).0,
+            client_bucket: client.as_ref().map(bucket),
+        },
+        instruction::ExecuteAction {},
+    );
+    if matches!(proposal.action, AdminAction::Block { .. }) {
+        ix.accounts.extend(hook_accounts(config, &[]));
+    }
+    ix
+}
+
+pub fn build_migrate_account_ix(account: &Pubkey, payer: &Pubkey) -> Instruction {
+    build(
+        accounts::MigrateAccount {
+            account: *account,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateAccount {},
+    )
+}
+
+pub fn build_top_up_rent_ix(account: &Pubkey, payer: &Pubkey) -> Instruction {
+    build(
+        accounts::TopUpRent {
+            account: *account,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::TopUpRent {},
+    )
+}
+
+// The bucket of `bucket_owner`, if given, rides along as the snapshot's sampled client
+pub fn build_emit_stats_snapshot_ix(bucket_owner: Option<&Pubkey>) -> Instruction {
+    let mut ix = build(
+        accounts::EmitStatsSnapshot {
+            global_stats: global_stats(),
+        },
+        instruction::EmitStatsSnapshot {},
+    );
+    ix.accounts
+        .extend(bucket_owner.map(|owner| AccountMeta::new_readonly(bucket(owner), false)));
+    ix
+}
+
+pub fn build_verify_consumed_in_tx_ix(client: Pubkey) -> Instruction {
+    build(
+        accounts::VerifyConsumedInTx {
+            instructions: sysvar::instructions::ID,
+        },
+        instruction::VerifyConsumedInTx { client },
+    )
+}
+
+pub fn build_verify_bucket_ix(owner: &Pubkey) -> Instruction {
+    build(
+        accounts::VerifyBucket {
+            global_config: global_config(),
+            client_bucket: bucket(owner),
+        },
+        instruction::VerifyBucket {},
+    )
+}
+
+pub fn build_estimate_config_impact_ix(
+    owners: &[Pubkey],
+    new_config: RateLimiterConfig,
+) -> Instruction {
+    let mut ix = build(
+        accounts::EstimateConfigImpact {
+            global_config: global_config(),
+        },
+        instruction::EstimateConfigImpact { new_config },
+    );
+    ix.accounts.extend(
+        owners
+            .iter()
+            .map(|owner| AccountMeta::new_readonly(bucket(owner), false)),
+    );
+    ix
+}
+
+pub fn build_get_quota_ix(config: &GlobalConfig, owner: &Pubkey) -> Instruction {
+    build(
+        accounts::GetQuota {
+            global_config: global_config(),
+            client_bucket: bucket(owner),
+            schedule: schedule(config),
+        },
+        instruction::GetQuota {},
+    )
+}
+
+// Simulate it to read the EffectiveConfig from return data. An owner adds its
+// bucket's block and freeze to the answer.
+pub fn build_get_config_ix(config: &GlobalConfig, owner: Option<&Pubkey>) -> Instruction {
+    build(
+        accounts::GetConfig {
+            global_config: global_config(),
+            schedule: schedule(config),
+            client_bucket: owner.map(bucket),
+        },
+        instruction::GetConfig {},
+    )
+}
+
+// Send it to record the export for import_bucket_snapshot; the BucketSnapshot
+// comes back in return data. `payer` funds the record the first time.
+pub fn build_export_bucket_snapshot_ix(owner: &Pubkey, payer: &Pubkey) -> Instruction {
+    build(
+        accounts::ExportBucketSnapshot {
+            global_config: global_config(),
+            client_bucket: bucket(owner),
+            snapshot_record: pda::snapshot_record_address(owner).0,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::ExportBucketSnapshot {},
+    )
+}
+
+// Simulate it to read the UsageHistogram from return data
+pub fn build_get_usage_histogram_ix() -> Instruction {
+    build(
+        accounts::GetStats {
+            global_stats: global_stats(),
+        },
+        instruction::GetUsageHistogram {},
+    )
+}
+
+// Simulate it to read the DeploymentInfo from return data, then hand that to
+// check_compatibility
+pub fn build_get_deployment_info_ix() -> Instruction {
+    build(
+        accounts::GetDeploymentInfo {
+            global_config: global_config(),
+        },
+        instruction::GetDeploymentInfo {},
+    )
+}
+
+// Simulate it to read the RegistrationPreview from return data. Neither `client`
+// nor `payer` signs, so a wallet can check the terms before holding any SOL.
+pub fn build_preview_registration_ix(
+    config: &GlobalConfig,
+    client: &Pubkey,
+    payer: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::PreviewRegistration {
+            global_config: global_config(),
+            schedule: schedule(config),
+            client: *client,
+            client_bucket: bucket(client),
+            fast_bucket: pda::fast_bucket_address(client).0,
+            shard_parent: pda::shard_parent_address(client).0,
+            payer: *payer,
+            payer_meta: pda::payer_meta_address(payer).0,
+        },
+        instruction::PreviewRegistration {},
+    )
+}
+
+// For a deployment built with snapshot-import. `snapshot` comes from the old
+// deployment's export and its owner signs along with the admin.
+#[cfg(feature = "snapshot-import")]
+pub fn build_import_bucket_snapshot_ix(
+    admin: &Pubkey,
+    payer: &Pubkey,
+    snapshot: BucketSnapshot,
+) -> Instruction {
+    let client = snapshot.owner;
+    build(
+        accounts::ImportBucketSnapshot {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(&client),
+            fast_bucket: pda::fast_bucket_address(&client).0,
+            shard_parent: pda::shard_parent_address(&client).0,
+            snapshot_record: pda::snapshot_record_address_in(&SNAPSHOT_SOURCE_PROGRAM_ID, &client).0,
+            client,
+            admin: *admin,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::ImportBucketSnapshot { snapshot },
+    )
+}
+
+// Checks the discriminator and decodes the current layout. An account still on
+// an older version fails to decode, the same as it would on-chain; the
+// transaction that touches it next migrates it, or migrate_account does.
+macro_rules! impl_try_from_account_data {
+    ($($account:ty),* $(,)?) => {
+        $(
+            impl $account {
+                pub fn try_from_account_data(data: &[u8]) -> Result<Self> {
+                    let mut data = data;
+                    <Self as AccountDeserialize>::try_deserialize(&mut data)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_account_data!(
+    GlobalConfig,
+    GlobalStats,
+    ClientBucket,
+    ShardParent,
+    ProgramBucket,
+    CallerAllowlist,
+    Proposal,
+    Schedule,
+    PayerMeta,
+    ClientNote,
+    Appeal,
+    Offer,
+    SponsorPool,
+);
+
+// Zero-copy, so it is read rather than Borsh-decoded
+impl FastBucket {
+    pub fn try_from_account_data(data: &[u8]) -> Result<Self> {
+        require!(
+            data.len() >= FastBucket::LEN,
+            RateLimiterError::InvalidAccountLayout
+        );
+        require!(
+            data.starts_with(FastBucket::DISCRIMINATOR),
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        Ok(anchor_lang::__private::bytemuck::pod_read_unaligned(
+            &data[8..FastBucket::LEN],
+        ))
+    }
+}
+
+// Why check_compatibility turned a deployment down
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Incompatibility {
+    // Built from an older crate than the client needs
+    Version {
+        deployed: [u16; 3],
+        required: [u16; 3],
+    },
+    // FEATURE_* bits the client needs that the config has off
+    MissingFeatures(u64),
+    // A limit algorithm this crate does not know, from a newer deployment
+    UnknownAlgorithm(u8),
+}
+
+impl std::fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Incompatibility::Version { deployed, required } => write!(
+                f,
+                "program version {}.{}.{} is older than the {}.{}.{} this client needs",
+                deployed[0], deployed[1], deployed[2], required[0], required[1], required[2]
+            ),
+            Incompatibility::MissingFeatures(bits) => {
+                write!(f, "features {:#x} are off on this deployment", bits)
+            }
+            Incompatibility::UnknownAlgorithm(algorithm) => {
+                write!(f, "unknown limit algorithm {}", algorithm)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Incompatibility {}
+
+impl DeploymentInfo {
+    // For a client at startup, with the DeploymentInfo a simulated
+    // get_deployment_info returned: whether the deployment is at least
+    // `min_version` and has every FEATURE_* bit in `required_features` on.
+    // Versions compare major, then minor, then patch.
+    pub fn check_compatibility(
+        &self,
+        min_version: [u16; 3],
+        required_features: u64,
+    ) -> std::result::Result<(), Incompatibility> {
+        if self.program_version < min_version {
+            return Err(Incompatibility::Version {
+                deployed: self.program_version,
+                required: min_version,
+            });
+        }
+        if self.limit_algorithm != LIMIT_ALGORITHM_FIXED_WINDOW {
+            return Err(Incompatibility::UnknownAlgorithm(self.limit_algorithm));
+        }
+        let missing = required_features & !self.features_bitmask;
+        if missing != 0 {
+            return Err(Incompatibility::MissingFeatures(missing));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{AUTHORITY_KINDS, GLOBAL_CONFIG_VERSION, MAX_DISCOUNT_TIERS};
+    use crate::state::{PendingAuthority, PreviousConfig};
+
+    // program-test is not a dependency here (the TS suite drives the deployed
+    // program), so these pin the builders against the program's own context
+    // structs and instruction encodings instead
+
+    pub(super) fn config() -> GlobalConfig {
+        GlobalConfig {
+            version: GLOBAL_CONFIG_VERSION,
+            admin: Pubkey::new_unique(),
+            max_requests: 10,
+            window_seconds: 60,
+            burst_limit: 20,
+            flags: 0,
+            config_epoch: 0,
+            bump: 0,
+            hook_program: Pubkey::default(),
+            limit_feeder: Pubkey::default(),
+            dynamic_floor: 0,
+            dynamic_ceiling: 0,
+            dynamic_max: 0,
+            dynamic_valid_until: 0,
+            council: [Pubkey::default(); 5],
+            council_size: 0,
+            council_threshold: 0,
+            proposal_count: 0,
+            recovery_admin: Pubkey::default(),
+            recovery_eta: 0,
+            scheduled_pause_start: 0,
+            scheduled_pause_end: 0,
+            treasury: Pubkey::default(),
+            registration_fee_lamports: 0,
+            registration_cooldown_seconds: 0,
+            stale_after_seconds: 0,
+            gc_bounty_bps: 0,
+            discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+            discount_tier_count: 0,
+            appeal_bond_lamports: 0,
+            appeal_cooldown_seconds: 0,
+            paused_at: 0,
+            pause_reason: 0,
+            auto_unpause_at: 0,
+            config_preset: 0,
+            violation_halflife_seconds: 0,
+            admin_action_window_seconds: 0,
+            max_admin_actions_per_window: 0,
+            admin_action_window_start: 0,
+            admin_actions_in_window: 0,
+            refund_authority: Pubkey::default(),
+            max_refunds_per_window: 0,
+            max_buckets_per_payer: 0,
+            pause_grace_seconds: 0,
+            pause_grace_budget: 0,
+            grace_budget: 0,
+            inactivity_forgiveness_seconds: 0,
+            min_client_balance_lamports: 0,
+            recovery_inactive_seconds: 0,
+            proration_mode: 0,
+            previous_config: PreviousConfig::default(),
+            denied_owner_programs: 0,
+            pending_authorities: [PendingAuthority::default(); AUTHORITY_KINDS],
+            reserved: [0; 1],
+        }
+    }
+
+    #[test]
+    fn initialize_lists_accounts_in_context_order() {
+        let admin = Pubkey::new_unique();
+        let limits = RateLimiterConfig {
+            max_requests: 5,
+            window_seconds: 60,
+            burst_limit: 7,
+        };
+        let ix = build_initialize_ix(&admin, limits.clone());
+        assert_eq!(ix.program_id, crate::ID);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                AccountMeta::new(pda::global_config_address().0, false),
+                AccountMeta::new(admin, true),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(program_data_address(), false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ]
+        );
+        assert!(ix.data.starts_with(instruction::Initialize::DISCRIMINATOR));
+        let decoded = RateLimiterConfig::try_from_slice(&ix.data[8..]).unwrap();
+        assert_eq!(decoded.max_requests, limits.max_requests);
+        assert_eq!(decoded.burst_limit, limits.burst_limit);
+    }
+
+    #[test]
+    fn consume_includes_the_schedule_only_while_one_is_set() {
+        let client = Pubkey::new_unique();
+        let mut config = config();
+        let ix = build_consume_ix(&config, &client);
+        assert_eq!(ix.accounts.len(), 9);
+        assert_eq!(
+            ix.accounts[2],
+            AccountMeta::new(pda::client_bucket_address(&client).0, false)
+        );
+        assert_eq!(ix.accounts[3], AccountMeta::new_readonly(client, true));
+        // Unset optional accounts are the program id, as Anchor expects
+        assert!(ix.accounts[4..].iter().all(|meta| meta.pubkey == crate::ID));
+
+        config.set_scheduled(true);
+        let ix = build_consume_ix(&config, &client);
+        assert_eq!(ix.accounts[6].pubkey, pda::schedule_address().0);
+    }
+
+    #[test]
+    fn consume_includes_the_pause_grace_only_while_a_pause_has_one() {
+        let client = Pubkey::new_unique();
+        let mut config = config();
+        config.set_paused(true);
+        assert_eq!(build_consume_ix(&config, &client).accounts[8].pubkey, crate::ID);
+
+        config.pause_grace_seconds = 30;
+        let ix = build_consume_ix(&config, &client);
+        assert_eq!(ix.accounts[8], AccountMeta::new(pda::pause_grace_address().0, false));
+        config.set_paused(false);
+        assert_eq!(build_consume_ix(&config, &client).accounts[8].pubkey, crate::ID);
+    }
+
+    #[test]
+    fn consumes_pass_the_stats_only_while_the_breaker_is_on() {
+        let client = Pubkey::new_unique();
+        let mut config = config();
+        assert_eq!(build_consume_ix(&config, &client).accounts[1].pubkey, crate::ID);
+
+        config.set_circuit_breaker_on(true);
+        let stats = AccountMeta::new(pda::global_stats_address().0, false);
+        assert_eq!(build_consume_ix(&config, &client).accounts[1], stats);
+        assert_eq!(build_try_consume_ix(&config, &client).accounts[1], stats);
+        // Sharded consumes only read it
+        let sharded = build_consume_sharded_ix(&config, &client, 0);
+        assert_eq!(sharded.accounts[5], AccountMeta::new_readonly(stats.pubkey, false));
+    }
+
+    #[test]
+    fn registration_passes_the_treasury_only_while_a_fee_is_charged() {
+        let (client, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut config = config();
+        assert_eq!(
+            build_register_client_ix(&config, &client, &payer).accounts[6].pubkey,
+            crate::ID
+        );
+
+        config.registration_fee_lamports = 1_000;
+        config.treasury = Pubkey::new_unique();
+        let ix = build_register_client_ix(&config, &client, &payer);
+        assert_eq!(ix.accounts[6], AccountMeta::new(config.treasury, false));
+        assert_eq!(ix.accounts[5].pubkey, pda::payer_meta_address(&payer).0);
+    }
+
+    #[test]
+    fn previews_read_what_registration_would_without_signing() {
+        let (client, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = build_preview_registration_ix(&config(), &client, &payer);
+        assert!(ix
+            .accounts
+            .iter()
+            .all(|meta| !meta.is_signer && !meta.is_writable));
+        assert_eq!(ix.accounts[3].pubkey, pda::client_bucket_address(&client).0);
+        assert_eq!(ix.accounts[7].pubkey, pda::payer_meta_address(&payer).0);
+    }
+
+    #[test]
+    fn sharded_registration_appends_every_shard() {
+        let (client, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = build_register_sharded_ix(&config(), &client, &payer, 3);
+        let shards = &ix.accounts[ix.accounts.len() - 3..];
+        for (index, meta) in shards.iter().enumerate() {
+            assert_eq!(
+                *meta,
+                AccountMeta::new(pda::client_shard_address(&client, index as u8).0, false)
+            );
+        }
+    }
+
+    #[test]
+    fn consume_v2_round_trips_its_args() {
+        let client = Pubkey::new_unique();
+        let args = ConsumeArgs {
+            amount: 3,
+            deadline: Some(100),
+            nonce: Some(7),
+            priority: false,
+        };
+        let ix = build_consume_v2_ix(&config(), &client, args.clone());
+        assert!(ix.data.starts_with(instruction::ConsumeV2::DISCRIMINATOR));
+        assert_eq!(ConsumeArgs::try_from_slice(&ix.data[8..]).unwrap(), args);
+    }
+
+    #[test]
+    fn decoding_checks_the_discriminator() {
+        let config = config();
+        let mut data = Vec::new();
+        config.try_serialize(&mut data).unwrap();
+        let decoded = GlobalConfig::try_from_account_data(&data).unwrap();
+        assert_eq!(decoded.admin, config.admin);
+        assert_eq!(decoded.max_requests, config.max_requests);
+
+        assert!(ClientBucket::try_from_account_data(&data).is_err());
+        assert!(GlobalConfig::try_from_account_data(&data[..8]).is_err());
+    }
+
+    #[test]
+    fn fast_buckets_decode_in_place() {
+        let owner = Pubkey::new_unique();
+        let mut data = FastBucket::DISCRIMINATOR.to_vec();
+        let bucket = FastBucket {
+            owner,
+            request_count: 4,
+            window_start: 1_000,
+            total_requests: 9,
+            violations: 1,
+            config_epoch: 2,
+            burst_credits: 3,
+            total_saturated: 0,
+            is_blocked: 1,
+            bump: 254,
+            _padding: [0; 5],
+        };
+        data.extend_from_slice(anchor_lang::__private::bytemuck::bytes_of(&bucket));
+        let decoded = FastBucket::try_from_account_data(&data).unwrap();
+        assert_eq!(decoded.owner, owner);
+        assert_eq!(decoded.request_count, 4);
+        assert_eq!(decoded.is_blocked, 1);
+
+        data[0] ^= 0xff;
+        assert!(FastBucket::try_from_account_data(&data).is_err());
+        assert!(FastBucket::try_from_account_data(&data[..16]).is_err());
+    }
+
+    #[test]
+    fn compatibility_needs_the_version_and_every_required_feature() {
+        use crate::constants::{FEATURE_HOOK, FEATURE_REGISTRATION_FEE, FEATURE_SCHEDULE};
+
+        let info = DeploymentInfo {
+            program_version: [1, 4, 2],
+            features_bitmask: FEATURE_REGISTRATION_FEE | FEATURE_SCHEDULE,
+            limit_algorithm: LIMIT_ALGORITHM_FIXED_WINDOW,
+            config_epoch: 3,
+        };
+        assert_eq!(
+            info.check_compatibility([1, 4, 2], FEATURE_SCHEDULE),
+            Ok(())
+        );
+        assert_eq!(info.check_compatibility([0, 9, 9], 0), Ok(()));
+        // Minor outranks patch
+        assert_eq!(
+            info.check_compatibility([1, 3, 9], FEATURE_REGISTRATION_FEE),
+            Ok(())
+        );
+        assert_eq!(
+            info.check_compatibility([1, 5, 0], 0),
+            Err(Incompatibility::Version {
+                deployed: [1, 4, 2],
+                required: [1, 5, 0],
+            })
+        );
+        assert_eq!(
+            info.check_compatibility([1, 0, 0], FEATURE_SCHEDULE | FEATURE_HOOK),
+            Err(Incompatibility::MissingFeatures(FEATURE_HOOK))
+        );
+        let newer = DeploymentInfo {
+            limit_algorithm: 7,
+            ..info
+        };
+        assert_eq!(
+            newer.check_compatibility([1, 0, 0], 0),
+            Err(Incompatibility::UnknownAlgorithm(7))
+        );
+
+        let ix = build_get_deployment_info_ix();
+        assert_eq!(
+            ix.accounts,
+            vec![AccountMeta::new_readonly(global_config(), false)]
+        );
+        assert!(ix
+            .data
+            .starts_with(instruction::GetDeploymentInfo::DISCRIMINATOR));
+    }
+}
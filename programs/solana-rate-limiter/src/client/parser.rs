@@ -0,0 +1,529 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::transaction::TransactionError;
+
+use crate::errors::RateLimiterError;
+use crate::events::*;
+
+// Typed events out of a transaction's log messages, for indexers. Reads the
+// `Program data:` lines emit! writes, and the msg! and sol_log_64 lines that
+// carry consumes, blocks, resets and config updates without an event. Only lines
+// logged while this program is the one executing count: the invoke/success
+// lines around each call are tracked as a stack, so another program's lines, a
+// CPI caller's included, are skipped even when they look like ours. Lines that
+// do not decode are skipped rather than failing the transaction's other events,
+// and parsing stops at the runtime's "Log truncated" marker. The same logs also
+// say which of the limiter's errors a failed transaction hit.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RateLimiterEvent {
+    // From log lines. They name no account except where shown.
+    Consumed {
+        used: u64,
+        max_requests: u64,
+        window_ends_in: i64,
+    },
+    Blocked {
+        client: Pubkey,
+        fast: bool,
+    },
+    Reset {
+        client: Pubkey,
+        fast: bool,
+    },
+    ConfigUpdated {
+        max_requests: u64,
+        window_seconds: i64,
+    },
+
+    // From emit!
+    Rejected(ConsumeRejected),
+    WindowClosed(WindowClosed),
+    StatsSnapshot(StatsSnapshot),
+    DynamicLimitSet(DynamicLimitSet),
+    AdminRecoveryStarted(AdminRecoveryStarted),
+    AdminRecoveryCancelled(AdminRecoveryCancelled),
+    AdminRecoveryFinalized(AdminRecoveryFinalized),
+    BucketTransferred(BucketTransferred),
+    BucketCollected(BucketCollected),
+    AppealFiled(AppealFiled),
+    AppealResolved(AppealResolved),
+    PauseChanged(PauseChanged),
+    QuotaOffered(QuotaOffered),
+    OfferAccepted(OfferAccepted),
+    OfferCancelled(OfferCancelled),
+    PriorityConsumed(PriorityConsumed),
+    ExcessSwept(ExcessSwept),
+    CircuitTripped(CircuitTripped),
+    SponsorPoolFunded(SponsorPoolFunded),
+    ClientSponsored(ClientSponsored),
+    SponsorPoolWithdrawn(SponsorPoolWithdrawn),
+    RentToppedUp(RentToppedUp),
+    LimitsRebalanced(LimitsRebalanced),
+    BucketSnapshotExported(BucketSnapshotExported),
+    RequestRefunded(RequestRefunded),
+    GracePeriodConsume(GracePeriodConsume),
+    PenaltiesForgiven(PenaltiesForgiven),
+    LeaderboardUpdated(LeaderboardUpdated),
+    BucketRecovered(BucketRecovered),
+    AuthorityRotationScheduled(AuthorityRotationScheduled),
+    AuthorityRotationCancelled(AuthorityRotationCancelled),
+    BucketConsumed(BucketConsumed),
+    BucketBlocked(BucketBlocked),
+    EventTagSet(EventTagSet),
+}
+
+pub fn parse_events(logs: &[String]) -> Vec<RateLimiterEvent> {
+    let program = crate::ID.to_string();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+    for line in logs {
+        if line == "Log truncated" {
+            break;
+        }
+        if let Some(invoked) = invoke_line(line) {
+            stack.push(invoked);
+            continue;
+        }
+        if let Some(returned) = return_line(line) {
+            // Unwinds past frames whose closing line is missing
+            if let Some(depth) = stack.iter().rposition(|id| *id == returned) {
+                stack.truncate(depth);
+            }
+            continue;
+        }
+        if stack.last() != Some(&program.as_str()) {
+            continue;
+        }
+        let event = match (
+            line.strip_prefix("Program data: "),
+            line.strip_prefix("Program log: "),
+        ) {
+            (Some(data), _) => decode_data(data),
+            (_, Some(message)) => parse_message(message),
+            _ => None,
+        };
+        events.extend(event);
+    }
+    events
+}
+
+fn invoke_line(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("Program ")?;
+    let (id, depth) = rest.split_once(" invoke [")?;
+    depth.ends_with(']').then_some(id)
+}
+
+fn return_line(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("Program ")?;
+    let (id, outcome) = rest.split_once(' ')?;
+    (outcome == "success" || outcome.starts_with("failed")).then_some(id)
+}
+
+macro_rules! decode_emitted {
+    ($bytes:expr, $($variant:ident => $event:ty),* $(,)?) => {{
+        let bytes: &[u8] = $bytes;
+        $(
+            if let Some(body) = bytes.strip_prefix(<$event as Discriminator>::DISCRIMINATOR) {
+                return <$event>::try_from_slice(body).ok().map(RateLimiterEvent::$variant);
+            }
+        )*
+        None
+    }};
+}
+
+// emit! logs one base64 field: the event's discriminator, then its Borsh body
+fn decode_data(data: &str) -> Option<RateLimiterEvent> {
+    let bytes = STANDARD.decode(data.trim()).ok()?;
+    decode_emitted!(
+        &bytes,
+        Rejected => ConsumeRejected,
+        WindowClosed => WindowClosed,
+        StatsSnapshot => StatsSnapshot,
+        DynamicLimitSet => DynamicLimitSet,
+        AdminRecoveryStarted => AdminRecoveryStarted,
+        AdminRecoveryCancelled => AdminRecoveryCancelled,
+        AdminRecoveryFinalized => AdminRecoveryFinalized,
+        BucketTransferred => BucketTransferred,
+        BucketCollected => BucketCollected,
+        AppealFiled => AppealFiled,
+        AppealResolved => AppealResolved,
+        PauseChanged => PauseChanged,
+        QuotaOffered => QuotaOffered,
+        OfferAccepted => OfferAccepted,
+        OfferCancelled => OfferCancelled,
+        PriorityConsumed => PriorityConsumed,
+        ExcessSwept => ExcessSwept,
+        CircuitTripped => CircuitTripped,
+        SponsorPoolFunded => SponsorPoolFunded,
+        ClientSponsored => ClientSponsored,
+        SponsorPoolWithdrawn => SponsorPoolWithdrawn,
+        RentToppedUp => RentToppedUp,
+        LimitsRebalanced => LimitsRebalanced,
+        BucketSnapshotExported => BucketSnapshotExported,
+        RequestRefunded => RequestRefunded,
+        GracePeriodConsume => GracePeriodConsume,
+        PenaltiesForgiven => PenaltiesForgiven,
+        LeaderboardUpdated => LeaderboardUpdated,
+        BucketRecovered => BucketRecovered,
+        AuthorityRotationScheduled => AuthorityRotationScheduled,
+        AuthorityRotationCancelled => AuthorityRotationCancelled,
+        BucketConsumed => BucketConsumed,
+        BucketBlocked => BucketBlocked,
+        EventTagSet => EventTagSet,
+    )
+}
+
+// The msg! formats in lib.rs, and the sol_log_64 line default builds log on
+// every accepted consume in place of the verbose one
+fn parse_message(message: &str) -> Option<RateLimiterEvent> {
+    let key = |text: &str| text.trim().parse::<Pubkey>().ok();
+    if let Some(client) = message.strip_prefix("Client blocked: ") {
+        return Some(RateLimiterEvent::Blocked {
+            client: key(client)?,
+            fast: false,
+        });
+    }
+    if let Some(client) = message.strip_prefix("Fast client blocked: ") {
+        return Some(RateLimiterEvent::Blocked {
+            client: key(client)?,
+            fast: true,
+        });
+    }
+    if let Some(client) = message.strip_prefix("Client bucket reset by admin: ") {
+        return Some(RateLimiterEvent::Reset {
+            client: key(client)?,
+            fast: false,
+        });
+    }
+    if let Some(client) = message.strip_prefix("Fast client bucket reset by admin: ") {
+        return Some(RateLimiterEvent::Reset {
+            client: key(client)?,
+            fast: true,
+        });
+    }
+    if let Some(limits) = message.strip_prefix("Config updated. Max: ") {
+        let (max, window) = limits.strip_suffix('s')?.split_once(" req / ")?;
+        return Some(RateLimiterEvent::ConfigUpdated {
+            max_requests: max.parse().ok()?,
+            window_seconds: window.parse().ok()?,
+        });
+    }
+    if let Some(usage) = message.strip_prefix("Request consumed. Used: ") {
+        let (counts, ends_in) = usage.strip_suffix('s')?.split_once(" | Window ends in: ")?;
+        let (used, max) = counts.split_once('/')?;
+        return Some(RateLimiterEvent::Consumed {
+            used: used.parse().ok()?,
+            max_requests: max.parse().ok()?,
+            window_ends_in: ends_in.parse().ok()?,
+        });
+    }
+    parse_log_64(message)
+}
+
+// "0x3, 0xa, 0x3c, 0x0, 0x0": used, max, seconds left, then two unused zeros
+fn parse_log_64(message: &str) -> Option<RateLimiterEvent> {
+    let mut values = message
+        .split(", ")
+        .map(|field| u64::from_str_radix(field.strip_prefix("0x")?, 16).ok());
+    let mut next = || values.next().flatten();
+    let (used, max_requests, ends_in, a, b) = (next()?, next()?, next()?, next()?, next()?);
+    if a != 0 || b != 0 || values.next().is_some() {
+        return None;
+    }
+    Some(RateLimiterEvent::Consumed {
+        used,
+        max_requests,
+        window_ends_in: ends_in as i64,
+    })
+}
+
+impl RateLimiterError {
+    // The limiter's error a confirmed or simulated transaction failed with.
+    // Framework codes, and codes from programs without Anchor's offset, are None.
+    pub fn from_transaction_error(err: &TransactionError) -> Option<Self> {
+        match err {
+            TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+                Self::from_code(*code)
+            }
+            _ => None,
+        }
+    }
+
+    // The same from log messages alone, as a simulation or logsSubscribe gives
+    // them. Takes the runtime's "failed: custom program error: 0x.." line for
+    // the limiter, or failing that the AnchorError line it logged first, which
+    // survives a truncated log. A CPI caller failing with the code it got back
+    // is not taken for the limiter.
+    pub fn from_logs(logs: &[String]) -> Option<Self> {
+        let program = crate::ID.to_string();
+        let failed = format!("Program {} failed: custom program error: ", program);
+        let mut stack: Vec<&str> = Vec::new();
+        for line in logs {
+            if let Some(hex) = line.strip_prefix(&failed) {
+                let code = u32::from_str_radix(hex.trim_start_matches("0x"), 16).ok()?;
+                return Self::from_code(code);
+            }
+            if let Some(invoked) = invoke_line(line) {
+                stack.push(invoked);
+                continue;
+            }
+            if let Some(returned) = return_line(line) {
+                if let Some(depth) = stack.iter().rposition(|id| *id == returned) {
+                    stack.truncate(depth);
+                }
+                continue;
+            }
+            if stack.last() != Some(&program.as_str()) {
+                continue;
+            }
+            let code = line
+                .strip_prefix("Program log: AnchorError")
+                .and_then(|rest| rest.split_once("Error Number: "))
+                .and_then(|(_, rest)| rest.split('.').next()?.parse().ok());
+            if let Some(code) = code {
+                return Self::from_code(code);
+            }
+        }
+        None
+    }
+}
+
+// Off-chain only, where it can sit in an anyhow or Box<dyn Error> chain
+impl std::error::Error for RateLimiterError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fixtures are laid out as the runtime logs them, with the limiter at its
+    // declared id next to gated-counter and block-hook
+    fn fixture(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    fn owner() -> Pubkey {
+        Pubkey::new_from_array(std::array::from_fn(|i| i as u8 + 1))
+    }
+
+    fn rejected() -> ConsumeRejected {
+        ConsumeRejected {
+            owner: owner(),
+            reason_code: 1,
+            request_count: 10,
+            violations: 3,
+            label: "indexer-eu".to_string(),
+            event_tag: [0; 16],
+        }
+    }
+
+    #[test]
+    fn keeps_only_the_limiter_s_lines_inside_a_cpi() {
+        let logs = fixture(include_str!("../../test-data/logs/gated_counter_cpi.log"));
+        // gated-counter's own data and log lines, before and after the CPI, are
+        // left out however much they look like ours
+        assert_eq!(
+            parse_events(&logs),
+            vec![
+                RateLimiterEvent::WindowClosed(WindowClosed {
+                    owner: owner(),
+                    closed_window_start: 1_000,
+                    closed_count: 10,
+                    new_window_start: 1_120,
+                    skipped_windows: 2,
+                    label: String::new(),
+                    event_tag: [0; 16],
+                }),
+                RateLimiterEvent::Consumed {
+                    used: 1,
+                    max_requests: 10,
+                    window_ends_in: 120
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_events_from_failed_calls_and_stops_at_truncation() {
+        let logs = fixture(include_str!("../../test-data/logs/rejected_truncated.log"));
+        // The second call's data line was cut short and does not decode
+        assert_eq!(
+            parse_events(&logs),
+            vec![RateLimiterEvent::Rejected(rejected())]
+        );
+    }
+
+    #[test]
+    fn reads_the_msg_formats() {
+        let logs = fixture(include_str!("../../test-data/logs/admin_legacy.log"));
+        assert_eq!(
+            parse_events(&logs),
+            vec![
+                RateLimiterEvent::ConfigUpdated {
+                    max_requests: 25,
+                    window_seconds: 60
+                },
+                RateLimiterEvent::Blocked {
+                    client: owner(),
+                    fast: false
+                },
+                RateLimiterEvent::Reset {
+                    client: owner(),
+                    fast: true
+                },
+                RateLimiterEvent::Consumed {
+                    used: 3,
+                    max_requests: 25,
+                    window_ends_in: 41
+                },
+            ]
+        );
+    }
+
+    // What emit! writes, built with the event's own serializer
+    #[test]
+    fn emitted_events_round_trip() {
+        let event = PauseChanged {
+            paused: true,
+            paused_at: 1_700_000_000,
+            reason: 2,
+        };
+        let logs = vec![
+            format!("Program {} invoke [1]", crate::ID),
+            format!(
+                "Program data: {}",
+                STANDARD.encode(anchor_lang::Event::data(&event))
+            ),
+            format!(
+                "Program data: {}",
+                STANDARD.encode(anchor_lang::Event::data(&rejected()))
+            ),
+            format!("Program {} success", crate::ID),
+        ];
+        assert_eq!(
+            parse_events(&logs),
+            vec![
+                RateLimiterEvent::PauseChanged(event),
+                RateLimiterEvent::Rejected(rejected())
+            ]
+        );
+    }
+
+    #[test]
+    fn tagged_events_keep_their_tag() {
+        let tag = *b"tenant-0042\0\0\0\0\0";
+        let consumed = BucketConsumed {
+            owner: owner(),
+            amount: 2,
+            request_count: 5,
+            event_tag: tag,
+        };
+        let rejection = ConsumeRejected {
+            event_tag: tag,
+            ..rejected()
+        };
+        let logs = vec![
+            format!("Program {} invoke [1]", crate::ID),
+            format!(
+                "Program data: {}",
+                STANDARD.encode(anchor_lang::Event::data(&consumed))
+            ),
+            format!(
+                "Program data: {}",
+                STANDARD.encode(anchor_lang::Event::data(&rejection))
+            ),
+            format!("Program {} success", crate::ID),
+        ];
+        assert_eq!(
+            parse_events(&logs),
+            vec![
+                RateLimiterEvent::BucketConsumed(consumed),
+                RateLimiterEvent::Rejected(rejection)
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_lines_outside_any_call() {
+        let logs =
+            fixture("Program log: Client blocked: 4wBqpZM9xaSheZzJSMawUKKwhdpChKbZ5eu5ky4Vigw");
+        assert!(parse_events(&logs).is_empty());
+        assert_eq!(parse_log_64("0x1, 0x2, 0x3, 0x4, 0x0"), None);
+        assert_eq!(parse_log_64("0x1, 0x2"), None);
+    }
+
+    fn custom(code: u32) -> TransactionError {
+        TransactionError::InstructionError(1, InstructionError::Custom(code))
+    }
+
+    #[test]
+    fn transaction_errors_map_to_the_limiter_s_variants() {
+        for err in RateLimiterError::ALL {
+            let found = RateLimiterError::from_transaction_error(&custom(u32::from(err)));
+            assert_eq!(found.map(u32::from), Some(u32::from(err)));
+        }
+        let not_ours = [
+            custom(ErrorCode::AccountNotInitialized as u32),
+            custom(ErrorCode::ConstraintSeeds as u32),
+            // The system program's "already in use"
+            custom(0),
+            TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature),
+            TransactionError::BlockhashNotFound,
+        ];
+        for err in not_ours {
+            assert!(
+                RateLimiterError::from_transaction_error(&err).is_none(),
+                "{:?}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn logs_name_the_error_even_when_truncated() {
+        let failed = fixture(include_str!("../../test-data/logs/rejected_truncated.log"));
+        assert_eq!(
+            RateLimiterError::from_logs(&failed).map(u32::from),
+            Some(u32::from(RateLimiterError::RateLimitExceeded))
+        );
+        // Only Anchor's line made it in
+        let cut = failed
+            .iter()
+            .take_while(|line| !line.contains("consumed"))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            RateLimiterError::from_logs(&cut).map(u32::from),
+            Some(u32::from(RateLimiterError::RateLimitExceeded))
+        );
+        let succeeded = fixture(include_str!("../../test-data/logs/gated_counter_cpi.log"));
+        assert!(RateLimiterError::from_logs(&succeeded).is_none());
+    }
+
+    #[test]
+    fn a_cpi_caller_s_errors_are_not_the_limiter_s() {
+        let caller = "FAX2X8J527nvBvfrSHZ9fWoYfbj4SvSwx7ndQzgYVaJw";
+        let logs = vec![
+            format!("Program {} invoke [1]", caller),
+            format!("Program {} invoke [2]", crate::ID),
+            format!("Program {} success", crate::ID),
+            "Program log: AnchorError occurred. Error Code: Overdrawn. Error Number: 6002. Error Message: Overdrawn.".to_string(),
+            format!("Program {} failed: custom program error: 0x1772", caller),
+        ];
+        assert!(RateLimiterError::from_logs(&logs).is_none());
+
+        let passed_up = vec![
+            format!("Program {} invoke [1]", caller),
+            format!("Program {} invoke [2]", crate::ID),
+            format!("Program {} failed: custom program error: 0x1772", crate::ID),
+            format!("Program {} failed: custom program error: 0x1772", caller),
+        ];
+        assert_eq!(
+            RateLimiterError::from_logs(&passed_up).map(u32::from),
+            Some(u32::from(RateLimiterError::ClientBlocked))
+        );
+    }
+}
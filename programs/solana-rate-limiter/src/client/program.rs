@@ -0,0 +1,370 @@
+use std::fmt;
+use std::ops::Deref;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anchor_client::{Client, ClientError as AnchorClientError, Program};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use solana_client::client_error::ClientError as RpcError;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+pub use anchor_client::Cluster;
+
+use crate::errors::RateLimiterError;
+use crate::logic::{self, QuotaInfo};
+use crate::pda;
+use crate::state::{ClientBucket, GlobalConfig, Schedule};
+use crate::RateLimiterConfig;
+
+// A typed handle on a deployed limiter for Rust services, on anchor_client's
+// async API. Transactions are put together from this module's builders and the
+// accounts read through anchor_client, so what comes back is either the decoded
+// state or a RateLimiterClientError a service can match on: the program's
+// answers about a client are variants of their own, and anything the network
+// did is Transport.
+
+const SNAPSHOT_INTERVAL_SECONDS: i64 = 3600;
+
+#[derive(Debug)]
+pub enum RateLimiterClientError {
+    RateLimitExceeded,
+    BurstLimitExceeded,
+    ClientBlocked,
+    ProgramPaused,
+    // The wallet has no bucket; register it first
+    NotRegistered(Pubkey),
+    // Any other error the program or Anchor returned, by code
+    Program(u32),
+    // The transaction never reached the program, or a read failed. Retried as
+    // ProgramOptions allows before it gets here. Both boxed, as
+    // ClientError::Rpc is: the wrapped errors dwarf every other variant.
+    Transport(Box<RpcError>),
+    Client(Box<AnchorClientError>),
+}
+
+impl fmt::Display for RateLimiterClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateLimiterClientError::RateLimitExceeded => f.write_str("rate limit exceeded"),
+            RateLimiterClientError::BurstLimitExceeded => f.write_str("burst limit exceeded"),
+            RateLimiterClientError::ClientBlocked => f.write_str("client is blocked"),
+            RateLimiterClientError::ProgramPaused => f.write_str("program is paused"),
+            RateLimiterClientError::NotRegistered(wallet) => {
+                write!(f, "{} has no bucket", wallet)
+            }
+            RateLimiterClientError::Program(code) => write!(f, "program error {}", code),
+            RateLimiterClientError::Transport(err) => write!(f, "rpc: {}", err),
+            RateLimiterClientError::Client(err) => write!(f, "anchor client: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RateLimiterClientError {}
+
+pub type Result<T> = std::result::Result<T, RateLimiterClientError>;
+
+impl RateLimiterClientError {
+    // What a failed send means for `wallet`, the client the instruction was about
+    fn from_send(err: RpcError, wallet: Pubkey) -> Self {
+        let Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) =
+            err.get_transaction_error()
+        else {
+            return RateLimiterClientError::Transport(Box::new(err));
+        };
+        let known = [
+            (
+                RateLimiterError::RateLimitExceeded,
+                RateLimiterClientError::RateLimitExceeded,
+            ),
+            (
+                RateLimiterError::BurstLimitExceeded,
+                RateLimiterClientError::BurstLimitExceeded,
+            ),
+            (
+                RateLimiterError::ClientBlocked,
+                RateLimiterClientError::ClientBlocked,
+            ),
+            (
+                RateLimiterError::ProgramPaused,
+                RateLimiterClientError::ProgramPaused,
+            ),
+            (
+                RateLimiterError::ClientNotRegistered,
+                RateLimiterClientError::NotRegistered(wallet),
+            ),
+        ];
+        // Anchor's own codes for a missing bucket count as not registered too
+        let cause = RateLimiterError::from_framework_code(code).map_or(code, u32::from);
+        known
+            .into_iter()
+            .find(|(program, _)| u32::from(*program) == cause)
+            .map_or(RateLimiterClientError::Program(code), |(_, err)| err)
+    }
+
+    // Worth sending again: the transaction never ran, or its blockhash lapsed
+    fn retryable(&self) -> bool {
+        match self {
+            RateLimiterClientError::Transport(err) => matches!(
+                err.get_transaction_error(),
+                None | Some(TransactionError::BlockhashNotFound)
+            ),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ProgramOptions {
+    // What sends wait for and reads are made at
+    pub commitment: CommitmentConfig,
+    // Further sends after a transport failure. A send that timed out may still
+    // land, so a retried consume can count twice; use consume_v2 with a nonce
+    // where that matters.
+    pub retries: u32,
+    pub retry_delay: Duration,
+}
+
+impl Default for ProgramOptions {
+    fn default() -> Self {
+        ProgramOptions {
+            commitment: CommitmentConfig::confirmed(),
+            retries: 2,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+pub struct RateLimiterProgram<C> {
+    program: Program<C>,
+    rpc: RpcClient,
+    payer: C,
+    options: ProgramOptions,
+}
+
+impl<C, S> RateLimiterProgram<C>
+where
+    C: Deref<Target = S> + Clone,
+    S: Signer,
+{
+    // `payer` pays every fee, and signs as the admin for the admin methods
+    pub fn new(cluster: Cluster, payer: C) -> Result<Self> {
+        Self::with_options(cluster, payer, ProgramOptions::default())
+    }
+
+    pub fn with_options(cluster: Cluster, payer: C, options: ProgramOptions) -> Result<Self> {
+        let client = Client::new_with_options(cluster.clone(), payer.clone(), options.commitment);
+        let program = client
+            .program(crate::ID)
+            .map_err(|err| RateLimiterClientError::Client(Box::new(err)))?;
+        Ok(RateLimiterProgram {
+            program,
+            rpc: RpcClient::new_with_commitment(cluster.url().to_string(), options.commitment),
+            payer,
+            options,
+        })
+    }
+
+    pub fn program(&self) -> &Program<C> {
+        &self.program
+    }
+
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc
+    }
+
+    // Creates the config and the stats account, with the payer as admin. Must be
+    // signed by the program's upgrade authority.
+    pub async fn initialize(&self, config: RateLimiterConfig) -> Result<Signature> {
+        let admin = self.payer.pubkey();
+        let instructions = [
+            super::build_initialize_ix(&admin, config),
+            super::build_initialize_stats_ix(&admin, &admin, SNAPSHOT_INTERVAL_SECONDS),
+        ];
+        self.send(&instructions, None, admin).await
+    }
+
+    // The payer pays the bucket's rent
+    pub async fn register(&self, client: &dyn Signer) -> Result<Signature> {
+        let config = self.config().await?;
+        let ix = super::build_register_client_ix(&config, &client.pubkey(), &self.payer.pubkey());
+        self.send(&[ix], Some(client), client.pubkey()).await
+    }
+
+    pub async fn consume(&self, client: &dyn Signer) -> Result<Signature> {
+        let config = self.config().await?;
+        let ix = super::build_consume_ix(&config, &client.pubkey());
+        self.send(&[ix], Some(client), client.pubkey()).await
+    }
+
+    pub async fn consume_amount(&self, client: &dyn Signer, amount: u64) -> Result<Signature> {
+        let config = self.config().await?;
+        let ix = super::build_consume_amount_ix(&config, &client.pubkey(), amount);
+        self.send(&[ix], Some(client), client.pubkey()).await
+    }
+
+    // What a one-unit consume by `owner` would get now, worked out locally from
+    // the fetched accounts. Measured against the local clock, like the sender's
+    // retry wait.
+    pub async fn quota(&self, owner: &Pubkey) -> Result<QuotaInfo> {
+        let config = self.config().await?;
+        let bucket = self.bucket(owner).await?;
+        let schedule = if config.scheduled() {
+            Some(self.account::<Schedule>(pda::schedule_address().0).await?)
+        } else {
+            None
+        };
+        let entries = schedule.as_ref().map_or(&[][..], |s| s.entries());
+        Ok(logic::simulate_scheduled_consume(
+            &config,
+            entries,
+            &bucket,
+            unix_now(),
+        ))
+    }
+
+    pub async fn block(&self, wallet: &Pubkey) -> Result<Signature> {
+        let config = self.config().await?;
+        let ix = super::build_block_client_ix(&config, &self.payer.pubkey(), wallet);
+        self.send(&[ix], None, *wallet).await
+    }
+
+    // Also lifts a block, there being no instruction that only does that
+    pub async fn reset(&self, wallet: &Pubkey) -> Result<Signature> {
+        let ix = super::build_reset_client_ix(&self.payer.pubkey(), wallet);
+        self.send(&[ix], None, *wallet).await
+    }
+
+    pub async fn config(&self) -> Result<GlobalConfig> {
+        self.account(pda::global_config_address().0).await
+    }
+
+    pub async fn bucket(&self, owner: &Pubkey) -> Result<ClientBucket> {
+        self.account(pda::client_bucket_address(owner).0)
+            .await
+            .map_err(|err| match err {
+                RateLimiterClientError::NotRegistered(_) => {
+                    RateLimiterClientError::NotRegistered(*owner)
+                }
+                err => err,
+            })
+    }
+
+    async fn account<T: AccountDeserialize>(&self, address: Pubkey) -> Result<T> {
+        self.program
+            .account::<T>(address)
+            .await
+            .map_err(|err| match err {
+                AnchorClientError::AccountNotFound => {
+                    RateLimiterClientError::NotRegistered(address)
+                }
+                err => RateLimiterClientError::Client(Box::new(err)),
+            })
+    }
+
+    async fn send(
+        &self,
+        instructions: &[Instruction],
+        client: Option<&dyn Signer>,
+        wallet: Pubkey,
+    ) -> Result<Signature> {
+        let payer: &dyn Signer = &*self.payer;
+        let mut signers = vec![payer];
+        signers.extend(client);
+        let mut attempt = 0;
+        loop {
+            let blockhash = self
+                .rpc
+                .get_latest_blockhash()
+                .await
+                .map_err(|err| RateLimiterClientError::Transport(Box::new(err)));
+            let result = match blockhash {
+                Ok(blockhash) => {
+                    let tx = Transaction::new_signed_with_payer(
+                        instructions,
+                        Some(&payer.pubkey()),
+                        &signers,
+                        blockhash,
+                    );
+                    self.rpc
+                        .send_and_confirm_transaction(&tx)
+                        .await
+                        .map_err(|err| RateLimiterClientError::from_send(err, wallet))
+                }
+                Err(err) => Err(err),
+            };
+            match result {
+                Err(err) if err.retryable() && attempt < self.options.retries => {
+                    attempt += 1;
+                    tokio::time::sleep(self.options.retry_delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed(code: u32) -> RateLimiterClientError {
+        let err = RpcError::from(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code),
+        ));
+        RateLimiterClientError::from_send(err, Pubkey::default())
+    }
+
+    #[test]
+    fn program_errors_map_to_their_variants() {
+        assert!(matches!(
+            failed(u32::from(RateLimiterError::RateLimitExceeded)),
+            RateLimiterClientError::RateLimitExceeded
+        ));
+        assert!(matches!(
+            failed(u32::from(RateLimiterError::ClientBlocked)),
+            RateLimiterClientError::ClientBlocked
+        ));
+        assert!(matches!(
+            failed(u32::from(RateLimiterError::ProgramPaused)),
+            RateLimiterClientError::ProgramPaused
+        ));
+        let unknown = u32::from(RateLimiterError::InvalidConfig);
+        assert!(
+            matches!(failed(unknown), RateLimiterClientError::Program(code) if code == unknown)
+        );
+    }
+
+    #[test]
+    fn a_missing_bucket_is_not_registered_whichever_code_says_so() {
+        let codes = [
+            u32::from(RateLimiterError::ClientNotRegistered),
+            ErrorCode::AccountNotInitialized as u32,
+        ];
+        for code in codes {
+            assert!(matches!(
+                failed(code),
+                RateLimiterClientError::NotRegistered(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn only_transport_failures_are_retried() {
+        assert!(!failed(u32::from(RateLimiterError::RateLimitExceeded)).retryable());
+        let lapsed = RpcError::from(TransactionError::BlockhashNotFound);
+        assert!(RateLimiterClientError::from_send(lapsed, Pubkey::default()).retryable());
+        let io = RpcError::from(std::io::Error::other("connection reset"));
+        assert!(RateLimiterClientError::from_send(io, Pubkey::default()).retryable());
+    }
+}
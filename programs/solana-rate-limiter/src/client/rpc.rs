@@ -0,0 +1,290 @@
+use std::fmt;
+
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use solana_account_decoder_client_types::UiAccountEncoding;
+use solana_client::client_error::ClientError as RpcError;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+
+use crate::pda;
+use crate::state::{ClientBucket, GlobalConfig, Schedule};
+
+// Fetch-and-decode over RPC, blocking here and async in `nonblocking`. A missing
+// account and one that fails to decode are told apart, so a caller can send
+// register_client on `NotRegistered` without string-matching RPC errors.
+
+#[derive(Debug)]
+pub enum ClientError {
+    // No account at the address. For a bucket: never registered, or since closed
+    NotRegistered(Pubkey),
+    // Wrong discriminator, or a layout older than this crate's
+    Decode {
+        address: Pubkey,
+        source: anchor_lang::error::Error,
+    },
+    // Boxed, the RPC error is several times larger than every other variant
+    Rpc(Box<RpcError>),
+    // RateLimitedSender gave up, still rate limited after this many sends
+    RetriesExhausted { attempts: u32 },
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::NotRegistered(address) => write!(f, "no account at {}", address),
+            ClientError::Decode { address, source } => {
+                write!(f, "account {} did not decode: {}", address, source)
+            }
+            ClientError::Rpc(err) => write!(f, "rpc: {}", err),
+            ClientError::RetriesExhausted { attempts } => {
+                write!(f, "still rate limited after {} attempts", attempts)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<RpcError> for ClientError {
+    fn from(err: RpcError) -> Self {
+        ClientError::Rpc(Box::new(err))
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+fn decode<T>(
+    address: Pubkey,
+    data: Option<&[u8]>,
+    try_from: fn(&[u8]) -> anchor_lang::Result<T>,
+) -> Result<T> {
+    let data = data.ok_or(ClientError::NotRegistered(address))?;
+    try_from(data).map_err(|source| ClientError::Decode { address, source })
+}
+
+// Every current-layout ClientBucket, optionally only `owner`'s. The size filter
+// leaves out buckets still on an older layout, whose owner sits at another
+// offset; they show up here once migrated.
+pub fn bucket_filters(owner: Option<&Pubkey>) -> Vec<RpcFilterType> {
+    let mut filters = vec![
+        RpcFilterType::DataSize((8 + ClientBucket::INIT_SPACE) as u64),
+        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            0,
+            ClientBucket::DISCRIMINATOR.to_vec(),
+        )),
+    ];
+    if let Some(owner) = owner {
+        // After the discriminator and the version byte
+        filters.push(RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            9,
+            owner.to_bytes().to_vec(),
+        )));
+    }
+    filters
+}
+
+fn bucket_accounts_config(owner: Option<&Pubkey>) -> RpcProgramAccountsConfig {
+    RpcProgramAccountsConfig {
+        filters: Some(bucket_filters(owner)),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    }
+}
+
+pub fn fetch_global_config(rpc: &RpcClient) -> Result<GlobalConfig> {
+    let address = pda::global_config_address().0;
+    let account = rpc
+        .get_account_with_commitment(&address, rpc.commitment())?
+        .value;
+    decode(
+        address,
+        account.as_ref().map(|a| a.data.as_slice()),
+        GlobalConfig::try_from_account_data,
+    )
+}
+
+pub fn fetch_client_bucket(rpc: &RpcClient, owner: &Pubkey) -> Result<ClientBucket> {
+    let address = pda::client_bucket_address(owner).0;
+    let account = rpc
+        .get_account_with_commitment(&address, rpc.commitment())?
+        .value;
+    decode(
+        address,
+        account.as_ref().map(|a| a.data.as_slice()),
+        ClientBucket::try_from_account_data,
+    )
+}
+
+pub fn fetch_schedule(rpc: &RpcClient) -> Result<Schedule> {
+    let address = pda::schedule_address().0;
+    let account = rpc
+        .get_account_with_commitment(&address, rpc.commitment())?
+        .value;
+    decode(
+        address,
+        account.as_ref().map(|a| a.data.as_slice()),
+        Schedule::try_from_account_data,
+    )
+}
+
+pub fn list_client_buckets(
+    rpc: &RpcClient,
+    owner: Option<&Pubkey>,
+) -> Result<Vec<(Pubkey, ClientBucket)>> {
+    rpc.get_program_accounts_with_config(&crate::ID, bucket_accounts_config(owner))?
+        .into_iter()
+        .map(|(address, account)| {
+            let bucket = decode(
+                address,
+                Some(&account.data),
+                ClientBucket::try_from_account_data,
+            )?;
+            Ok((address, bucket))
+        })
+        .collect()
+}
+
+pub mod nonblocking {
+    use solana_client::nonblocking::rpc_client::RpcClient;
+
+    use super::*;
+
+    pub async fn fetch_global_config(rpc: &RpcClient) -> Result<GlobalConfig> {
+        let address = pda::global_config_address().0;
+        let account = rpc
+            .get_account_with_commitment(&address, rpc.commitment())
+            .await?
+            .value;
+        decode(
+            address,
+            account.as_ref().map(|a| a.data.as_slice()),
+            GlobalConfig::try_from_account_data,
+        )
+    }
+
+    pub async fn fetch_client_bucket(rpc: &RpcClient, owner: &Pubkey) -> Result<ClientBucket> {
+        let address = pda::client_bucket_address(owner).0;
+        let account = rpc
+            .get_account_with_commitment(&address, rpc.commitment())
+            .await?
+            .value;
+        decode(
+            address,
+            account.as_ref().map(|a| a.data.as_slice()),
+            ClientBucket::try_from_account_data,
+        )
+    }
+
+    pub async fn fetch_schedule(rpc: &RpcClient) -> Result<Schedule> {
+        let address = pda::schedule_address().0;
+        let account = rpc
+            .get_account_with_commitment(&address, rpc.commitment())
+            .await?
+            .value;
+        decode(
+            address,
+            account.as_ref().map(|a| a.data.as_slice()),
+            Schedule::try_from_account_data,
+        )
+    }
+
+    pub async fn list_client_buckets(
+        rpc: &RpcClient,
+        owner: Option<&Pubkey>,
+    ) -> Result<Vec<(Pubkey, ClientBucket)>> {
+        rpc.get_program_accounts_with_config(&crate::ID, bucket_accounts_config(owner))
+            .await?
+            .into_iter()
+            .map(|(address, account)| {
+                let bucket = decode(
+                    address,
+                    Some(&account.data),
+                    ClientBucket::try_from_account_data,
+                )?;
+                Ok((address, bucket))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::CLIENT_BUCKET_VERSION;
+
+    fn bucket(owner: Pubkey) -> ClientBucket {
+        ClientBucket {
+            version: CLIENT_BUCKET_VERSION,
+            owner,
+            request_count: 0,
+            window_start: 0,
+            total_requests: 0,
+            flags: 0,
+            violations: 0,
+            violations_updated_at: 0,
+            config_epoch: 0,
+            burst_credits: 0,
+            priority_count: 0,
+            escrowed_count: 0,
+            bonus_requests: 0,
+            last_nonce: 0,
+            nonce_bitmap: 0,
+            last_refund_nonce: 0,
+            refunded_count: 0,
+            registered_by: Pubkey::default(),
+            recovery_delegate: Pubkey::default(),
+            last_request_ts: 0,
+            bump: 255,
+            label: [0; 32],
+            event_tag: [0; 16],
+        }
+    }
+
+    // What the RPC node does with a filter, applied to a serialized bucket
+    fn matches(filter: &RpcFilterType, data: &[u8]) -> bool {
+        match filter {
+            RpcFilterType::DataSize(size) => data.len() as u64 == *size,
+            RpcFilterType::Memcmp(memcmp) => memcmp.bytes_match(data),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn bucket_filters_select_the_owner_s_bucket() {
+        let owner = Pubkey::new_unique();
+        let mut data = Vec::new();
+        bucket(owner).try_serialize(&mut data).unwrap();
+
+        assert!(bucket_filters(None).iter().all(|f| matches(f, &data)));
+        assert!(bucket_filters(Some(&owner))
+            .iter()
+            .all(|f| matches(f, &data)));
+        let other = Pubkey::new_unique();
+        assert!(!bucket_filters(Some(&other))
+            .iter()
+            .all(|f| matches(f, &data)));
+    }
+
+    #[test]
+    fn missing_and_undecodable_accounts_are_distinct_errors() {
+        let address = Pubkey::new_unique();
+        assert!(matches!(
+            decode(address, None, ClientBucket::try_from_account_data),
+            Err(ClientError::NotRegistered(a)) if a == address
+        ));
+        assert!(matches!(
+            decode(address, Some(&[0u8; 16]), ClientBucket::try_from_account_data),
+            Err(ClientError::Decode { address: a, .. }) if a == address
+        ));
+
+        let mut data = Vec::new();
+        bucket(address).try_serialize(&mut data).unwrap();
+        let decoded = decode(address, Some(&data), ClientBucket::try_from_account_data).unwrap();
+        assert_eq!(decoded.owner, address);
+    }
+}
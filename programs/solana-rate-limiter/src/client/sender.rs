@@ -0,0 +1,228 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use solana_client::client_error::ClientError as RpcError;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+use super::build_consume_ix;
+use super::rpc::nonblocking::{fetch_client_bucket, fetch_global_config, fetch_schedule};
+use super::rpc::{ClientError, Result};
+use crate::errors::RateLimiterError;
+use crate::logic;
+
+// Sends consume_request for one client, and on a rate-limit rejection waits for
+// the window to roll over before trying again. The wait comes from running the
+// program's own decision locally (logic::simulate_consume) on freshly fetched
+// state, so it is one sleep per rejected window rather than a polling loop.
+// Blocked, frozen and paused rejections are returned as they are: waiting does
+// not fix them.
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub min_wait: Duration, // floor for each wait, also used when the hint is already past
+    pub max_wait: Duration, // cap for each wait, jitter included
+    pub max_jitter: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            min_wait: Duration::from_millis(500),
+            max_wait: Duration::from_secs(120),
+            max_jitter: Duration::from_millis(500),
+        }
+    }
+}
+
+// Called on every send and every wait, for counters or logs
+pub trait SendMetrics: Send + Sync {
+    fn on_attempt(&self, _attempt: u32) {}
+    fn on_wait(&self, _wait: Duration) {}
+}
+
+pub struct NoMetrics;
+
+impl SendMetrics for NoMetrics {}
+
+pub struct RateLimitedSender<S: Signer> {
+    rpc: RpcClient,
+    client: S,
+    policy: RetryPolicy,
+    metrics: Box<dyn SendMetrics>,
+}
+
+impl<S: Signer> RateLimitedSender<S> {
+    // `client` signs and pays for every consume
+    pub fn new(rpc: RpcClient, client: S) -> Self {
+        RateLimitedSender {
+            rpc,
+            client,
+            policy: RetryPolicy::default(),
+            metrics: Box::new(NoMetrics),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: impl SendMetrics + 'static) -> Self {
+        self.metrics = Box::new(metrics);
+        self
+    }
+
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc
+    }
+
+    pub async fn send_consume(&self) -> Result<Signature> {
+        let attempts = self.policy.max_attempts.max(1);
+        for attempt in 1..=attempts {
+            self.metrics.on_attempt(attempt);
+            // Refetched each time: the schedule flag or the treasury may have moved
+            let config = fetch_global_config(&self.rpc).await?;
+            let ix = build_consume_ix(&config, &self.client.pubkey());
+            let blockhash = self.rpc.get_latest_blockhash().await?;
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&self.client.pubkey()),
+                &[&self.client],
+                blockhash,
+            );
+            match self.rpc.send_and_confirm_transaction(&tx).await {
+                Ok(signature) => return Ok(signature),
+                Err(err) if rate_limited(&err) => {
+                    if attempt == attempts {
+                        break;
+                    }
+                    let hint = self.retry_after().await?;
+                    let wait = backoff(&self.policy, hint, jitter(self.policy.max_jitter));
+                    self.metrics.on_wait(wait);
+                    tokio::time::sleep(wait).await;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Err(ClientError::RetriesExhausted { attempts })
+    }
+
+    // Seconds until the bucket's window rolls over, as the program would see it.
+    // Measured against the local clock, which may run a little off the cluster's;
+    // the jitter and a further attempt absorb that.
+    async fn retry_after(&self) -> Result<i64> {
+        let config = fetch_global_config(&self.rpc).await?;
+        let bucket = fetch_client_bucket(&self.rpc, &self.client.pubkey()).await?;
+        let schedule = if config.scheduled() {
+            Some(fetch_schedule(&self.rpc).await?)
+        } else {
+            None
+        };
+        let entries = schedule.as_ref().map_or(&[][..], |s| s.entries());
+        let quota = logic::simulate_scheduled_consume(&config, entries, &bucket, unix_now());
+        Ok(if quota.allowed { 0 } else { quota.resets_in })
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+// Whether a failed send, confirmed or refused at preflight, was the limiter
+// saying no for now: over the window allowance or over burst_limit
+pub fn rate_limited(err: &RpcError) -> bool {
+    matches!(err.get_transaction_error(), Some(tx_err) if is_rate_limit_error(&tx_err))
+}
+
+fn is_rate_limit_error(err: &TransactionError) -> bool {
+    let TransactionError::InstructionError(_, InstructionError::Custom(code)) = err else {
+        return false;
+    };
+    *code == u32::from(RateLimiterError::RateLimitExceeded)
+        || *code == u32::from(RateLimiterError::BurstLimitExceeded)
+}
+
+// Sleep for a `hint_seconds` retry-after: at least min_wait, and at most max_wait
+// once the jitter is added
+fn backoff(policy: &RetryPolicy, hint_seconds: i64, jitter: Duration) -> Duration {
+    let hint = Duration::from_secs(hint_seconds.max(0) as u64);
+    (hint.max(policy.min_wait) + jitter).min(policy.max_wait)
+}
+
+// Spreads out bots that were refused in the same window. Not random enough for
+// anything but that.
+fn jitter(max: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos() as u64);
+    match max.as_millis() as u64 {
+        0 => Duration::ZERO,
+        max_ms => Duration::from_millis(nanos % (max_ms + 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // program-test is not a dependency, so the send loop itself is exercised only
+    // against a validator; these cover the decisions it makes
+
+    fn custom(code: u32) -> TransactionError {
+        TransactionError::InstructionError(0, InstructionError::Custom(code))
+    }
+
+    #[test]
+    fn only_limit_rejections_are_retried() {
+        assert!(is_rate_limit_error(&custom(u32::from(
+            RateLimiterError::RateLimitExceeded
+        ))));
+        assert!(is_rate_limit_error(&custom(u32::from(
+            RateLimiterError::BurstLimitExceeded
+        ))));
+        assert!(!is_rate_limit_error(&custom(u32::from(
+            RateLimiterError::ClientBlocked
+        ))));
+        assert!(!is_rate_limit_error(&custom(u32::from(
+            RateLimiterError::ProgramPaused
+        ))));
+        assert!(!is_rate_limit_error(&TransactionError::AccountNotFound));
+        // The raw enum index without Anchor's offset is some other program's error
+        assert!(!is_rate_limit_error(&custom(
+            RateLimiterError::RateLimitExceeded as u32
+        )));
+    }
+
+    #[test]
+    fn waits_for_the_window_within_the_policy() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            min_wait: Duration::from_millis(200),
+            max_wait: Duration::from_secs(10),
+            max_jitter: Duration::from_millis(300),
+        };
+        let jitter = Duration::from_millis(100);
+        assert_eq!(backoff(&policy, 4, jitter), Duration::from_millis(4_100));
+        // Already rolled over, or a stale hint from a skewed clock
+        assert_eq!(backoff(&policy, 0, jitter), Duration::from_millis(300));
+        assert_eq!(
+            backoff(&policy, -5, Duration::ZERO),
+            Duration::from_millis(200)
+        );
+        assert_eq!(backoff(&policy, 3_600, jitter), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn jitter_stays_under_its_bound() {
+        for _ in 0..100 {
+            assert!(jitter(Duration::from_millis(50)) <= Duration::from_millis(50));
+        }
+        assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+    }
+}
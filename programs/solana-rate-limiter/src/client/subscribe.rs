@@ -0,0 +1,489 @@
+use std::error::Error;
+use std::ops::ControlFlow;
+use std::pin::Pin;
+use std::task::{self, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anchor_lang::prelude::*;
+use futures_util::stream::{self, BoxStream, Stream, StreamExt};
+use solana_account_decoder_client_types::{UiAccount, UiAccountEncoding};
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{
+    RpcAccountInfoConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter,
+};
+use solana_client::rpc_response::RpcLogsResponse;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::transaction::TransactionError;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use super::parser::{parse_events, RateLimiterEvent};
+use crate::events::ConsumeRejected;
+use crate::logic::{self, QuotaInfo};
+use crate::pda;
+use crate::state::{ClientBucket, GlobalConfig, Schedule, ScheduleEntry};
+
+// Live bucket updates over the websocket (accountSubscribe), for dashboards. The
+// config and schedule are subscribed to alongside the bucket, since the quota
+// depends on them. accountSubscribe only reports changes, so each (re)subscribe
+// also reads the three accounts over HTTP at `rpc_url`: that gives the first
+// update without waiting for a consume, and catches up on whatever changed while
+// the socket was down. A dropped connection is retried until the subscription is
+// dropped, with a backoff. Updates come in slot order and a state already passed
+// on is not repeated. Quotas are worked out at the local clock.
+
+const RECONNECT_MIN: Duration = Duration::from_millis(500);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BucketUpdate {
+    pub slot: u64,
+    pub bucket: ClientBucket,
+    pub quota: QuotaInfo,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BucketEvent {
+    Updated(BucketUpdate),
+    // The account was closed or collected
+    Closed {
+        slot: u64,
+    },
+    // A transaction naming the owner that the limiter failed. `event` is the
+    // ConsumeRejected it logged; pauses and frozen buckets are rejected without
+    // one, and without touching the account, so this is the only sign of them.
+    Rejected {
+        signature: String,
+        code: u32,
+        event: Option<ConsumeRejected>,
+    },
+}
+
+// Ends when dropped, which also stops the background task and its connection.
+// Needs a tokio runtime.
+pub struct Subscription<T> {
+    receiver: mpsc::UnboundedReceiver<T>,
+    task: JoinHandle<()>,
+}
+
+impl<T> Stream for Subscription<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().receiver.poll_recv(cx)
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// The bucket's states as it changes
+pub fn bucket_updates(rpc_url: &str, ws_url: &str, owner: Pubkey) -> Subscription<BucketUpdate> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let task = tokio::spawn(follow(
+        rpc_url.to_string(),
+        ws_url.to_string(),
+        owner,
+        false,
+        move |event| match event {
+            BucketEvent::Updated(update) => sender.send(update).is_ok(),
+            _ => !sender.is_closed(),
+        },
+    ));
+    Subscription { receiver, task }
+}
+
+// bucket_updates, with closes, and with the rejections a logsSubscribe on the
+// owner turns up
+pub fn bucket_events(rpc_url: &str, ws_url: &str, owner: Pubkey) -> Subscription<BucketEvent> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let task = tokio::spawn(follow(
+        rpc_url.to_string(),
+        ws_url.to_string(),
+        owner,
+        true,
+        move |event| sender.send(event).is_ok(),
+    ));
+    Subscription { receiver, task }
+}
+
+// Calls `callback` with each update until it breaks
+pub async fn subscribe_bucket(
+    rpc_url: &str,
+    ws_url: &str,
+    owner: Pubkey,
+    mut callback: impl FnMut(BucketUpdate) -> ControlFlow<()>,
+) {
+    let mut updates = bucket_updates(rpc_url, ws_url, owner);
+    while let Some(update) = updates.next().await {
+        if callback(update).is_break() {
+            return;
+        }
+    }
+}
+
+type Failure = Box<dyn Error + Send + Sync>;
+
+// `deliver` returns false once nobody is listening
+async fn follow(
+    rpc_url: String,
+    ws_url: String,
+    owner: Pubkey,
+    with_logs: bool,
+    mut deliver: impl FnMut(BucketEvent) -> bool + Send,
+) {
+    let rpc = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
+    let mut tracker = Tracker::new(owner);
+    let mut delay = RECONNECT_MIN;
+    loop {
+        match connection(&rpc, &ws_url, with_logs, &mut tracker, &mut deliver).await {
+            Ok(Ended::Listener) => return,
+            // Connected and then lost: start the backoff over
+            Ok(Ended::Socket) => delay = RECONNECT_MIN,
+            Err(_) => {}
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(RECONNECT_MAX);
+    }
+}
+
+enum Ended {
+    Listener,
+    Socket,
+}
+
+enum Notice {
+    Config(UiAccount),
+    Schedule(UiAccount),
+    Bucket(u64, UiAccount),
+    Logs(RpcLogsResponse),
+}
+
+async fn connection(
+    rpc: &RpcClient,
+    ws_url: &str,
+    with_logs: bool,
+    tracker: &mut Tracker,
+    deliver: &mut (impl FnMut(BucketEvent) -> bool + Send),
+) -> std::result::Result<Ended, Failure> {
+    let client = PubsubClient::new(ws_url).await?;
+    let account_config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        ..RpcAccountInfoConfig::default()
+    };
+    let addresses = [
+        pda::global_config_address().0,
+        pda::schedule_address().0,
+        pda::client_bucket_address(&tracker.owner).0,
+    ];
+    let (configs, _) = client
+        .account_subscribe(&addresses[0], Some(account_config.clone()))
+        .await?;
+    let (schedules, _) = client
+        .account_subscribe(&addresses[1], Some(account_config.clone()))
+        .await?;
+    let (buckets, _) = client
+        .account_subscribe(&addresses[2], Some(account_config))
+        .await?;
+    let mut notices: Vec<BoxStream<'_, Notice>> = vec![
+        configs.map(|r| Notice::Config(r.value)).boxed(),
+        schedules.map(|r| Notice::Schedule(r.value)).boxed(),
+        buckets
+            .map(|r| Notice::Bucket(r.context.slot, r.value))
+            .boxed(),
+    ];
+    if with_logs {
+        let (logs, _) = client
+            .logs_subscribe(
+                RpcTransactionLogsFilter::Mentions(vec![tracker.owner.to_string()]),
+                RpcTransactionLogsConfig {
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .await?;
+        notices.push(logs.map(|r| Notice::Logs(r.value)).boxed());
+    }
+
+    // Read after subscribing, so a change between the two is in one or the other
+    let snapshot = rpc
+        .get_multiple_accounts_with_commitment(&addresses, CommitmentConfig::confirmed())
+        .await?;
+    let slot = snapshot.context.slot;
+    let data = |index: usize| {
+        snapshot.value[index]
+            .as_ref()
+            .map_or(&[][..], |a| &a.data[..])
+    };
+    tracker.config(data(0));
+    tracker.schedule(data(1));
+    if let Some(event) = tracker.bucket(slot, data(2), unix_now()) {
+        if !deliver(event) {
+            return Ok(Ended::Listener);
+        }
+    }
+
+    let mut notices = stream::select_all(notices);
+    while let Some(notice) = notices.next().await {
+        let event = match notice {
+            Notice::Config(account) => {
+                tracker.config(&decoded(&account));
+                None
+            }
+            Notice::Schedule(account) => {
+                tracker.schedule(&decoded(&account));
+                None
+            }
+            Notice::Bucket(slot, account) => tracker.bucket(slot, &decoded(&account), unix_now()),
+            Notice::Logs(logs) => rejection(&logs, &tracker.owner),
+        };
+        if let Some(event) = event {
+            if !deliver(event) {
+                return Ok(Ended::Listener);
+            }
+        }
+    }
+    Ok(Ended::Socket)
+}
+
+// A closed account comes through as one with no data
+fn decoded(account: &UiAccount) -> Vec<u8> {
+    account.data.decode().unwrap_or_default()
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+// What a quota needs across notifications, and the last bucket state passed on
+struct Tracker {
+    owner: Pubkey,
+    config: Option<GlobalConfig>,
+    schedule: Vec<ScheduleEntry>,
+    last_slot: u64,
+    last_data: Option<Vec<u8>>,
+}
+
+impl Tracker {
+    fn new(owner: Pubkey) -> Self {
+        Tracker {
+            owner,
+            config: None,
+            schedule: Vec::new(),
+            last_slot: 0,
+            last_data: None,
+        }
+    }
+
+    fn config(&mut self, data: &[u8]) {
+        if let Ok(config) = GlobalConfig::try_from_account_data(data) {
+            self.config = Some(config);
+        }
+    }
+
+    fn schedule(&mut self, data: &[u8]) {
+        self.schedule = Schedule::try_from_account_data(data)
+            .map(|schedule| schedule.entries().to_vec())
+            .unwrap_or_default();
+    }
+
+    // None for a state older than, or the same as, the last one passed on, and
+    // for a bucket that does not decode or whose config has not been read yet
+    fn bucket(&mut self, slot: u64, data: &[u8], now: i64) -> Option<BucketEvent> {
+        if slot < self.last_slot || self.last_data.as_deref() == Some(data) {
+            return None;
+        }
+        let event = if data.is_empty() {
+            // Never registered: nothing to report
+            self.last_data.as_ref()?;
+            BucketEvent::Closed { slot }
+        } else {
+            let bucket = ClientBucket::try_from_account_data(data).ok()?;
+            let quota = logic::simulate_scheduled_consume(
+                self.config.as_ref()?,
+                &self.schedule,
+                &bucket,
+                now,
+            );
+            BucketEvent::Updated(BucketUpdate {
+                slot,
+                bucket,
+                quota,
+            })
+        };
+        self.last_slot = slot;
+        self.last_data = Some(data.to_vec());
+        Some(event)
+    }
+}
+
+// Only transactions that failed inside the limiter itself; the owner's other
+// transactions, failed or not, are left out
+fn rejection(logs: &RpcLogsResponse, owner: &Pubkey) -> Option<BucketEvent> {
+    let Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) = logs.err
+    else {
+        return None;
+    };
+    let failed = format!("Program {} failed", crate::ID);
+    if !logs.logs.iter().any(|line| line.starts_with(&failed)) {
+        return None;
+    }
+    let event = parse_events(&logs.logs)
+        .into_iter()
+        .find_map(|event| match event {
+            RateLimiterEvent::Rejected(rejected) if rejected.owner == *owner => Some(rejected),
+            _ => None,
+        });
+    Some(BucketEvent::Rejected {
+        signature: logs.signature.clone(),
+        code,
+        event,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::CLIENT_BUCKET_VERSION;
+    use crate::errors::RateLimiterError;
+
+    use super::super::tests::config;
+
+    fn bucket_data(owner: Pubkey, request_count: u64) -> Vec<u8> {
+        let bucket = ClientBucket {
+            version: CLIENT_BUCKET_VERSION,
+            owner,
+            request_count,
+            window_start: 1_000,
+            total_requests: request_count,
+            flags: 0,
+            violations: 0,
+            violations_updated_at: 0,
+            config_epoch: 0,
+            burst_credits: 0,
+            priority_count: 0,
+            escrowed_count: 0,
+            bonus_requests: 0,
+            last_nonce: 0,
+            nonce_bitmap: 0,
+            last_refund_nonce: 0,
+            refunded_count: 0,
+            registered_by: Pubkey::default(),
+            recovery_delegate: Pubkey::default(),
+            last_request_ts: 1_000,
+            bump: 255,
+            label: [0; 32],
+            event_tag: [0; 16],
+        };
+        let mut data = Vec::new();
+        bucket.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    fn tracker(owner: Pubkey) -> Tracker {
+        let mut tracker = Tracker::new(owner);
+        let mut data = Vec::new();
+        config().try_serialize(&mut data).unwrap();
+        tracker.config(&data);
+        tracker
+    }
+
+    fn count(event: Option<BucketEvent>) -> Option<u64> {
+        match event? {
+            BucketEvent::Updated(update) => Some(update.bucket.request_count),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn passes_on_each_state_once_and_in_slot_order() {
+        let owner = Pubkey::new_unique();
+        let mut tracker = tracker(owner);
+        assert_eq!(
+            count(tracker.bucket(10, &bucket_data(owner, 1), 1_010)),
+            Some(1)
+        );
+        // The same state again, as the snapshot after a reconnect would give it
+        assert_eq!(tracker.bucket(12, &bucket_data(owner, 1), 1_012), None);
+        assert_eq!(
+            count(tracker.bucket(13, &bucket_data(owner, 2), 1_013)),
+            Some(2)
+        );
+        // A notification from before the last snapshot
+        assert_eq!(tracker.bucket(11, &bucket_data(owner, 3), 1_013), None);
+        assert_eq!(
+            tracker.bucket(14, &[], 1_014),
+            Some(BucketEvent::Closed { slot: 14 })
+        );
+    }
+
+    #[test]
+    fn quotas_wait_for_the_config_and_skip_unregistered_buckets() {
+        let owner = Pubkey::new_unique();
+        let mut tracker = Tracker::new(owner);
+        assert_eq!(tracker.bucket(5, &[], 1_000), None);
+        assert_eq!(tracker.bucket(6, &bucket_data(owner, 1), 1_000), None);
+
+        // The state held back is passed on once the config is in
+        let mut data = Vec::new();
+        config().try_serialize(&mut data).unwrap();
+        tracker.config(&data);
+        let Some(BucketEvent::Updated(update)) = tracker.bucket(6, &bucket_data(owner, 1), 1_010)
+        else {
+            panic!("no update");
+        };
+        assert_eq!(
+            update.quota,
+            logic::simulate_consume(&config(), &update.bucket, 1_010)
+        );
+    }
+
+    fn logs(err: Option<TransactionError>, lines: Vec<String>) -> RpcLogsResponse {
+        RpcLogsResponse {
+            signature: "sig".to_string(),
+            err,
+            logs: lines,
+        }
+    }
+
+    #[test]
+    fn rejections_are_the_limiter_s_failures() {
+        let owner = Pubkey::new_unique();
+        let paused = u32::from(RateLimiterError::ProgramPaused);
+        let failed = Some(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(paused),
+        ));
+        let ours = vec![
+            format!("Program {} invoke [1]", crate::ID),
+            format!(
+                "Program {} failed: custom program error: {:#x}",
+                crate::ID,
+                paused
+            ),
+        ];
+        assert_eq!(
+            rejection(&logs(failed.clone(), ours.clone()), &owner),
+            Some(BucketEvent::Rejected {
+                signature: "sig".to_string(),
+                code: paused,
+                event: None,
+            })
+        );
+        assert_eq!(rejection(&logs(None, ours), &owner), None);
+
+        let other = Pubkey::new_unique();
+        let theirs = vec![
+            format!("Program {} invoke [1]", other),
+            format!("Program {} failed: custom program error: 0x1", other),
+        ];
+        assert_eq!(rejection(&logs(failed, theirs), &owner), None);
+    }
+}
@@ -1,2 +1,220 @@
+use anchor_lang::prelude::*;
+
+// Seeds are #[constant] so they reach the IDL, where TypeScript clients can read
+// them instead of restating the strings. pda.rs derives every address from them.
+#[constant]
 pub const GLOBAL_CONFIG_SEED: &[u8] = b"global-config";
-pub const CLIENT_BUCKET_SEED: &[u8] = b"client-bucket";
\ No newline at end of file
+#[constant]
+pub const CLIENT_BUCKET_SEED: &[u8] = b"client-bucket";
+#[constant]
+pub const GLOBAL_STATS_SEED: &[u8] = b"global-stats";
+#[constant]
+pub const FAST_BUCKET_SEED: &[u8] = b"fast-bucket";
+#[constant]
+pub const SHARD_PARENT_SEED: &[u8] = b"shard-parent";
+#[constant]
+pub const CLIENT_SHARD_SEED: &[u8] = b"client-shard";
+#[constant]
+pub const PROGRAM_BUCKET_SEED: &[u8] = b"program-bucket";
+#[constant]
+pub const CALLER_ALLOWLIST_SEED: &[u8] = b"caller-allowlist";
+#[constant]
+pub const PROPOSAL_SEED: &[u8] = b"proposal";
+#[constant]
+pub const SCHEDULE_SEED: &[u8] = b"limit-schedule";
+#[constant]
+pub const PAYER_META_SEED: &[u8] = b"payer-meta";
+#[constant]
+pub const CLIENT_NOTE_SEED: &[u8] = b"client-note";
+#[constant]
+pub const APPEAL_SEED: &[u8] = b"appeal";
+#[constant]
+pub const OFFER_SEED: &[u8] = b"quota-offer";
+#[constant]
+pub const SPONSOR_POOL_SEED: &[u8] = b"sponsor-pool";
+#[constant]
+pub const REBALANCE_POLICY_SEED: &[u8] = b"rebalance-policy";
+#[constant]
+pub const LEADERBOARD_SEED: &[u8] = b"leaderboard";
+#[constant]
+pub const PROGRAM_DENYLIST_SEED: &[u8] = b"program-denylist";
+#[constant]
+pub const SNAPSHOT_RECORD_SEED: &[u8] = b"snapshot-record";
+#[constant]
+pub const PAUSE_GRACE_SEED: &[u8] = b"pause-grace";
+
+// Derived under the *caller's* program id: the PDA a caller program signs with
+// (invoke_signed) to prove its identity to consume_as_program
+#[constant]
+pub const CALLER_AUTHORITY_SEED: &[u8] = b"rate-limiter-caller";
+
+// The limiter's own PDA, signing every hook CPI so hook programs can tell a real
+// notification from a direct call
+#[constant]
+pub const HOOK_AUTHORITY_SEED: &[u8] = b"hook-authority";
+
+// Upper bound for register_sharded, keeps aggregate_shards within one transaction
+pub const MAX_SHARDS: u8 = 16;
+
+// Seats in GlobalConfig.council
+pub const MAX_COUNCIL_MEMBERS: usize = 5;
+
+// Longest client note, in bytes of UTF-8
+pub const MAX_NOTE_LEN: usize = 64;
+
+// Size of ClientBucket.label
+pub const MAX_LABEL_LEN: usize = 32;
+
+// Size of ClientBucket.event_tag
+pub const EVENT_TAG_LEN: usize = 16;
+
+// gc_bounty_bps is out of this; 10_000 pays the whole rent to the cranker
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+// Rows in GlobalConfig.discount_tiers
+pub const MAX_DISCOUNT_TIERS: usize = 4;
+
+// Entries a limit schedule holds
+pub const MAX_SCHEDULE_ENTRIES: usize = 8;
+
+// Buckets the leaderboard ranks
+pub const LEADERBOARD_SIZE: usize = 10;
+
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+// Longest window schedule_pause accepts
+pub const MAX_SCHEDULED_PAUSE: i64 = 24 * 60 * 60;
+
+// Furthest ahead pause may set auto_unpause_at
+pub const MAX_AUTO_UNPAUSE_DELAY: i64 = 7 * 24 * 60 * 60;
+
+// Longest grace period set_pause_grace accepts
+pub const MAX_PAUSE_GRACE_SECONDS: i64 = 60 * 60;
+
+// How long an admin recovery waits before it can be finalized, giving the current
+// admin time to cancel it. The short-admin-recovery feature is for localnet tests.
+#[cfg(not(feature = "short-admin-recovery"))]
+pub const ADMIN_RECOVERY_DELAY: i64 = 7 * 24 * 60 * 60;
+#[cfg(feature = "short-admin-recovery")]
+pub const ADMIN_RECOVERY_DELAY: i64 = 5;
+
+// The deployment import_bucket_snapshot takes buckets over from: only snapshots
+// recorded under this program id import. Set it to the old program's id when
+// building the redeployment with snapshot-import.
+#[cfg(feature = "snapshot-import")]
+pub const SNAPSHOT_SOURCE_PROGRAM_ID: Pubkey = pubkey!("7KoXq7yEB7HccYeCKu9559v38bArHYpKmnp42gYAUpnc");
+
+// ConsumeRejected reason codes
+pub const REJECT_RATE_LIMIT: u8 = 1;
+pub const REJECT_BURST_LIMIT: u8 = 2;
+pub const REJECT_CLIENT_BLOCKED: u8 = 3;
+
+// Hook reason codes: the REJECT_* codes above, plus
+pub const HOOK_REASON_ADMIN_BLOCK: u8 = 4;
+
+// GlobalConfig.pause_reason values, returned with ProgramPaused. Scheduled pauses
+// report maintenance and circuit breaker trips an incident.
+pub const PAUSE_REASON_UNSPECIFIED: u8 = 0;
+pub const PAUSE_REASON_MAINTENANCE: u8 = 1;
+pub const PAUSE_REASON_INCIDENT: u8 = 2;
+pub const PAUSE_REASON_MIGRATION: u8 = 3;
+
+// GlobalConfig.config_preset values. Custom is also what a config reads as after
+// plain initialize or any update_config.
+pub const CONFIG_PRESET_CUSTOM: u8 = 0;
+pub const CONFIG_PRESET_STRICT: u8 = 1;
+pub const CONFIG_PRESET_STANDARD: u8 = 2;
+pub const CONFIG_PRESET_LENIENT: u8 = 3;
+
+// EffectiveConfig.limit_source values: the layer max_requests came from
+pub const LIMIT_SOURCE_BASE: u8 = 0;
+pub const LIMIT_SOURCE_SCHEDULE: u8 = 1;
+pub const LIMIT_SOURCE_FEEDER: u8 = 2;
+
+// GlobalConfig.proration_mode: what an update_config that changes max_requests or
+// window_seconds does to windows already open. Keep judges them against the new
+// limits as they stand, reset starts them over, and prorate scales their counts
+// by the new max over the old.
+pub const PRORATION_KEEP: u8 = 0;
+pub const PRORATION_RESET: u8 = 1;
+pub const PRORATION_PRORATE: u8 = 2;
+
+// Roles set_authority can rotate, which index GlobalConfig.pending_authorities
+pub const AUTHORITY_LIMIT_FEEDER: u8 = 0;
+pub const AUTHORITY_REFUND: u8 = 1;
+pub const AUTHORITY_KINDS: usize = 2;
+
+// DeploymentInfo.features_bitmask bits, each read off the config as it stands
+pub const FEATURE_REGISTRATION_FEE: u64 = 1 << 0;
+pub const FEATURE_DISCOUNT_TIERS: u64 = 1 << 1;
+pub const FEATURE_PROGRAM_KEYED: u64 = 1 << 2;
+pub const FEATURE_SCHEDULE: u64 = 1 << 3;
+pub const FEATURE_LIMIT_FEEDER: u64 = 1 << 4;
+pub const FEATURE_HOOK: u64 = 1 << 5;
+pub const FEATURE_COUNCIL: u64 = 1 << 6;
+pub const FEATURE_APPEALS: u64 = 1 << 7;
+pub const FEATURE_REFUNDS: u64 = 1 << 8;
+pub const FEATURE_BUCKET_RECOVERY: u64 = 1 << 9;
+
+// DeploymentInfo.limit_algorithm values. Every deployment so far counts fixed
+// windows, with burst credits on top.
+pub const LIMIT_ALGORITHM_FIXED_WINDOW: u8 = 0;
+
+// The presets' limits. Each sets a burst_limit at or above max_requests, so
+// banked credits buy Strict nothing, Standard half a window and Lenient a whole one.
+pub const STRICT_MAX_REQUESTS: u64 = 5;
+pub const STRICT_WINDOW_SECONDS: i64 = 60;
+pub const STRICT_BURST_LIMIT: u64 = 5;
+pub const STANDARD_MAX_REQUESTS: u64 = 100;
+pub const STANDARD_WINDOW_SECONDS: i64 = 60;
+pub const STANDARD_BURST_LIMIT: u64 = 150;
+pub const LENIENT_MAX_REQUESTS: u64 = 1_000;
+pub const LENIENT_WINDOW_SECONDS: i64 = 60;
+pub const LENIENT_BURST_LIMIT: u64 = 2_000;
+
+// Account layout versions (see migrations.rs). Handlers refuse accounts older
+// than the minimum with MigrationRequired.
+pub const GLOBAL_CONFIG_VERSION: u8 = 21;
+pub const CLIENT_BUCKET_VERSION: u8 = 13;
+pub const MIN_GLOBAL_CONFIG_VERSION: u8 = 21;
+pub const MIN_CLIENT_BUCKET_VERSION: u8 = 13;
+pub const PROGRAM_BUCKET_VERSION: u8 = 1;
+
+// GlobalConfig.flags bits
+pub const CONFIG_FLAG_PAUSED: u16 = 1 << 0;
+pub const CONFIG_FLAG_PROGRAM_KEYED: u16 = 1 << 1;
+pub const CONFIG_FLAG_WALLET_FALLBACK: u16 = 1 << 2;
+pub const CONFIG_FLAG_HOOK_ON_REJECT: u16 = 1 << 3;
+pub const CONFIG_FLAG_HOOK_PROPAGATE: u16 = 1 << 4;
+pub const CONFIG_FLAG_SCHEDULED: u16 = 1 << 5;
+pub const CONFIG_FLAG_REFUND_TOTALS: u16 = 1 << 6;
+// A bucket's event tag can be set once and never changed after
+pub const CONFIG_FLAG_EVENT_TAG_SET_ONCE: u16 = 1 << 7;
+// set_circuit_breaker turned the breaker on; its count and trip live in GlobalStats
+pub const CONFIG_FLAG_CIRCUIT_BREAKER: u16 = 1 << 8;
+
+// ClientBucket.flags bits
+pub const BUCKET_FLAG_BLOCKED: u16 = 1 << 0;
+pub const BUCKET_FLAG_TOTAL_SATURATED: u16 = 1 << 1;
+pub const BUCKET_FLAG_FROZEN: u16 = 1 << 2;
+pub const BUCKET_FLAG_SPONSORED: u16 = 1 << 3;
+pub const BUCKET_FLAG_BALANCE_EXEMPT: u16 = 1 << 4;
+// A gate_check denied a consume in the current window; cleared on rollover
+pub const BUCKET_FLAG_WINDOW_REJECTED: u16 = 1 << 5;
+
+// GlobalStats.usage_histogram: closed windows by how full they got against their
+// allowance, in quarters, and a last slot for windows with a denial in them
+pub const USAGE_HISTOGRAM_BUCKETS: usize = 5;
+pub const USAGE_REJECTED: usize = 4;
+
+// verify_bucket return codes
+pub const INVARIANT_OK: u8 = 0;
+pub const INVARIANT_EPOCH_AHEAD: u8 = 1;
+pub const INVARIANT_SATURATION_FLAG: u8 = 2;
+pub const INVARIANT_TOTAL_BELOW_WINDOW: u8 = 3;
+pub const INVARIANT_PRIORITY_ABOVE_WINDOW: u8 = 4;
+pub const INVARIANT_ESCROW_ABOVE_WINDOW: u8 = 5;
+
+// PriorityConsumed sources
+pub const CONSUME_SOURCE_WINDOW: u8 = 0;
+pub const CONSUME_SOURCE_BURST: u8 = 1;
@@ -1,3 +1,4 @@
+use anchor_lang::error::ERROR_CODE_OFFSET;
 use anchor_lang::prelude::*;
 
 #[error_code]
@@ -19,4 +20,356 @@ pub enum RateLimiterError {
 
     #[msg("Invalid configuration values.")]
     InvalidConfig,
-}
\ No newline at end of file
+
+    #[msg("Stats snapshot requested before the interval elapsed.")]
+    SnapshotTooSoon,
+
+    #[msg("Arithmetic overflow.")]
+    ArithmeticOverflow,
+
+    #[msg("Client bucket does not belong to the given wallet.")]
+    OwnerMismatch,
+
+    #[msg("Signer is not the program's upgrade authority.")]
+    NotUpgradeAuthority,
+
+    #[msg("Client has not registered a bucket.")]
+    ClientNotRegistered,
+
+    #[msg("Amount must be greater than zero.")]
+    InvalidAmount,
+
+    #[msg("Amount exceeds the allowed maximum.")]
+    AmountExceedsCap,
+
+    #[msg("Client bucket is internally inconsistent.")]
+    InvariantViolated,
+
+    #[msg("Client already has a bucket of the other kind.")]
+    BucketKindConflict,
+
+    #[msg("Shard count must be between 2 and MAX_SHARDS.")]
+    InvalidShardCount,
+
+    #[msg("Shard account does not match the client's shard PDAs.")]
+    InvalidShard,
+
+    #[msg("Account already uses the current layout.")]
+    AlreadyMigrated,
+
+    #[msg("Account data does not match a known layout.")]
+    InvalidAccountLayout,
+
+    #[msg("Account uses an old layout. Run migrate_account first.")]
+    MigrationRequired,
+
+    #[msg("No gate_check result in return data.")]
+    NoGateResult,
+
+    #[msg("Wallet buckets are disabled in program-keyed mode; consume through a registered program.")]
+    CallerProgramRequired,
+
+    #[msg("Caller program account is not an executable program.")]
+    InvalidCallerProgram,
+
+    #[msg("No consume by this client earlier in the transaction.")]
+    ConsumeNotFound,
+
+    #[msg("Calling program is not on the caller allowlist.")]
+    CallerNotAllowed,
+
+    #[msg("A hook is configured but its accounts were not passed.")]
+    HookAccountsMissing,
+
+    #[msg("A council is seated; this action needs an approved proposal.")]
+    CouncilApprovalRequired,
+
+    #[msg("Signer is not a council member.")]
+    NotCouncilMember,
+
+    #[msg("The council has no free seats.")]
+    CouncilFull,
+
+    #[msg("This member already approved the proposal.")]
+    AlreadyApproved,
+
+    #[msg("The proposal does not have enough approvals yet.")]
+    InsufficientApprovals,
+
+    #[msg("The proposal was already executed.")]
+    ProposalAlreadyExecuted,
+
+    #[msg("No admin recovery is pending.")]
+    NoRecoveryPending,
+
+    #[msg("The admin recovery timelock has not expired yet.")]
+    RecoveryTimelockActive,
+
+    #[msg("Scheduled pause must end after it starts, in the future, and last at most a day.")]
+    InvalidSchedule,
+
+    #[msg("Limit schedule entries must start before 86400, in increasing order, with max_requests > 0 (at most 8).")]
+    InvalidLimitSchedule,
+
+    #[msg("A limit schedule is active; pass the schedule account.")]
+    ScheduleAccountMissing,
+
+    #[msg("This payer registered too recently; wait out the registration cooldown.")]
+    RegistrationCooldown,
+
+    #[msg("Registration fees go to the config's treasury; pass that account.")]
+    InvalidTreasury,
+
+    #[msg("Client notes must be 1 to 64 bytes of UTF-8.")]
+    InvalidNote,
+
+    #[msg("Labels are at most 32 bytes of UTF-8 and cannot contain NUL.")]
+    InvalidLabel,
+
+    #[msg("This bucket is not stale yet, or stale-bucket collection is off.")]
+    BucketNotStale,
+
+    #[msg("Only accounts owned by this program can be swept.")]
+    SweepTargetNotOwned,
+
+    #[msg("Discount tiers must start at 2+ units with 1-9999 bps, both increasing row to row (at most 4).")]
+    InvalidDiscountTiers,
+
+    #[msg("Appeals are off: no appeal bond is set.")]
+    AppealsDisabled,
+
+    #[msg("Only a blocked client can appeal.")]
+    ClientNotBlocked,
+
+    #[msg("This client already has an appeal open.")]
+    AppealAlreadyOpen,
+
+    #[msg("The client's last appeal was denied too recently to file again.")]
+    AppealCooldown,
+
+    #[msg("This client has no appeal open.")]
+    NoAppealOpen,
+
+    #[msg("The offer lapsed with the seller's window.")]
+    OfferExpired,
+
+    #[msg("A seller cannot take their own offer.")]
+    SelfTrade,
+
+    #[msg("Client is frozen: consumes are on hold until an admin thaws it.")]
+    ClientFrozen,
+
+    #[msg("The sponsor pool cannot cover another bucket's rent.")]
+    SponsorPoolEmpty,
+
+    #[msg("A sponsored bucket's rent goes back to the sponsor pool, which must be passed.")]
+    SponsorPoolRequired,
+
+    #[msg("The payer cannot cover the rent the account is short of.")]
+    InsufficientRentFunding,
+
+    #[msg("The consume's deadline has passed.")]
+    DeadlineExceeded,
+
+    #[msg("The nonce is too far below the bucket's highest to tell whether it was used.")]
+    NonceReused,
+
+    #[msg("No rebalance policy is set: its step is zero.")]
+    RebalanceDisabled,
+
+    #[msg("Rebalance requested before the policy's interval elapsed.")]
+    RebalanceTooSoon,
+
+    #[msg("The nonce was already used by a consume on this bucket.")]
+    NonceAlreadyUsed,
+
+    #[msg("The snapshot's hash does not match its fields.")]
+    SnapshotHashMismatch,
+
+    #[msg("The admin has used up this window's destructive actions.")]
+    AdminThrottled,
+
+    #[msg("The refund nonce is not above the bucket's last one.")]
+    RefundNonceReused,
+
+    #[msg("The refund would exceed this window's refund cap.")]
+    RefundCapExceeded,
+
+    #[msg("This payer has funded as many registrations as the cap allows.")]
+    PayerBucketCapReached,
+
+    #[msg("The client wallet holds less than the minimum balance required to consume.")]
+    BalanceTooLow,
+
+    #[msg("No consume_and_report result in return data.")]
+    NoConsumeReport,
+
+    #[msg("The bucket's total_requests does not beat the lowest entry of the full leaderboard.")]
+    BelowLeaderboardThreshold,
+
+    #[msg("Bucket recovery is off: recovery_inactive_seconds is zero.")]
+    RecoveryDisabled,
+
+    #[msg("The bucket has consumed too recently for its recovery delegate to close it.")]
+    BucketStillActive,
+
+    #[msg("The client is a PDA of a program whose owners are denylisted.")]
+    ProgramOwnersBlocked,
+
+    #[msg("A consume by a caller program's PDA needs that program's denylist address while any program is denylisted.")]
+    OwnerDenylistMissing,
+
+    #[msg("No rotation is scheduled for this authority.")]
+    NoPendingRotation,
+
+    #[msg("Event tags can only be set once.")]
+    EventTagLocked,
+
+    #[msg("The snapshot is not the latest export recorded by the source deployment.")]
+    SnapshotNotRecorded,
+
+    #[msg("The circuit breaker is on; pass the stats account.")]
+    StatsAccountMissing,
+
+    #[msg("The circuit breaker tripped; consumes are refused until the admin resets it.")]
+    CircuitBreakerTripped,
+}
+
+impl RateLimiterError {
+    // Every variant in declaration order, so the one at index i has code
+    // ERROR_CODE_OFFSET + i. New variants are appended here as well.
+    pub const ALL: [RateLimiterError; 77] = [
+        RateLimiterError::RateLimitExceeded,
+        RateLimiterError::BurstLimitExceeded,
+        RateLimiterError::ClientBlocked,
+        RateLimiterError::ProgramPaused,
+        RateLimiterError::Unauthorized,
+        RateLimiterError::InvalidConfig,
+        RateLimiterError::SnapshotTooSoon,
+        RateLimiterError::ArithmeticOverflow,
+        RateLimiterError::OwnerMismatch,
+        RateLimiterError::NotUpgradeAuthority,
+        RateLimiterError::ClientNotRegistered,
+        RateLimiterError::InvalidAmount,
+        RateLimiterError::AmountExceedsCap,
+        RateLimiterError::InvariantViolated,
+        RateLimiterError::BucketKindConflict,
+        RateLimiterError::InvalidShardCount,
+        RateLimiterError::InvalidShard,
+        RateLimiterError::AlreadyMigrated,
+        RateLimiterError::InvalidAccountLayout,
+        RateLimiterError::MigrationRequired,
+        RateLimiterError::NoGateResult,
+        RateLimiterError::CallerProgramRequired,
+        RateLimiterError::InvalidCallerProgram,
+        RateLimiterError::ConsumeNotFound,
+        RateLimiterError::CallerNotAllowed,
+        RateLimiterError::HookAccountsMissing,
+        RateLimiterError::CouncilApprovalRequired,
+        RateLimiterError::NotCouncilMember,
+        RateLimiterError::CouncilFull,
+        RateLimiterError::AlreadyApproved,
+        RateLimiterError::InsufficientApprovals,
+        RateLimiterError::ProposalAlreadyExecuted,
+        RateLimiterError::NoRecoveryPending,
+        RateLimiterError::RecoveryTimelockActive,
+        RateLimiterError::InvalidSchedule,
+        RateLimiterError::InvalidLimitSchedule,
+        RateLimiterError::ScheduleAccountMissing,
+        RateLimiterError::RegistrationCooldown,
+        RateLimiterError::InvalidTreasury,
+        RateLimiterError::InvalidNote,
+        RateLimiterError::InvalidLabel,
+        RateLimiterError::BucketNotStale,
+        RateLimiterError::SweepTargetNotOwned,
+        RateLimiterError::InvalidDiscountTiers,
+        RateLimiterError::AppealsDisabled,
+        RateLimiterError::ClientNotBlocked,
+        RateLimiterError::AppealAlreadyOpen,
+        RateLimiterError::AppealCooldown,
+        RateLimiterError::NoAppealOpen,
+        RateLimiterError::OfferExpired,
+        RateLimiterError::SelfTrade,
+        RateLimiterError::ClientFrozen,
+        RateLimiterError::SponsorPoolEmpty,
+        RateLimiterError::SponsorPoolRequired,
+        RateLimiterError::InsufficientRentFunding,
+        RateLimiterError::DeadlineExceeded,
+        RateLimiterError::NonceReused,
+        RateLimiterError::RebalanceDisabled,
+        RateLimiterError::RebalanceTooSoon,
+        RateLimiterError::NonceAlreadyUsed,
+        RateLimiterError::SnapshotHashMismatch,
+        RateLimiterError::AdminThrottled,
+        RateLimiterError::RefundNonceReused,
+        RateLimiterError::RefundCapExceeded,
+        RateLimiterError::PayerBucketCapReached,
+        RateLimiterError::BalanceTooLow,
+        RateLimiterError::NoConsumeReport,
+        RateLimiterError::BelowLeaderboardThreshold,
+        RateLimiterError::RecoveryDisabled,
+        RateLimiterError::BucketStillActive,
+        RateLimiterError::ProgramOwnersBlocked,
+        RateLimiterError::OwnerDenylistMissing,
+        RateLimiterError::NoPendingRotation,
+        RateLimiterError::EventTagLocked,
+        RateLimiterError::SnapshotNotRecorded,
+        RateLimiterError::StatsAccountMissing,
+        RateLimiterError::CircuitBreakerTripped,
+    ];
+
+    // The variant behind a custom program error code, with Anchor's offset
+    pub fn from_code(code: u32) -> Option<Self> {
+        let index = code.checked_sub(ERROR_CODE_OFFSET)?;
+        Self::ALL.get(index as usize).copied()
+    }
+
+    // Anchor reports a missing bucket with framework codes that look like any other
+    // account bug. Clients can run those codes through here to recover the real cause.
+    pub fn from_framework_code(code: u32) -> Option<Self> {
+        const NOT_REGISTERED: [u32; 3] = [
+            ErrorCode::AccountNotInitialized as u32,
+            ErrorCode::AccountDiscriminatorNotFound as u32,
+            ErrorCode::AccountOwnedByWrongProgram as u32,
+        ];
+        NOT_REGISTERED
+            .contains(&code)
+            .then_some(RateLimiterError::ClientNotRegistered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_variant_maps_back_from_its_code() {
+        for (index, err) in RateLimiterError::ALL.into_iter().enumerate() {
+            assert_eq!(u32::from(err), ERROR_CODE_OFFSET + index as u32);
+            let code = u32::from(err);
+            assert_eq!(RateLimiterError::from_code(code).map(u32::from), Some(code));
+        }
+        // A variant missing from ALL would leave the last one's code wrong
+        assert_eq!(
+            RateLimiterError::ALL.last().copied().map(u32::from),
+            Some(u32::from(RateLimiterError::CircuitBreakerTripped))
+        );
+    }
+
+    #[test]
+    fn other_codes_are_not_ours() {
+        let past_the_end = ERROR_CODE_OFFSET + RateLimiterError::ALL.len() as u32;
+        let framework = [
+            ErrorCode::InstructionMissing as u32,
+            ErrorCode::ConstraintHasOne as u32,
+            ErrorCode::AccountNotInitialized as u32,
+            ErrorCode::Deprecated as u32,
+        ];
+        for code in [0, 1, ERROR_CODE_OFFSET - 1, past_the_end]
+            .into_iter()
+            .chain(framework)
+        {
+            assert!(RateLimiterError::from_code(code).is_none(), "{}", code);
+        }
+    }
+}
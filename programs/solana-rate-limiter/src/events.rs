@@ -0,0 +1,297 @@
+use anchor_lang::prelude::*;
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WindowClosed {
+    pub owner: Pubkey,
+    pub closed_window_start: i64,
+    pub closed_count: u64,
+    pub new_window_start: i64,
+    pub skipped_windows: u64,   // full windows that elapsed with no activity
+    pub label: String,          // the owner's label, "" if unset
+    pub event_tag: [u8; 16],    // the bucket's event tag, zeros if unset
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConsumeRejected {
+    pub owner: Pubkey,
+    pub reason_code: u8,        // see REJECT_* in constants.rs
+    pub request_count: u64,
+    pub violations: u64,        // stored decayed count plus this rejection; not kept when the consume fails
+    pub label: String,
+    pub event_tag: [u8; 16],
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    pub total_clients: u64,
+    pub blocked_clients: u64,
+    pub total_requests: u64,
+    pub total_saturated: bool,
+    pub window_rollovers: u64,
+    pub timestamp: i64,
+    pub slot: u64,
+    pub bucket_owner: Option<Pubkey>,       // set when a bucket is passed in remaining accounts
+    pub bucket_request_count: Option<u64>,
+    pub bucket_total_requests: Option<u64>,
+    pub bucket_total_saturated: Option<bool>,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DynamicLimitSet {
+    pub feeder: Pubkey,
+    pub requested_max: u64,
+    pub applied_max: u64,       // after clamping to the admin's bounds
+    pub valid_until: i64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdminRecoveryStarted {
+    pub current_admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub eta: i64,               // earliest finalize_admin_recovery
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdminRecoveryCancelled {
+    pub admin: Pubkey,
+    pub new_admin: Pubkey,      // the recovery that was dropped
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdminRecoveryFinalized {
+    pub old_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BucketTransferred {
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub total_requests: u64,    // lifetime total carried over
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BucketCollected {
+    pub owner: Pubkey,
+    pub cranker: Pubkey,
+    pub bounty: u64,            // lamports paid to the cranker
+    pub refunded: u64,          // lamports returned to the owner
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppealFiled {
+    pub client: Pubkey,
+    pub bond_lamports: u64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppealResolved {
+    pub client: Pubkey,
+    pub approved: bool,         // refunded and unblocked; otherwise forfeited
+    pub bond_lamports: u64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PauseChanged {
+    pub paused: bool,
+    pub paused_at: i64,         // 0 once unpaused
+    pub reason: u8,             // PAUSE_REASON_*
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuotaOffered {
+    pub seller: Pubkey,
+    pub amount: u64,
+    pub price_lamports: u64,
+    pub window_start: i64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OfferAccepted {
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub amount: u64,
+    pub price_lamports: u64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OfferCancelled {
+    pub seller: Pubkey,
+    pub released: u64,          // units handed back to the seller's window; 0 once it lapsed
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PriorityConsumed {
+    pub owner: Pubkey,
+    pub source: u8,             // CONSUME_SOURCE_* the unit was paid from
+    pub burst_credits: u64,     // credits left
+    pub priority_count: u64,    // units priority consumes paid with credits this window
+    pub event_tag: [u8; 16],
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExcessSwept {
+    pub accounts: u8,           // accounts that held more than their rent
+    pub lamports: u64,          // total moved to the treasury
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitTripped {
+    pub rejections: u64,        // denials counted in the window that tripped it
+    pub window_start: i64,
+    pub tripped_at: i64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LimitsRebalanced {
+    pub old_max_requests: u64,
+    pub new_max_requests: u64,  // the same as old_max_requests if the interval changed nothing
+    pub accepted: u64,          // units consumed during the interval
+    pub denied: u64,            // gate_check denials during it
+    pub rejection_bps: u64,
+    pub quiet_streak: u8,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SponsorPoolFunded {
+    pub lamports: u64,
+    pub available: u64,         // spendable balance after the deposit, above the pool's rent
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientSponsored {
+    pub client: Pubkey,
+    pub lamports: u64,          // rent moved from the pool into the bucket
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SponsorPoolWithdrawn {
+    pub lamports: u64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RentToppedUp {
+    pub account: Pubkey,
+    pub lamports: u64,          // 0 if the account was already rent-exempt
+}
+
+// The snapshot export_bucket_snapshot returned, kept in the logs for an admin
+// to check an import against
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BucketSnapshotExported {
+    pub owner: Pubkey,
+    pub total_requests: u64,
+    pub violations: u64,
+    pub exported_at: i64,
+    pub hash: [u8; 32],
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestRefunded {
+    pub owner: Pubkey,
+    pub requested: u64,
+    pub refunded: u64,          // after clamping to the window's own units
+    pub attestation_nonce: u64,
+    pub request_count: u64,     // the window's count after the refund
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GracePeriodConsume {
+    pub owner: Pubkey,
+    pub grace_budget: u64,      // grace consumes left in this pause
+    pub grace_ends_at: i64,     // full rejection from here on
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PenaltiesForgiven {
+    pub owner: Pubkey,
+    pub violations: u64,        // cleared, as stored before decay
+    pub idle_since: i64,        // the bucket's last accepted consume
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeaderboardUpdated {
+    pub owner: Pubkey,
+    pub total: u64,             // total_requests as submitted
+    pub rank: u8,               // 0 is the top
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorityRotationScheduled {
+    pub kind: u8,               // AUTHORITY_*
+    pub old_key: Pubkey,        // accepted until effective_at
+    pub new_key: Pubkey,        // accepted from effective_at
+    pub effective_at: i64,
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorityRotationCancelled {
+    pub kind: u8,
+    pub new_key: Pubkey,        // the key that will not take over
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BucketRecovered {
+    pub owner: Pubkey,
+    pub delegate: Pubkey,
+    pub lamports: u64,          // the bucket's balance, sponsored rent excluded
+    pub idle_since: i64,        // the bucket's last accepted consume, or its registration
+}
+
+// Only buckets with an event tag emit this, so untagged consumes log no more than
+// they did
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BucketConsumed {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub request_count: u64,     // used in the window after this consume
+    pub event_tag: [u8; 16],
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BucketBlocked {
+    pub owner: Pubkey,
+    pub label: String,
+    pub event_tag: [u8; 16],
+}
+
+#[event]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventTagSet {
+    pub owner: Pubkey,
+    pub old_tag: [u8; 16],
+    pub event_tag: [u8; 16],
+}
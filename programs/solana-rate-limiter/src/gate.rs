@@ -0,0 +1,155 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::get_return_data;
+
+use crate::errors::RateLimiterError;
+
+// What gate_check returns to its caller
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GateResult {
+    pub allowed: bool,      // the request was consumed
+    pub remaining: u64,     // consumes still possible in this window, 0 when denied
+    pub retry_after: i64,   // seconds until the window rolls over, 0 when allowed
+}
+
+// What consume_and_report returns to its caller: the window as the consume left it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConsumeReport {
+    pub used: u64,          // request_count, this consume included
+    pub max: u64,           // the window's allowance, bought quota included
+    pub remaining: u64,     // consumes still possible in this window, as in GateResult
+    pub window_end: i64,    // when the window rolls over
+}
+
+// What get_usage_histogram returns: windows closed across all clients, by how much
+// of their allowance they used
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UsageHistogram {
+    pub under_25: u64,
+    pub under_50: u64,
+    pub under_75: u64,
+    pub full: u64,          // 75% and over
+    pub rejected: u64,      // a gate_check was denied in it, however full it got
+}
+
+// What get_quota returns: a bucket as dashboards show it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Quota {
+    pub owner: Pubkey,
+    pub remaining: u64,     // consumes still possible in this window
+    pub resets_in: i64,     // seconds until the window rolls over
+    pub total_requests: u64,
+    pub violations: u64,    // decayed to the time of the call
+    pub label: String,      // the owner's label, "" if unset
+}
+
+// Return data of a ProgramPaused rejection. For a scheduled pause, paused_at is
+// the start of its window.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PauseInfo {
+    pub paused_at: i64,
+    pub reason: u8,         // PAUSE_REASON_*
+}
+
+// What get_config returns: the limits a wallet consume would be held to at the
+// time of the call, with every layer resolved, and whether anything stops it.
+// blocked and frozen are the passed bucket's, false without one.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EffectiveConfig {
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub limit_source: u8,   // LIMIT_SOURCE_* max_requests came from
+    pub config_preset: u8,  // CONFIG_PRESET_* the base limits came from
+    pub config_epoch: u64,
+    pub paused: bool,       // an emergency or scheduled pause is in force
+    pub pause: PauseInfo,   // zeroed while not paused
+    pub blocked: bool,
+    pub frozen: bool,
+}
+
+// What get_deployment_info returns, for clients checking at startup that they know
+// the deployment they are talking to
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeploymentInfo {
+    pub program_version: [u16; 3], // major, minor, patch of the crate the program was built from
+    pub features_bitmask: u64,  // FEATURE_* bits the config has on
+    pub limit_algorithm: u8,    // LIMIT_ALGORITHM_*
+    pub config_epoch: u64,
+}
+
+pub const PROGRAM_VERSION: [u16; 3] = parse_version(env!("CARGO_PKG_VERSION"));
+
+// major.minor.patch, any pre-release or build suffix dropped. Runs at compile
+// time, so a version it cannot read fails the build.
+const fn parse_version(version: &str) -> [u16; 3] {
+    let bytes = version.as_bytes();
+    let mut parts = [0u16; 3];
+    let (mut part, mut i) = (0, 0);
+    while i < bytes.len() {
+        match bytes[i] {
+            b'.' if part < 2 => part += 1,
+            digit @ b'0'..=b'9' => parts[part] = parts[part] * 10 + (digit - b'0') as u16,
+            b'-' | b'+' => break,
+            _ => panic!("CARGO_PKG_VERSION is not major.minor.patch"),
+        }
+        i += 1;
+    }
+    parts
+}
+
+// What preview_registration returns: what registering would cost a client and
+// what it would get, before it pays anything
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegistrationPreview {
+    pub max_requests: u64,      // the limits a consume would be held to right now
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub registration_fee_lamports: u64, // to the treasury, kept
+    pub rent_lamports: u64,     // the bucket's rent, plus the payer's PayerMeta on its first registration
+    pub registration_open: bool, // register_client would go through right now
+    pub requires_allowlist: bool, // the client is a program account, so consumes come by CPI from an allowed caller
+    pub wallet_consumes_allowed: bool, // program-keyed mode would still let the bucket be consumed from
+}
+
+// What estimate_config_impact returns
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConfigImpact {
+    pub evaluated: u64,     // buckets looked at
+    pub would_reject: u64,  // of those, buckets whose last window went over the new max
+}
+
+// For caller programs: reads the GateResult a gate_check CPI left in return data.
+// Fails if the last return data was not set by this program.
+pub fn read_gate_result() -> Result<GateResult> {
+    let (program_id, data) = get_return_data().ok_or(RateLimiterError::NoGateResult)?;
+    require_keys_eq!(program_id, crate::ID, RateLimiterError::NoGateResult);
+    GateResult::try_from_slice(&data).map_err(|_| error!(RateLimiterError::NoGateResult))
+}
+
+// The same for a consume_and_report CPI, saving the caller a reload of the bucket
+pub fn read_consume_report() -> Result<ConsumeReport> {
+    let (program_id, data) = get_return_data().ok_or(RateLimiterError::NoConsumeReport)?;
+    require_keys_eq!(program_id, crate::ID, RateLimiterError::NoConsumeReport);
+    ConsumeReport::try_from_slice(&data).map_err(|_| error!(RateLimiterError::NoConsumeReport))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_parse_to_their_triple() {
+        assert_eq!(parse_version("0.1.0"), [0, 1, 0]);
+        assert_eq!(parse_version("12.0.345"), [12, 0, 345]);
+        assert_eq!(parse_version("1.2.3-rc.1"), [1, 2, 3]);
+        assert_eq!(parse_version("1.2.3+build.9"), [1, 2, 3]);
+        // Cargo's own split of the crate version
+        let cargo = [
+            env!("CARGO_PKG_VERSION_MAJOR"),
+            env!("CARGO_PKG_VERSION_MINOR"),
+            env!("CARGO_PKG_VERSION_PATCH"),
+        ]
+        .map(|part| part.parse::<u16>().unwrap());
+        assert_eq!(PROGRAM_VERSION, cargo);
+    }
+}
@@ -0,0 +1,108 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+
+use crate::constants::HOOK_AUTHORITY_SEED;
+use crate::errors::RateLimiterError;
+use crate::pda;
+use crate::state::GlobalConfig;
+
+// CPI notifications to GlobalConfig.hook_program. A hook program implements
+//
+//     pub fn on_rate_limit_event(ctx, client: Pubkey, reason_code: u8) -> Result<()>
+//
+// with accounts [hook_authority (signer), bucket (read-only), ...extra]. The
+// discriminator below is Anchor's for that name, so an Anchor hook needs nothing
+// else. Callers of the notifying instructions pass, as remaining accounts, the hook
+// program, the hook authority PDA and then whatever extra accounts the hook wants.
+//
+// The failure policy only covers missing hook accounts: skipped by default, an
+// error with CONFIG_FLAG_HOOK_PROPAGATE. A hook that runs and fails always fails
+// the transaction, because the runtime does not let a program recover from a
+// failed CPI.
+
+// sha256("global:on_rate_limit_event")[..8]
+pub const HOOK_DISCRIMINATOR: [u8; 8] = [78, 213, 155, 53, 191, 6, 148, 236];
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct HookEvent {
+    pub client: Pubkey,
+    pub reason_code: u8,
+}
+
+pub fn hook_instruction_data(event: &HookEvent) -> Result<Vec<u8>> {
+    let mut data = HOOK_DISCRIMINATOR.to_vec();
+    event.serialize(&mut data)?;
+    Ok(data)
+}
+
+pub fn notify<'info>(
+    config: &GlobalConfig,
+    bucket: &AccountInfo<'info>,
+    event: HookEvent,
+    remaining: &[AccountInfo<'info>],
+) -> Result<()> {
+    if !config.has_hook() {
+        return Ok(());
+    }
+    let (authority_key, bump) = pda::hook_authority_address();
+    let (program, authority, extra) = match remaining {
+        [program, authority, extra @ ..]
+            if program.key() == config.hook_program
+                && program.executable
+                && authority.key() == authority_key =>
+        {
+            (program, authority, extra)
+        }
+        _ => {
+            require!(!config.hook_propagates(), RateLimiterError::HookAccountsMissing);
+            msg!("Hook {} skipped: accounts not supplied", config.hook_program);
+            return Ok(());
+        }
+    };
+
+    let mut metas = vec![
+        AccountMeta::new_readonly(authority_key, true),
+        AccountMeta::new_readonly(bucket.key(), false),
+    ];
+    metas.extend(extra.iter().map(|info| AccountMeta {
+        pubkey: info.key(),
+        is_signer: info.is_signer,
+        is_writable: info.is_writable,
+    }));
+    let ix = Instruction {
+        program_id: config.hook_program,
+        accounts: metas,
+        data: hook_instruction_data(&event)?,
+    };
+
+    let mut infos = vec![authority.clone(), bucket.clone()];
+    infos.extend(extra.iter().cloned());
+    infos.push(program.clone());
+    invoke_signed(&ix, &infos, &[&[HOOK_AUTHORITY_SEED, &[bump]]])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::hash::hash;
+
+    #[test]
+    fn discriminator_is_anchors_for_the_hook_name() {
+        assert_eq!(
+            HOOK_DISCRIMINATOR[..],
+            hash(b"global:on_rate_limit_event").to_bytes()[..8]
+        );
+    }
+
+    #[test]
+    fn data_is_discriminator_then_borsh_args() {
+        let client = Pubkey::new_unique();
+        let data = hook_instruction_data(&HookEvent { client, reason_code: 4 }).unwrap();
+        assert_eq!(data.len(), 8 + 32 + 1);
+        assert_eq!(data[..8], HOOK_DISCRIMINATOR);
+        assert_eq!(data[8..40], client.to_bytes());
+        assert_eq!(data[40], 4);
+    }
+}
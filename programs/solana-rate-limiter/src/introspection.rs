@@ -0,0 +1,147 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::Discriminator;
+
+use crate::instruction::{ConsumeAmount, ConsumeRequest, ConsumeV2};
+
+// Position of `client` in the ConsumeRequest accounts (global_config,
+// global_stats, client_bucket, client). Pinned by the tests below.
+const CONSUME_CLIENT_INDEX: usize = 3;
+
+// Whether `ix` is a top-level consume_request, consume_amount or consume_v2 by
+// `client`.
+// Only instructions that name this program count: a consume reached by CPI sits
+// inside another program's instruction and is not listed in the sysvar at all.
+pub fn is_consume_by(ix: &Instruction, client: &Pubkey) -> bool {
+    ix.program_id == crate::ID
+        && (ix.data.starts_with(ConsumeRequest::DISCRIMINATOR)
+            || ix.data.starts_with(ConsumeAmount::DISCRIMINATOR)
+            || ix.data.starts_with(ConsumeV2::DISCRIMINATOR))
+        && ix
+            .accounts
+            .get(CONSUME_CLIENT_INDEX)
+            .is_some_and(|meta| meta.pubkey == *client && meta.is_signer)
+}
+
+// Whether any of the instructions before the current one is a consume by
+// `client`. Instructions after it never count, and any one consume is enough.
+pub fn consumed_before<I>(earlier: I, client: &Pubkey) -> bool
+where
+    I: IntoIterator<Item = Instruction>,
+{
+    earlier.into_iter().any(|ix| is_consume_by(&ix, client))
+}
+
+// Whether a CPI into a wallet consume may proceed. `caller` is the immediate
+// caller's program id when it can be known, which is only one level down: the
+// sysvar names the top-level program, and deeper stacks leave the intermediate
+// callers anonymous, so they are refused. `entry` is the allowlist entry passed in.
+pub fn caller_allowed(caller: Option<Pubkey>, entry: Option<Pubkey>) -> bool {
+    matches!((caller, entry), (Some(caller), Some(entry)) if caller == entry)
+}
+
+// Whether the caller program signed for `client` itself, making it one of the
+// caller's PDAs. `caller_ix` is the caller's top-level instruction. A CPI can only
+// hand on accounts that instruction lists, and the sysvar marks the ones that
+// signed the transaction, so a client listed there without that mark got its
+// signature from invoke_signed. Only the caller can sign that way one level down.
+pub fn signed_by_caller(caller_ix: &Instruction, client: &Pubkey) -> bool {
+    !caller_ix.accounts.iter().any(|meta| meta.pubkey == *client && meta.is_signer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::instruction::AccountMeta;
+    use anchor_lang::InstructionData;
+
+    fn consume_ix(client: Pubkey, data: Vec<u8>) -> Instruction {
+        Instruction {
+            program_id: crate::ID,
+            accounts: vec![
+                AccountMeta::new_readonly(Pubkey::new_unique(), false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(client, true),
+            ],
+            data,
+        }
+    }
+
+    #[test]
+    fn client_index_matches_the_accounts_struct() {
+        let metas = crate::accounts::ConsumeRequest {
+            global_config: Pubkey::new_unique(),
+            global_stats: Some(Pubkey::new_unique()),
+            client_bucket: Pubkey::new_unique(),
+            client: Pubkey::new_unique(),
+            caller_entry: None,
+            instructions: None,
+            schedule: None,
+            owner_denylist: None,
+            pause_grace: None,
+        };
+        let client = metas.client;
+        let listed = metas.to_account_metas(None);
+        assert_eq!(listed[CONSUME_CLIENT_INDEX].pubkey, client);
+        assert!(listed[CONSUME_CLIENT_INDEX].is_signer);
+    }
+
+    #[test]
+    fn finds_the_clients_consume_among_several() {
+        let client = Pubkey::new_unique();
+        let earlier = vec![
+            consume_ix(Pubkey::new_unique(), ConsumeRequest {}.data()),
+            consume_ix(client, ConsumeAmount { amount: 3 }.data()),
+        ];
+        assert!(consumed_before(earlier, &client));
+    }
+
+    #[test]
+    fn counts_consume_v2() {
+        let client = Pubkey::new_unique();
+        let args = crate::ConsumeArgs { nonce: Some(7), ..Default::default() };
+        assert!(consumed_before(vec![consume_ix(client, ConsumeV2 { args }.data())], &client));
+    }
+
+    #[test]
+    fn ignores_other_clients_programs_and_instructions() {
+        let client = Pubkey::new_unique();
+        let other_client = consume_ix(Pubkey::new_unique(), ConsumeRequest {}.data());
+        let mut other_program = consume_ix(client, ConsumeRequest {}.data());
+        other_program.program_id = Pubkey::new_unique();
+        let other_instruction = consume_ix(client, crate::instruction::GateCheck {}.data());
+        let mut unsigned = consume_ix(client, ConsumeRequest {}.data());
+        unsigned.accounts[CONSUME_CLIENT_INDEX].is_signer = false;
+
+        assert!(!consumed_before(
+            vec![other_client, other_program, other_instruction, unsigned],
+            &client
+        ));
+        assert!(!consumed_before(Vec::new(), &client));
+    }
+
+    #[test]
+    fn clients_that_did_not_sign_the_transaction_are_the_callers() {
+        let wallet = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let caller_ix = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new_readonly(wallet, true), AccountMeta::new_readonly(vault, false)],
+            data: Vec::new(),
+        };
+        assert!(!signed_by_caller(&caller_ix, &wallet));
+        assert!(signed_by_caller(&caller_ix, &vault));
+    }
+
+    #[test]
+    fn caller_needs_its_own_entry() {
+        let caller = Pubkey::new_unique();
+        assert!(caller_allowed(Some(caller), Some(caller)));
+        assert!(!caller_allowed(Some(caller), None));
+        assert!(!caller_allowed(Some(caller), Some(Pubkey::new_unique())));
+        // Unknown caller (nested CPI or no sysvar passed)
+        assert!(!caller_allowed(None, Some(caller)));
+        assert!(!caller_allowed(None, None));
+    }
+}
@@ -0,0 +1,213 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::RateLimiterError;
+use crate::state::{ClientBucket, GlobalConfig};
+
+// Properties every bucket the program writes must satisfy. Per-window limits are
+// deliberately not listed: update_config without reset_windows can lower them
+// under a bucket mid-window, and that bucket is still consistent.
+
+// First violated invariant as an INVARIANT_* code, INVARIANT_OK if none
+pub fn first_violation(bucket: &ClientBucket, config: &GlobalConfig) -> u8 {
+    // Epochs only move forward, and buckets copy the config's
+    if bucket.config_epoch > config.config_epoch {
+        return INVARIANT_EPOCH_AHEAD;
+    }
+    // The flag marks a total pinned at u64::MAX, never a smaller value
+    if bucket.total_saturated() && bucket.total_requests != u64::MAX {
+        return INVARIANT_SATURATION_FLAG;
+    }
+    // Every request in the current window was also added to the lifetime total,
+    // except the units a quota offer took
+    if !bucket.total_saturated()
+        && bucket.total_requests < bucket.request_count.saturating_sub(bucket.escrowed_count)
+    {
+        return INVARIANT_TOTAL_BELOW_WINDOW;
+    }
+    // Priority units are a share of the window's requests
+    if bucket.priority_count > bucket.request_count {
+        return INVARIANT_PRIORITY_ABOVE_WINDOW;
+    }
+    // Escrowed units are another share, never paid with credits
+    if bucket.escrowed_count > bucket.request_count - bucket.priority_count {
+        return INVARIANT_ESCROW_ABOVE_WINDOW;
+    }
+    INVARIANT_OK
+}
+
+pub fn check_invariants(bucket: &ClientBucket, config: &GlobalConfig) -> Result<()> {
+    match first_violation(bucket, config) {
+        INVARIANT_OK => Ok(()),
+        code => {
+            msg!("Bucket {} violates invariant {}", bucket.owner, code);
+            err!(RateLimiterError::InvariantViolated)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{DiscountTier, PendingAuthority, PreviousConfig};
+
+    fn config() -> GlobalConfig {
+        GlobalConfig {
+            version: GLOBAL_CONFIG_VERSION,
+            admin: Pubkey::default(),
+            max_requests: 10,
+            window_seconds: 60,
+            burst_limit: 20,
+            flags: 0,
+            config_epoch: 2,
+            bump: 0,
+            hook_program: Pubkey::default(),
+            limit_feeder: Pubkey::default(),
+            dynamic_floor: 0,
+            dynamic_ceiling: 0,
+            dynamic_max: 0,
+            dynamic_valid_until: 0,
+            council: [Pubkey::default(); 5],
+            council_size: 0,
+            council_threshold: 0,
+            proposal_count: 0,
+            recovery_admin: Pubkey::default(),
+            recovery_eta: 0,
+            scheduled_pause_start: 0,
+            scheduled_pause_end: 0,
+            treasury: Pubkey::default(),
+            registration_fee_lamports: 0,
+            registration_cooldown_seconds: 0,
+            stale_after_seconds: 0,
+            gc_bounty_bps: 0,
+            discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+            discount_tier_count: 0,
+            appeal_bond_lamports: 0,
+            appeal_cooldown_seconds: 0,
+            paused_at: 0,
+            pause_reason: 0,
+            auto_unpause_at: 0,
+            config_preset: 0,
+            violation_halflife_seconds: 0,
+            admin_action_window_seconds: 0,
+            max_admin_actions_per_window: 0,
+            admin_action_window_start: 0,
+            admin_actions_in_window: 0,
+            refund_authority: Pubkey::default(),
+            max_refunds_per_window: 0,
+            max_buckets_per_payer: 0,
+            pause_grace_seconds: 0,
+            pause_grace_budget: 0,
+            grace_budget: 0,
+            inactivity_forgiveness_seconds: 0,
+            min_client_balance_lamports: 0,
+            recovery_inactive_seconds: 0,
+            proration_mode: 0,
+            previous_config: PreviousConfig::default(),
+            denied_owner_programs: 0,
+            pending_authorities: [PendingAuthority::default(); AUTHORITY_KINDS],
+            reserved: [0; 1],
+        }
+    }
+
+    fn bucket() -> ClientBucket {
+        ClientBucket {
+            version: CLIENT_BUCKET_VERSION,
+            owner: Pubkey::default(),
+            request_count: 4,
+            window_start: 1_000,
+            total_requests: 40,
+            flags: 0,
+            violations: 0,
+            violations_updated_at: 0,
+            config_epoch: 2,
+            burst_credits: 3,
+            priority_count: 0,
+            escrowed_count: 0,
+            bonus_requests: 0,
+            last_nonce: 0,
+            nonce_bitmap: 0,
+            last_refund_nonce: 0,
+            refunded_count: 0,
+            registered_by: Pubkey::default(),
+            recovery_delegate: Pubkey::default(),
+            last_request_ts: 1_000,
+            bump: 0,
+            label: [0; 32],
+            event_tag: [0; 16],
+        }
+    }
+
+    #[test]
+    fn consistent_bucket_passes() {
+        assert_eq!(first_violation(&bucket(), &config()), INVARIANT_OK);
+        assert!(check_invariants(&bucket(), &config()).is_ok());
+    }
+
+    #[test]
+    fn older_epoch_is_fine() {
+        let mut b = bucket();
+        b.config_epoch = 0;
+        assert_eq!(first_violation(&b, &config()), INVARIANT_OK);
+    }
+
+    #[test]
+    fn epoch_ahead_of_config_trips() {
+        let mut b = bucket();
+        b.config_epoch = 3;
+        assert_eq!(first_violation(&b, &config()), INVARIANT_EPOCH_AHEAD);
+        assert!(check_invariants(&b, &config()).is_err());
+    }
+
+    #[test]
+    fn saturation_flag_without_max_total_trips() {
+        let mut b = bucket();
+        b.set_total_saturated(true);
+        assert_eq!(first_violation(&b, &config()), INVARIANT_SATURATION_FLAG);
+        b.total_requests = u64::MAX;
+        assert_eq!(first_violation(&b, &config()), INVARIANT_OK);
+    }
+
+    #[test]
+    fn total_below_window_count_trips() {
+        let mut b = bucket();
+        b.total_requests = 3;
+        assert_eq!(first_violation(&b, &config()), INVARIANT_TOTAL_BELOW_WINDOW);
+        b.total_requests = 4;
+        assert_eq!(first_violation(&b, &config()), INVARIANT_OK);
+    }
+
+    #[test]
+    fn priority_units_beyond_the_window_trip() {
+        let mut b = bucket();
+        b.priority_count = 5;
+        assert_eq!(first_violation(&b, &config()), INVARIANT_PRIORITY_ABOVE_WINDOW);
+        b.priority_count = 4;
+        assert_eq!(first_violation(&b, &config()), INVARIANT_OK);
+    }
+
+    #[test]
+    fn escrowed_units_are_outside_the_total_but_inside_the_window() {
+        let mut b = bucket();
+        b.escrowed_count = 2;
+        b.total_requests = 2;
+        assert_eq!(first_violation(&b, &config()), INVARIANT_OK);
+        b.total_requests = 1;
+        assert_eq!(first_violation(&b, &config()), INVARIANT_TOTAL_BELOW_WINDOW);
+
+        b.total_requests = 40;
+        b.priority_count = 1;
+        b.escrowed_count = 4;
+        assert_eq!(first_violation(&b, &config()), INVARIANT_ESCROW_ABOVE_WINDOW);
+        b.escrowed_count = 3;
+        assert_eq!(first_violation(&b, &config()), INVARIANT_OK);
+    }
+
+    #[test]
+    fn count_over_lowered_limits_is_not_a_violation() {
+        let mut b = bucket();
+        b.request_count = 30;
+        b.burst_credits = 15;
+        assert_eq!(first_violation(&b, &config()), INVARIANT_OK);
+    }
+}
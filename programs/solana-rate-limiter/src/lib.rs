@@ -1,14 +1,71 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_lang::solana_program::instruction::{get_stack_height, Instruction, TRANSACTION_LEVEL_STACK_HEIGHT};
+use anchor_lang::solana_program::program::set_return_data;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
 
 declare_id!("7KoXq7yEB7HccYeCKu9559v38bArHYpKmnp42gYAUpnc");
 
+#[cfg(feature = "client")]
+pub mod client;
 pub mod constants;
 pub mod errors;
+pub mod events;
+pub mod gate;
+pub mod hook;
+pub mod introspection;
+pub mod invariants;
+pub mod logic;
+pub mod math;
+pub mod migrations;
+pub mod nonce;
+pub mod pda;
+pub mod snapshot;
 pub mod state;
+pub mod validation;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod window;
 
-use state::{GlobalConfig, ClientBucket};
-use constants::{GLOBAL_CONFIG_SEED, CLIENT_BUCKET_SEED};
+use state::{
+    GlobalConfig, ClientBucket, GlobalStats, FastBucket, ShardParent, ProgramBucket, CallerAllowlist, Proposal,
+    AdminAction, Schedule, ScheduleEntry, PayerMeta, ClientNote, DiscountTier, Appeal, Offer,
+    SponsorPool, RebalancePolicy, RebalanceParams, Leaderboard, PreviousConfig, ProgramDenylist, PendingAuthority,
+    SnapshotRecord, PauseGrace,
+};
+use constants::*;
 use errors::RateLimiterError;
+use events::{
+    WindowClosed, ConsumeRejected, StatsSnapshot, DynamicLimitSet, AdminRecoveryStarted,
+    AdminRecoveryCancelled, AdminRecoveryFinalized, CircuitTripped, BucketTransferred,
+    BucketCollected, ExcessSwept, PriorityConsumed, AppealFiled, AppealResolved, QuotaOffered,
+    OfferAccepted, OfferCancelled, PauseChanged, SponsorPoolFunded, ClientSponsored,
+    SponsorPoolWithdrawn, RentToppedUp, LimitsRebalanced, BucketSnapshotExported, RequestRefunded,
+    GracePeriodConsume, PenaltiesForgiven, LeaderboardUpdated, BucketRecovered, AuthorityRotationScheduled,
+    AuthorityRotationCancelled, BucketConsumed, BucketBlocked, EventTagSet,
+};
+use logic::{RefundRefusal, Rejection};
+use gate::{
+    ConfigImpact, ConsumeReport, DeploymentInfo, EffectiveConfig, GateResult, Quota, RegistrationPreview,
+    UsageHistogram,
+};
+use snapshot::BucketSnapshot;
+
+// Formatted logs on the consume path cost more CU than the rate limiting itself.
+// They are compiled in only with the `verbose-logs` feature; default builds log
+// numbers with sol_log_64 and rely on events for everything else. Those builds
+// still take the arguments by reference, so a value only logged is not unused.
+macro_rules! verbose_msg {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "verbose-logs")]
+        msg!($($arg)*);
+        #[cfg(not(feature = "verbose-logs"))]
+        let _ = format_args!($($arg)*);
+    };
+}
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct RateLimiterConfig {
@@ -17,176 +74,3979 @@ pub struct RateLimiterConfig {
     pub burst_limit: u64,
 }
 
+// initialize_with_preset's policy. The named ones take their limits from
+// constants.rs; Custom is initialize with the limits as given.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub enum ConfigPreset {
+    Strict,
+    Standard,
+    Lenient,
+    Custom(RateLimiterConfig),
+}
+
+impl ConfigPreset {
+    pub fn limits(&self) -> RateLimiterConfig {
+        let (max_requests, window_seconds, burst_limit) = match self {
+            ConfigPreset::Strict => (STRICT_MAX_REQUESTS, STRICT_WINDOW_SECONDS, STRICT_BURST_LIMIT),
+            ConfigPreset::Standard => (STANDARD_MAX_REQUESTS, STANDARD_WINDOW_SECONDS, STANDARD_BURST_LIMIT),
+            ConfigPreset::Lenient => (LENIENT_MAX_REQUESTS, LENIENT_WINDOW_SECONDS, LENIENT_BURST_LIMIT),
+            ConfigPreset::Custom(limits) => return limits.clone(),
+        };
+        RateLimiterConfig { max_requests, window_seconds, burst_limit }
+    }
+
+    // What GlobalConfig.config_preset records
+    pub fn id(&self) -> u8 {
+        match self {
+            ConfigPreset::Strict => CONFIG_PRESET_STRICT,
+            ConfigPreset::Standard => CONFIG_PRESET_STANDARD,
+            ConfigPreset::Lenient => CONFIG_PRESET_LENIENT,
+            ConfigPreset::Custom(_) => CONFIG_PRESET_CUSTOM,
+        }
+    }
+}
+
+// consume_v2's options. New ones are added here as fields, so the instruction
+// keeps its name and argument list; the default is a plain consume_request.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ConsumeArgs {
+    pub amount: u64,
+    pub deadline: Option<i64>,  // refuse once the clock is past this unix time
+    pub nonce: Option<u64>,     // unused and at most 63 below the bucket's highest, see nonce.rs
+    pub priority: bool,         // pay with a burst credit first, as consume_priority does
+}
+
+impl Default for ConsumeArgs {
+    fn default() -> Self {
+        Self { amount: 1, deadline: None, nonce: None, priority: false }
+    }
+}
+
 #[program]
 pub mod solana_rate_limiter {
     use super::*;
 
     pub fn initialize(ctx: Context<Initialize>, config: RateLimiterConfig) -> Result<()> {
-        require!(config.max_requests > 0, RateLimiterError::InvalidConfig);
-        require!(config.window_seconds > 0, RateLimiterError::InvalidConfig);
-        require!(config.burst_limit >= config.max_requests, RateLimiterError::InvalidConfig);
+        init_config(ctx, config, CONFIG_PRESET_CUSTOM)
+    }
 
-        let gc = &mut ctx.accounts.global_config;
-        gc.admin = ctx.accounts.admin.key();
-        gc.max_requests = config.max_requests;
-        gc.window_seconds = config.window_seconds;
-        gc.burst_limit = config.burst_limit;
-        gc.is_paused = false;
-        gc.bump = ctx.bumps.global_config;
+    // initialize without working out burst math: one of the policies in
+    // constants.rs, recorded on the config, or Custom limits
+    pub fn initialize_with_preset(ctx: Context<Initialize>, preset: ConfigPreset) -> Result<()> {
+        init_config(ctx, preset.limits(), preset.id())
+    }
+
+    pub fn initialize_stats(ctx: Context<InitializeStats>, snapshot_interval_seconds: i64) -> Result<()> {
+        require!(snapshot_interval_seconds > 0, RateLimiterError::InvalidConfig);
 
-        msg!("Rate limiter initialized. Max: {} req / {}s", config.max_requests, config.window_seconds);
+        let stats = &mut ctx.accounts.global_stats;
+        stats.total_clients = 0;
+        stats.blocked_clients = 0;
+        stats.total_requests = 0;
+        stats.total_saturated = false;
+        stats.window_rollovers = 0;
+        stats.snapshot_interval_seconds = snapshot_interval_seconds;
+        stats.last_snapshot_at = 0;
+        stats.bump = ctx.bumps.global_stats;
+        stats.circuit_breaker_threshold = 0;
+        stats.circuit_breaker_window_seconds = 0;
+        stats.rejection_window_start = 0;
+        stats.rejection_count = 0;
+        stats.total_denials = 0;
+        stats.usage_histogram = [0; USAGE_HISTOGRAM_BUCKETS];
+
+        msg!("Global stats initialized. Snapshot interval: {}s", snapshot_interval_seconds);
         Ok(())
     }
 
     pub fn register_client(ctx: Context<RegisterClient>) -> Result<()> {
-        require!(!ctx.accounts.global_config.is_paused, RateLimiterError::ProgramPaused);
-
-        let bucket = &mut ctx.accounts.client_bucket;
         let clock = Clock::get()?;
+        require_unpaused(&ctx.accounts.global_config, clock.unix_timestamp)?;
+        // One bucket per client, or the kinds would add up to more than its allowance
+        require!(
+            ctx.accounts.fast_bucket.data_is_empty() && ctx.accounts.shard_parent.data_is_empty(),
+            RateLimiterError::BucketKindConflict
+        );
+        charge_registration(
+            &ctx.accounts.global_config,
+            &mut ctx.accounts.payer_meta,
+            &ctx.accounts.payer,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.system_program,
+            clock.unix_timestamp,
+            ctx.bumps.payer_meta,
+        )?;
 
+        let bucket = &mut ctx.accounts.client_bucket;
+        bucket.version = CLIENT_BUCKET_VERSION;
         bucket.owner = ctx.accounts.client.key();
         bucket.request_count = 0;
         bucket.window_start = clock.unix_timestamp;
         bucket.total_requests = 0;
-        bucket.is_blocked = false;
+        bucket.flags = 0;
+        bucket.violations = 0;
+        bucket.violations_updated_at = 0;
+        bucket.config_epoch = ctx.accounts.global_config.config_epoch;
+        bucket.burst_credits = 0;
+        bucket.priority_count = 0;
+        bucket.escrowed_count = 0;
+        bucket.bonus_requests = 0;
+        bucket.last_nonce = 0;
+        bucket.nonce_bitmap = 0;
+        bucket.last_refund_nonce = 0;
+        bucket.refunded_count = 0;
+        bucket.registered_by = ctx.accounts.payer.key();
+        bucket.last_request_ts = clock.unix_timestamp;
         bucket.bump = ctx.bumps.client_bucket;
+        bucket.label = [0; 32];
+
+        let stats = &mut ctx.accounts.global_stats;
+        stats.total_clients = stats.total_clients.saturating_add(1);
+        debug_assert_invariants(bucket, &ctx.accounts.global_config);
 
         msg!("Client registered: {}", ctx.accounts.client.key());
         Ok(())
     }
 
+    // Registers a zero-copy bucket instead: for relayers and other clients whose
+    // consume volume makes Borsh round-trips of the bucket worth avoiding
+    pub fn register_fast_client(ctx: Context<RegisterFastClient>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require_unpaused(&ctx.accounts.global_config, now)?;
+        require!(
+            ctx.accounts.client_bucket.data_is_empty() && ctx.accounts.shard_parent.data_is_empty(),
+            RateLimiterError::BucketKindConflict
+        );
+        charge_registration(
+            &ctx.accounts.global_config,
+            &mut ctx.accounts.payer_meta,
+            &ctx.accounts.payer,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.system_program,
+            now,
+            ctx.bumps.payer_meta,
+        )?;
+
+        let mut bucket = ctx.accounts.fast_bucket.load_init()?;
+        bucket.owner = ctx.accounts.client.key();
+        bucket.window_start = now;
+        bucket.config_epoch = ctx.accounts.global_config.config_epoch;
+        bucket.bump = ctx.bumps.fast_bucket;
+        // Every other field starts zeroed
+
+        let stats = &mut ctx.accounts.global_stats;
+        stats.total_clients = stats.total_clients.saturating_add(1);
+
+        msg!("Fast client registered: {}", ctx.accounts.client.key());
+        Ok(())
+    }
+
+    // Splits a hot client over `shard_count` buckets so its consumes can run in
+    // parallel instead of queuing on one write lock. The shard PDAs
+    // ["client-shard", client, index] are passed in order as remaining accounts
+    // and created here.
+    pub fn register_sharded<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RegisterSharded<'info>>,
+        shard_count: u8,
+    ) -> Result<()> {
+        let gc = &ctx.accounts.global_config;
+        let now = Clock::get()?.unix_timestamp;
+        require_unpaused(gc, now)?;
+        require!((2..=MAX_SHARDS).contains(&shard_count), RateLimiterError::InvalidShardCount);
+        require!(
+            ctx.remaining_accounts.len() == shard_count as usize,
+            RateLimiterError::InvalidShardCount
+        );
+        require!(
+            ctx.accounts.client_bucket.data_is_empty() && ctx.accounts.fast_bucket.data_is_empty(),
+            RateLimiterError::BucketKindConflict
+        );
+        charge_registration(
+            gc,
+            &mut ctx.accounts.payer_meta,
+            &ctx.accounts.payer,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.system_program,
+            now,
+            ctx.bumps.payer_meta,
+        )?;
+
+        let client = ctx.accounts.client.key();
+        let rent = Rent::get()?.minimum_balance(8 + ClientBucket::INIT_SPACE);
+
+        for (index, info) in ctx.remaining_accounts.iter().enumerate() {
+            let index = [index as u8];
+            let (expected, bump) = pda::client_shard_address(&client, index[0]);
+            require_keys_eq!(info.key(), expected, RateLimiterError::InvalidShard);
+
+            system_program::create_account(
+                CpiContext::new_with_signer(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::CreateAccount {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: info.clone(),
+                    },
+                    &[&[CLIENT_SHARD_SEED, client.as_ref(), &index, &[bump]]],
+                ),
+                rent,
+                (8 + ClientBucket::INIT_SPACE) as u64,
+                &crate::ID,
+            )?;
+
+            let shard = ClientBucket {
+                version: CLIENT_BUCKET_VERSION,
+                owner: client,
+                request_count: 0,
+                window_start: now,
+                total_requests: 0,
+                flags: 0,
+                violations: 0,
+                violations_updated_at: 0,
+                config_epoch: gc.config_epoch,
+                burst_credits: 0,
+                priority_count: 0,
+                escrowed_count: 0,
+                bonus_requests: 0,
+                last_nonce: 0,
+                nonce_bitmap: 0,
+                last_refund_nonce: 0,
+                refunded_count: 0,
+                registered_by: ctx.accounts.payer.key(),
+                recovery_delegate: Pubkey::default(),
+                last_request_ts: now,
+                bump,
+                label: [0; 32],
+                event_tag: [0; 16],
+            };
+            shard.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+        }
+
+        let parent = &mut ctx.accounts.shard_parent;
+        parent.owner = client;
+        parent.shard_count = shard_count;
+        parent.window_requests = 0;
+        parent.total_requests = 0;
+        parent.total_saturated = false;
+        parent.last_aggregated_at = 0;
+        parent.bump = ctx.bumps.shard_parent;
+
+        let stats = &mut ctx.accounts.global_stats;
+        stats.total_clients = stats.total_clients.saturating_add(1);
+
+        msg!("Sharded client registered: {} ({} shards)", client, shard_count);
+        Ok(())
+    }
+
     pub fn consume_request(ctx: Context<ConsumeRequest>) -> Result<()> {
+        ctx.accounts.require_allowed_caller()?;
+        ctx.accounts.require_min_balance()?;
+        ctx.accounts.require_circuit_closed()?;
+        let now = Clock::get()?.unix_timestamp;
+        let grace = ctx.accounts.take_pause_grace(now);
+        consume(
+            grace.as_ref().unwrap_or(&ctx.accounts.global_config),
+            ctx.accounts.schedule.as_deref(),
+            &mut ctx.accounts.client_bucket,
+            ctx.accounts.global_stats.as_deref_mut(),
+            now,
+            1,
+        )
+    }
+
+    // consume_request that returns the bucket's window afterwards, for caller
+    // programs to read with gate::read_consume_report instead of reloading the
+    // bucket. Anchor sets the return data once the handler has returned, so no
+    // later step of ours can overwrite it; the caller's own next CPI would, though.
+    pub fn consume_and_report(ctx: Context<ConsumeRequest>) -> Result<ConsumeReport> {
+        ctx.accounts.require_allowed_caller()?;
+        ctx.accounts.require_min_balance()?;
+        ctx.accounts.require_circuit_closed()?;
+        let now = Clock::get()?.unix_timestamp;
+        let grace = ctx.accounts.take_pause_grace(now);
+        consume(
+            grace.as_ref().unwrap_or(&ctx.accounts.global_config),
+            ctx.accounts.schedule.as_deref(),
+            &mut ctx.accounts.client_bucket,
+            ctx.accounts.global_stats.as_deref_mut(),
+            now,
+            1,
+        )?;
+        let config = wallet_limits(&ctx.accounts.global_config, ctx.accounts.schedule.as_deref(), now)?;
+        Ok(logic::consume_report(&ctx.accounts.client_bucket, &config))
+    }
+
+    // Weighted consume: one call spends `amount` units of the window allowance
+    pub fn consume_amount(ctx: Context<ConsumeRequest>, amount: u64) -> Result<()> {
+        // A single consume can never exceed what one window can hold
+        validation::validate_amount(amount, ctx.accounts.global_config.burst_limit)?;
+        ctx.accounts.require_allowed_caller()?;
+        ctx.accounts.require_min_balance()?;
+        ctx.accounts.require_circuit_closed()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let grace = ctx.accounts.take_pause_grace(now);
+        consume(
+            grace.as_ref().unwrap_or(&ctx.accounts.global_config),
+            ctx.accounts.schedule.as_deref(),
+            &mut ctx.accounts.client_bucket,
+            ctx.accounts.global_stats.as_deref_mut(),
+            now,
+            amount,
+        )
+    }
+
+    // consume_request that, with `priority` set, pays with a banked burst credit
+    // first so the window allowance is left for background traffic; without
+    // credits it falls back to the allowance. PriorityConsumed names the source.
+    pub fn consume_priority(ctx: Context<ConsumeRequest>, priority: bool) -> Result<()> {
+        ctx.accounts.require_allowed_caller()?;
+        ctx.accounts.require_min_balance()?;
+        ctx.accounts.require_circuit_closed()?;
+        let now = Clock::get()?.unix_timestamp;
+        let grace = ctx.accounts.take_pause_grace(now);
+        priority_consume(
+            grace.as_ref().unwrap_or(&ctx.accounts.global_config),
+            ctx.accounts.schedule.as_deref(),
+            &mut ctx.accounts.client_bucket,
+            ctx.accounts.global_stats.as_deref_mut(),
+            now,
+            priority,
+        )
+    }
+
+    // Every consume option in one struct argument, so that adding an option does
+    // not change the instruction's signature. The v1 consumes stay frozen for
+    // existing integrations; with ConsumeArgs::default() this is consume_request.
+    // A refused consume fails the transaction, so it leaves the nonce unused.
+    pub fn consume_v2(ctx: Context<ConsumeRequest>, args: ConsumeArgs) -> Result<()> {
+        validation::validate_consume_args(&args, ctx.accounts.global_config.burst_limit)?;
+        ctx.accounts.require_allowed_caller()?;
+        ctx.accounts.require_min_balance()?;
+        ctx.accounts.require_circuit_closed()?;
+        let now = Clock::get()?.unix_timestamp;
+        if let Some(deadline) = args.deadline {
+            require!(now <= deadline, RateLimiterError::DeadlineExceeded);
+        }
+        let grace = ctx.accounts.take_pause_grace(now);
+
+        let bucket = &mut ctx.accounts.client_bucket;
+        if let Some(nonce) = args.nonce {
+            (bucket.last_nonce, bucket.nonce_bitmap) = nonce::use_nonce(bucket.last_nonce, bucket.nonce_bitmap, nonce)?;
+        }
+        let config = grace.as_ref().unwrap_or(&ctx.accounts.global_config);
+        let schedule = ctx.accounts.schedule.as_deref();
+        let stats = ctx.accounts.global_stats.as_deref_mut();
+        if args.priority {
+            priority_consume(config, schedule, bucket, stats, now, true)
+        } else {
+            consume(config, schedule, bucket, stats, now, args.amount)
+        }
+    }
+
+    // consume_request for zero-copy buckets. The bucket is copied out to the
+    // regular layout for the shared consume path and written back in place.
+    pub fn consume_request_fast(ctx: Context<ConsumeRequestFast>) -> Result<()> {
+        // The allowlist accounts only travel with ConsumeRequest; CPIs go through there
+        require_allowed_caller(None, None)?;
+        let mut fast = ctx.accounts.fast_bucket.load_mut()?;
+        let mut bucket = fast.to_bucket();
+        // Checked on the copy, which carries the fast bucket's exemption flags
+        require_min_balance(&ctx.accounts.global_config, &bucket, ctx.accounts.client.lamports())?;
+        require_circuit_closed(&ctx.accounts.global_config, ctx.accounts.global_stats.as_deref())?;
+        let now = Clock::get()?.unix_timestamp;
+        consume(
+            &ctx.accounts.global_config,
+            ctx.accounts.schedule.as_deref(),
+            &mut bucket,
+            ctx.accounts.global_stats.as_deref_mut(),
+            now,
+            1,
+        )?;
+        fast.store(&bucket);
+        Ok(())
+    }
+
+    // Consumes from one shard against that shard's slice of the limits (see
+    // logic::shard_config). The parent is only read and the stats account at most
+    // read, for the circuit breaker, so consumes on different shards do not contend
+    // for a write lock; aggregate_shards counts them into the stats instead.
+    pub fn consume_sharded(ctx: Context<ConsumeSharded>, shard: u8) -> Result<()> {
+        require_allowed_caller(None, None)?;
+        require_min_balance(&ctx.accounts.global_config, &ctx.accounts.shard_bucket, ctx.accounts.client.lamports())?;
+        require_circuit_closed(&ctx.accounts.global_config, ctx.accounts.global_stats.as_deref())?;
+        let now = Clock::get()?.unix_timestamp;
+        let config = logic::shard_config(
+            &wallet_limits(&ctx.accounts.global_config, ctx.accounts.schedule.as_deref(), now)?,
+            ctx.accounts.shard_parent.shard_count,
+        );
+        verbose_msg!("Consuming from shard {}", shard);
+        consume(&config, None, &mut ctx.accounts.shard_bucket, None, now, 1)
+    }
+
+    // Permissionless crank: folds every shard of a client into its parent so the
+    // parent shows the client's real usage, and adds the requests the shards served
+    // since the last aggregate to the program-wide total. All shards must be
+    // passed, in order, as remaining accounts.
+    pub fn aggregate_shards<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AggregateShards<'info>>,
+    ) -> Result<()> {
+        let parent = &mut ctx.accounts.shard_parent;
+        let window_seconds = ctx.accounts.global_config.window_seconds;
+        require!(
+            ctx.remaining_accounts.len() == parent.shard_count as usize,
+            RateLimiterError::InvalidShardCount
+        );
+        let now = Clock::get()?.unix_timestamp;
+
+        let mut window_requests: u64 = 0;
+        let mut total_requests: u64 = 0;
+        let mut total_saturated = false;
+        for (index, info) in ctx.remaining_accounts.iter().enumerate() {
+            let shard = Account::<ClientBucket>::try_from(info)?;
+            pda::assert_canonical(
+                &[CLIENT_SHARD_SEED, parent.owner.as_ref(), &[index as u8]],
+                shard.bump,
+                info.key,
+                RateLimiterError::InvalidShard,
+            )?;
+
+            // A shard whose window has closed has no requests in flight
+            if !window::window_expired(now, shard.window_start, window_seconds) {
+                window_requests = window_requests.saturating_add(shard.request_count);
+            }
+            total_saturated |= shard.total_saturated();
+            total_requests = match total_requests.checked_add(shard.total_requests) {
+                Some(total) => total,
+                None => {
+                    total_saturated = true;
+                    u64::MAX
+                }
+            };
+        }
+
+        let stats = &mut ctx.accounts.global_stats;
+        let served = total_requests.saturating_sub(parent.total_requests);
+        match stats.total_requests.checked_add(served) {
+            Some(total) => stats.total_requests = total,
+            None => {
+                stats.total_requests = u64::MAX;
+                stats.total_saturated = true;
+            }
+        }
+
+        parent.window_requests = window_requests;
+        parent.total_requests = total_requests;
+        parent.total_saturated = total_saturated;
+        parent.last_aggregated_at = now;
+        msg!("Aggregated {} shards: {} in window, {} total", parent.shard_count, window_requests, total_requests);
+        Ok(())
+    }
+
+    // consume_request for callers that want to branch instead of fail. Over-limit
+    // consumes succeed with `allowed: false` in the returned GateResult (and the
+    // violation is recorded for good); blocked, paused and malformed requests
+    // still error. Caller programs read the result with gate::read_gate_result.
+    // With hook_on_reject, denials notify the hook (remaining accounts, see hook.rs);
+    // they are the only rejections that do not roll back, so the only ones a hook
+    // could act on.
+    pub fn gate_check<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ConsumeRequest<'info>>,
+    ) -> Result<GateResult> {
+        ctx.accounts.require_allowed_caller()?;
+        ctx.accounts.require_min_balance()?;
+        ctx.accounts.require_circuit_closed()?;
+        let now = Clock::get()?.unix_timestamp;
+        let grace = ctx.accounts.take_pause_grace(now);
+        let config = grace.as_ref().unwrap_or(&ctx.accounts.global_config);
+        let config = &wallet_limits(config, ctx.accounts.schedule.as_deref(), now)?;
+        let bucket = &mut ctx.accounts.client_bucket;
+        require_wallet_mode(config)?;
+        require_supported(bucket, config)?;
+        forgive_idle_penalties(config, bucket, now);
+
+        match logic::apply_consume(bucket, config, now, 1) {
+            Ok(consumed) => {
+                commit_consume(config, bucket, ctx.accounts.global_stats.as_deref_mut(), now, 1, consumed)?;
+                Ok(GateResult {
+                    allowed: true,
+                    remaining: logic::remaining_capacity(bucket, config),
+                    retry_after: 0,
+                })
+            }
+            Err(rejection @ (Rejection::RateLimit | Rejection::BurstLimit)) => {
+                let reason_code = if rejection == Rejection::BurstLimit {
+                    REJECT_BURST_LIMIT
+                } else {
+                    REJECT_RATE_LIMIT
+                };
+                let result = deny(bucket, config, now, reason_code);
+                if let Some(stats) = ctx.accounts.global_stats.as_deref_mut() {
+                    stats.total_denials = stats.total_denials.saturating_add(1);
+                    count_for_breaker(stats, now);
+                }
+                if config.hook_on_reject() {
+                    bucket.exit(&crate::ID)?;
+                    hook::notify(
+                        config,
+                        &bucket.to_account_info(),
+                        hook::HookEvent { client: bucket.owner, reason_code },
+                        ctx.remaining_accounts,
+                    )?;
+                }
+                Ok(result)
+            }
+            Err(rejection) => {
+                reject(config, now, bucket, rejection)?;
+                unreachable!("reject always fails")
+            }
+        }
+    }
+
+    // Gives an integrating program its own allowance, shared by all its users. The
+    // program proves who it is on each consume by signing with its
+    // ["rate-limiter-caller"] PDA, whose address is fixed here.
+    pub fn register_program(ctx: Context<RegisterProgram>, limits: RateLimiterConfig) -> Result<()> {
+        require!(limits.max_requests > 0, RateLimiterError::InvalidConfig);
+        require!(limits.window_seconds > 0, RateLimiterError::InvalidConfig);
+        require!(limits.burst_limit >= limits.max_requests, RateLimiterError::InvalidConfig);
+
+        let program_id = ctx.accounts.caller_program.key();
+        let (caller_authority, _) = pda::caller_authority_address(&program_id);
+
+        let bucket = &mut ctx.accounts.program_bucket;
+        bucket.version = PROGRAM_BUCKET_VERSION;
+        bucket.program_id = program_id;
+        bucket.caller_authority = caller_authority;
+        bucket.max_requests = limits.max_requests;
+        bucket.window_seconds = limits.window_seconds;
+        bucket.burst_limit = limits.burst_limit;
+        bucket.request_count = 0;
+        bucket.window_start = Clock::get()?.unix_timestamp;
+        bucket.total_requests = 0;
+        bucket.flags = 0;
+        bucket.violations = 0;
+        bucket.violations_updated_at = 0;
+        bucket.config_epoch = ctx.accounts.global_config.config_epoch;
+        bucket.burst_credits = 0;
+        bucket.bump = ctx.bumps.program_bucket;
+        bucket.reserved = [0; 24];
+        debug_assert_invariants(
+            &bucket.to_bucket(),
+            &logic::program_config(&ctx.accounts.global_config, bucket),
+        );
+
+        msg!("Program registered: {}. Max: {} req / {}s", program_id, limits.max_requests, limits.window_seconds);
+        Ok(())
+    }
+
+    // consume_request keyed by the calling program instead of the wallet. Only
+    // reachable by CPI: the caller authority is a PDA of the caller program, so
+    // nothing but that program's invoke_signed can produce its signature. There is
+    // no wallet signing, so the minimum balance does not apply.
+    pub fn consume_as_program(ctx: Context<ConsumeAsProgram>) -> Result<()> {
+        require_circuit_closed(&ctx.accounts.global_config, ctx.accounts.global_stats.as_deref())?;
+        let program = &mut ctx.accounts.program_bucket;
+        let config = logic::program_config(&ctx.accounts.global_config, program);
+        let mut bucket = program.to_bucket();
+        let now = Clock::get()?.unix_timestamp;
+
+        match logic::apply_consume(&bucket, &config, now, 1) {
+            Ok(consumed) => {
+                commit_consume(&config, &mut bucket, ctx.accounts.global_stats.as_deref_mut(), now, 1, consumed)?
+            }
+            Err(rejection) => return reject(&config, now, &mut bucket, rejection),
+        }
+        program.store(&bucket);
+        Ok(())
+    }
+
+    // Approves `caller_program` to CPI into consume_request, consume_amount and
+    // gate_check. Top-level calls never need an entry.
+    pub fn allow_caller(ctx: Context<AllowCaller>) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        let entry = &mut ctx.accounts.caller_entry;
+        entry.program_id = ctx.accounts.caller_program.key();
+        entry.allowed_at = Clock::get()?.unix_timestamp;
+        entry.bump = ctx.bumps.caller_entry;
+        msg!("Caller allowed: {}", entry.program_id);
+        Ok(())
+    }
+
+    // Closes the entry; the program's next CPI consume fails
+    pub fn revoke_caller(ctx: Context<RevokeCaller>) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        msg!("Caller revoked: {}", ctx.accounts.caller_entry.program_id);
+        Ok(())
+    }
+
+    // Refuses consumes by every PDA of `program_id` at once, where blocking their
+    // buckets one by one cannot keep up with a program minting new ones. Wallets
+    // consuming through the program are unaffected; revoke_caller stops those too.
+    pub fn block_program_owners(ctx: Context<BlockProgramOwners>, program_id: Pubkey) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        let now = Clock::get()?.unix_timestamp;
+        let config = &mut ctx.accounts.global_config;
+        throttle_admin_action(config, now)?;
+        config.denied_owner_programs = config.denied_owner_programs.saturating_add(1);
+
+        let entry = &mut ctx.accounts.denylist_entry;
+        entry.program_id = program_id;
+        entry.blocked_at = now;
+        entry.bump = ctx.bumps.denylist_entry;
+        msg!("Program owners blocked: {}", program_id);
+        Ok(())
+    }
+
+    // Closes the entry; the program's PDAs consume again from their next call
+    pub fn unblock_program_owners(ctx: Context<UnblockProgramOwners>) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        let config = &mut ctx.accounts.global_config;
+        config.denied_owner_programs = config.denied_owner_programs.saturating_sub(1);
+        msg!("Program owners unblocked: {}", ctx.accounts.denylist_entry.program_id);
+        Ok(())
+    }
+
+    // Switches wallet buckets between the default mode and program-keyed mode,
+    // where CPIs must use consume_as_program and direct calls only reach wallet
+    // buckets when wallet_fallback is set
+    pub fn set_caller_mode(
+        ctx: Context<UpdateConfig>,
+        program_keyed: bool,
+        wallet_fallback: bool,
+    ) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        let gc = &mut ctx.accounts.global_config;
+        gc.set_program_keyed(program_keyed);
+        gc.set_wallet_fallback(wallet_fallback);
+        msg!("Program keyed: {}, wallet fallback: {}", program_keyed, wallet_fallback);
+        Ok(())
+    }
+
+    // Whether owners may change their bucket's event tag after first setting it.
+    // Tags already set are left as they are either way.
+    pub fn set_event_tag_policy(ctx: Context<UpdateConfig>, set_once: bool) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        ctx.accounts.global_config.set_event_tag_set_once(set_once);
+        msg!("Event tags set once: {}", set_once);
+        Ok(())
+    }
+
+    // Same as consume_request, but loads the bucket by hand so a client that never
+    // registered gets ClientNotRegistered instead of Anchor's AccountNotInitialized
+    pub fn try_consume(ctx: Context<TryConsume>) -> Result<()> {
+        require_allowed_caller(None, None)?;
+        let info = ctx.accounts.client_bucket.to_account_info();
+        let client = ctx.accounts.client.key();
+
+        if info.owner != &crate::ID || info.data_is_empty() {
+            msg!("No bucket for {}. Call register_client first.", client);
+            return err!(RateLimiterError::ClientNotRegistered);
+        }
+        let mut bucket = {
+            let data = info.try_borrow_data()?;
+            match ClientBucket::try_deserialize(&mut &data[..]) {
+                Ok(bucket) => bucket,
+                // A bucket in an old layout exists, it just cannot be read yet
+                Err(_) if data.starts_with(ClientBucket::DISCRIMINATOR)
+                    && migrations::bucket_version(&data)? < MIN_CLIENT_BUCKET_VERSION =>
+                {
+                    return err!(RateLimiterError::MigrationRequired)
+                }
+                Err(_) => return err!(RateLimiterError::ClientNotRegistered),
+            }
+        };
+        require_keys_eq!(bucket.owner, client, RateLimiterError::Unauthorized);
+        require_min_balance(&ctx.accounts.global_config, &bucket, ctx.accounts.client.lamports())?;
+        require_circuit_closed(&ctx.accounts.global_config, ctx.accounts.global_stats.as_deref())?;
+
+        let now = Clock::get()?.unix_timestamp;
+        consume(
+            &ctx.accounts.global_config,
+            ctx.accounts.schedule.as_deref(),
+            &mut bucket,
+            ctx.accounts.global_stats.as_deref_mut(),
+            now,
+            1,
+        )?;
+
+        let mut data = info.try_borrow_mut_data()?;
+        bucket.try_serialize(&mut &mut data[..])?;
+        Ok(())
+    }
+
+    // Moves a bucket to a new wallet on rotation. The PDA is keyed by owner, so the
+    // bucket is recreated under the new wallet's seeds with every field carried
+    // over, usage and violations included, and the old one is closed. Both wallets
+    // sign: nobody gets a bucket (and its history) pushed onto their seeds.
+    pub fn transfer_bucket(ctx: Context<TransferBucket>) -> Result<()> {
+        require!(
+            ctx.accounts.new_fast_bucket.data_is_empty() && ctx.accounts.new_shard_parent.data_is_empty(),
+            RateLimiterError::BucketKindConflict
+        );
+        let old = &ctx.accounts.client_bucket;
+        require_supported(old, &ctx.accounts.global_config)?;
+        require!(!old.is_blocked(), RateLimiterError::ClientBlocked);
+
+        let from = old.owner;
+        let to = ctx.accounts.new_wallet.key();
+        let mut moved = ClientBucket::clone(old);
+        moved.owner = to;
+        moved.bump = ctx.bumps.new_bucket;
+        // The payer funds the new bucket, so the sponsorship ends with the old one
+        return_sponsored_rent(old, ctx.accounts.sponsor_pool.as_ref())?;
+        moved.set_sponsored(false);
+        ctx.accounts.new_bucket.set_inner(moved);
+
+        emit!(BucketTransferred { from, to, total_requests: ctx.accounts.new_bucket.total_requests });
+        msg!("Bucket moved from {} to {}", from, to);
+        Ok(())
+    }
+
+    // Owner-chosen name for the bucket, carried in get_quota and events. Admin
+    // resets leave it alone; an empty label clears it.
+    pub fn set_label(ctx: Context<SetLabel>, label: String) -> Result<()> {
+        validation::validate_label(&label)?;
+        let bucket = &mut ctx.accounts.client_bucket;
+        require_supported(bucket, &ctx.accounts.global_config)?;
+        bucket.set_label(&label);
+        msg!("Label for {} set to {:?}", bucket.owner, label);
+        Ok(())
+    }
+
+    // Owner-chosen bytes copied into every consume, rejection and block event for
+    // the bucket, so an indexer can filter by tag instead of keeping its own map of
+    // owners. All zeros clears it. Under the config's set-once policy a tag, once
+    // set, stays.
+    pub fn set_event_tag(ctx: Context<SetLabel>, event_tag: [u8; 16]) -> Result<()> {
         let config = &ctx.accounts.global_config;
         let bucket = &mut ctx.accounts.client_bucket;
+        require_supported(bucket, config)?;
+        if config.event_tag_set_once() {
+            require!(!bucket.has_event_tag(), RateLimiterError::EventTagLocked);
+        }
+        let old_tag = bucket.event_tag;
+        bucket.event_tag = event_tag;
+        emit!(EventTagSet { owner: bucket.owner, old_tag, event_tag });
+        Ok(())
+    }
+
+    // Puts `amount` units of the seller's current window up for sale. They are
+    // escrowed straight away (added to request_count), so the seller cannot also
+    // consume them, and the offer lapses with that window. One offer per seller.
+    pub fn offer_quota(ctx: Context<OfferQuota>, amount: u64, price_lamports: u64) -> Result<()> {
+        validation::validate_amount(amount, ctx.accounts.global_config.burst_limit)?;
+        let now = Clock::get()?.unix_timestamp;
+        let config = &wallet_limits(&ctx.accounts.global_config, ctx.accounts.schedule.as_deref(), now)?;
+        let bucket = &mut ctx.accounts.seller_bucket;
+        require_supported(bucket, config)?;
+
+        match logic::escrow_quota(bucket, config, now, amount) {
+            // Nothing was consumed, so the totals stay as they are
+            Ok(consumed) => commit_consume(config, bucket, Some(&mut ctx.accounts.global_stats), now, 0, consumed)?,
+            Err(rejection) => return reject(config, now, bucket, rejection),
+        }
+        ctx.accounts.offer.set_inner(Offer {
+            seller: bucket.owner,
+            amount,
+            price_lamports,
+            window_start: bucket.window_start,
+            config_epoch: bucket.config_epoch,
+            bump: ctx.bumps.offer,
+        });
+
+        emit!(QuotaOffered { seller: bucket.owner, amount, price_lamports, window_start: bucket.window_start });
+        msg!("{} offers {} units for {} lamports", bucket.owner, amount, price_lamports);
+        Ok(())
+    }
+
+    // The buyer pays price_lamports to the seller and gets the units as
+    // bonus_requests for the buyer's own current window. The offer closes to the
+    // seller.
+    pub fn accept_offer(ctx: Context<AcceptOffer>) -> Result<()> {
         let now = Clock::get()?.unix_timestamp;
+        let config = &wallet_limits(&ctx.accounts.global_config, ctx.accounts.schedule.as_deref(), now)?;
+        let offer = &ctx.accounts.offer;
+        require!(
+            logic::offer_live(&ctx.accounts.seller_bucket, config, offer.window_start, offer.config_epoch, now),
+            RateLimiterError::OfferExpired
+        );
+        let bucket = &mut ctx.accounts.buyer_bucket;
+        require_supported(bucket, config)?;
+
+        // Opens the buyer's window first, so the units land in the current one
+        match logic::apply_consume(bucket, config, now, 0) {
+            Ok(consumed) => commit_consume(config, bucket, Some(&mut ctx.accounts.global_stats), now, 0, consumed)?,
+            Err(rejection) => return reject(config, now, bucket, rejection),
+        }
+        bucket.bonus_requests = bucket
+            .bonus_requests
+            .checked_add(offer.amount)
+            .ok_or(RateLimiterError::ArithmeticOverflow)?;
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.buyer.to_account_info(),
+                    to: ctx.accounts.seller.to_account_info(),
+                },
+            ),
+            offer.price_lamports,
+        )?;
 
-        require!(!config.is_paused, RateLimiterError::ProgramPaused);
-        require!(!bucket.is_blocked, RateLimiterError::ClientBlocked);
+        emit!(OfferAccepted {
+            seller: offer.seller,
+            buyer: bucket.owner,
+            amount: offer.amount,
+            price_lamports: offer.price_lamports,
+        });
+        msg!("{} bought {} units from {}", bucket.owner, offer.amount, offer.seller);
+        debug_assert_invariants(bucket, config);
+        Ok(())
+    }
 
-        if now >= bucket.window_start + config.window_seconds {
-            bucket.request_count = 0;
-            bucket.window_start = now;
-            msg!("Window reset for client: {}", bucket.owner);
+    // Withdraws an offer. While its window is still open the escrowed units go back
+    // to the seller; a lapsed offer just closes, its units having gone with the
+    // window. Without the bucket (e.g. after transfer_bucket) the offer still
+    // closes, releasing nothing.
+    pub fn cancel_offer(ctx: Context<CancelOffer>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &wallet_limits(&ctx.accounts.global_config, ctx.accounts.schedule.as_deref(), now)?;
+        let offer = &ctx.accounts.offer;
+        let mut released = 0;
+        if let Some(bucket) = ctx.accounts.seller_bucket.as_mut() {
+            if logic::offer_live(bucket, config, offer.window_start, offer.config_epoch, now) {
+                // An admin reset in the meantime has already dropped the escrow
+                bucket.request_count = bucket.request_count.saturating_sub(offer.amount);
+                bucket.escrowed_count = bucket.escrowed_count.saturating_sub(offer.amount);
+                released = offer.amount;
+                debug_assert_invariants(bucket, config);
+            }
         }
 
-        require!(bucket.request_count < config.max_requests, RateLimiterError::RateLimitExceeded);
-        require!(bucket.request_count < config.burst_limit, RateLimiterError::BurstLimitExceeded);
+        emit!(OfferCancelled { seller: offer.seller, released });
+        msg!("Offer by {} cancelled, {} units released", offer.seller, released);
+        Ok(())
+    }
+
+    // Permissionless crank: closes a stale wallet bucket, paying the cranker its
+    // bounty and the owner the rest of the rent. A bucket holds nothing but rent,
+    // so there is no balance to settle first. The owner can register again.
+    pub fn gc_bucket(ctx: Context<GcBucket>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.global_config;
+        let bucket = &ctx.accounts.client_bucket;
+        require_supported(bucket, config)?;
+        require!(!bucket.is_blocked(), RateLimiterError::ClientBlocked);
+        require!(logic::gc_collectable(bucket, config, now), RateLimiterError::BucketNotStale);
 
-        bucket.request_count += 1;
-        bucket.total_requests += 1;
+        let lamports = bucket.get_lamports();
+        let bounty = logic::gc_bounty(lamports, config.gc_bounty_bps);
+        bucket.sub_lamports(bounty)?;
+        ctx.accounts.cranker.add_lamports(bounty)?;
+        return_sponsored_rent(bucket, ctx.accounts.sponsor_pool.as_ref())?;
 
-        msg!(
-            "Request consumed. Used: {}/{} | Window ends in: {}s",
-            bucket.request_count,
-            config.max_requests,
-            (bucket.window_start + config.window_seconds) - now
+        let stats = &mut ctx.accounts.global_stats;
+        stats.total_clients = stats.total_clients.saturating_sub(1);
+        emit!(BucketCollected {
+            owner: bucket.owner,
+            cranker: ctx.accounts.cranker.key(),
+            bounty,
+            refunded: lamports - bounty,
+        });
+        msg!("Stale bucket {} collected", bucket.owner);
+        // `close = owner` hands over the remaining lamports
+        Ok(())
+    }
+
+    // Names the key that may close the owner's bucket if the owner's wallet is lost,
+    // see recover_bucket. None clears it.
+    pub fn set_recovery_delegate(ctx: Context<SetRecoveryDelegate>, delegate: Option<Pubkey>) -> Result<()> {
+        let bucket = &mut ctx.accounts.client_bucket;
+        require_supported(bucket, &ctx.accounts.global_config)?;
+        bucket.recovery_delegate = delegate.unwrap_or_default();
+        match delegate {
+            Some(delegate) => msg!("Recovery delegate for {} set to {}", bucket.owner, delegate),
+            None => msg!("Recovery delegate for {} cleared", bucket.owner),
+        }
+        Ok(())
+    }
+
+    // The recovery delegate closes a bucket whose owner has not consumed for
+    // recovery_inactive_seconds and takes every lamport it holds. Refused while
+    // paused, when the owner could not have consumed to show it is still around.
+    // A blocked bucket stays, as with close_bucket, and sponsored rent goes back to
+    // the pool.
+    pub fn recover_bucket(ctx: Context<RecoverBucket>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.global_config;
+        let bucket = &ctx.accounts.client_bucket;
+        require_supported(bucket, config)?;
+        require_unpaused(config, now)?;
+        require!(config.recovery_inactive_seconds > 0, RateLimiterError::RecoveryDisabled);
+        require!(!bucket.is_blocked(), RateLimiterError::ClientBlocked);
+        require!(logic::bucket_recoverable(bucket, config, now), RateLimiterError::BucketStillActive);
+
+        return_sponsored_rent(bucket, ctx.accounts.sponsor_pool.as_ref())?;
+        let stats = &mut ctx.accounts.global_stats;
+        stats.total_clients = stats.total_clients.saturating_sub(1);
+        emit!(BucketRecovered {
+            owner: bucket.owner,
+            delegate: bucket.recovery_delegate,
+            lamports: bucket.get_lamports(),
+            idle_since: bucket.last_request_ts,
+        });
+        msg!("Bucket {} recovered by {}", bucket.owner, bucket.recovery_delegate);
+        // `close = delegate` hands over the lamports
+        Ok(())
+    }
+
+    // Closes the caller's own bucket and refunds its rent. A blocked bucket is the
+    // block, so it stays; any other bucket, frozen ones included, can go.
+    pub fn close_bucket(ctx: Context<CloseBucket>) -> Result<()> {
+        let bucket = &ctx.accounts.client_bucket;
+        require!(!bucket.is_blocked(), RateLimiterError::ClientBlocked);
+        return_sponsored_rent(bucket, ctx.accounts.sponsor_pool.as_ref())?;
+        let stats = &mut ctx.accounts.global_stats;
+        stats.total_clients = stats.total_clients.saturating_sub(1);
+        msg!("Bucket {} closed by its owner", bucket.owner);
+        Ok(())
+    }
+
+    // Tops up the pool register_sponsored spends from, creating it on first use.
+    // `payer` supplies both the lamports and the pool's rent, so a PDA admin only
+    // has to sign.
+    pub fn fund_sponsor_pool(ctx: Context<FundSponsorPool>, lamports: u64) -> Result<()> {
+        require!(lamports > 0, RateLimiterError::InvalidAmount);
+        let pool = &mut ctx.accounts.sponsor_pool;
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: pool.to_account_info(),
+                },
+            ),
+            lamports,
+        )?;
+        pool.bump = ctx.bumps.sponsor_pool;
+
+        let available = sponsor_pool_available(pool)?;
+        emit!(SponsorPoolFunded { lamports, available });
+        msg!("Sponsor pool funded with {} lamports, {} available", lamports, available);
+        Ok(())
+    }
+
+    // Registers a wallet bucket whose rent comes out of the sponsor pool, for
+    // clients with no SOL of their own. The client still signs, so nobody gets a
+    // bucket created under their seeds. There is no payer, so no fee; the
+    // registration cooldown runs on the pool instead, spacing out all sponsored
+    // registrations together.
+    pub fn register_sponsored(ctx: Context<RegisterSponsored>) -> Result<()> {
+        let config = &ctx.accounts.global_config;
+        let now = Clock::get()?.unix_timestamp;
+        require_unpaused(config, now)?;
+        require!(
+            ctx.accounts.fast_bucket.data_is_empty() && ctx.accounts.shard_parent.data_is_empty(),
+            RateLimiterError::BucketKindConflict
+        );
+        let pool = &mut ctx.accounts.sponsor_pool;
+        require!(
+            logic::registration_allowed(config, pool.last_sponsored_at, now),
+            RateLimiterError::RegistrationCooldown
         );
+
+        let info = ctx.accounts.client_bucket.to_account_info();
+        let space = 8 + ClientBucket::INIT_SPACE;
+        // Lamports already sitting at the address count towards the rent
+        let lamports = Rent::get()?.minimum_balance(space).saturating_sub(info.lamports());
+        require!(sponsor_pool_available(pool)? >= lamports, RateLimiterError::SponsorPoolEmpty);
+
+        // The pool belongs to this program, not the system program, so it cannot
+        // fund a create_account. Move the rent across in place, then have the
+        // bucket PDA allocate and assign itself.
+        pool.sub_lamports(lamports)?;
+        info.add_lamports(lamports)?;
+        let client = ctx.accounts.client.key();
+        let signer: &[&[&[u8]]] = &[&[CLIENT_BUCKET_SEED, client.as_ref(), &[ctx.bumps.client_bucket]]];
+        system_program::allocate(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Allocate { account_to_allocate: info.clone() },
+                signer,
+            ),
+            space as u64,
+        )?;
+        system_program::assign(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Assign { account_to_assign: info.clone() },
+                signer,
+            ),
+            &crate::ID,
+        )?;
+
+        let bucket = ClientBucket {
+            version: CLIENT_BUCKET_VERSION,
+            owner: client,
+            request_count: 0,
+            window_start: now,
+            total_requests: 0,
+            flags: BUCKET_FLAG_SPONSORED,
+            violations: 0,
+            violations_updated_at: 0,
+            config_epoch: config.config_epoch,
+            burst_credits: 0,
+            priority_count: 0,
+            escrowed_count: 0,
+            bonus_requests: 0,
+            last_nonce: 0,
+            nonce_bitmap: 0,
+            last_refund_nonce: 0,
+            refunded_count: 0,
+            registered_by: pool.key(),
+            recovery_delegate: Pubkey::default(),
+            last_request_ts: now,
+            bump: ctx.bumps.client_bucket,
+            label: [0; 32],
+            event_tag: [0; 16],
+        };
+        bucket.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+        debug_assert_invariants(&bucket, config);
+
+        pool.sponsored_clients = pool.sponsored_clients.saturating_add(1);
+        pool.lamports_sponsored = pool.lamports_sponsored.saturating_add(lamports);
+        pool.last_sponsored_at = now;
+        let stats = &mut ctx.accounts.global_stats;
+        stats.total_clients = stats.total_clients.saturating_add(1);
+
+        emit!(ClientSponsored { client, lamports });
+        msg!("Sponsored client registered: {}", client);
+        Ok(())
+    }
+
+    // Sends whatever the pool holds above its own rent back to the admin. The pool
+    // stays open, so it can be funded again and keeps taking back sponsored rent.
+    pub fn withdraw_sponsor_pool(ctx: Context<WithdrawSponsorPool>) -> Result<()> {
+        let pool = &ctx.accounts.sponsor_pool;
+        let lamports = sponsor_pool_available(pool)?;
+        pool.sub_lamports(lamports)?;
+        ctx.accounts.admin.add_lamports(lamports)?;
+        emit!(SponsorPoolWithdrawn { lamports });
+        msg!("Withdrew {} lamports from the sponsor pool", lamports);
         Ok(())
     }
 
     pub fn reset_client(ctx: Context<ResetClient>) -> Result<()> {
-        let bucket = &mut ctx.accounts.client_bucket;
-        let clock = Clock::get()?;
+        require_no_council(&ctx.accounts.global_config)?;
+        let now = Clock::get()?.unix_timestamp;
+        throttle_admin_action(&mut ctx.accounts.global_config, now)?;
+        reset_bucket(
+            &ctx.accounts.global_config,
+            &mut ctx.accounts.global_stats,
+            &mut ctx.accounts.client_bucket,
+            now,
+        )
+    }
 
-        bucket.request_count = 0;
-        bucket.window_start = clock.unix_timestamp;
-        bucket.is_blocked = false;
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        config: RateLimiterConfig,
+        reset_windows: bool,
+    ) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        throttle_admin_action(&mut ctx.accounts.global_config, Clock::get()?.unix_timestamp)?;
+        apply_config(&mut ctx.accounts.global_config, &config, reset_windows)
+    }
+
+    // Hands the admin role to `new_admin`, which may be a PDA: a governance or
+    // multisig program then signs admin instructions with invoke_signed. Takes
+    // effect at once, so the new key has to be one that can actually sign.
+    pub fn transfer_admin(ctx: Context<UpdateConfig>, new_admin: Pubkey) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        require_keys_neq!(new_admin, Pubkey::default(), RateLimiterError::InvalidConfig);
+        let gc = &mut ctx.accounts.global_config;
+        msg!("Admin transferred: {} -> {}", gc.admin, new_admin);
+        gc.admin = new_admin;
+        Ok(())
+    }
+
+    // For a lost admin key: the upgrade authority names a new admin, who takes over
+    // after ADMIN_RECOVERY_DELAY unless the current admin cancels first. Starting
+    // again replaces the pending recovery and restarts the timelock.
+    pub fn begin_admin_recovery(ctx: Context<BeginAdminRecovery>, new_admin: Pubkey) -> Result<()> {
+        verify_upgrade_authority(
+            &ctx.accounts.program,
+            &ctx.accounts.program_data.to_account_info(),
+            &ctx.accounts.authority.key(),
+        )?;
+        require_keys_neq!(new_admin, Pubkey::default(), RateLimiterError::InvalidConfig);
 
-        msg!("Client bucket reset by admin: {}", bucket.owner);
+        let gc = &mut ctx.accounts.global_config;
+        let eta = Clock::get()?.unix_timestamp.saturating_add(ADMIN_RECOVERY_DELAY);
+        gc.recovery_admin = new_admin;
+        gc.recovery_eta = eta;
+        emit!(AdminRecoveryStarted { current_admin: gc.admin, new_admin, eta });
+        msg!("Admin recovery to {} can be finalized from {}", new_admin, eta);
+        Ok(())
+    }
+
+    // Caps how many blocks, resets and config updates the admin key can make per
+    // window, so a stolen key cannot wipe every bucket before anyone notices. Set
+    // by the upgrade authority rather than the admin, which is the key it limits;
+    // window_seconds or max_actions of 0 turns it off. Proposals a council
+    // approves are not counted.
+    pub fn set_admin_throttle(ctx: Context<BeginAdminRecovery>, window_seconds: i64, max_actions: u32) -> Result<()> {
+        verify_upgrade_authority(
+            &ctx.accounts.program,
+            &ctx.accounts.program_data.to_account_info(),
+            &ctx.accounts.authority.key(),
+        )?;
+        require!(window_seconds >= 0, RateLimiterError::InvalidConfig);
+
+        let gc = &mut ctx.accounts.global_config;
+        gc.admin_action_window_seconds = window_seconds;
+        gc.max_admin_actions_per_window = max_actions;
+        gc.admin_action_window_start = 0;
+        gc.admin_actions_in_window = 0;
+        msg!("Admin throttle: {} actions / {}s", max_actions, window_seconds);
         Ok(())
     }
 
-    pub fn update_config(ctx: Context<UpdateConfig>, config: RateLimiterConfig) -> Result<()> {
-        require!(config.max_requests > 0, RateLimiterError::InvalidConfig);
-        require!(config.window_seconds > 0, RateLimiterError::InvalidConfig);
-        require!(config.burst_limit >= config.max_requests, RateLimiterError::InvalidConfig);
+    pub fn cancel_admin_recovery(ctx: Context<UpdateConfig>) -> Result<()> {
+        let gc = &mut ctx.accounts.global_config;
+        require!(gc.recovery_pending(), RateLimiterError::NoRecoveryPending);
+        emit!(AdminRecoveryCancelled { admin: gc.admin, new_admin: gc.recovery_admin });
+        msg!("Admin recovery to {} cancelled", gc.recovery_admin);
+        gc.recovery_admin = Pubkey::default();
+        gc.recovery_eta = 0;
+        Ok(())
+    }
 
+    // Permissionless: the new admin was chosen when the recovery began
+    pub fn finalize_admin_recovery(ctx: Context<FinalizeAdminRecovery>) -> Result<()> {
         let gc = &mut ctx.accounts.global_config;
-        gc.max_requests = config.max_requests;
-        gc.window_seconds = config.window_seconds;
-        gc.burst_limit = config.burst_limit;
+        require!(gc.recovery_pending(), RateLimiterError::NoRecoveryPending);
+        let now = Clock::get()?.unix_timestamp;
+        require!(logic::recovery_finalizable(gc, now), RateLimiterError::RecoveryTimelockActive);
 
-        msg!("Config updated. Max: {} req / {}s", config.max_requests, config.window_seconds);
+        let old_admin = gc.admin;
+        gc.admin = gc.recovery_admin;
+        gc.recovery_admin = Pubkey::default();
+        gc.recovery_eta = 0;
+        emit!(AdminRecoveryFinalized { old_admin, new_admin: gc.admin });
+        msg!("Admin recovered: {} -> {}", old_admin, gc.admin);
         Ok(())
     }
 
+    // Pausing this way records PAUSE_REASON_UNSPECIFIED and no deadline; `pause`
+    // takes both. A pause whose deadline has passed counts as over.
     pub fn toggle_pause(ctx: Context<TogglePause>) -> Result<()> {
         let gc = &mut ctx.accounts.global_config;
-        gc.is_paused = !gc.is_paused;
-        msg!("Program paused: {}", gc.is_paused);
+        require_no_council(gc)?;
+        let now = Clock::get()?.unix_timestamp;
+        let paused = !gc.emergency_pause_active(now);
+        set_pause(gc, paused, PAUSE_REASON_UNSPECIFIED, now);
         Ok(())
     }
 
-    pub fn block_client(ctx: Context<BlockClient>) -> Result<()> {
-        let bucket = &mut ctx.accounts.client_bucket;
-        bucket.is_blocked = true;
-        msg!("Client blocked: {}", bucket.owner);
+    // Emergency pause with a PAUSE_REASON_* that rejected consumes get back. On a
+    // program that is already paused it only changes the reason and deadline. A
+    // nonzero `auto_unpause_at` (at most MAX_AUTO_UNPAUSE_DELAY ahead) ends the
+    // pause by itself: consumes and registrations ignore it from then on. Nothing
+    // clears the flag until the admin's next pause or unpause.
+    pub fn pause(ctx: Context<TogglePause>, reason: u8, auto_unpause_at: i64) -> Result<()> {
+        let gc = &mut ctx.accounts.global_config;
+        require_no_council(gc)?;
+        require!(reason <= PAUSE_REASON_MIGRATION, RateLimiterError::InvalidConfig);
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            auto_unpause_at == 0
+                || (auto_unpause_at > now && auto_unpause_at - now <= MAX_AUTO_UNPAUSE_DELAY),
+            RateLimiterError::InvalidSchedule
+        );
+        set_pause(gc, true, reason, now);
+        gc.auto_unpause_at = auto_unpause_at;
+        if auto_unpause_at != 0 {
+            msg!("Unpausing automatically at {}", auto_unpause_at);
+        }
+        Ok(())
+    }
+
+    // Lets integrators finish in-flight work when the emergency pause lands: for
+    // `grace_seconds` after paused_at, up to `budget` consumes across all clients
+    // still go through, each emitting GracePeriodConsume. A pause already running
+    // keeps the budget it started with. Zero seconds turns grace off. The first
+    // call creates the PauseGrace account the spends are counted in.
+    pub fn set_pause_grace(ctx: Context<SetPauseGrace>, grace_seconds: i64, budget: u64) -> Result<()> {
+        let gc = &mut ctx.accounts.global_config;
+        require_no_council(gc)?;
+        require!((0..=MAX_PAUSE_GRACE_SECONDS).contains(&grace_seconds), RateLimiterError::InvalidConfig);
+        gc.pause_grace_seconds = grace_seconds;
+        gc.pause_grace_budget = budget;
+        ctx.accounts.pause_grace.bump = ctx.bumps.pause_grace;
+        msg!("Pause grace set: {}s, {} consumes", grace_seconds, budget);
+        Ok(())
+    }
+
+    // Pauses the program for [start, end) without anyone online: consumes and
+    // registrations compare the clock against the window themselves. Replaces any
+    // earlier schedule.
+    pub fn schedule_pause(ctx: Context<UpdateConfig>, start: i64, end: i64) -> Result<()> {
+        let gc = &mut ctx.accounts.global_config;
+        require_no_council(gc)?;
+        let now = Clock::get()?.unix_timestamp;
+        require!(start < end && end > now, RateLimiterError::InvalidSchedule);
+        require!(end.saturating_sub(start) <= MAX_SCHEDULED_PAUSE, RateLimiterError::InvalidSchedule);
+        gc.scheduled_pause_start = start;
+        gc.scheduled_pause_end = end;
+        msg!("Pause scheduled from {} to {}", start, end);
+        Ok(())
+    }
+
+    pub fn clear_schedule(ctx: Context<UpdateConfig>) -> Result<()> {
+        let gc = &mut ctx.accounts.global_config;
+        require_no_council(gc)?;
+        gc.scheduled_pause_start = 0;
+        gc.scheduled_pause_end = 0;
+        msg!("Pause schedule cleared");
+        Ok(())
+    }
+
+    // Replaces the time-of-day limits. An empty list switches the schedule off and
+    // wallet consumes go back to the config's max_requests.
+    pub fn set_schedule(ctx: Context<SetSchedule>, entries: Vec<ScheduleEntry>) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        validation::validate_schedule(&entries)?;
+
+        let schedule = &mut ctx.accounts.schedule;
+        schedule.entries = [ScheduleEntry::default(); MAX_SCHEDULE_ENTRIES];
+        schedule.entries[..entries.len()].copy_from_slice(&entries);
+        schedule.entry_count = entries.len() as u8;
+        schedule.bump = ctx.bumps.schedule;
+        ctx.accounts.global_config.set_scheduled(!entries.is_empty());
+
+        msg!("Limit schedule set: {} entries", entries.len());
         Ok(())
     }
+
+    // Remaining accounts are for the hook, see hook.rs
+    pub fn block_client<'info>(ctx: Context<'_, '_, 'info, 'info, BlockClient<'info>>) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        throttle_admin_action(&mut ctx.accounts.global_config, Clock::get()?.unix_timestamp)?;
+        block_bucket(
+            &ctx.accounts.global_config,
+            &mut ctx.accounts.global_stats,
+            &mut ctx.accounts.client_bucket,
+            ctx.remaining_accounts,
+        )
+    }
+
+    // Puts a client's consumes on hold without blocking it: it stays out of
+    // blocked_clients, no hook is notified, and it can still close its bucket.
+    pub fn freeze_client(ctx: Context<FreezeClient>) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        freeze_bucket(&ctx.accounts.global_config, &mut ctx.accounts.client_bucket, true)
+    }
+
+    // Lifts a freeze. The counters were left alone, so the client picks up where
+    // it stopped.
+    pub fn thaw_client(ctx: Context<FreezeClient>) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        freeze_bucket(&ctx.accounts.global_config, &mut ctx.accounts.client_bucket, false)
+    }
+
+    // Lets a client consume whatever its wallet holds, e.g. a service wallet that
+    // is topped up just in time
+    pub fn set_balance_exempt(ctx: Context<FreezeClient>, exempt: bool) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        let bucket = &mut ctx.accounts.client_bucket;
+        require_supported(bucket, &ctx.accounts.global_config)?;
+        bucket.set_balance_exempt(exempt);
+        msg!("Client {} balance exempt: {}", bucket.owner, exempt);
+        Ok(())
+    }
+
+    // Seats the council's first member, at threshold 1. From then on
+    // update_config, toggle_pause, block_client and reset_client only run as
+    // approved proposals, the other config setters do not run at all, and
+    // membership changes are proposals too: further seats and
+    // removals are AddCouncilMember and RemoveCouncilMember actions, so an admin
+    // cannot unseat the council and act alone again.
+    pub fn add_council_member(ctx: Context<UpdateConfig>, member: Pubkey, threshold: u8) -> Result<()> {
+        let gc = &mut ctx.accounts.global_config;
+        require_no_council(gc)?;
+        seat_council_member(gc, member, threshold)
+    }
+
+    // Opens proposal `proposal_count`, with the proposer as its first approval
+    pub fn propose_action(ctx: Context<ProposeAction>, action: AdminAction) -> Result<()> {
+        let gc = &mut ctx.accounts.global_config;
+        let proposer = ctx.accounts.proposer.key();
+        require!(gc.is_council_member(&proposer), RateLimiterError::NotCouncilMember);
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.id = gc.proposal_count;
+        proposal.proposer = proposer;
+        proposal.action = action;
+        proposal.approvers = [Pubkey::default(); MAX_COUNCIL_MEMBERS];
+        proposal.approval_count = 0;
+        proposal.approve(proposer);
+        proposal.executed = false;
+        proposal.created_at = Clock::get()?.unix_timestamp;
+        proposal.bump = ctx.bumps.proposal;
+        gc.proposal_count = gc.proposal_count.saturating_add(1);
+
+        msg!("Proposal {} opened by {}", proposal.id, proposer);
+        Ok(())
+    }
+
+    pub fn approve_action(ctx: Context<ApproveAction>) -> Result<()> {
+        let member = ctx.accounts.member.key();
+        require!(
+            ctx.accounts.global_config.is_council_member(&member),
+            RateLimiterError::NotCouncilMember
+        );
+        let proposal = &mut ctx.accounts.proposal;
+        require!(!proposal.executed, RateLimiterError::ProposalAlreadyExecuted);
+        require!(proposal.approve(member), RateLimiterError::AlreadyApproved);
+        msg!("Proposal {} approved by {} ({} approvals)", proposal.id, member, proposal.approval_count);
+        Ok(())
+    }
+
+    // Permissionless once enough current members have approved. Block and unblock
+    // take the client's bucket as `client_bucket`; a block's remaining accounts are
+    // for the hook, as in block_client.
+    pub fn execute_action<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteAction<'info>>,
+    ) -> Result<()> {
+        let accounts = ctx.accounts;
+        let proposal = &mut accounts.proposal;
+        require!(!proposal.executed, RateLimiterError::ProposalAlreadyExecuted);
+        let gc = &mut accounts.global_config;
+        require!(
+            gc.has_council()
+                && proposal.current_approvals(gc.council_members()) >= gc.council_threshold as usize,
+            RateLimiterError::InsufficientApprovals
+        );
+        proposal.executed = true;
+        msg!("Executing proposal {}", proposal.id);
+
+        let action = proposal.action;
+        match action {
+            AdminAction::UpdateConfig { max_requests, window_seconds, burst_limit, reset_windows } => {
+                let config = RateLimiterConfig { max_requests, window_seconds, burst_limit };
+                apply_config(gc, &config, reset_windows)
+            }
+            AdminAction::Pause { paused } => {
+                set_pause(gc, paused, PAUSE_REASON_UNSPECIFIED, Clock::get()?.unix_timestamp);
+                Ok(())
+            }
+            AdminAction::AddCouncilMember { member, threshold } => seat_council_member(gc, member, threshold),
+            AdminAction::RemoveCouncilMember { member, threshold } => unseat_council_member(gc, member, threshold),
+            AdminAction::Block { client }
+            | AdminAction::Unblock { client }
+            | AdminAction::Freeze { client, .. } => {
+                let bucket = accounts
+                    .client_bucket
+                    .as_mut()
+                    .ok_or(RateLimiterError::OwnerMismatch)?;
+                pda::assert_canonical(
+                    &[CLIENT_BUCKET_SEED, client.as_ref()],
+                    bucket.bump,
+                    &bucket.key(),
+                    RateLimiterError::OwnerMismatch,
+                )?;
+
+                match action {
+                    AdminAction::Block { .. } => {
+                        block_bucket(gc, &mut accounts.global_stats, bucket, ctx.remaining_accounts)
+                    }
+                    AdminAction::Freeze { frozen, .. } => freeze_bucket(gc, bucket, frozen),
+                    _ => {
+                        let now = Clock::get()?.unix_timestamp;
+                        reset_bucket(gc, &mut accounts.global_stats, bucket, now)
+                    }
+                }
+            }
+        }
+    }
+
+    // Points the hook at `hook_program` (the default key turns it off). `on_reject`
+    // also notifies gate_check denials; `propagate` makes missing hook accounts an
+    // error instead of a skipped notification.
+    pub fn set_hook(
+        ctx: Context<UpdateConfig>,
+        hook_program: Pubkey,
+        on_reject: bool,
+        propagate: bool,
+    ) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        let gc = &mut ctx.accounts.global_config;
+        gc.hook_program = hook_program;
+        gc.set_hook_on_reject(on_reject);
+        gc.set_hook_propagates(propagate);
+        msg!("Hook: {}, on reject: {}, propagate: {}", hook_program, on_reject, propagate);
+        Ok(())
+    }
+
+    // Lets `feeder` override max_requests for wallet buckets, within [floor, ceiling].
+    // The default key revokes the feeder. Any live override is dropped either way,
+    // so a new feeder or new bounds start from the base config.
+    pub fn set_limit_feeder(
+        ctx: Context<UpdateConfig>,
+        feeder: Pubkey,
+        floor: u64,
+        ceiling: u64,
+    ) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        if feeder != Pubkey::default() {
+            require!(floor > 0 && floor <= ceiling, RateLimiterError::InvalidConfig);
+        }
+        let gc = &mut ctx.accounts.global_config;
+        gc.limit_feeder = feeder;
+        // Replacing the feeder outright also drops a rotation of the old one
+        gc.pending_authorities[AUTHORITY_LIMIT_FEEDER as usize] = PendingAuthority::default();
+        gc.dynamic_floor = floor;
+        gc.dynamic_ceiling = ceiling;
+        gc.dynamic_max = 0;
+        gc.dynamic_valid_until = 0;
+        msg!("Limit feeder: {}, bounds [{}, {}]", feeder, floor, ceiling);
+        Ok(())
+    }
+
+    // Signed by the feeder: max_requests is `new_max` (clamped to the admin's
+    // bounds) until `valid_until`, then the base config applies again. Each push
+    // replaces the last one.
+    pub fn push_dynamic_limit(
+        ctx: Context<PushDynamicLimit>,
+        new_max: u64,
+        valid_until: i64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(valid_until > now, RateLimiterError::InvalidConfig);
+        let feeder = ctx.accounts.limit_feeder.key();
+        let gc = &mut ctx.accounts.global_config;
+        gc.dynamic_max = new_max;
+        gc.dynamic_valid_until = valid_until;
+        let applied_max = gc.dynamic_override(now).unwrap_or(gc.max_requests);
+
+        emit!(DynamicLimitSet {
+            feeder,
+            requested_max: new_max,
+            applied_max,
+            valid_until,
+        });
+        msg!("Dynamic max_requests {} (requested {}) until {}", applied_max, new_max, valid_until);
+        Ok(())
+    }
+
+    // Lets `authority` (a backend's key) give back quota with refund_request, at most
+    // `max_per_window` units per bucket per window. With `lower_totals` refunds
+    // also come off total_requests. The default key turns refunds off.
+    pub fn set_refund_authority(
+        ctx: Context<UpdateConfig>,
+        authority: Pubkey,
+        max_per_window: u64,
+        lower_totals: bool,
+    ) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        if authority != Pubkey::default() {
+            require!(max_per_window > 0, RateLimiterError::InvalidConfig);
+        }
+        let gc = &mut ctx.accounts.global_config;
+        gc.refund_authority = authority;
+        gc.pending_authorities[AUTHORITY_REFUND as usize] = PendingAuthority::default();
+        gc.max_refunds_per_window = max_per_window;
+        gc.set_refunds_lower_totals(lower_totals);
+        msg!("Refund authority: {}, {} per window", authority, max_per_window);
+        Ok(())
+    }
+
+    // Hands an AUTHORITY_* role to `new_key` at `effective_at`. The current key
+    // signs up to the second before and the new one from then on, so whoever holds
+    // the role can switch over at a known time with no moment where neither key
+    // works. Scheduling again replaces a rotation that has not taken effect yet.
+    // Turning a role on or off stays with set_limit_feeder and set_refund_authority.
+    pub fn set_authority(ctx: Context<UpdateConfig>, kind: u8, new_key: Pubkey, effective_at: i64) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        require!((kind as usize) < AUTHORITY_KINDS, RateLimiterError::InvalidConfig);
+        let now = Clock::get()?.unix_timestamp;
+        require!(effective_at >= now && new_key != Pubkey::default(), RateLimiterError::InvalidConfig);
+        let gc = &mut ctx.accounts.global_config;
+        gc.settle_authority(kind, now);
+        let old_key = gc.authority(kind, now);
+        require!(old_key != Pubkey::default(), RateLimiterError::InvalidConfig);
+        gc.pending_authorities[kind as usize] = PendingAuthority { new_key, effective_at };
+
+        emit!(AuthorityRotationScheduled { kind, old_key, new_key, effective_at });
+        msg!("Authority {} rotates from {} to {} at {}", kind, old_key, new_key, effective_at);
+        Ok(())
+    }
+
+    // Drops a rotation before its effective_at; the current key stays. One that
+    // has taken effect can only be undone by rotating back.
+    pub fn cancel_authority_rotation(ctx: Context<UpdateConfig>, kind: u8) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        require!((kind as usize) < AUTHORITY_KINDS, RateLimiterError::InvalidConfig);
+        let now = Clock::get()?.unix_timestamp;
+        let gc = &mut ctx.accounts.global_config;
+        gc.settle_authority(kind, now);
+        let pending = gc.pending_authorities[kind as usize];
+        require!(pending.is_scheduled(), RateLimiterError::NoPendingRotation);
+        gc.pending_authorities[kind as usize] = PendingAuthority::default();
+
+        emit!(AuthorityRotationCancelled { kind, new_key: pending.new_key });
+        msg!("Authority {} rotation to {} cancelled", kind, pending.new_key);
+        Ok(())
+    }
+
+    // Signed by the refund authority when a request that consumed quota was not
+    // served: takes up to `amount` units off the bucket's current window, clamped
+    // to what the window allowance paid for. Each attestation_nonce has to be
+    // above the last one refunded on the bucket, so an attestation cannot be
+    // replayed.
+    pub fn refund_request(ctx: Context<RefundRequest>, amount: u64, attestation_nonce: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.global_config;
+        let bucket = &mut ctx.accounts.client_bucket;
+        require_supported(bucket, config)?;
+
+        let (next, refunded) = match logic::apply_refund(bucket, config, now, amount, attestation_nonce) {
+            Ok(refund) => refund,
+            Err(RefundRefusal::NonceUsed) => return err!(RateLimiterError::RefundNonceReused),
+            Err(RefundRefusal::CapExceeded) => return err!(RateLimiterError::RefundCapExceeded),
+        };
+        bucket.set_inner(next);
+        debug_assert_invariants(bucket, config);
+
+        emit!(RequestRefunded {
+            owner: bucket.owner,
+            requested: amount,
+            refunded,
+            attestation_nonce,
+            request_count: bucket.request_count,
+        });
+        msg!("Refunded {} of {} to {}", refunded, amount, bucket.owner);
+        Ok(())
+    }
+
+    // Fast buckets have no AdminAction yet, so with a council they cannot be reset
+    // or blocked at all
+    pub fn reset_fast_client(ctx: Context<AdminFastClient>) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        let now = Clock::get()?.unix_timestamp;
+        throttle_admin_action(&mut ctx.accounts.global_config, now)?;
+        let mut bucket = ctx.accounts.fast_bucket.load_mut()?;
+        if bucket.is_blocked != 0 {
+            let stats = &mut ctx.accounts.global_stats;
+            stats.blocked_clients = stats.blocked_clients.saturating_sub(1);
+        }
+
+        bucket.request_count = 0;
+        bucket.window_start = now;
+        bucket.is_blocked = 0;
+        bucket.config_epoch = ctx.accounts.global_config.config_epoch;
+        bucket.burst_credits = 0;
+        debug_assert_invariants(&bucket.to_bucket(), &ctx.accounts.global_config);
+
+        msg!("Fast client bucket reset by admin: {}", bucket.owner);
+        Ok(())
+    }
+
+    pub fn block_fast_client(ctx: Context<AdminFastClient>) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        throttle_admin_action(&mut ctx.accounts.global_config, Clock::get()?.unix_timestamp)?;
+        let mut bucket = ctx.accounts.fast_bucket.load_mut()?;
+        if bucket.is_blocked == 0 {
+            let stats = &mut ctx.accounts.global_stats;
+            stats.blocked_clients = stats.blocked_clients.saturating_add(1);
+        }
+        bucket.is_blocked = 1;
+        debug_assert_invariants(&bucket.to_bucket(), &ctx.accounts.global_config);
+        msg!("Fast client blocked: {}", bucket.owner);
+        Ok(())
+    }
+
+    // Brings a ClientBucket (regular or shard), the GlobalConfig, the GlobalStats or
+    // a PayerMeta up to the current layout, whatever version it was written in. Permissionless: migration only
+    // re-encodes existing state. The payer covers any extra rent.
+    pub fn migrate_account(ctx: Context<MigrateAccount>) -> Result<()> {
+        let info = ctx.accounts.account.to_account_info();
+        let data = info.try_borrow_data()?;
+
+        if data.starts_with(ClientBucket::DISCRIMINATOR) {
+            let from = migrations::bucket_version(&data)?;
+            let bucket = migrations::migrate_bucket(&data)?;
+            drop(data);
+            grow_account(&info, &ctx.accounts.payer, &ctx.accounts.system_program, 8 + ClientBucket::INIT_SPACE)?;
+            bucket.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+            msg!("Bucket {} migrated from v{} to v{}", bucket.owner, from, CLIENT_BUCKET_VERSION);
+        } else if data.starts_with(GlobalConfig::DISCRIMINATOR) {
+            let from = migrations::config_version(&data)?;
+            let config = migrations::migrate_config(&data)?;
+            drop(data);
+            grow_account(&info, &ctx.accounts.payer, &ctx.accounts.system_program, 8 + GlobalConfig::INIT_SPACE)?;
+            config.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+            msg!("Config migrated from v{} to v{}", from, GLOBAL_CONFIG_VERSION);
+        } else if data.starts_with(GlobalStats::DISCRIMINATOR) {
+            let stats = migrations::migrate_stats(&data)?;
+            drop(data);
+            grow_account(&info, &ctx.accounts.payer, &ctx.accounts.system_program, GlobalStats::LEN)?;
+            stats.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+            msg!("Stats migrated to the current layout");
+        } else if data.starts_with(PayerMeta::DISCRIMINATOR) {
+            let meta = migrations::migrate_payer_meta(&data)?;
+            drop(data);
+            grow_account(&info, &ctx.accounts.payer, &ctx.accounts.system_program, 8 + PayerMeta::INIT_SPACE)?;
+            meta.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+            msg!("Payer record for {} migrated to the current layout", meta.payer);
+        } else {
+            return err!(RateLimiterError::InvalidAccountLayout);
+        }
+        Ok(())
+    }
+
+    // Brings any account of ours up to rent exemption for its current length, for
+    // one resized without enough lamports added. migrate_account already tops up
+    // inline as it reallocs. Anyone may pay; nothing is charged past the minimum.
+    pub fn top_up_rent(ctx: Context<TopUpRent>) -> Result<()> {
+        let info = ctx.accounts.account.to_account_info();
+        let lamports = top_up_to_rent(&info, &ctx.accounts.payer, &ctx.accounts.system_program, info.data_len())?;
+        emit!(RentToppedUp { account: info.key(), lamports });
+        msg!("Topped up {} with {} lamports", info.key(), lamports);
+        Ok(())
+    }
+
+    pub fn adjust_client_usage(ctx: Context<AdjustClientUsage>, total_requests: u64) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        let bucket = &mut ctx.accounts.client_bucket;
+        require_supported(bucket, &ctx.accounts.global_config)?;
+        // The lifetime total already includes the open window
+        require!(total_requests >= bucket.request_count, RateLimiterError::InvalidAmount);
+        bucket.total_requests = total_requests;
+        bucket.set_total_saturated(false);
+        debug_assert_invariants(bucket, &ctx.accounts.global_config);
+        msg!("Client usage adjusted by admin: {} total: {}", bucket.owner, total_requests);
+        Ok(())
+    }
+
+    // Support annotations. They live in their own PDA and nothing on the consume
+    // path reads them.
+    pub fn set_client_note(ctx: Context<SetClientNote>, note: String) -> Result<()> {
+        validation::validate_note(&note)?;
+        let entry = &mut ctx.accounts.client_note;
+        entry.client = ctx.accounts.client_wallet.key();
+        entry.note = note;
+        entry.updated_at = Clock::get()?.unix_timestamp;
+        entry.updated_by = ctx.accounts.admin.key();
+        entry.bump = ctx.bumps.client_note;
+        msg!("Note set for {}", entry.client);
+        Ok(())
+    }
+
+    // Closes the note, refunding its rent to the admin
+    pub fn clear_client_note(ctx: Context<ClearClientNote>) -> Result<()> {
+        msg!("Note cleared for {}", ctx.accounts.client_note.client);
+        Ok(())
+    }
+
+    // Anti-sybil knobs for every registration kind, both off at zero. The fee needs
+    // a treasury to go to.
+    pub fn set_registration_policy(
+        ctx: Context<UpdateConfig>,
+        fee_lamports: u64,
+        cooldown_seconds: i64,
+        treasury: Pubkey,
+    ) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        require!(cooldown_seconds >= 0, RateLimiterError::InvalidConfig);
+        require!(fee_lamports == 0 || treasury != Pubkey::default(), RateLimiterError::InvalidTreasury);
+        let gc = &mut ctx.accounts.global_config;
+        gc.registration_fee_lamports = fee_lamports;
+        gc.registration_cooldown_seconds = cooldown_seconds;
+        gc.treasury = treasury;
+        msg!("Registration fee {} lamports, cooldown {}s", fee_lamports, cooldown_seconds);
+        Ok(())
+    }
+
+    // Caps how many registrations one payer may fund, counted in its PayerMeta.
+    // Owners cost nothing to make, so this is the limit on a single funding wallet
+    // grinding out buckets. 0 lifts the cap; lowering it keeps existing buckets.
+    pub fn set_max_buckets_per_payer(ctx: Context<UpdateConfig>, max_buckets: u32) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        ctx.accounts.global_config.max_buckets_per_payer = max_buckets;
+        msg!("Max buckets per payer: {}", max_buckets);
+        Ok(())
+    }
+
+    // Moves lamports sent straight to our PDAs, beyond what their data needs to stay
+    // rent-exempt, to the treasury. Takes any accounts this program owns as
+    // writable remaining accounts. The only deposits they hold are open appeal
+    // bonds, which are kept along with the rent.
+    pub fn sweep_excess_lamports(ctx: Context<SweepExcess>) -> Result<()> {
+        let rent = Rent::get()?;
+        let treasury = ctx.accounts.treasury.to_account_info();
+        let (mut accounts, mut lamports) = (0u8, 0u64);
+        for info in ctx.remaining_accounts {
+            require_keys_eq!(*info.owner, crate::ID, RateLimiterError::SweepTargetNotOwned);
+            let kept = rent.minimum_balance(info.data_len()).saturating_add(deposit_held(info)?);
+            let excess = info.lamports().saturating_sub(kept);
+            if excess == 0 {
+                continue;
+            }
+            info.sub_lamports(excess)?;
+            treasury.add_lamports(excess)?;
+            msg!("Swept {} lamports from {}", excess, info.key());
+            accounts = accounts.saturating_add(1);
+            lamports = lamports.saturating_add(excess);
+        }
+        emit!(ExcessSwept { accounts, lamports });
+        Ok(())
+    }
+
+    // Bond and cooldown for file_appeal. A zero bond turns appeals off; a non-zero
+    // one needs a treasury to forfeit denied bonds to.
+    pub fn set_appeal_policy(ctx: Context<UpdateConfig>, bond_lamports: u64, cooldown_seconds: i64) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        require!(cooldown_seconds >= 0, RateLimiterError::InvalidConfig);
+        let gc = &mut ctx.accounts.global_config;
+        require!(bond_lamports == 0 || gc.treasury != Pubkey::default(), RateLimiterError::InvalidTreasury);
+        gc.appeal_bond_lamports = bond_lamports;
+        gc.appeal_cooldown_seconds = cooldown_seconds;
+        msg!("Appeal bond {} lamports, cooldown {}s", bond_lamports, cooldown_seconds);
+        Ok(())
+    }
+
+    // A blocked client asks to be unblocked, posting appeal_bond_lamports into its
+    // ["appeal", wallet] PDA. One appeal can be open at a time, and after a denial
+    // the client waits appeal_cooldown_seconds before filing again.
+    pub fn file_appeal(ctx: Context<FileAppeal>) -> Result<()> {
+        let config = &ctx.accounts.global_config;
+        let bucket = &ctx.accounts.client_bucket;
+        require_supported(bucket, config)?;
+        require!(config.appeal_bond_lamports > 0, RateLimiterError::AppealsDisabled);
+        require!(bucket.is_blocked(), RateLimiterError::ClientNotBlocked);
+
+        let now = Clock::get()?.unix_timestamp;
+        let appeal = &mut ctx.accounts.appeal;
+        require!(!appeal.is_open(), RateLimiterError::AppealAlreadyOpen);
+        require!(logic::appeal_allowed(config, appeal.denied_at, now), RateLimiterError::AppealCooldown);
+
+        let bond = config.appeal_bond_lamports;
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.client.to_account_info(),
+                    to: appeal.to_account_info(),
+                },
+            ),
+            bond,
+        )?;
+        appeal.client = ctx.accounts.client.key();
+        appeal.bond_lamports = bond;
+        appeal.filed_at = now;
+        appeal.bump = ctx.bumps.appeal;
+
+        emit!(AppealFiled { client: appeal.client, bond_lamports: bond });
+        msg!("Appeal filed by {}", appeal.client);
+        Ok(())
+    }
+
+    // Unblocks the client and closes the appeal, refunding the bond with the rent.
+    // A client a council proposal has already unblocked is just refunded, so the
+    // bond does not stay stuck while a council is seated.
+    pub fn approve_appeal(ctx: Context<ApproveAppeal>) -> Result<()> {
+        require!(ctx.accounts.appeal.is_open(), RateLimiterError::NoAppealOpen);
+        if ctx.accounts.client_bucket.is_blocked() {
+            require_no_council(&ctx.accounts.global_config)?;
+            let now = Clock::get()?.unix_timestamp;
+            reset_bucket(
+                &ctx.accounts.global_config,
+                &mut ctx.accounts.global_stats,
+                &mut ctx.accounts.client_bucket,
+                now,
+            )?;
+        }
+        let appeal = &ctx.accounts.appeal;
+        emit!(AppealResolved { client: appeal.client, approved: true, bond_lamports: appeal.bond_lamports });
+        msg!("Appeal approved: {}", appeal.client);
+        // `close = client_wallet` hands back the bond and the rent
+        Ok(())
+    }
+
+    // Keeps the block and forfeits the bond to the treasury. The appeal account
+    // stays, closed, so the cooldown runs from the denial.
+    pub fn deny_appeal(ctx: Context<DenyAppeal>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let appeal = &mut ctx.accounts.appeal;
+        require!(appeal.is_open(), RateLimiterError::NoAppealOpen);
+
+        let bond = appeal.bond_lamports;
+        appeal.sub_lamports(bond)?;
+        ctx.accounts.treasury.add_lamports(bond)?;
+        appeal.bond_lamports = 0;
+        appeal.filed_at = 0;
+        appeal.denied_at = now;
+
+        emit!(AppealResolved { client: appeal.client, approved: false, bond_lamports: bond });
+        msg!("Appeal denied: {}", appeal.client);
+        Ok(())
+    }
+
+    // Lets anyone close buckets idle for `stale_after_seconds`, paying them
+    // `gc_bounty_bps` of the rent for it. A zero threshold turns collection off.
+    pub fn set_gc_policy(ctx: Context<UpdateConfig>, stale_after_seconds: i64, gc_bounty_bps: u16) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        require!(stale_after_seconds >= 0, RateLimiterError::InvalidConfig);
+        require!(gc_bounty_bps as u64 <= BPS_DENOMINATOR, RateLimiterError::InvalidConfig);
+        let gc = &mut ctx.accounts.global_config;
+        gc.stale_after_seconds = stale_after_seconds;
+        gc.gc_bounty_bps = gc_bounty_bps;
+        msg!("Buckets idle {}s are collectable, bounty {} bps", stale_after_seconds, gc_bounty_bps);
+        Ok(())
+    }
+
+    // How fast violations wear off: each bucket's count halves once per
+    // `halflife_seconds`, worked out whenever it is read or added to. Zero keeps
+    // them forever. Buckets already stamped decay under the new value from then on.
+    pub fn set_violation_halflife(ctx: Context<UpdateConfig>, halflife_seconds: u32) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        ctx.accounts.global_config.violation_halflife_seconds = halflife_seconds;
+        msg!("Violations halve every {}s", halflife_seconds);
+        Ok(())
+    }
+
+    // Clears a bucket's violations once it has gone `seconds` without an accepted
+    // consume, at its next consume. Blocks are never lifted this way. Zero turns
+    // forgiveness off.
+    pub fn set_inactivity_forgiveness(ctx: Context<UpdateConfig>, seconds: i64) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        require!(seconds >= 0, RateLimiterError::InvalidConfig);
+        ctx.accounts.global_config.inactivity_forgiveness_seconds = seconds;
+        msg!("Violations forgiven after {}s idle", seconds);
+        Ok(())
+    }
+
+    // Lamports a client wallet must hold for its consumes to go through, checked
+    // against the signer's balance and never charged. Sponsored buckets and ones
+    // set_balance_exempt has marked skip the check. Zero turns it off.
+    pub fn set_min_client_balance(ctx: Context<UpdateConfig>, lamports: u64) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        ctx.accounts.global_config.min_client_balance_lamports = lamports;
+        msg!("Clients must hold {} lamports to consume", lamports);
+        Ok(())
+    }
+
+    // How long a bucket must go without an accepted consume before its recovery
+    // delegate may close it. Zero turns recovery off.
+    pub fn set_recovery_inactivity(ctx: Context<UpdateConfig>, seconds: i64) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        require!(seconds >= 0, RateLimiterError::InvalidConfig);
+        ctx.accounts.global_config.recovery_inactive_seconds = seconds;
+        msg!("Recovery delegates may close buckets idle for {}s", seconds);
+        Ok(())
+    }
+
+    // What later update_config calls that change max_requests or window_seconds do
+    // to windows already open, as a PRORATION_* mode. reset_windows still resets
+    // whatever the mode.
+    pub fn set_proration_mode(ctx: Context<UpdateConfig>, mode: u8) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        require!(mode <= PRORATION_PRORATE, RateLimiterError::InvalidConfig);
+        ctx.accounts.global_config.proration_mode = mode;
+        msg!("Proration mode set to {}", mode);
+        Ok(())
+    }
+
+    // Replaces consume_amount's bulk discounts. An empty list charges every batch
+    // in full again.
+    pub fn set_discount_tiers(ctx: Context<UpdateConfig>, tiers: Vec<DiscountTier>) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        validation::validate_discount_tiers(&tiers)?;
+
+        let gc = &mut ctx.accounts.global_config;
+        gc.discount_tiers = [DiscountTier::default(); MAX_DISCOUNT_TIERS];
+        gc.discount_tiers[..tiers.len()].copy_from_slice(&tiers);
+        gc.discount_tier_count = tiers.len() as u8;
+        msg!("Discount tiers set: {} rows", tiers.len());
+        Ok(())
+    }
+
+    // Refuses consumes once gate_check denials pass `threshold` within
+    // `window_seconds`. Either set to zero turns the breaker off. The count starts
+    // over on every change, which is also how the admin resets a tripped breaker.
+    pub fn set_circuit_breaker(ctx: Context<ConfigureStats>, threshold: u64, window_seconds: i64) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        require!(window_seconds >= 0, RateLimiterError::InvalidConfig);
+        let stats = &mut ctx.accounts.global_stats;
+        stats.circuit_breaker_threshold = threshold;
+        stats.circuit_breaker_window_seconds = window_seconds;
+        stats.rejection_window_start = 0;
+        stats.rejection_count = 0;
+        ctx.accounts.global_config.set_circuit_breaker_on(stats.circuit_breaker_enabled());
+        msg!("Circuit breaker: {} denials per {}s", threshold, window_seconds);
+        Ok(())
+    }
+
+    // Lets anyone run rebalance_limits under `params`, see logic::rebalance. The
+    // first interval starts now, from the current stats. A zero step turns the
+    // crank off.
+    pub fn set_rebalance_policy(ctx: Context<SetRebalancePolicy>, params: RebalanceParams) -> Result<()> {
+        require_no_council(&ctx.accounts.global_config)?;
+        validation::validate_rebalance_params(&params)?;
+        let stats = &ctx.accounts.global_stats;
+        let policy = &mut ctx.accounts.rebalance_policy;
+        policy.params = params;
+        policy.last_rebalance_at = Clock::get()?.unix_timestamp;
+        policy.requests_mark = stats.total_requests;
+        policy.denials_mark = stats.total_denials;
+        policy.quiet_streak = 0;
+        policy.bump = ctx.bumps.rebalance_policy;
+        msg!(
+            "Rebalance: step {} within {}..={}, raise above {} bps denied, every {}s",
+            params.step,
+            params.floor,
+            params.ceiling,
+            params.target_rejection_bps,
+            params.min_interval_seconds
+        );
+        Ok(())
+    }
+
+    // Permissionless crank: judges the interval since the last run and moves
+    // max_requests by at most one step. Once per min_interval_seconds, and not
+    // while paused, when no consume gets far enough to be denied and every
+    // interval would look quiet.
+    pub fn rebalance_limits(ctx: Context<RebalanceLimits>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let policy = &mut ctx.accounts.rebalance_policy;
+        require!(policy.params.step > 0, RateLimiterError::RebalanceDisabled);
+        require!(
+            now.saturating_sub(policy.last_rebalance_at) >= policy.params.min_interval_seconds,
+            RateLimiterError::RebalanceTooSoon
+        );
+        let gc = &mut ctx.accounts.global_config;
+        require_unpaused(gc, now)?;
+
+        let stats = &ctx.accounts.global_stats;
+        let outcome = logic::rebalance(policy, stats, gc);
+        let old_max_requests = gc.max_requests;
+        if outcome.max_requests != old_max_requests {
+            gc.max_requests = outcome.max_requests;
+            gc.burst_limit = outcome.burst_limit;
+            gc.config_preset = CONFIG_PRESET_CUSTOM;
+        }
+        policy.last_rebalance_at = now;
+        policy.requests_mark = stats.total_requests;
+        policy.denials_mark = stats.total_denials;
+        policy.quiet_streak = outcome.quiet_streak;
+
+        emit!(LimitsRebalanced {
+            old_max_requests,
+            new_max_requests: outcome.max_requests,
+            accepted: outcome.accepted,
+            denied: outcome.denied,
+            rejection_bps: outcome.rejection_bps,
+            quiet_streak: outcome.quiet_streak,
+        });
+        msg!(
+            "Limits rebalanced: max_requests {} -> {}, {} bps denied",
+            old_max_requests,
+            outcome.max_requests,
+            outcome.rejection_bps
+        );
+        Ok(())
+    }
+
+    // Permissionless crank: ranks `client_bucket` on the leaderboard by its
+    // total_requests, creating the board on first use. A bucket already on it is
+    // updated in place; a new one has to beat the lowest entry of a full board,
+    // which it evicts. Purely advisory, nothing in the limits reads the board.
+    pub fn submit_to_leaderboard(ctx: Context<SubmitToLeaderboard>) -> Result<()> {
+        let bucket = &ctx.accounts.client_bucket;
+        let board = &mut ctx.accounts.leaderboard;
+        board.bump = ctx.bumps.leaderboard;
+        let rank = board.submit(bucket.owner, bucket.total_requests);
+        let rank = rank.ok_or(RateLimiterError::BelowLeaderboardThreshold)?;
+        emit!(LeaderboardUpdated { owner: bucket.owner, total: bucket.total_requests, rank: rank as u8 });
+        msg!("Leaderboard: {} at rank {} with {} requests", bucket.owner, rank + 1, bucket.total_requests);
+        Ok(())
+    }
+
+    pub fn emit_stats_snapshot<'info>(
+        ctx: Context<'_, '_, 'info, 'info, EmitStatsSnapshot<'info>>,
+    ) -> Result<()> {
+        let stats = &mut ctx.accounts.global_stats;
+        let clock = Clock::get()?;
+
+        // Permissionless, so throttle it to keep cranks from bloating logs
+        let next_snapshot_at = stats
+            .last_snapshot_at
+            .checked_add(stats.snapshot_interval_seconds)
+            .ok_or(RateLimiterError::ArithmeticOverflow)?;
+        require!(clock.unix_timestamp >= next_snapshot_at, RateLimiterError::SnapshotTooSoon);
+        stats.last_snapshot_at = clock.unix_timestamp;
+
+        let bucket = match ctx.remaining_accounts.first() {
+            Some(info) => Some(Account::<ClientBucket>::try_from(info)?),
+            None => None,
+        };
+
+        emit!(StatsSnapshot {
+            total_clients: stats.total_clients,
+            blocked_clients: stats.blocked_clients,
+            total_requests: stats.total_requests,
+            total_saturated: stats.total_saturated,
+            window_rollovers: stats.window_rollovers,
+            timestamp: clock.unix_timestamp,
+            slot: clock.slot,
+            bucket_owner: bucket.as_ref().map(|b| b.owner),
+            bucket_request_count: bucket.as_ref().map(|b| b.request_count),
+            bucket_total_requests: bucket.as_ref().map(|b| b.total_requests),
+            bucket_total_saturated: bucket.as_ref().map(|b| b.total_saturated()),
+        });
+        Ok(())
+    }
+
+    // For integrators that cannot CPI into us: fails with ConsumeNotFound unless a
+    // consume_request or consume_amount signed by `client` runs earlier in the same
+    // transaction. Called directly or by CPI, "earlier" is relative to the top-level
+    // instruction. Consumes made by CPI are invisible to the sysvar and never count.
+    pub fn verify_consumed_in_tx(ctx: Context<VerifyConsumedInTx>, client: Pubkey) -> Result<()> {
+        let sysvar = ctx.accounts.instructions.to_account_info();
+        let current = load_current_index_checked(&sysvar)?;
+        let earlier = (0..current)
+            .map(|index| load_instruction_at_checked(index as usize, &sysvar))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        require!(
+            introspection::consumed_before(earlier, &client),
+            RateLimiterError::ConsumeNotFound
+        );
+        Ok(())
+    }
+
+    // Permissionless audit hook: returns the first violated invariant as an
+    // INVARIANT_* code (INVARIANT_OK if none) so off-chain tools can simulate it
+    pub fn verify_bucket(ctx: Context<VerifyBucket>) -> Result<u8> {
+        let code = invariants::first_violation(&ctx.accounts.client_bucket, &ctx.accounts.global_config);
+        if code != INVARIANT_OK {
+            msg!("Bucket {} violates invariant {}", ctx.accounts.client_bucket.owner, code);
+        }
+        Ok(code)
+    }
+
+    // Dry run for an update_config: how many of the buckets passed as remaining
+    // accounts used more than `new_config.max_requests` in their latest window.
+    // Writes nothing; simulate it. Buckets passed twice are counted twice.
+    pub fn estimate_config_impact<'info>(
+        ctx: Context<'_, '_, 'info, 'info, EstimateConfigImpact<'info>>,
+        new_config: RateLimiterConfig,
+    ) -> Result<ConfigImpact> {
+        require!(new_config.max_requests > 0, RateLimiterError::InvalidConfig);
+        require!(new_config.window_seconds > 0, RateLimiterError::InvalidConfig);
+        require!(new_config.burst_limit >= new_config.max_requests, RateLimiterError::InvalidConfig);
+
+        let mut impact = ConfigImpact { evaluated: 0, would_reject: 0 };
+        for info in ctx.remaining_accounts {
+            let bucket = Account::<ClientBucket>::try_from(info)?;
+            require_supported(&bucket, &ctx.accounts.global_config)?;
+            impact.evaluated += 1;
+            if logic::exceeds_max(&bucket, new_config.max_requests) {
+                impact.would_reject += 1;
+            }
+        }
+        Ok(impact)
+    }
+
+    // Read-only view of a bucket for dashboards, meant to be simulated. Counts as
+    // of now under the limits a consume would see, schedule and feeder included.
+    pub fn get_quota(ctx: Context<GetQuota>) -> Result<Quota> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &wallet_limits(&ctx.accounts.global_config, ctx.accounts.schedule.as_deref(), now)?;
+        let bucket = &ctx.accounts.client_bucket;
+        require_supported(bucket, config)?;
+        let (remaining, resets_in) = logic::quota_at(bucket, config, now);
+        Ok(Quota {
+            owner: bucket.owner,
+            remaining,
+            resets_in,
+            total_requests: bucket.total_requests,
+            violations: logic::decayed_violations(bucket, config, now).0,
+            label: bucket.label_text().to_string(),
+        })
+    }
+
+    // The limits in force right now, as an EffectiveConfig in return data, so a
+    // client can show them without replaying the feeder and schedule rules. With a
+    // bucket passed, its block and freeze are reported as well.
+    pub fn get_config(ctx: Context<GetConfig>) -> Result<EffectiveConfig> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.global_config;
+        migrations::require_supported(config.version, MIN_GLOBAL_CONFIG_VERSION)?;
+        let bucket = ctx.accounts.client_bucket.as_deref();
+        if let Some(bucket) = bucket {
+            require_supported(bucket, config)?;
+        }
+        let entries = schedule_entries(config, ctx.accounts.schedule.as_deref())?;
+        Ok(logic::effective_view(config, entries, bucket, now))
+    }
+
+    // A bucket's lifetime history as a BucketSnapshot, in return data and a
+    // BucketSnapshotExported event, for import into a redeployment. Its hash goes
+    // into the owner's SnapshotRecord, which the import checks it against, so only
+    // the latest export imports. Permissionless: the payer funds the record.
+    pub fn export_bucket_snapshot(ctx: Context<ExportBucketSnapshot>) -> Result<BucketSnapshot> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.global_config;
+        let bucket = &ctx.accounts.client_bucket;
+        require_supported(bucket, config)?;
+        let violations = logic::decayed_violations(bucket, config, now).0;
+        let snapshot = BucketSnapshot::new(crate::ID, bucket.owner, bucket.total_requests, violations, now);
+
+        let record = &mut ctx.accounts.snapshot_record;
+        record.owner = snapshot.owner;
+        record.hash = snapshot.hash;
+        record.exported_at = snapshot.exported_at;
+        record.bump = ctx.bumps.snapshot_record;
+        emit!(BucketSnapshotExported {
+            owner: snapshot.owner,
+            total_requests: snapshot.total_requests,
+            violations: snapshot.violations,
+            exported_at: snapshot.exported_at,
+            hash: snapshot.hash,
+        });
+        Ok(snapshot)
+    }
+
+    // The stats' usage histogram in return data, for deciding whether limits are
+    // tight or slack without scanning every bucket. Only windows a later consume
+    // closed are counted, each once, so idle buckets add nothing.
+    pub fn get_usage_histogram(ctx: Context<GetStats>) -> Result<UsageHistogram> {
+        let [under_25, under_50, under_75, full, rejected] = ctx.accounts.global_stats.usage_histogram;
+        Ok(UsageHistogram { under_25, under_50, under_75, full, rejected })
+    }
+
+    // What this deployment is, in return data, for clients to check they are
+    // compatible with before sending anything. The version is the crate's, fixed
+    // at build time; the features are whatever the config has on right now.
+    pub fn get_deployment_info(ctx: Context<GetDeploymentInfo>) -> Result<DeploymentInfo> {
+        let config = &ctx.accounts.global_config;
+        migrations::require_supported(config.version, MIN_GLOBAL_CONFIG_VERSION)?;
+        Ok(DeploymentInfo {
+            program_version: gate::PROGRAM_VERSION,
+            features_bitmask: logic::deployment_features(config),
+            limit_algorithm: LIMIT_ALGORITHM_FIXED_WINDOW,
+            config_epoch: config.config_epoch,
+        })
+    }
+
+    // What registering `client` would cost `payer` and the limits it would get,
+    // in return data, for a prospective client to check before paying rent.
+    // Nothing is created or written, so simulating it needs no signatures.
+    pub fn preview_registration(ctx: Context<PreviewRegistration>) -> Result<RegistrationPreview> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.global_config;
+        migrations::require_supported(config.version, MIN_GLOBAL_CONFIG_VERSION)?;
+        let entries = schedule_entries(config, ctx.accounts.schedule.as_deref())?;
+        let payer_meta = if ctx.accounts.payer_meta.data_is_empty() {
+            None
+        } else {
+            // A PayerMeta in an old layout fails here, as registering would
+            Some(PayerMeta::try_deserialize(&mut &ctx.accounts.payer_meta.try_borrow_data()?[..])?)
+        };
+        let taken = !(ctx.accounts.client_bucket.data_is_empty()
+            && ctx.accounts.fast_bucket.data_is_empty()
+            && ctx.accounts.shard_parent.data_is_empty());
+        let program_owned = ctx.accounts.client.owner != &system_program::ID;
+        Ok(logic::registration_preview(config, entries, now, payer_meta.as_ref(), taken, program_owned, &Rent::get()?))
+    }
+
+    // Registers the snapshot's owner with its exported history and a fresh
+    // window. The snapshot must be the one the source deployment last recorded
+    // for the owner; see snapshot.rs. The owner signs for its bucket as in
+    // register_client, and the admin co-signs. Registration fees and cooldowns do
+    // not apply to an import.
+    #[cfg(feature = "snapshot-import")]
+    pub fn import_bucket_snapshot(ctx: Context<ImportBucketSnapshot>, snapshot: BucketSnapshot) -> Result<()> {
+        // The owner constraint already pinned the account to the source program
+        let record = SnapshotRecord::try_deserialize(&mut &ctx.accounts.snapshot_record.try_borrow_data()?[..])
+            .map_err(|_| error!(RateLimiterError::SnapshotNotRecorded))?;
+        snapshot.verify_recorded(&SNAPSHOT_SOURCE_PROGRAM_ID, &record)?;
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.global_config;
+        require_unpaused(config, now)?;
+        require!(
+            ctx.accounts.fast_bucket.data_is_empty() && ctx.accounts.shard_parent.data_is_empty(),
+            RateLimiterError::BucketKindConflict
+        );
+
+        let bucket = &mut ctx.accounts.client_bucket;
+        bucket.version = CLIENT_BUCKET_VERSION;
+        bucket.owner = snapshot.owner;
+        bucket.request_count = 0;
+        bucket.window_start = now;
+        bucket.total_requests = snapshot.total_requests;
+        bucket.flags = 0;
+        bucket.set_total_saturated(snapshot.total_requests == u64::MAX);
+        bucket.violations = snapshot.violations;
+        // The count was decayed to the export, so the decay carries on from there
+        bucket.violations_updated_at = if snapshot.violations == 0 { 0 } else { snapshot.exported_at };
+        bucket.config_epoch = config.config_epoch;
+        bucket.burst_credits = 0;
+        bucket.priority_count = 0;
+        bucket.escrowed_count = 0;
+        bucket.bonus_requests = 0;
+        bucket.last_nonce = 0;
+        bucket.nonce_bitmap = 0;
+        bucket.last_refund_nonce = 0;
+        bucket.refunded_count = 0;
+        bucket.registered_by = ctx.accounts.payer.key();
+        bucket.last_request_ts = now;
+        bucket.bump = ctx.bumps.client_bucket;
+        bucket.label = [0; 32];
+
+        let stats = &mut ctx.accounts.global_stats;
+        stats.total_clients = stats.total_clients.saturating_add(1);
+        debug_assert_invariants(bucket, config);
+
+        msg!(
+            "Bucket imported: {} with {} requests from {}",
+            snapshot.owner,
+            snapshot.total_requests,
+            snapshot.program_id
+        );
+        Ok(())
+    }
+}
+
+// Shared by every consume entry point once the accounts are loaded. `amount` has
+// already been validated by the caller. The decision itself lives in
+// logic::apply_consume; this applies it to the accounts and the logs.
+fn consume(
+    config: &GlobalConfig,
+    schedule: Option<&Schedule>,
+    bucket: &mut ClientBucket,
+    stats: Option<&mut GlobalStats>,
+    now: i64,
+    amount: u64,
+) -> Result<()> {
+    let config = &wallet_limits(config, schedule, now)?;
+    require_wallet_mode(config)?;
+    require_supported(bucket, config)?;
+    forgive_idle_penalties(config, bucket, now);
+    // Bulk discounts shrink what the window is charged; the lifetime totals still
+    // count every unit. No tier starts below 2, so only consume_amount gets one.
+    let charged = logic::discounted_amount(config.discount_tiers(), amount);
+    match logic::apply_consume(bucket, config, now, charged) {
+        Ok(consumed) => commit_consume(config, bucket, stats, now, amount, consumed),
+        Err(rejection) => reject(config, now, bucket, rejection),
+    }
+}
+
+// consume_priority's path, shared with consume_v2: one unit, paid with a banked
+// credit first when `priority` is set
+fn priority_consume(
+    config: &GlobalConfig,
+    schedule: Option<&Schedule>,
+    bucket: &mut ClientBucket,
+    stats: Option<&mut GlobalStats>,
+    now: i64,
+    priority: bool,
+) -> Result<()> {
+    let config = &wallet_limits(config, schedule, now)?;
+    require_wallet_mode(config)?;
+    require_supported(bucket, config)?;
+    forgive_idle_penalties(config, bucket, now);
+
+    match logic::apply_priority_consume(bucket, config, now, priority) {
+        Ok((consumed, from_burst)) => {
+            commit_consume(config, bucket, stats, now, 1, consumed)?;
+            emit!(PriorityConsumed {
+                owner: bucket.owner,
+                source: if from_burst { CONSUME_SOURCE_BURST } else { CONSUME_SOURCE_WINDOW },
+                burst_credits: bucket.burst_credits,
+                priority_count: bucket.priority_count,
+                event_tag: bucket.event_tag,
+            });
+            Ok(())
+        }
+        Err(rejection) => reject(config, now, bucket, rejection),
+    }
+}
+
+// Wallet limits at `now`: the time-of-day schedule when one is set, then any live
+// feeder override on top. A set schedule has to be passed, otherwise a client
+// could skip the quieter hours' limit by leaving the account out.
+fn wallet_limits(config: &GlobalConfig, schedule: Option<&Schedule>, now: i64) -> Result<GlobalConfig> {
+    Ok(logic::wallet_config(config, schedule_entries(config, schedule)?, now))
+}
+
+fn schedule_entries<'a>(config: &GlobalConfig, schedule: Option<&'a Schedule>) -> Result<&'a [ScheduleEntry]> {
+    match schedule {
+        Some(schedule) => Ok(schedule.entries()),
+        None if config.scheduled() => err!(RateLimiterError::ScheduleAccountMissing),
+        None => Ok(&[]),
+    }
+}
+
+fn require_wallet_mode(config: &GlobalConfig) -> Result<()> {
+    let top_level = get_stack_height() == TRANSACTION_LEVEL_STACK_HEIGHT;
+    require!(
+        logic::wallet_consume_allowed(config, top_level),
+        RateLimiterError::CallerProgramRequired
+    );
+    Ok(())
+}
+
+// Top-level calls pass. A CPI needs the immediate caller's allowlist entry, and the
+// instructions sysvar to name that caller; see introspection::caller_allowed. For
+// a CPI, returns the caller's instruction.
+fn require_allowed_caller(
+    entry: Option<&CallerAllowlist>,
+    instructions: Option<&AccountInfo>,
+) -> Result<Option<Instruction>> {
+    let height = get_stack_height();
+    if height <= TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Ok(None);
+    }
+    let caller_ix = match instructions {
+        Some(sysvar) if height == TRANSACTION_LEVEL_STACK_HEIGHT + 1 => {
+            let current = load_current_index_checked(sysvar)?;
+            Some(load_instruction_at_checked(current as usize, sysvar)?)
+        }
+        _ => None,
+    };
+    require!(
+        introspection::caller_allowed(
+            caller_ix.as_ref().map(|ix| ix.program_id),
+            entry.map(|entry| entry.program_id)
+        ),
+        RateLimiterError::CallerNotAllowed
+    );
+    Ok(caller_ix)
+}
+
+// A client the caller program signed for is one of its PDAs. While any program is
+// denylisted, such a consume must pass the caller's ProgramDenylist address, which
+// proves the caller is not on the list by being empty.
+fn require_owner_not_denied(
+    config: &GlobalConfig,
+    client: &Pubkey,
+    caller_ix: Option<&Instruction>,
+    denylist: Option<&AccountInfo>,
+) -> Result<()> {
+    let caller_ix = match caller_ix {
+        Some(ix) if config.denied_owner_programs > 0 && introspection::signed_by_caller(ix, client) => ix,
+        _ => return Ok(()),
+    };
+    let denylist = denylist.ok_or(RateLimiterError::OwnerDenylistMissing)?;
+    require_keys_eq!(
+        denylist.key(),
+        pda::program_denylist_address(&caller_ix.program_id).0,
+        RateLimiterError::OwnerDenylistMissing
+    );
+    // Only block_program_owners can put data at that address
+    require!(denylist.data_is_empty(), RateLimiterError::ProgramOwnersBlocked);
+    Ok(())
+}
+
+// Writes an accepted consume to the accounts, with its events and logs. The
+// program-wide totals only move when the consume was passed the stats account.
+fn commit_consume(
+    config: &GlobalConfig,
+    bucket: &mut ClientBucket,
+    mut stats: Option<&mut GlobalStats>,
+    now: i64,
+    amount: u64,
+    consumed: logic::Consumed,
+) -> Result<()> {
+    if consumed.clock_clamped {
+        msg!("Clock anomaly: window_start {} ahead of now {}, clamping", bucket.window_start, now);
+    }
+    if let Some(rollover) = consumed.rollover {
+        emit!(WindowClosed {
+            owner: bucket.owner,
+            closed_window_start: rollover.closed_window_start,
+            closed_count: rollover.closed_count,
+            new_window_start: consumed.bucket.window_start,
+            skipped_windows: rollover.skipped_windows,
+            label: bucket.label_text().to_string(),
+            event_tag: bucket.event_tag,
+        });
+        if let Some(stats) = stats.as_deref_mut() {
+            stats.window_rollovers = stats.window_rollovers.saturating_add(1);
+            let class = logic::usage_class(bucket, config);
+            stats.usage_histogram[class] = stats.usage_histogram[class].saturating_add(1);
+        }
+        verbose_msg!("Window reset for client: {}", bucket.owner);
+    }
+    *bucket = consumed.bucket;
+
+    // Lifetime totals are analytics only: pin them at u64::MAX and flag them as a
+    // floor rather than failing the consume
+    logic::add_to_total(bucket, amount);
+    if let Some(stats) = stats {
+        match stats.total_requests.checked_add(amount) {
+            Some(total) => stats.total_requests = total,
+            None => {
+                stats.total_requests = u64::MAX;
+                stats.total_saturated = true;
+            }
+        }
+    }
+
+    let remaining = window::window_remaining(now, bucket.window_start, config.window_seconds);
+    #[cfg(feature = "verbose-logs")]
+    msg!(
+        "Request consumed. Used: {}/{} | Window ends in: {}s",
+        bucket.request_count,
+        config.max_requests,
+        remaining
+    );
+    // Used, max, seconds left in the window
+    #[cfg(not(feature = "verbose-logs"))]
+    solana_program::log::sol_log_64(
+        bucket.request_count,
+        config.max_requests,
+        remaining as u64,
+        0,
+        0,
+    );
+    // Escrowing for an offer commits nothing consumed, so it has no event
+    if amount > 0 && bucket.has_event_tag() {
+        emit!(BucketConsumed {
+            owner: bucket.owner,
+            amount,
+            request_count: bucket.request_count,
+            event_tag: bucket.event_tag,
+        });
+    }
+    debug_assert_invariants(bucket, config);
+    Ok(())
+}
+
+// Clears the violations of a bucket idle past inactivity_forgiveness_seconds
+// before its consume is judged. A rejected consume rolls this back with the rest.
+fn forgive_idle_penalties(config: &GlobalConfig, bucket: &mut ClientBucket, now: i64) {
+    if !logic::penalties_forgivable(bucket, config, now) {
+        return;
+    }
+    emit!(PenaltiesForgiven { owner: bucket.owner, violations: bucket.violations, idle_since: bucket.last_request_ts });
+    bucket.violations = 0;
+    bucket.violations_updated_at = 0;
+}
+
+// Refuses accounts older than the minimum supported layout versions
+fn require_supported(bucket: &ClientBucket, config: &GlobalConfig) -> Result<()> {
+    migrations::require_supported(config.version, MIN_GLOBAL_CONFIG_VERSION)?;
+    migrations::require_supported(bucket.version, MIN_CLIENT_BUCKET_VERSION)
+}
+
+// Only reads the wallet's balance; nothing is moved
+fn require_min_balance(config: &GlobalConfig, bucket: &ClientBucket, lamports: u64) -> Result<()> {
+    require!(logic::balance_sufficient(bucket, config, lamports), RateLimiterError::BalanceTooLow);
+    Ok(())
+}
+
+// Resizes a program-owned account to `new_len`, topping it up to rent exemption
+// from `payer` first
+fn grow_account<'info>(
+    info: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    new_len: usize,
+) -> Result<()> {
+    top_up_to_rent(info, payer, system_program, new_len)?;
+    info.resize(new_len)?;
+    Ok(())
+}
+
+// Has `payer` send `info` exactly what it lacks to be rent-exempt at `len` bytes,
+// and returns the amount. A payer that cannot cover it gets a named error rather
+// than the system program's bare transfer failure.
+fn top_up_to_rent<'info>(
+    info: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    len: usize,
+) -> Result<u64> {
+    let shortfall = logic::rent_shortfall(&Rent::get()?, len, info.lamports());
+    if shortfall == 0 {
+        return Ok(0);
+    }
+    require!(payer.lamports() >= shortfall, RateLimiterError::InsufficientRentFunding);
+    system_program::transfer(
+        CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: payer.to_account_info(),
+                to: info.clone(),
+            },
+        ),
+        shortfall,
+    )?;
+    Ok(shortfall)
+}
+
+// Called at the end of every handler that writes a bucket. Compiled out of release
+// builds; verify_bucket is the on-chain equivalent.
+fn debug_assert_invariants(bucket: &ClientBucket, config: &GlobalConfig) {
+    debug_assert!(
+        invariants::check_invariants(bucket, config).is_ok(),
+        "bucket invariant {} violated",
+        invariants::first_violation(bucket, config)
+    );
+}
+
+// The config is a singleton, so whoever initializes it first owns the limiter. Only
+// the program's upgrade authority may do that, which stops anyone racing the deploy.
+#[cfg(not(feature = "skip-upgrade-authority-check"))]
+fn verify_upgrade_authority(
+    program: &Program<program::SolanaRateLimiter>,
+    program_data: &AccountInfo,
+    admin: &Pubkey,
+) -> Result<()> {
+    require!(
+        program.programdata_address()? == Some(program_data.key()),
+        RateLimiterError::NotUpgradeAuthority
+    );
+    let data = program_data.try_borrow_data()?;
+    let program_data = ProgramData::try_deserialize(&mut &data[..])?;
+    require!(
+        program_data.upgrade_authority_address == Some(*admin),
+        RateLimiterError::NotUpgradeAuthority
+    );
+    Ok(())
+}
+
+// Localnet escape hatch for test harnesses that load the program without an
+// upgradeable ProgramData account
+#[cfg(feature = "skip-upgrade-authority-check")]
+fn verify_upgrade_authority(
+    _program: &Program<program::SolanaRateLimiter>,
+    _program_data: &AccountInfo,
+    _admin: &Pubkey,
+) -> Result<()> {
+    Ok(())
+}
+
+// Records the violation and emits ConsumeRejected before failing. The runtime rolls
+// back account writes when the error propagates, so the new violation count only
+// sticks on paths that reject without failing (gate_check); the event survives in
+// the failed transaction's logs either way.
+fn reject(config: &GlobalConfig, now: i64, bucket: &mut ClientBucket, rejection: Rejection) -> Result<()> {
+    let (reason_code, error) = match rejection {
+        // Not the client's doing: no violation, no event
+        Rejection::Paused => return Err(paused_error(config, now)),
+        Rejection::Overflow => return err!(RateLimiterError::ArithmeticOverflow),
+        Rejection::Frozen => return err!(RateLimiterError::ClientFrozen),
+        Rejection::Blocked => (REJECT_CLIENT_BLOCKED, RateLimiterError::ClientBlocked),
+        Rejection::RateLimit => (REJECT_RATE_LIMIT, RateLimiterError::RateLimitExceeded),
+        Rejection::BurstLimit => (REJECT_BURST_LIMIT, RateLimiterError::BurstLimitExceeded),
+    };
+    record_violation(bucket, config, now, reason_code);
+    Err(error.into())
+}
+
+// ProgramPaused, with a PauseInfo in return data saying since when and why. The
+// runtime logs return data even for failed instructions, so clients can read it
+// from the logs of the rejected transaction.
+fn paused_error(config: &GlobalConfig, now: i64) -> Error {
+    let mut data = Vec::new();
+    let info = logic::pause_info(config, now);
+    if info.serialize(&mut data).is_ok() {
+        set_return_data(&data);
+    }
+    error!(RateLimiterError::ProgramPaused)
+}
+
+fn require_unpaused(config: &GlobalConfig, now: i64) -> Result<()> {
+    if config.pause_active(now) {
+        return Err(paused_error(config, now));
+    }
+    Ok(())
+}
+
+// gate_check's soft rejection: the client may try again once its window rolls over
+fn deny(bucket: &mut ClientBucket, config: &GlobalConfig, now: i64, reason_code: u8) -> GateResult {
+    record_violation(bucket, config, now, reason_code);
+    GateResult {
+        allowed: false,
+        remaining: 0,
+        retry_after: window::window_remaining(now, bucket.window_start, config.window_seconds),
+    }
+}
+
+fn record_violation(bucket: &mut ClientBucket, config: &GlobalConfig, now: i64, reason_code: u8) {
+    logic::add_violation(bucket, config, now);
+    bucket.set_window_rejected(true);
+    emit!(ConsumeRejected {
+        owner: bucket.owner,
+        reason_code,
+        request_count: bucket.request_count,
+        violations: bucket.violations,
+        label: bucket.label_text().to_string(),
+        event_tag: bucket.event_tag,
+    });
+}
+
+// Lamports a program account holds for someone beyond its rent: an open appeal's
+// bond, or the whole of the sponsor pool, which withdraw_sponsor_pool drains
+// instead. Any other account holds nothing.
+fn deposit_held(info: &AccountInfo) -> Result<u64> {
+    let data = info.try_borrow_data()?;
+    if data.starts_with(SponsorPool::DISCRIMINATOR) {
+        return Ok(info.lamports());
+    }
+    if !data.starts_with(Appeal::DISCRIMINATOR) {
+        return Ok(0);
+    }
+    Ok(Appeal::try_deserialize(&mut &data[..])?.bond_lamports)
+}
+
+// What register_sponsored can still spend: the pool's balance above its own rent
+fn sponsor_pool_available(pool: &Account<SponsorPool>) -> Result<u64> {
+    let rent = Rent::get()?.minimum_balance(8 + SponsorPool::INIT_SPACE);
+    Ok(pool.get_lamports().saturating_sub(rent))
+}
+
+// A sponsored bucket's rent came from the pool, so on any close it goes back there
+// rather than to whoever the close would pay. It has to move before Anchor's
+// `close` runs, which then finds nothing left to hand over.
+fn return_sponsored_rent(bucket: &Account<ClientBucket>, pool: Option<&UncheckedAccount>) -> Result<()> {
+    if !bucket.is_sponsored() {
+        return Ok(());
+    }
+    let pool = pool.ok_or(RateLimiterError::SponsorPoolRequired)?;
+    let lamports = bucket.get_lamports();
+    bucket.sub_lamports(lamports)?;
+    pool.add_lamports(lamports)?;
+    msg!("Returned {} lamports of sponsored rent", lamports);
+    Ok(())
+}
+
+// Every registration kind runs this for its payer: the cap on buckets it funds
+// and the cooldown since its last registration, then the fee to the treasury.
+// Throwaway clients need no funds of their own, so the payer is what a griefer
+// cannot multiply for free.
+fn charge_registration<'info>(
+    config: &GlobalConfig,
+    payer_meta: &mut Account<'info, PayerMeta>,
+    payer: &Signer<'info>,
+    treasury: Option<&UncheckedAccount<'info>>,
+    system_program: &Program<'info, System>,
+    now: i64,
+    bump: u8,
+) -> Result<()> {
+    require!(
+        logic::payer_under_cap(config, payer_meta.buckets_created),
+        RateLimiterError::PayerBucketCapReached
+    );
+    require!(
+        logic::registration_allowed(config, payer_meta.last_registration_ts, now),
+        RateLimiterError::RegistrationCooldown
+    );
+    payer_meta.payer = payer.key();
+    payer_meta.last_registration_ts = now;
+    payer_meta.bump = bump;
+    payer_meta.buckets_created = payer_meta.buckets_created.saturating_add(1);
+
+    if config.registration_fee_lamports > 0 {
+        let treasury = treasury.ok_or(RateLimiterError::InvalidTreasury)?;
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer { from: payer.to_account_info(), to: treasury.to_account_info() },
+            ),
+            config.registration_fee_lamports,
+        )?;
+    }
+    Ok(())
+}
+
+// Sets or lifts the emergency pause with its start time and reason. Re-pausing
+// keeps the original start and grace budget; unpausing clears them. Either way
+// any auto-unpause deadline is dropped, and `pause` sets a new one after.
+fn set_pause(config: &mut GlobalConfig, paused: bool, reason: u8, now: i64) {
+    let was_paused = config.emergency_pause_active(now);
+    config.set_paused(paused);
+    config.auto_unpause_at = 0;
+    if !paused {
+        config.paused_at = 0;
+        config.pause_reason = PAUSE_REASON_UNSPECIFIED;
+        config.grace_budget = 0;
+    } else {
+        if !was_paused {
+            config.paused_at = now;
+            config.grace_budget = config.pause_grace_budget;
+        }
+        config.pause_reason = reason;
+    }
+    emit!(PauseChanged { paused, paused_at: config.paused_at, reason: config.pause_reason });
+    msg!("Program paused: {} (reason {})", paused, config.pause_reason);
+}
+
+// Spends one consume of the emergency pause's grace budget while its grace period
+// lasts, returning a copy of the config with the pause lifted for the consume to
+// be checked against. The spend goes to `grace`, so a consume that leaves the
+// account out gets no grace. A rejected consume rolls the spend back with
+// everything else; a gate_check denial does not, like the rest of a denial.
+fn take_pause_grace(config: &GlobalConfig, grace: Option<&mut PauseGrace>, owner: Pubkey, now: i64) -> Option<GlobalConfig> {
+    let grace = grace?;
+    if !logic::pause_grace_open(config, grace, now) {
+        return None;
+    }
+    if grace.paused_at != config.paused_at {
+        grace.paused_at = config.paused_at;
+        grace.spent = 0;
+    }
+    grace.spent += 1;
+    let grace_ends_at = config.paused_at.saturating_add(config.pause_grace_seconds);
+    emit!(GracePeriodConsume { owner, grace_budget: logic::grace_left(config, grace), grace_ends_at });
+    let mut lifted = config.clone();
+    lifted.set_paused(false);
+    Some(lifted)
+}
+
+// Hard rejections roll the transaction back, stats included, so gate_check denials
+// are the only rejections the breaker can count. The count that trips it is kept,
+// and require_circuit_closed refuses consumes from then on.
+fn count_for_breaker(stats: &mut GlobalStats, now: i64) {
+    let Some(count) = logic::count_rejection(stats, now) else {
+        return;
+    };
+    if count.tripped {
+        emit!(CircuitTripped { rejections: count.count, window_start: count.window_start, tripped_at: now });
+        msg!("Circuit breaker tripped: {} denials since {}", count.count, count.window_start);
+    }
+    stats.rejection_window_start = count.window_start;
+    stats.rejection_count = count.count;
+}
+
+// The trip is recorded in the stats rather than the config, which consumes only
+// read, so while the breaker is on a consume has to bring the stats to be let in
+fn require_circuit_closed(config: &GlobalConfig, stats: Option<&GlobalStats>) -> Result<()> {
+    match stats {
+        Some(stats) => require!(!stats.circuit_tripped(), RateLimiterError::CircuitBreakerTripped),
+        None => require!(!config.circuit_breaker_on(), RateLimiterError::StatsAccountMissing),
+    }
+    Ok(())
+}
+
+// Counts one destructive admin action, refusing it past the throttle's cap
+fn throttle_admin_action(config: &mut GlobalConfig, now: i64) -> Result<()> {
+    let Some(count) = logic::count_admin_action(config, now) else {
+        return Ok(());
+    };
+    require!(!count.throttled, RateLimiterError::AdminThrottled);
+    config.admin_action_window_start = count.window_start;
+    config.admin_actions_in_window = count.count;
+    Ok(())
+}
+
+// The direct admin path for the actions a council takes over: every admin
+// instruction that changes the config or a bucket's standing
+fn require_no_council(config: &GlobalConfig) -> Result<()> {
+    require!(!config.has_council(), RateLimiterError::CouncilApprovalRequired);
+    Ok(())
+}
+
+// add_council_member's effect, shared with AdminAction::AddCouncilMember
+fn seat_council_member(gc: &mut GlobalConfig, member: Pubkey, threshold: u8) -> Result<()> {
+    let size = gc.council_size as usize;
+    require!(size < MAX_COUNCIL_MEMBERS, RateLimiterError::CouncilFull);
+    require!(member != Pubkey::default() && !gc.is_council_member(&member), RateLimiterError::InvalidConfig);
+    gc.council[size] = member;
+    gc.council_size += 1;
+    set_council_threshold(gc, threshold)?;
+    msg!("Council member added: {} ({} of {})", member, threshold, gc.council_size);
+    Ok(())
+}
+
+// AdminAction::RemoveCouncilMember's effect. Removing the last member returns the
+// admin to acting alone (threshold 0).
+fn unseat_council_member(gc: &mut GlobalConfig, member: Pubkey, threshold: u8) -> Result<()> {
+    let seat = gc
+        .council_members()
+        .iter()
+        .position(|key| *key == member)
+        .ok_or(RateLimiterError::NotCouncilMember)?;
+    let size = gc.council_size as usize;
+    gc.council.copy_within(seat + 1..size, seat);
+    gc.council[size - 1] = Pubkey::default();
+    gc.council_size -= 1;
+    set_council_threshold(gc, threshold)?;
+    msg!("Council member removed: {} ({} of {})", member, threshold, gc.council_size);
+    Ok(())
+}
+
+// 1..=size with a council, 0 without one
+fn set_council_threshold(config: &mut GlobalConfig, threshold: u8) -> Result<()> {
+    let valid = if config.has_council() {
+        threshold >= 1 && threshold <= config.council_size
+    } else {
+        threshold == 0
+    };
+    require!(valid, RateLimiterError::InvalidConfig);
+    config.council_threshold = threshold;
+    Ok(())
+}
+
+// initialize's effect, shared with initialize_with_preset, which records the
+// preset it resolved
+fn init_config(ctx: Context<Initialize>, config: RateLimiterConfig, preset: u8) -> Result<()> {
+    verify_upgrade_authority(
+        &ctx.accounts.program,
+        &ctx.accounts.program_data.to_account_info(),
+        &ctx.accounts.admin.key(),
+    )?;
+
+    validation::validate_limits(&config)?;
+
+    let gc = &mut ctx.accounts.global_config;
+    gc.version = GLOBAL_CONFIG_VERSION;
+    gc.admin = ctx.accounts.admin.key();
+    gc.max_requests = config.max_requests;
+    gc.window_seconds = config.window_seconds;
+    gc.burst_limit = config.burst_limit;
+    gc.flags = 0;
+    gc.config_epoch = 0;
+    gc.bump = ctx.bumps.global_config;
+    gc.hook_program = Pubkey::default();
+    gc.limit_feeder = Pubkey::default();
+    gc.dynamic_floor = 0;
+    gc.dynamic_ceiling = 0;
+    gc.dynamic_max = 0;
+    gc.dynamic_valid_until = 0;
+    gc.council = [Pubkey::default(); MAX_COUNCIL_MEMBERS];
+    gc.council_size = 0;
+    gc.council_threshold = 0;
+    gc.proposal_count = 0;
+    gc.recovery_admin = Pubkey::default();
+    gc.recovery_eta = 0;
+    gc.scheduled_pause_start = 0;
+    gc.scheduled_pause_end = 0;
+    gc.treasury = Pubkey::default();
+    gc.registration_fee_lamports = 0;
+    gc.registration_cooldown_seconds = 0;
+    gc.stale_after_seconds = 0;
+    gc.gc_bounty_bps = 0;
+    gc.discount_tiers = [DiscountTier::default(); MAX_DISCOUNT_TIERS];
+    gc.discount_tier_count = 0;
+    gc.appeal_bond_lamports = 0;
+    gc.appeal_cooldown_seconds = 0;
+    gc.paused_at = 0;
+    gc.pause_reason = PAUSE_REASON_UNSPECIFIED;
+    gc.auto_unpause_at = 0;
+    gc.config_preset = preset;
+    gc.violation_halflife_seconds = 0;
+    gc.admin_action_window_seconds = 0;
+    gc.max_admin_actions_per_window = 0;
+    gc.admin_action_window_start = 0;
+    gc.admin_actions_in_window = 0;
+    gc.refund_authority = Pubkey::default();
+    gc.max_refunds_per_window = 0;
+    gc.max_buckets_per_payer = 0;
+    gc.pause_grace_seconds = 0;
+    gc.pause_grace_budget = 0;
+    gc.grace_budget = 0;
+    gc.inactivity_forgiveness_seconds = 0;
+    gc.min_client_balance_lamports = 0;
+    gc.recovery_inactive_seconds = 0;
+    gc.proration_mode = PRORATION_KEEP;
+    gc.previous_config = PreviousConfig::default();
+    gc.denied_owner_programs = 0;
+    gc.pending_authorities = [PendingAuthority::default(); AUTHORITY_KINDS];
+    gc.reserved = [0; 1];
+
+    msg!("Rate limiter initialized. Max: {} req / {}s", config.max_requests, config.window_seconds);
+    Ok(())
+}
+
+// update_config's effect, also run by an approved AdminAction::UpdateConfig
+fn apply_config(config: &mut GlobalConfig, limits: &RateLimiterConfig, reset_windows: bool) -> Result<()> {
+    validation::validate_limits(limits)?;
+
+    let previous = PreviousConfig {
+        max_requests: config.max_requests,
+        window_seconds: config.window_seconds,
+        config_epoch: config.config_epoch,
+    };
+    let changed = (limits.max_requests, limits.window_seconds) != (previous.max_requests, previous.window_seconds);
+    config.max_requests = limits.max_requests;
+    config.window_seconds = limits.window_seconds;
+    config.burst_limit = limits.burst_limit;
+    // Even the same numbers are the admin's own from here on
+    config.config_preset = CONFIG_PRESET_CUSTOM;
+    // Opt-in "reset everyone" lever for breaking changes: every bucket restarts its
+    // window on its next consume. Without it, proration_mode decides what a new max
+    // or window does to windows in flight: kept, they are judged against the new
+    // limits until they roll over naturally; prorated, the old limits are recorded
+    // for the next consume to restate their counts against.
+    let mode = match (reset_windows, changed) {
+        (true, _) => PRORATION_RESET,
+        (false, true) => config.proration_mode,
+        (false, false) => PRORATION_KEEP,
+    };
+    if mode == PRORATION_PRORATE {
+        config.previous_config = previous;
+    }
+    if mode != PRORATION_KEEP {
+        config.config_epoch = config.config_epoch.saturating_add(1);
+    }
+
+    msg!("Config updated. Max: {} req / {}s", limits.max_requests, limits.window_seconds);
+    Ok(())
+}
+
+// freeze_client's and thaw_client's effect, shared with AdminAction::Freeze. Unlike
+// a block, it is not counted in the stats and does not notify the hook.
+fn freeze_bucket(config: &GlobalConfig, bucket: &mut ClientBucket, frozen: bool) -> Result<()> {
+    require_supported(bucket, config)?;
+    bucket.set_frozen(frozen);
+    debug_assert_invariants(bucket, config);
+    msg!("Client {}: {}", if frozen { "frozen" } else { "thawed" }, bucket.owner);
+    Ok(())
+}
+
+// block_client's effect, shared with AdminAction::Block. Notifies the hook only
+// when the client was not already blocked.
+fn block_bucket<'info>(
+    config: &GlobalConfig,
+    stats: &mut GlobalStats,
+    bucket: &mut Account<'info, ClientBucket>,
+    remaining: &[AccountInfo<'info>],
+) -> Result<()> {
+    require_supported(bucket, config)?;
+    let newly_blocked = !bucket.is_blocked();
+    if newly_blocked {
+        stats.blocked_clients = stats.blocked_clients.saturating_add(1);
+    }
+    bucket.set_blocked(true);
+    debug_assert_invariants(bucket, config);
+    msg!("Client blocked: {}", bucket.owner);
+
+    if newly_blocked {
+        emit!(BucketBlocked {
+            owner: bucket.owner,
+            label: bucket.label_text().to_string(),
+            event_tag: bucket.event_tag,
+        });
+        // Write the flag out first so the hook reads the blocked bucket
+        bucket.exit(&crate::ID)?;
+        hook::notify(
+            config,
+            &bucket.to_account_info(),
+            hook::HookEvent { client: bucket.owner, reason_code: HOOK_REASON_ADMIN_BLOCK },
+            remaining,
+        )?;
+    }
+    Ok(())
+}
+
+// reset_client's effect, shared with AdminAction::Unblock
+fn reset_bucket(config: &GlobalConfig, stats: &mut GlobalStats, bucket: &mut ClientBucket, now: i64) -> Result<()> {
+    require_supported(bucket, config)?;
+    if bucket.is_blocked() {
+        stats.blocked_clients = stats.blocked_clients.saturating_sub(1);
+    }
+
+    logic::reset_window(bucket, config, now);
+    debug_assert_invariants(bucket, config);
+
+    msg!("Client bucket reset by admin: {}", bucket.owner);
+    Ok(())
+}
+
+// =====================
+// Account Contexts
+// =====================
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + GlobalConfig::INIT_SPACE,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub program: Program<'info, program::SolanaRateLimiter>,
+    /// CHECK: checked against program.programdata_address() in the handler
+    pub program_data: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeStats<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        init,
+        payer = payer,
+        space = GlobalStats::LEN,
+        seeds = [GLOBAL_STATS_SEED],
+        bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterClient<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ClientBucket::INIT_SPACE,
+        seeds = [CLIENT_BUCKET_SEED, client.key().as_ref()],
+        bump
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    /// CHECK: must not exist; only its emptiness is read
+    #[account(seeds = [FAST_BUCKET_SEED, client.key().as_ref()], bump)]
+    pub fast_bucket: UncheckedAccount<'info>,
+    /// CHECK: must not exist; only its emptiness is read
+    #[account(seeds = [SHARD_PARENT_SEED, client.key().as_ref()], bump)]
+    pub shard_parent: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + PayerMeta::INIT_SPACE,
+        seeds = [PAYER_META_SEED, payer.key().as_ref()],
+        bump
+    )]
+    pub payer_meta: Account<'info, PayerMeta>,
+    /// CHECK: only receives the registration fee; required while it is non-zero
+    #[account(mut, address = global_config.treasury @ RateLimiterError::InvalidTreasury)]
+    pub treasury: Option<UncheckedAccount<'info>>,
+    pub client: Signer<'info>,
+    // Pays rent and the registration fee, and is what the cooldown is keyed on
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterFastClient<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        init,
+        payer = payer,
+        space = FastBucket::LEN,
+        seeds = [FAST_BUCKET_SEED, client.key().as_ref()],
+        bump
+    )]
+    pub fast_bucket: AccountLoader<'info, FastBucket>,
+    /// CHECK: must not exist; only its emptiness is read
+    #[account(seeds = [CLIENT_BUCKET_SEED, client.key().as_ref()], bump)]
+    pub client_bucket: UncheckedAccount<'info>,
+    /// CHECK: must not exist; only its emptiness is read
+    #[account(seeds = [SHARD_PARENT_SEED, client.key().as_ref()], bump)]
+    pub shard_parent: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + PayerMeta::INIT_SPACE,
+        seeds = [PAYER_META_SEED, payer.key().as_ref()],
+        bump
+    )]
+    pub payer_meta: Account<'info, PayerMeta>,
+    /// CHECK: only receives the registration fee; required while it is non-zero
+    #[account(mut, address = global_config.treasury @ RateLimiterError::InvalidTreasury)]
+    pub treasury: Option<UncheckedAccount<'info>>,
+    pub client: Signer<'info>,
+    // Pays rent and the registration fee, and is what the cooldown is keyed on
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterSharded<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        init,
+        payer = payer,
+        space = ShardParent::LEN,
+        seeds = [SHARD_PARENT_SEED, client.key().as_ref()],
+        bump
+    )]
+    pub shard_parent: Account<'info, ShardParent>,
+    /// CHECK: must not exist; only its emptiness is read
+    #[account(seeds = [CLIENT_BUCKET_SEED, client.key().as_ref()], bump)]
+    pub client_bucket: UncheckedAccount<'info>,
+    /// CHECK: must not exist; only its emptiness is read
+    #[account(seeds = [FAST_BUCKET_SEED, client.key().as_ref()], bump)]
+    pub fast_bucket: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + PayerMeta::INIT_SPACE,
+        seeds = [PAYER_META_SEED, payer.key().as_ref()],
+        bump
+    )]
+    pub payer_meta: Account<'info, PayerMeta>,
+    /// CHECK: only receives the registration fee; required while it is non-zero
+    #[account(mut, address = global_config.treasury @ RateLimiterError::InvalidTreasury)]
+    pub treasury: Option<UncheckedAccount<'info>>,
+    pub client: Signer<'info>,
+    // Pays rent and the registration fee, and is what the cooldown is keyed on
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ConsumeRequest<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    // Opt-in: passed, the consume is counted in the program-wide totals. Left out,
+    // consumes by different clients share no writable account and run side by side.
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+    #[account(
+        mut,
+        seeds = [CLIENT_BUCKET_SEED, client.key().as_ref()],
+        bump = client_bucket.bump,
+        constraint = client_bucket.owner == client.key() @ RateLimiterError::Unauthorized,
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    pub client: Signer<'info>,
+    // Only needed by CPI callers, see allow_caller
+    #[account(seeds = [CALLER_ALLOWLIST_SEED, caller_entry.program_id.as_ref()], bump = caller_entry.bump)]
+    pub caller_entry: Option<Account<'info, CallerAllowlist>>,
+    /// CHECK: the address constraint pins it to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+    // Required while a limit schedule is set, see set_schedule
+    #[account(seeds = [SCHEDULE_SEED], bump = schedule.bump)]
+    pub schedule: Option<Account<'info, Schedule>>,
+    /// CHECK: the caller's ProgramDenylist address, checked in require_owner_not_denied.
+    /// Only needed by a caller program's PDAs while any program is denylisted.
+    pub owner_denylist: Option<UncheckedAccount<'info>>,
+    // Only written during an emergency pause's grace period, see set_pause_grace.
+    // Left out, the consume gets no grace and fails as paused.
+    #[account(mut, seeds = [PAUSE_GRACE_SEED], bump = pause_grace.bump)]
+    pub pause_grace: Option<Account<'info, PauseGrace>>,
+}
+
+impl ConsumeRequest<'_> {
+    fn require_min_balance(&self) -> Result<()> {
+        require_min_balance(&self.global_config, &self.client_bucket, self.client.lamports())
+    }
+
+    fn require_circuit_closed(&self) -> Result<()> {
+        require_circuit_closed(&self.global_config, self.global_stats.as_deref())
+    }
+
+    fn take_pause_grace(&mut self, now: i64) -> Option<GlobalConfig> {
+        take_pause_grace(&self.global_config, self.pause_grace.as_deref_mut(), self.client.key(), now)
+    }
+
+    fn require_allowed_caller(&self) -> Result<()> {
+        let caller_ix = require_allowed_caller(
+            self.caller_entry.as_deref(),
+            self.instructions.as_ref().map(|sysvar| sysvar.as_ref()),
+        )?;
+        require_owner_not_denied(
+            &self.global_config,
+            self.client.key,
+            caller_ix.as_ref(),
+            self.owner_denylist.as_ref().map(|denylist| denylist.as_ref()),
+        )
+    }
+}
+
+#[derive(Accounts)]
+pub struct ConsumeRequestFast<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    // Opt-in, as on ConsumeRequest
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+    #[account(
+        mut,
+        seeds = [FAST_BUCKET_SEED, client.key().as_ref()],
+        bump = fast_bucket.load()?.bump,
+        constraint = fast_bucket.load()?.owner == client.key() @ RateLimiterError::Unauthorized,
+    )]
+    pub fast_bucket: AccountLoader<'info, FastBucket>,
+    pub client: Signer<'info>,
+    #[account(seeds = [SCHEDULE_SEED], bump = schedule.bump)]
+    pub schedule: Option<Account<'info, Schedule>>,
+}
+
+#[derive(Accounts)]
+#[instruction(shard: u8)]
+pub struct ConsumeSharded<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        seeds = [SHARD_PARENT_SEED, client.key().as_ref()],
+        bump = shard_parent.bump,
+        constraint = shard < shard_parent.shard_count @ RateLimiterError::InvalidShard,
+    )]
+    pub shard_parent: Account<'info, ShardParent>,
+    #[account(
+        mut,
+        seeds = [CLIENT_SHARD_SEED, client.key().as_ref(), shard.to_le_bytes().as_ref()],
+        bump = shard_bucket.bump,
+        constraint = shard_bucket.owner == client.key() @ RateLimiterError::Unauthorized,
+    )]
+    pub shard_bucket: Account<'info, ClientBucket>,
+    pub client: Signer<'info>,
+    #[account(seeds = [SCHEDULE_SEED], bump = schedule.bump)]
+    pub schedule: Option<Account<'info, Schedule>>,
+    // Read-only, and only needed while the circuit breaker is on
+    #[account(seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+}
+
+#[derive(Accounts)]
+pub struct AggregateShards<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        mut,
+        seeds = [SHARD_PARENT_SEED, shard_parent.owner.as_ref()],
+        bump = shard_parent.bump,
+    )]
+    pub shard_parent: Account<'info, ShardParent>,
+}
+
+#[derive(Accounts)]
+pub struct TryConsume<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    // Opt-in, as on ConsumeRequest
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
+    /// CHECK: may not exist yet; deserialized and owner-checked in the handler
+    #[account(mut, seeds = [CLIENT_BUCKET_SEED, client.key().as_ref()], bump)]
+    pub client_bucket: UncheckedAccount<'info>,
+    pub client: Signer<'info>,
+    #[account(seeds = [SCHEDULE_SEED], bump = schedule.bump)]
+    pub schedule: Option<Account<'info, Schedule>>,
+}
+
+#[derive(Accounts)]
+pub struct TransferBucket<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [CLIENT_BUCKET_SEED, owner.key().as_ref()],
+        bump = client_bucket.bump,
+        constraint = client_bucket.owner == owner.key() @ RateLimiterError::Unauthorized,
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    // init fails if the new wallet already has a bucket
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ClientBucket::INIT_SPACE,
+        seeds = [CLIENT_BUCKET_SEED, new_wallet.key().as_ref()],
+        bump
+    )]
+    pub new_bucket: Account<'info, ClientBucket>,
+    /// CHECK: must not exist; only its emptiness is read
+    #[account(seeds = [FAST_BUCKET_SEED, new_wallet.key().as_ref()], bump)]
+    pub new_fast_bucket: UncheckedAccount<'info>,
+    /// CHECK: must not exist; only its emptiness is read
+    #[account(seeds = [SHARD_PARENT_SEED, new_wallet.key().as_ref()], bump)]
+    pub new_shard_parent: UncheckedAccount<'info>,
+    pub owner: Signer<'info>,
+    pub new_wallet: Signer<'info>,
+    /// CHECK: the sponsor pool; only receives a sponsored bucket's rent, and only
+    /// has to be passed for one
+    #[account(mut, seeds = [SPONSOR_POOL_SEED], bump)]
+    pub sponsor_pool: Option<UncheckedAccount<'info>>,
+    // Funds the new bucket and gets the old one's rent back, unless it was sponsored
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetLabel<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        mut,
+        seeds = [CLIENT_BUCKET_SEED, owner.key().as_ref()],
+        bump = client_bucket.bump,
+        constraint = client_bucket.owner == owner.key() @ RateLimiterError::Unauthorized,
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct OfferQuota<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        mut,
+        seeds = [CLIENT_BUCKET_SEED, seller.key().as_ref()],
+        bump = seller_bucket.bump,
+        constraint = seller_bucket.owner == seller.key() @ RateLimiterError::Unauthorized,
+    )]
+    pub seller_bucket: Account<'info, ClientBucket>,
+    // init fails while an earlier offer is still out, even a lapsed one
+    #[account(
+        init,
+        payer = seller,
+        space = 8 + Offer::INIT_SPACE,
+        seeds = [OFFER_SEED, seller.key().as_ref()],
+        bump
+    )]
+    pub offer: Account<'info, Offer>,
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(seeds = [SCHEDULE_SEED], bump = schedule.bump)]
+    pub schedule: Option<Account<'info, Schedule>>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptOffer<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        mut,
+        close = seller,
+        seeds = [OFFER_SEED, seller.key().as_ref()],
+        bump = offer.bump,
+    )]
+    pub offer: Account<'info, Offer>,
+    #[account(
+        seeds = [CLIENT_BUCKET_SEED, seller.key().as_ref()],
+        bump = seller_bucket.bump,
+        constraint = seller_bucket.owner == seller.key() @ RateLimiterError::OwnerMismatch,
+    )]
+    pub seller_bucket: Account<'info, ClientBucket>,
+    #[account(
+        mut,
+        seeds = [CLIENT_BUCKET_SEED, buyer.key().as_ref()],
+        bump = buyer_bucket.bump,
+        constraint = buyer_bucket.owner == buyer.key() @ RateLimiterError::Unauthorized,
+    )]
+    pub buyer_bucket: Account<'info, ClientBucket>,
+    /// CHECK: the offer's seeds tie it to this wallet; it only receives lamports
+    #[account(mut, constraint = seller.key() != buyer.key() @ RateLimiterError::SelfTrade)]
+    pub seller: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(seeds = [SCHEDULE_SEED], bump = schedule.bump)]
+    pub schedule: Option<Account<'info, Schedule>>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOffer<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        mut,
+        close = seller,
+        seeds = [OFFER_SEED, seller.key().as_ref()],
+        bump = offer.bump,
+    )]
+    pub offer: Account<'info, Offer>,
+    #[account(
+        mut,
+        seeds = [CLIENT_BUCKET_SEED, seller.key().as_ref()],
+        bump = seller_bucket.bump,
+        constraint = seller_bucket.owner == seller.key() @ RateLimiterError::Unauthorized,
+    )]
+    pub seller_bucket: Option<Account<'info, ClientBucket>>,
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    #[account(seeds = [SCHEDULE_SEED], bump = schedule.bump)]
+    pub schedule: Option<Account<'info, Schedule>>,
+}
+
+#[derive(Accounts)]
+pub struct GcBucket<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        mut,
+        close = owner,
+        seeds = [CLIENT_BUCKET_SEED, owner.key().as_ref()],
+        bump = client_bucket.bump,
+        constraint = client_bucket.owner == owner.key() @ RateLimiterError::OwnerMismatch,
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    /// CHECK: the bucket's seeds tie it to this wallet; it only receives lamports
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+    /// CHECK: the sponsor pool; only receives a sponsored bucket's rent, and only
+    /// has to be passed for one
+    #[account(mut, seeds = [SPONSOR_POOL_SEED], bump)]
+    pub sponsor_pool: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRecoveryDelegate<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        mut,
+        seeds = [CLIENT_BUCKET_SEED, owner.key().as_ref()],
+        bump = client_bucket.bump,
+        constraint = client_bucket.owner == owner.key() @ RateLimiterError::Unauthorized,
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RecoverBucket<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        mut,
+        close = delegate,
+        constraint = client_bucket.recovery_delegate == delegate.key() @ RateLimiterError::Unauthorized,
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    /// CHECK: the sponsor pool; only receives a sponsored bucket's rent, and only
+    /// has to be passed for one
+    #[account(mut, seeds = [SPONSOR_POOL_SEED], bump)]
+    pub sponsor_pool: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub delegate: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseBucket<'info> {
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        mut,
+        close = owner,
+        seeds = [CLIENT_BUCKET_SEED, owner.key().as_ref()],
+        bump = client_bucket.bump,
+        constraint = client_bucket.owner == owner.key() @ RateLimiterError::Unauthorized,
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    /// CHECK: the sponsor pool; only receives a sponsored bucket's rent, and only
+    /// has to be passed for one
+    #[account(mut, seeds = [SPONSOR_POOL_SEED], bump)]
+    pub sponsor_pool: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FundSponsorPool<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + SponsorPool::INIT_SPACE,
+        seeds = [SPONSOR_POOL_SEED],
+        bump
+    )]
+    pub sponsor_pool: Account<'info, SponsorPool>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterSponsored<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    /// CHECK: created in the handler with the pool's lamports; allocate fails if
+    /// the client already has a bucket
+    #[account(mut, seeds = [CLIENT_BUCKET_SEED, client.key().as_ref()], bump)]
+    pub client_bucket: UncheckedAccount<'info>,
+    /// CHECK: must not exist; only its emptiness is read
+    #[account(seeds = [FAST_BUCKET_SEED, client.key().as_ref()], bump)]
+    pub fast_bucket: UncheckedAccount<'info>,
+    /// CHECK: must not exist; only its emptiness is read
+    #[account(seeds = [SHARD_PARENT_SEED, client.key().as_ref()], bump)]
+    pub shard_parent: UncheckedAccount<'info>,
+    #[account(mut, seeds = [SPONSOR_POOL_SEED], bump = sponsor_pool.bump)]
+    pub sponsor_pool: Account<'info, SponsorPool>,
+    pub client: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawSponsorPool<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [SPONSOR_POOL_SEED], bump = sponsor_pool.bump)]
+    pub sponsor_pool: Account<'info, SponsorPool>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResetClient<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        mut,
+        seeds = [CLIENT_BUCKET_SEED, client_wallet.key().as_ref()],
+        bump = client_bucket.bump,
+        constraint = client_bucket.owner == client_wallet.key() @ RateLimiterError::OwnerMismatch,
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    pub admin: Signer<'info>,
+    /// CHECK: used as seed reference only
+    pub client_wallet: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FileAppeal<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        seeds = [CLIENT_BUCKET_SEED, client.key().as_ref()],
+        bump = client_bucket.bump,
+        constraint = client_bucket.owner == client.key() @ RateLimiterError::OwnerMismatch,
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    // Survives a denial, so later appeals reuse it
+    #[account(
+        init_if_needed,
+        payer = client,
+        space = 8 + Appeal::INIT_SPACE,
+        seeds = [APPEAL_SEED, client.key().as_ref()],
+        bump
+    )]
+    pub appeal: Account<'info, Appeal>,
+    #[account(mut)]
+    pub client: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveAppeal<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        mut,
+        seeds = [CLIENT_BUCKET_SEED, client_wallet.key().as_ref()],
+        bump = client_bucket.bump,
+        constraint = client_bucket.owner == client_wallet.key() @ RateLimiterError::OwnerMismatch,
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    #[account(
+        mut,
+        close = client_wallet,
+        seeds = [APPEAL_SEED, client_wallet.key().as_ref()],
+        bump = appeal.bump,
+    )]
+    pub appeal: Account<'info, Appeal>,
+    pub admin: Signer<'info>,
+    /// CHECK: the seeds tie both PDAs to this wallet; it only receives the refund
+    #[account(mut)]
+    pub client_wallet: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DenyAppeal<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+        constraint = global_config.treasury != Pubkey::default() @ RateLimiterError::InvalidTreasury,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [APPEAL_SEED, client_wallet.key().as_ref()], bump = appeal.bump)]
+    pub appeal: Account<'info, Appeal>,
+    /// CHECK: only receives the forfeited bond
+    #[account(mut, address = global_config.treasury @ RateLimiterError::InvalidTreasury)]
+    pub treasury: UncheckedAccount<'info>,
+    pub admin: Signer<'info>,
+    /// CHECK: used as seed reference only
+    pub client_wallet: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPauseGrace<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + PauseGrace::INIT_SPACE,
+        seeds = [PAUSE_GRACE_SEED],
+        bump
+    )]
+    pub pause_grace: Account<'info, PauseGrace>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SweepExcess<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+        constraint = global_config.treasury != Pubkey::default() @ RateLimiterError::InvalidTreasury,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    /// CHECK: only receives the swept lamports
+    #[account(mut, address = global_config.treasury @ RateLimiterError::InvalidTreasury)]
+    pub treasury: UncheckedAccount<'info>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAction<'info> {
+    #[account(mut, seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + Proposal::INIT_SPACE,
+        seeds = [PROPOSAL_SEED, &global_config.proposal_count.to_le_bytes()],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveAction<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [PROPOSAL_SEED, &proposal.id.to_le_bytes()], bump = proposal.bump)]
+    pub proposal: Account<'info, Proposal>,
+    pub member: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteAction<'info> {
+    #[account(mut, seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(mut, seeds = [PROPOSAL_SEED, &proposal.id.to_le_bytes()], bump = proposal.bump)]
+    pub proposal: Account<'info, Proposal>,
+    // Required for block and unblock; checked against the proposal's client
+    #[account(mut)]
+    pub client_bucket: Option<Account<'info, ClientBucket>>,
+}
+
+// Shared by begin_admin_recovery and set_admin_throttle
+#[derive(Accounts)]
+pub struct BeginAdminRecovery<'info> {
+    #[account(mut, seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    pub authority: Signer<'info>,
+    pub program: Program<'info, program::SolanaRateLimiter>,
+    /// CHECK: checked against program.programdata_address() in the handler
+    pub program_data: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeAdminRecovery<'info> {
+    #[account(mut, seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+}
+
+#[derive(Accounts)]
+pub struct PushDynamicLimit<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        constraint = global_config.authority(AUTHORITY_LIMIT_FEEDER, Clock::get()?.unix_timestamp) == limit_feeder.key()
+            @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    pub limit_feeder: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefundRequest<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        constraint = global_config.authority(AUTHORITY_REFUND, Clock::get()?.unix_timestamp) == refund_authority.key()
+            @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        mut,
+        seeds = [CLIENT_BUCKET_SEED, client_wallet.key().as_ref()],
+        bump = client_bucket.bump,
+        constraint = client_bucket.owner == client_wallet.key() @ RateLimiterError::OwnerMismatch,
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    pub refund_authority: Signer<'info>,
+    /// CHECK: used as seed reference only
+    pub client_wallet: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetSchedule<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + Schedule::INIT_SPACE,
+        seeds = [SCHEDULE_SEED],
+        bump
+    )]
+    pub schedule: Account<'info, Schedule>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterProgram<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ProgramBucket::INIT_SPACE,
+        seeds = [PROGRAM_BUCKET_SEED, caller_program.key().as_ref()],
+        bump
+    )]
+    pub program_bucket: Account<'info, ProgramBucket>,
+    /// CHECK: only its key and executable flag are read
+    #[account(constraint = caller_program.executable @ RateLimiterError::InvalidCallerProgram)]
+    pub caller_program: UncheckedAccount<'info>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
-// =====================
-// Account Contexts
-// =====================
-
 #[derive(Accounts)]
-pub struct Initialize<'info> {
+pub struct AllowCaller<'info> {
     #[account(
-        init,
-        payer = admin,
-        space = GlobalConfig::LEN,
         seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + CallerAllowlist::INIT_SPACE,
+        seeds = [CALLER_ALLOWLIST_SEED, caller_program.key().as_ref()],
         bump
     )]
+    pub caller_entry: Account<'info, CallerAllowlist>,
+    /// CHECK: only its key and executable flag are read
+    #[account(constraint = caller_program.executable @ RateLimiterError::InvalidCallerProgram)]
+    pub caller_program: UncheckedAccount<'info>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeCaller<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
     pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        mut,
+        close = admin,
+        seeds = [CALLER_ALLOWLIST_SEED, caller_entry.program_id.as_ref()],
+        bump = caller_entry.bump,
+    )]
+    pub caller_entry: Account<'info, CallerAllowlist>,
     #[account(mut)]
     pub admin: Signer<'info>,
-    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct RegisterClient<'info> {
-    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+#[instruction(program_id: Pubkey)]
+pub struct BlockProgramOwners<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
     pub global_config: Account<'info, GlobalConfig>,
     #[account(
         init,
-        payer = client,
-        space = ClientBucket::LEN,
-        seeds = [CLIENT_BUCKET_SEED, client.key().as_ref()],
+        payer = payer,
+        space = 8 + ProgramDenylist::INIT_SPACE,
+        seeds = [PROGRAM_DENYLIST_SEED, program_id.as_ref()],
         bump
     )]
-    pub client_bucket: Account<'info, ClientBucket>,
+    pub denylist_entry: Account<'info, ProgramDenylist>,
+    pub admin: Signer<'info>,
     #[account(mut)]
-    pub client: Signer<'info>,
+    pub payer: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ConsumeRequest<'info> {
+pub struct UnblockProgramOwners<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        mut,
+        close = admin,
+        seeds = [PROGRAM_DENYLIST_SEED, denylist_entry.program_id.as_ref()],
+        bump = denylist_entry.bump,
+    )]
+    pub denylist_entry: Account<'info, ProgramDenylist>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConsumeAsProgram<'info> {
     #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
     pub global_config: Account<'info, GlobalConfig>,
+    // Opt-in, as on ConsumeRequest
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Option<Account<'info, GlobalStats>>,
     #[account(
         mut,
-        seeds = [CLIENT_BUCKET_SEED, client.key().as_ref()],
-        bump = client_bucket.bump,
-        constraint = client_bucket.owner == client.key() @ RateLimiterError::Unauthorized,
+        seeds = [PROGRAM_BUCKET_SEED, program_bucket.program_id.as_ref()],
+        bump = program_bucket.bump,
+        has_one = caller_authority @ RateLimiterError::Unauthorized,
     )]
-    pub client_bucket: Account<'info, ClientBucket>,
-    pub client: Signer<'info>,
+    pub program_bucket: Account<'info, ProgramBucket>,
+    pub caller_authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct ResetClient<'info> {
+pub struct TogglePause<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BlockClient<'info> {
     #[account(
+        mut,
         seeds = [GLOBAL_CONFIG_SEED],
         bump = global_config.bump,
         has_one = admin @ RateLimiterError::Unauthorized,
     )]
     pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
     #[account(
         mut,
         seeds = [CLIENT_BUCKET_SEED, client_wallet.key().as_ref()],
         bump = client_bucket.bump,
+        constraint = client_bucket.owner == client_wallet.key() @ RateLimiterError::OwnerMismatch,
     )]
     pub client_bucket: Account<'info, ClientBucket>,
     pub admin: Signer<'info>,
@@ -194,20 +4054,30 @@ pub struct ResetClient<'info> {
     pub client_wallet: UncheckedAccount<'info>,
 }
 
+// Shared by freeze_client, thaw_client and set_balance_exempt
 #[derive(Accounts)]
-pub struct UpdateConfig<'info> {
+pub struct FreezeClient<'info> {
     #[account(
-        mut,
         seeds = [GLOBAL_CONFIG_SEED],
         bump = global_config.bump,
         has_one = admin @ RateLimiterError::Unauthorized,
     )]
     pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        mut,
+        seeds = [CLIENT_BUCKET_SEED, client_wallet.key().as_ref()],
+        bump = client_bucket.bump,
+        constraint = client_bucket.owner == client_wallet.key() @ RateLimiterError::OwnerMismatch,
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
     pub admin: Signer<'info>,
+    /// CHECK: used as seed reference only
+    pub client_wallet: UncheckedAccount<'info>,
 }
 
+// Shared by reset_fast_client and block_fast_client
 #[derive(Accounts)]
-pub struct TogglePause<'info> {
+pub struct AdminFastClient<'info> {
     #[account(
         mut,
         seeds = [GLOBAL_CONFIG_SEED],
@@ -215,11 +4085,43 @@ pub struct TogglePause<'info> {
         has_one = admin @ RateLimiterError::Unauthorized,
     )]
     pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        mut,
+        seeds = [FAST_BUCKET_SEED, client_wallet.key().as_ref()],
+        bump = fast_bucket.load()?.bump,
+        constraint = fast_bucket.load()?.owner == client_wallet.key() @ RateLimiterError::OwnerMismatch,
+    )]
+    pub fast_bucket: AccountLoader<'info, FastBucket>,
     pub admin: Signer<'info>,
+    /// CHECK: used as seed reference only
+    pub client_wallet: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
-pub struct BlockClient<'info> {
+pub struct MigrateAccount<'info> {
+    /// CHECK: may be in any historical layout; the handler dispatches on the
+    /// discriminator and decodes it through migrations.rs
+    #[account(mut, owner = crate::ID)]
+    pub account: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TopUpRent<'info> {
+    /// CHECK: any account this program owns; only its length and balance are read
+    #[account(mut, owner = crate::ID)]
+    pub account: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdjustClientUsage<'info> {
     #[account(
         seeds = [GLOBAL_CONFIG_SEED],
         bump = global_config.bump,
@@ -230,9 +4132,259 @@ pub struct BlockClient<'info> {
         mut,
         seeds = [CLIENT_BUCKET_SEED, client_wallet.key().as_ref()],
         bump = client_bucket.bump,
+        constraint = client_bucket.owner == client_wallet.key() @ RateLimiterError::OwnerMismatch,
     )]
     pub client_bucket: Account<'info, ClientBucket>,
     pub admin: Signer<'info>,
     /// CHECK: used as seed reference only
     pub client_wallet: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetClientNote<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + ClientNote::INIT_SPACE,
+        seeds = [CLIENT_NOTE_SEED, client_wallet.key().as_ref()],
+        bump
+    )]
+    pub client_note: Account<'info, ClientNote>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: used as seed reference only
+    pub client_wallet: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClearClientNote<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(
+        mut,
+        close = admin,
+        seeds = [CLIENT_NOTE_SEED, client_note.client.as_ref()],
+        bump = client_note.bump,
+    )]
+    pub client_note: Account<'info, ClientNote>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureStats<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRebalancePolicy<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + RebalancePolicy::INIT_SPACE,
+        seeds = [REBALANCE_POLICY_SEED],
+        bump
+    )]
+    pub rebalance_policy: Account<'info, RebalancePolicy>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RebalanceLimits<'info> {
+    #[account(mut, seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(mut, seeds = [REBALANCE_POLICY_SEED], bump = rebalance_policy.bump)]
+    pub rebalance_policy: Account<'info, RebalancePolicy>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitToLeaderboard<'info> {
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + Leaderboard::INIT_SPACE,
+        seeds = [LEADERBOARD_SEED],
+        bump
+    )]
+    pub leaderboard: Account<'info, Leaderboard>,
+    pub client_bucket: Account<'info, ClientBucket>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EmitStatsSnapshot<'info> {
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyBucket<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    pub client_bucket: Account<'info, ClientBucket>,
+}
+
+#[derive(Accounts)]
+pub struct EstimateConfigImpact<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+}
+
+#[derive(Accounts)]
+pub struct GetQuota<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    pub client_bucket: Account<'info, ClientBucket>,
+    #[account(seeds = [SCHEDULE_SEED], bump = schedule.bump)]
+    pub schedule: Option<Account<'info, Schedule>>,
+}
+
+#[derive(Accounts)]
+pub struct GetConfig<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(seeds = [SCHEDULE_SEED], bump = schedule.bump)]
+    pub schedule: Option<Account<'info, Schedule>>,
+    pub client_bucket: Option<Account<'info, ClientBucket>>,
+}
+
+#[derive(Accounts)]
+pub struct GetDeploymentInfo<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+}
+
+#[derive(Accounts)]
+pub struct PreviewRegistration<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(seeds = [SCHEDULE_SEED], bump = schedule.bump)]
+    pub schedule: Option<Account<'info, Schedule>>,
+    /// CHECK: the would-be client; only its owner is read
+    pub client: UncheckedAccount<'info>,
+    /// CHECK: only its emptiness is read
+    #[account(seeds = [CLIENT_BUCKET_SEED, client.key().as_ref()], bump)]
+    pub client_bucket: UncheckedAccount<'info>,
+    /// CHECK: only its emptiness is read
+    #[account(seeds = [FAST_BUCKET_SEED, client.key().as_ref()], bump)]
+    pub fast_bucket: UncheckedAccount<'info>,
+    /// CHECK: only its emptiness is read
+    #[account(seeds = [SHARD_PARENT_SEED, client.key().as_ref()], bump)]
+    pub shard_parent: UncheckedAccount<'info>,
+    /// CHECK: the wallet that would pay; only its key is used
+    pub payer: UncheckedAccount<'info>,
+    /// CHECK: decoded when it exists, for the payer's cap and cooldown
+    #[account(seeds = [PAYER_META_SEED, payer.key().as_ref()], bump)]
+    pub payer_meta: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetStats<'info> {
+    #[account(seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+}
+
+#[derive(Accounts)]
+pub struct ExportBucketSnapshot<'info> {
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+    // The wallet bucket only: a shard carries part of the owner's history
+    #[account(seeds = [CLIENT_BUCKET_SEED, client_bucket.owner.as_ref()], bump = client_bucket.bump)]
+    pub client_bucket: Account<'info, ClientBucket>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + SnapshotRecord::INIT_SPACE,
+        seeds = [SNAPSHOT_RECORD_SEED, client_bucket.owner.as_ref()],
+        bump
+    )]
+    pub snapshot_record: Account<'info, SnapshotRecord>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(feature = "snapshot-import")]
+#[derive(Accounts)]
+#[instruction(snapshot: BucketSnapshot)]
+pub struct ImportBucketSnapshot<'info> {
+    #[account(
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump,
+        has_one = admin @ RateLimiterError::Unauthorized,
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+    #[account(mut, seeds = [GLOBAL_STATS_SEED], bump = global_stats.bump)]
+    pub global_stats: Account<'info, GlobalStats>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ClientBucket::INIT_SPACE,
+        seeds = [CLIENT_BUCKET_SEED, client.key().as_ref()],
+        bump
+    )]
+    pub client_bucket: Account<'info, ClientBucket>,
+    /// CHECK: must not exist; only its emptiness is read
+    #[account(seeds = [FAST_BUCKET_SEED, client.key().as_ref()], bump)]
+    pub fast_bucket: UncheckedAccount<'info>,
+    /// CHECK: must not exist; only its emptiness is read
+    #[account(seeds = [SHARD_PARENT_SEED, client.key().as_ref()], bump)]
+    pub shard_parent: UncheckedAccount<'info>,
+    /// CHECK: the source deployment's SnapshotRecord, decoded in the handler
+    #[account(
+        seeds = [SNAPSHOT_RECORD_SEED, snapshot.owner.as_ref()],
+        bump,
+        seeds::program = SNAPSHOT_SOURCE_PROGRAM_ID,
+        owner = SNAPSHOT_SOURCE_PROGRAM_ID @ RateLimiterError::SnapshotNotRecorded,
+    )]
+    pub snapshot_record: UncheckedAccount<'info>,
+    #[account(address = snapshot.owner @ RateLimiterError::Unauthorized)]
+    pub client: Signer<'info>,
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyConsumedInTx<'info> {
+    /// CHECK: the address constraint pins it to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
 }
\ No newline at end of file
@@ -0,0 +1,2411 @@
+use crate::constants::{
+    BPS_DENOMINATOR, FEATURE_APPEALS, FEATURE_BUCKET_RECOVERY, FEATURE_COUNCIL, FEATURE_DISCOUNT_TIERS, FEATURE_HOOK,
+    FEATURE_LIMIT_FEEDER, FEATURE_PROGRAM_KEYED, FEATURE_REFUNDS, FEATURE_REGISTRATION_FEE, FEATURE_SCHEDULE,
+    LIMIT_SOURCE_BASE, LIMIT_SOURCE_FEEDER, LIMIT_SOURCE_SCHEDULE, PAUSE_REASON_MAINTENANCE, PAUSE_REASON_UNSPECIFIED,
+    SECONDS_PER_DAY, USAGE_HISTOGRAM_BUCKETS, USAGE_REJECTED,
+};
+use crate::gate::{ConsumeReport, EffectiveConfig, PauseInfo, RegistrationPreview};
+use crate::state::{
+    ClientBucket, DiscountTier, GlobalConfig, GlobalStats, PauseGrace, PayerMeta, ProgramBucket, RebalancePolicy,
+    ScheduleEntry,
+};
+use crate::window;
+use anchor_lang::prelude::Rent;
+use anchor_lang::Space;
+
+// Rate-limiting decisions as pure functions of (state, config, now). Handlers read
+// the clock once, call in here, and turn the outcome into account writes, events
+// and logs; nothing in this module touches the runtime.
+
+// Why a consume was refused. Paused and Overflow are not the client's fault, and a
+// freeze is meant to carry no record, so none of the three counts as a violation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "wasm", derive(serde::Serialize))]
+pub enum Rejection {
+    Paused,
+    Blocked,
+    Frozen,
+    RateLimit,
+    BurstLimit,
+    Overflow,
+}
+
+// The window that was closed on the way to accepting a consume
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rollover {
+    pub closed_window_start: i64,
+    pub closed_count: u64,
+    pub skipped_windows: u64,
+}
+
+pub struct Consumed {
+    pub bucket: ClientBucket, // state to write back
+    pub rollover: Option<Rollover>,
+    pub clock_clamped: bool, // window_start was ahead of now
+}
+
+// Decides whether `amount` units may be consumed at `now` and returns the bucket as
+// it should be stored. The input bucket is never modified, so a rejection leaves
+// nothing to undo. Lifetime totals are left to the caller.
+pub fn apply_consume(
+    bucket: &ClientBucket,
+    config: &GlobalConfig,
+    now: i64,
+    amount: u64,
+) -> Result<Consumed, Rejection> {
+    // Paused rejections are about the program, not the client
+    if config.pause_active(now) {
+        return Err(Rejection::Paused);
+    }
+    if bucket.is_blocked() {
+        return Err(Rejection::Blocked);
+    }
+    if bucket.is_frozen() {
+        return Err(Rejection::Frozen);
+    }
+
+    let mut next = bucket.clone();
+
+    // Validator clock drift can leave window_start ahead of the current clock.
+    // Treat the window as opening now instead of carrying a negative age around.
+    let clock_clamped = next.window_start > now;
+    if clock_clamped {
+        next.window_start = now;
+    }
+
+    // A config update since this window opened invalidates it: counts taken under
+    // the old max/window are not comparable with the new ones, so start over,
+    // unless the update was prorated and the counts can be restated instead.
+    let mut stale_epoch = next.config_epoch != config.config_epoch;
+    if stale_epoch && prorates(&next, config, now) {
+        prorate_window(&mut next, config);
+        stale_epoch = false;
+    }
+
+    let mut rollover = None;
+    if stale_epoch || window::window_expired(now, next.window_start, config.window_seconds) {
+        // One rollover however many windows passed while idle
+        let skipped_windows =
+            window::elapsed_windows(now, next.window_start, config.window_seconds)
+                .saturating_sub(1);
+        rollover = Some(Rollover {
+            closed_window_start: next.window_start,
+            closed_count: next.request_count,
+            skipped_windows,
+        });
+
+        // Capacity left unused in the closed window (and any idle windows after it)
+        // is banked as burst credit, at most one window's worth. A window
+        // invalidated by a config change earns nothing.
+        if !stale_epoch {
+            let unused = config
+                .max_requests
+                .saturating_sub(steady_count(&next))
+                .saturating_add(config.max_requests.saturating_mul(skipped_windows));
+            next.burst_credits = next
+                .burst_credits
+                .saturating_add(unused)
+                .min(config.max_requests);
+        }
+
+        next.request_count = 0;
+        next.priority_count = 0;
+        next.escrowed_count = 0;
+        next.bonus_requests = 0;
+        next.refunded_count = 0;
+        next.set_window_rejected(false);
+        next.window_start = now;
+        next.config_epoch = config.config_epoch;
+    }
+
+    let steady = steady_count(&next);
+    let new_steady = steady.checked_add(amount).ok_or(Rejection::Overflow)?;
+    let new_count = next
+        .request_count
+        .checked_add(amount)
+        .ok_or(Rejection::Overflow)?;
+
+    // Every unit of the window allowance past max_requests (plus any bought quota)
+    // must spend a banked burst credit, and no window may ever exceed burst_limit
+    // (likewise raised) in total
+    let max = window_max(&next, config);
+    if new_steady > max {
+        let over = new_steady - steady.max(max);
+        if next.burst_credits < over {
+            return Err(Rejection::RateLimit);
+        }
+        next.burst_credits -= over;
+    }
+    if new_count > window_ceiling(&next, config) {
+        return Err(Rejection::BurstLimit);
+    }
+    next.request_count = new_count;
+    next.last_request_ts = now;
+
+    // Refuse windows whose end is not a representable timestamp
+    next.window_start
+        .checked_add(config.window_seconds)
+        .ok_or(Rejection::Overflow)?;
+
+    Ok(Consumed {
+        bucket: next,
+        rollover,
+        clock_clamped,
+    })
+}
+
+// consume_priority: with `priority` set and credits banked, the unit is paid with a
+// credit and leaves the window allowance alone, so background traffic keeps it.
+// Otherwise, or once credits run out, it is an ordinary one-unit consume. Either
+// way it counts towards request_count and burst_limit. The flag says whether a
+// credit was spent.
+pub fn apply_priority_consume(
+    bucket: &ClientBucket,
+    config: &GlobalConfig,
+    now: i64,
+    priority: bool,
+) -> Result<(Consumed, bool), Rejection> {
+    // Opens the window, with its pause and block checks, without taking anything
+    let mut consumed = apply_consume(bucket, config, now, 0)?;
+    let credits = consumed.bucket.burst_credits;
+    if priority && credits > 0 {
+        let next = &mut consumed.bucket;
+        if next.request_count >= window_ceiling(next, config) {
+            return Err(Rejection::BurstLimit);
+        }
+        next.burst_credits -= 1;
+        next.priority_count += 1;
+        next.request_count += 1;
+        return Ok((consumed, true));
+    }
+    consumed.bucket = apply_consume(&consumed.bucket, config, now, 1)?.bucket;
+    let from_burst = consumed.bucket.burst_credits < credits;
+    Ok((consumed, from_burst))
+}
+
+// What a consume of `amount` counts against the window: the highest tier it reaches
+// takes its discount_bps off. The discount rounds down, which rounds the charge up,
+// so with discount_bps below BPS_DENOMINATOR a batch always costs at least one unit.
+pub fn discounted_amount(tiers: &[DiscountTier], amount: u64) -> u64 {
+    let bps = tiers
+        .iter()
+        .rev()
+        .find(|tier| amount >= tier.min_amount)
+        .map_or(0, |tier| tier.discount_bps as u64);
+    amount - (amount as u128 * bps as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+// offer_quota: moves `amount` units of the seller's open window into escrow. They
+// are added to request_count straight away, so the seller cannot consume them
+// while the offer is out, and can only come from the window allowance itself:
+// burst credits are not for sale. The window is opened first, with the same
+// pause and block checks as a consume.
+pub fn escrow_quota(
+    bucket: &ClientBucket,
+    config: &GlobalConfig,
+    now: i64,
+    amount: u64,
+) -> Result<Consumed, Rejection> {
+    let mut consumed = apply_consume(bucket, config, now, 0)?;
+    let next = &mut consumed.bucket;
+    let headroom = window_max(next, config)
+        .saturating_sub(steady_count(next))
+        .min(window_ceiling(next, config).saturating_sub(next.request_count));
+    if amount > headroom {
+        return Err(Rejection::RateLimit);
+    }
+    next.request_count += amount;
+    next.escrowed_count += amount;
+    Ok(consumed)
+}
+
+// Why refund_request was refused
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefundRefusal {
+    NonceUsed,   // attestation_nonce not above the bucket's last one
+    CapExceeded, // would take the window's refunds past max_refunds_per_window
+}
+
+// refund_request: gives back up to `amount` units of the bucket's current window,
+// returning the bucket as it should be stored and the units actually refunded.
+// Only units paid from the window allowance come back, so request_count never
+// drops below what priority consumes and quota offers hold, and a window that has
+// ended (or a config update invalidated) has nothing left to refund. The nonce is
+// used up either way; the cap applies to what is refunded after clamping.
+pub fn apply_refund(
+    bucket: &ClientBucket,
+    config: &GlobalConfig,
+    now: i64,
+    amount: u64,
+    nonce: u64,
+) -> Result<(ClientBucket, u64), RefundRefusal> {
+    if nonce <= bucket.last_refund_nonce {
+        return Err(RefundRefusal::NonceUsed);
+    }
+    let mut next = bucket.clone();
+    next.last_refund_nonce = nonce;
+
+    let live = next.config_epoch == config.config_epoch
+        && next.window_start <= now
+        && !window::window_expired(now, next.window_start, config.window_seconds);
+    let refundable = if live { steady_count(&next).saturating_sub(next.escrowed_count) } else { 0 };
+    let refunded = amount.min(refundable);
+    let window_total = next.refunded_count.saturating_add(refunded);
+    if window_total > config.max_refunds_per_window {
+        return Err(RefundRefusal::CapExceeded);
+    }
+    next.request_count -= refunded;
+    next.refunded_count = window_total;
+    if config.refunds_lower_totals() && !next.total_saturated() {
+        next.total_requests = next.total_requests.saturating_sub(refunded);
+    }
+    Ok((next, refunded))
+}
+
+// Adds accepted units to the lifetime total. Totals are analytics only, so one that
+// would overflow is pinned at u64::MAX and flagged as a floor rather than failing.
+pub fn add_to_total(bucket: &mut ClientBucket, amount: u64) {
+    match bucket.total_requests.checked_add(amount) {
+        Some(total) => bucket.total_requests = total,
+        None => {
+            bucket.total_requests = u64::MAX;
+            bucket.set_total_saturated(true);
+        }
+    }
+}
+
+// reset_client: an unblocked bucket with a window opening at `now` and nothing
+// used, banked or bought in it. Lifetime totals and violations are kept.
+pub fn reset_window(bucket: &mut ClientBucket, config: &GlobalConfig, now: i64) {
+    bucket.request_count = 0;
+    bucket.window_start = now;
+    bucket.set_blocked(false);
+    bucket.config_epoch = config.config_epoch;
+    bucket.burst_credits = 0;
+    bucket.priority_count = 0;
+    bucket.escrowed_count = 0;
+    bucket.bonus_requests = 0;
+    bucket.refunded_count = 0;
+}
+
+// Whether a bucket on a stale epoch keeps its window, scaled: the one change since
+// it last consumed was prorated, and the window it opened under the old limits is
+// still open under the new window_seconds. A bucket that missed more than one
+// change, or whose window has closed anyway, starts over.
+fn prorates(bucket: &ClientBucket, config: &GlobalConfig, now: i64) -> bool {
+    let previous = &config.previous_config;
+    previous.max_requests > 0
+        && bucket.config_epoch == previous.config_epoch
+        && config.config_epoch == previous.config_epoch.saturating_add(1)
+        && !window::window_expired(now, bucket.window_start, config.window_seconds)
+}
+
+// Restates the open window's counts in the new max_requests: each is multiplied by
+// new max over old, rounded down. Priority units and the count are capped so the
+// lifetime total still covers the window, which only bites when the max went up
+// and the bucket has served less than the scaled count.
+fn prorate_window(bucket: &mut ClientBucket, config: &GlobalConfig) {
+    let old_max = config.previous_config.max_requests as u128;
+    let scale = |count: u64| {
+        let scaled = (count as u128).checked_mul(config.max_requests as u128).map_or(u128::MAX, |n| n / old_max);
+        u64::try_from(scaled).unwrap_or(u64::MAX)
+    };
+    let served = if bucket.total_saturated() { u64::MAX } else { bucket.total_requests };
+    bucket.priority_count = scale(bucket.priority_count).min(served);
+    bucket.escrowed_count = scale(bucket.escrowed_count);
+    bucket.request_count = scale(bucket.request_count).min(served.saturating_add(bucket.escrowed_count));
+    bucket.config_epoch = config.config_epoch;
+}
+
+// Units of the current window paid from the window allowance. Priority units were
+// paid with credits instead and do not count against max_requests.
+fn steady_count(bucket: &ClientBucket) -> u64 {
+    bucket.request_count.saturating_sub(bucket.priority_count)
+}
+
+// The window's allowance and ceiling, both raised by quota bought this window
+fn window_max(bucket: &ClientBucket, config: &GlobalConfig) -> u64 {
+    config.max_requests.saturating_add(bucket.bonus_requests)
+}
+
+fn window_ceiling(bucket: &ClientBucket, config: &GlobalConfig) -> u64 {
+    config.burst_limit.saturating_add(bucket.bonus_requests)
+}
+
+// Further unit consumes the bucket could take in its current window: what is left
+// of max_requests plus the burst credits it could spend, up to burst_limit
+pub fn remaining_capacity(bucket: &ClientBucket, config: &GlobalConfig) -> u64 {
+    let steady = window_max(bucket, config).saturating_sub(steady_count(bucket));
+    steady
+        .saturating_add(bucket.burst_credits)
+        .min(window_ceiling(bucket, config).saturating_sub(bucket.request_count))
+}
+
+// The usage histogram slot of a window as it closes: USAGE_REJECTED if a
+// gate_check denied anything in it, otherwise its quarter of the allowance (0 for
+// under 25%, up to 3 for 75% and over, burst included). Judged against the config
+// the window closes under.
+pub fn usage_class(closed: &ClientBucket, config: &GlobalConfig) -> usize {
+    if closed.window_rejected() {
+        return USAGE_REJECTED;
+    }
+    let max = u128::from(window_max(closed, config).max(1));
+    let quarters = u128::from(closed.request_count) * 4 / max;
+    quarters.min(USAGE_HISTOGRAM_BUCKETS as u128 - 2) as usize
+}
+
+// consume_and_report's return data, for the bucket as the consume left it
+pub fn consume_report(bucket: &ClientBucket, config: &GlobalConfig) -> ConsumeReport {
+    ConsumeReport {
+        used: bucket.request_count,
+        max: window_max(bucket, config),
+        remaining: remaining_capacity(bucket, config),
+        window_end: bucket.window_start.saturating_add(config.window_seconds),
+    }
+}
+
+// Why the program is paused at `now`, for ProgramPaused's return data. The
+// emergency pause has its own record; a scheduled window counts as maintenance.
+pub fn pause_info(config: &GlobalConfig, now: i64) -> PauseInfo {
+    if config.emergency_pause_active(now) || !config.pause_active(now) {
+        return PauseInfo { paused_at: config.paused_at, reason: config.pause_reason };
+    }
+    PauseInfo { paused_at: config.scheduled_pause_start, reason: PAUSE_REASON_MAINTENANCE }
+}
+
+// Grace consumes the running pause has left. `grace` counts spends against the
+// pause that started at its paused_at, so a newer pause starts from the full
+// budget without anything resetting the account.
+pub fn grace_left(config: &GlobalConfig, grace: &PauseGrace) -> u64 {
+    let spent = if grace.paused_at == config.paused_at { grace.spent } else { 0 };
+    config.grace_budget.saturating_sub(spent)
+}
+
+// Whether a consume at `now` falls in the grace period after the emergency pause
+// began, with budget left to spend. A scheduled pause has no grace period.
+pub fn pause_grace_open(config: &GlobalConfig, grace: &PauseGrace, now: i64) -> bool {
+    config.emergency_pause_active(now)
+        && grace_left(config, grace) > 0
+        && now < config.paused_at.saturating_add(config.pause_grace_seconds)
+}
+
+// Whether an offer escrowed from the seller's window opened at `window_start`
+// under `config_epoch` can still be taken at `now`. Offers lapse with that window,
+// whether or not anything has rolled the seller's bucket over yet.
+pub fn offer_live(
+    seller: &ClientBucket,
+    config: &GlobalConfig,
+    window_start: i64,
+    config_epoch: u64,
+    now: i64,
+) -> bool {
+    seller.window_start == window_start
+        && seller.config_epoch == config_epoch
+        && config.config_epoch == config_epoch
+        && !window::window_expired(now, window_start, config.window_seconds)
+}
+
+// What a client could consume at `now` and the seconds until its window rolls over,
+// as apply_consume would see it: an expired or stale window counts as already
+// reopened, with its unused capacity banked. Paused and blocked buckets have none.
+pub fn quota_at(bucket: &ClientBucket, config: &GlobalConfig, now: i64) -> (u64, i64) {
+    match apply_consume(bucket, config, now, 0) {
+        Ok(Consumed { bucket: next, .. }) => (
+            remaining_capacity(&next, config),
+            window::window_remaining(now, next.window_start, config.window_seconds),
+        ),
+        Err(_) => (0, window::window_remaining(now, bucket.window_start, config.window_seconds)),
+    }
+}
+
+// What a frontend can show without sending a transaction: whether a
+// consume_request at `now` would go through and, as get_quota reports it, what is
+// left and when the window rolls over
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "wasm", derive(serde::Serialize), serde(rename_all = "camelCase"))]
+pub struct QuotaInfo {
+    pub allowed: bool,
+    pub rejection: Option<Rejection>, // None when allowed, or when only a CPI caller could consume
+    pub remaining: u64,
+    pub resets_in: i64,
+}
+
+// The program's own decision for a one-unit consume_request by a top-level
+// caller, off-chain. While a schedule is set its entries change the limits, so use
+// simulate_scheduled_consume with them; this one leaves the base max in place.
+pub fn simulate_consume(config: &GlobalConfig, bucket: &ClientBucket, now: i64) -> QuotaInfo {
+    simulate_scheduled_consume(config, &[], bucket, now)
+}
+
+pub fn simulate_scheduled_consume(
+    config: &GlobalConfig,
+    entries: &[ScheduleEntry],
+    bucket: &ClientBucket,
+    now: i64,
+) -> QuotaInfo {
+    let config = &wallet_config(config, entries, now);
+    let (remaining, resets_in) = quota_at(bucket, config, now);
+    if !wallet_consume_allowed(config, true) {
+        return QuotaInfo { allowed: false, rejection: None, remaining, resets_in };
+    }
+    let rejection = apply_consume(bucket, config, now, 1).err();
+    QuotaInfo { allowed: rejection.is_none(), rejection, remaining, resets_in }
+}
+
+// Limits for one shard of a client split over `shard_count` buckets. Each shard
+// gets floor(limit / shard_count), but at least 1. Shards enforce independently,
+// so across all of them a window admits between limit - (shard_count - 1) and
+// limit requests; the rounding loss is the price of consumes on different shards
+// holding no write lock in common. Only when shard_count > limit does the floor of 1 let the shards
+// together exceed it, by at most shard_count - limit.
+pub fn shard_config(config: &GlobalConfig, shard_count: u8) -> GlobalConfig {
+    let shards = shard_count.max(1) as u64;
+    let mut previous_config = config.previous_config;
+    if previous_config.max_requests > 0 {
+        previous_config.max_requests = (previous_config.max_requests / shards).max(1);
+    }
+    GlobalConfig {
+        max_requests: (config.max_requests / shards).max(1),
+        burst_limit: (config.burst_limit / shards).max(1),
+        previous_config,
+        ..config.clone()
+    }
+}
+
+// Limits for a program bucket: its own max/window/burst, with the pause flag and
+// config epoch still taken from the global config. A prorated global change left
+// the program's own limits alone, so its window carries over unscaled.
+pub fn program_config(config: &GlobalConfig, program: &ProgramBucket) -> GlobalConfig {
+    let mut previous_config = config.previous_config;
+    if previous_config.max_requests > 0 {
+        previous_config.max_requests = program.max_requests;
+    }
+    GlobalConfig {
+        max_requests: program.max_requests,
+        window_seconds: program.window_seconds,
+        burst_limit: program.burst_limit,
+        previous_config,
+        ..config.clone()
+    }
+}
+
+// Whether a wallet bucket may be consumed from. Outside program-keyed mode always;
+// inside it, only by a top-level instruction and only if the config falls back to
+// wallet buckets. A CPI in that mode has to come through consume_as_program.
+pub fn wallet_consume_allowed(config: &GlobalConfig, top_level: bool) -> bool {
+    !config.program_keyed() || (top_level && config.wallet_fallback())
+}
+
+// Wallet-bucket limits at `now`: the feeder's override replaces max_requests while
+// it is live, lifting burst_limit with it if needed. Expiry needs no instruction,
+// the override just stops applying. The result carries no live override, so
+// passing it through again (e.g. shard_config then consume) changes nothing.
+pub fn effective_config(config: &GlobalConfig, now: i64) -> GlobalConfig {
+    let mut effective = config.clone();
+    if let Some(max) = config.dynamic_override(now) {
+        effective.max_requests = max;
+        effective.burst_limit = config.burst_limit.max(max);
+    }
+    effective.dynamic_valid_until = 0;
+    effective
+}
+
+// max_requests under a time-of-day schedule at `now`: the entry with the latest
+// start at or before the current UTC second of day. Before the day's first entry
+// the previous day's last one is still running. None for an empty schedule.
+pub fn scheduled_max(entries: &[ScheduleEntry], now: i64) -> Option<u64> {
+    let second = now.rem_euclid(SECONDS_PER_DAY) as u32;
+    entries
+        .iter()
+        .rev()
+        .find(|entry| entry.start_second_of_day <= second)
+        .or_else(|| entries.last())
+        .map(|entry| entry.max_requests)
+}
+
+// The config with the schedule's max_requests, lifting burst_limit with it like a
+// feeder override does. The scheduled flag is dropped from the result, so it can
+// go through shard_config and consume without asking for the schedule again.
+pub fn scheduled_config(config: &GlobalConfig, entries: &[ScheduleEntry], now: i64) -> GlobalConfig {
+    let mut scheduled = config.clone();
+    if let Some(max) = scheduled_max(entries, now) {
+        scheduled.max_requests = max;
+        scheduled.burst_limit = config.burst_limit.max(max);
+    }
+    scheduled.set_scheduled(false);
+    scheduled
+}
+
+// Wallet limits at `now`: the schedule's entries while one is set, then any live
+// feeder override on top. The handlers pass the schedule account's entries, after
+// checking the account was supplied.
+pub fn wallet_config(config: &GlobalConfig, entries: &[ScheduleEntry], now: i64) -> GlobalConfig {
+    if !config.scheduled() {
+        return effective_config(config, now);
+    }
+    effective_config(&scheduled_config(config, entries, now), now)
+}
+
+// get_config's answer: wallet_config's limits, tagged with the layer that set
+// max_requests (the feeder over the schedule over the base config), plus the pause
+// and, when a bucket is given, its block and freeze
+pub fn effective_view(
+    config: &GlobalConfig,
+    entries: &[ScheduleEntry],
+    bucket: Option<&ClientBucket>,
+    now: i64,
+) -> EffectiveConfig {
+    let limits = wallet_config(config, entries, now);
+    let limit_source = if config.dynamic_override(now).is_some() {
+        LIMIT_SOURCE_FEEDER
+    } else if config.scheduled() && !entries.is_empty() {
+        LIMIT_SOURCE_SCHEDULE
+    } else {
+        LIMIT_SOURCE_BASE
+    };
+    let paused = config.pause_active(now);
+    EffectiveConfig {
+        max_requests: limits.max_requests,
+        window_seconds: limits.window_seconds,
+        burst_limit: limits.burst_limit,
+        limit_source,
+        config_preset: config.config_preset,
+        config_epoch: config.config_epoch,
+        paused,
+        pause: if paused {
+            pause_info(config, now)
+        } else {
+            PauseInfo { paused_at: 0, reason: PAUSE_REASON_UNSPECIFIED }
+        },
+        blocked: bucket.is_some_and(ClientBucket::is_blocked),
+        frozen: bucket.is_some_and(ClientBucket::is_frozen),
+    }
+}
+
+// The circuit breaker's counting window after one more denial at `now`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BreakerCount {
+    pub window_start: i64,
+    pub count: u64,
+    pub tripped: bool, // count went past the threshold; the program should pause
+}
+
+// Counts a denial against the breaker, starting a new window once the current one
+// has run its length (or the clock went backwards). None while it is disabled.
+pub fn count_rejection(stats: &GlobalStats, now: i64) -> Option<BreakerCount> {
+    if !stats.circuit_breaker_enabled() {
+        return None;
+    }
+    let elapsed = now.saturating_sub(stats.rejection_window_start);
+    let (window_start, previous) = if (0..stats.circuit_breaker_window_seconds).contains(&elapsed) {
+        (stats.rejection_window_start, stats.rejection_count)
+    } else {
+        (now, 0)
+    };
+    let count = previous.saturating_add(1);
+    Some(BreakerCount { window_start, count, tripped: count > stats.circuit_breaker_threshold })
+}
+
+// The admin action throttle's window after one more destructive action at `now`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AdminActionCount {
+    pub window_start: i64,
+    pub count: u32,
+    pub throttled: bool, // count went past the cap; the action has to be refused
+}
+
+// count_rejection's windowing, applied to the admin's block, reset and
+// update_config calls. None while the throttle is off.
+pub fn count_admin_action(config: &GlobalConfig, now: i64) -> Option<AdminActionCount> {
+    if !config.admin_throttle_enabled() {
+        return None;
+    }
+    let elapsed = now.saturating_sub(config.admin_action_window_start);
+    let (window_start, previous) = if (0..config.admin_action_window_seconds).contains(&elapsed) {
+        (config.admin_action_window_start, config.admin_actions_in_window)
+    } else {
+        (now, 0)
+    };
+    let count = previous.saturating_add(1);
+    Some(AdminActionCount { window_start, count, throttled: count > config.max_admin_actions_per_window })
+}
+
+// What rebalance_limits makes of the interval since the policy's marks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rebalance {
+    pub max_requests: u64,
+    pub burst_limit: u64,
+    pub accepted: u64,
+    pub denied: u64,
+    pub rejection_bps: u64, // denied out of accepted + denied, 0 for an idle interval
+    pub quiet_streak: u8,
+}
+
+// Denials above target_rejection_bps of attempts raise max_requests by a step,
+// up to the policy's ceiling, and quiet_intervals quiet intervals in a row lower
+// it by one, down to its floor. Anything in between leaves it and ends the
+// streak. A limit an update_config put outside the bounds is never moved further
+// out, nor pulled across them the wrong way. burst_limit is lifted to stay at or
+// above the result. Hard rejections roll their transaction back, so attempts can
+// only be the accepted units plus gate_check denials.
+pub fn rebalance(policy: &RebalancePolicy, stats: &GlobalStats, config: &GlobalConfig) -> Rebalance {
+    let params = &policy.params;
+    let accepted = stats.total_requests.saturating_sub(policy.requests_mark);
+    let denied = stats.total_denials.saturating_sub(policy.denials_mark);
+    let attempts = accepted as u128 + denied as u128;
+    let rejection_bps =
+        if attempts == 0 { 0 } else { (denied as u128 * BPS_DENOMINATOR as u128 / attempts) as u64 };
+
+    let current = config.max_requests;
+    let mut max_requests = current;
+    let mut quiet_streak = 0;
+    if rejection_bps > params.target_rejection_bps as u64 {
+        max_requests = current.saturating_add(params.step).min(params.ceiling).max(current);
+    } else if rejection_bps <= params.quiet_rejection_bps as u64 {
+        quiet_streak = policy.quiet_streak.saturating_add(1);
+        if params.quiet_intervals > 0 && quiet_streak >= params.quiet_intervals {
+            max_requests = current.saturating_sub(params.step).max(params.floor).min(current);
+            quiet_streak = 0;
+        }
+    }
+    Rebalance {
+        max_requests,
+        burst_limit: config.burst_limit.max(max_requests),
+        accepted,
+        denied,
+        rejection_bps,
+        quiet_streak,
+    }
+}
+
+// Whether a payer that last registered at `last_registration_ts` (0 if never) may
+// register again at `now`
+pub fn registration_allowed(config: &GlobalConfig, last_registration_ts: i64, now: i64) -> bool {
+    config.registration_cooldown_seconds <= 0
+        || last_registration_ts == 0
+        || now.saturating_sub(last_registration_ts) >= config.registration_cooldown_seconds
+}
+
+// Whether a payer that has funded `buckets_created` registrations may fund another
+pub fn payer_under_cap(config: &GlobalConfig, buckets_created: u32) -> bool {
+    config.max_buckets_per_payer == 0 || buckets_created < config.max_buckets_per_payer
+}
+
+// Whether a client whose last appeal was denied at `denied_at` (0 if never) may
+// file again at `now`
+pub fn appeal_allowed(config: &GlobalConfig, denied_at: i64, now: i64) -> bool {
+    config.appeal_cooldown_seconds <= 0
+        || denied_at == 0
+        || now.saturating_sub(denied_at) >= config.appeal_cooldown_seconds
+}
+
+// The bucket's violations as of `now`, halved once for every whole
+// violation_halflife_seconds since violations_updated_at, and the time that decay
+// runs from. The time only moves on by the half-lives applied, so reading early
+// loses no part of one. A zero half-life never decays, and neither does an
+// unstamped count (a migrated bucket, or a fast one, which has nowhere to keep it).
+pub fn decayed_violations(bucket: &ClientBucket, config: &GlobalConfig, now: i64) -> (u64, i64) {
+    let halflife = i64::from(config.violation_halflife_seconds);
+    let since = bucket.violations_updated_at;
+    if halflife == 0 || since == 0 || now <= since {
+        return (bucket.violations, since);
+    }
+    let halvings = (now - since) / halflife;
+    let violations = u32::try_from(halvings).ok().and_then(|n| bucket.violations.checked_shr(n)).unwrap_or(0);
+    (violations, since + halvings * halflife)
+}
+
+// One more violation at `now`, on top of the decayed count. A count that started
+// from nothing starts its half-life now; otherwise the new one joins the old
+// count's half-life rather than restarting it.
+pub fn add_violation(bucket: &mut ClientBucket, config: &GlobalConfig, now: i64) {
+    let (violations, since) = decayed_violations(bucket, config, now);
+    bucket.violations = violations.saturating_add(1);
+    bucket.violations_updated_at = if violations == 0 || since == 0 { now } else { since };
+}
+
+// Whether a client wallet holding `lamports` may consume: it meets
+// min_client_balance_lamports, or its bucket is exempt. Sponsored buckets are,
+// since sponsorship is for wallets with nothing to pay with.
+pub fn balance_sufficient(bucket: &ClientBucket, config: &GlobalConfig, lamports: u64) -> bool {
+    lamports >= config.min_client_balance_lamports || bucket.is_balance_exempt() || bucket.is_sponsored()
+}
+
+// Whether the bucket has gone longer than inactivity_forgiveness_seconds without
+// an accepted consume and has violations to clear. Blocks are not penalties here:
+// only the admin lifts one. A fast bucket keeps no last consume time, so it is
+// never taken for idle.
+pub fn penalties_forgivable(bucket: &ClientBucket, config: &GlobalConfig, now: i64) -> bool {
+    config.inactivity_forgiveness_seconds > 0
+        && bucket.violations > 0
+        && bucket.last_request_ts != 0
+        && now.saturating_sub(bucket.last_request_ts) > config.inactivity_forgiveness_seconds
+}
+
+// Whether the bucket's recovery delegate may close it: recovery is on and the
+// bucket has gone recovery_inactive_seconds without an accepted consume. A bucket
+// that never consumed counts from its registration.
+pub fn bucket_recoverable(bucket: &ClientBucket, config: &GlobalConfig, now: i64) -> bool {
+    config.recovery_inactive_seconds > 0
+        && bucket.has_recovery_delegate()
+        && now.saturating_sub(bucket.last_request_ts) >= config.recovery_inactive_seconds
+}
+
+// Whether the bucket's latest window holds more than `max_requests`. request_count
+// is the open window's count, or the last window's until the next consume resets
+// it, so either way it is the most recent usage on record. Blocked buckets are
+// rejected regardless of limits and never count.
+pub fn exceeds_max(bucket: &ClientBucket, max_requests: u64) -> bool {
+    !bucket.is_blocked() && bucket.request_count > max_requests
+}
+
+// Whether gc_bucket may close `bucket` at `now`: collection is on, nothing has been
+// consumed for stale_after_seconds, and the bucket is not blocked. A blocked
+// bucket is the block itself, so closing it would let the wallet start over.
+pub fn gc_collectable(bucket: &ClientBucket, config: &GlobalConfig, now: i64) -> bool {
+    config.stale_after_seconds > 0
+        && !bucket.is_blocked()
+        && now.saturating_sub(bucket.last_request_ts) >= config.stale_after_seconds
+}
+
+// preview_registration's answer for a client that would register at `now`, paid
+// for by the payer behind `payer_meta` (None if it has never registered anyone).
+// `taken` is whether the client already has a bucket of any kind, and
+// `program_owned` whether its account belongs to a program rather than a wallet.
+// registration_open is register_client's own checks: the pause, an existing
+// bucket, and the payer's cap and cooldown.
+pub fn registration_preview(
+    config: &GlobalConfig,
+    entries: &[ScheduleEntry],
+    now: i64,
+    payer_meta: Option<&PayerMeta>,
+    taken: bool,
+    program_owned: bool,
+    rent: &Rent,
+) -> RegistrationPreview {
+    let limits = wallet_config(config, entries, now);
+    let (buckets_created, last_registration_ts) = payer_meta.map_or((0, 0), |meta| {
+        (meta.buckets_created, meta.last_registration_ts)
+    });
+    let mut rent_lamports = rent.minimum_balance(8 + ClientBucket::INIT_SPACE);
+    if payer_meta.is_none() {
+        rent_lamports += rent.minimum_balance(8 + PayerMeta::INIT_SPACE);
+    }
+    RegistrationPreview {
+        max_requests: limits.max_requests,
+        window_seconds: limits.window_seconds,
+        burst_limit: limits.burst_limit,
+        registration_fee_lamports: config.registration_fee_lamports,
+        rent_lamports,
+        registration_open: !config.pause_active(now)
+            && !taken
+            && payer_under_cap(config, buckets_created)
+            && registration_allowed(config, last_registration_ts, now),
+        requires_allowlist: program_owned,
+        wallet_consumes_allowed: wallet_consume_allowed(config, !program_owned),
+    }
+}
+
+// The cranker's cut of a collected bucket's `lamports`, rounded down
+pub fn gc_bounty(lamports: u64, bounty_bps: u16) -> u64 {
+    let bps = (bounty_bps as u64).min(BPS_DENOMINATOR);
+    (lamports as u128 * bps as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+// Lamports an account holding `lamports` still needs to be rent-exempt at
+// `data_len` bytes; 0 if it already is, so nobody pays over the minimum
+pub fn rent_shortfall(rent: &Rent, data_len: usize, lamports: u64) -> u64 {
+    rent.minimum_balance(data_len).saturating_sub(lamports)
+}
+
+// DeploymentInfo.features_bitmask: the FEATURE_* bits for what the config has
+// switched on. A feature counts as on once its setting would change a call's
+// outcome, so a hook program set is on and a zero registration fee is off.
+pub fn deployment_features(config: &GlobalConfig) -> u64 {
+    [
+        (FEATURE_REGISTRATION_FEE, config.registration_fee_lamports > 0),
+        (FEATURE_DISCOUNT_TIERS, config.discount_tier_count > 0),
+        (FEATURE_PROGRAM_KEYED, config.program_keyed()),
+        (FEATURE_SCHEDULE, config.scheduled()),
+        (FEATURE_LIMIT_FEEDER, config.has_feeder()),
+        (FEATURE_HOOK, config.has_hook()),
+        (FEATURE_COUNCIL, config.has_council()),
+        (FEATURE_APPEALS, config.appeal_bond_lamports > 0),
+        (FEATURE_REFUNDS, config.has_refund_authority()),
+        (FEATURE_BUCKET_RECOVERY, config.recovery_inactive_seconds > 0),
+    ]
+    .into_iter()
+    .filter(|&(_, on)| on)
+    .fold(0, |bits, (bit, _)| bits | bit)
+}
+
+// Whether a pending admin recovery has sat out its timelock
+pub fn recovery_finalizable(config: &GlobalConfig, now: i64) -> bool {
+    config.recovery_pending() && now >= config.recovery_eta
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{
+        ADMIN_RECOVERY_DELAY, AUTHORITY_KINDS, AUTHORITY_LIMIT_FEEDER, AUTHORITY_REFUND, CLIENT_BUCKET_VERSION,
+        GLOBAL_CONFIG_VERSION, MAX_DISCOUNT_TIERS, PAUSE_REASON_INCIDENT,
+    };
+    use crate::state::{PendingAuthority, PreviousConfig, RebalanceParams};
+    use anchor_lang::prelude::Pubkey;
+
+    const START: i64 = 1_000;
+
+    fn config(max_requests: u64, window_seconds: i64, burst_limit: u64) -> GlobalConfig {
+        GlobalConfig {
+            version: GLOBAL_CONFIG_VERSION,
+            admin: Pubkey::default(),
+            max_requests,
+            window_seconds,
+            burst_limit,
+            flags: 0,
+            config_epoch: 0,
+            bump: 0,
+            hook_program: Pubkey::default(),
+            limit_feeder: Pubkey::default(),
+            dynamic_floor: 0,
+            dynamic_ceiling: 0,
+            dynamic_max: 0,
+            dynamic_valid_until: 0,
+            council: [Pubkey::default(); 5],
+            council_size: 0,
+            council_threshold: 0,
+            proposal_count: 0,
+            recovery_admin: Pubkey::default(),
+            recovery_eta: 0,
+            scheduled_pause_start: 0,
+            scheduled_pause_end: 0,
+            treasury: Pubkey::default(),
+            registration_fee_lamports: 0,
+            registration_cooldown_seconds: 0,
+            stale_after_seconds: 0,
+            gc_bounty_bps: 0,
+            discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+            discount_tier_count: 0,
+            appeal_bond_lamports: 0,
+            appeal_cooldown_seconds: 0,
+            paused_at: 0,
+            pause_reason: 0,
+            auto_unpause_at: 0,
+            config_preset: 0,
+            violation_halflife_seconds: 0,
+            admin_action_window_seconds: 0,
+            max_admin_actions_per_window: 0,
+            admin_action_window_start: 0,
+            admin_actions_in_window: 0,
+            refund_authority: Pubkey::default(),
+            max_refunds_per_window: 0,
+            max_buckets_per_payer: 0,
+            pause_grace_seconds: 0,
+            pause_grace_budget: 0,
+            grace_budget: 0,
+            inactivity_forgiveness_seconds: 0,
+            min_client_balance_lamports: 0,
+            recovery_inactive_seconds: 0,
+            proration_mode: 0,
+            previous_config: PreviousConfig::default(),
+            denied_owner_programs: 0,
+            pending_authorities: [PendingAuthority::default(); AUTHORITY_KINDS],
+            reserved: [0; 1],
+        }
+    }
+
+    fn bucket(request_count: u64, burst_credits: u64) -> ClientBucket {
+        ClientBucket {
+            version: CLIENT_BUCKET_VERSION,
+            owner: Pubkey::default(),
+            request_count,
+            window_start: START,
+            total_requests: 0,
+            flags: 0,
+            violations: 0,
+            violations_updated_at: 0,
+            config_epoch: 0,
+            burst_credits,
+            priority_count: 0,
+            escrowed_count: 0,
+            bonus_requests: 0,
+            last_nonce: 0,
+            nonce_bitmap: 0,
+            last_refund_nonce: 0,
+            refunded_count: 0,
+            registered_by: Pubkey::default(),
+            recovery_delegate: Pubkey::default(),
+            last_request_ts: START,
+            bump: 0,
+            label: [0; 32],
+            event_tag: [0; 16],
+        }
+    }
+
+    fn consume(b: &ClientBucket, c: &GlobalConfig, now: i64, amount: u64) -> Result<ClientBucket, Rejection> {
+        apply_consume(b, c, now, amount).map(|consumed| consumed.bucket)
+    }
+
+    #[test]
+    fn counts_within_the_window() {
+        let c = config(3, 60, 3);
+        let b = consume(&bucket(0, 0), &c, START + 1, 1).unwrap();
+        let b = consume(&b, &c, START + 2, 2).unwrap();
+        assert_eq!(b.request_count, 3);
+        assert_eq!(b.window_start, START);
+        assert_eq!(b.last_request_ts, START + 2);
+        assert_eq!(consume(&b, &c, START + 3, 1).err(), Some(Rejection::RateLimit));
+    }
+
+    #[test]
+    fn rolls_over_on_the_boundary() {
+        let c = config(3, 60, 3);
+        let full = bucket(3, 0);
+        assert_eq!(consume(&full, &c, START + 59, 1).err(), Some(Rejection::RateLimit));
+
+        let consumed = apply_consume(&full, &c, START + 60, 1).unwrap();
+        assert_eq!(
+            consumed.rollover,
+            Some(Rollover { closed_window_start: START, closed_count: 3, skipped_windows: 0 })
+        );
+        assert_eq!(consumed.bucket.request_count, 1);
+        assert_eq!(consumed.bucket.window_start, START + 60);
+    }
+
+    fn priority(b: &ClientBucket, c: &GlobalConfig, now: i64, flag: bool) -> Result<(ClientBucket, bool), Rejection> {
+        apply_priority_consume(b, c, now, flag).map(|(consumed, from_burst)| (consumed.bucket, from_burst))
+    }
+
+    #[test]
+    fn deployment_features_follow_the_config() {
+        let mut c = config(4, 60, 8);
+        assert_eq!(deployment_features(&c), 0);
+
+        c.registration_fee_lamports = 5_000;
+        c.set_scheduled(true);
+        c.hook_program = Pubkey::new_unique();
+        assert_eq!(deployment_features(&c), FEATURE_REGISTRATION_FEE | FEATURE_SCHEDULE | FEATURE_HOOK);
+
+        c.registration_fee_lamports = 0;
+        c.discount_tier_count = 1;
+        c.council_size = 2;
+        c.refund_authority = Pubkey::new_unique();
+        c.recovery_inactive_seconds = 3_600;
+        assert_eq!(
+            deployment_features(&c),
+            FEATURE_DISCOUNT_TIERS | FEATURE_SCHEDULE | FEATURE_HOOK | FEATURE_COUNCIL | FEATURE_REFUNDS
+                | FEATURE_BUCKET_RECOVERY
+        );
+    }
+
+    #[test]
+    fn closing_windows_are_classed_by_quarter_or_denial() {
+        let c = config(4, 60, 8);
+        let classes: Vec<usize> = [0, 1, 2, 3, 4, 8].iter().map(|&count| usage_class(&bucket(count, 0), &c)).collect();
+        assert_eq!(classes, [0, 1, 2, 3, 3, 3]);
+
+        let mut denied = bucket(1, 0);
+        denied.set_window_rejected(true);
+        assert_eq!(usage_class(&denied, &c), USAGE_REJECTED);
+        // The mark goes with the window it was set in
+        let next = consume(&denied, &c, START + 60, 1).unwrap();
+        assert!(!next.window_rejected());
+        assert!(consume(&denied, &c, START + 1, 1).unwrap().window_rejected());
+    }
+
+    #[test]
+    fn consume_report_matches_the_bucket_it_describes() {
+        let c = config(3, 60, 6);
+        let b = consume(&bucket(0, 2), &c, START + 5, 2).unwrap();
+        let report = consume_report(&b, &c);
+        assert_eq!(report, ConsumeReport { used: 2, max: 3, remaining: 3, window_end: START + 60 });
+        assert_eq!(report.remaining, remaining_capacity(&b, &c));
+
+        // Bought quota raises the allowance the report shows
+        let bought = ClientBucket { bonus_requests: 2, ..b };
+        assert_eq!(consume_report(&bought, &c).max, 5);
+    }
+
+    #[test]
+    fn priority_spends_credit_before_the_window() {
+        let c = config(3, 60, 6);
+        let (b, from_burst) = priority(&bucket(0, 2), &c, START + 1, true).unwrap();
+        assert!(from_burst);
+        assert_eq!((b.request_count, b.priority_count, b.burst_credits), (1, 1, 1));
+        // The whole window allowance is still there for ordinary consumes
+        assert_eq!(remaining_capacity(&b, &c), 4);
+        let b = consume(&b, &c, START + 2, 2).unwrap();
+        assert_eq!((b.request_count, b.burst_credits), (3, 1));
+
+        // Out of credits, a priority unit takes the window allowance like any other
+        let (b, _) = priority(&b, &c, START + 3, true).unwrap();
+        let (b, from_burst) = priority(&b, &c, START + 4, true).unwrap();
+        assert!(!from_burst);
+        assert_eq!(b.priority_count, 2);
+        assert_eq!(priority(&b, &c, START + 5, true).err(), Some(Rejection::RateLimit));
+    }
+
+    #[test]
+    fn unflagged_priority_consumes_report_burst_only_past_the_max() {
+        let c = config(2, 60, 4);
+        let (b, from_burst) = priority(&bucket(1, 2), &c, START + 1, false).unwrap();
+        assert!(!from_burst);
+        let (b, from_burst) = priority(&b, &c, START + 2, false).unwrap();
+        assert!(from_burst);
+        assert_eq!((b.request_count, b.priority_count, b.burst_credits), (3, 0, 1));
+    }
+
+    #[test]
+    fn priority_units_still_count_towards_the_ceiling() {
+        let c = config(3, 60, 4);
+        let mut b = bucket(0, 3);
+        for at in 1..=3 {
+            b = priority(&b, &c, START + at, true).unwrap().0;
+        }
+        b = consume(&b, &c, START + 4, 1).unwrap();
+        assert_eq!(b.request_count, 4);
+        assert_eq!(remaining_capacity(&b, &c), 0);
+        assert_eq!(consume(&b, &c, START + 5, 1).err(), Some(Rejection::BurstLimit));
+    }
+
+    #[test]
+    fn mixed_traffic_adds_up_and_rolls_over() {
+        let c = config(4, 60, 8);
+        let mut b = bucket(0, 4);
+        let mut taken: u64 = 0;
+        for (at, flag) in [(1, true), (2, false), (3, true), (4, false), (5, false)] {
+            b = priority(&b, &c, START + at, flag).unwrap().0;
+            taken += 1;
+        }
+        assert_eq!(b.request_count, taken);
+        assert_eq!((b.priority_count, b.burst_credits), (2, 2));
+        // Only the three ordinary units used the allowance, so one is banked
+        let next = consume(&b, &c, START + 60, 1).unwrap();
+        assert_eq!((next.request_count, next.priority_count, next.burst_credits), (1, 0, 3));
+    }
+
+    #[test]
+    fn escrowed_quota_is_held_out_of_the_sellers_window() {
+        let c = config(4, 60, 6);
+        let b = escrow_quota(&bucket(1, 2), &c, START + 1, 2).unwrap().bucket;
+        assert_eq!((b.request_count, b.escrowed_count, b.burst_credits), (3, 2, 2));
+        // Only one unit of the allowance is left, then the credits as usual
+        let b = consume(&b, &c, START + 2, 1).unwrap();
+        assert_eq!(consume(&b, &c, START + 3, 2).unwrap().burst_credits, 0);
+        // Credits are spent consuming, never sold
+        assert_eq!(escrow_quota(&b, &c, START + 3, 1).err(), Some(Rejection::RateLimit));
+        // The escrow goes with the window
+        let next = consume(&b, &c, START + 60, 1).unwrap();
+        assert_eq!((next.request_count, next.escrowed_count), (1, 0));
+    }
+
+    fn refunding(max_refunds: u64) -> GlobalConfig {
+        let mut c = config(4, 60, 6);
+        c.max_refunds_per_window = max_refunds;
+        c
+    }
+
+    #[test]
+    fn a_refund_gives_the_window_its_headroom_back() {
+        let c = refunding(10);
+        let mut b = bucket(4, 0);
+        b.total_requests = 10;
+        assert_eq!(consume(&b, &c, START + 1, 1).err(), Some(Rejection::RateLimit));
+        let (b, refunded) = apply_refund(&b, &c, START + 1, 2, 1).unwrap();
+        assert_eq!((refunded, b.request_count, b.refunded_count, b.last_refund_nonce), (2, 2, 2, 1));
+        // Totals are kept unless the config says otherwise
+        assert_eq!(b.total_requests, 10);
+        assert_eq!(consume(&b, &c, START + 2, 2).unwrap().request_count, 4);
+
+        let mut lowers = c.clone();
+        lowers.set_refunds_lower_totals(true);
+        let (b, _) = apply_refund(&b, &lowers, START + 3, 1, 2).unwrap();
+        assert_eq!((b.request_count, b.total_requests), (1, 9));
+    }
+
+    #[test]
+    fn refund_nonces_only_move_forward() {
+        let c = refunding(10);
+        let (b, _) = apply_refund(&bucket(4, 0), &c, START + 1, 1, 5).unwrap();
+        for nonce in [0, 4, 5] {
+            assert_eq!(apply_refund(&b, &c, START + 2, 1, nonce).err(), Some(RefundRefusal::NonceUsed));
+        }
+        assert!(apply_refund(&b, &c, START + 2, 1, 6).is_ok());
+    }
+
+    #[test]
+    fn refunds_are_capped_per_window() {
+        let c = refunding(3);
+        let (b, _) = apply_refund(&bucket(4, 0), &c, START + 1, 2, 1).unwrap();
+        assert_eq!(apply_refund(&b, &c, START + 2, 2, 2).err(), Some(RefundRefusal::CapExceeded));
+        let (b, refunded) = apply_refund(&b, &c, START + 2, 1, 3).unwrap();
+        assert_eq!((refunded, b.refunded_count), (1, 3));
+        // The cap starts over with the next window
+        let next = consume(&b, &c, START + 60, 4).unwrap();
+        assert_eq!(next.refunded_count, 0);
+        assert_eq!(apply_refund(&next, &c, START + 61, 3, 4).unwrap().1, 3);
+    }
+
+    #[test]
+    fn refunds_clamp_to_what_the_window_paid_for() {
+        let c = refunding(100);
+        let mut b = bucket(4, 0);
+        b.priority_count = 1;
+        b.escrowed_count = 1;
+        let (b, refunded) = apply_refund(&b, &c, START + 1, 50, 1).unwrap();
+        assert_eq!((refunded, b.request_count), (2, 2));
+        let (b, refunded) = apply_refund(&b, &c, START + 1, 1, 2).unwrap();
+        assert_eq!((refunded, b.request_count), (0, 2));
+        // An ended window has nothing to give back, but the nonce is spent
+        let (b, refunded) = apply_refund(&bucket(4, 0), &c, START + 60, 1, 1).unwrap();
+        assert_eq!((refunded, b.request_count, b.last_refund_nonce), (0, 4, 1));
+    }
+
+    #[test]
+    fn bought_quota_raises_the_window_and_lapses_with_it() {
+        let c = config(2, 60, 3);
+        let mut b = bucket(2, 1);
+        b.bonus_requests = 2;
+        assert_eq!(remaining_capacity(&b, &c), 3);
+        let b = consume(&b, &c, START + 1, 2).unwrap();
+        assert_eq!((b.request_count, b.burst_credits), (4, 1));
+        let b = consume(&b, &c, START + 2, 1).unwrap();
+        assert_eq!(b.request_count, 5);
+        assert_eq!(consume(&b, &c, START + 3, 1).err(), Some(Rejection::RateLimit));
+        let next = consume(&b, &c, START + 60, 2).unwrap();
+        assert_eq!((next.request_count, next.bonus_requests), (2, 0));
+        assert_eq!(consume(&next, &c, START + 61, 1).err(), Some(Rejection::RateLimit));
+    }
+
+    #[test]
+    fn offers_lapse_with_the_sellers_window() {
+        let c = config(2, 60, 3);
+        let seller = bucket(1, 0);
+        assert!(offer_live(&seller, &c, START, 0, START + 59));
+        // Lazily: nothing has rolled the seller over yet
+        assert!(!offer_live(&seller, &c, START, 0, START + 60));
+        let rolled = consume(&seller, &c, START + 61, 1).unwrap();
+        assert!(!offer_live(&rolled, &c, START, 0, START + 62));
+        let mut bumped = c.clone();
+        bumped.config_epoch = 1;
+        assert!(!offer_live(&seller, &bumped, START, 0, START + 1));
+    }
+
+    #[test]
+    fn idle_windows_are_skipped_and_bank_capped_credit() {
+        let c = config(3, 60, 5);
+        let consumed = apply_consume(&bucket(1, 0), &c, START + 60 * 4 + 10, 1).unwrap();
+        assert_eq!(consumed.rollover.unwrap().skipped_windows, 3);
+        assert_eq!(consumed.bucket.window_start, START + 250);
+        assert_eq!(consumed.bucket.burst_credits, 3);
+    }
+
+    #[test]
+    fn burst_spends_credit_up_to_the_ceiling() {
+        let c = config(2, 60, 3);
+        let b = consume(&bucket(2, 2), &c, START + 1, 1).unwrap();
+        assert_eq!(b.request_count, 3);
+        assert_eq!(b.burst_credits, 1);
+        // A credit is left but the window is at burst_limit
+        assert_eq!(consume(&b, &c, START + 2, 1).err(), Some(Rejection::BurstLimit));
+    }
+
+    #[test]
+    fn amounts_spend_credit_only_for_the_excess() {
+        let c = config(4, 60, 8);
+        let b = consume(&bucket(3, 4), &c, START + 1, 3).unwrap();
+        assert_eq!(b.request_count, 6);
+        assert_eq!(b.burst_credits, 2);
+        assert_eq!(consume(&bucket(3, 1), &c, START + 1, 3).err(), Some(Rejection::RateLimit));
+    }
+
+    #[test]
+    fn stale_epoch_resets_without_credit() {
+        let mut c = config(3, 60, 5);
+        c.config_epoch = 1;
+        let consumed = apply_consume(&bucket(1, 0), &c, START + 1, 1).unwrap();
+        assert!(consumed.rollover.is_some());
+        assert_eq!(consumed.bucket.burst_credits, 0);
+        assert_eq!(consumed.bucket.config_epoch, 1);
+        assert_eq!(consumed.bucket.window_start, START + 1);
+    }
+
+    // The config after one prorated change from 10 per 60s: epoch 0 to 1
+    fn prorated(max_requests: u64, window_seconds: i64) -> GlobalConfig {
+        let mut c = config(max_requests, window_seconds, max_requests);
+        c.config_epoch = 1;
+        c.previous_config = PreviousConfig { max_requests: 10, window_seconds: 60, config_epoch: 0 };
+        c
+    }
+
+    #[test]
+    fn prorated_changes_restate_the_open_window() {
+        let mut b = bucket(6, 0);
+        // Served more in earlier windows, so loosening is not capped at this one
+        b.total_requests = 40;
+        // Tightened to 5: 6 of 10 becomes 3 of 5, so two more fit
+        let tightened = consume(&b, &prorated(5, 60), START + 1, 2).unwrap();
+        assert_eq!((tightened.request_count, tightened.window_start, tightened.config_epoch), (5, START, 1));
+        assert_eq!(consume(&tightened, &prorated(5, 60), START + 2, 1).err(), Some(Rejection::RateLimit));
+        // Loosened to 20 and 120s: 6 of 10 becomes 12 of 20 in the same window
+        let loosened = consume(&b, &prorated(20, 120), START + 90, 1).unwrap();
+        assert_eq!((loosened.request_count, loosened.window_start), (13, START));
+    }
+
+    #[test]
+    fn loosening_stays_within_what_the_bucket_served() {
+        // Counts doubled past the lifetime total are capped back to it
+        let mut b = bucket(4, 0);
+        b.priority_count = 2;
+        b.total_requests = 5;
+        let next = consume(&b, &prorated(20, 60), START + 1, 0).unwrap();
+        assert_eq!((next.request_count, next.priority_count), (5, 4));
+        assert!(crate::invariants::check_invariants(&next, &prorated(20, 60)).is_ok());
+    }
+
+    #[test]
+    fn proration_falls_back_to_a_reset() {
+        let mut b = bucket(6, 0);
+        b.total_requests = 6;
+        // Shrinking the window past the one open closes it
+        let expired = consume(&b, &prorated(5, 30), START + 40, 1).unwrap();
+        assert_eq!((expired.request_count, expired.window_start), (1, START + 40));
+        // A second change since the bucket last consumed
+        let mut twice = prorated(5, 60);
+        twice.config_epoch = 2;
+        twice.previous_config.config_epoch = 1;
+        let missed = consume(&b, &twice, START + 1, 1).unwrap();
+        assert_eq!((missed.request_count, missed.window_start), (1, START + 1));
+        // A reset or unprorated change leaves no previous limits behind
+        let mut reset = prorated(5, 60);
+        reset.previous_config = PreviousConfig::default();
+        assert_eq!(consume(&b, &reset, START + 1, 1).unwrap().request_count, 1);
+    }
+
+    #[test]
+    fn future_window_start_is_clamped_to_now() {
+        let c = config(3, 60, 3);
+        let mut b = bucket(0, 0);
+        b.window_start = START + 500;
+        let consumed = apply_consume(&b, &c, START, 1).unwrap();
+        assert!(consumed.clock_clamped);
+        assert!(consumed.rollover.is_none());
+        assert_eq!(consumed.bucket.window_start, START);
+    }
+
+    #[test]
+    fn pause_takes_precedence_over_block() {
+        let mut c = config(3, 60, 3);
+        let mut b = bucket(0, 0);
+        b.set_blocked(true);
+        assert_eq!(consume(&b, &c, START, 1).err(), Some(Rejection::Blocked));
+        c.set_paused(true);
+        assert_eq!(consume(&b, &c, START, 1).err(), Some(Rejection::Paused));
+    }
+
+    #[test]
+    fn frozen_buckets_keep_their_counters_for_the_thaw() {
+        let c = config(3, 60, 3);
+        let mut b = bucket(2, 0);
+        b.set_frozen(true);
+        assert_eq!(consume(&b, &c, START + 1, 1).err(), Some(Rejection::Frozen));
+        // A block still reads as a block
+        b.set_blocked(true);
+        assert_eq!(consume(&b, &c, START + 1, 1).err(), Some(Rejection::Blocked));
+
+        b.set_blocked(false);
+        b.set_frozen(false);
+        let b = consume(&b, &c, START + 2, 1).unwrap();
+        assert_eq!(b.request_count, 3);
+    }
+
+    #[test]
+    fn scheduled_pause_applies_only_inside_its_window() {
+        let mut c = config(3, 60, 3);
+        c.scheduled_pause_start = START + 10;
+        c.scheduled_pause_end = START + 20;
+        let b = bucket(0, 0);
+        assert!(consume(&b, &c, START + 9, 1).is_ok());
+        assert_eq!(consume(&b, &c, START + 10, 1).err(), Some(Rejection::Paused));
+        assert_eq!(consume(&b, &c, START + 19, 1).err(), Some(Rejection::Paused));
+        assert!(consume(&b, &c, START + 20, 1).is_ok());
+    }
+
+    #[test]
+    fn pause_info_prefers_the_emergency_pause() {
+        let mut c = config(3, 60, 3);
+        c.scheduled_pause_start = START + 10;
+        c.scheduled_pause_end = START + 20;
+        let scheduled = PauseInfo { paused_at: START + 10, reason: PAUSE_REASON_MAINTENANCE };
+        assert_eq!(pause_info(&c, START + 15), scheduled);
+
+        c.set_paused(true);
+        c.paused_at = START + 12;
+        c.pause_reason = PAUSE_REASON_INCIDENT;
+        let emergency = PauseInfo { paused_at: START + 12, reason: PAUSE_REASON_INCIDENT };
+        assert_eq!(pause_info(&c, START + 15), emergency);
+    }
+
+    #[test]
+    fn pause_grace_needs_time_and_budget_left() {
+        let mut c = config(3, 60, 3);
+        c.set_paused(true);
+        c.paused_at = START;
+        c.pause_grace_seconds = 30;
+        c.grace_budget = 2;
+        let mut grace = PauseGrace { paused_at: START, spent: 0, bump: 255 };
+        assert!(pause_grace_open(&c, &grace, START));
+        assert!(pause_grace_open(&c, &grace, START + 29));
+        assert!(!pause_grace_open(&c, &grace, START + 30));
+
+        grace.spent = 2;
+        assert!(!pause_grace_open(&c, &grace, START + 1));
+
+        // Spends from an earlier pause do not count against this one
+        grace.paused_at = START - 100;
+        assert_eq!(grace_left(&c, &grace), 2);
+        assert!(pause_grace_open(&c, &grace, START + 1));
+
+        // A scheduled pause has no grace, and neither does a lifted one
+        c.set_paused(false);
+        c.scheduled_pause_start = START;
+        c.scheduled_pause_end = START + 60;
+        assert!(!pause_grace_open(&c, &grace, START + 1));
+    }
+
+    #[test]
+    fn overflow_is_rejected() {
+        let c = config(u64::MAX, i64::MAX, u64::MAX);
+        assert_eq!(consume(&bucket(1, 0), &c, START, u64::MAX).err(), Some(Rejection::Overflow));
+        // Window end START + i64::MAX is not representable
+        assert_eq!(consume(&bucket(0, 0), &c, START, 1).err(), Some(Rejection::Overflow));
+    }
+
+    #[test]
+    fn rejection_leaves_input_untouched() {
+        let c = config(3, 60, 3);
+        let mut b = bucket(3, 0);
+        b.window_start = START + 500;
+        assert_eq!(consume(&b, &c, START, 1).err(), Some(Rejection::RateLimit));
+        assert_eq!(b.window_start, START + 500);
+        assert_eq!(b.request_count, 3);
+    }
+
+    #[test]
+    fn shard_limits_split_the_window() {
+        let c = shard_config(&config(10, 60, 20), 3);
+        assert_eq!((c.max_requests, c.burst_limit), (3, 6));
+        assert_eq!(c.window_seconds, 60);
+        // More shards than requests still lets every shard admit one
+        let c = shard_config(&config(2, 60, 2), 4);
+        assert_eq!((c.max_requests, c.burst_limit), (1, 1));
+    }
+
+    #[test]
+    fn shards_together_stay_within_shard_count_of_the_limit() {
+        for max in 1..=40u64 {
+            for shards in 2..=16u8 {
+                let c = config(max, 60, max);
+                let sc = shard_config(&c, shards);
+                let mut accepted = 0;
+                for _ in 0..shards {
+                    let mut b = bucket(0, 0);
+                    while let Ok(next) = consume(&b, &sc, START, 1) {
+                        b = next;
+                        accepted += 1;
+                    }
+                }
+                let slack = shards as u64;
+                assert!(accepted + slack > max, "max {} shards {}: {}", max, shards, accepted);
+                assert!(accepted < max + slack, "max {} shards {}: {}", max, shards, accepted);
+            }
+        }
+    }
+
+    #[test]
+    fn remaining_capacity_counts_steady_and_burst() {
+        let c = config(5, 60, 8);
+        assert_eq!(remaining_capacity(&bucket(0, 0), &c), 5);
+        assert_eq!(remaining_capacity(&bucket(3, 2), &c), 4);
+        // Credits beyond burst_limit cannot be spent this window
+        assert_eq!(remaining_capacity(&bucket(5, 5), &c), 3);
+        assert_eq!(remaining_capacity(&bucket(8, 5), &c), 0);
+        // Counts above max after a config change leave nothing
+        assert_eq!(remaining_capacity(&bucket(9, 0), &config(5, 60, 5)), 0);
+    }
+
+    #[test]
+    fn quota_treats_an_expired_window_as_reopened() {
+        let c = config(5, 60, 8);
+        assert_eq!(quota_at(&bucket(3, 0), &c, START + 10), (2, 50));
+        // Two unused then banked on rollover, spendable up to burst_limit
+        assert_eq!(quota_at(&bucket(3, 0), &c, START + 60), (7, 60));
+        let mut stale = bucket(5, 0);
+        stale.config_epoch = 1;
+        assert_eq!(quota_at(&stale, &c, START + 10), (5, 60));
+        let mut blocked = bucket(0, 0);
+        blocked.set_blocked(true);
+        assert_eq!(quota_at(&blocked, &c, START + 10), (0, 50));
+    }
+
+    #[test]
+    fn remaining_capacity_matches_what_apply_consume_accepts() {
+        let c = config(4, 60, 7);
+        for count in 0..=7 {
+            for credits in 0..=4 {
+                let mut b = bucket(count, credits);
+                let expected = remaining_capacity(&b, &c);
+                let mut accepted = 0;
+                while let Ok(next) = consume(&b, &c, START, 1) {
+                    b = next;
+                    accepted += 1;
+                }
+                assert_eq!(accepted, expected, "count {} credits {}", count, credits);
+            }
+        }
+    }
+
+    #[test]
+    fn program_config_keeps_global_flags_and_epoch() {
+        let mut global = config(10, 60, 20);
+        global.set_paused(true);
+        global.config_epoch = 7;
+        let program = ProgramBucket {
+            version: 1,
+            program_id: Pubkey::new_unique(),
+            caller_authority: Pubkey::new_unique(),
+            max_requests: 1_000,
+            window_seconds: 3_600,
+            burst_limit: 1_200,
+            request_count: 0,
+            window_start: START,
+            total_requests: 0,
+            flags: 0,
+            violations: 0,
+            config_epoch: 0,
+            burst_credits: 0,
+            bump: 0,
+            violations_updated_at: 0,
+            reserved: [0; 24],
+        };
+        let c = program_config(&global, &program);
+        assert_eq!((c.max_requests, c.window_seconds, c.burst_limit), (1_000, 3_600, 1_200));
+        assert!(c.is_paused());
+        assert_eq!(c.config_epoch, 7);
+    }
+
+    #[test]
+    fn wallet_consumes_in_program_keyed_mode() {
+        let mut c = config(10, 60, 20);
+        assert!(wallet_consume_allowed(&c, true));
+        assert!(wallet_consume_allowed(&c, false));
+
+        c.set_program_keyed(true);
+        assert!(!wallet_consume_allowed(&c, true));
+        assert!(!wallet_consume_allowed(&c, false));
+
+        c.set_wallet_fallback(true);
+        assert!(wallet_consume_allowed(&c, true));
+        assert!(!wallet_consume_allowed(&c, false));
+    }
+
+    fn fed(max: u64, floor: u64, ceiling: u64, valid_until: i64) -> GlobalConfig {
+        let mut c = config(10, 60, 20);
+        c.limit_feeder = Pubkey::new_unique();
+        c.dynamic_floor = floor;
+        c.dynamic_ceiling = ceiling;
+        c.dynamic_max = max;
+        c.dynamic_valid_until = valid_until;
+        c
+    }
+
+    #[test]
+    fn effective_config_applies_live_override() {
+        let c = effective_config(&fed(15, 5, 50, START + 10), START);
+        assert_eq!(c.max_requests, 15);
+        assert_eq!(c.burst_limit, 20);
+
+        let c = effective_config(&fed(40, 5, 50, START + 10), START);
+        assert_eq!(c.max_requests, 40);
+        assert_eq!(c.burst_limit, 40);
+        assert_eq!(effective_config(&c, START).max_requests, 40);
+    }
+
+    #[test]
+    fn effective_config_clamps_to_bounds() {
+        assert_eq!(effective_config(&fed(100, 5, 50, START + 10), START).max_requests, 50);
+        assert_eq!(effective_config(&fed(1, 5, 50, START + 10), START).max_requests, 5);
+    }
+
+    #[test]
+    fn effective_config_reverts_after_expiry() {
+        let c = fed(15, 5, 50, START + 10);
+        assert_eq!(effective_config(&c, START + 9).max_requests, 15);
+        assert_eq!(effective_config(&c, START + 10).max_requests, 10);
+        assert_eq!(effective_config(&c, START + 10).burst_limit, 20);
+    }
+
+    #[test]
+    fn effective_config_ignores_override_without_feeder() {
+        let mut c = fed(15, 5, 50, START + 10);
+        c.limit_feeder = Pubkey::default();
+        assert_eq!(effective_config(&c, START).max_requests, 10);
+    }
+
+    #[test]
+    fn a_rotation_hands_over_at_effective_at_and_settles_once() {
+        let mut c = fed(15, 5, 50, START + 10);
+        let (old, new) = (c.limit_feeder, Pubkey::new_unique());
+        let slot = AUTHORITY_LIMIT_FEEDER as usize;
+        c.pending_authorities[slot] = PendingAuthority { new_key: new, effective_at: START + 5 };
+        assert_eq!(c.authority(AUTHORITY_LIMIT_FEEDER, START + 4), old);
+        assert_eq!(c.authority(AUTHORITY_LIMIT_FEEDER, START + 5), new);
+        // The other role is untouched
+        assert_eq!(c.authority(AUTHORITY_REFUND, START + 5), c.refund_authority);
+
+        // Settling early changes nothing
+        c.settle_authority(AUTHORITY_LIMIT_FEEDER, START + 4);
+        assert_eq!(c.limit_feeder, old);
+        assert!(c.pending_authorities[slot].is_scheduled());
+        c.settle_authority(AUTHORITY_LIMIT_FEEDER, START + 5);
+        assert_eq!(c.limit_feeder, new);
+        assert!(!c.pending_authorities[slot].is_scheduled());
+        assert_eq!(c.authority(AUTHORITY_LIMIT_FEEDER, START), new);
+    }
+
+    const DAY: i64 = SECONDS_PER_DAY;
+
+    fn day_schedule() -> Vec<ScheduleEntry> {
+        // 20 from 08:00, 50 from 18:00, 5 from 23:00 until 08:00 the next day
+        [(8 * 3600, 20), (18 * 3600, 50), (23 * 3600, 5)]
+            .map(|(start_second_of_day, max_requests)| ScheduleEntry { start_second_of_day, max_requests })
+            .to_vec()
+    }
+
+    #[test]
+    fn scheduled_max_switches_at_mid_day_boundaries() {
+        let day = 19_000 * DAY;
+        let entries = day_schedule();
+        assert_eq!(scheduled_max(&entries, day + 8 * 3600), Some(20));
+        assert_eq!(scheduled_max(&entries, day + 18 * 3600 - 1), Some(20));
+        assert_eq!(scheduled_max(&entries, day + 18 * 3600), Some(50));
+        assert_eq!(scheduled_max(&entries, day + 23 * 3600 - 1), Some(50));
+    }
+
+    #[test]
+    fn scheduled_max_wraps_around_midnight() {
+        let day = 19_000 * DAY;
+        let entries = day_schedule();
+        assert_eq!(scheduled_max(&entries, day + 23 * 3600), Some(5));
+        assert_eq!(scheduled_max(&entries, day + DAY - 1), Some(5));
+        // Past midnight, before the day's first entry: still the 23:00 entry
+        assert_eq!(scheduled_max(&entries, day + DAY), Some(5));
+        assert_eq!(scheduled_max(&entries, day + DAY + 8 * 3600 - 1), Some(5));
+        assert_eq!(scheduled_max(&entries, day + DAY + 8 * 3600), Some(20));
+        // Timestamps before the epoch still land on the right second of day
+        assert_eq!(scheduled_max(&entries, -1), Some(5));
+    }
+
+    #[test]
+    fn scheduled_max_is_none_without_entries() {
+        assert_eq!(scheduled_max(&[], START), None);
+        let mut c = config(10, 60, 20);
+        c.set_scheduled(true);
+        let scheduled = scheduled_config(&c, &[], START);
+        assert_eq!(scheduled.max_requests, 10);
+        assert!(!scheduled.scheduled());
+    }
+
+    #[test]
+    fn scheduled_config_lifts_burst_and_yields_to_a_live_override() {
+        let mut c = fed(15, 5, 100, START + 10);
+        c.set_scheduled(true);
+        let entries = day_schedule();
+        let evening = 18 * 3600;
+        let scheduled = scheduled_config(&c, &entries, evening);
+        assert_eq!(scheduled.max_requests, 50);
+        assert_eq!(scheduled.burst_limit, 50);
+        assert!(!scheduled.scheduled());
+        // The feeder's override still wins while it is live
+        let c = fed(15, 5, 100, evening + 10);
+        assert_eq!(effective_config(&scheduled_config(&c, &entries, evening), evening).max_requests, 15);
+    }
+
+    #[test]
+    fn effective_view_of_a_plain_config_is_the_base_limits() {
+        let view = effective_view(&config(10, 60, 20), &[], None, START);
+        assert_eq!((view.max_requests, view.window_seconds, view.burst_limit), (10, 60, 20));
+        assert_eq!((view.limit_source, view.config_preset, view.config_epoch), (LIMIT_SOURCE_BASE, 0, 0));
+        assert!(!view.paused && !view.blocked && !view.frozen);
+        assert_eq!(view.pause, PauseInfo { paused_at: 0, reason: PAUSE_REASON_UNSPECIFIED });
+    }
+
+    #[test]
+    fn effective_view_takes_each_layer_over_the_last() {
+        let entries = day_schedule();
+        let evening = 18 * 3600;
+        let mut c = fed(15, 5, 100, evening + 10);
+        c.set_scheduled(true);
+        let fed_view = effective_view(&c, &entries, None, evening);
+        assert_eq!((fed_view.max_requests, fed_view.limit_source), (15, LIMIT_SOURCE_FEEDER));
+        // Once the override lapses the schedule shows through, then the base config
+        let scheduled = effective_view(&c, &entries, None, evening + 10);
+        assert_eq!((scheduled.max_requests, scheduled.burst_limit), (50, 50));
+        assert_eq!(scheduled.limit_source, LIMIT_SOURCE_SCHEDULE);
+        c.set_scheduled(false);
+        let base = effective_view(&c, &entries, None, evening + 10);
+        assert_eq!((base.max_requests, base.burst_limit, base.limit_source), (10, 20, LIMIT_SOURCE_BASE));
+    }
+
+    #[test]
+    fn effective_view_reports_the_pause_and_the_buckets_holds() {
+        let mut c = config(10, 60, 20);
+        c.set_paused(true);
+        c.paused_at = START - 5;
+        c.pause_reason = PAUSE_REASON_INCIDENT;
+        let mut b = bucket(0, 0);
+        b.set_frozen(true);
+        let view = effective_view(&c, &[], Some(&b), START);
+        assert!(view.paused && view.frozen && !view.blocked);
+        assert_eq!(view.pause, PauseInfo { paused_at: START - 5, reason: PAUSE_REASON_INCIDENT });
+        // A lapsed deadline ends the pause even with the flag still set
+        c.auto_unpause_at = START;
+        assert!(!effective_view(&c, &[], Some(&b), START).paused);
+    }
+
+    fn breaker(threshold: u64, window_seconds: i64, window_start: i64, count: u64) -> GlobalStats {
+        GlobalStats {
+            total_clients: 0,
+            blocked_clients: 0,
+            total_requests: 0,
+            total_saturated: false,
+            window_rollovers: 0,
+            snapshot_interval_seconds: 60,
+            last_snapshot_at: 0,
+            bump: 0,
+            circuit_breaker_threshold: threshold,
+            circuit_breaker_window_seconds: window_seconds,
+            rejection_window_start: window_start,
+            rejection_count: count,
+            total_denials: 0,
+            usage_histogram: [0; USAGE_HISTOGRAM_BUCKETS],
+        }
+    }
+
+    #[test]
+    fn breaker_trips_once_the_threshold_is_exceeded() {
+        let at_threshold = count_rejection(&breaker(3, 60, START, 2), START + 10).unwrap();
+        assert_eq!(at_threshold, BreakerCount { window_start: START, count: 3, tripped: false });
+        let over = count_rejection(&breaker(3, 60, START, 3), START + 59).unwrap();
+        assert_eq!(over, BreakerCount { window_start: START, count: 4, tripped: true });
+    }
+
+    #[test]
+    fn breaker_window_starts_over_after_its_length() {
+        let fresh = count_rejection(&breaker(3, 60, START, 3), START + 60).unwrap();
+        assert_eq!(fresh, BreakerCount { window_start: START + 60, count: 1, tripped: false });
+        // A clock that went backwards also opens a new window
+        let rewound = count_rejection(&breaker(3, 60, START, 3), START - 1).unwrap();
+        assert_eq!(rewound.count, 1);
+    }
+
+    #[test]
+    fn breaker_stays_tripped_until_its_count_starts_over() {
+        assert!(!breaker(3, 60, START, 3).circuit_tripped());
+        // However long ago the window opened
+        assert!(breaker(3, 60, START, 4).circuit_tripped());
+        assert!(!breaker(3, 60, 0, 0).circuit_tripped());
+        assert!(!breaker(0, 60, START, 4).circuit_tripped());
+    }
+
+    #[test]
+    fn breaker_is_off_when_either_setting_is_zero() {
+        assert!(count_rejection(&breaker(0, 60, START, 100), START).is_none());
+        assert!(count_rejection(&breaker(3, 0, START, 100), START).is_none());
+    }
+
+    fn throttled(max_actions: u32, window_seconds: i64, window_start: i64, count: u32) -> GlobalConfig {
+        let mut c = config(10, 60, 15);
+        c.admin_action_window_seconds = window_seconds;
+        c.max_admin_actions_per_window = max_actions;
+        c.admin_action_window_start = window_start;
+        c.admin_actions_in_window = count;
+        c
+    }
+
+    #[test]
+    fn admin_actions_past_the_cap_are_throttled() {
+        let first = count_admin_action(&throttled(2, 60, 0, 0), START).unwrap();
+        assert_eq!(first, AdminActionCount { window_start: START, count: 1, throttled: false });
+        let at_cap = count_admin_action(&throttled(2, 60, START, 1), START + 30).unwrap();
+        assert_eq!(at_cap, AdminActionCount { window_start: START, count: 2, throttled: false });
+        let over = count_admin_action(&throttled(2, 60, START, 2), START + 59).unwrap();
+        assert_eq!(over, AdminActionCount { window_start: START, count: 3, throttled: true });
+    }
+
+    #[test]
+    fn admin_throttle_window_rolls_over() {
+        let fresh = count_admin_action(&throttled(2, 60, START, 2), START + 60).unwrap();
+        assert_eq!(fresh, AdminActionCount { window_start: START + 60, count: 1, throttled: false });
+    }
+
+    #[test]
+    fn admin_throttle_is_off_when_either_setting_is_zero() {
+        assert!(count_admin_action(&throttled(0, 60, START, 100), START).is_none());
+        assert!(count_admin_action(&throttled(2, 0, START, 100), START).is_none());
+        assert!(count_admin_action(&config(10, 60, 15), START).is_none());
+    }
+
+    fn policy(quiet_streak: u8) -> RebalancePolicy {
+        RebalancePolicy {
+            params: RebalanceParams {
+                step: 10,
+                floor: 20,
+                ceiling: 50,
+                target_rejection_bps: 1_000,
+                quiet_rejection_bps: 100,
+                quiet_intervals: 3,
+                min_interval_seconds: 60,
+            },
+            last_rebalance_at: 0,
+            requests_mark: 0,
+            denials_mark: 0,
+            quiet_streak,
+            bump: 0,
+        }
+    }
+
+    fn interval(accepted: u64, denied: u64) -> GlobalStats {
+        let mut stats = breaker(0, 0, 0, 0);
+        stats.total_requests = accepted;
+        stats.total_denials = denied;
+        stats
+    }
+
+    #[test]
+    fn rebalance_loosens_up_to_the_ceiling_while_denials_run_high() {
+        let mut c = config(30, 60, 30);
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            let outcome = rebalance(&policy(0), &interval(80, 20), &c);
+            assert_eq!(outcome.rejection_bps, 2_000);
+            c.max_requests = outcome.max_requests;
+            c.burst_limit = outcome.burst_limit;
+            seen.push(c.max_requests);
+        }
+        assert_eq!(seen, [40, 50, 50, 50]);
+        // The burst allowance is lifted with it
+        assert_eq!(c.burst_limit, 50);
+    }
+
+    #[test]
+    fn rebalance_tightens_after_enough_quiet_intervals() {
+        let c = config(30, 60, 45);
+        let quiet = interval(1_000, 1);
+        let outcome = rebalance(&policy(0), &quiet, &c);
+        assert_eq!((outcome.max_requests, outcome.quiet_streak), (30, 1));
+        let outcome = rebalance(&policy(2), &quiet, &c);
+        assert_eq!((outcome.max_requests, outcome.quiet_streak), (20, 0));
+        // A larger burst allowance is left alone
+        assert_eq!(outcome.burst_limit, 45);
+        // Never below the floor
+        let outcome = rebalance(&policy(2), &quiet, &config(25, 60, 25));
+        assert_eq!(outcome.max_requests, 20);
+        let outcome = rebalance(&policy(2), &quiet, &config(20, 60, 20));
+        assert_eq!(outcome.max_requests, 20);
+    }
+
+    #[test]
+    fn rebalance_holds_and_ends_the_streak_between_the_thresholds() {
+        let c = config(30, 60, 30);
+        let outcome = rebalance(&policy(2), &interval(95, 5), &c);
+        assert_eq!((outcome.max_requests, outcome.quiet_streak), (30, 0));
+        // Exactly at target is not over it
+        let outcome = rebalance(&policy(0), &interval(90, 10), &c);
+        assert_eq!(outcome.max_requests, 30);
+    }
+
+    #[test]
+    fn rebalance_counts_an_idle_interval_as_quiet() {
+        let outcome = rebalance(&policy(0), &interval(0, 0), &config(30, 60, 30));
+        assert_eq!((outcome.rejection_bps, outcome.quiet_streak), (0, 1));
+    }
+
+    #[test]
+    fn rebalance_only_counts_since_the_marks() {
+        let mut p = policy(0);
+        p.requests_mark = 500;
+        p.denials_mark = 100;
+        let outcome = rebalance(&p, &interval(590, 110), &config(30, 60, 30));
+        assert_eq!((outcome.accepted, outcome.denied), (90, 10));
+        assert_eq!(outcome.rejection_bps, 1_000);
+    }
+
+    #[test]
+    fn rebalance_never_pulls_an_out_of_bounds_limit_the_wrong_way() {
+        // Above the ceiling with denials high: held, not cut to the ceiling
+        let outcome = rebalance(&policy(0), &interval(50, 50), &config(80, 60, 80));
+        assert_eq!(outcome.max_requests, 80);
+        // Below the floor and quiet: held, not raised to the floor
+        let outcome = rebalance(&policy(2), &interval(10, 0), &config(5, 60, 5));
+        assert_eq!(outcome.max_requests, 5);
+    }
+
+    #[test]
+    fn registration_cooldown_is_per_last_registration() {
+        let mut c = config(10, 60, 20);
+        assert!(registration_allowed(&c, START, START));
+
+        c.registration_cooldown_seconds = 30;
+        assert!(registration_allowed(&c, 0, START));
+        assert!(!registration_allowed(&c, START, START + 29));
+        assert!(registration_allowed(&c, START, START + 30));
+        // A clock behind the last registration waits too
+        assert!(!registration_allowed(&c, START, START - 5));
+    }
+
+    #[test]
+    fn payer_cap_counts_every_funded_registration() {
+        let mut c = config(10, 60, 20);
+        assert!(payer_under_cap(&c, u32::MAX));
+
+        c.max_buckets_per_payer = 2;
+        assert!(payer_under_cap(&c, 0));
+        assert!(payer_under_cap(&c, 1));
+        assert!(!payer_under_cap(&c, 2));
+        assert!(!payer_under_cap(&c, 3));
+    }
+
+    #[test]
+    fn appeals_wait_out_the_cooldown_after_a_denial() {
+        let mut c = config(10, 60, 20);
+        assert!(appeal_allowed(&c, START, START));
+
+        c.appeal_cooldown_seconds = 3_600;
+        assert!(appeal_allowed(&c, 0, START));
+        assert!(!appeal_allowed(&c, START, START + 3_599));
+        assert!(appeal_allowed(&c, START, START + 3_600));
+    }
+
+    fn violated(violations: u64, updated_at: i64) -> ClientBucket {
+        ClientBucket { violations, violations_updated_at: updated_at, ..bucket(0, 0) }
+    }
+
+    #[test]
+    fn violations_halve_once_per_whole_halflife() {
+        let mut c = config(10, 60, 20);
+        c.violation_halflife_seconds = 100;
+        let b = violated(40, START);
+        let schedule: Vec<u64> =
+            [0, 99, 100, 199, 200, 300, 400, 550].iter().map(|t| decayed_violations(&b, &c, START + t).0).collect();
+        assert_eq!(schedule, [40, 40, 20, 20, 10, 5, 2, 1]);
+        assert_eq!(decayed_violations(&b, &c, START + 64 * 100).0, 0);
+        assert_eq!(decayed_violations(&b, &c, i64::MAX).0, 0);
+        // The time runs on from the last whole half-life, not from the read
+        assert_eq!(decayed_violations(&b, &c, START + 250), (10, START + 200));
+    }
+
+    #[test]
+    fn violations_keep_without_a_halflife_or_a_stamp() {
+        let mut c = config(10, 60, 20);
+        assert_eq!(decayed_violations(&violated(40, START), &c, START + 1_000_000).0, 40);
+        c.violation_halflife_seconds = 100;
+        assert_eq!(decayed_violations(&violated(40, 0), &c, START + 1_000_000).0, 40);
+        // A clock behind the stamp decays nothing
+        assert_eq!(decayed_violations(&violated(40, START), &c, START - 500), (40, START));
+    }
+
+    #[test]
+    fn new_violations_add_to_the_decayed_count() {
+        let mut c = config(10, 60, 20);
+        c.violation_halflife_seconds = 100;
+        let mut b = violated(0, 0);
+        add_violation(&mut b, &c, START);
+        assert_eq!((b.violations, b.violations_updated_at), (1, START));
+        for t in 1..4 {
+            add_violation(&mut b, &c, START + t);
+        }
+        assert_eq!((b.violations, b.violations_updated_at), (4, START));
+
+        // Halved twice, then the new one joins the running half-life
+        add_violation(&mut b, &c, START + 250);
+        assert_eq!((b.violations, b.violations_updated_at), (2, START + 200));
+        // What the view shows is what the next violation builds on
+        assert_eq!(decayed_violations(&b, &c, START + 300).0, 1);
+        add_violation(&mut b, &c, START + 300);
+        assert_eq!(b.violations, 2);
+
+        // Fully decayed, the next violation starts over from now
+        add_violation(&mut b, &c, START + 10_000);
+        assert_eq!((b.violations, b.violations_updated_at), (1, START + 10_000));
+        // An unstamped migrated count gets its stamp from the first new violation
+        let mut migrated = violated(9, 0);
+        add_violation(&mut migrated, &c, START);
+        assert_eq!((migrated.violations, migrated.violations_updated_at), (10, START));
+    }
+
+    #[test]
+    fn low_balances_are_refused_unless_the_bucket_is_exempt() {
+        let mut c = config(10, 60, 20);
+        let mut b = bucket(0, 0);
+        assert!(balance_sufficient(&b, &c, 0));
+
+        c.min_client_balance_lamports = 1_000;
+        assert!(balance_sufficient(&b, &c, 1_000));
+        assert!(!balance_sufficient(&b, &c, 999));
+        b.set_balance_exempt(true);
+        assert!(balance_sufficient(&b, &c, 0));
+        b.set_balance_exempt(false);
+        b.set_sponsored(true);
+        assert!(balance_sufficient(&b, &c, 0));
+    }
+
+    #[test]
+    fn violations_are_forgiven_only_past_the_idle_threshold() {
+        let mut c = config(10, 60, 20);
+        let b = violated(5, START);
+        assert!(!penalties_forgivable(&b, &c, START + 1_000_000));
+
+        c.inactivity_forgiveness_seconds = 1_000;
+        assert!(!penalties_forgivable(&b, &c, START + 1_000));
+        assert!(penalties_forgivable(&b, &c, START + 1_001));
+        // Nothing to forgive on a clean bucket, and a clock behind the last consume is not idle time
+        assert!(!penalties_forgivable(&violated(0, 0), &c, START + 1_001));
+        assert!(!penalties_forgivable(&b, &c, START - 5_000));
+        let fast = ClientBucket { last_request_ts: 0, ..b };
+        assert!(!penalties_forgivable(&fast, &c, START + 1_001));
+    }
+
+    #[test]
+    fn impact_counts_windows_over_the_new_max() {
+        assert!(!exceeds_max(&bucket(5, 0), 5));
+        assert!(exceeds_max(&bucket(6, 0), 5));
+        let mut blocked = bucket(9, 0);
+        blocked.set_blocked(true);
+        assert!(!exceeds_max(&blocked, 5));
+    }
+
+    #[test]
+    fn only_idle_unblocked_buckets_are_collectable() {
+        let mut c = config(10, 60, 20);
+        let mut b = bucket(0, 0);
+        // Collection is off until a threshold is set
+        assert!(!gc_collectable(&b, &c, i64::MAX));
+
+        c.stale_after_seconds = 86_400;
+        assert!(!gc_collectable(&b, &c, START + 86_399));
+        assert!(gc_collectable(&b, &c, START + 86_400));
+        b.set_blocked(true);
+        assert!(!gc_collectable(&b, &c, START + 86_400));
+    }
+
+    #[test]
+    fn buckets_are_recoverable_only_after_the_idle_period() {
+        let mut c = config(4, 60, 8);
+        let mut b = bucket(0, 0);
+        b.recovery_delegate = Pubkey::new_unique();
+        // Off by default
+        assert!(!bucket_recoverable(&b, &c, START + 1_000_000));
+
+        c.recovery_inactive_seconds = 3_600;
+        assert!(!bucket_recoverable(&b, &c, START + 3_599));
+        assert!(bucket_recoverable(&b, &c, START + 3_600));
+        // A consume restarts the clock
+        let consumed = consume(&b, &c, START + 3_000, 1).unwrap();
+        assert!(!bucket_recoverable(&consumed, &c, START + 3_600));
+        assert!(bucket_recoverable(&consumed, &c, START + 6_600));
+
+        b.recovery_delegate = Pubkey::default();
+        assert!(!bucket_recoverable(&b, &c, START + 3_600));
+    }
+
+    #[test]
+    fn gc_bounty_splits_the_rent() {
+        assert_eq!(gc_bounty(1_000_000, 0), 0);
+        assert_eq!(gc_bounty(1_000_000, 500), 50_000);
+        assert_eq!(gc_bounty(999, 5_000), 499);
+        assert_eq!(gc_bounty(u64::MAX, 10_000), u64::MAX);
+        // Out-of-range shares are capped at the whole amount
+        assert_eq!(gc_bounty(1_000, u16::MAX), 1_000);
+    }
+
+    #[test]
+    fn rent_shortfall_covers_exactly_the_growth() {
+        let rent = Rent::default();
+        let (old, new) = (140, 156);
+        let funded = rent.minimum_balance(old);
+        assert_eq!(rent_shortfall(&rent, new, funded), rent.minimum_balance(new) - funded);
+        assert_eq!(rent_shortfall(&rent, new, funded + rent_shortfall(&rent, new, funded)), 0);
+        // An over-funded account is owed nothing back and pays nothing more
+        assert_eq!(rent_shortfall(&rent, old, funded + 1_000), 0);
+    }
+
+    #[test]
+    fn registration_previews_follow_register_client_s_checks() {
+        let rent = Rent::default();
+        let mut c = fed(15, 5, 50, START + 10);
+        c.registration_fee_lamports = 5_000;
+        c.registration_cooldown_seconds = 60;
+        c.max_buckets_per_payer = 2;
+        let bucket_rent = rent.minimum_balance(8 + ClientBucket::INIT_SPACE);
+        let meta = |buckets_created, last_registration_ts| PayerMeta {
+            payer: Pubkey::new_unique(),
+            last_registration_ts,
+            bump: 255,
+            buckets_created,
+        };
+
+        let first = registration_preview(&c, &[], START, None, false, false, &rent);
+        assert_eq!((first.max_requests, first.window_seconds, first.burst_limit), (15, 60, 20));
+        assert_eq!(first.registration_fee_lamports, 5_000);
+        assert_eq!(first.rent_lamports, bucket_rent + rent.minimum_balance(8 + PayerMeta::INIT_SPACE));
+        assert!(first.registration_open && first.wallet_consumes_allowed && !first.requires_allowlist);
+        // Past the feeder's push the base limits apply again
+        assert_eq!(registration_preview(&c, &[], START + 10, None, false, false, &rent).max_requests, 10);
+
+        // A payer that has registered before already has its PayerMeta
+        let again = registration_preview(&c, &[], START, Some(&meta(1, START - 60)), false, false, &rent);
+        assert_eq!(again.rent_lamports, bucket_rent);
+        assert!(again.registration_open);
+        let closed = [
+            registration_preview(&c, &[], START, Some(&meta(1, START - 59)), false, false, &rent),
+            registration_preview(&c, &[], START, Some(&meta(2, 0)), false, false, &rent),
+            registration_preview(&c, &[], START, None, true, false, &rent),
+        ];
+        assert!(closed.iter().all(|preview| !preview.registration_open));
+        c.set_paused(true);
+        assert!(!registration_preview(&c, &[], START, None, false, false, &rent).registration_open);
+        c.set_paused(false);
+
+        let program = registration_preview(&c, &[], START, None, false, true, &rent);
+        assert!(program.requires_allowlist && program.registration_open);
+        c.set_program_keyed(true);
+        c.set_wallet_fallback(true);
+        assert!(!registration_preview(&c, &[], START, None, false, true, &rent).wallet_consumes_allowed);
+        assert!(registration_preview(&c, &[], START, None, false, false, &rent).wallet_consumes_allowed);
+    }
+
+    fn tiers() -> [DiscountTier; MAX_DISCOUNT_TIERS] {
+        [
+            DiscountTier { min_amount: 10, discount_bps: 500 },
+            DiscountTier { min_amount: 100, discount_bps: 1_000 },
+            DiscountTier { min_amount: 500, discount_bps: 2_000 },
+            DiscountTier { min_amount: 1_000, discount_bps: 2_500 },
+        ]
+    }
+
+    #[test]
+    fn discount_tiers_apply_from_their_min_amount() {
+        let t = tiers();
+        assert_eq!(discounted_amount(&t, 9), 9);
+        assert_eq!(discounted_amount(&t, 10), 10); // 5% of 10 rounds down to nothing
+        assert_eq!(discounted_amount(&t, 99), 95);
+        assert_eq!(discounted_amount(&t, 100), 90);
+        assert_eq!(discounted_amount(&t, 499), 450);
+        assert_eq!(discounted_amount(&t, 500), 400);
+        assert_eq!(discounted_amount(&t, 1_000), 750);
+        // Only the configured rows count
+        assert_eq!(discounted_amount(&t[..2], 1_000), 900);
+        assert_eq!(discounted_amount(&[], 1_000), 1_000);
+    }
+
+    #[test]
+    fn discounts_round_in_the_limiter_favour() {
+        let t = [DiscountTier { min_amount: 2, discount_bps: 3_333 }];
+        assert_eq!(discounted_amount(&t, 2), 2);
+        assert_eq!(discounted_amount(&t, 3), 3);
+        assert_eq!(discounted_amount(&t, 4), 3);
+        assert_eq!(discounted_amount(&t, 10_000), 6_667);
+        // The steepest allowed discount still leaves a unit to pay: 2 units less
+        // floor(1.9998) is 1
+        let steep = [DiscountTier { min_amount: 2, discount_bps: 9_999 }];
+        assert_eq!(discounted_amount(&steep, 2), 1);
+        assert_eq!(discounted_amount(&steep, 10_000), 1);
+        // The product is taken in u128
+        assert_eq!(discounted_amount(&steep, u64::MAX), 1_844_674_407_370_956);
+    }
+
+    #[test]
+    fn recovery_finalizes_only_after_the_eta() {
+        let mut c = config(10, 60, 20);
+        assert!(!recovery_finalizable(&c, i64::MAX));
+
+        c.recovery_admin = Pubkey::new_unique();
+        c.recovery_eta = START + ADMIN_RECOVERY_DELAY;
+        assert!(!recovery_finalizable(&c, START));
+        assert!(!recovery_finalizable(&c, START + ADMIN_RECOVERY_DELAY - 1));
+        assert!(recovery_finalizable(&c, START + ADMIN_RECOVERY_DELAY));
+        assert!(recovery_finalizable(&c, START + 30 * ADMIN_RECOVERY_DELAY));
+    }
+
+    #[test]
+    fn simulation_follows_the_schedule_and_caller_mode() {
+        let mut c = config(10, 60, 10);
+        let b = bucket(4, 0);
+        let quota = simulate_consume(&c, &b, START + 20);
+        assert_eq!(quota, QuotaInfo { allowed: true, rejection: None, remaining: 6, resets_in: 40 });
+
+        // A schedule capping the window at 4 leaves nothing
+        c.set_scheduled(true);
+        let entries = [ScheduleEntry { start_second_of_day: 0, max_requests: 4 }];
+        let quota = simulate_scheduled_consume(&c, &entries, &b, START + 20);
+        assert_eq!(quota.remaining, 0);
+        assert_eq!(quota.rejection, Some(Rejection::RateLimit));
+
+        c.set_scheduled(false);
+        c.set_program_keyed(true);
+        let quota = simulate_consume(&c, &b, START + 20);
+        assert!(!quota.allowed);
+        assert_eq!(quota.rejection, None);
+    }
+
+    // The simulator's answer is the handler's decision: `remaining` unit consumes
+    // go through back to back, and the next one is refused for the reason it named
+    mod simulation {
+        use super::*;
+        use proptest::prelude::*;
+
+        proptest! {
+            #[test]
+            fn remaining_is_what_consumes_admit(
+                max in 1u64..20,
+                extra_burst in 0u64..20,
+                window in 1i64..120,
+                count in 0u64..40,
+                credits in 0u64..20,
+                stale in any::<bool>(),
+                blocked in any::<bool>(),
+                elapsed in -30i64..400,
+            ) {
+                let mut c = config(max, window, max + extra_burst);
+                c.config_epoch = stale as u64;
+                let mut b = bucket(count, credits);
+                b.set_blocked(blocked);
+                let now = START + elapsed;
+
+                let quota = simulate_consume(&c, &b, now);
+                let mut admitted = 0;
+                let mut next = b.clone();
+                let refused = loop {
+                    match consume(&next, &c, now, 1) {
+                        Ok(after) => {
+                            next = after;
+                            admitted += 1;
+                        }
+                        Err(rejection) => break rejection,
+                    }
+                };
+                prop_assert_eq!(quota.allowed, admitted > 0);
+                prop_assert_eq!(quota.remaining, admitted);
+                if !quota.allowed {
+                    prop_assert_eq!(quota.rejection, Some(refused));
+                }
+            }
+        }
+    }
+
+    // Random traffic against one bucket: consumes of a few units at random gaps,
+    // with the odd admin reset, under any valid config. Failing cases shrink to the
+    // shortest sequence and are kept in proptest-regressions/ so they rerun first.
+    mod sequences {
+        use super::*;
+        use crate::constants::INVARIANT_OK;
+        use crate::invariants::first_violation;
+        use crate::validation::validate_discount_tiers;
+        use proptest::collection::{btree_set, vec};
+        use proptest::prelude::*;
+
+        #[derive(Clone, Debug)]
+        enum Op {
+            Consume { after: i64, amount: u64 },
+            Reset { after: i64 },
+        }
+
+        fn op() -> impl Strategy<Value = Op> {
+            prop_oneof![
+                8 => (0i64..90, 1u64..6).prop_map(|(after, amount)| Op::Consume { after, amount }),
+                1 => (0i64..90).prop_map(|after| Op::Reset { after }),
+            ]
+        }
+
+        fn tiers() -> impl Strategy<Value = Vec<DiscountTier>> {
+            // Both sides ascending and distinct, which is what set_discount_tiers accepts
+            (btree_set(2u64..1_000, 0..=MAX_DISCOUNT_TIERS), btree_set(1u16..10_000, MAX_DISCOUNT_TIERS)).prop_map(
+                |(mins, bps)| {
+                    mins.into_iter()
+                        .zip(bps)
+                        .map(|(min_amount, discount_bps)| DiscountTier { min_amount, discount_bps })
+                        .collect()
+                },
+            )
+        }
+
+        proptest! {
+            #[test]
+            fn traffic_stays_within_the_limits(
+                max in 1u64..10,
+                extra_burst in 0u64..10,
+                window in 1i64..120,
+                ops in vec(op(), 1..80),
+            ) {
+                let c = config(max, window, max + extra_burst);
+                let mut b = bucket(0, 0);
+                let mut now = START;
+                let mut accepted = 0u64;
+                // Units accepted since the current window opened
+                let mut in_window = 0u64;
+
+                for op in ops {
+                    match op {
+                        Op::Consume { after, amount } => {
+                            now += after;
+                            match apply_consume(&b, &c, now, amount) {
+                                Ok(consumed) => {
+                                    if consumed.bucket.window_start != b.window_start {
+                                        in_window = 0;
+                                    }
+                                    b = consumed.bucket;
+                                    add_to_total(&mut b, amount);
+                                    accepted += amount;
+                                    in_window += amount;
+                                }
+                                Err(rejection) => prop_assert!(
+                                    matches!(rejection, Rejection::RateLimit | Rejection::BurstLimit),
+                                    "{:?}",
+                                    rejection
+                                ),
+                            }
+                        }
+                        Op::Reset { after } => {
+                            now += after;
+                            reset_window(&mut b, &c, now);
+                            in_window = 0;
+                        }
+                    }
+
+                    prop_assert_eq!(b.request_count, in_window);
+                    prop_assert!(b.request_count <= c.burst_limit);
+                    // Past max_requests a window only runs on credits, and at most one
+                    // window's worth is ever banked
+                    prop_assert!(b.burst_credits <= max);
+                    prop_assert!(in_window <= 2 * max);
+                    prop_assert_eq!(b.total_requests, accepted);
+                    prop_assert_eq!(first_violation(&b, &c), INVARIANT_OK);
+                }
+            }
+
+            #[test]
+            fn unused_allowance_is_banked_at_rollover(
+                max in 1u64..20,
+                window in 1i64..120,
+                used in 0u64..20,
+                credits in 0u64..20,
+                windows_idle in 1i64..5,
+            ) {
+                let c = config(max, window, max * 3);
+                let b = bucket(used.min(max), credits.min(max));
+                let consumed = apply_consume(&b, &c, START + window * windows_idle, 0).unwrap();
+                let skipped = windows_idle as u64 - 1;
+                let expected = (b.burst_credits + (max - b.request_count) + max * skipped).min(max);
+                prop_assert_eq!(consumed.bucket.burst_credits, expected);
+                prop_assert_eq!(consumed.rollover.map(|r| r.skipped_windows), Some(skipped));
+            }
+
+            // Anything the validated config and the stored bucket can hold, together
+            // with any clock reading: decisions come back as Ok or a Rejection, never
+            // a panic, and an accepted window stays under its ceiling
+            #[test]
+            fn extreme_inputs_never_panic(
+                max in 1u64..=u64::MAX,
+                extra_burst in any::<u64>(),
+                window in 1i64..=i64::MAX,
+                count in any::<u64>(),
+                credits in any::<u64>(),
+                window_start in any::<i64>(),
+                now in any::<i64>(),
+                amount in any::<u64>(),
+                epoch in any::<u64>(),
+            ) {
+                let mut c = config(max, window, max.saturating_add(extra_burst));
+                c.config_epoch = epoch;
+                let mut b = bucket(count, credits);
+                b.window_start = window_start;
+                b.last_request_ts = window_start;
+
+                if let Ok(consumed) = apply_consume(&b, &c, now, amount) {
+                    prop_assert!(consumed.bucket.request_count <= c.burst_limit);
+                }
+                let _ = apply_priority_consume(&b, &c, now, true);
+                let _ = escrow_quota(&b, &c, now, amount);
+                let _ = remaining_capacity(&b, &c);
+                let _ = simulate_consume(&c, &b, now);
+                let (remaining, resets_in) = quota_at(&b, &c, now);
+                prop_assert!(remaining <= c.burst_limit);
+                prop_assert!((0..=window).contains(&resets_in));
+                b.total_requests = count;
+                add_to_total(&mut b, amount);
+                prop_assert_eq!(b.total_requests, count.saturating_add(amount));
+                prop_assert_eq!(b.total_saturated(), count.checked_add(amount).is_none());
+            }
+
+            #[test]
+            fn discounts_never_charge_more_or_nothing(tiers in tiers(), amount in 1u64..=u64::MAX) {
+                prop_assert!(validate_discount_tiers(&tiers).is_ok());
+                let charged = discounted_amount(&tiers, amount);
+                prop_assert!(charged >= 1 && charged <= amount);
+            }
+
+            #[test]
+            fn violations_only_decay_over_time(
+                violations in any::<u64>(),
+                halflife in 0u32..10_000,
+                since in 0i64..1_000_000,
+                first in 0i64..1_000_000,
+                later in 0i64..1_000_000,
+            ) {
+                let mut c = config(10, 60, 20);
+                c.violation_halflife_seconds = halflife;
+                let b = ClientBucket { violations, violations_updated_at: since, ..bucket(0, 0) };
+                let (at_first, from) = decayed_violations(&b, &c, since + first);
+                let at_later = decayed_violations(&b, &c, since + first + later).0;
+                prop_assert!(at_later <= at_first && at_first <= violations);
+                // Reading in between changes nothing
+                let reread = ClientBucket { violations: at_first, violations_updated_at: from, ..b };
+                prop_assert_eq!(decayed_violations(&reread, &c, since + first + later).0, at_later);
+            }
+
+            #[test]
+            fn gc_bounty_is_a_share_of_the_lamports(lamports in any::<u64>(), bps in any::<u16>()) {
+                let bounty = gc_bounty(lamports, bps);
+                prop_assert!(bounty <= lamports);
+                if bps as u64 >= BPS_DENOMINATOR {
+                    prop_assert_eq!(bounty, lamports);
+                }
+            }
+
+            #[test]
+            fn rebalancing_keeps_the_limits_in_bounds(
+                start in 20u64..=50,
+                intervals in vec((0u64..1_000, 0u64..200), 1..30),
+            ) {
+                let mut p = policy(0);
+                let mut c = config(start, 60, start);
+                let mut stats = interval(0, 0);
+                for (accepted, denied) in intervals {
+                    stats.total_requests += accepted;
+                    stats.total_denials += denied;
+                    let outcome = rebalance(&p, &stats, &c);
+                    c.max_requests = outcome.max_requests;
+                    c.burst_limit = outcome.burst_limit;
+                    p.requests_mark = stats.total_requests;
+                    p.denials_mark = stats.total_denials;
+                    p.quiet_streak = outcome.quiet_streak;
+                    prop_assert!((p.params.floor..=p.params.ceiling).contains(&c.max_requests));
+                    prop_assert!(c.burst_limit >= c.max_requests);
+                    prop_assert!(p.quiet_streak < p.params.quiet_intervals);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use anchor_lang::prelude::*;
+
+// Unsigned fixed-point number with nine decimal places, for per-second refill and
+// decay rates that plain integer division would truncate by up to a whole token per
+// call. The raw value is a u128 so any u64 integer part plus a fraction fits, and
+// every operation is checked: None means the result does not fit.
+
+pub const SCALE: u128 = 1_000_000_000;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FixedPoint {
+    raw: u128,
+}
+
+impl FixedPoint {
+    pub const ZERO: Self = Self { raw: 0 };
+    pub const ONE: Self = Self { raw: SCALE };
+
+    pub const fn from_raw(raw: u128) -> Self {
+        Self { raw }
+    }
+
+    pub const fn raw(self) -> u128 {
+        self.raw
+    }
+
+    pub const fn from_int(value: u64) -> Self {
+        Self { raw: value as u128 * SCALE }
+    }
+
+    // num / den, truncated to nine decimal places
+    pub fn from_ratio(num: u64, den: u64) -> Option<Self> {
+        if den == 0 {
+            return None;
+        }
+        Some(Self { raw: num as u128 * SCALE / den as u128 })
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.raw.checked_add(other.raw).map(Self::from_raw)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.raw.checked_sub(other.raw).map(Self::from_raw)
+    }
+
+    // Split self into whole and fractional parts so the intermediate product only
+    // overflows when the result itself would
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        let whole = (self.raw / SCALE).checked_mul(other.raw)?;
+        let frac = (self.raw % SCALE).checked_mul(other.raw)? / SCALE;
+        whole.checked_add(frac).map(Self::from_raw)
+    }
+
+    pub fn checked_mul_int(self, value: u64) -> Option<Self> {
+        self.raw.checked_mul(value as u128).map(Self::from_raw)
+    }
+
+    // Long division: quotient digits first, then the remainder scaled, so large
+    // dividends do not overflow the way raw * SCALE would
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        if other.raw == 0 {
+            return None;
+        }
+        let whole = (self.raw / other.raw).checked_mul(SCALE)?;
+        let frac = (self.raw % other.raw).checked_mul(SCALE)? / other.raw;
+        whole.checked_add(frac).map(Self::from_raw)
+    }
+
+    pub fn checked_div_int(self, value: u64) -> Option<Self> {
+        if value == 0 {
+            return None;
+        }
+        Some(Self { raw: self.raw / value as u128 })
+    }
+
+    // Whole units, or None if they do not fit in a u64
+    pub fn floor(self) -> Option<u64> {
+        u64::try_from(self.raw / SCALE).ok()
+    }
+
+    pub fn fract(self) -> Self {
+        Self { raw: self.raw % SCALE }
+    }
+
+    // Whole units to spend now plus the remainder to carry forward, so repeated
+    // refills never lose the fractional part
+    pub fn split(self) -> Option<(u64, Self)> {
+        Some((self.floor()?, self.fract()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn to_f64(value: FixedPoint) -> f64 {
+        value.raw() as f64 / SCALE as f64
+    }
+
+    fn fp(value: f64) -> FixedPoint {
+        FixedPoint::from_raw((value * SCALE as f64).round() as u128)
+    }
+
+    // Each truncating op can lose one raw unit; allow a few plus f64 rounding
+    fn close(actual: FixedPoint, expected: f64) -> bool {
+        (to_f64(actual) - expected).abs() <= 4e-9 + expected.abs() * 1e-12
+    }
+
+    proptest! {
+        #[test]
+        fn add_matches_f64(a in 0.0f64..1e6, b in 0.0f64..1e6) {
+            let sum = fp(a).checked_add(fp(b)).unwrap();
+            prop_assert!(close(sum, a + b));
+        }
+
+        #[test]
+        fn sub_matches_f64_or_fails_below_zero(a in 0.0f64..1e6, b in 0.0f64..1e6) {
+            match fp(a).checked_sub(fp(b)) {
+                Some(diff) => prop_assert!(close(diff, a - b)),
+                None => prop_assert!(fp(a) < fp(b)),
+            }
+        }
+
+        #[test]
+        fn mul_matches_f64(a in 0.0f64..1e6, b in 0.0f64..1e3) {
+            let product = fp(a).checked_mul(fp(b)).unwrap();
+            prop_assert!(close(product, to_f64(fp(a)) * to_f64(fp(b))));
+        }
+
+        #[test]
+        fn div_matches_f64(a in 0.0f64..1e6, b in 1e-3f64..1e3) {
+            let quotient = fp(a).checked_div(fp(b)).unwrap();
+            prop_assert!(close(quotient, to_f64(fp(a)) / to_f64(fp(b))));
+        }
+
+        #[test]
+        fn ratio_matches_f64(num in 0u64..1_000_000_000, den in 1u64..1_000_000) {
+            let ratio = FixedPoint::from_ratio(num, den).unwrap();
+            prop_assert!(close(ratio, num as f64 / den as f64));
+        }
+
+        #[test]
+        fn split_round_trips(raw in 0u128..(u64::MAX as u128 * SCALE)) {
+            let value = FixedPoint::from_raw(raw);
+            let (whole, rest) = value.split().unwrap();
+            prop_assert!(rest < FixedPoint::ONE);
+            prop_assert_eq!(FixedPoint::from_int(whole).checked_add(rest), Some(value));
+        }
+
+        #[test]
+        fn carried_remainders_never_lose_tokens(rate_num in 1u64..1_000, rate_den in 1u64..1_000, steps in 1usize..200) {
+            // Refilling in `steps` one-second increments must hand out exactly as many
+            // whole tokens as refilling all the seconds at once
+            let rate = FixedPoint::from_ratio(rate_num, rate_den).unwrap();
+            let mut carry = FixedPoint::ZERO;
+            let mut handed_out = 0u64;
+            for _ in 0..steps {
+                let (whole, rest) = carry.checked_add(rate).unwrap().split().unwrap();
+                handed_out += whole;
+                carry = rest;
+            }
+            let at_once = rate.checked_mul_int(steps as u64).unwrap().floor().unwrap();
+            prop_assert_eq!(handed_out, at_once);
+        }
+    }
+
+    #[test]
+    fn overflow_adjacent_magnitudes() {
+        let max_int = FixedPoint::from_int(u64::MAX);
+        assert_eq!(max_int.floor(), Some(u64::MAX));
+        assert_eq!(max_int.checked_mul(FixedPoint::ONE), Some(max_int));
+        assert_eq!(max_int.checked_div(FixedPoint::ONE), Some(max_int));
+        assert!(max_int.checked_mul_int(1_000_000).is_some());
+        assert!(max_int.checked_mul_int(u64::MAX).is_none());
+        assert_eq!(max_int.checked_add(FixedPoint::ONE).unwrap().floor(), None);
+
+        let huge = FixedPoint::from_raw(u128::MAX);
+        assert_eq!(huge.checked_add(FixedPoint::from_raw(1)), None);
+        assert_eq!(huge.checked_mul(FixedPoint::from_int(2)), None);
+        assert_eq!(huge.checked_div(FixedPoint::from_ratio(1, 2).unwrap()), None);
+        assert_eq!(huge.checked_div(FixedPoint::ONE), Some(huge));
+        assert_eq!(FixedPoint::ZERO.checked_sub(FixedPoint::from_raw(1)), None);
+    }
+
+    #[test]
+    fn division_by_zero_is_none() {
+        assert_eq!(FixedPoint::from_ratio(1, 0), None);
+        assert_eq!(FixedPoint::ONE.checked_div(FixedPoint::ZERO), None);
+        assert_eq!(FixedPoint::ONE.checked_div_int(0), None);
+    }
+
+    #[test]
+    fn truncates_toward_zero() {
+        let third = FixedPoint::from_ratio(1, 3).unwrap();
+        assert_eq!(third.raw(), 333_333_333);
+        assert_eq!(third.checked_mul_int(3).unwrap().floor(), Some(0));
+        assert_eq!(FixedPoint::from_ratio(7, 2).unwrap().split(), Some((3, FixedPoint::from_ratio(1, 2).unwrap())));
+    }
+}
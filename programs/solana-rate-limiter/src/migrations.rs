@@ -0,0 +1,4300 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+
+use crate::constants::*;
+use crate::errors::RateLimiterError;
+use crate::state::{ClientBucket, DiscountTier, GlobalConfig, GlobalStats, PayerMeta, PendingAuthority, PreviousConfig};
+
+// Every layout ClientBucket and GlobalConfig have shipped with, and pure
+// functions stepping each one to the next. migrate_account decodes whatever
+// version an account is in and walks the chain up to the current one.
+//
+// Versions 0-2 predate the version byte and are told apart by account length
+// (discriminator included), so no later layout may reuse one of those lengths.
+// From version 3 on, `version` is the first byte after the discriminator.
+//
+//   version  ClientBucket  GlobalConfig
+//   0        66 bytes      66 bytes      original deployment
+//   1        91 bytes      74 bytes      violations, config epoch, burst credits, saturation
+//   2        123 bytes     107 bytes     bools packed into flags, 32 reserved bytes
+//   3        124 bytes     108 bytes     version byte (config: reserved bytes later read as hook_program)
+//   4        132 bytes     204 bytes     last request time; config: limit feeder, dynamic override, 32 reserved bytes
+//   5        140 bytes     374 bytes     priority burst units; config: council seats, threshold, proposal counter
+//   6        156 bytes     414 bytes     escrowed and bought quota; config: pending admin recovery
+//   7        164 bytes     462 bytes     consume_v2 nonce; config: registration fee, cooldown and treasury
+//   8        172 bytes     503 bytes     violation decay time; config: consume_amount discount tiers
+//   9        180 bytes     519 bytes     consume_v2 nonce window; config: appeal bond and cooldown
+//   10       196 bytes     528 bytes     refund nonce and count; config: pause start and reason
+//   11       228 bytes     536 bytes     registering payer; config: auto-unpause deadline
+//   12       260 bytes     560 bytes     recovery delegate; config: admin action throttle
+//   13       276 bytes     600 bytes     event tag; config: refund authority and cap
+//   14       -             604 bytes     per-payer registration cap
+//   15       -             628 bytes     pause grace period and budget
+//   16       -             636 bytes     inactivity forgiveness
+//   17       -             644 bytes     minimum client balance
+//   18       -             652 bytes     recovery inactivity period
+//   19       -             677 bytes     window proration mode and the limits it replaced
+//   20       -             681 bytes     denylisted owner program count
+//   21       -             761 bytes     pending authority rotations
+//
+// GlobalConfig v3's 32 reserved bytes became hook_program in place. They were
+// always zeroed, which reads as the default key (no hook), so that did not need a
+// new version. The same goes for v6's scheduled pause, taken from the first 16
+// reserved bytes (zero start and end is no schedule), and for v7's stale-bucket
+// policy, taken from ten of its 16 (a zero threshold never collects). v11's
+// config_preset is the first of the last six, where zero reads as custom limits,
+// and violation_halflife_seconds the next four, where zero never decays.
+// ClientBucket v3's 32 reserved bytes likewise became the owner's label, where
+// zeros mean no label.
+//
+// GlobalStats has no version byte at all, so its layouts are told apart by
+// length too: 58 bytes before the circuit breaker fields, 90 bytes before the
+// lifetime denial count, 98 before the usage histogram. PayerMeta has none
+// either and was 49 bytes before it counted the payer's buckets.
+
+const BUCKET_UNVERSIONED: [(usize, u8); 3] = [(66, 0), (91, 1), (123, 2)];
+const CONFIG_UNVERSIONED: [(usize, u8); 3] = [(66, 0), (74, 1), (107, 2)];
+const STATS_V0_LEN: usize = 58;
+const STATS_V1_LEN: usize = 90;
+const STATS_V2_LEN: usize = 98;
+const PAYER_META_V0_LEN: usize = 49;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV0 {
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub is_blocked: bool,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV1 {
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub total_saturated: bool,
+    pub is_blocked: bool,
+    pub violations: u64,
+    pub config_epoch: u64,
+    pub burst_credits: u64,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV2 {
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub flags: u16,
+    pub violations: u64,
+    pub config_epoch: u64,
+    pub burst_credits: u64,
+    pub bump: u8,
+    pub reserved: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV3 {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub flags: u16,
+    pub violations: u64,
+    pub config_epoch: u64,
+    pub burst_credits: u64,
+    pub bump: u8,
+    pub label: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV4 {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub flags: u16,
+    pub violations: u64,
+    pub config_epoch: u64,
+    pub burst_credits: u64,
+    pub last_request_ts: i64,
+    pub bump: u8,
+    pub label: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV5 {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub flags: u16,
+    pub violations: u64,
+    pub config_epoch: u64,
+    pub burst_credits: u64,
+    pub priority_count: u64,
+    pub last_request_ts: i64,
+    pub bump: u8,
+    pub label: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV6 {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub flags: u16,
+    pub violations: u64,
+    pub config_epoch: u64,
+    pub burst_credits: u64,
+    pub priority_count: u64,
+    pub escrowed_count: u64,
+    pub bonus_requests: u64,
+    pub last_request_ts: i64,
+    pub bump: u8,
+    pub label: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV7 {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub flags: u16,
+    pub violations: u64,
+    pub config_epoch: u64,
+    pub burst_credits: u64,
+    pub priority_count: u64,
+    pub escrowed_count: u64,
+    pub bonus_requests: u64,
+    pub last_nonce: u64,
+    pub last_request_ts: i64,
+    pub bump: u8,
+    pub label: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV8 {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub flags: u16,
+    pub violations: u64,
+    pub violations_updated_at: i64,
+    pub config_epoch: u64,
+    pub burst_credits: u64,
+    pub priority_count: u64,
+    pub escrowed_count: u64,
+    pub bonus_requests: u64,
+    pub last_nonce: u64,
+    pub last_request_ts: i64,
+    pub bump: u8,
+    pub label: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV9 {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub flags: u16,
+    pub violations: u64,
+    pub violations_updated_at: i64,
+    pub config_epoch: u64,
+    pub burst_credits: u64,
+    pub priority_count: u64,
+    pub escrowed_count: u64,
+    pub bonus_requests: u64,
+    pub last_nonce: u64,
+    pub nonce_bitmap: u64,
+    pub last_request_ts: i64,
+    pub bump: u8,
+    pub label: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV10 {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub flags: u16,
+    pub violations: u64,
+    pub violations_updated_at: i64,
+    pub config_epoch: u64,
+    pub burst_credits: u64,
+    pub priority_count: u64,
+    pub escrowed_count: u64,
+    pub bonus_requests: u64,
+    pub last_nonce: u64,
+    pub nonce_bitmap: u64,
+    pub last_refund_nonce: u64,
+    pub refunded_count: u64,
+    pub last_request_ts: i64,
+    pub bump: u8,
+    pub label: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV11 {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub flags: u16,
+    pub violations: u64,
+    pub violations_updated_at: i64,
+    pub config_epoch: u64,
+    pub burst_credits: u64,
+    pub priority_count: u64,
+    pub escrowed_count: u64,
+    pub bonus_requests: u64,
+    pub last_nonce: u64,
+    pub nonce_bitmap: u64,
+    pub last_refund_nonce: u64,
+    pub refunded_count: u64,
+    pub registered_by: Pubkey,
+    pub last_request_ts: i64,
+    pub bump: u8,
+    pub label: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ClientBucketV12 {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub total_requests: u64,
+    pub flags: u16,
+    pub violations: u64,
+    pub violations_updated_at: i64,
+    pub config_epoch: u64,
+    pub burst_credits: u64,
+    pub priority_count: u64,
+    pub escrowed_count: u64,
+    pub bonus_requests: u64,
+    pub last_nonce: u64,
+    pub nonce_bitmap: u64,
+    pub last_refund_nonce: u64,
+    pub refunded_count: u64,
+    pub registered_by: Pubkey,
+    pub recovery_delegate: Pubkey,
+    pub last_request_ts: i64,
+    pub bump: u8,
+    pub label: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV0 {
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub is_paused: bool,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV1 {
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub is_paused: bool,
+    pub config_epoch: u64,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV2 {
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub reserved: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV3 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV4 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub reserved: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV5 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub reserved: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV6 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub reserved: [u8; 16],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV7 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub reserved: [u8; 6],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV8 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub reserved: [u8; 6],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV9 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub appeal_bond_lamports: u64,
+    pub appeal_cooldown_seconds: i64,
+    pub reserved: [u8; 6],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV10 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub appeal_bond_lamports: u64,
+    pub appeal_cooldown_seconds: i64,
+    pub paused_at: i64,
+    pub pause_reason: u8,
+    pub reserved: [u8; 6],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV11 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub appeal_bond_lamports: u64,
+    pub appeal_cooldown_seconds: i64,
+    pub paused_at: i64,
+    pub pause_reason: u8,
+    pub auto_unpause_at: i64,
+    pub config_preset: u8,
+    pub violation_halflife_seconds: u32,
+    pub reserved: [u8; 1],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV12 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub appeal_bond_lamports: u64,
+    pub appeal_cooldown_seconds: i64,
+    pub paused_at: i64,
+    pub pause_reason: u8,
+    pub auto_unpause_at: i64,
+    pub config_preset: u8,
+    pub violation_halflife_seconds: u32,
+    pub admin_action_window_seconds: i64,
+    pub max_admin_actions_per_window: u32,
+    pub admin_action_window_start: i64,
+    pub admin_actions_in_window: u32,
+    pub reserved: [u8; 1],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV13 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub appeal_bond_lamports: u64,
+    pub appeal_cooldown_seconds: i64,
+    pub paused_at: i64,
+    pub pause_reason: u8,
+    pub auto_unpause_at: i64,
+    pub config_preset: u8,
+    pub violation_halflife_seconds: u32,
+    pub admin_action_window_seconds: i64,
+    pub max_admin_actions_per_window: u32,
+    pub admin_action_window_start: i64,
+    pub admin_actions_in_window: u32,
+    pub refund_authority: Pubkey,
+    pub max_refunds_per_window: u64,
+    pub reserved: [u8; 1],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV14 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub appeal_bond_lamports: u64,
+    pub appeal_cooldown_seconds: i64,
+    pub paused_at: i64,
+    pub pause_reason: u8,
+    pub auto_unpause_at: i64,
+    pub config_preset: u8,
+    pub violation_halflife_seconds: u32,
+    pub admin_action_window_seconds: i64,
+    pub max_admin_actions_per_window: u32,
+    pub admin_action_window_start: i64,
+    pub admin_actions_in_window: u32,
+    pub refund_authority: Pubkey,
+    pub max_refunds_per_window: u64,
+    pub max_buckets_per_payer: u32,
+    pub reserved: [u8; 1],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV15 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub appeal_bond_lamports: u64,
+    pub appeal_cooldown_seconds: i64,
+    pub paused_at: i64,
+    pub pause_reason: u8,
+    pub auto_unpause_at: i64,
+    pub config_preset: u8,
+    pub violation_halflife_seconds: u32,
+    pub admin_action_window_seconds: i64,
+    pub max_admin_actions_per_window: u32,
+    pub admin_action_window_start: i64,
+    pub admin_actions_in_window: u32,
+    pub refund_authority: Pubkey,
+    pub max_refunds_per_window: u64,
+    pub max_buckets_per_payer: u32,
+    pub pause_grace_seconds: i64,
+    pub pause_grace_budget: u64,
+    pub grace_budget: u64,
+    pub reserved: [u8; 1],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV16 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub appeal_bond_lamports: u64,
+    pub appeal_cooldown_seconds: i64,
+    pub paused_at: i64,
+    pub pause_reason: u8,
+    pub auto_unpause_at: i64,
+    pub config_preset: u8,
+    pub violation_halflife_seconds: u32,
+    pub admin_action_window_seconds: i64,
+    pub max_admin_actions_per_window: u32,
+    pub admin_action_window_start: i64,
+    pub admin_actions_in_window: u32,
+    pub refund_authority: Pubkey,
+    pub max_refunds_per_window: u64,
+    pub max_buckets_per_payer: u32,
+    pub pause_grace_seconds: i64,
+    pub pause_grace_budget: u64,
+    pub grace_budget: u64,
+    pub inactivity_forgiveness_seconds: i64,
+    pub reserved: [u8; 1],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV17 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub appeal_bond_lamports: u64,
+    pub appeal_cooldown_seconds: i64,
+    pub paused_at: i64,
+    pub pause_reason: u8,
+    pub auto_unpause_at: i64,
+    pub config_preset: u8,
+    pub violation_halflife_seconds: u32,
+    pub admin_action_window_seconds: i64,
+    pub max_admin_actions_per_window: u32,
+    pub admin_action_window_start: i64,
+    pub admin_actions_in_window: u32,
+    pub refund_authority: Pubkey,
+    pub max_refunds_per_window: u64,
+    pub max_buckets_per_payer: u32,
+    pub pause_grace_seconds: i64,
+    pub pause_grace_budget: u64,
+    pub grace_budget: u64,
+    pub inactivity_forgiveness_seconds: i64,
+    pub min_client_balance_lamports: u64,
+    pub reserved: [u8; 1],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV18 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub appeal_bond_lamports: u64,
+    pub appeal_cooldown_seconds: i64,
+    pub paused_at: i64,
+    pub pause_reason: u8,
+    pub auto_unpause_at: i64,
+    pub config_preset: u8,
+    pub violation_halflife_seconds: u32,
+    pub admin_action_window_seconds: i64,
+    pub max_admin_actions_per_window: u32,
+    pub admin_action_window_start: i64,
+    pub admin_actions_in_window: u32,
+    pub refund_authority: Pubkey,
+    pub max_refunds_per_window: u64,
+    pub max_buckets_per_payer: u32,
+    pub pause_grace_seconds: i64,
+    pub pause_grace_budget: u64,
+    pub grace_budget: u64,
+    pub inactivity_forgiveness_seconds: i64,
+    pub min_client_balance_lamports: u64,
+    pub recovery_inactive_seconds: i64,
+    pub reserved: [u8; 1],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV19 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub appeal_bond_lamports: u64,
+    pub appeal_cooldown_seconds: i64,
+    pub paused_at: i64,
+    pub pause_reason: u8,
+    pub auto_unpause_at: i64,
+    pub config_preset: u8,
+    pub violation_halflife_seconds: u32,
+    pub admin_action_window_seconds: i64,
+    pub max_admin_actions_per_window: u32,
+    pub admin_action_window_start: i64,
+    pub admin_actions_in_window: u32,
+    pub refund_authority: Pubkey,
+    pub max_refunds_per_window: u64,
+    pub max_buckets_per_payer: u32,
+    pub pause_grace_seconds: i64,
+    pub pause_grace_budget: u64,
+    pub grace_budget: u64,
+    pub inactivity_forgiveness_seconds: i64,
+    pub min_client_balance_lamports: u64,
+    pub recovery_inactive_seconds: i64,
+    pub proration_mode: u8,
+    pub previous_config: PreviousConfig,
+    pub reserved: [u8; 1],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalConfigV20 {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub flags: u16,
+    pub config_epoch: u64,
+    pub bump: u8,
+    pub hook_program: Pubkey,
+    pub limit_feeder: Pubkey,
+    pub dynamic_floor: u64,
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,
+    pub dynamic_valid_until: i64,
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS],
+    pub council_size: u8,
+    pub council_threshold: u8,
+    pub proposal_count: u64,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub scheduled_pause_start: i64,
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,
+    pub registration_fee_lamports: u64,
+    pub registration_cooldown_seconds: i64,
+    pub stale_after_seconds: i64,
+    pub gc_bounty_bps: u16,
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS],
+    pub discount_tier_count: u8,
+    pub appeal_bond_lamports: u64,
+    pub appeal_cooldown_seconds: i64,
+    pub paused_at: i64,
+    pub pause_reason: u8,
+    pub auto_unpause_at: i64,
+    pub config_preset: u8,
+    pub violation_halflife_seconds: u32,
+    pub admin_action_window_seconds: i64,
+    pub max_admin_actions_per_window: u32,
+    pub admin_action_window_start: i64,
+    pub admin_actions_in_window: u32,
+    pub refund_authority: Pubkey,
+    pub max_refunds_per_window: u64,
+    pub max_buckets_per_payer: u32,
+    pub pause_grace_seconds: i64,
+    pub pause_grace_budget: u64,
+    pub grace_budget: u64,
+    pub inactivity_forgiveness_seconds: i64,
+    pub min_client_balance_lamports: u64,
+    pub recovery_inactive_seconds: i64,
+    pub proration_mode: u8,
+    pub previous_config: PreviousConfig,
+    pub denied_owner_programs: u32,
+    pub reserved: [u8; 1],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalStatsV0 {
+    pub total_clients: u64,
+    pub blocked_clients: u64,
+    pub total_requests: u64,
+    pub total_saturated: bool,
+    pub window_rollovers: u64,
+    pub snapshot_interval_seconds: i64,
+    pub last_snapshot_at: i64,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalStatsV1 {
+    pub total_clients: u64,
+    pub blocked_clients: u64,
+    pub total_requests: u64,
+    pub total_saturated: bool,
+    pub window_rollovers: u64,
+    pub snapshot_interval_seconds: i64,
+    pub last_snapshot_at: i64,
+    pub bump: u8,
+    pub circuit_breaker_threshold: u64,
+    pub circuit_breaker_window_seconds: i64,
+    pub rejection_window_start: i64,
+    pub rejection_count: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct GlobalStatsV2 {
+    pub total_clients: u64,
+    pub blocked_clients: u64,
+    pub total_requests: u64,
+    pub total_saturated: bool,
+    pub window_rollovers: u64,
+    pub snapshot_interval_seconds: i64,
+    pub last_snapshot_at: i64,
+    pub bump: u8,
+    pub circuit_breaker_threshold: u64,
+    pub circuit_breaker_window_seconds: i64,
+    pub rejection_window_start: i64,
+    pub rejection_count: u64,
+    pub total_denials: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct PayerMetaV0 {
+    pub payer: Pubkey,
+    pub last_registration_ts: i64,
+    pub bump: u8,
+}
+
+// Version 0 had no epochs, so buckets join epoch 0 alongside their migrated config
+pub fn migrate_bucket_v0_to_v1(old: ClientBucketV0) -> ClientBucketV1 {
+    ClientBucketV1 {
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        total_saturated: false,
+        is_blocked: old.is_blocked,
+        violations: 0,
+        config_epoch: 0,
+        burst_credits: 0,
+        bump: old.bump,
+    }
+}
+
+pub fn migrate_bucket_v1_to_v2(old: ClientBucketV1) -> ClientBucketV2 {
+    let mut flags = 0;
+    if old.is_blocked {
+        flags |= BUCKET_FLAG_BLOCKED;
+    }
+    if old.total_saturated {
+        flags |= BUCKET_FLAG_TOTAL_SATURATED;
+    }
+    ClientBucketV2 {
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        flags,
+        violations: old.violations,
+        config_epoch: old.config_epoch,
+        burst_credits: old.burst_credits,
+        bump: old.bump,
+        reserved: [0; 32],
+    }
+}
+
+pub fn migrate_bucket_v2_to_v3(old: ClientBucketV2) -> ClientBucketV3 {
+    ClientBucketV3 {
+        version: 3,
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        flags: old.flags,
+        violations: old.violations,
+        config_epoch: old.config_epoch,
+        burst_credits: old.burst_credits,
+        bump: old.bump,
+        label: old.reserved,
+    }
+}
+
+// Older buckets never recorded their last request. The current window's start is
+// the latest moment one is known to have happened, so staleness counts from there.
+pub fn migrate_bucket_v3_to_v4(old: ClientBucketV3) -> ClientBucketV4 {
+    ClientBucketV4 {
+        version: 4,
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        flags: old.flags,
+        violations: old.violations,
+        config_epoch: old.config_epoch,
+        burst_credits: old.burst_credits,
+        last_request_ts: old.window_start,
+        bump: old.bump,
+        label: old.label,
+    }
+}
+
+// No priority consume has drawn on an older bucket's credits
+pub fn migrate_bucket_v4_to_v5(old: ClientBucketV4) -> ClientBucketV5 {
+    ClientBucketV5 {
+        version: 5,
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        flags: old.flags,
+        violations: old.violations,
+        config_epoch: old.config_epoch,
+        burst_credits: old.burst_credits,
+        priority_count: 0,
+        last_request_ts: old.last_request_ts,
+        bump: old.bump,
+        label: old.label,
+    }
+}
+
+// Nothing was traded before quota offers existed
+pub fn migrate_bucket_v5_to_v6(old: ClientBucketV5) -> ClientBucketV6 {
+    ClientBucketV6 {
+        version: 6,
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        flags: old.flags,
+        violations: old.violations,
+        config_epoch: old.config_epoch,
+        burst_credits: old.burst_credits,
+        priority_count: old.priority_count,
+        escrowed_count: 0,
+        bonus_requests: 0,
+        last_request_ts: old.last_request_ts,
+        bump: old.bump,
+        label: old.label,
+    }
+}
+
+// No consume_v2 has carried a nonce for an older bucket, so any nonce is fresh
+pub fn migrate_bucket_v6_to_v7(old: ClientBucketV6) -> ClientBucketV7 {
+    ClientBucketV7 {
+        version: 7,
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        flags: old.flags,
+        violations: old.violations,
+        config_epoch: old.config_epoch,
+        burst_credits: old.burst_credits,
+        priority_count: old.priority_count,
+        escrowed_count: old.escrowed_count,
+        bonus_requests: old.bonus_requests,
+        last_nonce: 0,
+        last_request_ts: old.last_request_ts,
+        bump: old.bump,
+        label: old.label,
+    }
+}
+
+// An unstamped count is taken as of the next violation, which starts its decay:
+// the count only ever shrinks later than it would have, never grows
+pub fn migrate_bucket_v7_to_v8(old: ClientBucketV7) -> ClientBucketV8 {
+    ClientBucketV8 {
+        version: 8,
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        flags: old.flags,
+        violations: old.violations,
+        violations_updated_at: 0,
+        config_epoch: old.config_epoch,
+        burst_credits: old.burst_credits,
+        priority_count: old.priority_count,
+        escrowed_count: old.escrowed_count,
+        bonus_requests: old.bonus_requests,
+        last_nonce: old.last_nonce,
+        last_request_ts: old.last_request_ts,
+        bump: old.bump,
+        label: old.label,
+    }
+}
+
+// Which nonces below last_nonce went unused was never recorded, and a v8 bucket
+// only ever took increasing ones, so the whole window starts out marked: nothing
+// at or below last_nonce is accepted until a higher nonce slides it forward
+pub fn migrate_bucket_v8_to_v9(old: ClientBucketV8) -> ClientBucketV9 {
+    ClientBucketV9 {
+        version: 9,
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        flags: old.flags,
+        violations: old.violations,
+        violations_updated_at: old.violations_updated_at,
+        config_epoch: old.config_epoch,
+        burst_credits: old.burst_credits,
+        priority_count: old.priority_count,
+        escrowed_count: old.escrowed_count,
+        bonus_requests: old.bonus_requests,
+        last_nonce: old.last_nonce,
+        nonce_bitmap: u64::MAX,
+        last_request_ts: old.last_request_ts,
+        bump: old.bump,
+        label: old.label,
+    }
+}
+
+// Nothing was refunded before refund_request existed
+pub fn migrate_bucket_v9_to_v10(old: ClientBucketV9) -> ClientBucketV10 {
+    ClientBucketV10 {
+        version: 10,
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        flags: old.flags,
+        violations: old.violations,
+        violations_updated_at: old.violations_updated_at,
+        config_epoch: old.config_epoch,
+        burst_credits: old.burst_credits,
+        priority_count: old.priority_count,
+        escrowed_count: old.escrowed_count,
+        bonus_requests: old.bonus_requests,
+        last_nonce: old.last_nonce,
+        nonce_bitmap: old.nonce_bitmap,
+        last_refund_nonce: 0,
+        refunded_count: 0,
+        last_request_ts: old.last_request_ts,
+        bump: old.bump,
+        label: old.label,
+    }
+}
+
+// Who paid for a bucket was not recorded before v11
+pub fn migrate_bucket_v10_to_v11(old: ClientBucketV10) -> ClientBucketV11 {
+    ClientBucketV11 {
+        version: 11,
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        flags: old.flags,
+        violations: old.violations,
+        violations_updated_at: old.violations_updated_at,
+        config_epoch: old.config_epoch,
+        burst_credits: old.burst_credits,
+        priority_count: old.priority_count,
+        escrowed_count: old.escrowed_count,
+        bonus_requests: old.bonus_requests,
+        last_nonce: old.last_nonce,
+        nonce_bitmap: old.nonce_bitmap,
+        last_refund_nonce: old.last_refund_nonce,
+        refunded_count: old.refunded_count,
+        registered_by: Pubkey::default(),
+        last_request_ts: old.last_request_ts,
+        bump: old.bump,
+        label: old.label,
+    }
+}
+
+// A recovery delegate is the owner's to name, so v11 buckets start without one
+pub fn migrate_bucket_v11_to_v12(old: ClientBucketV11) -> ClientBucketV12 {
+    ClientBucketV12 {
+        version: 12,
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        flags: old.flags,
+        violations: old.violations,
+        violations_updated_at: old.violations_updated_at,
+        config_epoch: old.config_epoch,
+        burst_credits: old.burst_credits,
+        priority_count: old.priority_count,
+        escrowed_count: old.escrowed_count,
+        bonus_requests: old.bonus_requests,
+        last_nonce: old.last_nonce,
+        nonce_bitmap: old.nonce_bitmap,
+        last_refund_nonce: old.last_refund_nonce,
+        refunded_count: old.refunded_count,
+        registered_by: old.registered_by,
+        recovery_delegate: Pubkey::default(),
+        last_request_ts: old.last_request_ts,
+        bump: old.bump,
+        label: old.label,
+    }
+}
+
+// Tags are the owner's to set, so buckets from before v13 have none
+pub fn migrate_bucket_v12_to_v13(old: ClientBucketV12) -> ClientBucket {
+    ClientBucket {
+        version: 13,
+        owner: old.owner,
+        request_count: old.request_count,
+        window_start: old.window_start,
+        total_requests: old.total_requests,
+        flags: old.flags,
+        violations: old.violations,
+        violations_updated_at: old.violations_updated_at,
+        config_epoch: old.config_epoch,
+        burst_credits: old.burst_credits,
+        priority_count: old.priority_count,
+        escrowed_count: old.escrowed_count,
+        bonus_requests: old.bonus_requests,
+        last_nonce: old.last_nonce,
+        nonce_bitmap: old.nonce_bitmap,
+        last_refund_nonce: old.last_refund_nonce,
+        refunded_count: old.refunded_count,
+        registered_by: old.registered_by,
+        recovery_delegate: old.recovery_delegate,
+        last_request_ts: old.last_request_ts,
+        bump: old.bump,
+        label: old.label,
+        event_tag: [0; 16],
+    }
+}
+
+pub fn migrate_config_v0_to_v1(old: GlobalConfigV0) -> GlobalConfigV1 {
+    GlobalConfigV1 {
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        is_paused: old.is_paused,
+        config_epoch: 0,
+        bump: old.bump,
+    }
+}
+
+pub fn migrate_config_v1_to_v2(old: GlobalConfigV1) -> GlobalConfigV2 {
+    GlobalConfigV2 {
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: if old.is_paused { CONFIG_FLAG_PAUSED } else { 0 },
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        reserved: [0; 32],
+    }
+}
+
+pub fn migrate_config_v2_to_v3(old: GlobalConfigV2) -> GlobalConfigV3 {
+    GlobalConfigV3 {
+        version: 3,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        // The reserved bytes, always zeroed so far: no hook
+        hook_program: Pubkey::new_from_array(old.reserved),
+    }
+}
+
+// No feeder: the admin has to opt in with set_limit_feeder
+pub fn migrate_config_v3_to_v4(old: GlobalConfigV3) -> GlobalConfigV4 {
+    GlobalConfigV4 {
+        version: 4,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: Pubkey::default(),
+        dynamic_floor: 0,
+        dynamic_ceiling: 0,
+        dynamic_max: 0,
+        dynamic_valid_until: 0,
+        reserved: [0; 32],
+    }
+}
+
+// No council: the admin keeps acting alone until it seats one
+pub fn migrate_config_v4_to_v5(old: GlobalConfigV4) -> GlobalConfigV5 {
+    GlobalConfigV5 {
+        version: 5,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+        council_size: 0,
+        council_threshold: 0,
+        proposal_count: 0,
+        reserved: old.reserved,
+    }
+}
+
+// No recovery in flight
+pub fn migrate_config_v5_to_v6(old: GlobalConfigV5) -> GlobalConfigV6 {
+    GlobalConfigV6 {
+        version: 6,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: Pubkey::default(),
+        recovery_eta: 0,
+        scheduled_pause_start: 0,
+        scheduled_pause_end: 0,
+        reserved: [0; 16],
+    }
+}
+
+// Registration stays free and unthrottled until the admin sets a fee or cooldown
+pub fn migrate_config_v6_to_v7(old: GlobalConfigV6) -> GlobalConfigV7 {
+    GlobalConfigV7 {
+        version: 7,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: Pubkey::default(),
+        registration_fee_lamports: 0,
+        registration_cooldown_seconds: 0,
+        stale_after_seconds: 0,
+        gc_bounty_bps: 0,
+        reserved: [0; 6],
+    }
+}
+
+// Batches are charged in full until the admin sets discount tiers
+pub fn migrate_config_v7_to_v8(old: GlobalConfigV7) -> GlobalConfigV8 {
+    GlobalConfigV8 {
+        version: 8,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+        discount_tier_count: 0,
+        reserved: old.reserved,
+    }
+}
+
+// Appeals stay off until the admin sets a bond
+pub fn migrate_config_v8_to_v9(old: GlobalConfigV8) -> GlobalConfigV9 {
+    GlobalConfigV9 {
+        version: 9,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: 0,
+        appeal_cooldown_seconds: 0,
+        reserved: old.reserved,
+    }
+}
+
+// A pause already in force carries over with no recorded start or reason
+pub fn migrate_config_v9_to_v10(old: GlobalConfigV9) -> GlobalConfigV10 {
+    GlobalConfigV10 {
+        version: 10,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: old.appeal_bond_lamports,
+        appeal_cooldown_seconds: old.appeal_cooldown_seconds,
+        paused_at: 0,
+        pause_reason: PAUSE_REASON_UNSPECIFIED,
+        reserved: old.reserved,
+    }
+}
+
+// Pauses stay manual-only until the admin gives one a deadline
+pub fn migrate_config_v10_to_v11(old: GlobalConfigV10) -> GlobalConfigV11 {
+    GlobalConfigV11 {
+        version: 11,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: old.appeal_bond_lamports,
+        appeal_cooldown_seconds: old.appeal_cooldown_seconds,
+        paused_at: old.paused_at,
+        pause_reason: old.pause_reason,
+        auto_unpause_at: 0,
+        config_preset: CONFIG_PRESET_CUSTOM,
+        violation_halflife_seconds: 0,
+        reserved: [0; 1],
+    }
+}
+
+// The admin's actions stay unthrottled until the upgrade authority sets a cap
+pub fn migrate_config_v11_to_v12(old: GlobalConfigV11) -> GlobalConfigV12 {
+    GlobalConfigV12 {
+        version: 12,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: old.appeal_bond_lamports,
+        appeal_cooldown_seconds: old.appeal_cooldown_seconds,
+        paused_at: old.paused_at,
+        pause_reason: old.pause_reason,
+        auto_unpause_at: old.auto_unpause_at,
+        config_preset: old.config_preset,
+        violation_halflife_seconds: old.violation_halflife_seconds,
+        admin_action_window_seconds: 0,
+        max_admin_actions_per_window: 0,
+        admin_action_window_start: 0,
+        admin_actions_in_window: 0,
+        reserved: old.reserved,
+    }
+}
+
+// No refund authority until the admin names one
+pub fn migrate_config_v12_to_v13(old: GlobalConfigV12) -> GlobalConfigV13 {
+    GlobalConfigV13 {
+        version: 13,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: old.appeal_bond_lamports,
+        appeal_cooldown_seconds: old.appeal_cooldown_seconds,
+        paused_at: old.paused_at,
+        pause_reason: old.pause_reason,
+        auto_unpause_at: old.auto_unpause_at,
+        config_preset: old.config_preset,
+        violation_halflife_seconds: old.violation_halflife_seconds,
+        admin_action_window_seconds: old.admin_action_window_seconds,
+        max_admin_actions_per_window: old.max_admin_actions_per_window,
+        admin_action_window_start: old.admin_action_window_start,
+        admin_actions_in_window: old.admin_actions_in_window,
+        refund_authority: Pubkey::default(),
+        max_refunds_per_window: 0,
+        reserved: old.reserved,
+    }
+}
+
+// Payers were not capped before v14
+pub fn migrate_config_v13_to_v14(old: GlobalConfigV13) -> GlobalConfigV14 {
+    GlobalConfigV14 {
+        version: 14,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: old.appeal_bond_lamports,
+        appeal_cooldown_seconds: old.appeal_cooldown_seconds,
+        paused_at: old.paused_at,
+        pause_reason: old.pause_reason,
+        auto_unpause_at: old.auto_unpause_at,
+        config_preset: old.config_preset,
+        violation_halflife_seconds: old.violation_halflife_seconds,
+        admin_action_window_seconds: old.admin_action_window_seconds,
+        max_admin_actions_per_window: old.max_admin_actions_per_window,
+        admin_action_window_start: old.admin_action_window_start,
+        admin_actions_in_window: old.admin_actions_in_window,
+        refund_authority: old.refund_authority,
+        max_refunds_per_window: old.max_refunds_per_window,
+        max_buckets_per_payer: 0,
+        reserved: old.reserved,
+    }
+}
+
+// No grace period until the admin sets one
+pub fn migrate_config_v14_to_v15(old: GlobalConfigV14) -> GlobalConfigV15 {
+    GlobalConfigV15 {
+        version: 15,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: old.appeal_bond_lamports,
+        appeal_cooldown_seconds: old.appeal_cooldown_seconds,
+        paused_at: old.paused_at,
+        pause_reason: old.pause_reason,
+        auto_unpause_at: old.auto_unpause_at,
+        config_preset: old.config_preset,
+        violation_halflife_seconds: old.violation_halflife_seconds,
+        admin_action_window_seconds: old.admin_action_window_seconds,
+        max_admin_actions_per_window: old.max_admin_actions_per_window,
+        admin_action_window_start: old.admin_action_window_start,
+        admin_actions_in_window: old.admin_actions_in_window,
+        refund_authority: old.refund_authority,
+        max_refunds_per_window: old.max_refunds_per_window,
+        max_buckets_per_payer: old.max_buckets_per_payer,
+        pause_grace_seconds: 0,
+        pause_grace_budget: 0,
+        grace_budget: 0,
+        reserved: old.reserved,
+    }
+}
+
+// Nothing is forgiven until the admin sets a threshold
+pub fn migrate_config_v15_to_v16(old: GlobalConfigV15) -> GlobalConfigV16 {
+    GlobalConfigV16 {
+        version: 16,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: old.appeal_bond_lamports,
+        appeal_cooldown_seconds: old.appeal_cooldown_seconds,
+        paused_at: old.paused_at,
+        pause_reason: old.pause_reason,
+        auto_unpause_at: old.auto_unpause_at,
+        config_preset: old.config_preset,
+        violation_halflife_seconds: old.violation_halflife_seconds,
+        admin_action_window_seconds: old.admin_action_window_seconds,
+        max_admin_actions_per_window: old.max_admin_actions_per_window,
+        admin_action_window_start: old.admin_action_window_start,
+        admin_actions_in_window: old.admin_actions_in_window,
+        refund_authority: old.refund_authority,
+        max_refunds_per_window: old.max_refunds_per_window,
+        max_buckets_per_payer: old.max_buckets_per_payer,
+        pause_grace_seconds: old.pause_grace_seconds,
+        pause_grace_budget: old.pause_grace_budget,
+        grace_budget: old.grace_budget,
+        inactivity_forgiveness_seconds: 0,
+        reserved: old.reserved,
+    }
+}
+
+// No minimum balance until the admin sets one
+pub fn migrate_config_v16_to_v17(old: GlobalConfigV16) -> GlobalConfigV17 {
+    GlobalConfigV17 {
+        version: 17,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: old.appeal_bond_lamports,
+        appeal_cooldown_seconds: old.appeal_cooldown_seconds,
+        paused_at: old.paused_at,
+        pause_reason: old.pause_reason,
+        auto_unpause_at: old.auto_unpause_at,
+        config_preset: old.config_preset,
+        violation_halflife_seconds: old.violation_halflife_seconds,
+        admin_action_window_seconds: old.admin_action_window_seconds,
+        max_admin_actions_per_window: old.max_admin_actions_per_window,
+        admin_action_window_start: old.admin_action_window_start,
+        admin_actions_in_window: old.admin_actions_in_window,
+        refund_authority: old.refund_authority,
+        max_refunds_per_window: old.max_refunds_per_window,
+        max_buckets_per_payer: old.max_buckets_per_payer,
+        pause_grace_seconds: old.pause_grace_seconds,
+        pause_grace_budget: old.pause_grace_budget,
+        grace_budget: old.grace_budget,
+        inactivity_forgiveness_seconds: old.inactivity_forgiveness_seconds,
+        min_client_balance_lamports: 0,
+        reserved: old.reserved,
+    }
+}
+
+// No bucket recovery until the admin sets an inactivity period
+pub fn migrate_config_v17_to_v18(old: GlobalConfigV17) -> GlobalConfigV18 {
+    GlobalConfigV18 {
+        version: 18,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: old.appeal_bond_lamports,
+        appeal_cooldown_seconds: old.appeal_cooldown_seconds,
+        paused_at: old.paused_at,
+        pause_reason: old.pause_reason,
+        auto_unpause_at: old.auto_unpause_at,
+        config_preset: old.config_preset,
+        violation_halflife_seconds: old.violation_halflife_seconds,
+        admin_action_window_seconds: old.admin_action_window_seconds,
+        max_admin_actions_per_window: old.max_admin_actions_per_window,
+        admin_action_window_start: old.admin_action_window_start,
+        admin_actions_in_window: old.admin_actions_in_window,
+        refund_authority: old.refund_authority,
+        max_refunds_per_window: old.max_refunds_per_window,
+        max_buckets_per_payer: old.max_buckets_per_payer,
+        pause_grace_seconds: old.pause_grace_seconds,
+        pause_grace_budget: old.pause_grace_budget,
+        grace_budget: old.grace_budget,
+        inactivity_forgiveness_seconds: old.inactivity_forgiveness_seconds,
+        min_client_balance_lamports: old.min_client_balance_lamports,
+        recovery_inactive_seconds: 0,
+        reserved: old.reserved,
+    }
+}
+
+// Window proration is off (PRORATION_KEEP, how updates behaved before) and no
+// change has been recorded
+pub fn migrate_config_v18_to_v19(old: GlobalConfigV18) -> GlobalConfigV19 {
+    GlobalConfigV19 {
+        version: 19,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: old.appeal_bond_lamports,
+        appeal_cooldown_seconds: old.appeal_cooldown_seconds,
+        paused_at: old.paused_at,
+        pause_reason: old.pause_reason,
+        auto_unpause_at: old.auto_unpause_at,
+        config_preset: old.config_preset,
+        violation_halflife_seconds: old.violation_halflife_seconds,
+        admin_action_window_seconds: old.admin_action_window_seconds,
+        max_admin_actions_per_window: old.max_admin_actions_per_window,
+        admin_action_window_start: old.admin_action_window_start,
+        admin_actions_in_window: old.admin_actions_in_window,
+        refund_authority: old.refund_authority,
+        max_refunds_per_window: old.max_refunds_per_window,
+        max_buckets_per_payer: old.max_buckets_per_payer,
+        pause_grace_seconds: old.pause_grace_seconds,
+        pause_grace_budget: old.pause_grace_budget,
+        grace_budget: old.grace_budget,
+        inactivity_forgiveness_seconds: old.inactivity_forgiveness_seconds,
+        min_client_balance_lamports: old.min_client_balance_lamports,
+        recovery_inactive_seconds: old.recovery_inactive_seconds,
+        proration_mode: PRORATION_KEEP,
+        previous_config: PreviousConfig::default(),
+        reserved: old.reserved,
+    }
+}
+
+// Program-owner denylisting is new, so nothing starts out denylisted
+pub fn migrate_config_v19_to_v20(old: GlobalConfigV19) -> GlobalConfigV20 {
+    GlobalConfigV20 {
+        version: 20,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: old.appeal_bond_lamports,
+        appeal_cooldown_seconds: old.appeal_cooldown_seconds,
+        paused_at: old.paused_at,
+        pause_reason: old.pause_reason,
+        auto_unpause_at: old.auto_unpause_at,
+        config_preset: old.config_preset,
+        violation_halflife_seconds: old.violation_halflife_seconds,
+        admin_action_window_seconds: old.admin_action_window_seconds,
+        max_admin_actions_per_window: old.max_admin_actions_per_window,
+        admin_action_window_start: old.admin_action_window_start,
+        admin_actions_in_window: old.admin_actions_in_window,
+        refund_authority: old.refund_authority,
+        max_refunds_per_window: old.max_refunds_per_window,
+        max_buckets_per_payer: old.max_buckets_per_payer,
+        pause_grace_seconds: old.pause_grace_seconds,
+        pause_grace_budget: old.pause_grace_budget,
+        grace_budget: old.grace_budget,
+        inactivity_forgiveness_seconds: old.inactivity_forgiveness_seconds,
+        min_client_balance_lamports: old.min_client_balance_lamports,
+        recovery_inactive_seconds: old.recovery_inactive_seconds,
+        proration_mode: old.proration_mode,
+        previous_config: old.previous_config,
+        denied_owner_programs: 0,
+        reserved: old.reserved,
+    }
+}
+
+// Nothing rotates until the admin schedules it
+pub fn migrate_config_v20_to_v21(old: GlobalConfigV20) -> GlobalConfig {
+    GlobalConfig {
+        version: 21,
+        admin: old.admin,
+        max_requests: old.max_requests,
+        window_seconds: old.window_seconds,
+        burst_limit: old.burst_limit,
+        flags: old.flags,
+        config_epoch: old.config_epoch,
+        bump: old.bump,
+        hook_program: old.hook_program,
+        limit_feeder: old.limit_feeder,
+        dynamic_floor: old.dynamic_floor,
+        dynamic_ceiling: old.dynamic_ceiling,
+        dynamic_max: old.dynamic_max,
+        dynamic_valid_until: old.dynamic_valid_until,
+        council: old.council,
+        council_size: old.council_size,
+        council_threshold: old.council_threshold,
+        proposal_count: old.proposal_count,
+        recovery_admin: old.recovery_admin,
+        recovery_eta: old.recovery_eta,
+        scheduled_pause_start: old.scheduled_pause_start,
+        scheduled_pause_end: old.scheduled_pause_end,
+        treasury: old.treasury,
+        registration_fee_lamports: old.registration_fee_lamports,
+        registration_cooldown_seconds: old.registration_cooldown_seconds,
+        stale_after_seconds: old.stale_after_seconds,
+        gc_bounty_bps: old.gc_bounty_bps,
+        discount_tiers: old.discount_tiers,
+        discount_tier_count: old.discount_tier_count,
+        appeal_bond_lamports: old.appeal_bond_lamports,
+        appeal_cooldown_seconds: old.appeal_cooldown_seconds,
+        paused_at: old.paused_at,
+        pause_reason: old.pause_reason,
+        auto_unpause_at: old.auto_unpause_at,
+        config_preset: old.config_preset,
+        violation_halflife_seconds: old.violation_halflife_seconds,
+        admin_action_window_seconds: old.admin_action_window_seconds,
+        max_admin_actions_per_window: old.max_admin_actions_per_window,
+        admin_action_window_start: old.admin_action_window_start,
+        admin_actions_in_window: old.admin_actions_in_window,
+        refund_authority: old.refund_authority,
+        max_refunds_per_window: old.max_refunds_per_window,
+        max_buckets_per_payer: old.max_buckets_per_payer,
+        pause_grace_seconds: old.pause_grace_seconds,
+        pause_grace_budget: old.pause_grace_budget,
+        grace_budget: old.grace_budget,
+        inactivity_forgiveness_seconds: old.inactivity_forgiveness_seconds,
+        min_client_balance_lamports: old.min_client_balance_lamports,
+        recovery_inactive_seconds: old.recovery_inactive_seconds,
+        proration_mode: old.proration_mode,
+        previous_config: old.previous_config,
+        denied_owner_programs: old.denied_owner_programs,
+        pending_authorities: [PendingAuthority::default(); AUTHORITY_KINDS],
+        reserved: old.reserved,
+    }
+}
+
+// The breaker starts out disabled
+pub fn migrate_stats_v0_to_v1(old: GlobalStatsV0) -> GlobalStatsV1 {
+    GlobalStatsV1 {
+        total_clients: old.total_clients,
+        blocked_clients: old.blocked_clients,
+        total_requests: old.total_requests,
+        total_saturated: old.total_saturated,
+        window_rollovers: old.window_rollovers,
+        snapshot_interval_seconds: old.snapshot_interval_seconds,
+        last_snapshot_at: old.last_snapshot_at,
+        bump: old.bump,
+        circuit_breaker_threshold: 0,
+        circuit_breaker_window_seconds: 0,
+        rejection_window_start: 0,
+        rejection_count: 0,
+    }
+}
+
+// Denials before the upgrade went uncounted; rebalance_limits only ever compares
+// the count with a mark it took itself
+pub fn migrate_stats_v1_to_v2(old: GlobalStatsV1) -> GlobalStatsV2 {
+    GlobalStatsV2 {
+        total_clients: old.total_clients,
+        blocked_clients: old.blocked_clients,
+        total_requests: old.total_requests,
+        total_saturated: old.total_saturated,
+        window_rollovers: old.window_rollovers,
+        snapshot_interval_seconds: old.snapshot_interval_seconds,
+        last_snapshot_at: old.last_snapshot_at,
+        bump: old.bump,
+        circuit_breaker_threshold: old.circuit_breaker_threshold,
+        circuit_breaker_window_seconds: old.circuit_breaker_window_seconds,
+        rejection_window_start: old.rejection_window_start,
+        rejection_count: old.rejection_count,
+        total_denials: 0,
+    }
+}
+
+// Windows closed before the upgrade went unclassified; the histogram only counts
+// from it
+pub fn migrate_stats_v2_to_v3(old: GlobalStatsV2) -> GlobalStats {
+    GlobalStats {
+        total_clients: old.total_clients,
+        blocked_clients: old.blocked_clients,
+        total_requests: old.total_requests,
+        total_saturated: old.total_saturated,
+        window_rollovers: old.window_rollovers,
+        snapshot_interval_seconds: old.snapshot_interval_seconds,
+        last_snapshot_at: old.last_snapshot_at,
+        bump: old.bump,
+        circuit_breaker_threshold: old.circuit_breaker_threshold,
+        circuit_breaker_window_seconds: old.circuit_breaker_window_seconds,
+        rejection_window_start: old.rejection_window_start,
+        rejection_count: old.rejection_count,
+        total_denials: old.total_denials,
+        usage_histogram: [0; USAGE_HISTOGRAM_BUCKETS],
+    }
+}
+
+// Registrations before the upgrade went uncounted, so the cap counts from it
+pub fn migrate_payer_meta_v0_to_v1(old: PayerMetaV0) -> PayerMeta {
+    PayerMeta { payer: old.payer, last_registration_ts: old.last_registration_ts, bump: old.bump, buckets_created: 0 }
+}
+
+// Layout version of a full account (discriminator included)
+pub fn account_version(data: &[u8], unversioned: &[(usize, u8)]) -> Result<u8> {
+    require!(data.len() > 8, RateLimiterError::InvalidAccountLayout);
+    Ok(unversioned
+        .iter()
+        .find(|(len, _)| *len == data.len())
+        .map(|(_, version)| *version)
+        .unwrap_or(data[8]))
+}
+
+pub fn bucket_version(data: &[u8]) -> Result<u8> {
+    account_version(data, &BUCKET_UNVERSIONED)
+}
+
+pub fn config_version(data: &[u8]) -> Result<u8> {
+    account_version(data, &CONFIG_UNVERSIONED)
+}
+
+// Decodes a bucket account in any known version and migrates it to the current one
+pub fn migrate_bucket(data: &[u8]) -> Result<ClientBucket> {
+    require!(data.starts_with(ClientBucket::DISCRIMINATOR), RateLimiterError::InvalidAccountLayout);
+    let version = bucket_version(data)?;
+    require!(version != CLIENT_BUCKET_VERSION, RateLimiterError::AlreadyMigrated);
+    // Data too short for the layout its version byte names is no bucket either
+    decode_bucket(version, &mut &data[8..]).map_err(|_| error!(RateLimiterError::InvalidAccountLayout))
+}
+
+fn decode_bucket(version: u8, body: &mut &[u8]) -> Result<ClientBucket> {
+    let v9 = match version {
+        0..=6 => {
+            let v6 = match version {
+                0..=4 => {
+                    let v4 = match version {
+                        0 => {
+                            let v1 = migrate_bucket_v0_to_v1(ClientBucketV0::deserialize(body)?);
+                            migrate_bucket_v3_to_v4(migrate_bucket_v2_to_v3(migrate_bucket_v1_to_v2(v1)))
+                        }
+                        1 => {
+                            let v1 = ClientBucketV1::deserialize(body)?;
+                            migrate_bucket_v3_to_v4(migrate_bucket_v2_to_v3(migrate_bucket_v1_to_v2(v1)))
+                        }
+                        2 => migrate_bucket_v3_to_v4(migrate_bucket_v2_to_v3(ClientBucketV2::deserialize(body)?)),
+                        3 => migrate_bucket_v3_to_v4(ClientBucketV3::deserialize(body)?),
+                        _ => ClientBucketV4::deserialize(body)?,
+                    };
+                    migrate_bucket_v5_to_v6(migrate_bucket_v4_to_v5(v4))
+                }
+                5 => migrate_bucket_v5_to_v6(ClientBucketV5::deserialize(body)?),
+                _ => ClientBucketV6::deserialize(body)?,
+            };
+            migrate_bucket_v8_to_v9(migrate_bucket_v7_to_v8(migrate_bucket_v6_to_v7(v6)))
+        }
+        7 => migrate_bucket_v8_to_v9(migrate_bucket_v7_to_v8(ClientBucketV7::deserialize(body)?)),
+        8 => migrate_bucket_v8_to_v9(ClientBucketV8::deserialize(body)?),
+        9 => ClientBucketV9::deserialize(body)?,
+        10 => {
+            let v11 = migrate_bucket_v10_to_v11(ClientBucketV10::deserialize(body)?);
+            return Ok(migrate_bucket_v12_to_v13(migrate_bucket_v11_to_v12(v11)));
+        }
+        11 => {
+            let v11 = ClientBucketV11::deserialize(body)?;
+            return Ok(migrate_bucket_v12_to_v13(migrate_bucket_v11_to_v12(v11)));
+        }
+        12 => return Ok(migrate_bucket_v12_to_v13(ClientBucketV12::deserialize(body)?)),
+        _ => return err!(RateLimiterError::InvalidAccountLayout),
+    };
+    let v11 = migrate_bucket_v10_to_v11(migrate_bucket_v9_to_v10(v9));
+    Ok(migrate_bucket_v12_to_v13(migrate_bucket_v11_to_v12(v11)))
+}
+
+pub fn migrate_config(data: &[u8]) -> Result<GlobalConfig> {
+    require!(data.starts_with(GlobalConfig::DISCRIMINATOR), RateLimiterError::InvalidAccountLayout);
+    let version = config_version(data)?;
+    require!(version != GLOBAL_CONFIG_VERSION, RateLimiterError::AlreadyMigrated);
+    let body = &mut &data[8..];
+    let v8 = match version {
+        0..=6 => {
+            let v6 = match version {
+                0..=4 => {
+                    let v4 = match version {
+                        0 => migrate_config_v3_to_v4(migrate_config_v2_to_v3(migrate_config_v1_to_v2(
+                            migrate_config_v0_to_v1(GlobalConfigV0::deserialize(body)?),
+                        ))),
+                        1 => migrate_config_v3_to_v4(migrate_config_v2_to_v3(migrate_config_v1_to_v2(
+                            GlobalConfigV1::deserialize(body)?,
+                        ))),
+                        2 => migrate_config_v3_to_v4(migrate_config_v2_to_v3(GlobalConfigV2::deserialize(body)?)),
+                        3 => migrate_config_v3_to_v4(GlobalConfigV3::deserialize(body)?),
+                        _ => GlobalConfigV4::deserialize(body)?,
+                    };
+                    migrate_config_v5_to_v6(migrate_config_v4_to_v5(v4))
+                }
+                5 => migrate_config_v5_to_v6(GlobalConfigV5::deserialize(body)?),
+                _ => GlobalConfigV6::deserialize(body)?,
+            };
+            migrate_config_v7_to_v8(migrate_config_v6_to_v7(v6))
+        }
+        7 => migrate_config_v7_to_v8(GlobalConfigV7::deserialize(body)?),
+        8 => GlobalConfigV8::deserialize(body)?,
+        9..=20 => {
+            let v16 = match version {
+                9..=13 => {
+                    let v13 = match version {
+                        9 => {
+                            let v9 = GlobalConfigV9::deserialize(body)?;
+                            let v11 = migrate_config_v10_to_v11(migrate_config_v9_to_v10(v9));
+                            migrate_config_v12_to_v13(migrate_config_v11_to_v12(v11))
+                        }
+                        10 => {
+                            let v11 = migrate_config_v10_to_v11(GlobalConfigV10::deserialize(body)?);
+                            migrate_config_v12_to_v13(migrate_config_v11_to_v12(v11))
+                        }
+                        11 => migrate_config_v12_to_v13(migrate_config_v11_to_v12(GlobalConfigV11::deserialize(body)?)),
+                        12 => migrate_config_v12_to_v13(GlobalConfigV12::deserialize(body)?),
+                        _ => GlobalConfigV13::deserialize(body)?,
+                    };
+                    migrate_config_v15_to_v16(migrate_config_v14_to_v15(migrate_config_v13_to_v14(v13)))
+                }
+                14 => migrate_config_v15_to_v16(migrate_config_v14_to_v15(GlobalConfigV14::deserialize(body)?)),
+                15 => migrate_config_v15_to_v16(GlobalConfigV15::deserialize(body)?),
+                16 => GlobalConfigV16::deserialize(body)?,
+                17 => return Ok(config_from_v18(migrate_config_v17_to_v18(GlobalConfigV17::deserialize(body)?))),
+                18 => return Ok(config_from_v18(GlobalConfigV18::deserialize(body)?)),
+                19 => {
+                    let v20 = migrate_config_v19_to_v20(GlobalConfigV19::deserialize(body)?);
+                    return Ok(migrate_config_v20_to_v21(v20));
+                }
+                _ => return Ok(migrate_config_v20_to_v21(GlobalConfigV20::deserialize(body)?)),
+            };
+            return Ok(config_from_v18(migrate_config_v17_to_v18(migrate_config_v16_to_v17(v16))));
+        }
+        _ => return err!(RateLimiterError::InvalidAccountLayout),
+    };
+    let v11 = migrate_config_v10_to_v11(migrate_config_v9_to_v10(migrate_config_v8_to_v9(v8)));
+    let v14 = migrate_config_v13_to_v14(migrate_config_v12_to_v13(migrate_config_v11_to_v12(v11)));
+    let v16 = migrate_config_v15_to_v16(migrate_config_v14_to_v15(v14));
+    Ok(config_from_v18(migrate_config_v17_to_v18(migrate_config_v16_to_v17(v16))))
+}
+
+// The steps from v18 on, shared by every older path
+fn config_from_v18(v18: GlobalConfigV18) -> GlobalConfig {
+    migrate_config_v20_to_v21(migrate_config_v19_to_v20(migrate_config_v18_to_v19(v18)))
+}
+
+pub fn migrate_stats(data: &[u8]) -> Result<GlobalStats> {
+    require!(data.starts_with(GlobalStats::DISCRIMINATOR), RateLimiterError::InvalidAccountLayout);
+    require!(data.len() != GlobalStats::LEN, RateLimiterError::AlreadyMigrated);
+    let body = &mut &data[8..];
+    match data.len() {
+        STATS_V0_LEN => {
+            Ok(migrate_stats_v2_to_v3(migrate_stats_v1_to_v2(migrate_stats_v0_to_v1(GlobalStatsV0::deserialize(body)?))))
+        }
+        STATS_V1_LEN => Ok(migrate_stats_v2_to_v3(migrate_stats_v1_to_v2(GlobalStatsV1::deserialize(body)?))),
+        STATS_V2_LEN => Ok(migrate_stats_v2_to_v3(GlobalStatsV2::deserialize(body)?)),
+        _ => err!(RateLimiterError::InvalidAccountLayout),
+    }
+}
+
+pub fn migrate_payer_meta(data: &[u8]) -> Result<PayerMeta> {
+    require!(data.starts_with(PayerMeta::DISCRIMINATOR), RateLimiterError::InvalidAccountLayout);
+    require!(data.len() != 8 + PayerMeta::INIT_SPACE, RateLimiterError::AlreadyMigrated);
+    require!(data.len() == PAYER_META_V0_LEN, RateLimiterError::InvalidAccountLayout);
+    Ok(migrate_payer_meta_v0_to_v1(PayerMetaV0::deserialize(&mut &data[8..])?))
+}
+
+// For handlers holding a decoded account: anything older than the minimum
+// supported version has to go through migrate_account first
+pub fn require_supported(version: u8, min_version: u8) -> Result<()> {
+    require!(version >= min_version, RateLimiterError::MigrationRequired);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_discriminator<T: AnchorSerialize>(discriminator: &[u8], body: &T) -> Vec<u8> {
+        let mut data = discriminator.to_vec();
+        body.serialize(&mut data).unwrap();
+        data
+    }
+
+    fn owner() -> Pubkey {
+        Pubkey::new_from_array([7; 32])
+    }
+
+    fn bucket_v0(is_blocked: bool) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV0 {
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: 40,
+                is_blocked,
+                bump: 254,
+            },
+        )
+    }
+
+    fn bucket_v1(total_saturated: bool, is_blocked: bool) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV1 {
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: u64::MAX,
+                total_saturated,
+                is_blocked,
+                violations: 7,
+                config_epoch: 2,
+                burst_credits: 4,
+                bump: 254,
+            },
+        )
+    }
+
+    fn bucket_v2(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV2 {
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: u64::MAX,
+                flags,
+                violations: 7,
+                config_epoch: 2,
+                burst_credits: 4,
+                bump: 254,
+                reserved: [0; 32],
+            },
+        )
+    }
+
+    fn bucket_v3(label: [u8; 32]) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV3 {
+                version: 3,
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: 40,
+                flags: BUCKET_FLAG_BLOCKED,
+                violations: 7,
+                config_epoch: 2,
+                burst_credits: 4,
+                bump: 254,
+                label,
+            },
+        )
+    }
+
+    fn bucket_v4(last_request_ts: i64) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV4 {
+                version: 4,
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: 40,
+                flags: 0,
+                violations: 7,
+                config_epoch: 2,
+                burst_credits: 4,
+                last_request_ts,
+                bump: 254,
+                label: [0; 32],
+            },
+        )
+    }
+
+    fn bucket_v5(priority_count: u64) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV5 {
+                version: 5,
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: 40,
+                flags: 0,
+                violations: 7,
+                config_epoch: 2,
+                burst_credits: 4,
+                priority_count,
+                last_request_ts: 1_030,
+                bump: 254,
+                label: [0; 32],
+            },
+        )
+    }
+
+    fn bucket_v6(escrowed_count: u64, bonus_requests: u64) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV6 {
+                version: 6,
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: 40,
+                flags: BUCKET_FLAG_FROZEN,
+                violations: 7,
+                config_epoch: 2,
+                burst_credits: 4,
+                priority_count: 1,
+                escrowed_count,
+                bonus_requests,
+                last_request_ts: 1_030,
+                bump: 254,
+                label: [0; 32],
+            },
+        )
+    }
+
+    fn bucket_v7(violations: u64) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV7 {
+                version: 7,
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: 40,
+                flags: 0,
+                violations,
+                config_epoch: 2,
+                burst_credits: 4,
+                priority_count: 1,
+                escrowed_count: 2,
+                bonus_requests: 5,
+                last_nonce: 9,
+                last_request_ts: 1_030,
+                bump: 254,
+                label: [0; 32],
+            },
+        )
+    }
+
+    fn bucket_v8(last_nonce: u64) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV8 {
+                version: 8,
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: 40,
+                flags: 0,
+                violations: 6,
+                violations_updated_at: 1_010,
+                config_epoch: 2,
+                burst_credits: 4,
+                priority_count: 1,
+                escrowed_count: 2,
+                bonus_requests: 5,
+                last_nonce,
+                last_request_ts: 1_030,
+                bump: 254,
+                label: [0; 32],
+            },
+        )
+    }
+
+    fn bucket_v9(nonce_bitmap: u64) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV9 {
+                version: 9,
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: 40,
+                flags: 0,
+                violations: 6,
+                violations_updated_at: 1_010,
+                config_epoch: 2,
+                burst_credits: 4,
+                priority_count: 1,
+                escrowed_count: 2,
+                bonus_requests: 5,
+                last_nonce: 9,
+                nonce_bitmap,
+                last_request_ts: 1_030,
+                bump: 254,
+                label: [0; 32],
+            },
+        )
+    }
+
+    fn bucket_v10(refunded_count: u64) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV10 {
+                version: 10,
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: 40,
+                flags: 0,
+                violations: 6,
+                violations_updated_at: 1_010,
+                config_epoch: 2,
+                burst_credits: 4,
+                priority_count: 1,
+                escrowed_count: 2,
+                bonus_requests: 5,
+                last_nonce: 9,
+                nonce_bitmap: 0b11,
+                last_refund_nonce: 8,
+                refunded_count,
+                last_request_ts: 1_030,
+                bump: 254,
+                label: [0; 32],
+            },
+        )
+    }
+
+    fn bucket_v11(registered_by: Pubkey) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV11 {
+                version: 11,
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: 40,
+                flags: 0,
+                violations: 6,
+                violations_updated_at: 1_010,
+                config_epoch: 2,
+                burst_credits: 4,
+                priority_count: 1,
+                escrowed_count: 2,
+                bonus_requests: 5,
+                last_nonce: 9,
+                nonce_bitmap: 0b11,
+                last_refund_nonce: 8,
+                refunded_count: 3,
+                registered_by,
+                last_request_ts: 1_030,
+                bump: 254,
+                label: [0; 32],
+            },
+        )
+    }
+
+    fn bucket_v12(recovery_delegate: Pubkey) -> Vec<u8> {
+        with_discriminator(
+            ClientBucket::DISCRIMINATOR,
+            &ClientBucketV12 {
+                version: 12,
+                owner: owner(),
+                request_count: 3,
+                window_start: 1_000,
+                total_requests: 40,
+                flags: 0,
+                violations: 6,
+                violations_updated_at: 1_010,
+                config_epoch: 2,
+                burst_credits: 4,
+                priority_count: 1,
+                escrowed_count: 2,
+                bonus_requests: 5,
+                last_nonce: 9,
+                nonce_bitmap: 0b11,
+                last_refund_nonce: 8,
+                refunded_count: 3,
+                registered_by: Pubkey::new_from_array([9; 32]),
+                recovery_delegate,
+                last_request_ts: 1_030,
+                bump: 254,
+                label: [0; 32],
+            },
+        )
+    }
+
+    fn config_v0(is_paused: bool) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV0 {
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                is_paused,
+                bump: 253,
+            },
+        )
+    }
+
+    fn config_v1(is_paused: bool) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV1 {
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                is_paused,
+                config_epoch: 5,
+                bump: 253,
+            },
+        )
+    }
+
+    fn config_v2(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV2 {
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                reserved: [0; 32],
+            },
+        )
+    }
+
+    fn config_v3(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV3 {
+                version: 3,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+            },
+        )
+    }
+
+    fn config_v4(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV4 {
+                version: 4,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                reserved: [0; 32],
+            },
+        )
+    }
+
+    fn config_v5(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV5 {
+                version: 5,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                reserved: [0; 32],
+            },
+        )
+    }
+
+    fn config_v6(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV6 {
+                version: 6,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                reserved: [0; 16],
+            },
+        )
+    }
+
+    fn config_v7(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV7 {
+                version: 7,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                reserved: [0; 6],
+            },
+        )
+    }
+
+    fn config_v8(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV8 {
+                version: 8,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                reserved: [0; 6],
+            },
+        )
+    }
+
+    fn config_v9(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV9 {
+                version: 9,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                appeal_bond_lamports: 0,
+                appeal_cooldown_seconds: 0,
+                reserved: [0; 6],
+            },
+        )
+    }
+
+    fn config_v10(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV10 {
+                version: 10,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                appeal_bond_lamports: 0,
+                appeal_cooldown_seconds: 0,
+                paused_at: 0,
+                pause_reason: 0,
+                reserved: [0; 6],
+            },
+        )
+    }
+
+    fn config_v11(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV11 {
+                version: 11,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                appeal_bond_lamports: 0,
+                appeal_cooldown_seconds: 0,
+                paused_at: 0,
+                pause_reason: 0,
+                auto_unpause_at: 0,
+                config_preset: CONFIG_PRESET_CUSTOM,
+                violation_halflife_seconds: 0,
+                reserved: [0; 1],
+            },
+        )
+    }
+
+    fn config_v12(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV12 {
+                version: 12,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                appeal_bond_lamports: 0,
+                appeal_cooldown_seconds: 0,
+                paused_at: 0,
+                pause_reason: 0,
+                auto_unpause_at: 0,
+                config_preset: CONFIG_PRESET_CUSTOM,
+                violation_halflife_seconds: 0,
+                admin_action_window_seconds: 0,
+                max_admin_actions_per_window: 0,
+                admin_action_window_start: 0,
+                admin_actions_in_window: 0,
+                reserved: [0; 1],
+            },
+        )
+    }
+
+    fn config_v13(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV13 {
+                version: 13,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                appeal_bond_lamports: 0,
+                appeal_cooldown_seconds: 0,
+                paused_at: 0,
+                pause_reason: 0,
+                auto_unpause_at: 0,
+                config_preset: CONFIG_PRESET_CUSTOM,
+                violation_halflife_seconds: 0,
+                admin_action_window_seconds: 0,
+                max_admin_actions_per_window: 0,
+                admin_action_window_start: 0,
+                admin_actions_in_window: 0,
+                refund_authority: Pubkey::default(),
+                max_refunds_per_window: 0,
+                reserved: [0; 1],
+            },
+        )
+    }
+
+    fn config_v14(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV14 {
+                version: 14,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                appeal_bond_lamports: 0,
+                appeal_cooldown_seconds: 0,
+                paused_at: 0,
+                pause_reason: 0,
+                auto_unpause_at: 0,
+                config_preset: CONFIG_PRESET_CUSTOM,
+                violation_halflife_seconds: 0,
+                admin_action_window_seconds: 0,
+                max_admin_actions_per_window: 0,
+                admin_action_window_start: 0,
+                admin_actions_in_window: 0,
+                refund_authority: Pubkey::default(),
+                max_refunds_per_window: 0,
+                max_buckets_per_payer: 0,
+                reserved: [0; 1],
+            },
+        )
+    }
+
+    fn config_v15(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV15 {
+                version: 15,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                appeal_bond_lamports: 0,
+                appeal_cooldown_seconds: 0,
+                paused_at: 0,
+                pause_reason: 0,
+                auto_unpause_at: 0,
+                config_preset: CONFIG_PRESET_CUSTOM,
+                violation_halflife_seconds: 0,
+                admin_action_window_seconds: 0,
+                max_admin_actions_per_window: 0,
+                admin_action_window_start: 0,
+                admin_actions_in_window: 0,
+                refund_authority: Pubkey::default(),
+                max_refunds_per_window: 0,
+                max_buckets_per_payer: 0,
+                pause_grace_seconds: 0,
+                pause_grace_budget: 0,
+                grace_budget: 0,
+                reserved: [0; 1],
+            },
+        )
+    }
+
+    fn config_v16(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV16 {
+                version: 16,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                appeal_bond_lamports: 0,
+                appeal_cooldown_seconds: 0,
+                paused_at: 0,
+                pause_reason: 0,
+                auto_unpause_at: 0,
+                config_preset: CONFIG_PRESET_CUSTOM,
+                violation_halflife_seconds: 0,
+                admin_action_window_seconds: 0,
+                max_admin_actions_per_window: 0,
+                admin_action_window_start: 0,
+                admin_actions_in_window: 0,
+                refund_authority: Pubkey::default(),
+                max_refunds_per_window: 0,
+                max_buckets_per_payer: 0,
+                pause_grace_seconds: 0,
+                pause_grace_budget: 0,
+                grace_budget: 0,
+                inactivity_forgiveness_seconds: 0,
+                reserved: [0; 1],
+            },
+        )
+    }
+
+    fn config_v17(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV17 {
+                version: 17,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                appeal_bond_lamports: 0,
+                appeal_cooldown_seconds: 0,
+                paused_at: 0,
+                pause_reason: 0,
+                auto_unpause_at: 0,
+                config_preset: CONFIG_PRESET_CUSTOM,
+                violation_halflife_seconds: 0,
+                admin_action_window_seconds: 0,
+                max_admin_actions_per_window: 0,
+                admin_action_window_start: 0,
+                admin_actions_in_window: 0,
+                refund_authority: Pubkey::default(),
+                max_refunds_per_window: 0,
+                max_buckets_per_payer: 0,
+                pause_grace_seconds: 0,
+                pause_grace_budget: 0,
+                grace_budget: 0,
+                inactivity_forgiveness_seconds: 0,
+                min_client_balance_lamports: 0,
+                reserved: [0; 1],
+            },
+        )
+    }
+
+    fn config_v18(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV18 {
+                version: 18,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                appeal_bond_lamports: 0,
+                appeal_cooldown_seconds: 0,
+                paused_at: 0,
+                pause_reason: 0,
+                auto_unpause_at: 0,
+                config_preset: CONFIG_PRESET_CUSTOM,
+                violation_halflife_seconds: 0,
+                admin_action_window_seconds: 0,
+                max_admin_actions_per_window: 0,
+                admin_action_window_start: 0,
+                admin_actions_in_window: 0,
+                refund_authority: Pubkey::default(),
+                max_refunds_per_window: 0,
+                max_buckets_per_payer: 0,
+                pause_grace_seconds: 0,
+                pause_grace_budget: 0,
+                grace_budget: 0,
+                inactivity_forgiveness_seconds: 0,
+                min_client_balance_lamports: 0,
+                recovery_inactive_seconds: 0,
+                reserved: [0; 1],
+            },
+        )
+    }
+
+    fn config_v19(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV19 {
+                version: 19,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                appeal_bond_lamports: 0,
+                appeal_cooldown_seconds: 0,
+                paused_at: 0,
+                pause_reason: 0,
+                auto_unpause_at: 0,
+                config_preset: CONFIG_PRESET_CUSTOM,
+                violation_halflife_seconds: 0,
+                admin_action_window_seconds: 0,
+                max_admin_actions_per_window: 0,
+                admin_action_window_start: 0,
+                admin_actions_in_window: 0,
+                refund_authority: Pubkey::default(),
+                max_refunds_per_window: 0,
+                max_buckets_per_payer: 0,
+                pause_grace_seconds: 0,
+                pause_grace_budget: 0,
+                grace_budget: 0,
+                inactivity_forgiveness_seconds: 0,
+                min_client_balance_lamports: 0,
+                recovery_inactive_seconds: 0,
+                proration_mode: 0,
+                previous_config: PreviousConfig::default(),
+                reserved: [0; 1],
+            },
+        )
+    }
+
+    fn config_v20(flags: u16) -> Vec<u8> {
+        with_discriminator(
+            GlobalConfig::DISCRIMINATOR,
+            &GlobalConfigV20 {
+                version: 20,
+                admin: owner(),
+                max_requests: 10,
+                window_seconds: 60,
+                burst_limit: 15,
+                flags,
+                config_epoch: 5,
+                bump: 253,
+                hook_program: Pubkey::default(),
+                limit_feeder: Pubkey::default(),
+                dynamic_floor: 0,
+                dynamic_ceiling: 0,
+                dynamic_max: 0,
+                dynamic_valid_until: 0,
+                council: [Pubkey::default(); MAX_COUNCIL_MEMBERS],
+                council_size: 0,
+                council_threshold: 0,
+                proposal_count: 0,
+                recovery_admin: Pubkey::default(),
+                recovery_eta: 0,
+                scheduled_pause_start: 0,
+                scheduled_pause_end: 0,
+                treasury: Pubkey::default(),
+                registration_fee_lamports: 0,
+                registration_cooldown_seconds: 0,
+                stale_after_seconds: 0,
+                gc_bounty_bps: 0,
+                discount_tiers: [DiscountTier::default(); MAX_DISCOUNT_TIERS],
+                discount_tier_count: 0,
+                appeal_bond_lamports: 0,
+                appeal_cooldown_seconds: 0,
+                paused_at: 0,
+                pause_reason: 0,
+                auto_unpause_at: 0,
+                config_preset: CONFIG_PRESET_CUSTOM,
+                violation_halflife_seconds: 0,
+                admin_action_window_seconds: 0,
+                max_admin_actions_per_window: 0,
+                admin_action_window_start: 0,
+                admin_actions_in_window: 0,
+                refund_authority: Pubkey::default(),
+                max_refunds_per_window: 0,
+                max_buckets_per_payer: 0,
+                pause_grace_seconds: 0,
+                pause_grace_budget: 0,
+                grace_budget: 0,
+                inactivity_forgiveness_seconds: 0,
+                min_client_balance_lamports: 0,
+                recovery_inactive_seconds: 0,
+                proration_mode: 0,
+                previous_config: PreviousConfig::default(),
+                denied_owner_programs: 0,
+                reserved: [0; 1],
+            },
+        )
+    }
+
+    // The migrated account must load through the normal Anchor path
+    fn reload_bucket(bucket: &ClientBucket) -> ClientBucket {
+        let mut data = Vec::new();
+        bucket.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + ClientBucket::INIT_SPACE);
+        ClientBucket::try_deserialize(&mut &data[..]).unwrap()
+    }
+
+    fn reload_config(config: &GlobalConfig) -> GlobalConfig {
+        let mut data = Vec::new();
+        config.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + GlobalConfig::INIT_SPACE);
+        GlobalConfig::try_deserialize(&mut &data[..]).unwrap()
+    }
+
+    #[test]
+    fn historical_layouts_have_their_recorded_lengths() {
+        assert_eq!(bucket_v0(false).len(), 66);
+        assert_eq!(bucket_v1(false, false).len(), 91);
+        assert_eq!(bucket_v2(0).len(), 123);
+        assert_eq!(bucket_v3([0; 32]).len(), 124);
+        assert_eq!(bucket_v4(0).len(), 132);
+        assert_eq!(bucket_v5(0).len(), 140);
+        assert_eq!(bucket_v6(0, 0).len(), 156);
+        assert_eq!(bucket_v7(0).len(), 164);
+        assert_eq!(bucket_v8(0).len(), 172);
+        assert_eq!(bucket_v9(0).len(), 180);
+        assert_eq!(bucket_v10(0).len(), 196);
+        assert_eq!(bucket_v11(Pubkey::default()).len(), 228);
+        assert_eq!(bucket_v12(Pubkey::default()).len(), 260);
+        assert_eq!(config_v0(false).len(), 66);
+        assert_eq!(config_v1(false).len(), 74);
+        assert_eq!(config_v2(0).len(), 107);
+        assert_eq!(config_v3(0).len(), 108);
+        assert_eq!(config_v4(0).len(), 204);
+        assert_eq!(config_v5(0).len(), 374);
+        assert_eq!(config_v6(0).len(), 414);
+        assert_eq!(config_v7(0).len(), 462);
+        assert_eq!(config_v8(0).len(), 503);
+        assert_eq!(config_v9(0).len(), 519);
+        assert_eq!(config_v10(0).len(), 528);
+        assert_eq!(config_v11(0).len(), 536);
+        assert_eq!(config_v12(0).len(), 560);
+        assert_eq!(config_v13(0).len(), 600);
+        assert_eq!(config_v14(0).len(), 604);
+        assert_eq!(config_v15(0).len(), 628);
+        assert_eq!(config_v16(0).len(), 636);
+        assert_eq!(config_v17(0).len(), 644);
+        assert_eq!(config_v18(0).len(), 652);
+        assert_eq!(config_v19(0).len(), 677);
+        assert_eq!(config_v20(0).len(), 681);
+        // Versioned layouts must never collide with an unversioned length
+        assert!(BUCKET_UNVERSIONED.iter().all(|(len, _)| *len != 8 + ClientBucket::INIT_SPACE));
+        assert!(CONFIG_UNVERSIONED.iter().all(|(len, _)| *len != 8 + GlobalConfig::INIT_SPACE));
+    }
+
+    #[test]
+    fn bucket_v0_runs_the_whole_chain() {
+        for blocked in [false, true] {
+            let bucket = reload_bucket(&migrate_bucket(&bucket_v0(blocked)).unwrap());
+            assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+            assert_eq!(bucket.owner, owner());
+            assert_eq!((bucket.request_count, bucket.window_start, bucket.total_requests), (3, 1_000, 40));
+            assert_eq!(bucket.is_blocked(), blocked);
+            assert!(!bucket.total_saturated());
+            assert_eq!((bucket.violations, bucket.config_epoch, bucket.burst_credits), (0, 0, 0));
+            assert_eq!(bucket.last_request_ts, 1_000);
+            assert_eq!(bucket.bump, 254);
+        }
+    }
+
+    #[test]
+    fn bucket_v1_keeps_every_flag_combination() {
+        for (saturated, blocked) in [(false, false), (true, false), (false, true), (true, true)] {
+            let bucket = reload_bucket(&migrate_bucket(&bucket_v1(saturated, blocked)).unwrap());
+            assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+            assert_eq!(bucket.total_saturated(), saturated);
+            assert_eq!(bucket.is_blocked(), blocked);
+            assert_eq!((bucket.violations, bucket.config_epoch, bucket.burst_credits), (7, 2, 4));
+            assert_eq!(bucket.total_requests, u64::MAX);
+            assert_eq!(bucket.label_text(), "");
+        }
+    }
+
+    #[test]
+    fn bucket_v2_keeps_its_flags() {
+        let flags = BUCKET_FLAG_BLOCKED | BUCKET_FLAG_TOTAL_SATURATED;
+        let bucket = reload_bucket(&migrate_bucket(&bucket_v2(flags)).unwrap());
+        assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+        assert_eq!(bucket.flags, flags);
+        assert_eq!(bucket.bump, 254);
+    }
+
+    #[test]
+    fn bucket_v3_keeps_its_label_and_dates_its_last_request() {
+        let mut label = [0; 32];
+        label[..4].copy_from_slice(b"bot7");
+        let bucket = reload_bucket(&migrate_bucket(&bucket_v3(label)).unwrap());
+        assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+        assert_eq!(bucket.label_text(), "bot7");
+        assert!(bucket.is_blocked());
+        assert_eq!((bucket.window_start, bucket.last_request_ts), (1_000, 1_000));
+        assert_eq!((bucket.request_count, bucket.total_requests, bucket.violations), (3, 40, 7));
+        assert_eq!(bucket.priority_count, 0);
+    }
+
+    #[test]
+    fn bucket_v4_starts_with_no_priority_units() {
+        let bucket = reload_bucket(&migrate_bucket(&bucket_v4(1_030)).unwrap());
+        assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+        assert_eq!(bucket.last_request_ts, 1_030);
+        assert_eq!((bucket.request_count, bucket.burst_credits, bucket.priority_count), (3, 4, 0));
+    }
+
+    #[test]
+    fn bucket_v5_keeps_its_priority_units_and_starts_untraded() {
+        let bucket = reload_bucket(&migrate_bucket(&bucket_v5(2)).unwrap());
+        assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+        assert_eq!((bucket.request_count, bucket.priority_count), (3, 2));
+        assert_eq!((bucket.escrowed_count, bucket.bonus_requests), (0, 0));
+        assert_eq!(bucket.last_request_ts, 1_030);
+    }
+
+    #[test]
+    fn bucket_v6_keeps_its_trades_and_starts_with_no_nonce() {
+        let bucket = reload_bucket(&migrate_bucket(&bucket_v6(2, 5)).unwrap());
+        assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+        assert_eq!((bucket.escrowed_count, bucket.bonus_requests), (2, 5));
+        assert_eq!((bucket.priority_count, bucket.last_request_ts), (1, 1_030));
+        assert!(bucket.is_frozen());
+        assert_eq!(bucket.last_nonce, 0);
+    }
+
+    #[test]
+    fn bucket_v7_keeps_its_violations_undecayed() {
+        let bucket = reload_bucket(&migrate_bucket(&bucket_v7(12)).unwrap());
+        assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+        assert_eq!((bucket.violations, bucket.violations_updated_at), (12, 0));
+        assert_eq!((bucket.last_nonce, bucket.bonus_requests), (9, 5));
+    }
+
+    #[test]
+    fn bucket_v8_only_takes_nonces_above_its_last() {
+        let bucket = reload_bucket(&migrate_bucket(&bucket_v8(9)).unwrap());
+        assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+        assert_eq!((bucket.violations, bucket.violations_updated_at), (6, 1_010));
+        assert_eq!((bucket.last_nonce, bucket.nonce_bitmap), (9, u64::MAX));
+        for nonce in 0..=9 {
+            assert!(!crate::nonce::nonce_available(bucket.last_nonce, bucket.nonce_bitmap, nonce));
+        }
+        assert!(crate::nonce::nonce_available(bucket.last_nonce, bucket.nonce_bitmap, 10));
+    }
+
+    #[test]
+    fn bucket_v9_keeps_its_nonce_window() {
+        let bucket = reload_bucket(&migrate_bucket(&bucket_v9(0b101)).unwrap());
+        assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+        assert_eq!((bucket.last_nonce, bucket.nonce_bitmap), (9, 0b101));
+        assert_eq!((bucket.request_count, bucket.priority_count, bucket.escrowed_count), (3, 1, 2));
+        assert_eq!((bucket.last_refund_nonce, bucket.refunded_count), (0, 0));
+    }
+
+    #[test]
+    fn bucket_v10_keeps_its_refunds_and_has_no_known_payer() {
+        let bucket = reload_bucket(&migrate_bucket(&bucket_v10(3)).unwrap());
+        assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+        assert_eq!((bucket.last_refund_nonce, bucket.refunded_count), (8, 3));
+        assert_eq!((bucket.last_nonce, bucket.nonce_bitmap), (9, 0b11));
+        assert_eq!(bucket.registered_by, Pubkey::default());
+    }
+
+    #[test]
+    fn bucket_v11_keeps_its_payer_and_has_no_recovery_delegate() {
+        let payer = Pubkey::new_from_array([9; 32]);
+        let bucket = reload_bucket(&migrate_bucket(&bucket_v11(payer)).unwrap());
+        assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+        assert_eq!(bucket.registered_by, payer);
+        assert_eq!((bucket.refunded_count, bucket.last_request_ts), (3, 1_030));
+        assert_eq!(bucket.recovery_delegate, Pubkey::default());
+        assert_eq!(migrate_bucket(&bucket_v0(false)).unwrap().recovery_delegate, Pubkey::default());
+    }
+
+    #[test]
+    fn bucket_v12_keeps_its_recovery_delegate_and_has_no_event_tag() {
+        let delegate = Pubkey::new_from_array([4; 32]);
+        let bucket = reload_bucket(&migrate_bucket(&bucket_v12(delegate)).unwrap());
+        assert_eq!(bucket.version, CLIENT_BUCKET_VERSION);
+        assert_eq!(bucket.recovery_delegate, delegate);
+        assert_eq!((bucket.registered_by, bucket.last_request_ts), (Pubkey::new_from_array([9; 32]), 1_030));
+        assert_eq!(bucket.event_tag, [0; 16]);
+        assert_eq!(migrate_bucket(&bucket_v11(delegate)).unwrap().event_tag, [0; 16]);
+    }
+
+    #[test]
+    fn config_v7_keeps_its_fee_and_gc_policy() {
+        let mut data = config_v7(0);
+        let mut v7 = GlobalConfigV7::deserialize(&mut &data[8..]).unwrap();
+        v7.treasury = owner();
+        v7.registration_fee_lamports = 5_000;
+        v7.stale_after_seconds = 86_400;
+        v7.gc_bounty_bps = 250;
+        data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v7);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert_eq!((config.treasury, config.registration_fee_lamports), (owner(), 5_000));
+        assert_eq!((config.stale_after_seconds, config.gc_bounty_bps), (86_400, 250));
+        assert_eq!(config.discount_tier_count, 0);
+    }
+
+    #[test]
+    fn config_v8_keeps_its_discount_tiers() {
+        let mut v8 = GlobalConfigV8::deserialize(&mut &config_v8(0)[8..]).unwrap();
+        v8.discount_tiers[0] = DiscountTier { min_amount: 100, discount_bps: 1_000 };
+        v8.discount_tier_count = 1;
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v8);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert_eq!(config.discount_tiers(), &[DiscountTier { min_amount: 100, discount_bps: 1_000 }]);
+        assert_eq!(config.appeal_bond_lamports, 0);
+    }
+
+    #[test]
+    fn config_v9_keeps_its_appeal_policy_and_its_pause() {
+        let mut v9 = GlobalConfigV9::deserialize(&mut &config_v9(CONFIG_FLAG_PAUSED)[8..]).unwrap();
+        v9.appeal_bond_lamports = 1_000;
+        v9.appeal_cooldown_seconds = 60;
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v9);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert_eq!((config.appeal_bond_lamports, config.appeal_cooldown_seconds), (1_000, 60));
+        assert!(config.is_paused());
+        assert_eq!((config.paused_at, config.pause_reason), (0, PAUSE_REASON_UNSPECIFIED));
+    }
+
+    #[test]
+    fn config_v10_keeps_its_pause_record() {
+        let mut v10 = GlobalConfigV10::deserialize(&mut &config_v10(CONFIG_FLAG_PAUSED)[8..]).unwrap();
+        v10.paused_at = 1_000;
+        v10.pause_reason = PAUSE_REASON_MIGRATION;
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v10);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert!(config.is_paused());
+        assert_eq!((config.paused_at, config.pause_reason), (1_000, PAUSE_REASON_MIGRATION));
+        assert_eq!(config.auto_unpause_at, 0);
+    }
+
+    #[test]
+    fn config_v11_keeps_its_preset_and_decay() {
+        let mut v11 = GlobalConfigV11::deserialize(&mut &config_v11(CONFIG_FLAG_PAUSED)[8..]).unwrap();
+        v11.auto_unpause_at = 2_000;
+        v11.config_preset = CONFIG_PRESET_STRICT;
+        v11.violation_halflife_seconds = 3_600;
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v11);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert_eq!(config.auto_unpause_at, 2_000);
+        assert_eq!((config.config_preset, config.violation_halflife_seconds), (CONFIG_PRESET_STRICT, 3_600));
+        assert!(!config.admin_throttle_enabled());
+        assert_eq!((config.admin_action_window_start, config.admin_actions_in_window), (0, 0));
+    }
+
+    #[test]
+    fn config_v12_keeps_its_admin_throttle() {
+        let mut v12 = GlobalConfigV12::deserialize(&mut &config_v12(0)[8..]).unwrap();
+        v12.admin_action_window_seconds = 3_600;
+        v12.max_admin_actions_per_window = 5;
+        v12.admin_action_window_start = 1_000;
+        v12.admin_actions_in_window = 2;
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v12);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert!(config.admin_throttle_enabled());
+        assert_eq!((config.admin_action_window_seconds, config.max_admin_actions_per_window), (3_600, 5));
+        assert_eq!((config.admin_action_window_start, config.admin_actions_in_window), (1_000, 2));
+        assert_eq!((config.refund_authority, config.max_refunds_per_window), (Pubkey::default(), 0));
+    }
+
+    #[test]
+    fn config_v13_keeps_its_refund_authority() {
+        let mut v13 = GlobalConfigV13::deserialize(&mut &config_v13(0)[8..]).unwrap();
+        v13.refund_authority = owner();
+        v13.max_refunds_per_window = 4;
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v13);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert_eq!((config.refund_authority, config.max_refunds_per_window), (owner(), 4));
+        assert_eq!(config.max_buckets_per_payer, 0);
+    }
+
+    #[test]
+    fn config_v14_keeps_its_payer_cap_and_has_no_grace() {
+        let mut v14 = GlobalConfigV14::deserialize(&mut &config_v14(CONFIG_FLAG_PAUSED)[8..]).unwrap();
+        v14.max_buckets_per_payer = 3;
+        v14.paused_at = 1_000;
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v14);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert_eq!(config.max_buckets_per_payer, 3);
+        assert!(config.is_paused());
+        assert_eq!((config.pause_grace_seconds, config.pause_grace_budget, config.grace_budget), (0, 0, 0));
+    }
+
+    #[test]
+    fn config_v15_keeps_its_pause_grace_and_forgives_nothing() {
+        let mut v15 = GlobalConfigV15::deserialize(&mut &config_v15(CONFIG_FLAG_PAUSED)[8..]).unwrap();
+        v15.pause_grace_seconds = 30;
+        v15.pause_grace_budget = 10;
+        v15.grace_budget = 4;
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v15);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert_eq!((config.pause_grace_seconds, config.pause_grace_budget, config.grace_budget), (30, 10, 4));
+        assert_eq!(config.inactivity_forgiveness_seconds, 0);
+    }
+
+    #[test]
+    fn config_v20_keeps_its_denylist_count_and_rotates_nothing() {
+        let mut v20 = GlobalConfigV20::deserialize(&mut &config_v20(0)[8..]).unwrap();
+        v20.denied_owner_programs = 3;
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v20);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert_eq!(config.denied_owner_programs, 3);
+        assert!(config.pending_authorities.iter().all(|pending| !pending.is_scheduled()));
+    }
+
+    #[test]
+    fn config_v19_keeps_its_proration_and_denylists_nothing() {
+        let mut v19 = GlobalConfigV19::deserialize(&mut &config_v19(0)[8..]).unwrap();
+        v19.proration_mode = PRORATION_PRORATE;
+        v19.previous_config = PreviousConfig { max_requests: 10, window_seconds: 60, config_epoch: 4 };
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v19);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert_eq!(config.proration_mode, PRORATION_PRORATE);
+        assert_eq!(config.previous_config, v19.previous_config);
+        assert_eq!(config.denied_owner_programs, 0);
+    }
+
+    #[test]
+    fn config_v18_keeps_its_recovery_period_and_does_not_prorate() {
+        let mut v18 = GlobalConfigV18::deserialize(&mut &config_v18(0)[8..]).unwrap();
+        v18.recovery_inactive_seconds = 7_200;
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v18);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert_eq!(config.recovery_inactive_seconds, 7_200);
+        assert_eq!(config.proration_mode, PRORATION_KEEP);
+        assert_eq!(config.previous_config, PreviousConfig::default());
+    }
+
+    #[test]
+    fn config_v17_keeps_its_minimum_balance_and_has_recovery_off() {
+        let mut v17 = GlobalConfigV17::deserialize(&mut &config_v17(0)[8..]).unwrap();
+        v17.min_client_balance_lamports = 5_000;
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v17);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert_eq!(config.min_client_balance_lamports, 5_000);
+        assert_eq!(config.recovery_inactive_seconds, 0);
+    }
+
+    #[test]
+    fn config_v16_keeps_its_forgiveness_and_has_no_minimum_balance() {
+        let mut v16 = GlobalConfigV16::deserialize(&mut &config_v16(0)[8..]).unwrap();
+        v16.inactivity_forgiveness_seconds = 86_400;
+        let data = with_discriminator(GlobalConfig::DISCRIMINATOR, &v16);
+
+        let config = reload_config(&migrate_config(&data).unwrap());
+        assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+        assert_eq!(config.inactivity_forgiveness_seconds, 86_400);
+        assert_eq!(config.min_client_balance_lamports, 0);
+    }
+
+    #[test]
+    fn config_runs_the_chain_from_every_version() {
+        for paused in [false, true] {
+            let from_v0 = reload_config(&migrate_config(&config_v0(paused)).unwrap());
+            assert_eq!(from_v0.is_paused(), paused);
+            assert_eq!(from_v0.config_epoch, 0);
+
+            let from_v1 = reload_config(&migrate_config(&config_v1(paused)).unwrap());
+            assert_eq!(from_v1.is_paused(), paused);
+            assert_eq!(from_v1.config_epoch, 5);
+
+            let flags = if paused { CONFIG_FLAG_PAUSED } else { 0 };
+            let from_v2 = reload_config(&migrate_config(&config_v2(flags)).unwrap());
+            assert_eq!(from_v2.is_paused(), paused);
+
+            let from_v3 = reload_config(&migrate_config(&config_v3(flags)).unwrap());
+            assert_eq!(from_v3.is_paused(), paused);
+            assert_eq!(from_v3.config_epoch, 5);
+
+            let from_v4 = reload_config(&migrate_config(&config_v4(flags)).unwrap());
+            assert_eq!(from_v4.is_paused(), paused);
+            assert_eq!(from_v4.config_epoch, 5);
+
+            let from_v5 = reload_config(&migrate_config(&config_v5(flags)).unwrap());
+            assert_eq!(from_v5.is_paused(), paused);
+            assert_eq!(from_v5.config_epoch, 5);
+
+            let from_v6 = reload_config(&migrate_config(&config_v6(flags)).unwrap());
+            assert_eq!(from_v6.is_paused(), paused);
+            assert_eq!(from_v6.config_epoch, 5);
+
+            let from_v7 = reload_config(&migrate_config(&config_v7(flags)).unwrap());
+            assert_eq!(from_v7.is_paused(), paused);
+            assert_eq!(from_v7.config_epoch, 5);
+
+            let from_v8 = reload_config(&migrate_config(&config_v8(flags)).unwrap());
+            assert_eq!(from_v8.is_paused(), paused);
+            assert_eq!(from_v8.config_epoch, 5);
+
+            let from_v9 = reload_config(&migrate_config(&config_v9(flags)).unwrap());
+            assert_eq!(from_v9.is_paused(), paused);
+            assert_eq!(from_v9.config_epoch, 5);
+
+            let from_v10 = reload_config(&migrate_config(&config_v10(flags)).unwrap());
+            assert_eq!(from_v10.is_paused(), paused);
+            assert_eq!(from_v10.config_epoch, 5);
+
+            let from_v11 = reload_config(&migrate_config(&config_v11(flags)).unwrap());
+            assert_eq!(from_v11.is_paused(), paused);
+            assert_eq!(from_v11.config_epoch, 5);
+
+            let from_v12 = reload_config(&migrate_config(&config_v12(flags)).unwrap());
+            assert_eq!(from_v12.is_paused(), paused);
+            assert_eq!(from_v12.config_epoch, 5);
+
+            let from_v13 = reload_config(&migrate_config(&config_v13(flags)).unwrap());
+            assert_eq!(from_v13.is_paused(), paused);
+            assert_eq!(from_v13.config_epoch, 5);
+
+            let from_v14 = reload_config(&migrate_config(&config_v14(flags)).unwrap());
+            assert_eq!(from_v14.is_paused(), paused);
+            assert_eq!(from_v14.config_epoch, 5);
+
+            let from_v15 = reload_config(&migrate_config(&config_v15(flags)).unwrap());
+            assert_eq!(from_v15.is_paused(), paused);
+            assert_eq!(from_v15.config_epoch, 5);
+
+            let from_v16 = reload_config(&migrate_config(&config_v16(flags)).unwrap());
+            assert_eq!(from_v16.is_paused(), paused);
+            assert_eq!(from_v16.config_epoch, 5);
+
+            let from_v17 = reload_config(&migrate_config(&config_v17(flags)).unwrap());
+            assert_eq!(from_v17.is_paused(), paused);
+            assert_eq!(from_v17.config_epoch, 5);
+
+            let from_v18 = reload_config(&migrate_config(&config_v18(flags)).unwrap());
+            assert_eq!(from_v18.is_paused(), paused);
+            assert_eq!(from_v18.config_epoch, 5);
+
+            let from_v19 = reload_config(&migrate_config(&config_v19(flags)).unwrap());
+            assert_eq!(from_v19.is_paused(), paused);
+            assert_eq!(from_v19.config_epoch, 5);
+
+            let from_v20 = reload_config(&migrate_config(&config_v20(flags)).unwrap());
+            assert_eq!(from_v20.is_paused(), paused);
+            assert_eq!(from_v20.config_epoch, 5);
+
+            let migrated = [
+                from_v0, from_v1, from_v2, from_v3, from_v4, from_v5, from_v6, from_v7, from_v8, from_v9, from_v10,
+                from_v11, from_v12, from_v13, from_v14, from_v15, from_v16, from_v17, from_v18, from_v19, from_v20,
+            ];
+            for config in migrated {
+                assert_eq!(config.version, GLOBAL_CONFIG_VERSION);
+                assert_eq!(config.admin, owner());
+                assert_eq!((config.max_requests, config.window_seconds, config.burst_limit), (10, 60, 15));
+                assert_eq!(config.bump, 253);
+                assert!(!config.has_hook());
+                assert_eq!(config.limit_feeder, Pubkey::default());
+                assert_eq!(config.dynamic_valid_until, 0);
+                assert!(!config.has_council());
+                assert_eq!((config.council_threshold, config.proposal_count), (0, 0));
+                assert!(!config.recovery_pending());
+                assert_eq!(config.recovery_admin, Pubkey::default());
+                assert_eq!((config.scheduled_pause_start, config.scheduled_pause_end), (0, 0));
+                assert_eq!(config.treasury, Pubkey::default());
+                assert_eq!((config.registration_fee_lamports, config.registration_cooldown_seconds), (0, 0));
+                assert_eq!((config.stale_after_seconds, config.gc_bounty_bps), (0, 0));
+                assert!(config.discount_tiers().is_empty());
+                assert_eq!((config.appeal_bond_lamports, config.appeal_cooldown_seconds), (0, 0));
+                assert_eq!((config.paused_at, config.pause_reason), (0, PAUSE_REASON_UNSPECIFIED));
+                assert_eq!(config.auto_unpause_at, 0);
+                assert_eq!(config.config_preset, CONFIG_PRESET_CUSTOM);
+                assert_eq!(config.violation_halflife_seconds, 0);
+                assert!(!config.admin_throttle_enabled());
+                assert_eq!((config.refund_authority, config.max_refunds_per_window), (Pubkey::default(), 0));
+                assert!(!config.refunds_lower_totals());
+                assert_eq!(config.max_buckets_per_payer, 0);
+                assert_eq!((config.pause_grace_seconds, config.grace_budget), (0, 0));
+                assert_eq!(config.inactivity_forgiveness_seconds, 0);
+                assert_eq!(config.min_client_balance_lamports, 0);
+                assert_eq!(config.recovery_inactive_seconds, 0);
+                assert_eq!(config.reserved, [0; 1]);
+            }
+        }
+    }
+
+    #[test]
+    fn current_accounts_are_already_migrated() {
+        let bucket = migrate_bucket(&bucket_v2(0)).unwrap();
+        let mut data = Vec::new();
+        bucket.try_serialize(&mut data).unwrap();
+        assert_eq!(bucket_version(&data).unwrap(), CLIENT_BUCKET_VERSION);
+        let expected: Error = RateLimiterError::AlreadyMigrated.into();
+        assert_eq!(migrate_bucket(&data).err().unwrap(), expected);
+    }
+
+    #[test]
+    fn foreign_truncated_and_future_data_is_rejected() {
+        let expected: Error = RateLimiterError::InvalidAccountLayout.into();
+        let mut data = bucket_v1(false, false);
+        data.pop();
+        assert_eq!(migrate_bucket(&data).err().unwrap(), expected);
+        // A config is not a bucket
+        assert_eq!(migrate_bucket(&config_v1(false)).err().unwrap(), expected);
+        assert_eq!(migrate_bucket(&[]).err().unwrap(), expected);
+
+        let mut future = ClientBucket::DISCRIMINATOR.to_vec();
+        future.extend_from_slice(&[CLIENT_BUCKET_VERSION + 1; 116]);
+        assert_eq!(migrate_bucket(&future).err().unwrap(), expected);
+    }
+
+    #[test]
+    fn stats_gain_a_disabled_breaker() {
+        let v0 = with_discriminator(
+            GlobalStats::DISCRIMINATOR,
+            &GlobalStatsV0 {
+                total_clients: 12,
+                blocked_clients: 2,
+                total_requests: 900,
+                total_saturated: false,
+                window_rollovers: 40,
+                snapshot_interval_seconds: 60,
+                last_snapshot_at: 1_000,
+                bump: 253,
+            },
+        );
+        assert_eq!(v0.len(), STATS_V0_LEN);
+        let stats = migrate_stats(&v0).unwrap();
+        assert_eq!(stats.total_requests, 900);
+        assert_eq!(stats.snapshot_interval_seconds, 60);
+        assert_eq!(stats.bump, 253);
+        assert!(!stats.circuit_breaker_enabled());
+
+        let mut data = Vec::new();
+        stats.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), GlobalStats::LEN);
+        let expected: Error = RateLimiterError::AlreadyMigrated.into();
+        assert_eq!(migrate_stats(&data).err().unwrap(), expected);
+    }
+
+    #[test]
+    fn stats_v1_keep_their_breaker_and_start_counting_denials() {
+        let v1 = with_discriminator(
+            GlobalStats::DISCRIMINATOR,
+            &GlobalStatsV1 {
+                total_clients: 12,
+                blocked_clients: 2,
+                total_requests: 900,
+                total_saturated: false,
+                window_rollovers: 40,
+                snapshot_interval_seconds: 60,
+                last_snapshot_at: 1_000,
+                bump: 253,
+                circuit_breaker_threshold: 50,
+                circuit_breaker_window_seconds: 300,
+                rejection_window_start: 1_200,
+                rejection_count: 7,
+            },
+        );
+        assert_eq!(v1.len(), STATS_V1_LEN);
+        let stats = migrate_stats(&v1).unwrap();
+        assert_eq!((stats.circuit_breaker_threshold, stats.circuit_breaker_window_seconds), (50, 300));
+        assert_eq!((stats.rejection_window_start, stats.rejection_count), (1_200, 7));
+        assert_eq!((stats.total_requests, stats.total_denials), (900, 0));
+
+        let mut truncated = v1;
+        truncated.pop();
+        let expected: Error = RateLimiterError::InvalidAccountLayout.into();
+        assert_eq!(migrate_stats(&truncated).err().unwrap(), expected);
+    }
+
+    #[test]
+    fn stats_v2_keep_their_denials_and_start_an_empty_histogram() {
+        let v2 = with_discriminator(
+            GlobalStats::DISCRIMINATOR,
+            &GlobalStatsV2 {
+                total_clients: 12,
+                blocked_clients: 2,
+                total_requests: 900,
+                total_saturated: false,
+                window_rollovers: 40,
+                snapshot_interval_seconds: 60,
+                last_snapshot_at: 1_000,
+                bump: 253,
+                circuit_breaker_threshold: 50,
+                circuit_breaker_window_seconds: 300,
+                rejection_window_start: 1_200,
+                rejection_count: 7,
+                total_denials: 31,
+            },
+        );
+        assert_eq!(v2.len(), STATS_V2_LEN);
+        let stats = migrate_stats(&v2).unwrap();
+        assert_eq!((stats.window_rollovers, stats.total_denials), (40, 31));
+        assert_eq!(stats.usage_histogram, [0; USAGE_HISTOGRAM_BUCKETS]);
+
+        let mut data = Vec::new();
+        stats.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), GlobalStats::LEN);
+    }
+
+    #[test]
+    fn payer_meta_v0_keeps_its_cooldown_and_starts_counting() {
+        let v0 = with_discriminator(
+            PayerMeta::DISCRIMINATOR,
+            &PayerMetaV0 { payer: owner(), last_registration_ts: 1_000, bump: 252 },
+        );
+        assert_eq!(v0.len(), PAYER_META_V0_LEN);
+        let meta = migrate_payer_meta(&v0).unwrap();
+        assert_eq!((meta.payer, meta.last_registration_ts, meta.bump), (owner(), 1_000, 252));
+        assert_eq!(meta.buckets_created, 0);
+
+        let mut current = Vec::new();
+        meta.try_serialize(&mut current).unwrap();
+        let expected: Error = RateLimiterError::AlreadyMigrated.into();
+        assert_eq!(migrate_payer_meta(&current).err().unwrap(), expected);
+    }
+
+    #[test]
+    fn old_versions_require_migration() {
+        let expected: Error = RateLimiterError::MigrationRequired.into();
+        assert_eq!(require_supported(2, 3).err().unwrap(), expected);
+        assert!(require_supported(3, 3).is_ok());
+        assert_eq!(require_supported(3, MIN_CLIENT_BUCKET_VERSION).err().unwrap(), expected);
+    }
+}
@@ -0,0 +1,131 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::RateLimiterError;
+
+// Replay protection for consume_v2 nonces. Authorizations signed off chain can
+// land out of order, so a bucket keeps the highest nonce used and a bitmap of the
+// NONCE_WINDOW nonces up to it rather than the highest alone: bit i is set once
+// `highest - i` has been used. A nonce above the highest slides the window
+// forward, and any unused one inside it is still accepted. Below the window a
+// nonce can no longer be told from a used one, so it is refused as stale.
+
+pub const NONCE_WINDOW: u64 = u64::BITS as u64;
+
+// The bucket's (highest, bitmap) once `nonce` is used, or NonceAlreadyUsed for a
+// nonce the window has marked and NonceReused for one below it
+pub fn use_nonce(highest: u64, bitmap: u64, nonce: u64) -> Result<(u64, u64)> {
+    if nonce > highest {
+        let shift = nonce - highest;
+        let kept = if shift >= NONCE_WINDOW { 0 } else { bitmap << shift };
+        return Ok((nonce, kept | 1));
+    }
+    let age = highest - nonce;
+    require!(age < NONCE_WINDOW, RateLimiterError::NonceReused);
+    let bit = 1u64 << age;
+    require!(bitmap & bit == 0, RateLimiterError::NonceAlreadyUsed);
+    Ok((highest, bitmap | bit))
+}
+
+// Whether `nonce` would pass use_nonce, for clients picking one
+pub fn nonce_available(highest: u64, bitmap: u64, nonce: u64) -> bool {
+    nonce > highest || (highest - nonce < NONCE_WINDOW && bitmap & (1u64 << (highest - nonce)) == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::BTreeSet;
+
+    fn rejected_with(result: Result<(u64, u64)>, expected: RateLimiterError) -> bool {
+        let expected: Error = expected.into();
+        result.unwrap_err() == expected
+    }
+
+    fn used(nonces: &[u64]) -> (u64, u64) {
+        nonces.iter().fold((0, 0), |(highest, bitmap), &nonce| use_nonce(highest, bitmap, nonce).unwrap())
+    }
+
+    #[test]
+    fn a_fresh_bucket_takes_any_nonce_once() {
+        assert_eq!(use_nonce(0, 0, 0).unwrap(), (0, 1));
+        assert_eq!(use_nonce(0, 0, 7).unwrap(), (7, 1));
+        let (highest, bitmap) = used(&[7]);
+        assert!(rejected_with(use_nonce(highest, bitmap, 7), RateLimiterError::NonceAlreadyUsed));
+    }
+
+    #[test]
+    fn out_of_order_nonces_inside_the_window_are_accepted_once() {
+        let (highest, bitmap) = used(&[10, 12, 11, 3]);
+        assert_eq!(highest, 12);
+        for nonce in [3, 10, 11, 12] {
+            assert!(rejected_with(use_nonce(highest, bitmap, nonce), RateLimiterError::NonceAlreadyUsed));
+        }
+        for nonce in [0, 1, 2, 4, 9] {
+            assert!(use_nonce(highest, bitmap, nonce).is_ok(), "{}", nonce);
+        }
+    }
+
+    #[test]
+    fn the_window_reaches_63_below_the_highest() {
+        let (highest, bitmap) = used(&[100]);
+        assert!(use_nonce(highest, bitmap, 100 - 63).is_ok());
+        assert!(rejected_with(use_nonce(highest, bitmap, 100 - 64), RateLimiterError::NonceReused));
+        assert!(rejected_with(use_nonce(highest, bitmap, 0), RateLimiterError::NonceReused));
+    }
+
+    #[test]
+    fn sliding_forward_keeps_the_marks_still_inside() {
+        let (highest, bitmap) = used(&[100, 90]);
+        let (highest, bitmap) = use_nonce(highest, bitmap, 160).unwrap();
+        // 100 is 60 behind and still marked; 90, 70 behind, fell out with the slide
+        assert!(rejected_with(use_nonce(highest, bitmap, 100), RateLimiterError::NonceAlreadyUsed));
+        assert!(rejected_with(use_nonce(highest, bitmap, 90), RateLimiterError::NonceReused));
+        assert!(use_nonce(highest, bitmap, 120).is_ok());
+    }
+
+    #[test]
+    fn large_forward_jumps_clear_the_window() {
+        let (highest, bitmap) = used(&[5, 6, 7]);
+        assert_eq!(use_nonce(highest, bitmap, 7 + 64).unwrap(), (71, 1));
+        assert_eq!(use_nonce(highest, bitmap, u64::MAX).unwrap(), (u64::MAX, 1));
+        let (highest, bitmap) = used(&[u64::MAX]);
+        assert!(use_nonce(highest, bitmap, u64::MAX - 63).is_ok());
+        assert!(rejected_with(use_nonce(highest, bitmap, u64::MAX - 64), RateLimiterError::NonceReused));
+        assert!(rejected_with(use_nonce(highest, bitmap, u64::MAX), RateLimiterError::NonceAlreadyUsed));
+    }
+
+    #[test]
+    fn a_full_window_only_moves_forward() {
+        let (highest, bitmap) = (40, u64::MAX);
+        for nonce in 0..=40 {
+            assert!(use_nonce(highest, bitmap, nonce).is_err(), "{}", nonce);
+        }
+        assert!(use_nonce(highest, bitmap, 41).is_ok());
+    }
+
+    proptest! {
+        // Against the plain rule: a nonce is fresh if it was never used and is
+        // above, or fewer than NONCE_WINDOW below, the highest used so far
+        #[test]
+        fn matches_a_set_of_used_nonces(nonces in proptest::collection::vec(0u64..300, 1..200)) {
+            let (mut highest, mut bitmap) = (0, 0);
+            let mut seen = BTreeSet::new();
+            let mut top: Option<u64> = None;
+            for nonce in nonces {
+                let fresh = !seen.contains(&nonce)
+                    && top.is_none_or(|top| nonce > top || top - nonce < NONCE_WINDOW);
+                prop_assert_eq!(nonce_available(highest, bitmap, nonce), fresh);
+                match use_nonce(highest, bitmap, nonce) {
+                    Ok(next) => {
+                        prop_assert!(fresh);
+                        (highest, bitmap) = next;
+                        seen.insert(nonce);
+                        top = Some(top.map_or(nonce, |top| top.max(nonce)));
+                    }
+                    Err(_) => prop_assert!(!fresh),
+                }
+            }
+        }
+    }
+}
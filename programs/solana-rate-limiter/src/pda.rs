@@ -0,0 +1,227 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::RateLimiterError;
+
+// Addresses of every account the program derives, from the same seed constants
+// its `#[derive(Accounts)]` constraints use. Off-chain Rust clients and CPI
+// callers should derive through here rather than restate the seeds, so a new
+// namespace or index at the end of a seed list reaches them with the crate
+// update. Each returns `(address, bump)`, as `find_program_address` does.
+
+pub fn global_config_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GLOBAL_CONFIG_SEED], &crate::ID)
+}
+
+pub fn global_stats_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GLOBAL_STATS_SEED], &crate::ID)
+}
+
+pub fn schedule_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SCHEDULE_SEED], &crate::ID)
+}
+
+pub fn sponsor_pool_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SPONSOR_POOL_SEED], &crate::ID)
+}
+
+pub fn rebalance_policy_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REBALANCE_POLICY_SEED], &crate::ID)
+}
+
+pub fn leaderboard_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LEADERBOARD_SEED], &crate::ID)
+}
+
+pub fn pause_grace_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PAUSE_GRACE_SEED], &crate::ID)
+}
+
+// For handlers that check a PDA by hand instead of through a seeds constraint:
+// `key` must be the canonical address for `seeds` and `bump`, the one stored on
+// the account, its canonical bump. create_program_address alone would also take
+// an address made with any lower bump, and an account there would shadow the real
+// one. Anything else fails with `err`, the handler's own wrong-account error.
+pub fn assert_canonical(seeds: &[&[u8]], bump: u8, key: &Pubkey, err: RateLimiterError) -> Result<()> {
+    let (expected, canonical) = Pubkey::find_program_address(seeds, &crate::ID);
+    if !(*key == expected && bump == canonical) {
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+// Signs every hook CPI
+pub fn hook_authority_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[HOOK_AUTHORITY_SEED], &crate::ID)
+}
+
+pub fn client_bucket_address(owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CLIENT_BUCKET_SEED, owner.as_ref()], &crate::ID)
+}
+
+pub fn fast_bucket_address(owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FAST_BUCKET_SEED, owner.as_ref()], &crate::ID)
+}
+
+pub fn shard_parent_address(owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SHARD_PARENT_SEED, owner.as_ref()], &crate::ID)
+}
+
+// One of a sharded client's buckets, `index` counting from 0
+pub fn client_shard_address(owner: &Pubkey, index: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CLIENT_SHARD_SEED, owner.as_ref(), &[index]], &crate::ID)
+}
+
+pub fn payer_meta_address(payer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PAYER_META_SEED, payer.as_ref()], &crate::ID)
+}
+
+pub fn client_note_address(client: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CLIENT_NOTE_SEED, client.as_ref()], &crate::ID)
+}
+
+pub fn appeal_address(client: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[APPEAL_SEED, client.as_ref()], &crate::ID)
+}
+
+pub fn offer_address(seller: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[OFFER_SEED, seller.as_ref()], &crate::ID)
+}
+
+pub fn proposal_address(id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PROPOSAL_SEED, &id.to_le_bytes()], &crate::ID)
+}
+
+pub fn program_bucket_address(caller_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PROGRAM_BUCKET_SEED, caller_program.as_ref()], &crate::ID)
+}
+
+pub fn caller_allowlist_address(caller_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CALLER_ALLOWLIST_SEED, caller_program.as_ref()], &crate::ID)
+}
+
+pub fn program_denylist_address(program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PROGRAM_DENYLIST_SEED, program.as_ref()], &crate::ID)
+}
+
+pub fn snapshot_record_address(owner: &Pubkey) -> (Pubkey, u8) {
+    snapshot_record_address_in(&crate::ID, owner)
+}
+
+// The record an export wrote under `program`, for an import reading the old
+// deployment's
+pub fn snapshot_record_address_in(program: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SNAPSHOT_RECORD_SEED, owner.as_ref()], program)
+}
+
+// The odd one out: derived under the caller program's id, not ours. It is the
+// signer a caller program proves itself with to consume_as_program.
+pub fn caller_authority_address(caller_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CALLER_AUTHORITY_SEED], caller_program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Seeds written out as literals, so renaming a constant's value, which would
+    // strand every account already created under it, fails here
+    #[test]
+    fn helpers_match_the_deployed_seeds() {
+        let key = Pubkey::new_unique();
+        let derive = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &crate::ID);
+        assert_eq!(global_config_address(), derive(&[b"global-config"]));
+        assert_eq!(global_stats_address(), derive(&[b"global-stats"]));
+        assert_eq!(schedule_address(), derive(&[b"limit-schedule"]));
+        assert_eq!(sponsor_pool_address(), derive(&[b"sponsor-pool"]));
+        assert_eq!(rebalance_policy_address(), derive(&[b"rebalance-policy"]));
+        assert_eq!(leaderboard_address(), derive(&[b"leaderboard"]));
+        assert_eq!(pause_grace_address(), derive(&[b"pause-grace"]));
+        assert_eq!(hook_authority_address(), derive(&[b"hook-authority"]));
+        assert_eq!(client_bucket_address(&key), derive(&[b"client-bucket", key.as_ref()]));
+        assert_eq!(fast_bucket_address(&key), derive(&[b"fast-bucket", key.as_ref()]));
+        assert_eq!(shard_parent_address(&key), derive(&[b"shard-parent", key.as_ref()]));
+        assert_eq!(payer_meta_address(&key), derive(&[b"payer-meta", key.as_ref()]));
+        assert_eq!(client_note_address(&key), derive(&[b"client-note", key.as_ref()]));
+        assert_eq!(appeal_address(&key), derive(&[b"appeal", key.as_ref()]));
+        assert_eq!(offer_address(&key), derive(&[b"quota-offer", key.as_ref()]));
+        assert_eq!(program_bucket_address(&key), derive(&[b"program-bucket", key.as_ref()]));
+        assert_eq!(caller_allowlist_address(&key), derive(&[b"caller-allowlist", key.as_ref()]));
+        assert_eq!(program_denylist_address(&key), derive(&[b"program-denylist", key.as_ref()]));
+        assert_eq!(snapshot_record_address(&key), derive(&[b"snapshot-record", key.as_ref()]));
+        assert_eq!(
+            caller_authority_address(&key),
+            Pubkey::find_program_address(&[b"rate-limiter-caller"], &key)
+        );
+    }
+
+    // The encodings the constraints use: `shard.to_le_bytes()` on the u8 index and
+    // `proposal_count.to_le_bytes()` on the u64 id
+    #[test]
+    fn indexed_helpers_encode_like_the_constraints() {
+        let owner = Pubkey::new_unique();
+        let derive = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &crate::ID);
+        for index in [0u8, 1, 15] {
+            let expected = derive(&[CLIENT_SHARD_SEED, owner.as_ref(), index.to_le_bytes().as_ref()]);
+            assert_eq!(client_shard_address(&owner, index), expected);
+        }
+        assert_ne!(client_shard_address(&owner, 0), client_shard_address(&owner, 1));
+        assert_eq!(proposal_address(258), derive(&[PROPOSAL_SEED, &[2, 1, 0, 0, 0, 0, 0, 0]]));
+    }
+
+    // The highest bump below the canonical one that still gives an address off the
+    // curve: what a shadow account would be created at
+    fn shadow(seeds: &[&[u8]]) -> (Pubkey, u8) {
+        let (_, canonical) = Pubkey::find_program_address(seeds, &crate::ID);
+        (0..canonical)
+            .rev()
+            .find_map(|bump| {
+                let bump_seed = [bump];
+                let mut with_bump = seeds.to_vec();
+                with_bump.push(&bump_seed);
+                Pubkey::create_program_address(&with_bump, &crate::ID).ok().map(|key| (key, bump))
+            })
+            .expect("some lower bump is off the curve")
+    }
+
+    #[test]
+    fn only_the_canonical_address_and_bump_pass() {
+        let owner = Pubkey::new_unique();
+        let err = RateLimiterError::InvalidShard;
+        let expected: Error = err.into();
+        let families: [&[&[u8]]; 3] = [
+            &[CLIENT_BUCKET_SEED, owner.as_ref()],
+            &[CLIENT_SHARD_SEED, owner.as_ref(), &[3]],
+            &[PROGRAM_BUCKET_SEED, owner.as_ref()],
+        ];
+        for seeds in families {
+            let (key, bump) = Pubkey::find_program_address(seeds, &crate::ID);
+            assert!(assert_canonical(seeds, bump, &key, err).is_ok());
+
+            let (shadow, shadow_bump) = shadow(seeds);
+            assert_ne!(shadow, key);
+            assert_eq!(assert_canonical(seeds, shadow_bump, &shadow, err).unwrap_err(), expected);
+            // Neither half of the pair gets through with the other canonical
+            assert_eq!(assert_canonical(seeds, bump, &shadow, err).unwrap_err(), expected);
+            assert_eq!(assert_canonical(seeds, shadow_bump, &key, err).unwrap_err(), expected);
+        }
+    }
+
+    // Each kind of account sits at its own address for the same key
+    #[test]
+    fn kinds_do_not_share_addresses() {
+        let key = Pubkey::new_unique();
+        let mut addresses = vec![
+            client_bucket_address(&key).0,
+            fast_bucket_address(&key).0,
+            shard_parent_address(&key).0,
+            payer_meta_address(&key).0,
+            client_note_address(&key).0,
+            appeal_address(&key).0,
+            offer_address(&key).0,
+        ];
+        addresses.sort();
+        addresses.dedup();
+        assert_eq!(addresses.len(), 7);
+    }
+}
@@ -0,0 +1,126 @@
+use anchor_lang::prelude::*;
+use solana_program::hash::hashv;
+
+use crate::errors::RateLimiterError;
+use crate::state::SnapshotRecord;
+
+// A bucket's history, carried over to a redeployment under a new program id.
+// export_bucket_snapshot returns one, logs it and writes its hash to the owner's
+// SnapshotRecord; import_bucket_snapshot, built with the snapshot-import
+// feature, starts a bucket from it.
+//
+// `hash` is SHA-256 over the other fields and a fixed domain prefix, with the
+// exporting program's id among them, so a snapshot cannot be edited or replayed
+// as if it came from another deployment without the hash changing. The hash is
+// no secret, though: anyone can compute one for made-up numbers. What keeps a
+// client from importing inflated stats is the record: import reads it from the
+// old program id, which is the only program that can have written it, and takes
+// only the snapshot whose hash it holds.
+
+const SNAPSHOT_DOMAIN: &[u8] = b"solana-rate-limiter/bucket-snapshot/v1";
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BucketSnapshot {
+    pub program_id: Pubkey,     // deployment the bucket was exported from
+    pub owner: Pubkey,
+    pub total_requests: u64,
+    pub violations: u64,        // decayed to exported_at
+    pub exported_at: i64,
+    pub hash: [u8; 32],
+}
+
+impl BucketSnapshot {
+    pub fn new(program_id: Pubkey, owner: Pubkey, total_requests: u64, violations: u64, exported_at: i64) -> Self {
+        let mut snapshot = Self { program_id, owner, total_requests, violations, exported_at, hash: [0; 32] };
+        snapshot.hash = snapshot.expected_hash();
+        snapshot
+    }
+
+    pub fn expected_hash(&self) -> [u8; 32] {
+        hashv(&[
+            SNAPSHOT_DOMAIN,
+            self.program_id.as_ref(),
+            self.owner.as_ref(),
+            &self.total_requests.to_le_bytes(),
+            &self.violations.to_le_bytes(),
+            &self.exported_at.to_le_bytes(),
+        ])
+        .to_bytes()
+    }
+
+    pub fn verify(&self) -> Result<()> {
+        require!(self.hash == self.expected_hash(), RateLimiterError::SnapshotHashMismatch);
+        Ok(())
+    }
+
+    // verify, and that `record`, read from `source`'s records, holds this
+    // snapshot's hash. A snapshot from any other deployment, or an older export
+    // of the same bucket, fails.
+    pub fn verify_recorded(&self, source: &Pubkey, record: &SnapshotRecord) -> Result<()> {
+        self.verify()?;
+        require!(
+            self.program_id == *source && record.owner == self.owner && record.hash == self.hash,
+            RateLimiterError::SnapshotNotRecorded
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> BucketSnapshot {
+        BucketSnapshot::new(crate::ID, Pubkey::new_from_array([7; 32]), 12_345, 3, 1_700_000_000)
+    }
+
+    #[test]
+    fn an_exported_snapshot_verifies_after_a_round_trip() {
+        let bytes = snapshot().try_to_vec().unwrap();
+        let decoded = BucketSnapshot::try_from_slice(&bytes).unwrap();
+        assert_eq!(decoded, snapshot());
+        assert!(decoded.verify().is_ok());
+    }
+
+    #[test]
+    fn any_edited_field_breaks_the_hash() {
+        let expected: Error = RateLimiterError::SnapshotHashMismatch.into();
+        let original = snapshot();
+        let edits = [
+            BucketSnapshot { total_requests: original.total_requests + 1, ..original },
+            BucketSnapshot { violations: 0, ..original },
+            BucketSnapshot { owner: Pubkey::new_from_array([8; 32]), ..original },
+            BucketSnapshot { program_id: Pubkey::default(), ..original },
+            BucketSnapshot { exported_at: original.exported_at + 1, ..original },
+            BucketSnapshot { hash: [0; 32], ..original },
+        ];
+        for edited in edits {
+            assert_eq!(edited.verify().unwrap_err(), expected, "{:?}", edited);
+        }
+    }
+
+    fn record_of(snapshot: &BucketSnapshot) -> SnapshotRecord {
+        SnapshotRecord { owner: snapshot.owner, hash: snapshot.hash, exported_at: snapshot.exported_at, bump: 255 }
+    }
+
+    #[test]
+    fn only_the_recorded_export_imports() {
+        let original = snapshot();
+        let record = record_of(&original);
+        assert!(original.verify_recorded(&crate::ID, &record).is_ok());
+
+        // Inflated numbers with the hash recomputed pass verify but not the record
+        let expected: Error = RateLimiterError::SnapshotNotRecorded.into();
+        let forged = BucketSnapshot::new(original.program_id, original.owner, u64::MAX, 0, original.exported_at);
+        assert!(forged.verify().is_ok());
+        assert_eq!(forged.verify_recorded(&crate::ID, &record).unwrap_err(), expected);
+
+        // An export superseded by a later one no longer matches the record
+        let later = BucketSnapshot::new(original.program_id, original.owner, 12_346, 3, original.exported_at + 60);
+        assert_eq!(original.verify_recorded(&crate::ID, &record_of(&later)).unwrap_err(), expected);
+
+        // Records written by another deployment do not count
+        let elsewhere = BucketSnapshot::new(Pubkey::new_unique(), original.owner, 12_345, 3, original.exported_at);
+        assert_eq!(elsewhere.verify_recorded(&crate::ID, &record_of(&elsewhere)).unwrap_err(), expected);
+    }
+}
@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+// A blocked client's request to be unblocked, under ["appeal", wallet]. While it
+// is open the bond sits in the account on top of its rent. A denial leaves the
+// account in place, closed, so the cooldown can be timed from it; approval
+// closes it and refunds everything.
+#[account]
+#[derive(InitSpace)]
+pub struct Appeal {
+    pub client: Pubkey,
+    pub bond_lamports: u64,     // held while open, 0 otherwise
+    pub filed_at: i64,          // 0 while no appeal is open
+    pub denied_at: i64,         // last denial, 0 if none
+    pub bump: u8,
+}
+
+impl Appeal {
+    pub fn is_open(&self) -> bool {
+        self.filed_at != 0
+    }
+}
@@ -0,0 +1,12 @@
+use anchor_lang::prelude::*;
+
+// One per program the admin has approved to CPI into the consume instructions,
+// under ["caller-allowlist", program_id]. Existence is the approval: revoke_caller
+// closes it.
+#[account]
+#[derive(InitSpace)]
+pub struct CallerAllowlist {
+    pub program_id: Pubkey,     // approved caller program
+    pub allowed_at: i64,        // unix timestamp of allow_caller
+    pub bump: u8,
+}
@@ -1,21 +1,112 @@
 use anchor_lang::prelude::*;
 
+use super::config::set_flag;
+use crate::constants::*;
+
 #[account]
+#[derive(InitSpace, Debug, PartialEq, Eq)]
 pub struct ClientBucket {
+    pub version: u8,            // layout version, see migrations.rs
     pub owner: Pubkey,          // client's wallet
     pub request_count: u64,     // requests used in current window
     pub window_start: i64,      // when current window started (unix timestamp)
     pub total_requests: u64,    // lifetime request count (for analytics)
-    pub is_blocked: bool,       // admin can block a client
+    pub flags: u16,             // BUCKET_FLAG_* bits, see the accessors below
+    pub violations: u64,        // rejected consumes, halved per violation_halflife_seconds
+    pub violations_updated_at: i64, // time `violations` was last decayed from, 0 if never
+    pub config_epoch: u64,      // config epoch the current window was opened under
+    pub burst_credits: u64,     // banked unused capacity, spendable above max_requests
+    pub priority_count: u64,    // units of request_count priority consumes paid with credits
+    pub escrowed_count: u64,    // units of request_count held by, or sold through, a quota offer
+    pub bonus_requests: u64,    // allowance bought through accept_offer for this window
+    pub last_nonce: u64,        // highest nonce a consume_v2 has used, 0 if none
+    pub nonce_bitmap: u64,      // bit i set once last_nonce - i is used, see nonce.rs
+    pub last_refund_nonce: u64, // highest attestation nonce refund_request has used, 0 if none
+    pub refunded_count: u64,    // units refund_request gave back this window
+    pub registered_by: Pubkey,  // who paid the rent: the payer, the sponsor pool, or default if unknown
+    pub recovery_delegate: Pubkey, // may close the bucket once it idles past recovery_inactive_seconds, default if none
+    pub last_request_ts: i64,   // last accepted consume, or registration if none yet
     pub bump: u8,
+    pub label: [u8; 32],        // owner-set UTF-8, zero-padded; all zeros if unset
+    pub event_tag: [u8; 16],    // owner-set bytes copied into this bucket's events; all zeros if unset
 }
 
 impl ClientBucket {
-    pub const LEN: usize = 8    // discriminator
-        + 32                    // owner
-        + 8                     // request_count
-        + 8                     // window_start
-        + 8                     // total_requests
-        + 1                     // is_blocked
-        + 1;                    // bump
+    #[deprecated(note = "use `8 + ClientBucket::INIT_SPACE`")]
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+
+    // Admin can block a client
+    pub fn is_blocked(&self) -> bool {
+        self.flags & BUCKET_FLAG_BLOCKED != 0
+    }
+
+    pub fn set_blocked(&mut self, blocked: bool) {
+        set_flag(&mut self.flags, BUCKET_FLAG_BLOCKED, blocked);
+    }
+
+    // Admin hold on consumes that, unlike a block, keeps the client off the
+    // blocklist and lets it close its bucket
+    pub fn is_frozen(&self) -> bool {
+        self.flags & BUCKET_FLAG_FROZEN != 0
+    }
+
+    pub fn set_frozen(&mut self, frozen: bool) {
+        set_flag(&mut self.flags, BUCKET_FLAG_FROZEN, frozen);
+    }
+
+    // The rent came from the sponsor pool and goes back there when the bucket closes
+    pub fn is_sponsored(&self) -> bool {
+        self.flags & BUCKET_FLAG_SPONSORED != 0
+    }
+
+    pub fn set_sponsored(&mut self, sponsored: bool) {
+        set_flag(&mut self.flags, BUCKET_FLAG_SPONSORED, sponsored);
+    }
+
+    pub fn has_recovery_delegate(&self) -> bool {
+        self.recovery_delegate != Pubkey::default()
+    }
+
+    // The current window saw a gate_check denial, for the usage histogram
+    pub fn window_rejected(&self) -> bool {
+        self.flags & BUCKET_FLAG_WINDOW_REJECTED != 0
+    }
+
+    pub fn set_window_rejected(&mut self, rejected: bool) {
+        set_flag(&mut self.flags, BUCKET_FLAG_WINDOW_REJECTED, rejected);
+    }
+
+    // Admin exemption from min_client_balance_lamports
+    pub fn is_balance_exempt(&self) -> bool {
+        self.flags & BUCKET_FLAG_BALANCE_EXEMPT != 0
+    }
+
+    pub fn set_balance_exempt(&mut self, exempt: bool) {
+        set_flag(&mut self.flags, BUCKET_FLAG_BALANCE_EXEMPT, exempt);
+    }
+
+    // total_requests hit u64::MAX and is now a floor
+    pub fn total_saturated(&self) -> bool {
+        self.flags & BUCKET_FLAG_TOTAL_SATURATED != 0
+    }
+
+    pub fn set_total_saturated(&mut self, saturated: bool) {
+        set_flag(&mut self.flags, BUCKET_FLAG_TOTAL_SATURATED, saturated);
+    }
+
+    // The label without its zero padding; "" if unset or not valid UTF-8
+    pub fn label_text(&self) -> &str {
+        let len = self.label.iter().rposition(|&byte| byte != 0).map_or(0, |last| last + 1);
+        std::str::from_utf8(&self.label[..len]).unwrap_or("")
+    }
+
+    pub fn has_event_tag(&self) -> bool {
+        self.event_tag != [0; EVENT_TAG_LEN]
+    }
+
+    // Callers run validation::validate_label first; this only pads
+    pub fn set_label(&mut self, label: &str) {
+        self.label = [0; MAX_LABEL_LEN];
+        self.label[..label.len()].copy_from_slice(label.as_bytes());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,16 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::MAX_NOTE_LEN;
+
+// Support's annotation on a client, under ["client-note", wallet]. Kept out of
+// ClientBucket so the hot account stays small; no consume reads it.
+#[account]
+#[derive(InitSpace)]
+pub struct ClientNote {
+    pub client: Pubkey,
+    #[max_len(MAX_NOTE_LEN)]
+    pub note: String,
+    pub updated_at: i64,
+    pub updated_by: Pubkey,     // admin that last wrote it
+    pub bump: u8,
+}
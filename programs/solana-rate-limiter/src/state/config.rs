@@ -1,21 +1,277 @@
 use anchor_lang::prelude::*;
 
+use crate::constants::*;
+
 #[account]
+#[derive(InitSpace)]
 pub struct GlobalConfig {
+    pub version: u8,            // layout version, see migrations.rs
     pub admin: Pubkey,          // who controls this rate limiter
     pub max_requests: u64,      // requests allowed per window
     pub window_seconds: i64,    // window duration in seconds
-    pub burst_limit: u64,       // extra burst capacity
-    pub is_paused: bool,        // emergency pause
+    pub burst_limit: u64,       // per-window ceiling when spending burst credits
+    pub flags: u16,             // CONFIG_FLAG_* bits, see the accessors below
+    pub config_epoch: u64,      // bumped by update_config with reset_windows
     pub bump: u8,
+    pub hook_program: Pubkey,   // notified of blocks and rejections, default key if none
+    pub limit_feeder: Pubkey,   // may push temporary max_requests overrides, default key if none
+    pub dynamic_floor: u64,     // bounds the admin puts on feeder pushes
+    pub dynamic_ceiling: u64,
+    pub dynamic_max: u64,       // feeder's max_requests override, already clamped
+    pub dynamic_valid_until: i64, // override applies while now < this
+    pub council: [Pubkey; MAX_COUNCIL_MEMBERS], // first council_size seats are members
+    pub council_size: u8,       // 0: no council, the admin acts alone
+    pub council_threshold: u8,  // approvals execute_action needs
+    pub proposal_count: u64,    // next proposal id
+    pub recovery_admin: Pubkey, // admin a pending recovery installs
+    pub recovery_eta: i64,      // 0: no recovery pending; else earliest finalize time
+    pub scheduled_pause_start: i64, // paused while start <= now < end; both 0 if none
+    pub scheduled_pause_end: i64,
+    pub treasury: Pubkey,       // receives registration fees
+    pub registration_fee_lamports: u64, // charged to the payer of each registration, 0 = free
+    pub registration_cooldown_seconds: i64, // min gap between registrations by one payer, 0 = none
+    pub stale_after_seconds: i64, // idle time after which gc_bucket may close a bucket, 0 = never
+    pub gc_bounty_bps: u16,     // share of a collected bucket's rent paid to the cranker
+    pub discount_tiers: [DiscountTier; MAX_DISCOUNT_TIERS], // first discount_tier_count used, sorted by min_amount
+    pub discount_tier_count: u8, // 0: consume_amount charges the full amount
+    pub appeal_bond_lamports: u64, // posted by file_appeal, 0 = appeals off
+    pub appeal_cooldown_seconds: i64, // after a denial, before the client may file again
+    pub paused_at: i64,         // when the emergency pause started, 0 while unpaused
+    pub pause_reason: u8,       // PAUSE_REASON_* given for it
+    pub auto_unpause_at: i64,   // the emergency pause lapses from here on, 0 = manual only
+    pub config_preset: u8,      // CONFIG_PRESET_* the limits came from, for dashboards
+    pub violation_halflife_seconds: u32, // bucket violations halve once per this, 0 = never decay
+    pub admin_action_window_seconds: i64, // throttle on destructive admin actions, 0 = off
+    pub max_admin_actions_per_window: u32, // actions the admin may take per window, 0 = off
+    pub admin_action_window_start: i64, // current throttle window
+    pub admin_actions_in_window: u32, // actions taken in it
+    pub refund_authority: Pubkey, // may sign refund_request, default key if none
+    pub max_refunds_per_window: u64, // units refund_request may give a bucket per window
+    pub max_buckets_per_payer: u32, // registrations one payer may fund, 0 = no cap
+    pub pause_grace_seconds: i64, // consumes still pass this long after an emergency pause starts, 0 = none
+    pub pause_grace_budget: u64, // grace consumes allowed per pause
+    pub grace_budget: u64,      // grace consumes the current pause started with; PauseGrace counts the spent ones
+    pub inactivity_forgiveness_seconds: i64, // idle time after which violations are cleared, 0 = never
+    pub min_client_balance_lamports: u64, // lamports a client wallet must hold to consume, 0 = no minimum
+    pub recovery_inactive_seconds: i64, // idle time after which a recovery delegate may close a bucket, 0 = never
+    pub proration_mode: u8,     // PRORATION_*, for limit changes without reset_windows
+    pub previous_config: PreviousConfig, // limits before the last prorated change
+    pub denied_owner_programs: u32, // ProgramDenylist entries, see block_program_owners
+    pub pending_authorities: [PendingAuthority; AUTHORITY_KINDS], // rotation scheduled per AUTHORITY_* kind
+    pub reserved: [u8; 1],      // zeroed; room for new fields without a realloc
+}
+
+// consume_amount batches of at least min_amount units count discount_bps less
+// against the window, see logic::discounted_amount
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct DiscountTier {
+    pub min_amount: u64,
+    pub discount_bps: u16,
+}
+
+// The limits a prorated update_config replaced, and the epoch they applied in.
+// A bucket still on that epoch has its window carried over; see
+// logic::prorate_window.
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct PreviousConfig {
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub config_epoch: u64,
+}
+
+// A key set_authority has scheduled to take over one AUTHORITY_* role at
+// effective_at. The role's field keeps the old key, which is accepted until then.
+// An effective_at of 0 marks an empty slot.
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct PendingAuthority {
+    pub new_key: Pubkey,
+    pub effective_at: i64,
+}
+
+impl PendingAuthority {
+    pub fn is_scheduled(&self) -> bool {
+        self.effective_at != 0
+    }
 }
 
 impl GlobalConfig {
-    pub const LEN: usize = 8    // discriminator
-        + 32                    // admin
-        + 8                     // max_requests
-        + 8                     // window_seconds
-        + 8                     // burst_limit
-        + 1                     // is_paused
-        + 1;                    // bump
+    #[deprecated(note = "use `8 + GlobalConfig::INIT_SPACE`")]
+    pub const LEN: usize = 8 + Self::INIT_SPACE;
+
+    // Emergency pause
+    pub fn is_paused(&self) -> bool {
+        self.flags & CONFIG_FLAG_PAUSED != 0
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        set_flag(&mut self.flags, CONFIG_FLAG_PAUSED, paused);
+    }
+
+    // The emergency pause, unless its auto_unpause_at has passed. The flag itself
+    // is only cleared by the next write to the config.
+    pub fn emergency_pause_active(&self, now: i64) -> bool {
+        self.is_paused() && !(self.auto_unpause_at != 0 && now >= self.auto_unpause_at)
+    }
+
+    // The emergency pause or a scheduled window covering `now`. What consumes and
+    // registrations check; nothing has to run for a schedule to start or end.
+    pub fn pause_active(&self, now: i64) -> bool {
+        self.emergency_pause_active(now)
+            || (self.scheduled_pause_start <= now && now < self.scheduled_pause_end)
+    }
+
+    // Program-keyed mode: integrating programs consume from their ProgramBucket,
+    // and wallet buckets are only for direct calls, if wallet_fallback allows them
+    pub fn program_keyed(&self) -> bool {
+        self.flags & CONFIG_FLAG_PROGRAM_KEYED != 0
+    }
+
+    pub fn set_program_keyed(&mut self, on: bool) {
+        set_flag(&mut self.flags, CONFIG_FLAG_PROGRAM_KEYED, on);
+    }
+
+    pub fn wallet_fallback(&self) -> bool {
+        self.flags & CONFIG_FLAG_WALLET_FALLBACK != 0
+    }
+
+    pub fn set_wallet_fallback(&mut self, on: bool) {
+        set_flag(&mut self.flags, CONFIG_FLAG_WALLET_FALLBACK, on);
+    }
+
+    pub fn has_feeder(&self) -> bool {
+        self.limit_feeder != Pubkey::default()
+    }
+
+    // The feeder's max_requests, if it pushed one that has not expired. Clamped
+    // again on read in case the admin narrowed the bounds since the push.
+    pub fn dynamic_override(&self, now: i64) -> Option<u64> {
+        (self.has_feeder() && now < self.dynamic_valid_until)
+            .then(|| self.dynamic_max.max(self.dynamic_floor).min(self.dynamic_ceiling))
+    }
+
+    pub fn council_members(&self) -> &[Pubkey] {
+        &self.council[..self.council_size as usize]
+    }
+
+    // With a council, the sensitive admin actions only run through execute_action
+    pub fn has_council(&self) -> bool {
+        self.council_size > 0
+    }
+
+    pub fn is_council_member(&self, key: &Pubkey) -> bool {
+        self.council_members().contains(key)
+    }
+
+    pub fn recovery_pending(&self) -> bool {
+        self.recovery_eta != 0
+    }
+
+    // Set by the upgrade authority, so an admin key cannot lift its own limit
+    pub fn admin_throttle_enabled(&self) -> bool {
+        self.admin_action_window_seconds > 0 && self.max_admin_actions_per_window > 0
+    }
+
+    pub fn has_refund_authority(&self) -> bool {
+        self.refund_authority != Pubkey::default()
+    }
+
+    // The key an AUTHORITY_* role answers to at `now`: a scheduled rotation's key
+    // from its effective_at on, the role's own field before that
+    pub fn authority(&self, kind: u8, now: i64) -> Pubkey {
+        let pending = self.pending_authorities[kind as usize];
+        if pending.is_scheduled() && now >= pending.effective_at {
+            pending.new_key
+        } else {
+            match kind {
+                AUTHORITY_LIMIT_FEEDER => self.limit_feeder,
+                _ => self.refund_authority,
+            }
+        }
+    }
+
+    // Writes a rotation that has taken effect into the role's field and frees the
+    // slot, so the next one starts from the key in use
+    pub fn settle_authority(&mut self, kind: u8, now: i64) {
+        let current = self.authority(kind, now);
+        match kind {
+            AUTHORITY_LIMIT_FEEDER => self.limit_feeder = current,
+            _ => self.refund_authority = current,
+        }
+        let pending = &mut self.pending_authorities[kind as usize];
+        if pending.is_scheduled() && now >= pending.effective_at {
+            *pending = PendingAuthority::default();
+        }
+    }
+
+    // refund_request also takes the units back off total_requests, so the lifetime
+    // count only has what was actually served
+    pub fn refunds_lower_totals(&self) -> bool {
+        self.flags & CONFIG_FLAG_REFUND_TOTALS != 0
+    }
+
+    pub fn set_refunds_lower_totals(&mut self, enabled: bool) {
+        set_flag(&mut self.flags, CONFIG_FLAG_REFUND_TOTALS, enabled);
+    }
+
+    // Owners may set their bucket's event tag once; without this they can change it
+    // at will
+    pub fn event_tag_set_once(&self) -> bool {
+        self.flags & CONFIG_FLAG_EVENT_TAG_SET_ONCE != 0
+    }
+
+    pub fn set_event_tag_set_once(&mut self, on: bool) {
+        set_flag(&mut self.flags, CONFIG_FLAG_EVENT_TAG_SET_ONCE, on);
+    }
+
+    // Consumes have to pass the stats account, where a trip is recorded
+    pub fn circuit_breaker_on(&self) -> bool {
+        self.flags & CONFIG_FLAG_CIRCUIT_BREAKER != 0
+    }
+
+    pub fn set_circuit_breaker_on(&mut self, on: bool) {
+        set_flag(&mut self.flags, CONFIG_FLAG_CIRCUIT_BREAKER, on);
+    }
+
+    pub fn has_hook(&self) -> bool {
+        self.hook_program != Pubkey::default()
+    }
+
+    // Also call the hook for gate_check denials, not just blocks
+    pub fn hook_on_reject(&self) -> bool {
+        self.flags & CONFIG_FLAG_HOOK_ON_REJECT != 0
+    }
+
+    pub fn set_hook_on_reject(&mut self, on: bool) {
+        set_flag(&mut self.flags, CONFIG_FLAG_HOOK_ON_REJECT, on);
+    }
+
+    // Fail the instruction when the hook accounts are missing instead of skipping
+    pub fn hook_propagates(&self) -> bool {
+        self.flags & CONFIG_FLAG_HOOK_PROPAGATE != 0
+    }
+
+    pub fn set_hook_propagates(&mut self, on: bool) {
+        set_flag(&mut self.flags, CONFIG_FLAG_HOOK_PROPAGATE, on);
+    }
+
+    // A time-of-day schedule sets max_requests for wallet consumes
+    pub fn scheduled(&self) -> bool {
+        self.flags & CONFIG_FLAG_SCHEDULED != 0
+    }
+
+    pub fn set_scheduled(&mut self, on: bool) {
+        set_flag(&mut self.flags, CONFIG_FLAG_SCHEDULED, on);
+    }
+
+    pub fn discount_tiers(&self) -> &[DiscountTier] {
+        &self.discount_tiers[..self.discount_tier_count as usize]
+    }
+}
+
+pub(crate) fn set_flag(flags: &mut u16, flag: u16, on: bool) {
+    if on {
+        *flags |= flag;
+    } else {
+        *flags &= !flag;
+    }
 }
\ No newline at end of file
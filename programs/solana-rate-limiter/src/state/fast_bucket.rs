@@ -0,0 +1,88 @@
+use anchor_lang::prelude::*;
+
+use super::ClientBucket;
+use crate::constants::CLIENT_BUCKET_VERSION;
+
+// Zero-copy twin of ClientBucket for high-frequency consumers. Same counters in a
+// fixed layout with one u8 per flag, so consume_request_fast reads and writes it in
+// place instead of running Borsh over the whole account. Lives under its own seed
+// and discriminator.
+#[account(zero_copy)]
+#[repr(C)]
+pub struct FastBucket {
+    pub owner: Pubkey,          // client's wallet
+    pub request_count: u64,     // requests used in current window
+    pub window_start: i64,      // when current window started (unix timestamp)
+    pub total_requests: u64,    // lifetime request count (for analytics)
+    pub violations: u64,        // lifetime rejected consumes
+    pub config_epoch: u64,      // config epoch the current window was opened under
+    pub burst_credits: u64,     // banked unused capacity, spendable above max_requests
+    pub total_saturated: u8,    // bool: total_requests hit u64::MAX and is now a floor
+    pub is_blocked: u8,         // bool: admin can block a client
+    pub bump: u8,
+    pub _padding: [u8; 5],      // keeps the struct a multiple of 8 bytes
+}
+
+impl FastBucket {
+    pub const LEN: usize = 8    // discriminator
+        + 32                    // owner
+        + 8                     // request_count
+        + 8                     // window_start
+        + 8                     // total_requests
+        + 8                     // violations
+        + 8                     // config_epoch
+        + 8                     // burst_credits
+        + 1                     // total_saturated
+        + 1                     // is_blocked
+        + 1                     // bump
+        + 5;                    // _padding
+
+    // Copies out to the regular layout so both bucket kinds share one consume path.
+    // There is no room for violations_updated_at, so a fast bucket's violations
+    // never decay.
+    pub fn to_bucket(&self) -> ClientBucket {
+        let mut bucket = ClientBucket {
+            version: CLIENT_BUCKET_VERSION,
+            owner: self.owner,
+            request_count: self.request_count,
+            window_start: self.window_start,
+            total_requests: self.total_requests,
+            flags: 0,
+            violations: self.violations,
+            violations_updated_at: 0,
+            config_epoch: self.config_epoch,
+            burst_credits: self.burst_credits,
+            priority_count: 0,
+            escrowed_count: 0,
+            bonus_requests: 0,
+            last_nonce: 0,
+            nonce_bitmap: 0,
+            last_refund_nonce: 0,
+            refunded_count: 0,
+            registered_by: Pubkey::default(),
+            recovery_delegate: Pubkey::default(),
+            last_request_ts: 0,
+            bump: self.bump,
+            label: [0; 32],
+            event_tag: [0; 16],
+        };
+        bucket.set_total_saturated(self.total_saturated != 0);
+        bucket.set_blocked(self.is_blocked != 0);
+        bucket
+    }
+
+    pub fn store(&mut self, bucket: &ClientBucket) {
+        self.owner = bucket.owner;
+        self.request_count = bucket.request_count;
+        self.window_start = bucket.window_start;
+        self.total_requests = bucket.total_requests;
+        self.total_saturated = bucket.total_saturated() as u8;
+        self.is_blocked = bucket.is_blocked() as u8;
+        self.violations = bucket.violations;
+        self.config_epoch = bucket.config_epoch;
+        self.burst_credits = bucket.burst_credits;
+        self.bump = bucket.bump;
+    }
+}
+
+const _: () = assert!(FastBucket::LEN == 8 + std::mem::size_of::<FastBucket>());
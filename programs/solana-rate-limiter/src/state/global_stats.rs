@@ -0,0 +1,49 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::USAGE_HISTOGRAM_BUCKETS;
+
+#[account]
+pub struct GlobalStats {
+    pub total_clients: u64,              // buckets ever registered
+    pub blocked_clients: u64,            // buckets currently blocked
+    pub total_requests: u64,             // accepted consumes across all clients
+    pub total_saturated: bool,           // total_requests hit u64::MAX and is now a floor
+    pub window_rollovers: u64,           // windows closed across all clients
+    pub snapshot_interval_seconds: i64,  // min gap between emit_stats_snapshot calls
+    pub last_snapshot_at: i64,           // unix timestamp of the last snapshot
+    pub bump: u8,
+    pub circuit_breaker_threshold: u64,  // denials per window that stop consumes, 0 = off
+    pub circuit_breaker_window_seconds: i64, // 0 = off
+    pub rejection_window_start: i64,     // start of the breaker's current counting window
+    pub rejection_count: u64,            // gate_check denials in that window
+    pub total_denials: u64,              // gate_check denials ever, for rebalance_limits
+    pub usage_histogram: [u64; USAGE_HISTOGRAM_BUCKETS], // closed windows by fullness, see logic::usage_class
+}
+
+impl GlobalStats {
+    pub const LEN: usize = 8    // discriminator
+        + 8                     // total_clients
+        + 8                     // blocked_clients
+        + 8                     // total_requests
+        + 1                     // total_saturated
+        + 8                     // window_rollovers
+        + 8                     // snapshot_interval_seconds
+        + 8                     // last_snapshot_at
+        + 1                     // bump
+        + 8                     // circuit_breaker_threshold
+        + 8                     // circuit_breaker_window_seconds
+        + 8                     // rejection_window_start
+        + 8                     // rejection_count
+        + 8                     // total_denials
+        + 8 * USAGE_HISTOGRAM_BUCKETS; // usage_histogram
+
+    pub fn circuit_breaker_enabled(&self) -> bool {
+        self.circuit_breaker_threshold > 0 && self.circuit_breaker_window_seconds > 0
+    }
+
+    // The denial that tripped the breaker stays counted until set_circuit_breaker
+    // starts the count over
+    pub fn circuit_tripped(&self) -> bool {
+        self.circuit_breaker_enabled() && self.rejection_count > self.circuit_breaker_threshold
+    }
+}
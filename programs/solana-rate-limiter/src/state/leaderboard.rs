@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::LEADERBOARD_SIZE;
+
+// The buckets with the most total_requests, under ["leaderboard"], kept by the
+// permissionless submit_to_leaderboard crank. Advisory only: nothing reads it
+// back, and a bucket that was never submitted is simply not on it.
+#[account]
+#[derive(InitSpace)]
+pub struct Leaderboard {
+    pub entries: [LeaderboardEntry; LEADERBOARD_SIZE], // first entry_count used, highest total first
+    pub entry_count: u8,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct LeaderboardEntry {
+    pub owner: Pubkey,
+    pub total: u64, // the bucket's total_requests when last submitted
+}
+
+impl Leaderboard {
+    pub fn entries(&self) -> &[LeaderboardEntry] {
+        &self.entries[..self.entry_count as usize]
+    }
+
+    // What a newcomer has to beat: the last entry once the board is full, else 0
+    pub fn threshold(&self) -> u64 {
+        match self.entries() {
+            entries if entries.len() == LEADERBOARD_SIZE => entries[LEADERBOARD_SIZE - 1].total,
+            _ => 0,
+        }
+    }
+
+    // Puts `owner` on the board at `total` and returns its rank from 0. An owner
+    // already on it is updated in place whatever the total; a newcomer needs more
+    // than the threshold and, on a full board, takes the last entry's place. Ties
+    // keep the earlier submission ahead. None leaves the board unchanged.
+    pub fn submit(&mut self, owner: Pubkey, total: u64) -> Option<usize> {
+        let count = self.entry_count as usize;
+        let mut at = match self.entries().iter().position(|entry| entry.owner == owner) {
+            Some(at) => at,
+            None if total > self.threshold() => {
+                let at = count.min(LEADERBOARD_SIZE - 1);
+                self.entry_count = (at + 1) as u8;
+                at
+            }
+            None => return None,
+        };
+        self.entries[at] = LeaderboardEntry { owner, total };
+        while at > 0 && self.entries[at - 1].total < total {
+            self.entries.swap(at - 1, at);
+            at -= 1;
+        }
+        while at + 1 < self.entry_count as usize && self.entries[at + 1].total > total {
+            self.entries.swap(at, at + 1);
+            at += 1;
+        }
+        Some(at)
+    }
+}
@@ -1,5 +1,340 @@
 pub mod config;
 pub mod client_bucket;
+pub mod global_stats;
+pub mod fast_bucket;
+pub mod shard_parent;
+pub mod program_bucket;
+pub mod caller_allowlist;
+pub mod proposal;
+pub mod schedule;
+pub mod payer_meta;
+pub mod client_note;
+pub mod appeal;
+pub mod offer;
+pub mod sponsor_pool;
+pub mod rebalance_policy;
+pub mod leaderboard;
+pub mod program_denylist;
+pub mod snapshot_record;
+pub mod pause_grace;
 
 pub use config::*;
-pub use client_bucket::*;
\ No newline at end of file
+pub use client_bucket::*;
+pub use global_stats::*;
+pub use fast_bucket::*;
+pub use shard_parent::*;
+pub use program_bucket::*;
+pub use caller_allowlist::*;
+pub use proposal::*;
+pub use schedule::*;
+pub use payer_meta::*;
+pub use client_note::*;
+pub use appeal::*;
+pub use offer::*;
+pub use sponsor_pool::*;
+pub use rebalance_policy::*;
+pub use leaderboard::*;
+pub use program_denylist::*;
+pub use snapshot_record::*;
+pub use pause_grace::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::prelude::*;
+
+    // The space reserved at init must be exactly what a populated account
+    // serializes to, or the account fails to load at runtime
+    fn serialized_len<T: AccountSerialize>(account: &T) -> usize {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data.len()
+    }
+
+    #[test]
+    fn global_config_space_matches_serialized_size() {
+        let config = GlobalConfig {
+            version: u8::MAX,
+            admin: Pubkey::new_unique(),
+            max_requests: u64::MAX,
+            window_seconds: i64::MAX,
+            burst_limit: u64::MAX,
+            flags: u16::MAX,
+            config_epoch: u64::MAX,
+            bump: 255,
+            hook_program: Pubkey::new_unique(),
+            limit_feeder: Pubkey::new_unique(),
+            dynamic_floor: u64::MAX,
+            dynamic_ceiling: u64::MAX,
+            dynamic_max: u64::MAX,
+            dynamic_valid_until: i64::MAX,
+            council: [Pubkey::new_unique(); 5],
+            council_size: u8::MAX,
+            council_threshold: u8::MAX,
+            proposal_count: u64::MAX,
+            recovery_admin: Pubkey::new_unique(),
+            recovery_eta: i64::MAX,
+            scheduled_pause_start: i64::MIN,
+            scheduled_pause_end: i64::MAX,
+            treasury: Pubkey::new_unique(),
+            registration_fee_lamports: u64::MAX,
+            registration_cooldown_seconds: i64::MAX,
+            stale_after_seconds: i64::MAX,
+            gc_bounty_bps: u16::MAX,
+            discount_tiers: [DiscountTier { min_amount: u64::MAX, discount_bps: u16::MAX }; 4],
+            discount_tier_count: u8::MAX,
+            appeal_bond_lamports: u64::MAX,
+            appeal_cooldown_seconds: i64::MAX,
+            paused_at: i64::MAX,
+            pause_reason: u8::MAX,
+            auto_unpause_at: i64::MAX,
+            config_preset: u8::MAX,
+            violation_halflife_seconds: u32::MAX,
+            admin_action_window_seconds: i64::MAX,
+            max_admin_actions_per_window: u32::MAX,
+            admin_action_window_start: i64::MAX,
+            admin_actions_in_window: u32::MAX,
+            refund_authority: Pubkey::new_unique(),
+            max_refunds_per_window: u64::MAX,
+            max_buckets_per_payer: u32::MAX,
+            pause_grace_seconds: i64::MAX,
+            pause_grace_budget: u64::MAX,
+            grace_budget: u64::MAX,
+            inactivity_forgiveness_seconds: i64::MAX,
+            min_client_balance_lamports: u64::MAX,
+            recovery_inactive_seconds: i64::MAX,
+            proration_mode: u8::MAX,
+            previous_config: PreviousConfig { max_requests: u64::MAX, window_seconds: i64::MAX, config_epoch: u64::MAX },
+            denied_owner_programs: u32::MAX,
+            pending_authorities: [PendingAuthority { new_key: Pubkey::new_unique(), effective_at: i64::MAX }; 2],
+            reserved: [0xff; 1],
+        };
+        assert_eq!(serialized_len(&config), 8 + GlobalConfig::INIT_SPACE);
+        #[allow(deprecated)]
+        let len = GlobalConfig::LEN;
+        assert_eq!(len, 8 + GlobalConfig::INIT_SPACE);
+    }
+
+    #[test]
+    fn client_bucket_space_matches_serialized_size() {
+        let bucket = ClientBucket {
+            version: u8::MAX,
+            owner: Pubkey::new_unique(),
+            request_count: u64::MAX,
+            window_start: i64::MIN,
+            total_requests: u64::MAX,
+            flags: u16::MAX,
+            violations: u64::MAX,
+            violations_updated_at: i64::MIN,
+            config_epoch: u64::MAX,
+            burst_credits: u64::MAX,
+            priority_count: u64::MAX,
+            escrowed_count: u64::MAX,
+            bonus_requests: u64::MAX,
+            last_nonce: u64::MAX,
+            nonce_bitmap: u64::MAX,
+            last_refund_nonce: u64::MAX,
+            refunded_count: u64::MAX,
+            registered_by: Pubkey::new_unique(),
+            recovery_delegate: Pubkey::new_unique(),
+            last_request_ts: i64::MIN,
+            bump: 255,
+            label: [0xff; 32],
+            event_tag: [0xff; 16],
+        };
+        assert_eq!(serialized_len(&bucket), 8 + ClientBucket::INIT_SPACE);
+        #[allow(deprecated)]
+        let len = ClientBucket::LEN;
+        assert_eq!(len, 8 + ClientBucket::INIT_SPACE);
+    }
+
+    #[test]
+    fn labels_read_back_without_padding() {
+        let mut bucket = ClientBucket {
+            version: 0,
+            owner: Pubkey::new_unique(),
+            request_count: 0,
+            window_start: 0,
+            total_requests: 0,
+            flags: 0,
+            violations: 0,
+            violations_updated_at: 0,
+            config_epoch: 0,
+            burst_credits: 0,
+            priority_count: 0,
+            escrowed_count: 0,
+            bonus_requests: 0,
+            last_nonce: 0,
+            nonce_bitmap: 0,
+            last_refund_nonce: 0,
+            refunded_count: 0,
+            registered_by: Pubkey::default(),
+            recovery_delegate: Pubkey::default(),
+            last_request_ts: 0,
+            bump: 255,
+            label: [0; 32],
+            event_tag: [0; 16],
+        };
+        assert_eq!(bucket.label_text(), "");
+        bucket.set_label("indexer-eu");
+        assert_eq!(bucket.label_text(), "indexer-eu");
+        bucket.set_label(&"€".repeat(10));
+        assert_eq!(bucket.label_text(), "€".repeat(10));
+        bucket.set_label("");
+        assert_eq!(bucket.label, [0; 32]);
+        // Bytes written by an older build that are not UTF-8
+        bucket.label[0] = 0xff;
+        assert_eq!(bucket.label_text(), "");
+    }
+
+    #[test]
+    fn program_bucket_space_matches_serialized_size() {
+        let bucket = ProgramBucket {
+            version: u8::MAX,
+            program_id: Pubkey::new_unique(),
+            caller_authority: Pubkey::new_unique(),
+            max_requests: u64::MAX,
+            window_seconds: i64::MAX,
+            burst_limit: u64::MAX,
+            request_count: u64::MAX,
+            window_start: i64::MIN,
+            total_requests: u64::MAX,
+            flags: u16::MAX,
+            violations: u64::MAX,
+            config_epoch: u64::MAX,
+            burst_credits: u64::MAX,
+            bump: 255,
+            violations_updated_at: i64::MIN,
+            reserved: [0xff; 24],
+        };
+        assert_eq!(serialized_len(&bucket), 8 + ProgramBucket::INIT_SPACE);
+    }
+
+    #[test]
+    fn proposal_space_fits_the_largest_action() {
+        let proposal = Proposal {
+            id: u64::MAX,
+            proposer: Pubkey::new_unique(),
+            action: AdminAction::Freeze { client: Pubkey::new_unique(), frozen: true },
+            approvers: [Pubkey::new_unique(); 5],
+            approval_count: u8::MAX,
+            executed: true,
+            created_at: i64::MAX,
+            bump: 255,
+        };
+        assert_eq!(serialized_len(&proposal), 8 + Proposal::INIT_SPACE);
+    }
+
+    #[test]
+    fn proposal_approvals_are_unique_and_follow_the_council() {
+        let members = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut proposal = Proposal {
+            id: 0,
+            proposer: members[0],
+            action: AdminAction::Pause { paused: true },
+            approvers: [Pubkey::default(); 5],
+            approval_count: 0,
+            executed: false,
+            created_at: 0,
+            bump: 255,
+        };
+        assert!(proposal.approve(members[0]));
+        assert!(!proposal.approve(members[0]));
+        assert!(proposal.approve(members[1]));
+        assert_eq!(proposal.current_approvals(&members), 2);
+        // members[1] left the council after approving
+        assert_eq!(proposal.current_approvals(&[members[0], members[2]]), 1);
+    }
+
+    #[test]
+    fn client_note_space_fits_a_full_note() {
+        let note = ClientNote {
+            client: Pubkey::new_unique(),
+            note: "x".repeat(64),
+            updated_at: i64::MAX,
+            updated_by: Pubkey::new_unique(),
+            bump: 255,
+        };
+        assert_eq!(serialized_len(&note), 8 + ClientNote::INIT_SPACE);
+    }
+
+    #[test]
+    fn schedule_space_matches_serialized_size() {
+        let entry = ScheduleEntry { start_second_of_day: u32::MAX, max_requests: u64::MAX };
+        let schedule = Schedule { entries: [entry; 8], entry_count: u8::MAX, bump: 255 };
+        assert_eq!(serialized_len(&schedule), 8 + Schedule::INIT_SPACE);
+    }
+
+    // Pinned so a layout change is a deliberate diff here, not a surprise on devnet
+    #[test]
+    fn account_sizes_are_pinned() {
+        assert_eq!(8 + GlobalConfig::INIT_SPACE, 761);
+        assert_eq!(8 + ClientBucket::INIT_SPACE, 276);
+        assert_eq!(8 + ProgramBucket::INIT_SPACE, 180);
+        assert_eq!(8 + Proposal::INIT_SPACE, 253);
+        assert_eq!(8 + Schedule::INIT_SPACE, 106);
+        assert_eq!(8 + PayerMeta::INIT_SPACE, 53);
+        assert_eq!(8 + ClientNote::INIT_SPACE, 149);
+        assert_eq!(8 + Appeal::INIT_SPACE, 65);
+        assert_eq!(8 + Offer::INIT_SPACE, 73);
+        assert_eq!(8 + SponsorPool::INIT_SPACE, 33);
+        assert_eq!(8 + RebalancePolicy::INIT_SPACE, 71);
+        assert_eq!(8 + Leaderboard::INIT_SPACE, 410);
+        assert_eq!(8 + ProgramDenylist::INIT_SPACE, 49);
+        assert_eq!(8 + SnapshotRecord::INIT_SPACE, 81);
+        assert_eq!(8 + PauseGrace::INIT_SPACE, 25);
+    }
+
+    fn board() -> Leaderboard {
+        Leaderboard { entries: [LeaderboardEntry::default(); 10], entry_count: 0, bump: 255 }
+    }
+
+    fn totals(board: &Leaderboard) -> Vec<u64> {
+        board.entries().iter().map(|entry| entry.total).collect()
+    }
+
+    #[test]
+    fn leaderboard_fills_in_order_then_displaces_its_minimum() {
+        let mut board = board();
+        let owners: Vec<Pubkey> = (0..11).map(|_| Pubkey::new_unique()).collect();
+        for (i, owner) in owners.iter().take(10).enumerate() {
+            board.submit(*owner, [5, 9, 1, 7, 3, 8, 2, 6, 4, 10][i]).unwrap();
+        }
+        assert_eq!(totals(&board), [10, 9, 8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(serialized_len(&board), 8 + Leaderboard::INIT_SPACE);
+
+        assert_eq!(board.submit(owners[10], 6), Some(5));
+        assert_eq!(totals(&board), [10, 9, 8, 7, 6, 6, 5, 4, 3, 2]);
+        // The entry at 1 (owners[2]) was evicted, and ties rank behind
+        assert!(board.entries().iter().all(|entry| entry.owner != owners[2]));
+        assert_eq!(board.entries()[4].owner, owners[7]);
+    }
+
+    #[test]
+    fn leaderboard_updates_duplicates_in_place() {
+        let mut board = board();
+        let (a, b, c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        for (owner, total) in [(a, 30), (b, 20), (c, 10)] {
+            board.submit(owner, total).unwrap();
+        }
+        assert_eq!(board.submit(c, 40), Some(0));
+        assert_eq!(board.submit(a, 5), Some(2));
+        let order: Vec<Pubkey> = board.entries().iter().map(|entry| entry.owner).collect();
+        assert_eq!(order, [c, b, a]);
+        assert_eq!(totals(&board), [40, 20, 5]);
+    }
+
+    #[test]
+    fn leaderboard_refuses_what_does_not_beat_its_threshold() {
+        let mut board = board();
+        assert_eq!(board.submit(Pubkey::new_unique(), 0), None);
+        for total in 1..=10 {
+            board.submit(Pubkey::new_unique(), total * 10).unwrap();
+        }
+        assert_eq!(board.threshold(), 10);
+        let before = totals(&board);
+        assert_eq!(board.submit(Pubkey::new_unique(), 10), None);
+        assert_eq!(totals(&board), before);
+        assert_eq!(board.submit(Pubkey::new_unique(), 11), Some(9));
+    }
+}
\ No newline at end of file
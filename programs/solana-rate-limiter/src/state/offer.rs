@@ -0,0 +1,16 @@
+use anchor_lang::prelude::*;
+
+// A seller's escrowed quota up for sale, under ["quota-offer", seller]: one offer
+// per seller at a time. The units were already added to the seller's
+// request_count; the offer only records which window they came from, so it lapses
+// when that window does or the config epoch moves on.
+#[account]
+#[derive(InitSpace)]
+pub struct Offer {
+    pub seller: Pubkey,
+    pub amount: u64,
+    pub price_lamports: u64,
+    pub window_start: i64,      // the seller's window the units were escrowed from
+    pub config_epoch: u64,
+    pub bump: u8,
+}
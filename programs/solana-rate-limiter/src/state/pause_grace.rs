@@ -0,0 +1,12 @@
+use anchor_lang::prelude::*;
+
+// The grace consumes spent in an emergency pause, under ["pause-grace"], created
+// by set_pause_grace. Kept out of the config so that spending one does not make
+// every consume write-lock the config.
+#[account]
+#[derive(InitSpace)]
+pub struct PauseGrace {
+    pub paused_at: i64,         // the pause `spent` counts for; another paused_at starts from 0
+    pub spent: u64,
+    pub bump: u8,
+}
@@ -0,0 +1,14 @@
+use anchor_lang::prelude::*;
+
+// Per-payer registration record under ["payer-meta", payer], created by the
+// payer's first registration. It backs registration_cooldown_seconds and
+// max_buckets_per_payer: one payer cannot fund buckets faster than the cooldown
+// allows, nor more of them than the cap.
+#[account]
+#[derive(InitSpace)]
+pub struct PayerMeta {
+    pub payer: Pubkey,
+    pub last_registration_ts: i64, // 0 until the first registration
+    pub bump: u8,
+    pub buckets_created: u32,      // registrations this payer has funded, of any kind
+}
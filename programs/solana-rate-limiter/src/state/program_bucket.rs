@@ -0,0 +1,71 @@
+use anchor_lang::prelude::*;
+
+use super::ClientBucket;
+use crate::constants::CLIENT_BUCKET_VERSION;
+
+// Limits an integrating program as a whole, across all of its users. Lives under
+// ["program-bucket", program_id] and carries its own limits; only a CPI signed by
+// the program's caller authority PDA can consume from it.
+#[account]
+#[derive(InitSpace)]
+pub struct ProgramBucket {
+    pub version: u8,                // layout version, PROGRAM_BUCKET_VERSION
+    pub program_id: Pubkey,         // caller program this bucket limits
+    pub caller_authority: Pubkey,   // ["rate-limiter-caller"] under program_id
+    pub max_requests: u64,          // requests allowed per window, replaces the global value
+    pub window_seconds: i64,        // window duration in seconds
+    pub burst_limit: u64,           // per-window ceiling when spending burst credits
+    pub request_count: u64,         // requests used in current window
+    pub window_start: i64,          // when current window started (unix timestamp)
+    pub total_requests: u64,        // lifetime request count (for analytics)
+    pub flags: u16,                 // BUCKET_FLAG_* bits, same meaning as on ClientBucket
+    pub violations: u64,            // rejected consumes, decayed like a ClientBucket's
+    pub config_epoch: u64,          // config epoch the current window was opened under
+    pub burst_credits: u64,         // banked unused capacity, spendable above max_requests
+    pub bump: u8,
+    pub violations_updated_at: i64, // as on ClientBucket; taken from the reserved bytes
+    pub reserved: [u8; 24],         // zeroed; room for new fields without a realloc
+}
+
+impl ProgramBucket {
+    // Copies the counters out to the regular layout so program buckets share the
+    // consume path; owner is the program id, so events name the program
+    pub fn to_bucket(&self) -> ClientBucket {
+        ClientBucket {
+            version: CLIENT_BUCKET_VERSION,
+            owner: self.program_id,
+            request_count: self.request_count,
+            window_start: self.window_start,
+            total_requests: self.total_requests,
+            flags: self.flags,
+            violations: self.violations,
+            violations_updated_at: self.violations_updated_at,
+            config_epoch: self.config_epoch,
+            burst_credits: self.burst_credits,
+            priority_count: 0,
+            escrowed_count: 0,
+            bonus_requests: 0,
+            last_nonce: 0,
+            nonce_bitmap: 0,
+            last_refund_nonce: 0,
+            refunded_count: 0,
+            registered_by: Pubkey::default(),
+            recovery_delegate: Pubkey::default(),
+            last_request_ts: 0,
+            bump: self.bump,
+            label: [0; 32],
+            event_tag: [0; 16],
+        }
+    }
+
+    pub fn store(&mut self, bucket: &ClientBucket) {
+        self.request_count = bucket.request_count;
+        self.window_start = bucket.window_start;
+        self.total_requests = bucket.total_requests;
+        self.flags = bucket.flags;
+        self.violations = bucket.violations;
+        self.violations_updated_at = bucket.violations_updated_at;
+        self.config_epoch = bucket.config_epoch;
+        self.burst_credits = bucket.burst_credits;
+    }
+}
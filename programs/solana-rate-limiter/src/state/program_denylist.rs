@@ -0,0 +1,12 @@
+use anchor_lang::prelude::*;
+
+// One per program whose PDAs may not consume, under ["program-denylist",
+// program_id]. Existence is the denial, as with CallerAllowlist: block_program_owners
+// creates it and unblock_program_owners closes it.
+#[account]
+#[derive(InitSpace)]
+pub struct ProgramDenylist {
+    pub program_id: Pubkey,     // program whose PDA-owned buckets are refused
+    pub blocked_at: i64,        // unix timestamp of block_program_owners
+    pub bump: u8,
+}
@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::MAX_COUNCIL_MEMBERS;
+
+// A sensitive admin action waiting for council approvals, under
+// ["proposal", id as u64 LE]. Kept after execution so the id cannot run twice.
+#[account]
+#[derive(InitSpace)]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: Pubkey,       // council member who opened it (and its first approval)
+    pub action: AdminAction,
+    pub approvers: [Pubkey; MAX_COUNCIL_MEMBERS], // first approval_count entries
+    pub approval_count: u8,
+    pub executed: bool,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+// What a proposal does once approved. Each variant mirrors the single-admin
+// instruction of the same effect.
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AdminAction {
+    // update_config
+    UpdateConfig { max_requests: u64, window_seconds: i64, burst_limit: u64, reset_windows: bool },
+    // block_client
+    Block { client: Pubkey },
+    // reset_client, which also clears the block
+    Unblock { client: Pubkey },
+    // freeze_client / thaw_client
+    Freeze { client: Pubkey, frozen: bool },
+    // toggle_pause, but stating the end state so a late execution cannot flip it back
+    Pause { paused: bool },
+    // Seats another member, restating the threshold for the new size
+    AddCouncilMember { member: Pubkey, threshold: u8 },
+    // Unseats a member; removing the last one hands control back to the admin
+    RemoveCouncilMember { member: Pubkey, threshold: u8 },
+}
+
+impl Proposal {
+    pub fn approvals(&self) -> &[Pubkey] {
+        &self.approvers[..self.approval_count as usize]
+    }
+
+    // Approvals from keys still on the council; members removed since they approved
+    // no longer count
+    pub fn current_approvals(&self, council: &[Pubkey]) -> usize {
+        self.approvals().iter().filter(|key| council.contains(key)).count()
+    }
+
+    pub fn approve(&mut self, member: Pubkey) -> bool {
+        if self.approvals().contains(&member) || self.approval_count as usize >= MAX_COUNCIL_MEMBERS {
+            return false;
+        }
+        self.approvers[self.approval_count as usize] = member;
+        self.approval_count += 1;
+        true
+    }
+}
@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+// How rebalance_limits walks max_requests, under ["rebalance-policy"]. Written by
+// set_rebalance_policy; the crank keeps the rest. Each interval is judged on the
+// GlobalStats counters since the marks, see logic::rebalance.
+#[account]
+#[derive(InitSpace)]
+pub struct RebalancePolicy {
+    pub params: RebalanceParams,
+    pub last_rebalance_at: i64, // the current interval started here
+    pub requests_mark: u64,     // GlobalStats.total_requests at that time
+    pub denials_mark: u64,      // GlobalStats.total_denials at that time
+    pub quiet_streak: u8,       // quiet intervals in a row so far
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct RebalanceParams {
+    pub step: u64,                  // max_requests moves by this much at a time, 0 = crank off
+    pub floor: u64,                 // the crank keeps max_requests within these
+    pub ceiling: u64,
+    pub target_rejection_bps: u16,  // raise once denials are above this share of attempts
+    pub quiet_rejection_bps: u16,   // an interval at or below this share is quiet
+    pub quiet_intervals: u8,        // tighten after this many quiet intervals in a row, 0 = never
+    pub min_interval_seconds: i64,  // shortest interval the crank judges
+}
@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::MAX_SCHEDULE_ENTRIES;
+
+// Time-of-day limits under ["limit-schedule"], written by set_schedule. While
+// GlobalConfig has the scheduled flag set, wallet consumes take max_requests from
+// here instead of the config (see logic::scheduled_config).
+#[account]
+#[derive(InitSpace)]
+pub struct Schedule {
+    pub entries: [ScheduleEntry; MAX_SCHEDULE_ENTRIES], // first entry_count used, sorted by start
+    pub entry_count: u8,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct ScheduleEntry {
+    pub start_second_of_day: u32, // UTC, 0..86400
+    pub max_requests: u64,
+}
+
+impl Schedule {
+    pub fn entries(&self) -> &[ScheduleEntry] {
+        &self.entries[..self.entry_count as usize]
+    }
+}
@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+// Parent of a sharded client. The shards are ordinary ClientBuckets under
+// ["client-shard", owner, index]; this account only records how many there are
+// and the totals aggregate_shards last folded out of them.
+#[account]
+pub struct ShardParent {
+    pub owner: Pubkey,              // client's wallet
+    pub shard_count: u8,            // shards 0..shard_count exist
+    pub window_requests: u64,       // requests in open shard windows at last aggregate
+    pub total_requests: u64,        // lifetime requests across shards at last aggregate
+    pub total_saturated: bool,      // total_requests hit u64::MAX and is now a floor
+    pub last_aggregated_at: i64,    // unix timestamp of the last aggregate, 0 if never
+    pub bump: u8,
+}
+
+impl ShardParent {
+    pub const LEN: usize = 8    // discriminator
+        + 32                    // owner
+        + 1                     // shard_count
+        + 8                     // window_requests
+        + 8                     // total_requests
+        + 1                     // total_saturated
+        + 8                     // last_aggregated_at
+        + 1;                    // bump
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+use anchor_lang::prelude::*;
+
+// The latest export of a bucket, under ["snapshot-record", owner]. Only this
+// program can write it, so a redeployment reading it from the old program id
+// knows the hash it holds came from a real export. Each export overwrites it.
+#[account]
+#[derive(InitSpace)]
+pub struct SnapshotRecord {
+    pub owner: Pubkey,
+    pub hash: [u8; 32],         // BucketSnapshot::hash of the latest export
+    pub exported_at: i64,
+    pub bump: u8,
+}
@@ -0,0 +1,13 @@
+use anchor_lang::prelude::*;
+
+// Lamports the admin sets aside for other clients' bucket rent, under
+// ["sponsor-pool"]. Everything above the account's own rent can be spent by
+// register_sponsored; the counters are only for accounting.
+#[account]
+#[derive(InitSpace)]
+pub struct SponsorPool {
+    pub sponsored_clients: u64, // buckets registered on the pool's lamports
+    pub lamports_sponsored: u64, // rent paid out over the pool's lifetime
+    pub last_sponsored_at: i64, // registration_cooldown_seconds runs from here
+    pub bump: u8,
+}
@@ -0,0 +1,237 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::{
+    BPS_DENOMINATOR, MAX_DISCOUNT_TIERS, MAX_LABEL_LEN, MAX_NOTE_LEN, MAX_SCHEDULE_ENTRIES, SECONDS_PER_DAY,
+};
+use crate::errors::RateLimiterError;
+use crate::state::{DiscountTier, RebalanceParams, ScheduleEntry};
+use crate::{ConsumeArgs, RateLimiterConfig};
+
+// Every amount-taking instruction runs its input through here first, so zero and
+// oversized amounts are rejected the same way everywhere
+pub fn validate_amount(amount: u64, cap: u64) -> Result<()> {
+    require!(amount > 0, RateLimiterError::InvalidAmount);
+    require!(amount <= cap, RateLimiterError::AmountExceedsCap);
+    Ok(())
+}
+
+// consume_v2 input. A priority consume is one unit, as in consume_priority, so
+// it cannot be combined with a larger amount.
+pub fn validate_consume_args(args: &ConsumeArgs, cap: u64) -> Result<()> {
+    validate_amount(args.amount, cap)?;
+    require!(!args.priority || args.amount == 1, RateLimiterError::InvalidAmount);
+    Ok(())
+}
+
+// initialize and update_config limits, presets included: a non-empty window that
+// admits something, and a burst ceiling no lower than the steady allowance
+pub fn validate_limits(limits: &RateLimiterConfig) -> Result<()> {
+    require!(limits.max_requests > 0, RateLimiterError::InvalidConfig);
+    require!(limits.window_seconds > 0, RateLimiterError::InvalidConfig);
+    require!(limits.burst_limit >= limits.max_requests, RateLimiterError::InvalidConfig);
+    Ok(())
+}
+
+// set_schedule input: at most MAX_SCHEDULE_ENTRIES, starts strictly increasing
+// within one day, and no entry that would shut clients out entirely
+pub fn validate_schedule(entries: &[ScheduleEntry]) -> Result<()> {
+    require!(entries.len() <= MAX_SCHEDULE_ENTRIES, RateLimiterError::InvalidLimitSchedule);
+    require!(
+        entries
+            .iter()
+            .all(|entry| (entry.start_second_of_day as i64) < SECONDS_PER_DAY && entry.max_requests > 0),
+        RateLimiterError::InvalidLimitSchedule
+    );
+    require!(
+        entries.windows(2).all(|pair| pair[0].start_second_of_day < pair[1].start_second_of_day),
+        RateLimiterError::InvalidLimitSchedule
+    );
+    Ok(())
+}
+
+// set_discount_tiers input: at most MAX_DISCOUNT_TIERS, both min_amount and
+// discount_bps strictly increasing, so a bigger batch never gets the smaller
+// discount. Tiers start at 2 units, leaving single consumes at full price, and
+// stop short of a free batch.
+pub fn validate_discount_tiers(tiers: &[DiscountTier]) -> Result<()> {
+    require!(tiers.len() <= MAX_DISCOUNT_TIERS, RateLimiterError::InvalidDiscountTiers);
+    require!(
+        tiers
+            .iter()
+            .all(|tier| tier.min_amount >= 2 && tier.discount_bps > 0 && (tier.discount_bps as u64) < BPS_DENOMINATOR),
+        RateLimiterError::InvalidDiscountTiers
+    );
+    require!(
+        tiers
+            .windows(2)
+            .all(|pair| pair[0].min_amount < pair[1].min_amount && pair[0].discount_bps < pair[1].discount_bps),
+        RateLimiterError::InvalidDiscountTiers
+    );
+    Ok(())
+}
+
+// set_rebalance_policy input. A zero step switches the crank off and is taken as
+// it is; otherwise the bounds must leave max_requests somewhere to be, the quiet
+// share must sit at or below the target, and intervals must take some time.
+pub fn validate_rebalance_params(params: &RebalanceParams) -> Result<()> {
+    if params.step == 0 {
+        return Ok(());
+    }
+    require!(params.floor > 0 && params.floor <= params.ceiling, RateLimiterError::InvalidConfig);
+    require!(params.target_rejection_bps as u64 <= BPS_DENOMINATOR, RateLimiterError::InvalidConfig);
+    require!(params.quiet_rejection_bps <= params.target_rejection_bps, RateLimiterError::InvalidConfig);
+    require!(params.min_interval_seconds > 0, RateLimiterError::InvalidConfig);
+    Ok(())
+}
+
+// A String is UTF-8 by construction; the cap is on bytes, not characters, since
+// that is what the account stores
+pub fn validate_note(note: &str) -> Result<()> {
+    require!(!note.is_empty() && note.len() <= MAX_NOTE_LEN, RateLimiterError::InvalidNote);
+    Ok(())
+}
+
+// set_label input. Trailing zeros pad the stored label, so a NUL inside it
+// would read back truncated; empty clears the label
+pub fn validate_label(label: &str) -> Result<()> {
+    require!(
+        label.len() <= MAX_LABEL_LEN && !label.contains('\0'),
+        RateLimiterError::InvalidLabel
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected_with(result: Result<()>, expected: RateLimiterError) -> bool {
+        let expected: Error = expected.into();
+        result.unwrap_err() == expected
+    }
+
+    #[test]
+    fn zero_is_rejected() {
+        assert!(rejected_with(validate_amount(0, 10), RateLimiterError::InvalidAmount));
+        assert!(rejected_with(validate_amount(0, u64::MAX), RateLimiterError::InvalidAmount));
+    }
+
+    #[test]
+    fn consume_args_default_to_a_single_unit() {
+        assert!(validate_consume_args(&ConsumeArgs::default(), 1).is_ok());
+        let batch = ConsumeArgs { amount: 4, deadline: Some(100), nonce: Some(1), ..Default::default() };
+        assert!(validate_consume_args(&batch, 4).is_ok());
+        assert!(rejected_with(validate_consume_args(&batch, 3), RateLimiterError::AmountExceedsCap));
+        // Priority pays one unit with one credit
+        let priority = ConsumeArgs { priority: true, ..batch };
+        assert!(rejected_with(validate_consume_args(&priority, 4), RateLimiterError::InvalidAmount));
+        assert!(validate_consume_args(&ConsumeArgs { amount: 1, ..priority }, 4).is_ok());
+    }
+
+    #[test]
+    fn presets_pass_the_limit_checks_and_custom_is_checked_as_given() {
+        use crate::ConfigPreset;
+        for preset in [ConfigPreset::Strict, ConfigPreset::Standard, ConfigPreset::Lenient] {
+            assert!(validate_limits(&preset.limits()).is_ok());
+        }
+        let custom = RateLimiterConfig { max_requests: 10, window_seconds: 60, burst_limit: 9 };
+        let preset = ConfigPreset::Custom(custom.clone());
+        assert!(rejected_with(validate_limits(&preset.limits()), RateLimiterError::InvalidConfig));
+        assert!(rejected_with(
+            validate_limits(&RateLimiterConfig { window_seconds: 0, ..custom.clone() }),
+            RateLimiterError::InvalidConfig
+        ));
+        assert!(validate_limits(&RateLimiterConfig { burst_limit: 10, ..custom }).is_ok());
+    }
+
+    #[test]
+    fn cap_is_inclusive() {
+        assert!(validate_amount(1, 10).is_ok());
+        assert!(validate_amount(10, 10).is_ok());
+        assert!(rejected_with(validate_amount(11, 10), RateLimiterError::AmountExceedsCap));
+    }
+
+    #[test]
+    fn u64_max_only_passes_an_unbounded_cap() {
+        assert!(rejected_with(validate_amount(u64::MAX, u64::MAX - 1), RateLimiterError::AmountExceedsCap));
+        assert!(validate_amount(u64::MAX, u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn notes_are_capped_in_bytes() {
+        assert!(validate_note("contacted 2024-05-01 re abuse").is_ok());
+        assert!(validate_note(&"a".repeat(64)).is_ok());
+        assert!(rejected_with(validate_note(&"a".repeat(65)), RateLimiterError::InvalidNote));
+        // 22 three-byte characters are 66 bytes
+        assert!(rejected_with(validate_note(&"€".repeat(22)), RateLimiterError::InvalidNote));
+        assert!(rejected_with(validate_note(""), RateLimiterError::InvalidNote));
+    }
+
+    #[test]
+    fn labels_fit_the_bucket_and_carry_no_nul() {
+        assert!(validate_label("").is_ok());
+        assert!(validate_label(&"a".repeat(32)).is_ok());
+        assert!(rejected_with(validate_label(&"a".repeat(33)), RateLimiterError::InvalidLabel));
+        assert!(rejected_with(validate_label("trading\0bot"), RateLimiterError::InvalidLabel));
+    }
+
+    fn entry(start_second_of_day: u32, max_requests: u64) -> ScheduleEntry {
+        ScheduleEntry { start_second_of_day, max_requests }
+    }
+
+    #[test]
+    fn schedules_must_be_sorted_and_within_a_day() {
+        assert!(validate_schedule(&[]).is_ok());
+        assert!(validate_schedule(&[entry(0, 5), entry(86_399, 10)]).is_ok());
+        let invalid = RateLimiterError::InvalidLimitSchedule;
+        assert!(rejected_with(validate_schedule(&[entry(86_400, 5)]), invalid));
+        assert!(rejected_with(validate_schedule(&[entry(100, 5), entry(100, 6)]), invalid));
+        assert!(rejected_with(validate_schedule(&[entry(200, 5), entry(100, 6)]), invalid));
+        assert!(rejected_with(validate_schedule(&[entry(100, 0)]), invalid));
+        let full: Vec<_> = (0..9).map(|i| entry(i * 60, 5)).collect();
+        assert!(rejected_with(validate_schedule(&full), invalid));
+        assert!(validate_schedule(&full[..8]).is_ok());
+    }
+
+    #[test]
+    fn rebalance_bounds_must_hold_unless_the_policy_is_off() {
+        let params = RebalanceParams {
+            step: 10,
+            floor: 20,
+            ceiling: 50,
+            target_rejection_bps: 1_000,
+            quiet_rejection_bps: 100,
+            quiet_intervals: 3,
+            min_interval_seconds: 60,
+        };
+        assert!(validate_rebalance_params(&params).is_ok());
+        assert!(validate_rebalance_params(&RebalanceParams::default()).is_ok());
+        let invalid = RateLimiterError::InvalidConfig;
+        assert!(rejected_with(validate_rebalance_params(&RebalanceParams { floor: 0, ..params }), invalid));
+        assert!(rejected_with(validate_rebalance_params(&RebalanceParams { ceiling: 19, ..params }), invalid));
+        assert!(rejected_with(validate_rebalance_params(&RebalanceParams { target_rejection_bps: 10_001, ..params }), invalid));
+        assert!(rejected_with(validate_rebalance_params(&RebalanceParams { quiet_rejection_bps: 1_001, ..params }), invalid));
+        assert!(rejected_with(validate_rebalance_params(&RebalanceParams { min_interval_seconds: 0, ..params }), invalid));
+    }
+
+    fn tier(min_amount: u64, discount_bps: u16) -> DiscountTier {
+        DiscountTier { min_amount, discount_bps }
+    }
+
+    #[test]
+    fn discount_tiers_must_be_sorted_and_leave_a_charge() {
+        assert!(validate_discount_tiers(&[]).is_ok());
+        assert!(validate_discount_tiers(&[tier(2, 1), tier(100, 9_999)]).is_ok());
+        let invalid = RateLimiterError::InvalidDiscountTiers;
+        assert!(rejected_with(validate_discount_tiers(&[tier(1, 500)]), invalid));
+        assert!(rejected_with(validate_discount_tiers(&[tier(10, 0)]), invalid));
+        assert!(rejected_with(validate_discount_tiers(&[tier(10, 10_000)]), invalid));
+        assert!(rejected_with(validate_discount_tiers(&[tier(100, 500), tier(10, 1_000)]), invalid));
+        assert!(rejected_with(validate_discount_tiers(&[tier(10, 500), tier(10, 1_000)]), invalid));
+        // A bigger batch may not get a smaller discount
+        assert!(rejected_with(validate_discount_tiers(&[tier(10, 1_000), tier(100, 500)]), invalid));
+        let full: Vec<_> = (1..=5).map(|i| tier(i * 10, i as u16 * 100)).collect();
+        assert!(rejected_with(validate_discount_tiers(&full), invalid));
+        assert!(validate_discount_tiers(&full[..4]).is_ok());
+    }
+}
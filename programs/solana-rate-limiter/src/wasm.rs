@@ -0,0 +1,125 @@
+use anchor_lang::prelude::*;
+use serde::Serialize;
+use serde_wasm_bindgen::Serializer;
+use wasm_bindgen::prelude::*;
+
+use crate::logic::{self, QuotaInfo};
+use crate::state::{ClientBucket, GlobalConfig, Schedule};
+
+// The quota simulator for browser frontends, over the raw account bytes
+// getAccountInfo returns. Accounts are decoded with the program's own layouts and
+// run through logic::simulate_scheduled_consume, so a page shows what get_quota
+// would say without a TypeScript copy of the window rules. logic, window and math
+// only use core; the crate itself still needs std for anchor-lang, which
+// wasm32-unknown-unknown has.
+//
+// Results come back as plain objects with camelCase fields. 64-bit values, `now`
+// included, are BigInts on the JS side.
+
+// The config's limits and pause state, as a frontend shows them
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigView {
+    pub admin: String,
+    pub max_requests: u64,
+    pub window_seconds: i64,
+    pub burst_limit: u64,
+    pub paused: bool,
+    pub scheduled: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketView {
+    pub owner: String,
+    pub request_count: u64,
+    pub window_start: i64,
+    pub burst_credits: u64,
+    pub total_requests: u64,
+    pub blocked: bool,
+    pub frozen: bool,
+}
+
+fn decode<T: AccountDeserialize>(data: &[u8]) -> Result<T> {
+    let mut data = data;
+    T::try_deserialize(&mut data)
+}
+
+// simulateConsume without the JS conversions. `schedule` is the limit schedule
+// account, needed while the config says one is set.
+pub fn quota(config: &[u8], schedule: Option<&[u8]>, bucket: &[u8], now: i64) -> Result<QuotaInfo> {
+    let config: GlobalConfig = decode(config)?;
+    let bucket: ClientBucket = decode(bucket)?;
+    let schedule = match schedule {
+        Some(data) if config.scheduled() => Some(decode::<Schedule>(data)?),
+        None if config.scheduled() => {
+            return Err(crate::errors::RateLimiterError::ScheduleAccountMissing.into())
+        }
+        _ => None,
+    };
+    let entries = schedule.as_ref().map_or(&[][..], |s| s.entries());
+    Ok(logic::simulate_scheduled_consume(
+        &config, entries, &bucket, now,
+    ))
+}
+
+fn to_js<T: Serialize>(value: &T) -> std::result::Result<JsValue, JsError> {
+    let serializer = Serializer::new()
+        .serialize_large_number_types_as_bigints(true)
+        .serialize_missing_as_null(true);
+    value
+        .serialize(&serializer)
+        .map_err(|err| JsError::new(&err.to_string()))
+}
+
+fn js_error(err: anchor_lang::error::Error) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+// { allowed, rejection, remaining, resetsIn } for a one-unit consume_request at
+// `now`, a unix timestamp. `rejection` is null or one of logic::Rejection's names.
+#[wasm_bindgen(js_name = simulateConsume)]
+pub fn simulate_consume(
+    config: &[u8],
+    bucket: &[u8],
+    now: i64,
+) -> std::result::Result<JsValue, JsError> {
+    to_js(&quota(config, None, bucket, now).map_err(js_error)?)
+}
+
+#[wasm_bindgen(js_name = simulateScheduledConsume)]
+pub fn simulate_scheduled_consume(
+    config: &[u8],
+    schedule: &[u8],
+    bucket: &[u8],
+    now: i64,
+) -> std::result::Result<JsValue, JsError> {
+    to_js(&quota(config, Some(schedule), bucket, now).map_err(js_error)?)
+}
+
+#[wasm_bindgen(js_name = decodeConfig)]
+pub fn decode_config(data: &[u8]) -> std::result::Result<JsValue, JsError> {
+    let config: GlobalConfig = decode(data).map_err(js_error)?;
+    to_js(&ConfigView {
+        admin: config.admin.to_string(),
+        max_requests: config.max_requests,
+        window_seconds: config.window_seconds,
+        burst_limit: config.burst_limit,
+        paused: config.is_paused(),
+        scheduled: config.scheduled(),
+    })
+}
+
+#[wasm_bindgen(js_name = decodeBucket)]
+pub fn decode_bucket(data: &[u8]) -> std::result::Result<JsValue, JsError> {
+    let bucket: ClientBucket = decode(data).map_err(js_error)?;
+    to_js(&BucketView {
+        owner: bucket.owner.to_string(),
+        request_count: bucket.request_count,
+        window_start: bucket.window_start,
+        burst_credits: bucket.burst_credits,
+        total_requests: bucket.total_requests,
+        blocked: bucket.is_blocked(),
+        frozen: bucket.is_frozen(),
+    })
+}
@@ -0,0 +1,101 @@
+// A window opened at `start` with length `len` covers the half-open interval
+// [start, start + len). A request landing exactly on start + len belongs to the
+// next window, and a window of `len` seconds admits requests on `len` distinct
+// unix seconds. All comparisons widen to i128 so extreme values never wrap.
+
+pub fn window_expired(now: i64, start: i64, len: i64) -> bool {
+    now as i128 >= start as i128 + len as i128
+}
+
+// Seconds since `start`. A start in the future (clock skew, or a bad admin write)
+// counts as zero elapsed rather than a negative duration.
+pub fn elapsed_seconds(now: i64, start: i64) -> i64 {
+    (now as i128 - start as i128).clamp(0, i64::MAX as i128) as i64
+}
+
+// Seconds until the window expires, zero once it has and never more than `len`
+pub fn window_remaining(now: i64, start: i64, len: i64) -> i64 {
+    let remaining = len as i128 - elapsed_seconds(now, start) as i128;
+    remaining.clamp(0, i64::MAX as i128) as i64
+}
+
+// Whole windows between `start` and `now`; zero when `now` is not after `start`
+pub fn elapsed_windows(now: i64, start: i64, len: i64) -> u64 {
+    if len <= 0 || now <= start {
+        return 0;
+    }
+    ((now as i128 - start as i128) / len as i128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boundary_is_exclusive() {
+        let (start, len) = (1_000, 60);
+        assert!(!window_expired(start + len - 1, start, len));
+        assert!(window_expired(start + len, start, len));
+        assert!(window_expired(start + len + 1, start, len));
+    }
+
+    #[test]
+    fn start_second_is_inside_the_window() {
+        assert!(!window_expired(1_000, 1_000, 1));
+        assert!(window_expired(1_001, 1_000, 1));
+    }
+
+    #[test]
+    fn negative_timestamps() {
+        let (start, len) = (-100, 60);
+        assert!(!window_expired(-41, start, len));
+        assert!(window_expired(-40, start, len));
+        assert!(window_expired(-39, start, len));
+        assert!(!window_expired(-200, start, len));
+        assert_eq!(window_remaining(-41, start, len), 1);
+        assert_eq!(elapsed_windows(-40, start, len), 1);
+    }
+
+    #[test]
+    fn extreme_values_do_not_wrap() {
+        assert!(!window_expired(i64::MAX - 1, 0, i64::MAX));
+        assert!(window_expired(i64::MAX, 0, i64::MAX));
+        assert!(window_expired(i64::MAX, i64::MAX - 1, 1));
+        assert!(window_expired(i64::MIN + 1, i64::MIN, 1));
+        assert_eq!(window_remaining(i64::MIN, i64::MAX, i64::MAX), i64::MAX);
+        assert_eq!(elapsed_windows(i64::MAX, i64::MIN, 1), u64::MAX);
+    }
+
+    #[test]
+    fn remaining_agrees_with_expiry() {
+        let (start, len) = (1_000, 60);
+        assert_eq!(window_remaining(start + len - 1, start, len), 1);
+        assert_eq!(window_remaining(start + len, start, len), 0);
+        assert_eq!(window_remaining(start + len + 1, start, len), 0);
+        for now in start..start + 2 * len {
+            assert_eq!(window_remaining(now, start, len) == 0, window_expired(now, start, len));
+        }
+    }
+
+    #[test]
+    fn future_start_counts_as_no_time_elapsed() {
+        let (start, len) = (1_000, 60);
+        assert_eq!(elapsed_seconds(start - 30, start), 0);
+        assert_eq!(elapsed_seconds(i64::MIN, i64::MAX), 0);
+        assert_eq!(elapsed_seconds(start + 30, start), 30);
+        assert_eq!(elapsed_seconds(i64::MAX, i64::MIN), i64::MAX);
+        assert_eq!(window_remaining(start - 30, start, len), len);
+        assert!(!window_expired(start - 30, start, len));
+        assert_eq!(elapsed_windows(start - 30, start, len), 0);
+    }
+
+    #[test]
+    fn elapsed_window_counts() {
+        let (start, len) = (1_000, 60);
+        assert_eq!(elapsed_windows(start + len - 1, start, len), 0);
+        assert_eq!(elapsed_windows(start + len, start, len), 1);
+        assert_eq!(elapsed_windows(start + 3 * len + 5, start, len), 3);
+        assert_eq!(elapsed_windows(start - 5, start, len), 0);
+        assert_eq!(elapsed_windows(start + 5, start, 0), 0);
+    }
+}
@@ -0,0 +1,62 @@
+#![cfg(feature = "test-sbf")]
+
+mod common;
+
+use std::path::Path;
+
+use common::*;
+use solana_rate_limiter::pda;
+use solana_sdk::signature::{keypair_from_seed, Signer};
+
+// The account bytes in test-data/accounts, which the wasm tests decode, as the
+// program writes them today. Keys and the clock are fixed so a run is
+// reproducible byte for byte. After a layout change, regenerate them with
+// `UPDATE_ACCOUNT_FIXTURES=1 cargo test-sbf --test account_fixtures`.
+
+const START: i64 = 1_700_000_000;
+
+fn check(name: &str, live: &[u8]) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("test-data/accounts")
+        .join(name);
+    if std::env::var_os("UPDATE_ACCOUNT_FIXTURES").is_some() {
+        std::fs::write(&path, live).unwrap();
+        return;
+    }
+    let stored = std::fs::read(&path).unwrap();
+    assert!(
+        stored == live,
+        "{} is out of date; rerun with UPDATE_ACCOUNT_FIXTURES=1",
+        path.display()
+    );
+}
+
+#[tokio::test]
+async fn captured_accounts_match_the_program() {
+    let mut harness = TestHarness::start_with_admin(keypair_from_seed(&[1; 32]).unwrap()).await;
+    harness.set_clock(START).await;
+    let admin = harness.admin.pubkey();
+    harness
+        .send_as_admin(&[initialize_ix(&admin, LIMITS), initialize_stats_ix(&admin)])
+        .await
+        .unwrap();
+
+    let client = keypair_from_seed(&[2; 32]).unwrap();
+    harness.fund(&client.pubkey()).await;
+    harness
+        .send(&[register_ix(&client.pubkey())], &[&client])
+        .await
+        .unwrap();
+    harness.set_clock(START + 10).await;
+    harness
+        .send(&[consume_amount_ix(&client.pubkey(), 2)], &[&client])
+        .await
+        .unwrap();
+
+    let config = harness.raw_account(pda::global_config_address().0).await;
+    check("global_config.bin", &config);
+    let bucket = harness
+        .raw_account(pda::client_bucket_address(&client.pubkey()).0)
+        .await;
+    check("client_bucket.bin", &bucket);
+}
@@ -0,0 +1,230 @@
+#![cfg(feature = "test-sbf")]
+
+mod common;
+
+use common::*;
+use solana_rate_limiter::constants::{PAUSE_REASON_INCIDENT, PRORATION_PRORATE};
+use solana_rate_limiter::errors::RateLimiterError;
+use solana_rate_limiter::RateLimiterConfig;
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn block_refuses_consumes_until_reset() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let wallet = harness.client().await;
+    harness.consume(&wallet).await.unwrap();
+
+    harness
+        .send_as_admin(&[block_ix(&admin, &wallet.pubkey())])
+        .await
+        .unwrap();
+    assert!(harness.bucket(&wallet.pubkey()).await.is_blocked());
+    assert_eq!(harness.stats().await.blocked_clients, 1);
+    let blocked = harness.consume(&wallet).await;
+    assert_error(blocked, RateLimiterError::ClientBlocked);
+
+    harness
+        .send_as_admin(&[reset_ix(&admin, &wallet.pubkey())])
+        .await
+        .unwrap();
+    let bucket = harness.bucket(&wallet.pubkey()).await;
+    assert!(!bucket.is_blocked());
+    assert_eq!(bucket.request_count, 0);
+    assert_eq!(harness.stats().await.blocked_clients, 0);
+    harness.consume(&wallet).await.unwrap();
+}
+
+#[tokio::test]
+async fn reset_gives_a_spent_wallet_a_fresh_window() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let wallet = harness.client().await;
+    for _ in 0..LIMITS.max_requests {
+        harness.consume(&wallet).await.unwrap();
+    }
+    harness
+        .send_as_admin(&[reset_ix(&admin, &wallet.pubkey())])
+        .await
+        .unwrap();
+    harness.consume(&wallet).await.unwrap();
+    assert_eq!(harness.bucket(&wallet.pubkey()).await.request_count, 1);
+}
+
+#[tokio::test]
+async fn update_config_applies_valid_limits() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let limits = RateLimiterConfig {
+        max_requests: 10,
+        window_seconds: 120,
+        burst_limit: 15,
+    };
+    harness
+        .send_as_admin(&[update_config_ix(&admin, limits, false)])
+        .await
+        .unwrap();
+    let config = harness.config().await;
+    assert_eq!(config.max_requests, 10);
+    assert_eq!(config.window_seconds, 120);
+    assert_eq!(config.burst_limit, 15);
+}
+
+#[tokio::test]
+async fn update_config_rejects_invalid_limits() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let invalid = [
+        RateLimiterConfig {
+            max_requests: 0,
+            ..LIMITS
+        },
+        RateLimiterConfig {
+            window_seconds: 0,
+            ..LIMITS
+        },
+        RateLimiterConfig {
+            window_seconds: -1,
+            ..LIMITS
+        },
+        RateLimiterConfig {
+            burst_limit: LIMITS.max_requests - 1,
+            ..LIMITS
+        },
+    ];
+    for limits in invalid {
+        let result = harness
+            .send_as_admin(&[update_config_ix(&admin, limits, false)])
+            .await;
+        assert_error(result, RateLimiterError::InvalidConfig);
+    }
+    assert_eq!(harness.config().await.max_requests, LIMITS.max_requests);
+}
+
+#[tokio::test]
+async fn prorated_limit_changes_keep_the_open_window() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let wallet = harness.client().await;
+    harness.consume(&wallet).await.unwrap();
+    harness.consume(&wallet).await.unwrap();
+    let opened = harness.bucket(&wallet.pubkey()).await.window_start;
+
+    let unknown = harness
+        .send_as_admin(&[set_proration_mode_ix(&admin, PRORATION_PRORATE + 1)])
+        .await;
+    assert_error(unknown, RateLimiterError::InvalidConfig);
+    harness
+        .send_as_admin(&[set_proration_mode_ix(&admin, PRORATION_PRORATE)])
+        .await
+        .unwrap();
+
+    // Loosened: 2 of 3 becomes 4 of 6, and the window runs on at 120 seconds
+    let loosened = RateLimiterConfig {
+        max_requests: 6,
+        window_seconds: 120,
+        burst_limit: 6,
+    };
+    harness
+        .send_as_admin(&[update_config_ix(&admin, loosened.clone(), false)])
+        .await
+        .unwrap();
+    harness.consume(&wallet).await.unwrap();
+    let bucket = harness.bucket(&wallet.pubkey()).await;
+    assert_eq!((bucket.request_count, bucket.window_start), (5, opened));
+
+    // Tightened back: 5 of 6 becomes 2 of 3, so one more fits
+    harness
+        .send_as_admin(&[update_config_ix(&admin, LIMITS, false)])
+        .await
+        .unwrap();
+    harness.consume(&wallet).await.unwrap();
+    assert_eq!(harness.bucket(&wallet.pubkey()).await.request_count, 3);
+    let spent = harness.consume(&wallet).await;
+    assert_error(spent, RateLimiterError::RateLimitExceeded);
+
+    // reset_windows still starts every window over
+    harness
+        .send_as_admin(&[update_config_ix(&admin, loosened, true)])
+        .await
+        .unwrap();
+    harness.consume(&wallet).await.unwrap();
+    assert_eq!(harness.bucket(&wallet.pubkey()).await.request_count, 1);
+}
+
+#[tokio::test]
+async fn admin_instructions_refuse_other_signers() {
+    let mut harness = TestHarness::new().await;
+    let wallet = harness.client().await;
+    let outsider = harness.wallet().await;
+    let by = outsider.pubkey();
+    let target = wallet.pubkey();
+
+    let attempts = [
+        ("update_config", update_config_ix(&by, LIMITS, true)),
+        ("toggle_pause", toggle_pause_ix(&by)),
+        ("pause", pause_ix(&by, PAUSE_REASON_INCIDENT, 0)),
+        ("block_client", block_ix(&by, &target)),
+        ("reset_client", reset_ix(&by, &target)),
+    ];
+    for (name, ix) in attempts {
+        let result = harness.send(&[ix], &[&outsider]).await;
+        assert!(result.is_err(), "{} succeeded", name);
+        assert_error(result, RateLimiterError::Unauthorized);
+    }
+
+    // None of them got through
+    assert!(!harness.config().await.is_paused());
+    assert!(!harness.bucket(&target).await.is_blocked());
+}
+
+#[tokio::test]
+async fn admin_throttle_caps_destructive_actions_per_window() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let wallet = harness.client().await;
+    let target = wallet.pubkey();
+    harness
+        .send_as_admin(&[set_admin_throttle_ix(&admin, 60, 2)])
+        .await
+        .unwrap();
+
+    harness
+        .send_as_admin(&[block_ix(&admin, &target)])
+        .await
+        .unwrap();
+    harness
+        .send_as_admin(&[reset_ix(&admin, &target)])
+        .await
+        .unwrap();
+    let third = harness
+        .send_as_admin(&[update_config_ix(&admin, LIMITS, false)])
+        .await;
+    assert_error(third, RateLimiterError::AdminThrottled);
+    assert_eq!(harness.config().await.admin_actions_in_window, 2);
+
+    // Consumes and pauses are not counted
+    harness.consume(&wallet).await.unwrap();
+    harness
+        .send_as_admin(&[pause_ix(&admin, PAUSE_REASON_INCIDENT, 0)])
+        .await
+        .unwrap();
+    harness
+        .send_as_admin(&[toggle_pause_ix(&admin)])
+        .await
+        .unwrap();
+
+    // The next window has room again
+    harness.advance_clock(60).await;
+    let limits = RateLimiterConfig {
+        max_requests: 10,
+        ..LIMITS
+    };
+    harness
+        .send_as_admin(&[update_config_ix(&admin, limits, false)])
+        .await
+        .unwrap();
+    let config = harness.config().await;
+    assert_eq!(config.max_requests, 10);
+    assert_eq!(config.admin_actions_in_window, 1);
+}
@@ -0,0 +1,816 @@
+// Shared fixtures for the solana-program-test suite. The program is loaded from
+// the `cargo build-sbf` output as an upgradeable program whose upgrade authority
+// is the harness admin, so `initialize` runs its real authority check.
+//
+// Instructions are put together here from the generated `accounts` and
+// `instruction` types rather than the `client` builders: that feature pulls in
+// solana-client, which does not build for SBF, and cargo test-sbf builds the
+// program with the test's features.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use anchor_lang::error::ErrorCode;
+use anchor_lang::{system_program, AccountDeserialize, InstructionData, ToAccountMetas};
+use solana_loader_v3_interface::state::UpgradeableLoaderState;
+use solana_program_test::{BanksClientError, ProgramTest, ProgramTestContext};
+use solana_rate_limiter::constants::CLIENT_BUCKET_SEED;
+use solana_rate_limiter::errors::RateLimiterError;
+use solana_rate_limiter::state::{ClientBucket, GlobalConfig, GlobalStats, PauseGrace, PayerMeta};
+use solana_rate_limiter::{accounts, instruction, pda, ConfigPreset, RateLimiterConfig};
+use solana_sdk::account::Account;
+use solana_sdk::clock::Clock;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::{Transaction, TransactionError};
+use solana_sdk_ids::bpf_loader_upgradeable;
+use solana_system_interface::instruction as system_instruction;
+
+pub const LIMITS: RateLimiterConfig = RateLimiterConfig {
+    max_requests: 3,
+    window_seconds: 60,
+    burst_limit: 3,
+};
+
+pub struct TestHarness {
+    pub context: ProgramTestContext,
+    pub admin: Keypair,
+}
+
+impl TestHarness {
+    // The program deployed and nothing initialized
+    pub async fn start() -> Self {
+        Self::start_with_admin(Keypair::new()).await
+    }
+
+    // For runs that must come out byte for byte the same
+    pub async fn start_with_admin(admin: Keypair) -> Self {
+        let mut program_test = ProgramTest::default();
+        add_upgradeable_program(&mut program_test, &admin.pubkey());
+        program_test.add_account(
+            admin.pubkey(),
+            Account::new(10 * LAMPORTS_PER_SOL, 0, &system_program::ID),
+        );
+        let context = program_test.start_with_context().await;
+        Self { context, admin }
+    }
+
+    // A limiter initialized with `limits`, stats account included
+    pub async fn with_limits(limits: RateLimiterConfig) -> Self {
+        let mut harness = Self::start().await;
+        let admin = harness.admin.pubkey();
+        harness
+            .send_as_admin(&[initialize_ix(&admin, limits), initialize_stats_ix(&admin)])
+            .await
+            .unwrap();
+        harness
+    }
+
+    pub async fn new() -> Self {
+        Self::with_limits(LIMITS).await
+    }
+
+    // Signs with `signers` and the context payer, which pays the fee. Every call
+    // waits for a fresh blockhash, so sending the same instructions twice is two
+    // transactions.
+    pub async fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        self.process(instructions, signers, false).await
+    }
+
+    pub async fn send_as_admin(
+        &mut self,
+        instructions: &[Instruction],
+    ) -> Result<(), BanksClientError> {
+        self.process(instructions, &[], true).await
+    }
+
+    async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+        as_admin: bool,
+    ) -> Result<(), BanksClientError> {
+        let blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let mut all = vec![&self.context.payer];
+        if as_admin {
+            all.push(&self.admin);
+        }
+        all.extend_from_slice(signers);
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &all,
+            blockhash,
+        );
+        self.context.banks_client.process_transaction(tx).await
+    }
+
+    // Like `send`, but fails on an error and returns the compute units the
+    // transaction used
+    pub async fn send_metered(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> u64 {
+        let blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let mut all = vec![&self.context.payer];
+        all.extend_from_slice(signers);
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &all,
+            blockhash,
+        );
+        let processed = self
+            .context
+            .banks_client
+            .process_transaction_with_metadata(tx)
+            .await
+            .unwrap();
+        processed.result.unwrap();
+        processed.metadata.unwrap().compute_units_consumed
+    }
+
+    // A wallet with enough SOL to pay for its own bucket
+    pub async fn wallet(&mut self) -> Keypair {
+        let wallet = Keypair::new();
+        self.fund(&wallet.pubkey()).await;
+        wallet
+    }
+
+    pub async fn fund(&mut self, wallet: &Pubkey) {
+        let fund =
+            system_instruction::transfer(&self.context.payer.pubkey(), wallet, LAMPORTS_PER_SOL);
+        self.send(&[fund], &[]).await.unwrap();
+    }
+
+    // A funded wallet with its bucket registered
+    pub async fn client(&mut self) -> Keypair {
+        let wallet = self.wallet().await;
+        self.send(&[register_ix(&wallet.pubkey())], &[&wallet])
+            .await
+            .unwrap();
+        wallet
+    }
+
+    pub async fn consume(&mut self, wallet: &Keypair) -> Result<(), BanksClientError> {
+        self.send(&[consume_ix(&wallet.pubkey())], &[wallet]).await
+    }
+
+    // Moves the clock forward without producing slots. Windows are judged on
+    // unix_timestamp alone, so this is all a rollover needs.
+    pub async fn advance_clock(&mut self, seconds: i64) {
+        let clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        self.set_clock(clock.unix_timestamp + seconds).await;
+    }
+
+    pub async fn set_clock(&mut self, unix_timestamp: i64) {
+        let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp = unix_timestamp;
+        self.context.set_sysvar(&clock);
+    }
+
+    pub async fn balance(&mut self, address: &Pubkey) -> u64 {
+        self.context
+            .banks_client
+            .get_balance(*address)
+            .await
+            .unwrap()
+    }
+
+    pub async fn raw_account(&mut self, address: Pubkey) -> Vec<u8> {
+        let account = self
+            .context
+            .banks_client
+            .get_account(address)
+            .await
+            .unwrap();
+        account.expect("account exists").data
+    }
+
+    pub async fn account<T: AccountDeserialize>(&mut self, address: Pubkey) -> Option<T> {
+        let account = self
+            .context
+            .banks_client
+            .get_account(address)
+            .await
+            .unwrap()?;
+        Some(T::try_deserialize(&mut account.data.as_slice()).unwrap())
+    }
+
+    pub async fn config(&mut self) -> GlobalConfig {
+        self.account(pda::global_config_address().0).await.unwrap()
+    }
+
+    pub async fn stats(&mut self) -> GlobalStats {
+        self.account(pda::global_stats_address().0).await.unwrap()
+    }
+
+    pub async fn pause_grace(&mut self) -> PauseGrace {
+        self.account(pda::pause_grace_address().0).await.unwrap()
+    }
+
+    pub async fn bucket(&mut self, owner: &Pubkey) -> ClientBucket {
+        self.account(pda::client_bucket_address(owner).0)
+            .await
+            .unwrap()
+    }
+
+    pub async fn payer_meta(&mut self, payer: &Pubkey) -> PayerMeta {
+        self.account(pda::payer_meta_address(payer).0)
+            .await
+            .unwrap()
+    }
+}
+
+fn program_so() -> Vec<u8> {
+    // cargo test-sbf points these at its build output
+    let dir = ["SBF_OUT_DIR", "BPF_OUT_DIR"]
+        .into_iter()
+        .find_map(|var| std::env::var_os(var).map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../target/deploy"));
+    let path = dir.join("solana_rate_limiter.so");
+    std::fs::read(&path)
+        .unwrap_or_else(|err| panic!("{}: {}; run cargo test-sbf", path.display(), err))
+}
+
+// The loader's Program and ProgramData accounts, written into genesis the way a
+// deploy with `authority` as upgrade authority leaves them
+fn add_upgradeable_program(program_test: &mut ProgramTest, authority: &Pubkey) {
+    let rent = Rent::default();
+    let program_data = program_data_address();
+    let elf = program_so();
+
+    let metadata = UpgradeableLoaderState::size_of_programdata_metadata();
+    let mut data_account = Account::new_data_with_space(
+        rent.minimum_balance(metadata + elf.len()),
+        &UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: Some(*authority),
+        },
+        metadata + elf.len(),
+        &bpf_loader_upgradeable::ID,
+    )
+    .unwrap();
+    data_account.data[metadata..].copy_from_slice(&elf);
+    program_test.add_account(program_data, data_account);
+
+    let mut program_account = Account::new_data(
+        rent.minimum_balance(UpgradeableLoaderState::size_of_program()),
+        &UpgradeableLoaderState::Program {
+            programdata_address: program_data,
+        },
+        &bpf_loader_upgradeable::ID,
+    )
+    .unwrap();
+    program_account.executable = true;
+    program_test.add_account(solana_rate_limiter::ID, program_account);
+}
+
+fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(
+        &[solana_rate_limiter::ID.as_ref()],
+        &bpf_loader_upgradeable::ID,
+    )
+    .0
+}
+
+fn ix(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: solana_rate_limiter::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn global_config() -> Pubkey {
+    pda::global_config_address().0
+}
+
+fn global_stats() -> Pubkey {
+    pda::global_stats_address().0
+}
+
+fn bucket(owner: &Pubkey) -> Pubkey {
+    pda::client_bucket_address(owner).0
+}
+
+// `owner`'s bucket seeds under the highest bump below the canonical one, an
+// address no bucket should ever be created at
+pub fn shadow_bucket(owner: &Pubkey) -> Pubkey {
+    let (_, canonical) = pda::client_bucket_address(owner);
+    (0..canonical)
+        .rev()
+        .find_map(|bump| {
+            Pubkey::create_program_address(
+                &[CLIENT_BUCKET_SEED, owner.as_ref(), &[bump]],
+                &solana_rate_limiter::ID,
+            )
+            .ok()
+        })
+        .expect("some lower bump is off the curve")
+}
+
+// `instruction` with `owner`'s bucket swapped for `address`
+pub fn with_bucket_at(
+    mut instruction: Instruction,
+    owner: &Pubkey,
+    address: Pubkey,
+) -> Instruction {
+    for meta in &mut instruction.accounts {
+        if meta.pubkey == bucket(owner) {
+            meta.pubkey = address;
+        }
+    }
+    instruction
+}
+
+pub fn initialize_ix(admin: &Pubkey, config: RateLimiterConfig) -> Instruction {
+    ix(
+        accounts::Initialize {
+            global_config: global_config(),
+            admin: *admin,
+            program: solana_rate_limiter::ID,
+            program_data: program_data_address(),
+            system_program: system_program::ID,
+        },
+        instruction::Initialize { config },
+    )
+}
+
+pub fn initialize_with_preset_ix(admin: &Pubkey, preset: ConfigPreset) -> Instruction {
+    ix(
+        accounts::Initialize {
+            global_config: global_config(),
+            admin: *admin,
+            program: solana_rate_limiter::ID,
+            program_data: program_data_address(),
+            system_program: system_program::ID,
+        },
+        instruction::InitializeWithPreset { preset },
+    )
+}
+
+pub fn initialize_stats_ix(admin: &Pubkey) -> Instruction {
+    ix(
+        accounts::InitializeStats {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            admin: *admin,
+            payer: *admin,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeStats {
+            snapshot_interval_seconds: 3600,
+        },
+    )
+}
+
+// The wallet pays for its own bucket
+pub fn register_ix(client: &Pubkey) -> Instruction {
+    register_paid_by_ix(client, client)
+}
+
+pub fn register_paid_by_ix(client: &Pubkey, payer: &Pubkey) -> Instruction {
+    ix(
+        accounts::RegisterClient {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(client),
+            fast_bucket: pda::fast_bucket_address(client).0,
+            shard_parent: pda::shard_parent_address(client).0,
+            payer_meta: pda::payer_meta_address(payer).0,
+            treasury: None,
+            client: *client,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::RegisterClient {},
+    )
+}
+
+pub fn register_sponsored_ix(client: &Pubkey) -> Instruction {
+    ix(
+        accounts::RegisterSponsored {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(client),
+            fast_bucket: pda::fast_bucket_address(client).0,
+            shard_parent: pda::shard_parent_address(client).0,
+            sponsor_pool: pda::sponsor_pool_address().0,
+            client: *client,
+            system_program: system_program::ID,
+        },
+        instruction::RegisterSponsored {},
+    )
+}
+
+pub fn fund_sponsor_pool_ix(admin: &Pubkey, payer: &Pubkey, lamports: u64) -> Instruction {
+    ix(
+        accounts::FundSponsorPool {
+            global_config: global_config(),
+            sponsor_pool: pda::sponsor_pool_address().0,
+            admin: *admin,
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::FundSponsorPool { lamports },
+    )
+}
+
+// With the stats account, which consumes only get counted in when they pass it
+fn consume_accounts(client: &Pubkey) -> accounts::ConsumeRequest {
+    accounts::ConsumeRequest {
+        global_config: global_config(),
+        global_stats: Some(global_stats()),
+        client_bucket: bucket(client),
+        client: *client,
+        caller_entry: None,
+        instructions: None,
+        schedule: None,
+        owner_denylist: None,
+        pause_grace: None,
+    }
+}
+
+pub fn consume_ix(client: &Pubkey) -> Instruction {
+    ix(consume_accounts(client), instruction::ConsumeRequest {})
+}
+
+// With the PauseGrace account, which set_pause_grace has to have created
+pub fn consume_with_grace_ix(client: &Pubkey) -> Instruction {
+    ix(
+        accounts::ConsumeRequest {
+            pause_grace: Some(pda::pause_grace_address().0),
+            ..consume_accounts(client)
+        },
+        instruction::ConsumeRequest {},
+    )
+}
+
+pub fn gate_check_ix(client: &Pubkey) -> Instruction {
+    ix(consume_accounts(client), instruction::GateCheck {})
+}
+
+pub fn try_consume_ix(client: &Pubkey) -> Instruction {
+    ix(
+        accounts::TryConsume {
+            global_config: global_config(),
+            global_stats: Some(global_stats()),
+            client_bucket: bucket(client),
+            client: *client,
+            schedule: None,
+        },
+        instruction::TryConsume {},
+    )
+}
+
+pub fn consume_amount_ix(client: &Pubkey, amount: u64) -> Instruction {
+    ix(
+        consume_accounts(client),
+        instruction::ConsumeAmount { amount },
+    )
+}
+
+pub fn update_config_ix(
+    admin: &Pubkey,
+    config: RateLimiterConfig,
+    reset_windows: bool,
+) -> Instruction {
+    ix(
+        accounts::UpdateConfig {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::UpdateConfig {
+            config,
+            reset_windows,
+        },
+    )
+}
+
+pub fn toggle_pause_ix(admin: &Pubkey) -> Instruction {
+    ix(
+        accounts::TogglePause {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::TogglePause {},
+    )
+}
+
+pub fn pause_ix(admin: &Pubkey, reason: u8, auto_unpause_at: i64) -> Instruction {
+    ix(
+        accounts::TogglePause {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::Pause {
+            reason,
+            auto_unpause_at,
+        },
+    )
+}
+
+pub fn block_ix(admin: &Pubkey, client: &Pubkey) -> Instruction {
+    ix(
+        accounts::BlockClient {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(client),
+            admin: *admin,
+            client_wallet: *client,
+        },
+        instruction::BlockClient {},
+    )
+}
+
+pub fn reset_ix(admin: &Pubkey, client: &Pubkey) -> Instruction {
+    ix(
+        accounts::ResetClient {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(client),
+            admin: *admin,
+            client_wallet: *client,
+        },
+        instruction::ResetClient {},
+    )
+}
+
+// `authority` is the program's upgrade authority, which the harness makes the admin
+pub fn set_admin_throttle_ix(
+    authority: &Pubkey,
+    window_seconds: i64,
+    max_actions: u32,
+) -> Instruction {
+    ix(
+        accounts::BeginAdminRecovery {
+            global_config: global_config(),
+            authority: *authority,
+            program: solana_rate_limiter::ID,
+            program_data: program_data_address(),
+        },
+        instruction::SetAdminThrottle {
+            window_seconds,
+            max_actions,
+        },
+    )
+}
+
+pub fn set_min_client_balance_ix(admin: &Pubkey, lamports: u64) -> Instruction {
+    ix(
+        accounts::UpdateConfig {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::SetMinClientBalance { lamports },
+    )
+}
+
+pub fn set_balance_exempt_ix(admin: &Pubkey, client: &Pubkey, exempt: bool) -> Instruction {
+    ix(
+        accounts::FreezeClient {
+            global_config: global_config(),
+            client_bucket: bucket(client),
+            admin: *admin,
+            client_wallet: *client,
+        },
+        instruction::SetBalanceExempt { exempt },
+    )
+}
+
+pub fn set_inactivity_forgiveness_ix(admin: &Pubkey, seconds: i64) -> Instruction {
+    ix(
+        accounts::UpdateConfig {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::SetInactivityForgiveness { seconds },
+    )
+}
+
+pub fn set_recovery_inactivity_ix(admin: &Pubkey, seconds: i64) -> Instruction {
+    ix(
+        accounts::UpdateConfig {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::SetRecoveryInactivity { seconds },
+    )
+}
+
+pub fn set_proration_mode_ix(admin: &Pubkey, mode: u8) -> Instruction {
+    ix(
+        accounts::UpdateConfig {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::SetProrationMode { mode },
+    )
+}
+
+pub fn set_recovery_delegate_ix(client: &Pubkey, delegate: Option<Pubkey>) -> Instruction {
+    ix(
+        accounts::SetRecoveryDelegate {
+            global_config: global_config(),
+            client_bucket: bucket(client),
+            owner: *client,
+        },
+        instruction::SetRecoveryDelegate { delegate },
+    )
+}
+
+// For an unsponsored bucket
+pub fn recover_bucket_ix(client: &Pubkey, delegate: &Pubkey) -> Instruction {
+    ix(
+        accounts::RecoverBucket {
+            global_config: global_config(),
+            global_stats: global_stats(),
+            client_bucket: bucket(client),
+            sponsor_pool: None,
+            delegate: *delegate,
+        },
+        instruction::RecoverBucket {},
+    )
+}
+
+pub fn set_pause_grace_ix(admin: &Pubkey, grace_seconds: i64, budget: u64) -> Instruction {
+    ix(
+        accounts::SetPauseGrace {
+            global_config: global_config(),
+            pause_grace: pda::pause_grace_address().0,
+            admin: *admin,
+            system_program: system_program::ID,
+        },
+        instruction::SetPauseGrace {
+            grace_seconds,
+            budget,
+        },
+    )
+}
+
+pub fn set_max_buckets_per_payer_ix(admin: &Pubkey, max_buckets: u32) -> Instruction {
+    ix(
+        accounts::UpdateConfig {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::SetMaxBucketsPerPayer { max_buckets },
+    )
+}
+
+pub fn set_refund_authority_ix(
+    admin: &Pubkey,
+    authority: Pubkey,
+    max_per_window: u64,
+) -> Instruction {
+    ix(
+        accounts::UpdateConfig {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::SetRefundAuthority {
+            authority,
+            max_per_window,
+            lower_totals: false,
+        },
+    )
+}
+
+pub fn set_limit_feeder_ix(
+    admin: &Pubkey,
+    feeder: Pubkey,
+    floor: u64,
+    ceiling: u64,
+) -> Instruction {
+    ix(
+        accounts::UpdateConfig {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::SetLimitFeeder {
+            feeder,
+            floor,
+            ceiling,
+        },
+    )
+}
+
+pub fn push_dynamic_limit_ix(feeder: &Pubkey, new_max: u64, valid_until: i64) -> Instruction {
+    ix(
+        accounts::PushDynamicLimit {
+            global_config: global_config(),
+            limit_feeder: *feeder,
+        },
+        instruction::PushDynamicLimit {
+            new_max,
+            valid_until,
+        },
+    )
+}
+
+pub fn set_authority_ix(
+    admin: &Pubkey,
+    kind: u8,
+    new_key: Pubkey,
+    effective_at: i64,
+) -> Instruction {
+    ix(
+        accounts::UpdateConfig {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::SetAuthority {
+            kind,
+            new_key,
+            effective_at,
+        },
+    )
+}
+
+pub fn cancel_authority_rotation_ix(admin: &Pubkey, kind: u8) -> Instruction {
+    ix(
+        accounts::UpdateConfig {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::CancelAuthorityRotation { kind },
+    )
+}
+
+pub fn refund_ix(authority: &Pubkey, client: &Pubkey, amount: u64, nonce: u64) -> Instruction {
+    ix(
+        accounts::RefundRequest {
+            global_config: global_config(),
+            client_bucket: bucket(client),
+            refund_authority: *authority,
+            client_wallet: *client,
+        },
+        instruction::RefundRequest {
+            amount,
+            attestation_nonce: nonce,
+        },
+    )
+}
+
+pub fn set_event_tag_ix(owner: &Pubkey, event_tag: [u8; 16]) -> Instruction {
+    ix(
+        accounts::SetLabel {
+            global_config: global_config(),
+            client_bucket: bucket(owner),
+            owner: *owner,
+        },
+        instruction::SetEventTag { event_tag },
+    )
+}
+
+pub fn set_event_tag_policy_ix(admin: &Pubkey, set_once: bool) -> Instruction {
+    ix(
+        accounts::UpdateConfig {
+            global_config: global_config(),
+            admin: *admin,
+        },
+        instruction::SetEventTagPolicy { set_once },
+    )
+}
+
+pub fn submit_to_leaderboard_ix(client: &Pubkey, payer: &Pubkey) -> Instruction {
+    ix(
+        accounts::SubmitToLeaderboard {
+            leaderboard: pda::leaderboard_address().0,
+            client_bucket: bucket(client),
+            payer: *payer,
+            system_program: system_program::ID,
+        },
+        instruction::SubmitToLeaderboard {},
+    )
+}
+
+fn custom_code(result: Result<(), BanksClientError>) -> u32 {
+    match result.expect_err("transaction succeeded").unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => code,
+        other => panic!("expected a custom program error, got {:?}", other),
+    }
+}
+
+pub fn assert_error(result: Result<(), BanksClientError>, expected: RateLimiterError) {
+    assert_eq!(custom_code(result), u32::from(expected), "{:?}", expected);
+}
+
+pub fn assert_anchor_error(result: Result<(), BanksClientError>, expected: ErrorCode) {
+    assert_eq!(custom_code(result), u32::from(expected), "{:?}", expected);
+}
+
+// System program's AccountAlreadyInUse, what an `init` on an existing PDA fails with
+pub fn assert_already_exists(result: Result<(), BanksClientError>) {
+    assert_eq!(custom_code(result), 0);
+}
@@ -0,0 +1,713 @@
+#![cfg(feature = "test-sbf")]
+
+mod common;
+
+use anchor_lang::error::ErrorCode;
+use common::*;
+use solana_rate_limiter::constants::{
+    AUTHORITY_LIMIT_FEEDER, AUTHORITY_REFUND, MAX_AUTO_UNPAUSE_DELAY, MAX_PAUSE_GRACE_SECONDS,
+    PAUSE_REASON_MAINTENANCE,
+};
+use solana_rate_limiter::errors::RateLimiterError;
+use solana_rate_limiter::{logic, pda};
+use solana_rate_limiter::state::{ClientBucket, Leaderboard};
+use solana_sdk::clock::Clock;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_system_interface::instruction as system_instruction;
+
+#[tokio::test]
+async fn register_creates_an_empty_bucket() {
+    let mut harness = TestHarness::new().await;
+    let wallet = harness.client().await;
+    let bucket = harness.bucket(&wallet.pubkey()).await;
+    assert_eq!(bucket.owner, wallet.pubkey());
+    assert_eq!(bucket.request_count, 0);
+    assert!(!bucket.is_blocked());
+    assert_eq!(harness.stats().await.total_clients, 1);
+
+    let again = harness
+        .send(&[register_ix(&wallet.pubkey())], &[&wallet])
+        .await;
+    assert_already_exists(again);
+}
+
+#[tokio::test]
+async fn payers_are_capped_on_the_buckets_they_fund() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    harness
+        .send_as_admin(&[set_max_buckets_per_payer_ix(&admin, 2)])
+        .await
+        .unwrap();
+
+    let funder = harness.wallet().await;
+    for _ in 0..2 {
+        let client = Keypair::new();
+        let register = register_paid_by_ix(&client.pubkey(), &funder.pubkey());
+        harness
+            .send(&[register], &[&client, &funder])
+            .await
+            .unwrap();
+        let bucket = harness.bucket(&client.pubkey()).await;
+        assert_eq!(bucket.registered_by, funder.pubkey());
+    }
+    let meta = harness.payer_meta(&funder.pubkey()).await;
+    assert_eq!(meta.buckets_created, 2);
+    assert!(meta.last_registration_ts > 0);
+
+    // A third owner, still a fresh key, is refused on the funder's count alone
+    let client = Keypair::new();
+    let register = register_paid_by_ix(&client.pubkey(), &funder.pubkey());
+    let result = harness.send(&[register], &[&client, &funder]).await;
+    assert_error(result, RateLimiterError::PayerBucketCapReached);
+
+    // Other payers keep their own count
+    let wallet = harness.client().await;
+    assert_eq!(
+        harness.bucket(&wallet.pubkey()).await.registered_by,
+        wallet.pubkey()
+    );
+    assert_eq!(
+        harness.payer_meta(&wallet.pubkey()).await.buckets_created,
+        1
+    );
+}
+
+#[tokio::test]
+async fn sponsored_buckets_record_the_pool_as_their_payer() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    harness
+        .send_as_admin(&[fund_sponsor_pool_ix(&admin, &admin, LAMPORTS_PER_SOL)])
+        .await
+        .unwrap();
+
+    let client = Keypair::new();
+    harness
+        .send(&[register_sponsored_ix(&client.pubkey())], &[&client])
+        .await
+        .unwrap();
+    let bucket = harness.bucket(&client.pubkey()).await;
+    assert!(bucket.is_sponsored());
+    assert_eq!(bucket.registered_by, pda::sponsor_pool_address().0);
+}
+
+#[tokio::test]
+async fn consume_needs_a_bucket() {
+    let mut harness = TestHarness::new().await;
+    let wallet = harness.wallet().await;
+    let result = harness.consume(&wallet).await;
+    // Only try_consume turns this into ClientNotRegistered
+    assert_anchor_error(result, ErrorCode::AccountNotInitialized);
+}
+
+#[tokio::test]
+async fn buckets_only_live_at_the_canonical_bump() {
+    let mut harness = TestHarness::new().await;
+    let wallet = harness.wallet().await;
+    let owner = wallet.pubkey();
+    let shadow = shadow_bucket(&owner);
+
+    let registered = harness
+        .send(
+            &[with_bucket_at(register_ix(&owner), &owner, shadow)],
+            &[&wallet],
+        )
+        .await;
+    assert_anchor_error(registered, ErrorCode::ConstraintSeeds);
+    assert!(harness.account::<ClientBucket>(shadow).await.is_none());
+
+    harness
+        .send(&[register_ix(&owner)], &[&wallet])
+        .await
+        .unwrap();
+    let consumed = harness
+        .send(
+            &[with_bucket_at(consume_ix(&owner), &owner, shadow)],
+            &[&wallet],
+        )
+        .await;
+    // Nothing was created there, so there is no bucket to take the consume
+    assert_anchor_error(consumed, ErrorCode::AccountNotInitialized);
+    assert_eq!(harness.bucket(&owner).await.request_count, 0);
+    assert_eq!(harness.stats().await.total_clients, 1);
+}
+
+#[tokio::test]
+async fn consume_until_the_window_is_spent() {
+    let mut harness = TestHarness::new().await;
+    let wallet = harness.client().await;
+    for used in 1..=LIMITS.max_requests {
+        harness.consume(&wallet).await.unwrap();
+        assert_eq!(harness.bucket(&wallet.pubkey()).await.request_count, used);
+    }
+
+    let result = harness.consume(&wallet).await;
+    assert_error(result, RateLimiterError::RateLimitExceeded);
+    // The rejection rolls back with the transaction, count included
+    let bucket = harness.bucket(&wallet.pubkey()).await;
+    assert_eq!(bucket.request_count, LIMITS.max_requests);
+    assert_eq!(bucket.total_requests, LIMITS.max_requests);
+    assert_eq!(harness.stats().await.total_requests, LIMITS.max_requests);
+}
+
+// The agreed ceiling for a default build; verbose-logs builds format their logs
+// and are not held to it
+#[cfg(not(feature = "verbose-logs"))]
+#[tokio::test]
+async fn consume_stays_under_the_compute_budget() {
+    const CONSUME_CU_BUDGET: u64 = 6_000;
+    let mut harness = TestHarness::new().await;
+    let wallet = harness.client().await;
+    // The first consume opens the window, the second only adds to it
+    for _ in 0..2 {
+        let units = harness
+            .send_metered(&[consume_ix(&wallet.pubkey())], &[&wallet])
+            .await;
+        println!("consume_request: {} CU", units);
+        assert!(units <= CONSUME_CU_BUDGET, "{} CU", units);
+    }
+}
+
+#[tokio::test]
+async fn refunds_give_back_quota_once_per_nonce() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let backend = harness.wallet().await;
+    let wallet = harness.client().await;
+    harness
+        .send_as_admin(&[set_refund_authority_ix(&admin, backend.pubkey(), 2)])
+        .await
+        .unwrap();
+    for _ in 0..LIMITS.max_requests {
+        harness.consume(&wallet).await.unwrap();
+    }
+
+    let refund = refund_ix(&backend.pubkey(), &wallet.pubkey(), 1, 1);
+    harness.send(std::slice::from_ref(&refund), &[&backend]).await.unwrap();
+    let bucket = harness.bucket(&wallet.pubkey()).await;
+    assert_eq!(bucket.request_count, LIMITS.max_requests - 1);
+    assert_eq!(bucket.total_requests, LIMITS.max_requests);
+    harness.consume(&wallet).await.unwrap();
+
+    let replayed = harness.send(&[refund], &[&backend]).await;
+    assert_error(replayed, RateLimiterError::RefundNonceReused);
+    // One unit of the window's two has been refunded already
+    let over = refund_ix(&backend.pubkey(), &wallet.pubkey(), 2, 2);
+    assert_error(
+        harness.send(&[over], &[&backend]).await,
+        RateLimiterError::RefundCapExceeded,
+    );
+    // Only the refund authority can sign one
+    let outsider = refund_ix(&wallet.pubkey(), &wallet.pubkey(), 1, 3);
+    assert_error(
+        harness.send(&[outsider], &[&wallet]).await,
+        RateLimiterError::Unauthorized,
+    );
+}
+
+#[tokio::test]
+async fn rotated_refund_authorities_hand_over_at_the_scheduled_time() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let old = harness.wallet().await;
+    let new = harness.wallet().await;
+    let wallet = harness.client().await;
+    harness
+        .send_as_admin(&[set_refund_authority_ix(&admin, old.pubkey(), 3)])
+        .await
+        .unwrap();
+    for _ in 0..LIMITS.max_requests {
+        harness.consume(&wallet).await.unwrap();
+    }
+    let clock: Clock = harness.context.banks_client.get_sysvar().await.unwrap();
+    let handover = clock.unix_timestamp + 10;
+    harness
+        .send_as_admin(&[set_authority_ix(
+            &admin,
+            AUTHORITY_REFUND,
+            new.pubkey(),
+            handover,
+        )])
+        .await
+        .unwrap();
+
+    // Until the handover only the old key signs
+    let early = refund_ix(&new.pubkey(), &wallet.pubkey(), 1, 1);
+    assert_error(
+        harness.send(&[early], &[&new]).await,
+        RateLimiterError::Unauthorized,
+    );
+    let before = refund_ix(&old.pubkey(), &wallet.pubkey(), 1, 1);
+    harness.send(&[before], &[&old]).await.unwrap();
+
+    harness.set_clock(handover).await;
+    let late = refund_ix(&old.pubkey(), &wallet.pubkey(), 1, 2);
+    assert_error(
+        harness.send(&[late], &[&old]).await,
+        RateLimiterError::Unauthorized,
+    );
+    let after = refund_ix(&new.pubkey(), &wallet.pubkey(), 1, 2);
+    harness.send(&[after], &[&new]).await.unwrap();
+    assert_eq!(
+        harness.bucket(&wallet.pubkey()).await.request_count,
+        LIMITS.max_requests - 2
+    );
+
+    // Once the handover has passed there is no rotation left to call off
+    let settled = harness.config().await;
+    assert_eq!(settled.authority(AUTHORITY_REFUND, handover), new.pubkey());
+    assert_error(
+        harness
+            .send_as_admin(&[cancel_authority_rotation_ix(&admin, AUTHORITY_REFUND)])
+            .await,
+        RateLimiterError::NoPendingRotation,
+    );
+}
+
+#[tokio::test]
+async fn cancelled_rotations_leave_the_feeder_in_place() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let old = harness.wallet().await;
+    let new = harness.wallet().await;
+    harness
+        .send_as_admin(&[set_limit_feeder_ix(&admin, old.pubkey(), 1, 10)])
+        .await
+        .unwrap();
+    let clock: Clock = harness.context.banks_client.get_sysvar().await.unwrap();
+    let now = clock.unix_timestamp;
+    harness
+        .send_as_admin(&[set_authority_ix(
+            &admin,
+            AUTHORITY_LIMIT_FEEDER,
+            new.pubkey(),
+            now + 30,
+        )])
+        .await
+        .unwrap();
+    harness
+        .send_as_admin(&[cancel_authority_rotation_ix(&admin, AUTHORITY_LIMIT_FEEDER)])
+        .await
+        .unwrap();
+    assert_error(
+        harness
+            .send_as_admin(&[cancel_authority_rotation_ix(&admin, AUTHORITY_LIMIT_FEEDER)])
+            .await,
+        RateLimiterError::NoPendingRotation,
+    );
+
+    // Past the cancelled handover the old feeder still pushes and the new one never does
+    harness.set_clock(now + 30).await;
+    let refused = push_dynamic_limit_ix(&new.pubkey(), 5, now + 90);
+    assert_error(
+        harness.send(&[refused], &[&new]).await,
+        RateLimiterError::Unauthorized,
+    );
+    let pushed = push_dynamic_limit_ix(&old.pubkey(), 5, now + 90);
+    harness.send(&[pushed], &[&old]).await.unwrap();
+    assert_eq!(harness.config().await.dynamic_max, 5);
+
+    // Handovers cannot be backdated
+    assert_error(
+        harness
+            .send_as_admin(&[set_authority_ix(
+                &admin,
+                AUTHORITY_LIMIT_FEEDER,
+                new.pubkey(),
+                now,
+            )])
+            .await,
+        RateLimiterError::InvalidConfig,
+    );
+}
+
+#[tokio::test]
+async fn consume_amount_charges_the_whole_amount() {
+    let mut harness = TestHarness::new().await;
+    let wallet = harness.client().await;
+    harness
+        .send(&[consume_amount_ix(&wallet.pubkey(), 2)], &[&wallet])
+        .await
+        .unwrap();
+    assert_eq!(harness.bucket(&wallet.pubkey()).await.request_count, 2);
+
+    let over = harness
+        .send(&[consume_amount_ix(&wallet.pubkey(), 2)], &[&wallet])
+        .await;
+    assert_error(over, RateLimiterError::RateLimitExceeded);
+}
+
+#[tokio::test]
+async fn window_rolls_over_once_it_has_passed() {
+    let mut harness = TestHarness::new().await;
+    let wallet = harness.client().await;
+    for _ in 0..LIMITS.max_requests {
+        harness.consume(&wallet).await.unwrap();
+    }
+
+    // A second short of the end the window is still spent
+    harness.advance_clock(LIMITS.window_seconds - 1).await;
+    let early = harness.consume(&wallet).await;
+    assert_error(early, RateLimiterError::RateLimitExceeded);
+
+    harness.advance_clock(1).await;
+    harness.consume(&wallet).await.unwrap();
+    let bucket = harness.bucket(&wallet.pubkey()).await;
+    let clock: Clock = harness.context.banks_client.get_sysvar().await.unwrap();
+    assert_eq!(bucket.request_count, 1);
+    assert_eq!(bucket.window_start, clock.unix_timestamp);
+    assert_eq!(bucket.total_requests, LIMITS.max_requests + 1);
+}
+
+#[tokio::test]
+async fn closed_windows_are_counted_by_how_full_they_got() {
+    let mut harness = TestHarness::new().await;
+    let mut wallets = Vec::new();
+    for used in 0..=LIMITS.max_requests {
+        let wallet = harness.client().await;
+        for _ in 0..used {
+            harness.consume(&wallet).await.unwrap();
+        }
+        wallets.push(wallet);
+    }
+    let denied = harness.client().await;
+    for _ in 0..LIMITS.max_requests {
+        harness.consume(&denied).await.unwrap();
+    }
+    harness
+        .send(&[gate_check_ix(&denied.pubkey())], &[&denied])
+        .await
+        .unwrap();
+    // Nothing is counted until a consume closes the window
+    assert_eq!(harness.stats().await.usage_histogram, [0; 5]);
+
+    harness.advance_clock(LIMITS.window_seconds).await;
+    for wallet in wallets.iter().chain([&denied]) {
+        harness.consume(wallet).await.unwrap();
+    }
+    assert_eq!(harness.stats().await.usage_histogram, [1, 1, 1, 1, 1]);
+}
+
+#[tokio::test]
+async fn buckets_climb_the_leaderboard_by_their_totals() {
+    let mut harness = TestHarness::new().await;
+    let (top, second, idle) = (
+        harness.client().await,
+        harness.client().await,
+        harness.client().await,
+    );
+    for (wallet, used) in [(&top, 2), (&second, 1)] {
+        for _ in 0..used {
+            harness.consume(wallet).await.unwrap();
+        }
+    }
+    let submit = |wallet: &Keypair| submit_to_leaderboard_ix(&wallet.pubkey(), &wallet.pubkey());
+
+    let nothing_yet = harness.send(&[submit(&idle)], &[&idle]).await;
+    assert_error(nothing_yet, RateLimiterError::BelowLeaderboardThreshold);
+    for wallet in [&second, &top] {
+        harness.send(&[submit(wallet)], &[wallet]).await.unwrap();
+    }
+    let board: Leaderboard = harness.account(pda::leaderboard_address().0).await.unwrap();
+    let ranking: Vec<(Pubkey, u64)> = board.entries().iter().map(|e| (e.owner, e.total)).collect();
+    assert_eq!(ranking, [(top.pubkey(), 2), (second.pubkey(), 1)]);
+
+    // A resubmission moves the entry it already has
+    for _ in 0..2 {
+        harness.consume(&second).await.unwrap();
+    }
+    harness.send(&[submit(&second)], &[&second]).await.unwrap();
+    let board: Leaderboard = harness.account(pda::leaderboard_address().0).await.unwrap();
+    let ranking: Vec<(Pubkey, u64)> = board.entries().iter().map(|e| (e.owner, e.total)).collect();
+    assert_eq!(ranking, [(second.pubkey(), 3), (top.pubkey(), 2)]);
+}
+
+#[tokio::test]
+async fn a_recovery_delegate_can_close_a_bucket_only_once_it_idles() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    harness
+        .send_as_admin(&[set_recovery_inactivity_ix(&admin, 3600)])
+        .await
+        .unwrap();
+    let lost = harness.client().await;
+    let cleared = harness.client().await;
+    let delegate = harness.wallet().await;
+    for wallet in [&lost, &cleared] {
+        let ix = set_recovery_delegate_ix(&wallet.pubkey(), Some(delegate.pubkey()));
+        harness.send(&[ix], &[wallet]).await.unwrap();
+    }
+    let recover = |wallet: &Keypair| recover_bucket_ix(&wallet.pubkey(), &delegate.pubkey());
+
+    // Consuming keeps the owner in control
+    harness.advance_clock(3000).await;
+    harness.consume(&lost).await.unwrap();
+    harness.advance_clock(3000).await;
+    let active = harness.send(&[recover(&lost)], &[&delegate]).await;
+    assert_error(active, RateLimiterError::BucketStillActive);
+
+    // An owner who clears the delegate is never recovered
+    let ix = set_recovery_delegate_ix(&cleared.pubkey(), None);
+    harness.send(&[ix], &[&cleared]).await.unwrap();
+    harness.advance_clock(600).await;
+    let not_theirs = harness.send(&[recover(&cleared)], &[&delegate]).await;
+    assert_error(not_theirs, RateLimiterError::Unauthorized);
+
+    let bucket = pda::client_bucket_address(&lost.pubkey()).0;
+    let held = harness.balance(&bucket).await;
+    let before = harness.balance(&delegate.pubkey()).await;
+    let clients = harness.stats().await.total_clients;
+    harness.send(&[recover(&lost)], &[&delegate]).await.unwrap();
+    assert!(harness.account::<ClientBucket>(bucket).await.is_none());
+    assert_eq!(harness.balance(&delegate.pubkey()).await, before + held);
+    assert_eq!(harness.stats().await.total_clients, clients - 1);
+}
+
+#[tokio::test]
+async fn event_tags_change_freely_until_the_admin_holds_them_to_the_first() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let wallet = harness.client().await;
+    let tenant = |name: &[u8]| {
+        let mut tag = [0; 16];
+        tag[..name.len()].copy_from_slice(name);
+        tag
+    };
+    for tag in [tenant(b"tenant-a"), tenant(b"tenant-b")] {
+        let ix = set_event_tag_ix(&wallet.pubkey(), tag);
+        harness.send(&[ix], &[&wallet]).await.unwrap();
+    }
+    assert_eq!(
+        harness.bucket(&wallet.pubkey()).await.event_tag,
+        tenant(b"tenant-b")
+    );
+
+    harness
+        .send_as_admin(&[set_event_tag_policy_ix(&admin, true)])
+        .await
+        .unwrap();
+    // A tag set before the policy counts as set
+    for tag in [tenant(b"tenant-c"), [0; 16]] {
+        let ix = set_event_tag_ix(&wallet.pubkey(), tag);
+        assert_error(
+            harness.send(&[ix], &[&wallet]).await,
+            RateLimiterError::EventTagLocked,
+        );
+    }
+    // An untagged bucket still gets its one
+    let fresh = harness.client().await;
+    let ix = set_event_tag_ix(&fresh.pubkey(), tenant(b"tenant-d"));
+    harness.send(&[ix], &[&fresh]).await.unwrap();
+    harness.consume(&fresh).await.unwrap();
+    assert_eq!(
+        harness.bucket(&fresh.pubkey()).await.event_tag,
+        tenant(b"tenant-d")
+    );
+    assert_eq!(
+        harness.bucket(&wallet.pubkey()).await.event_tag,
+        tenant(b"tenant-b")
+    );
+}
+
+#[tokio::test]
+async fn pause_refuses_consumes_and_registrations() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let wallet = harness.client().await;
+    harness
+        .send_as_admin(&[toggle_pause_ix(&admin)])
+        .await
+        .unwrap();
+    assert!(harness.config().await.is_paused());
+
+    let consume = harness.consume(&wallet).await;
+    assert_error(consume, RateLimiterError::ProgramPaused);
+    let newcomer = harness.wallet().await;
+    let register = harness
+        .send(&[register_ix(&newcomer.pubkey())], &[&newcomer])
+        .await;
+    assert_error(register, RateLimiterError::ProgramPaused);
+
+    harness
+        .send_as_admin(&[toggle_pause_ix(&admin)])
+        .await
+        .unwrap();
+    assert!(!harness.config().await.is_paused());
+    harness.consume(&wallet).await.unwrap();
+}
+
+#[tokio::test]
+async fn a_pause_with_a_deadline_lifts_itself() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let wallet = harness.client().await;
+    let clock: Clock = harness.context.banks_client.get_sysvar().await.unwrap();
+    let now = clock.unix_timestamp;
+
+    let too_far = harness
+        .send_as_admin(&[pause_ix(
+            &admin,
+            PAUSE_REASON_MAINTENANCE,
+            now + MAX_AUTO_UNPAUSE_DELAY + 1,
+        )])
+        .await;
+    assert_error(too_far, RateLimiterError::InvalidSchedule);
+
+    harness
+        .send_as_admin(&[pause_ix(&admin, PAUSE_REASON_MAINTENANCE, now + 30)])
+        .await
+        .unwrap();
+    let paused = harness.consume(&wallet).await;
+    assert_error(paused, RateLimiterError::ProgramPaused);
+
+    harness.advance_clock(30).await;
+    harness.consume(&wallet).await.unwrap();
+    // The consume only read the config: the lapsed flag is left for the admin
+    let config = harness.config().await;
+    assert!(config.is_paused());
+    assert!(!config.emergency_pause_active(now + 30));
+}
+
+#[tokio::test]
+async fn consumes_need_the_minimum_balance_unless_the_client_is_exempt() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let wallet = harness.client().await;
+    let balance = harness.balance(&wallet.pubkey()).await;
+    harness
+        .send_as_admin(&[set_min_client_balance_ix(&admin, balance + 1)])
+        .await
+        .unwrap();
+
+    let short = harness.consume(&wallet).await;
+    assert_error(short, RateLimiterError::BalanceTooLow);
+    // try_consume is no way around it
+    let tried = harness.send(&[try_consume_ix(&wallet.pubkey())], &[&wallet]).await;
+    assert_error(tried, RateLimiterError::BalanceTooLow);
+
+    // The check only reads the balance: the consume takes nothing from the wallet
+    harness.fund(&wallet.pubkey()).await;
+    harness.consume(&wallet).await.unwrap();
+    let topped_up = balance + LAMPORTS_PER_SOL;
+    assert_eq!(harness.balance(&wallet.pubkey()).await, topped_up);
+
+    let payer = harness.context.payer.pubkey();
+    let drain = system_instruction::transfer(&wallet.pubkey(), &payer, LAMPORTS_PER_SOL);
+    harness.send(&[drain], &[&wallet]).await.unwrap();
+    let drained = harness.consume(&wallet).await;
+    assert_error(drained, RateLimiterError::BalanceTooLow);
+
+    harness
+        .send_as_admin(&[set_balance_exempt_ix(&admin, &wallet.pubkey(), true)])
+        .await
+        .unwrap();
+    harness.consume(&wallet).await.unwrap();
+}
+
+#[tokio::test]
+async fn long_idle_buckets_have_their_violations_forgiven_but_not_their_blocks() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let idle = harness.client().await;
+    let blocked = harness.client().await;
+    harness
+        .send_as_admin(&[set_inactivity_forgiveness_ix(&admin, 3600)])
+        .await
+        .unwrap();
+
+    // A gate_check denial is the one rejection that leaves a violation behind
+    for wallet in [&idle, &blocked] {
+        for _ in 0..LIMITS.max_requests {
+            harness.consume(wallet).await.unwrap();
+        }
+        harness
+            .send(&[gate_check_ix(&wallet.pubkey())], &[wallet])
+            .await
+            .unwrap();
+        assert_eq!(harness.bucket(&wallet.pubkey()).await.violations, 1);
+    }
+    harness
+        .send_as_admin(&[block_ix(&admin, &blocked.pubkey())])
+        .await
+        .unwrap();
+
+    harness.advance_clock(3600).await;
+    harness.consume(&idle).await.unwrap();
+    // Exactly the threshold is not past it yet
+    assert_eq!(harness.bucket(&idle.pubkey()).await.violations, 1);
+
+    harness.advance_clock(3601).await;
+    harness.consume(&idle).await.unwrap();
+    let bucket = harness.bucket(&idle.pubkey()).await;
+    assert_eq!((bucket.violations, bucket.violations_updated_at), (0, 0));
+
+    let still_blocked = harness.consume(&blocked).await;
+    assert_error(still_blocked, RateLimiterError::ClientBlocked);
+    assert!(harness.bucket(&blocked.pubkey()).await.is_blocked());
+}
+
+#[tokio::test]
+async fn consumes_get_through_a_fresh_pause_while_its_grace_lasts() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let wallet = harness.client().await;
+    harness
+        .send_as_admin(&[set_pause_grace_ix(&admin, 30, 1)])
+        .await
+        .unwrap();
+
+    harness
+        .send_as_admin(&[toggle_pause_ix(&admin)])
+        .await
+        .unwrap();
+    // Without the PauseGrace account a consume gets no grace
+    let without = harness.consume(&wallet).await;
+    assert_error(without, RateLimiterError::ProgramPaused);
+
+    let grace_consume = consume_with_grace_ix(&wallet.pubkey());
+    harness
+        .send(std::slice::from_ref(&grace_consume), &[&wallet])
+        .await
+        .unwrap();
+    assert_eq!(harness.bucket(&wallet.pubkey()).await.request_count, 1);
+    let paused_at = harness.config().await.paused_at;
+    let grace = harness.pause_grace().await;
+    assert_eq!((grace.paused_at, grace.spent), (paused_at, 1));
+    // Still inside the grace period, but the budget is spent
+    let spent = harness
+        .send(std::slice::from_ref(&grace_consume), &[&wallet])
+        .await;
+    assert_error(spent, RateLimiterError::ProgramPaused);
+
+    // The next pause starts a new budget without the account being reset
+    harness
+        .send_as_admin(&[toggle_pause_ix(&admin)])
+        .await
+        .unwrap();
+    harness
+        .send_as_admin(&[set_pause_grace_ix(&admin, 30, 10)])
+        .await
+        .unwrap();
+    harness.advance_clock(1).await;
+    harness
+        .send_as_admin(&[toggle_pause_ix(&admin)])
+        .await
+        .unwrap();
+    let config = harness.config().await;
+    assert_eq!(config.grace_budget, 10);
+    assert_eq!(
+        logic::grace_left(&config, &harness.pause_grace().await),
+        10
+    );
+    harness.advance_clock(30).await;
+    let late = harness.send(&[grace_consume], &[&wallet]).await;
+    assert_error(late, RateLimiterError::ProgramPaused);
+
+    let too_long = harness
+        .send_as_admin(&[set_pause_grace_ix(&admin, MAX_PAUSE_GRACE_SECONDS + 1, 10)])
+        .await;
+    assert_error(too_long, RateLimiterError::InvalidConfig);
+}
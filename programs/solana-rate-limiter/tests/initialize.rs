@@ -0,0 +1,191 @@
+#![cfg(feature = "test-sbf")]
+
+mod common;
+
+use common::*;
+use solana_rate_limiter::constants::*;
+use solana_rate_limiter::errors::RateLimiterError;
+use solana_rate_limiter::{ConfigPreset, RateLimiterConfig};
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn initialize_stores_the_limits_and_admin() {
+    let mut harness = TestHarness::new().await;
+    let config = harness.config().await;
+    assert_eq!(config.admin, harness.admin.pubkey());
+    assert_eq!(config.max_requests, LIMITS.max_requests);
+    assert_eq!(config.window_seconds, LIMITS.window_seconds);
+    assert_eq!(config.burst_limit, LIMITS.burst_limit);
+    assert!(!config.is_paused());
+    assert_eq!(harness.stats().await.total_clients, 0);
+}
+
+#[tokio::test]
+async fn initialize_twice_is_rejected() {
+    let mut harness = TestHarness::new().await;
+    let admin = harness.admin.pubkey();
+    let again = harness
+        .send_as_admin(&[initialize_ix(&admin, LIMITS)])
+        .await;
+    assert_already_exists(again);
+}
+
+#[tokio::test]
+async fn initialize_needs_the_upgrade_authority() {
+    let mut harness = TestHarness::start().await;
+    let outsider = harness.wallet().await;
+    let result = harness
+        .send(&[initialize_ix(&outsider.pubkey(), LIMITS)], &[&outsider])
+        .await;
+    assert_error(result, RateLimiterError::NotUpgradeAuthority);
+}
+
+#[tokio::test]
+async fn initialize_validates_the_limits() {
+    let invalid = [
+        RateLimiterConfig {
+            max_requests: 0,
+            ..LIMITS
+        },
+        RateLimiterConfig {
+            window_seconds: 0,
+            ..LIMITS
+        },
+        RateLimiterConfig {
+            burst_limit: LIMITS.max_requests - 1,
+            ..LIMITS
+        },
+    ];
+    let mut harness = TestHarness::start().await;
+    let admin = harness.admin.pubkey();
+    for limits in invalid {
+        let result = harness
+            .send_as_admin(&[initialize_ix(&admin, limits)])
+            .await;
+        assert_error(result, RateLimiterError::InvalidConfig);
+    }
+}
+
+#[tokio::test]
+async fn presets_set_their_limits_and_are_recorded() {
+    let presets = [
+        (
+            ConfigPreset::Strict,
+            (
+                STRICT_MAX_REQUESTS,
+                STRICT_WINDOW_SECONDS,
+                STRICT_BURST_LIMIT,
+            ),
+            CONFIG_PRESET_STRICT,
+        ),
+        (
+            ConfigPreset::Standard,
+            (
+                STANDARD_MAX_REQUESTS,
+                STANDARD_WINDOW_SECONDS,
+                STANDARD_BURST_LIMIT,
+            ),
+            CONFIG_PRESET_STANDARD,
+        ),
+        (
+            ConfigPreset::Lenient,
+            (
+                LENIENT_MAX_REQUESTS,
+                LENIENT_WINDOW_SECONDS,
+                LENIENT_BURST_LIMIT,
+            ),
+            CONFIG_PRESET_LENIENT,
+        ),
+        (
+            ConfigPreset::Custom(LIMITS),
+            (
+                LIMITS.max_requests,
+                LIMITS.window_seconds,
+                LIMITS.burst_limit,
+            ),
+            CONFIG_PRESET_CUSTOM,
+        ),
+    ];
+    for (preset, limits, id) in presets {
+        let mut harness = TestHarness::start().await;
+        let admin = harness.admin.pubkey();
+        harness
+            .send_as_admin(&[initialize_with_preset_ix(&admin, preset)])
+            .await
+            .unwrap();
+        let config = harness.config().await;
+        assert_eq!(
+            (
+                config.max_requests,
+                config.window_seconds,
+                config.burst_limit
+            ),
+            limits
+        );
+        assert_eq!(config.config_preset, id);
+        assert_eq!(config.admin, admin);
+    }
+}
+
+#[tokio::test]
+async fn custom_presets_are_still_validated() {
+    let mut harness = TestHarness::start().await;
+    let admin = harness.admin.pubkey();
+    let result = harness
+        .send_as_admin(&[initialize_with_preset_ix(
+            &admin,
+            ConfigPreset::Custom(RateLimiterConfig {
+                burst_limit: LIMITS.max_requests - 1,
+                ..LIMITS
+            }),
+        )])
+        .await;
+    assert_error(result, RateLimiterError::InvalidConfig);
+
+    // Nor does a preset skip the upgrade authority check
+    let outsider = harness.wallet().await;
+    let result = harness
+        .send(
+            &[initialize_with_preset_ix(
+                &outsider.pubkey(),
+                ConfigPreset::Standard,
+            )],
+            &[&outsider],
+        )
+        .await;
+    assert_error(result, RateLimiterError::NotUpgradeAuthority);
+}
+
+#[tokio::test]
+async fn plain_initialize_and_updates_read_as_custom() {
+    let mut harness = TestHarness::start().await;
+    let admin = harness.admin.pubkey();
+    harness
+        .send_as_admin(&[initialize_with_preset_ix(&admin, ConfigPreset::Strict)])
+        .await
+        .unwrap();
+    let strict = ConfigPreset::Strict.limits();
+    harness
+        .send_as_admin(&[update_config_ix(&admin, strict, false)])
+        .await
+        .unwrap();
+    assert_eq!(harness.config().await.config_preset, CONFIG_PRESET_CUSTOM);
+
+    let mut harness = TestHarness::new().await;
+    assert_eq!(harness.config().await.config_preset, CONFIG_PRESET_CUSTOM);
+}
+
+#[tokio::test]
+async fn initialize_stats_is_admin_only() {
+    let mut harness = TestHarness::start().await;
+    let admin = harness.admin.pubkey();
+    harness
+        .send_as_admin(&[initialize_ix(&admin, LIMITS)])
+        .await
+        .unwrap();
+    let outsider = harness.wallet().await;
+    let result = harness
+        .send(&[initialize_stats_ix(&outsider.pubkey())], &[&outsider])
+        .await;
+    assert_error(result, RateLimiterError::Unauthorized);
+}
@@ -0,0 +1,76 @@
+// The wasm exports against account bytes captured from program-test, see
+// account_fixtures.rs. Run with
+// `wasm-pack test --node -- --features wasm --test wasm`.
+
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use js_sys::{BigInt, Reflect};
+use solana_rate_limiter::logic::{QuotaInfo, Rejection};
+use solana_rate_limiter::wasm::{decode_bucket, decode_config, quota, simulate_consume};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+// A 3 per 60s limiter whose client registered at START and consumed 2 at START + 10
+const CONFIG: &[u8] = include_bytes!("../test-data/accounts/global_config.bin");
+const BUCKET: &[u8] = include_bytes!("../test-data/accounts/client_bucket.bin");
+const START: i64 = 1_700_000_000;
+
+fn field(object: &JsValue, name: &str) -> JsValue {
+    Reflect::get(object, &JsValue::from_str(name)).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn captured_accounts_decode() {
+    let config = decode_config(CONFIG).unwrap();
+    assert_eq!(field(&config, "maxRequests"), BigInt::from(3u64).into());
+    assert_eq!(field(&config, "windowSeconds"), BigInt::from(60i64).into());
+    assert_eq!(field(&config, "paused"), JsValue::FALSE);
+
+    let bucket = decode_bucket(BUCKET).unwrap();
+    assert_eq!(field(&bucket, "requestCount"), BigInt::from(2u64).into());
+    assert_eq!(field(&bucket, "windowStart"), BigInt::from(START).into());
+    assert_eq!(field(&bucket, "blocked"), JsValue::FALSE);
+}
+
+#[wasm_bindgen_test]
+fn quota_follows_the_window() {
+    assert_eq!(
+        quota(CONFIG, None, BUCKET, START + 10).unwrap(),
+        QuotaInfo {
+            allowed: true,
+            rejection: None,
+            remaining: 1,
+            resets_in: 50
+        }
+    );
+    // Past the window the unused unit is banked, but burst_limit caps the window at 3
+    assert_eq!(
+        quota(CONFIG, None, BUCKET, START + 70).unwrap(),
+        QuotaInfo {
+            allowed: true,
+            rejection: None,
+            remaining: 3,
+            resets_in: 60
+        }
+    );
+
+    let mut full = BUCKET.to_vec();
+    // request_count follows the discriminator, version and owner
+    full[41..49].copy_from_slice(&3u64.to_le_bytes());
+    let quota = quota(CONFIG, None, &full, START + 10).unwrap();
+    assert_eq!(quota.rejection, Some(Rejection::RateLimit));
+    assert_eq!(quota.remaining, 0);
+}
+
+#[wasm_bindgen_test]
+fn exports_return_plain_objects_and_errors() {
+    let quota = simulate_consume(CONFIG, BUCKET, START + 10).unwrap();
+    assert_eq!(field(&quota, "allowed"), JsValue::TRUE);
+    assert_eq!(field(&quota, "remaining"), BigInt::from(1u64).into());
+    assert_eq!(field(&quota, "resetsIn"), BigInt::from(50i64).into());
+    assert!(field(&quota, "rejection").is_null());
+
+    // The bucket where the config should be fails the discriminator check
+    assert!(simulate_consume(BUCKET, BUCKET, START).is_err());
+    assert!(decode_bucket(&BUCKET[..40]).is_err());
+}
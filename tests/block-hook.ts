@@ -0,0 +1,250 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { BlockHook } from "../target/types/block_hook";
+import { SolanaRateLimiter } from "../target/types/solana_rate_limiter";
+import {
+  AccountMeta,
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+} from "@solana/web3.js";
+import { assert } from "chai";
+
+// examples/block-hook: the limiter CPIs into it on block_client and, with
+// on_reject, on gate_check denials
+describe("block hook (CPI notifications)", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const hookProgram = anchor.workspace.BlockHook as Program<BlockHook>;
+  const limiter = anchor.workspace.SolanaRateLimiter as Program<SolanaRateLimiter>;
+  const admin = provider.wallet as anchor.Wallet;
+
+  const [globalConfigPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("global-config")],
+    limiter.programId
+  );
+  const [globalStatsPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("global-stats")],
+    limiter.programId
+  );
+  const [programDataPda] = PublicKey.findProgramAddressSync(
+    [limiter.programId.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+  const [hookAuthorityPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("hook-authority")],
+    limiter.programId
+  );
+  const [hookLogPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("hook-log")],
+    hookProgram.programId
+  );
+  const bucketFor = (wallet: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("client-bucket"), wallet.toBuffer()],
+      limiter.programId
+    )[0];
+
+  const hookAccounts: AccountMeta[] = [
+    { pubkey: hookProgram.programId, isSigner: false, isWritable: false },
+    { pubkey: hookAuthorityPda, isSigner: false, isWritable: false },
+    { pubkey: hookLogPda, isSigner: false, isWritable: true },
+  ];
+
+  async function newClient(): Promise<Keypair> {
+    const client = Keypair.generate();
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: client.publicKey,
+          lamports: 0.01 * LAMPORTS_PER_SOL,
+        })
+      )
+    );
+    await limiter.methods
+      .registerClient()
+      .accounts({ globalConfig: globalConfigPda, client: client.publicKey, payer: client.publicKey })
+      .signers([client])
+      .rpc();
+    return client;
+  }
+
+  async function setHook(program: PublicKey, onReject: boolean, propagate: boolean) {
+    await limiter.methods
+      .setHook(program, onReject, propagate)
+      .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+      .rpc();
+  }
+
+  function block(client: Keypair, remaining: AccountMeta[]) {
+    return limiter.methods
+      .blockClient()
+      .accounts({
+        globalConfig: globalConfigPda,
+        clientBucket: bucketFor(client.publicKey),
+        admin: admin.publicKey,
+        clientWallet: client.publicKey,
+      })
+      .remainingAccounts(remaining)
+      .rpc();
+  }
+
+  async function setRefuse(refuse: boolean) {
+    await hookProgram.methods.setRefuse(refuse).accounts({ hookLog: hookLogPda }).rpc();
+  }
+
+  async function expectFailure(call: Promise<unknown>, code: string) {
+    try {
+      await call;
+    } catch (e: any) {
+      const logs: string[] = e.logs ?? [];
+      assert.isTrue(
+        e.message.includes(code) || logs.some((line) => line.includes(code)),
+        `expected ${code}, got ${e.message}`
+      );
+      return;
+    }
+    assert.fail(`Expected ${code}`);
+  }
+
+  const events = async () =>
+    (await hookProgram.account.hookLog.fetch(hookLogPda)).events.toNumber();
+
+  before(async () => {
+    // Mocha may run this file before the main suite
+    if (!(await provider.connection.getAccountInfo(globalConfigPda))) {
+      await limiter.methods
+        .initialize({
+          maxRequests: new anchor.BN(5),
+          windowSeconds: new anchor.BN(60),
+          burstLimit: new anchor.BN(7),
+        })
+        .accounts({
+          globalConfig: globalConfigPda,
+          admin: admin.publicKey,
+          programData: programDataPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+    if (!(await provider.connection.getAccountInfo(globalStatsPda))) {
+      await limiter.methods
+        .initializeStats(new anchor.BN(5))
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey, payer: admin.publicKey })
+        .rpc();
+    }
+    if (!(await provider.connection.getAccountInfo(hookLogPda))) {
+      await hookProgram.methods.initializeLog().accounts({ payer: admin.publicKey }).rpc();
+    }
+    await setRefuse(false);
+  });
+
+  after(async () => {
+    await setHook(PublicKey.default, false, false);
+  });
+
+  it("block_client notifies the hook with the client and reason", async () => {
+    await setHook(hookProgram.programId, false, false);
+    const client = await newClient();
+    const before = await events();
+
+    await block(client, hookAccounts);
+
+    const log = await hookProgram.account.hookLog.fetch(hookLogPda);
+    assert.equal(log.events.toNumber(), before + 1);
+    assert.isTrue(log.lastClient.equals(client.publicKey));
+    assert.equal(log.lastReason, 4);
+  });
+
+  it("Re-blocking a blocked client does not notify again", async () => {
+    const client = await newClient();
+    await block(client, hookAccounts);
+    const before = await events();
+    await block(client, hookAccounts);
+    assert.equal(await events(), before);
+  });
+
+  it("Ignore policy: missing hook accounts skip the notification", async () => {
+    await setHook(hookProgram.programId, false, false);
+    const client = await newClient();
+    const before = await events();
+
+    await block(client, []);
+
+    assert.equal(await events(), before);
+    const bucket = await limiter.account.clientBucket.fetch(bucketFor(client.publicKey));
+    assert.equal(bucket.flags & 1, 1);
+  });
+
+  it("Propagate policy: missing hook accounts fail the block", async () => {
+    await setHook(hookProgram.programId, false, true);
+    const client = await newClient();
+
+    await expectFailure(block(client, []), "HookAccountsMissing");
+
+    const bucket = await limiter.account.clientBucket.fetch(bucketFor(client.publicKey));
+    assert.equal(bucket.flags & 1, 0);
+  });
+
+  it("A hook that fails fails the block under either policy", async () => {
+    await setRefuse(true);
+    try {
+      for (const propagate of [false, true]) {
+        await setHook(hookProgram.programId, false, propagate);
+        const client = await newClient();
+        await expectFailure(block(client, hookAccounts), "Refused");
+        const bucket = await limiter.account.clientBucket.fetch(bucketFor(client.publicKey));
+        assert.equal(bucket.flags & 1, 0);
+      }
+    } finally {
+      await setRefuse(false);
+    }
+  });
+
+  it("The hook refuses calls that do not come from the limiter", async () => {
+    const client = await newClient();
+    await expectFailure(
+      hookProgram.methods
+        .onRateLimitEvent(client.publicKey, 4)
+        .accounts({
+          hookAuthority: admin.publicKey,
+          bucket: bucketFor(client.publicKey),
+          hookLog: hookLogPda,
+        })
+        .rpc(),
+      "ConstraintSeeds"
+    );
+  });
+
+  it("With on_reject, gate_check denials notify the hook", async () => {
+    await setHook(hookProgram.programId, true, false);
+    const gc = await limiter.account.globalConfig.fetch(globalConfigPda);
+    const client = await newClient();
+    const gate = () =>
+      limiter.methods
+        .gateCheck()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          client: client.publicKey,
+        })
+        .remainingAccounts(hookAccounts)
+        .signers([client])
+        .rpc();
+
+    for (let i = 0; i < gc.maxRequests.toNumber(); i++) {
+      await gate();
+    }
+    const before = await events();
+    await gate();
+
+    const log = await hookProgram.account.hookLog.fetch(hookLogPda);
+    assert.equal(log.events.toNumber(), before + 1);
+    assert.isTrue(log.lastClient.equals(client.publicKey));
+    assert.equal(log.lastReason, 1);
+  });
+});
@@ -0,0 +1,292 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { GatedCounter } from "../target/types/gated_counter";
+import { ProgramCaller } from "../target/types/program_caller";
+import { SolanaRateLimiter } from "../target/types/solana_rate_limiter";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
+  Transaction,
+} from "@solana/web3.js";
+import { assert } from "chai";
+
+// Only programs with a caller-allowlist entry may CPI into consume_request.
+// program-caller's relay_consume forwards a user's consume with whatever entry
+// the test hands it.
+describe("caller allowlist", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const counterProgram = anchor.workspace.GatedCounter as Program<GatedCounter>;
+  const callerProgram = anchor.workspace.ProgramCaller as Program<ProgramCaller>;
+  const limiter = anchor.workspace.SolanaRateLimiter as Program<SolanaRateLimiter>;
+  const admin = provider.wallet as anchor.Wallet;
+
+  const [globalConfigPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("global-config")],
+    limiter.programId
+  );
+  const [globalStatsPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("global-stats")],
+    limiter.programId
+  );
+  const [programDataPda] = PublicKey.findProgramAddressSync(
+    [limiter.programId.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+
+  const pda = (seed: string, key: PublicKey, programId: PublicKey) =>
+    PublicKey.findProgramAddressSync([Buffer.from(seed), key.toBuffer()], programId)[0];
+  const callerEntryFor = (programId: PublicKey) =>
+    pda("caller-allowlist", programId, limiter.programId);
+  const bucketFor = (wallet: PublicKey) => pda("client-bucket", wallet, limiter.programId);
+  const vaultFor = (user: PublicKey) => pda("vault", user, callerProgram.programId);
+  const denylistFor = (programId: PublicKey) => pda("program-denylist", programId, limiter.programId);
+
+  async function newUser(): Promise<Keypair> {
+    const user = Keypair.generate();
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: user.publicKey,
+          lamports: 0.01 * LAMPORTS_PER_SOL,
+        })
+      )
+    );
+    await limiter.methods
+      .registerClient()
+      .accounts({ globalConfig: globalConfigPda, client: user.publicKey, payer: user.publicKey })
+      .signers([user])
+      .rpc();
+    return user;
+  }
+
+  function relay(user: Keypair, callerEntry: PublicKey | null) {
+    return callerProgram.methods
+      .relayConsume()
+      .accounts({
+        user: user.publicKey,
+        globalConfig: globalConfigPda,
+        globalStats: globalStatsPda,
+        clientBucket: bucketFor(user.publicKey),
+        callerEntry,
+        instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        rateLimiterProgram: limiter.programId,
+      })
+      .signers([user])
+      .rpc();
+  }
+
+  function relayVault(user: Keypair, ownerDenylist: PublicKey | null) {
+    return callerProgram.methods
+      .relayVaultConsume()
+      .accounts({
+        user: user.publicKey,
+        globalConfig: globalConfigPda,
+        globalStats: globalStatsPda,
+        clientBucket: bucketFor(vaultFor(user.publicKey)),
+        callerEntry: callerEntryFor(callerProgram.programId),
+        instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        ownerDenylist,
+        rateLimiterProgram: limiter.programId,
+      })
+      .signers([user])
+      .rpc();
+  }
+
+  async function allow(programId: PublicKey) {
+    await limiter.methods
+      .allowCaller()
+      .accounts({ globalConfig: globalConfigPda, callerProgram: programId, admin: admin.publicKey, payer: admin.publicKey })
+      .rpc();
+  }
+
+  async function revoke(programId: PublicKey) {
+    await limiter.methods
+      .revokeCaller()
+      .accounts({
+        globalConfig: globalConfigPda,
+        callerEntry: callerEntryFor(programId),
+        admin: admin.publicKey,
+      })
+      .rpc();
+  }
+
+  async function expectFailure(call: Promise<unknown>, code: string) {
+    try {
+      await call;
+    } catch (e: any) {
+      const logs: string[] = e.logs ?? [];
+      assert.isTrue(
+        e.message.includes(code) || logs.some((line) => line.includes(code)),
+        `expected ${code}, got ${e.message}`
+      );
+      return;
+    }
+    assert.fail(`Expected ${code}`);
+  }
+
+  async function requestCount(user: Keypair): Promise<number> {
+    const bucket = await limiter.account.clientBucket.fetch(bucketFor(user.publicKey));
+    return bucket.requestCount.toNumber();
+  }
+
+  before(async () => {
+    // Mocha may run this file before the main suite
+    if (!(await provider.connection.getAccountInfo(globalConfigPda))) {
+      await limiter.methods
+        .initialize({
+          maxRequests: new anchor.BN(5),
+          windowSeconds: new anchor.BN(60),
+          burstLimit: new anchor.BN(7),
+        })
+        .accounts({
+          globalConfig: globalConfigPda,
+          admin: admin.publicKey,
+          programData: programDataPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+    if (!(await provider.connection.getAccountInfo(globalStatsPda))) {
+      await limiter.methods
+        .initializeStats(new anchor.BN(5))
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey, payer: admin.publicKey })
+        .rpc();
+    }
+    if (await provider.connection.getAccountInfo(callerEntryFor(callerProgram.programId))) {
+      await revoke(callerProgram.programId);
+    }
+  });
+
+  it("Rejects a CPI from a program with no entry", async () => {
+    const user = await newUser();
+    await expectFailure(relay(user, null), "CallerNotAllowed");
+    assert.equal(await requestCount(user), 0);
+  });
+
+  it("Rejects a CPI that borrows another program's entry", async () => {
+    if (!(await provider.connection.getAccountInfo(callerEntryFor(counterProgram.programId)))) {
+      await allow(counterProgram.programId);
+    }
+    const user = await newUser();
+    await expectFailure(relay(user, callerEntryFor(counterProgram.programId)), "CallerNotAllowed");
+  });
+
+  it("Accepts a CPI from an approved program", async () => {
+    await allow(callerProgram.programId);
+    const user = await newUser();
+    await relay(user, callerEntryFor(callerProgram.programId));
+    assert.equal(await requestCount(user), 1);
+
+    const entry = await limiter.account.callerAllowlist.fetch(callerEntryFor(callerProgram.programId));
+    assert.isTrue(entry.programId.equals(callerProgram.programId));
+  });
+
+  it("Reports the consumed window back to the calling program", async () => {
+    const user = await newUser();
+    await relay(user, callerEntryFor(callerProgram.programId));
+    // The caller program fails the transaction if the report disagrees with the bucket
+    await callerProgram.methods
+      .relayConsumeAndReport()
+      .accounts({
+        user: user.publicKey,
+        globalConfig: globalConfigPda,
+        globalStats: globalStatsPda,
+        clientBucket: bucketFor(user.publicKey),
+        callerEntry: callerEntryFor(callerProgram.programId),
+        instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        rateLimiterProgram: limiter.programId,
+      })
+      .signers([user])
+      .rpc();
+    assert.equal(await requestCount(user), 2);
+  });
+
+  it("Revocation takes effect on the next CPI", async () => {
+    const user = await newUser();
+    await relay(user, callerEntryFor(callerProgram.programId));
+    await revoke(callerProgram.programId);
+
+    assert.isNull(await provider.connection.getAccountInfo(callerEntryFor(callerProgram.programId)));
+    await expectFailure(relay(user, null), "CallerNotAllowed");
+    assert.equal(await requestCount(user), 1);
+  });
+
+  it("Top-level calls need no entry", async () => {
+    const user = await newUser();
+    await limiter.methods
+      .consumeRequest()
+      .accounts({ globalConfig: globalConfigPda, clientBucket: bucketFor(user.publicKey), client: user.publicKey })
+      .signers([user])
+      .rpc();
+    assert.equal(await requestCount(user), 1);
+  });
+
+  it("Only the admin can allow a caller", async () => {
+    const intruder = await newUser();
+    await expectFailure(
+      limiter.methods
+        .allowCaller()
+        .accounts({
+          globalConfig: globalConfigPda,
+          callerProgram: callerProgram.programId,
+          admin: intruder.publicKey,
+          payer: intruder.publicKey,
+        })
+        .signers([intruder])
+        .rpc(),
+      "Unauthorized"
+    );
+  });
+
+  it("Denylisting a program stops its PDAs but not its users", async () => {
+    if (!(await provider.connection.getAccountInfo(callerEntryFor(callerProgram.programId)))) {
+      await allow(callerProgram.programId);
+    }
+    const user = await newUser();
+    const vault = vaultFor(user.publicKey);
+    await callerProgram.methods
+      .registerVault()
+      .accounts({
+        user: user.publicKey,
+        globalConfig: globalConfigPda,
+        globalStats: globalStatsPda,
+        clientBucket: bucketFor(vault),
+        fastBucket: pda("fast-bucket", vault, limiter.programId),
+        shardParent: pda("shard-parent", vault, limiter.programId),
+        payerMeta: pda("payer-meta", user.publicKey, limiter.programId),
+        rateLimiterProgram: limiter.programId,
+      })
+      .signers([user])
+      .rpc();
+    // Nothing is denylisted yet, so the vault needs no denylist account
+    await relayVault(user, null);
+
+    await limiter.methods
+      .blockProgramOwners(callerProgram.programId)
+      .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey, payer: admin.publicKey })
+      .rpc();
+    await expectFailure(relayVault(user, null), "OwnerDenylistMissing");
+    await expectFailure(relayVault(user, denylistFor(callerProgram.programId)), "ProgramOwnersBlocked");
+    // The user's own bucket, signed for by the user, still consumes through the program
+    await relay(user, callerEntryFor(callerProgram.programId));
+    assert.equal(await requestCount(user), 1);
+
+    await limiter.methods
+      .unblockProgramOwners()
+      .accounts({
+        globalConfig: globalConfigPda,
+        denylistEntry: denylistFor(callerProgram.programId),
+        admin: admin.publicKey,
+      })
+      .rpc();
+    await relayVault(user, null);
+    const bucket = await limiter.account.clientBucket.fetch(bucketFor(vault));
+    assert.equal(bucket.requestCount.toNumber(), 2);
+  });
+});
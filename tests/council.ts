@@ -0,0 +1,303 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { SolanaRateLimiter } from "../target/types/solana_rate_limiter";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+} from "@solana/web3.js";
+import { assert } from "chai";
+
+// 2-of-3 council: with members seated, config updates, pauses, blocks,
+// unblocks and seat changes only run as proposals that enough members approved
+describe("council approvals", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const limiter = anchor.workspace.SolanaRateLimiter as Program<SolanaRateLimiter>;
+  const admin = provider.wallet as anchor.Wallet;
+
+  const [globalConfigPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("global-config")],
+    limiter.programId
+  );
+  const [globalStatsPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("global-stats")],
+    limiter.programId
+  );
+  const [programDataPda] = PublicKey.findProgramAddressSync(
+    [limiter.programId.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+  const bucketFor = (wallet: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("client-bucket"), wallet.toBuffer()],
+      limiter.programId
+    )[0];
+  const proposalFor = (id: anchor.BN) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("proposal"), id.toArrayLike(Buffer, "le", 8)],
+      limiter.programId
+    )[0];
+
+  const isPaused = (gc: { flags: number }) => (gc.flags & (1 << 0)) !== 0;
+  const isBlocked = (bucket: { flags: number }) => (bucket.flags & (1 << 0)) !== 0;
+
+  let members: Keypair[] = [];
+  let originalConfig: { maxRequests: anchor.BN; windowSeconds: anchor.BN; burstLimit: anchor.BN };
+
+  async function fundedWallet(): Promise<Keypair> {
+    const wallet = Keypair.generate();
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: wallet.publicKey,
+          lamports: 0.01 * LAMPORTS_PER_SOL,
+        })
+      )
+    );
+    return wallet;
+  }
+
+  async function newClient(): Promise<Keypair> {
+    const client = await fundedWallet();
+    await limiter.methods
+      .registerClient()
+      .accounts({ globalConfig: globalConfigPda, client: client.publicKey, payer: client.publicKey })
+      .signers([client])
+      .rpc();
+    return client;
+  }
+
+  // Opens a proposal and returns its address
+  async function propose(proposer: Keypair, action: any): Promise<PublicKey> {
+    const { proposalCount } = await limiter.account.globalConfig.fetch(globalConfigPda);
+    const proposal = proposalFor(proposalCount);
+    await limiter.methods
+      .proposeAction(action)
+      .accounts({ globalConfig: globalConfigPda, proposal, proposer: proposer.publicKey })
+      .signers([proposer])
+      .rpc();
+    return proposal;
+  }
+
+  function approve(proposal: PublicKey, member: Keypair) {
+    return limiter.methods
+      .approveAction()
+      .accounts({ globalConfig: globalConfigPda, proposal, member: member.publicKey })
+      .signers([member])
+      .rpc();
+  }
+
+  function execute(proposal: PublicKey, clientBucket: PublicKey | null = null) {
+    return limiter.methods
+      .executeAction()
+      .accounts({ globalConfig: globalConfigPda, globalStats: globalStatsPda, proposal, clientBucket })
+      .rpc();
+  }
+
+  // Opens `action` as the first approver's proposal, collects the rest and runs it
+  async function passed(action: any, approvers: Keypair[]) {
+    const proposal = await propose(approvers[0], action);
+    for (const member of approvers.slice(1)) {
+      await approve(proposal, member);
+    }
+    await execute(proposal);
+  }
+
+  async function expectFailure(call: Promise<unknown>, code: string) {
+    try {
+      await call;
+    } catch (e: any) {
+      const logs: string[] = e.logs ?? [];
+      assert.isTrue(
+        e.message.includes(code) || logs.some((line) => line.includes(code)),
+        `expected ${code}, got ${e.message}`
+      );
+      return;
+    }
+    assert.fail(`Expected ${code}`);
+  }
+
+  before(async () => {
+    // Mocha may run this file before the main suite
+    if (!(await provider.connection.getAccountInfo(globalConfigPda))) {
+      await limiter.methods
+        .initialize({
+          maxRequests: new anchor.BN(5),
+          windowSeconds: new anchor.BN(60),
+          burstLimit: new anchor.BN(7),
+        })
+        .accounts({
+          globalConfig: globalConfigPda,
+          admin: admin.publicKey,
+          programData: programDataPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+    if (!(await provider.connection.getAccountInfo(globalStatsPda))) {
+      await limiter.methods
+        .initializeStats(new anchor.BN(5))
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey, payer: admin.publicKey })
+        .rpc();
+    }
+
+    const gc = await limiter.account.globalConfig.fetch(globalConfigPda);
+    originalConfig = {
+      maxRequests: gc.maxRequests,
+      windowSeconds: gc.windowSeconds,
+      burstLimit: gc.burstLimit,
+    };
+
+    // The admin seats the first member; the council seats the rest itself
+    members = [await fundedWallet(), await fundedWallet(), await fundedWallet()];
+    await limiter.methods
+      .addCouncilMember(members[0].publicKey, 1)
+      .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+      .rpc();
+    await passed({ addCouncilMember: { member: members[1].publicKey, threshold: 2 } }, [members[0]]);
+    await passed({ addCouncilMember: { member: members[2].publicKey, threshold: 2 } }, members.slice(0, 2));
+  });
+
+  after(async () => {
+    // Dissolve the council so the other suites keep the single-admin path
+    await passed({ removeCouncilMember: { member: members[2].publicKey, threshold: 2 } }, members.slice(0, 2));
+    await passed({ removeCouncilMember: { member: members[1].publicKey, threshold: 1 } }, members.slice(0, 2));
+    await passed({ removeCouncilMember: { member: members[0].publicKey, threshold: 0 } }, [members[0]]);
+    await limiter.methods
+      .updateConfig(originalConfig, false)
+      .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+      .rpc();
+  });
+
+  it("The admin cannot act alone while a council is seated", async () => {
+    await expectFailure(
+      limiter.methods
+        .togglePause()
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc(),
+      "CouncilApprovalRequired"
+    );
+  });
+
+  it("Every config setter refuses the admin while a council is seated", async () => {
+    const key = Keypair.generate().publicKey;
+    const m = limiter.methods;
+    const setters: [string, any][] = [
+      ["setCallerMode", m.setCallerMode(true, false)],
+      ["setEventTagPolicy", m.setEventTagPolicy(true)],
+      ["transferAdmin", m.transferAdmin(key)],
+      ["setHook", m.setHook(key, false, false)],
+      ["setLimitFeeder", m.setLimitFeeder(key, new anchor.BN(1), new anchor.BN(10))],
+      ["setRefundAuthority", m.setRefundAuthority(key, new anchor.BN(1), false)],
+      ["setAuthority", m.setAuthority(0, key, new anchor.BN(0))],
+      ["cancelAuthorityRotation", m.cancelAuthorityRotation(0)],
+      ["setRegistrationPolicy", m.setRegistrationPolicy(new anchor.BN(0), new anchor.BN(0), key)],
+      ["setMaxBucketsPerPayer", m.setMaxBucketsPerPayer(1)],
+      ["setAppealPolicy", m.setAppealPolicy(new anchor.BN(0), new anchor.BN(0))],
+      ["setGcPolicy", m.setGcPolicy(new anchor.BN(0), 0)],
+      ["setViolationHalflife", m.setViolationHalflife(0)],
+      ["setInactivityForgiveness", m.setInactivityForgiveness(new anchor.BN(0))],
+      ["setMinClientBalance", m.setMinClientBalance(new anchor.BN(1))],
+      ["setRecoveryInactivity", m.setRecoveryInactivity(new anchor.BN(0))],
+      ["setProrationMode", m.setProrationMode(0)],
+    ];
+    for (const [name, builder] of setters) {
+      await expectFailure(
+        builder.accounts({ globalConfig: globalConfigPda, admin: admin.publicKey }).rpc(),
+        "CouncilApprovalRequired"
+      ).catch((e) => assert.fail(`${name}: ${e.message}`));
+    }
+    await expectFailure(
+      m
+        .setCircuitBreaker(new anchor.BN(1), new anchor.BN(1))
+        .accounts({ globalConfig: globalConfigPda, globalStats: globalStatsPda, admin: admin.publicKey })
+        .rpc(),
+      "CouncilApprovalRequired"
+    );
+  });
+
+  it("Membership changes need the council too", async () => {
+    await expectFailure(
+      limiter.methods
+        .addCouncilMember(Keypair.generate().publicKey, 2)
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc(),
+      "CouncilApprovalRequired"
+    );
+
+    // Unseating a member takes the threshold like any other action
+    const proposal = await propose(members[0], {
+      removeCouncilMember: { member: members[1].publicKey, threshold: 1 },
+    });
+    await expectFailure(execute(proposal), "InsufficientApprovals");
+    const gc = await limiter.account.globalConfig.fetch(globalConfigPda);
+    assert.equal(gc.councilSize, 3);
+  });
+
+  it("Updates the config once the threshold is reached, and only once", async () => {
+    const proposal = await propose(members[0], {
+      updateConfig: {
+        maxRequests: new anchor.BN(8),
+        windowSeconds: new anchor.BN(60),
+        burstLimit: new anchor.BN(10),
+        resetWindows: false,
+      },
+    });
+
+    // The proposer's approval alone is 1 of 2
+    await expectFailure(execute(proposal), "InsufficientApprovals");
+    await expectFailure(approve(proposal, members[0]), "AlreadyApproved");
+
+    await approve(proposal, members[1]);
+    await execute(proposal);
+    const gc = await limiter.account.globalConfig.fetch(globalConfigPda);
+    assert.equal(gc.maxRequests.toNumber(), 8);
+    assert.equal(gc.burstLimit.toNumber(), 10);
+
+    await expectFailure(execute(proposal), "ProposalAlreadyExecuted");
+    await expectFailure(approve(proposal, members[2]), "ProposalAlreadyExecuted");
+  });
+
+  it("Pauses and unpauses through proposals", async () => {
+    const pause = await propose(members[1], { pause: { paused: true } });
+    await approve(pause, members[2]);
+    await execute(pause);
+    assert.isTrue(isPaused(await limiter.account.globalConfig.fetch(globalConfigPda)));
+
+    const unpause = await propose(members[2], { pause: { paused: false } });
+    await approve(unpause, members[0]);
+    await execute(unpause);
+    assert.isFalse(isPaused(await limiter.account.globalConfig.fetch(globalConfigPda)));
+  });
+
+  it("Blocks and unblocks a client through proposals", async () => {
+    const client = await newClient();
+    const bucket = bucketFor(client.publicKey);
+
+    const block = await propose(members[0], { block: { client: client.publicKey } });
+    await approve(block, members[2]);
+    // The bucket has to be the proposal's client's
+    const other = await newClient();
+    await expectFailure(execute(block, bucketFor(other.publicKey)), "OwnerMismatch");
+    await execute(block, bucket);
+    assert.isTrue(isBlocked(await limiter.account.clientBucket.fetch(bucket)));
+
+    const unblock = await propose(members[1], { unblock: { client: client.publicKey } });
+    await approve(unblock, members[0]);
+    await execute(unblock, bucket);
+    assert.isFalse(isBlocked(await limiter.account.clientBucket.fetch(bucket)));
+  });
+
+  it("Only members can propose or approve", async () => {
+    const outsider = await fundedWallet();
+    await expectFailure(propose(outsider, { pause: { paused: true } }), "NotCouncilMember");
+
+    const proposal = await propose(members[0], { pause: { paused: true } });
+    await expectFailure(approve(proposal, outsider), "NotCouncilMember");
+  });
+});
@@ -0,0 +1,276 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { GatedCounter } from "../target/types/gated_counter";
+import { SolanaRateLimiter } from "../target/types/solana_rate_limiter";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
+  Transaction,
+} from "@solana/web3.js";
+import { assert } from "chai";
+
+// examples/gated-counter: a second program that CPIs into consume_request before
+// doing its own work
+describe("gated-counter (CPI consumer)", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const counterProgram = anchor.workspace.GatedCounter as Program<GatedCounter>;
+  const limiter = anchor.workspace.SolanaRateLimiter as Program<SolanaRateLimiter>;
+  const admin = provider.wallet as anchor.Wallet;
+
+  const [globalConfigPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("global-config")],
+    limiter.programId
+  );
+  const [globalStatsPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("global-stats")],
+    limiter.programId
+  );
+  const [programDataPda] = PublicKey.findProgramAddressSync(
+    [limiter.programId.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+
+  const pda = (seed: string, wallet: PublicKey, programId: PublicKey) =>
+    PublicKey.findProgramAddressSync([Buffer.from(seed), wallet.toBuffer()], programId)[0];
+  const callerEntryFor = (programId: PublicKey) =>
+    pda("caller-allowlist", programId, limiter.programId);
+
+  let user: Keypair;
+  let maxRequests: number;
+
+  async function increment(method: "increment" | "tryIncrement" = "increment"): Promise<string> {
+    return counterProgram.methods[method]()
+      .accounts({
+        counter: pda("counter", user.publicKey, counterProgram.programId),
+        user: user.publicKey,
+        globalConfig: globalConfigPda,
+        globalStats: globalStatsPda,
+        clientBucket: pda("client-bucket", user.publicKey, limiter.programId),
+        callerEntry: callerEntryFor(counterProgram.programId),
+        instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        rateLimiterProgram: limiter.programId,
+      })
+      .signers([user])
+      .rpc();
+  }
+
+  before(async () => {
+    // Mocha may run this file before the main suite: set up the limiter the same
+    // way it does if nothing is deployed yet
+    if (!(await provider.connection.getAccountInfo(globalConfigPda))) {
+      await limiter.methods
+        .initialize({
+          maxRequests: new anchor.BN(5),
+          windowSeconds: new anchor.BN(60),
+          burstLimit: new anchor.BN(7),
+        })
+        .accounts({
+          globalConfig: globalConfigPda,
+          admin: admin.publicKey,
+          programData: programDataPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+    if (!(await provider.connection.getAccountInfo(globalStatsPda))) {
+      await limiter.methods
+        .initializeStats(new anchor.BN(5))
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey, payer: admin.publicKey })
+        .rpc();
+    }
+
+    // gated-counter CPIs into consume_request, so it has to be an approved caller
+    if (!(await provider.connection.getAccountInfo(callerEntryFor(counterProgram.programId)))) {
+      await limiter.methods
+        .allowCaller()
+        .accounts({
+          globalConfig: globalConfigPda,
+          callerProgram: counterProgram.programId,
+          admin: admin.publicKey,
+          payer: admin.publicKey,
+        })
+        .rpc();
+    }
+
+    user = Keypair.generate();
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: user.publicKey,
+          lamports: 0.01 * LAMPORTS_PER_SOL,
+        })
+      )
+    );
+    await limiter.methods
+      .registerClient()
+      .accounts({ globalConfig: globalConfigPda, client: user.publicKey, payer: user.publicKey })
+      .signers([user])
+      .rpc();
+    await counterProgram.methods
+      .initializeCounter()
+      .accounts({ user: user.publicKey })
+      .signers([user])
+      .rpc();
+
+    const gc = await limiter.account.globalConfig.fetch(globalConfigPda);
+    maxRequests = gc.maxRequests.toNumber();
+  });
+
+  it("Each increment consumes from the caller's bucket", async () => {
+    await increment();
+    const counter = await counterProgram.account.counter.fetch(
+      pda("counter", user.publicKey, counterProgram.programId)
+    );
+    assert.equal(counter.count.toNumber(), 1);
+    const bucket = await limiter.account.clientBucket.fetch(
+      pda("client-bucket", user.publicKey, limiter.programId)
+    );
+    assert.equal(bucket.requestCount.toNumber(), 1);
+  });
+
+  it("A rate-limit rejection in the CPI fails the caller's transaction", async () => {
+    for (let i = 1; i < maxRequests; i++) {
+      await increment();
+    }
+    try {
+      await increment();
+      assert.fail("Should have been rate limited");
+    } catch (e: any) {
+      const logs: string[] = e.logs ?? [];
+      assert.isTrue(
+        logs.some((line) => line.includes("RateLimitExceeded")),
+        "limiter error should surface in the caller's logs"
+      );
+    }
+
+    // Nothing the counter did survives the failed transaction
+    const counter = await counterProgram.account.counter.fetch(
+      pda("counter", user.publicKey, counterProgram.programId)
+    );
+    assert.equal(counter.count.toNumber(), maxRequests);
+  });
+
+  it("try_increment records a denial instead of failing when over the limit", async () => {
+    const bucketPda = pda("client-bucket", user.publicKey, limiter.programId);
+    const before = await limiter.account.clientBucket.fetch(bucketPda);
+
+    // The bucket is exhausted by the previous test, yet the transaction lands
+    await increment("tryIncrement");
+
+    const counter = await counterProgram.account.counter.fetch(
+      pda("counter", user.publicKey, counterProgram.programId)
+    );
+    assert.equal(counter.count.toNumber(), maxRequests);
+    assert.equal(counter.denied.toNumber(), 1);
+
+    // The violation is kept because nothing rolled back
+    const after = await limiter.account.clientBucket.fetch(bucketPda);
+    assert.equal(after.violations.toNumber(), before.violations.toNumber() + 1);
+    assert.equal(after.requestCount.toNumber(), before.requestCount.toNumber());
+  });
+
+  it("try_increment counts when the gate allows it", async () => {
+    // A fresh user has its whole window available
+    const other = Keypair.generate();
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: other.publicKey,
+          lamports: 0.01 * LAMPORTS_PER_SOL,
+        })
+      )
+    );
+    await limiter.methods
+      .registerClient()
+      .accounts({ globalConfig: globalConfigPda, client: other.publicKey, payer: other.publicKey })
+      .signers([other])
+      .rpc();
+    await counterProgram.methods
+      .initializeCounter()
+      .accounts({ user: other.publicKey })
+      .signers([other])
+      .rpc();
+    await counterProgram.methods
+      .tryIncrement()
+      .accounts({
+        counter: pda("counter", other.publicKey, counterProgram.programId),
+        user: other.publicKey,
+        globalConfig: globalConfigPda,
+        globalStats: globalStatsPda,
+        clientBucket: pda("client-bucket", other.publicKey, limiter.programId),
+        callerEntry: callerEntryFor(counterProgram.programId),
+        instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        rateLimiterProgram: limiter.programId,
+      })
+      .signers([other])
+      .rpc();
+
+    const counter = await counterProgram.account.counter.fetch(
+      pda("counter", other.publicKey, counterProgram.programId)
+    );
+    assert.equal(counter.count.toNumber(), 1);
+    assert.equal(counter.denied.toNumber(), 0);
+    const bucket = await limiter.account.clientBucket.fetch(
+      pda("client-bucket", other.publicKey, limiter.programId)
+    );
+    assert.equal(bucket.requestCount.toNumber(), 1);
+  });
+
+  it("verify_consumed_in_tx cannot see a consume made by CPI", async () => {
+    // Documented limitation: the instructions sysvar lists top-level instructions
+    // only, so gated-counter's CPI consume does not satisfy the check
+    const other = Keypair.generate();
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: other.publicKey,
+          lamports: 0.01 * LAMPORTS_PER_SOL,
+        })
+      )
+    );
+    await limiter.methods
+      .registerClient()
+      .accounts({ globalConfig: globalConfigPda, client: other.publicKey, payer: other.publicKey })
+      .signers([other])
+      .rpc();
+    await counterProgram.methods
+      .initializeCounter()
+      .accounts({ user: other.publicKey })
+      .signers([other])
+      .rpc();
+
+    const incrementIx = await counterProgram.methods
+      .increment()
+      .accounts({
+        counter: pda("counter", other.publicKey, counterProgram.programId),
+        user: other.publicKey,
+        globalConfig: globalConfigPda,
+        globalStats: globalStatsPda,
+        clientBucket: pda("client-bucket", other.publicKey, limiter.programId),
+        callerEntry: callerEntryFor(counterProgram.programId),
+        instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        rateLimiterProgram: limiter.programId,
+      })
+      .instruction();
+    const verifyIx = await limiter.methods
+      .verifyConsumedInTx(other.publicKey)
+      .accounts({ instructions: SYSVAR_INSTRUCTIONS_PUBKEY })
+      .instruction();
+
+    try {
+      await provider.sendAndConfirm(new Transaction().add(incrementIx, verifyIx), [other]);
+      assert.fail("The CPI consume should not have been visible");
+    } catch (e: any) {
+      const logs: string[] = e.logs ?? [];
+      assert.isTrue(logs.some((line) => line.includes("ConsumeNotFound")));
+    }
+  });
+});
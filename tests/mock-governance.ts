@@ -0,0 +1,195 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { MockGovernance } from "../target/types/mock_governance";
+import { SolanaRateLimiter } from "../target/types/solana_rate_limiter";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+} from "@solana/web3.js";
+import { assert } from "chai";
+
+// examples/mock-governance: the limiter admin is a PDA, and every admin
+// instruction reaches the limiter through invoke_signed
+describe("PDA admin (mock governance)", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const governanceProgram = anchor.workspace.MockGovernance as Program<MockGovernance>;
+  const limiter = anchor.workspace.SolanaRateLimiter as Program<SolanaRateLimiter>;
+  const admin = provider.wallet as anchor.Wallet;
+
+  const [globalConfigPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("global-config")],
+    limiter.programId
+  );
+  const [globalStatsPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("global-stats")],
+    limiter.programId
+  );
+  const [programDataPda] = PublicKey.findProgramAddressSync(
+    [limiter.programId.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+  const [governancePda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("governance")],
+    governanceProgram.programId
+  );
+  const bucketFor = (wallet: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("client-bucket"), wallet.toBuffer()],
+      limiter.programId
+    )[0];
+
+  const isPaused = (gc: { flags: number }) => (gc.flags & (1 << 0)) !== 0;
+  const isBlocked = (bucket: { flags: number }) => (bucket.flags & (1 << 0)) !== 0;
+
+  const executeAccounts = {
+    globalConfig: globalConfigPda,
+    governance: governancePda,
+    rateLimiterProgram: limiter.programId,
+  };
+
+  let originalConfig: { maxRequests: anchor.BN; windowSeconds: anchor.BN; burstLimit: anchor.BN };
+
+  async function newClient(): Promise<Keypair> {
+    const client = Keypair.generate();
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: client.publicKey,
+          lamports: 0.01 * LAMPORTS_PER_SOL,
+        })
+      )
+    );
+    await limiter.methods
+      .registerClient()
+      .accounts({ globalConfig: globalConfigPda, client: client.publicKey, payer: client.publicKey })
+      .signers([client])
+      .rpc();
+    return client;
+  }
+
+  function executeTransferAdmin(newAdmin: PublicKey) {
+    return governanceProgram.methods.executeTransferAdmin(newAdmin).accounts(executeAccounts).rpc();
+  }
+
+  async function expectFailure(call: Promise<unknown>, code: string) {
+    try {
+      await call;
+    } catch (e: any) {
+      const logs: string[] = e.logs ?? [];
+      assert.isTrue(
+        e.message.includes(code) || logs.some((line) => line.includes(code)),
+        `expected ${code}, got ${e.message}`
+      );
+      return;
+    }
+    assert.fail(`Expected ${code}`);
+  }
+
+  before(async () => {
+    // Mocha may run this file before the main suite
+    if (!(await provider.connection.getAccountInfo(globalConfigPda))) {
+      await limiter.methods
+        .initialize({
+          maxRequests: new anchor.BN(5),
+          windowSeconds: new anchor.BN(60),
+          burstLimit: new anchor.BN(7),
+        })
+        .accounts({
+          globalConfig: globalConfigPda,
+          admin: admin.publicKey,
+          programData: programDataPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+    if (!(await provider.connection.getAccountInfo(globalStatsPda))) {
+      await limiter.methods
+        .initializeStats(new anchor.BN(5))
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey, payer: admin.publicKey })
+        .rpc();
+    }
+
+    const gc = await limiter.account.globalConfig.fetch(globalConfigPda);
+    originalConfig = {
+      maxRequests: gc.maxRequests,
+      windowSeconds: gc.windowSeconds,
+      burstLimit: gc.burstLimit,
+    };
+    await limiter.methods
+      .transferAdmin(governancePda)
+      .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+      .rpc();
+  });
+
+  after(async () => {
+    await governanceProgram.methods
+      .executeUpdateConfig(originalConfig, false)
+      .accounts(executeAccounts)
+      .rpc();
+    await executeTransferAdmin(admin.publicKey);
+  });
+
+  it("The PDA holds the admin role and the old keypair is locked out", async () => {
+    const gc = await limiter.account.globalConfig.fetch(globalConfigPda);
+    assert.isTrue(gc.admin.equals(governancePda));
+
+    await expectFailure(
+      limiter.methods
+        .togglePause()
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc(),
+      "Unauthorized"
+    );
+  });
+
+  it("update_config signed by the PDA", async () => {
+    await governanceProgram.methods
+      .executeUpdateConfig(
+        { maxRequests: new anchor.BN(9), windowSeconds: new anchor.BN(90), burstLimit: new anchor.BN(12) },
+        false
+      )
+      .accounts(executeAccounts)
+      .rpc();
+
+    const gc = await limiter.account.globalConfig.fetch(globalConfigPda);
+    assert.equal(gc.maxRequests.toNumber(), 9);
+    assert.equal(gc.windowSeconds.toNumber(), 90);
+    assert.equal(gc.burstLimit.toNumber(), 12);
+  });
+
+  it("toggle_pause signed by the PDA", async () => {
+    await governanceProgram.methods.executeTogglePause().accounts(executeAccounts).rpc();
+    assert.isTrue(isPaused(await limiter.account.globalConfig.fetch(globalConfigPda)));
+
+    await governanceProgram.methods.executeTogglePause().accounts(executeAccounts).rpc();
+    assert.isFalse(isPaused(await limiter.account.globalConfig.fetch(globalConfigPda)));
+  });
+
+  it("block_client signed by the PDA", async () => {
+    const client = await newClient();
+    await governanceProgram.methods
+      .executeBlockClient()
+      .accounts({
+        globalConfig: globalConfigPda,
+        globalStats: globalStatsPda,
+        clientBucket: bucketFor(client.publicKey),
+        governance: governancePda,
+        clientWallet: client.publicKey,
+        rateLimiterProgram: limiter.programId,
+      })
+      .rpc();
+
+    const bucket = await limiter.account.clientBucket.fetch(bucketFor(client.publicKey));
+    assert.isTrue(isBlocked(bucket));
+  });
+
+  it("transfer_admin refuses the default key", async () => {
+    await expectFailure(executeTransferAdmin(PublicKey.default), "InvalidConfig");
+  });
+});
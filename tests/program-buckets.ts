@@ -0,0 +1,296 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { GatedCounter } from "../target/types/gated_counter";
+import { ProgramCaller } from "../target/types/program_caller";
+import { SolanaRateLimiter } from "../target/types/solana_rate_limiter";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
+  Transaction,
+} from "@solana/web3.js";
+import { assert } from "chai";
+
+// Program-keyed limits: gated-counter and program-caller each get one allowance
+// shared by all of their users, consumed through consume_as_program
+describe("program buckets (CPI caller identity)", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const counterProgram = anchor.workspace.GatedCounter as Program<GatedCounter>;
+  const callerProgram = anchor.workspace.ProgramCaller as Program<ProgramCaller>;
+  const limiter = anchor.workspace.SolanaRateLimiter as Program<SolanaRateLimiter>;
+  const admin = provider.wallet as anchor.Wallet;
+
+  const PROGRAM_LIMIT = 3;
+
+  const [globalConfigPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("global-config")],
+    limiter.programId
+  );
+  const [globalStatsPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("global-stats")],
+    limiter.programId
+  );
+  const [programDataPda] = PublicKey.findProgramAddressSync(
+    [limiter.programId.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+
+  const pda = (seed: string, key: PublicKey, programId: PublicKey) =>
+    PublicKey.findProgramAddressSync([Buffer.from(seed), key.toBuffer()], programId)[0];
+  const programBucketFor = (programId: PublicKey) =>
+    pda("program-bucket", programId, limiter.programId);
+  const callerEntryFor = (programId: PublicKey) =>
+    pda("caller-allowlist", programId, limiter.programId);
+
+  async function newUser(): Promise<Keypair> {
+    const user = Keypair.generate();
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: user.publicKey,
+          lamports: 0.01 * LAMPORTS_PER_SOL,
+        })
+      )
+    );
+    await limiter.methods
+      .registerClient()
+      .accounts({ globalConfig: globalConfigPda, client: user.publicKey, payer: user.publicKey })
+      .signers([user])
+      .rpc();
+    await counterProgram.methods
+      .initializeCounter()
+      .accounts({ user: user.publicKey })
+      .signers([user])
+      .rpc();
+    return user;
+  }
+
+  async function setCallerMode(programKeyed: boolean, walletFallback: boolean) {
+    await limiter.methods
+      .setCallerMode(programKeyed, walletFallback)
+      .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+      .rpc();
+  }
+
+  async function registerProgram(programId: PublicKey) {
+    if (await provider.connection.getAccountInfo(programBucketFor(programId))) {
+      return;
+    }
+    await limiter.methods
+      .registerProgram({
+        maxRequests: new anchor.BN(PROGRAM_LIMIT),
+        windowSeconds: new anchor.BN(3600),
+        burstLimit: new anchor.BN(PROGRAM_LIMIT),
+      })
+      .accounts({
+        globalConfig: globalConfigPda,
+        callerProgram: programId,
+        admin: admin.publicKey,
+        payer: admin.publicKey,
+      })
+      .rpc();
+  }
+
+  function incrementPooled(user: Keypair) {
+    return counterProgram.methods
+      .incrementPooled()
+      .accounts({
+        counter: pda("counter", user.publicKey, counterProgram.programId),
+        user: user.publicKey,
+        globalConfig: globalConfigPda,
+        globalStats: globalStatsPda,
+        programBucket: programBucketFor(counterProgram.programId),
+        rateLimiterProgram: limiter.programId,
+      })
+      .signers([user])
+      .rpc();
+  }
+
+  function ping() {
+    return callerProgram.methods
+      .ping()
+      .accounts({
+        globalConfig: globalConfigPda,
+        globalStats: globalStatsPda,
+        programBucket: programBucketFor(callerProgram.programId),
+        rateLimiterProgram: limiter.programId,
+      })
+      .rpc();
+  }
+
+  async function expectFailure(call: Promise<unknown>, code: string) {
+    try {
+      await call;
+    } catch (e: any) {
+      const logs: string[] = e.logs ?? [];
+      assert.isTrue(
+        e.message.includes(code) || logs.some((line) => line.includes(code)),
+        `expected ${code}, got ${e.message}`
+      );
+      return;
+    }
+    assert.fail(`Expected ${code}`);
+  }
+
+  before(async () => {
+    // Mocha may run this file before the main suite
+    if (!(await provider.connection.getAccountInfo(globalConfigPda))) {
+      await limiter.methods
+        .initialize({
+          maxRequests: new anchor.BN(5),
+          windowSeconds: new anchor.BN(60),
+          burstLimit: new anchor.BN(7),
+        })
+        .accounts({
+          globalConfig: globalConfigPda,
+          admin: admin.publicKey,
+          programData: programDataPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+    if (!(await provider.connection.getAccountInfo(globalStatsPda))) {
+      await limiter.methods
+        .initializeStats(new anchor.BN(5))
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey, payer: admin.publicKey })
+        .rpc();
+    }
+    await registerProgram(counterProgram.programId);
+    if (!(await provider.connection.getAccountInfo(callerEntryFor(counterProgram.programId)))) {
+      await limiter.methods
+        .allowCaller()
+        .accounts({
+          globalConfig: globalConfigPda,
+          callerProgram: counterProgram.programId,
+          admin: admin.publicKey,
+          payer: admin.publicKey,
+        })
+        .rpc();
+    }
+    await registerProgram(callerProgram.programId);
+    await setCallerMode(true, false);
+  });
+
+  after(async () => {
+    // The other suites consume from wallet buckets
+    await setCallerMode(false, false);
+  });
+
+  it("register_program refuses an account that is not a program", async () => {
+    await expectFailure(
+      limiter.methods
+        .registerProgram({
+          maxRequests: new anchor.BN(1),
+          windowSeconds: new anchor.BN(60),
+          burstLimit: new anchor.BN(1),
+        })
+        .accounts({
+          globalConfig: globalConfigPda,
+          callerProgram: Keypair.generate().publicKey,
+          admin: admin.publicKey,
+          payer: admin.publicKey,
+        })
+        .rpc(),
+      "InvalidCallerProgram"
+    );
+  });
+
+  it("Rejects direct wallet consumes unless fallback is on", async () => {
+    const user = await newUser();
+    const consume = () =>
+      limiter.methods
+        .consumeRequest()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: pda("client-bucket", user.publicKey, limiter.programId),
+          client: user.publicKey,
+        })
+        .signers([user])
+        .rpc();
+
+    await expectFailure(consume(), "CallerProgramRequired");
+
+    await setCallerMode(true, true);
+    try {
+      await consume();
+    } finally {
+      await setCallerMode(true, false);
+    }
+  });
+
+  it("Rejects wallet consumes made by CPI even with fallback on", async () => {
+    const user = await newUser();
+    await setCallerMode(true, true);
+    try {
+      await expectFailure(
+        counterProgram.methods
+          .increment()
+          .accounts({
+            counter: pda("counter", user.publicKey, counterProgram.programId),
+            user: user.publicKey,
+            globalConfig: globalConfigPda,
+            globalStats: globalStatsPda,
+            clientBucket: pda("client-bucket", user.publicKey, limiter.programId),
+            callerEntry: callerEntryFor(counterProgram.programId),
+            instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+            rateLimiterProgram: limiter.programId,
+          })
+          .signers([user])
+          .rpc(),
+        "CallerProgramRequired"
+      );
+    } finally {
+      await setCallerMode(true, false);
+    }
+  });
+
+  it("A caller program hits its own limit across users; another caller is unaffected", async () => {
+    const users = [await newUser(), await newUser()];
+    for (let i = 0; i < PROGRAM_LIMIT; i++) {
+      await incrementPooled(users[i % users.length]);
+    }
+    await expectFailure(incrementPooled(users[0]), "RateLimitExceeded");
+
+    const counted = await limiter.account.programBucket.fetch(
+      programBucketFor(counterProgram.programId)
+    );
+    assert.equal(counted.requestCount.toNumber(), PROGRAM_LIMIT);
+    assert.isTrue(counted.programId.equals(counterProgram.programId));
+
+    // The users' own buckets were never touched
+    for (const user of users) {
+      const bucket = await limiter.account.clientBucket.fetch(
+        pda("client-bucket", user.publicKey, limiter.programId)
+      );
+      assert.equal(bucket.requestCount.toNumber(), 0);
+    }
+
+    await ping();
+    const other = await limiter.account.programBucket.fetch(
+      programBucketFor(callerProgram.programId)
+    );
+    assert.equal(other.requestCount.toNumber(), 1);
+  });
+
+  it("Only the program's own caller authority can consume from its bucket", async () => {
+    // A wallet cannot stand in for the PDA signer
+    const impostor = Keypair.generate();
+    await expectFailure(
+      limiter.methods
+        .consumeAsProgram()
+        .accounts({
+          globalConfig: globalConfigPda,
+          programBucket: programBucketFor(callerProgram.programId),
+          callerAuthority: impostor.publicKey,
+        })
+        .signers([impostor])
+        .rpc(),
+      "Unauthorized"
+    );
+  });
+});
@@ -1,8 +1,16 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { SolanaRateLimiter } from "../target/types/solana_rate_limiter";
-import { PublicKey, SystemProgram } from "@solana/web3.js";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
+  Transaction,
+} from "@solana/web3.js";
 import { assert } from "chai";
+import * as fs from "fs";
 
 describe("solana-rate-limiter", () => {
   const provider = anchor.AnchorProvider.env();
@@ -11,22 +19,165 @@ describe("solana-rate-limiter", () => {
   const program = anchor.workspace.SolanaRateLimiter as Program<SolanaRateLimiter>;
   const admin = provider.wallet as anchor.Wallet;
 
+  // PDA seeds as the program declares them (the #[constant]s in constants.rs,
+  // carried in the IDL), so the addresses here cannot drift from its constraints
+  function seed(name: string): Buffer {
+    const normalize = (text: string) => text.replace(/_/g, "").toLowerCase();
+    const constant = (program.idl.constants ?? []).find((c) => normalize(c.name) === normalize(name));
+    assert.isDefined(constant, `no ${name} in the IDL`);
+    return Buffer.from(JSON.parse(constant!.value));
+  }
+
   const [globalConfigPda] = PublicKey.findProgramAddressSync(
-    [Buffer.from("global-config")],
+    [seed("GLOBAL_CONFIG_SEED")],
+    program.programId
+  );
+
+  const [globalStatsPda] = PublicKey.findProgramAddressSync(
+    [seed("GLOBAL_STATS_SEED")],
     program.programId
   );
 
   const [clientBucketPda] = PublicKey.findProgramAddressSync(
-    [Buffer.from("client-bucket"), admin.publicKey.toBuffer()],
+    [seed("CLIENT_BUCKET_SEED"), admin.publicKey.toBuffer()],
     program.programId
   );
 
+  const [programDataPda] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+
   const config = {
     maxRequests: new anchor.BN(5),
     windowSeconds: new anchor.BN(60),
     burstLimit: new anchor.BN(7),
   };
 
+  const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+  // Mirrors the *_FLAG_* constants in constants.rs
+  const isPaused = (gc: { flags: number }) => (gc.flags & (1 << 0)) !== 0;
+  const isBlocked = (bucket: { flags: number }) => (bucket.flags & (1 << 0)) !== 0;
+  const isTotalSaturated = (bucket: { flags: number }) => (bucket.flags & (1 << 1)) !== 0;
+
+  function bucketFor(wallet: PublicKey): PublicKey {
+    const [pda] = PublicKey.findProgramAddressSync(
+      [seed("CLIENT_BUCKET_SEED"), wallet.toBuffer()],
+      program.programId
+    );
+    return pda;
+  }
+
+  async function fundedWallet(): Promise<Keypair> {
+    const wallet = Keypair.generate();
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: admin.publicKey,
+          toPubkey: wallet.publicKey,
+          lamports: 0.01 * LAMPORTS_PER_SOL,
+        })
+      )
+    );
+    return wallet;
+  }
+
+  // Funds a fresh wallet from the admin and registers its bucket
+  async function newClient(): Promise<Keypair> {
+    const client = await fundedWallet();
+    await program.methods
+      .registerClient()
+      .accounts({
+        globalConfig: globalConfigPda,
+        clientBucket: bucketFor(client.publicKey),
+        client: client.publicKey,
+        payer: client.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([client])
+      .rpc({ commitment: "confirmed" });
+    return client;
+  }
+
+  async function consumeAs(client: Keypair): Promise<string> {
+    return program.methods
+      .consumeRequest()
+      .accounts({
+        globalConfig: globalConfigPda,
+        clientBucket: bucketFor(client.publicKey),
+        client: client.publicKey,
+      })
+      .signers([client])
+      .rpc({ commitment: "confirmed" });
+  }
+
+  async function eventsIn(sig: string) {
+    const tx = await provider.connection.getTransaction(sig, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    return Array.from(parser.parseLogs(tx?.meta?.logMessages ?? []));
+  }
+
+  function eventsNamed(events: { name: string; data: any }[], name: string) {
+    return events.filter((e) => e.name.toLowerCase() === name.toLowerCase());
+  }
+
+  // Runs a call expected to fail and returns the parsed error plus any events it logged
+  async function expectRejection(call: Promise<unknown>) {
+    try {
+      await call;
+    } catch (e: any) {
+      const parser = new anchor.EventParser(program.programId, program.coder);
+      return { error: e, events: Array.from(parser.parseLogs(e.logs ?? [])) };
+    }
+    assert.fail("Expected the transaction to be rejected");
+  }
+
+  async function setConfig(
+    maxRequests: number,
+    windowSeconds: number,
+    burstLimit: number,
+    resetWindows = false
+  ) {
+    await program.methods
+      .updateConfig(
+        {
+          maxRequests: new anchor.BN(maxRequests),
+          windowSeconds: new anchor.BN(windowSeconds),
+          burstLimit: new anchor.BN(burstLimit),
+        },
+        resetWindows
+      )
+      .accounts({
+        globalConfig: globalConfigPda,
+        admin: admin.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+  }
+
+  it("Rejects initialize from a wallet that is not the upgrade authority", async () => {
+    const intruder = await fundedWallet();
+    try {
+      await program.methods
+        .initialize(config)
+        .accounts({
+          globalConfig: globalConfigPda,
+          admin: intruder.publicKey,
+          programData: programDataPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([intruder])
+        .rpc();
+      assert.fail("Should have thrown NotUpgradeAuthority");
+    } catch (e: any) {
+      // Re-runs against an existing deployment fail on the init instead
+      assert.match(e.message, /NotUpgradeAuthority|already in use/);
+    }
+  });
+
   it("Initializes the rate limiter", async () => {
     try {
       const tx = await program.methods
@@ -34,6 +185,7 @@ describe("solana-rate-limiter", () => {
         .accounts({
           globalConfig: globalConfigPda,
           admin: admin.publicKey,
+          programData: programDataPda,
           systemProgram: SystemProgram.programId,
         })
         .rpc();
@@ -42,7 +194,7 @@ describe("solana-rate-limiter", () => {
       assert.equal(gc.maxRequests.toNumber(), 5);
       assert.equal(gc.windowSeconds.toNumber(), 60);
       assert.equal(gc.burstLimit.toNumber(), 7);
-      assert.equal(gc.isPaused, false);
+      assert.equal(isPaused(gc), false);
     } catch (e: any) {
       if (e.message?.includes("already in use")) {
         console.log("⚠️  Already initialized, skipping...");
@@ -52,6 +204,29 @@ describe("solana-rate-limiter", () => {
     }
   });
 
+  it("Initializes global stats", async () => {
+    try {
+      await program.methods
+        .initializeStats(new anchor.BN(5))
+        .accounts({
+          globalConfig: globalConfigPda,
+          globalStats: globalStatsPda,
+          admin: admin.publicKey,
+          payer: admin.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      const stats = await program.account.globalStats.fetch(globalStatsPda);
+      assert.equal(stats.snapshotIntervalSeconds.toNumber(), 5);
+    } catch (e: any) {
+      if (e.message?.includes("already in use")) {
+        console.log("⚠️  Stats already initialized, skipping...");
+      } else {
+        throw e;
+      }
+    }
+  });
+
   it("Registers a client", async () => {
     try {
       const tx = await program.methods
@@ -60,13 +235,14 @@ describe("solana-rate-limiter", () => {
           globalConfig: globalConfigPda,
           clientBucket: clientBucketPda,
           client: admin.publicKey,
+          payer: admin.publicKey,
           systemProgram: SystemProgram.programId,
         })
         .rpc();
       console.log("✅ Register client tx:", tx);
       const bucket = await program.account.clientBucket.fetch(clientBucketPda);
       assert.equal(bucket.requestCount.toNumber(), 0);
-      assert.equal(bucket.isBlocked, false);
+      assert.equal(isBlocked(bucket), false);
     } catch (e: any) {
       if (e.message?.includes("already in use")) {
         console.log("⚠️  Already registered, skipping...");
@@ -134,7 +310,7 @@ describe("solana-rate-limiter", () => {
       .rpc();
     console.log("✅ Block client tx:", tx);
     const bucket = await program.account.clientBucket.fetch(clientBucketPda);
-    assert.equal(bucket.isBlocked, true);
+    assert.equal(isBlocked(bucket), true);
   });
 
   it("Blocked client cannot consume requests", async () => {
@@ -164,7 +340,7 @@ describe("solana-rate-limiter", () => {
       .rpc();
     console.log("✅ Toggle pause tx:", tx);
     const gc = await program.account.globalConfig.fetch(globalConfigPda);
-    assert.equal(gc.isPaused, true);
+    assert.equal(isPaused(gc), true);
   });
 
   it("Admin can update config", async () => {
@@ -183,7 +359,7 @@ describe("solana-rate-limiter", () => {
     };
 
     const tx = await program.methods
-      .updateConfig(newConfig)
+      .updateConfig(newConfig, false)
       .accounts({
         globalConfig: globalConfigPda,
         admin: admin.publicKey,
@@ -194,4 +370,2951 @@ describe("solana-rate-limiter", () => {
     assert.equal(gc.maxRequests.toNumber(), 10);
     assert.equal(gc.windowSeconds.toNumber(), 120);
   });
+
+  describe("window rollover events", () => {
+    let client: Keypair;
+
+    before(async () => {
+      await setConfig(10, 2, 15);
+      client = await newClient();
+    });
+
+    after(async () => {
+      await setConfig(10, 120, 15);
+    });
+
+    it("Emits no WindowClosed when the window is still open", async () => {
+      const sig = await consumeAs(client);
+      assert.lengthOf(eventsNamed(await eventsIn(sig), "WindowClosed"), 0);
+    });
+
+    it("Emits WindowClosed once on a single rollover", async () => {
+      const before = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      await sleep(2500);
+      const sig = await consumeAs(client);
+      const closed = eventsNamed(await eventsIn(sig), "WindowClosed");
+      assert.lengthOf(closed, 1);
+      assert.equal(closed[0].data.closedCount.toNumber(), 1);
+      assert.equal(closed[0].data.closedWindowStart.toNumber(), before.windowStart.toNumber());
+      assert.equal(closed[0].data.skippedWindows.toNumber(), 0);
+
+      const after = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(closed[0].data.newWindowStart.toNumber(), after.windowStart.toNumber());
+      assert.equal(after.requestCount.toNumber(), 1);
+    });
+
+    it("Reports skipped windows after a multi-window idle gap", async () => {
+      await sleep(6500);
+      const sig = await consumeAs(client);
+      const closed = eventsNamed(await eventsIn(sig), "WindowClosed");
+      assert.lengthOf(closed, 1);
+      assert.equal(closed[0].data.closedCount.toNumber(), 1);
+      assert.isAtLeast(closed[0].data.skippedWindows.toNumber(), 2);
+    });
+  });
+
+  describe("rejection events", () => {
+    let client: Keypair;
+
+    before(async () => {
+      client = await newClient();
+      for (let i = 0; i < 10; i++) {
+        await consumeAs(client);
+      }
+    });
+
+    it("Emits ConsumeRejected with RateLimitExceeded reason", async () => {
+      const { error, events } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "RateLimitExceeded");
+      const rejected = eventsNamed(events, "ConsumeRejected");
+      assert.lengthOf(rejected, 1);
+      assert.equal(rejected[0].data.reasonCode, 1);
+      assert.equal(rejected[0].data.requestCount.toNumber(), 10);
+      assert.equal(rejected[0].data.violations.toNumber(), 1);
+
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.requestCount.toNumber(), 10);
+      assert.equal(bucket.totalRequests.toNumber(), 10);
+    });
+
+    it("Emits ConsumeRejected with ClientBlocked reason", async () => {
+      await program.methods
+        .blockClient()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          admin: admin.publicKey,
+          clientWallet: client.publicKey,
+        })
+        .rpc();
+
+      const { error, events } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "ClientBlocked");
+      const rejected = eventsNamed(events, "ConsumeRejected");
+      assert.lengthOf(rejected, 1);
+      assert.equal(rejected[0].data.reasonCode, 3);
+    });
+
+    it("Paused rejections emit nothing and leave the bucket untouched", async () => {
+      const other = await newClient();
+      await consumeAs(other);
+      const before = await program.account.clientBucket.fetch(bucketFor(other.publicKey));
+
+      await program.methods
+        .togglePause()
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc();
+      try {
+        const { error, events } = await expectRejection(consumeAs(other));
+        assert.include(error.message, "ProgramPaused");
+        assert.lengthOf(eventsNamed(events, "ConsumeRejected"), 0);
+      } finally {
+        await program.methods
+          .togglePause()
+          .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+          .rpc();
+      }
+
+      const after = await program.account.clientBucket.fetch(bucketFor(other.publicKey));
+      assert.equal(after.requestCount.toNumber(), before.requestCount.toNumber());
+      assert.equal(after.violations.toNumber(), before.violations.toNumber());
+    });
+  });
+
+  describe("stats snapshots", () => {
+    async function snapshot(bucket?: PublicKey): Promise<string> {
+      return program.methods
+        .emitStatsSnapshot()
+        .accounts({ globalStats: globalStatsPda })
+        .remainingAccounts(
+          bucket ? [{ pubkey: bucket, isSigner: false, isWritable: false }] : []
+        )
+        .rpc({ commitment: "confirmed" });
+    }
+
+    it("Emits a snapshot of the global counters and a supplied bucket", async () => {
+      const stats = await program.account.globalStats.fetch(globalStatsPda);
+      await sleep(stats.snapshotIntervalSeconds.toNumber() * 1000);
+
+      const client = await newClient();
+      await consumeAs(client);
+      const sig = await snapshot(bucketFor(client.publicKey));
+      const snapshots = eventsNamed(await eventsIn(sig), "StatsSnapshot");
+      assert.lengthOf(snapshots, 1);
+
+      const after = await program.account.globalStats.fetch(globalStatsPda);
+      assert.equal(snapshots[0].data.totalRequests.toNumber(), after.totalRequests.toNumber());
+      assert.equal(snapshots[0].data.totalClients.toNumber(), after.totalClients.toNumber());
+      assert.equal(snapshots[0].data.timestamp.toNumber(), after.lastSnapshotAt.toNumber());
+      assert.isTrue(snapshots[0].data.bucketOwner.equals(client.publicKey));
+      assert.equal(snapshots[0].data.bucketRequestCount.toNumber(), 1);
+    });
+
+    it("Rejects a second snapshot inside the interval", async () => {
+      const { error } = await expectRejection(snapshot());
+      assert.include(error.message, "SnapshotTooSoon");
+    });
+
+    it("Allows another snapshot once the interval has elapsed", async () => {
+      const stats = await program.account.globalStats.fetch(globalStatsPda);
+      await sleep(stats.snapshotIntervalSeconds.toNumber() * 1000 + 1000);
+      const sig = await snapshot();
+      const snapshots = eventsNamed(await eventsIn(sig), "StatsSnapshot");
+      assert.lengthOf(snapshots, 1);
+      assert.isNull(snapshots[0].data.bucketOwner);
+    });
+  });
+
+  describe("extreme config values", () => {
+    const I64_MAX = new anchor.BN("9223372036854775807");
+    const U64_MAX = new anchor.BN("18446744073709551615");
+    let client: Keypair;
+
+    before(async () => {
+      client = await newClient();
+    });
+
+    after(async () => {
+      await setConfig(10, 120, 15);
+    });
+
+    it("Fails cleanly with ArithmeticOverflow when window_seconds is i64::MAX", async () => {
+      await program.methods
+        .updateConfig(
+          { maxRequests: new anchor.BN(10), windowSeconds: I64_MAX, burstLimit: new anchor.BN(15) },
+          false
+        )
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc();
+
+      const { error } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "ArithmeticOverflow");
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.requestCount.toNumber(), 0);
+    });
+
+    it("Accepts consumes when max_requests and burst_limit are u64::MAX", async () => {
+      await program.methods
+        .updateConfig({ maxRequests: U64_MAX, windowSeconds: new anchor.BN(120), burstLimit: U64_MAX }, false)
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc();
+
+      await consumeAs(client);
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.requestCount.toNumber(), 1);
+    });
+  });
+
+  describe("config changes mid-window", () => {
+    let client: Keypair;
+
+    beforeEach(async () => {
+      await setConfig(10, 120, 15);
+      client = await newClient();
+      for (let i = 0; i < 5; i++) {
+        await consumeAs(client);
+      }
+    });
+
+    after(async () => {
+      await setConfig(10, 120, 15);
+    });
+
+    async function fetchBucket() {
+      return program.account.clientBucket.fetch(bucketFor(client.publicKey));
+    }
+
+    async function configEpoch(): Promise<number> {
+      return (await program.account.globalConfig.fetch(globalConfigPda)).configEpoch.toNumber();
+    }
+
+    it("Bumps config_epoch only when reset_windows is set", async () => {
+      const before = await configEpoch();
+      await setConfig(10, 120, 15);
+      assert.equal(await configEpoch(), before);
+      await setConfig(10, 120, 15, true);
+      assert.equal(await configEpoch(), before + 1);
+    });
+
+    it("Without reset_windows, a lowered max applies to the current window", async () => {
+      const before = await fetchBucket();
+      await setConfig(3, 120, 3);
+      const { error } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "RateLimitExceeded");
+
+      const after = await fetchBucket();
+      assert.equal(after.requestCount.toNumber(), 5);
+      assert.equal(after.windowStart.toNumber(), before.windowStart.toNumber());
+      assert.equal(after.configEpoch.toNumber(), before.configEpoch.toNumber());
+    });
+
+    it("With reset_windows, lowering max below usage restarts the window lazily", async () => {
+      await setConfig(3, 120, 3, true);
+      const untouched = await fetchBucket();
+      assert.equal(untouched.requestCount.toNumber(), 5);
+
+      const sig = await consumeAs(client);
+      const closed = eventsNamed(await eventsIn(sig), "WindowClosed");
+      assert.lengthOf(closed, 1);
+      assert.equal(closed[0].data.closedCount.toNumber(), 5);
+
+      const bucket = await fetchBucket();
+      assert.equal(bucket.requestCount.toNumber(), 1);
+      assert.equal(bucket.configEpoch.toNumber(), await configEpoch());
+      assert.equal(bucket.totalRequests.toNumber(), 6);
+    });
+
+    it("With reset_windows, raising max restarts the window under the new limit", async () => {
+      await setConfig(20, 120, 25, true);
+      await consumeAs(client);
+      assert.equal((await fetchBucket()).requestCount.toNumber(), 1);
+    });
+
+    it("With reset_windows, shortening or lengthening the window restarts it", async () => {
+      await setConfig(10, 30, 15, true);
+      await consumeAs(client);
+      const shortened = await fetchBucket();
+      assert.equal(shortened.requestCount.toNumber(), 1);
+
+      await setConfig(10, 3600, 15, true);
+      await consumeAs(client);
+      const lengthened = await fetchBucket();
+      assert.equal(lengthened.requestCount.toNumber(), 1);
+      assert.isAtLeast(lengthened.windowStart.toNumber(), shortened.windowStart.toNumber());
+    });
+  });
+
+  describe("burst credits", () => {
+    before(async () => {
+      await setConfig(2, 5, 3);
+    });
+
+    after(async () => {
+      await setConfig(10, 120, 15);
+    });
+
+    it("A client without banked credit is stopped at max_requests", async () => {
+      const client = await newClient();
+      await consumeAs(client);
+      await consumeAs(client);
+      const { error, events } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "RateLimitExceeded");
+      assert.equal(eventsNamed(events, "ConsumeRejected")[0].data.reasonCode, 1);
+    });
+
+    it("Unused capacity is banked and spent above max, until burst_limit", async () => {
+      const client = await newClient();
+      await sleep(5500);
+
+      // First consume closes an idle window, banking min(2 unused, 2 max) = 2 credits
+      await consumeAs(client);
+      let bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.burstCredits.toNumber(), 2);
+
+      await consumeAs(client);
+      await consumeAs(client);
+      bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.requestCount.toNumber(), 3);
+      assert.equal(bucket.burstCredits.toNumber(), 1);
+
+      // Credit is still banked, but the window is at its burst_limit ceiling
+      const { error, events } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "BurstLimitExceeded");
+      assert.equal(eventsNamed(events, "ConsumeRejected")[0].data.reasonCode, 2);
+    });
+  });
+
+  describe("lifetime total saturation", () => {
+    const U64_MAX = new anchor.BN("18446744073709551615");
+
+    it("Pins total_requests at u64::MAX and flags it as saturated", async () => {
+      const client = await newClient();
+      await program.methods
+        .adjustClientUsage(U64_MAX.subn(1))
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          admin: admin.publicKey,
+          clientWallet: client.publicKey,
+        })
+        .rpc();
+
+      await consumeAs(client);
+      let bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.isTrue(bucket.totalRequests.eq(U64_MAX));
+      assert.isFalse(isTotalSaturated(bucket));
+
+      await consumeAs(client);
+      bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.isTrue(bucket.totalRequests.eq(U64_MAX));
+      assert.isTrue(isTotalSaturated(bucket));
+      assert.equal(bucket.requestCount.toNumber(), 2);
+    });
+
+    it("Rejects usage adjustments from non-admins", async () => {
+      const client = await newClient();
+      const { error } = await expectRejection(
+        program.methods
+          .adjustClientUsage(new anchor.BN(0))
+          .accounts({
+            globalConfig: globalConfigPda,
+            clientBucket: bucketFor(client.publicKey),
+            admin: client.publicKey,
+            clientWallet: client.publicKey,
+          })
+          .signers([client])
+          .rpc()
+      );
+      assert.include(error.message, "Unauthorized");
+    });
+  });
+
+  describe("wallet/bucket mismatch", () => {
+    let alice: Keypair;
+    let bob: Keypair;
+
+    before(async () => {
+      alice = await newClient();
+      bob = await newClient();
+    });
+
+    // Alice's wallet paired with Bob's bucket must never reach the handler
+    const mismatched = () => ({
+      globalConfig: globalConfigPda,
+      clientBucket: bucketFor(bob.publicKey),
+      admin: admin.publicKey,
+      clientWallet: alice.publicKey,
+    });
+
+    function assertMismatchRejected(error: any) {
+      assert.match(error.message, /OwnerMismatch|ConstraintSeeds/);
+    }
+
+    it("reset_client rejects a mismatched wallet and bucket", async () => {
+      const { error } = await expectRejection(
+        program.methods.resetClient().accounts(mismatched()).rpc()
+      );
+      assertMismatchRejected(error);
+    });
+
+    it("block_client rejects a mismatched wallet and bucket", async () => {
+      const { error } = await expectRejection(
+        program.methods.blockClient().accounts(mismatched()).rpc()
+      );
+      assertMismatchRejected(error);
+      const bucket = await program.account.clientBucket.fetch(bucketFor(bob.publicKey));
+      assert.isFalse(isBlocked(bucket));
+    });
+
+    it("adjust_client_usage rejects a mismatched wallet and bucket", async () => {
+      const { error } = await expectRejection(
+        program.methods.adjustClientUsage(new anchor.BN(42)).accounts(mismatched()).rpc()
+      );
+      assertMismatchRejected(error);
+    });
+  });
+
+  describe("unregistered clients", () => {
+    async function tryConsumeAs(client: Keypair): Promise<string> {
+      return program.methods
+        .tryConsume()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          client: client.publicKey,
+        })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    it("consume_request surfaces Anchor's AccountNotInitialized", async () => {
+      const stranger = await fundedWallet();
+      const { error } = await expectRejection(consumeAs(stranger));
+      assert.include(error.message, "AccountNotInitialized");
+    });
+
+    it("try_consume surfaces ClientNotRegistered", async () => {
+      const stranger = await fundedWallet();
+      const { error } = await expectRejection(tryConsumeAs(stranger));
+      assert.include(error.message, "ClientNotRegistered");
+    });
+
+    it("try_consume behaves like consume_request for registered clients", async () => {
+      const client = await newClient();
+      await tryConsumeAs(client);
+      await consumeAs(client);
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.requestCount.toNumber(), 2);
+      assert.equal(bucket.totalRequests.toNumber(), 2);
+    });
+  });
+
+  describe("weighted consumption amounts", () => {
+    const U64_MAX = new anchor.BN("18446744073709551615");
+    let client: Keypair;
+
+    before(async () => {
+      await setConfig(10, 120, 15);
+    });
+
+    beforeEach(async () => {
+      client = await newClient();
+    });
+
+    async function consumeAmountAs(client: Keypair, amount: anchor.BN): Promise<string> {
+      return program.methods
+        .consumeAmount(amount)
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          client: client.publicKey,
+        })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    it("Rejects a zero amount", async () => {
+      const { error } = await expectRejection(consumeAmountAs(client, new anchor.BN(0)));
+      assert.include(error.message, "InvalidAmount");
+    });
+
+    it("Accepts an amount up to max_requests in one call", async () => {
+      await consumeAmountAs(client, new anchor.BN(10));
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.requestCount.toNumber(), 10);
+      assert.equal(bucket.totalRequests.toNumber(), 10);
+    });
+
+    it("Treats burst_limit as the cap and still enforces max_requests", async () => {
+      const { error } = await expectRejection(consumeAmountAs(client, new anchor.BN(15)));
+      assert.include(error.message, "RateLimitExceeded");
+    });
+
+    it("Rejects cap + 1", async () => {
+      const { error } = await expectRejection(consumeAmountAs(client, new anchor.BN(16)));
+      assert.include(error.message, "AmountExceedsCap");
+    });
+
+    it("Rejects u64::MAX without wrapping the counters", async () => {
+      const { error } = await expectRejection(consumeAmountAs(client, U64_MAX));
+      assert.include(error.message, "AmountExceedsCap");
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.requestCount.toNumber(), 0);
+    });
+  });
+
+  describe("bucket invariants", () => {
+    async function verify(client: Keypair): Promise<number> {
+      return program.methods
+        .verifyBucket()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+        })
+        .view();
+    }
+
+    it("verify_bucket reports a fresh and a used bucket as consistent", async () => {
+      await setConfig(5, 60, 10);
+      const client = await newClient();
+      assert.equal(await verify(client), 0);
+      await consumeAs(client);
+      await consumeAs(client);
+      assert.equal(await verify(client), 0);
+    });
+
+    it("adjust_client_usage cannot push the lifetime total below the open window", async () => {
+      const client = await newClient();
+      await consumeAs(client);
+      await consumeAs(client);
+      const { error } = await expectRejection(
+        program.methods
+          .adjustClientUsage(new anchor.BN(1))
+          .accounts({
+            globalConfig: globalConfigPda,
+            clientBucket: bucketFor(client.publicKey),
+            admin: admin.publicKey,
+            clientWallet: client.publicKey,
+          })
+          .rpc()
+      );
+      assert.include(error.message, "InvalidAmount");
+      assert.equal(await verify(client), 0);
+    });
+  });
+
+  describe("compute budget", () => {
+    // The agreed ceiling for the default (non verbose-logs) build, the same one
+    // tests/consume.rs holds program-test to. Raise it only with a measured reason.
+    const CONSUME_CU_BUDGET = 6_000;
+
+    async function unitsUsed(sig: string): Promise<number> {
+      const tx = await provider.connection.getTransaction(sig, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      return tx?.meta?.computeUnitsConsumed ?? Number.MAX_SAFE_INTEGER;
+    }
+
+    it("consume_request stays under the CU budget", async () => {
+      await setConfig(5, 60, 10);
+      const client = await newClient();
+      const first = await unitsUsed(await consumeAs(client));
+      const second = await unitsUsed(await consumeAs(client));
+      console.log(`    consume_request: ${first} / ${second} CU`);
+      assert.isAtMost(first, CONSUME_CU_BUDGET);
+      assert.isAtMost(second, CONSUME_CU_BUDGET);
+    });
+  });
+
+  describe("zero-copy fast buckets", () => {
+    function fastBucketFor(wallet: PublicKey): PublicKey {
+      return PublicKey.findProgramAddressSync(
+        [seed("FAST_BUCKET_SEED"), wallet.toBuffer()],
+        program.programId
+      )[0];
+    }
+
+    async function newFastClient(): Promise<Keypair> {
+      const client = await fundedWallet();
+      await program.methods
+        .registerFastClient()
+        .accounts({ globalConfig: globalConfigPda, client: client.publicKey, payer: client.publicKey })
+        .signers([client])
+        .rpc();
+      return client;
+    }
+
+    async function consumeFastAs(client: Keypair): Promise<string> {
+      return program.methods
+        .consumeRequestFast()
+        .accounts({ globalConfig: globalConfigPda, client: client.publicKey })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    async function unitsUsed(sig: string): Promise<number> {
+      const tx = await provider.connection.getTransaction(sig, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      return tx?.meta?.computeUnitsConsumed ?? Number.MAX_SAFE_INTEGER;
+    }
+
+    before(async () => {
+      await setConfig(3, 60, 3);
+    });
+
+    it("Enforces the same limit as a regular bucket", async () => {
+      const client = await newFastClient();
+      for (let i = 0; i < 3; i++) {
+        await consumeFastAs(client);
+      }
+      const { error } = await expectRejection(consumeFastAs(client));
+      assert.include(error.message, "RateLimitExceeded");
+
+      const bucket = await program.account.fastBucket.fetch(fastBucketFor(client.publicKey));
+      assert.equal(bucket.requestCount.toNumber(), 3);
+      assert.equal(bucket.totalRequests.toNumber(), 3);
+    });
+
+    it("Costs fewer compute units than consume_request", async () => {
+      const regular = await unitsUsed(await consumeAs(await newClient()));
+      const fast = await unitsUsed(await consumeFastAs(await newFastClient()));
+      console.log(`    consume_request: ${regular} CU, consume_request_fast: ${fast} CU`);
+      assert.isBelow(fast, regular);
+    });
+
+    it("A client cannot hold both bucket kinds", async () => {
+      const fastClient = await newFastClient();
+      const { error } = await expectRejection(
+        program.methods
+          .registerClient()
+          .accounts({ globalConfig: globalConfigPda, client: fastClient.publicKey, payer: fastClient.publicKey })
+          .signers([fastClient])
+          .rpc()
+      );
+      assert.include(error.message, "BucketKindConflict");
+
+      const regularClient = await newClient();
+      const second = await expectRejection(
+        program.methods
+          .registerFastClient()
+          .accounts({ globalConfig: globalConfigPda, client: regularClient.publicKey, payer: regularClient.publicKey })
+          .signers([regularClient])
+          .rpc()
+      );
+      assert.include(second.error.message, "BucketKindConflict");
+    });
+
+    it("A fast bucket is not accepted where a regular bucket is expected", async () => {
+      const client = await newFastClient();
+      const { error } = await expectRejection(
+        program.methods
+          .consumeRequest()
+          .accounts({
+            globalConfig: globalConfigPda,
+            clientBucket: fastBucketFor(client.publicKey),
+            client: client.publicKey,
+          })
+          .signers([client])
+          .rpc()
+      );
+      assert.match(error.message, /AccountDiscriminatorMismatch|ConstraintSeeds|AccountNotInitialized/);
+    });
+
+    it("Blocked fast clients are rejected", async () => {
+      const client = await newFastClient();
+      await program.methods
+        .blockFastClient()
+        .accounts({
+          globalConfig: globalConfigPda,
+          admin: admin.publicKey,
+          clientWallet: client.publicKey,
+        })
+        .rpc();
+      const { error } = await expectRejection(consumeFastAs(client));
+      assert.include(error.message, "ClientBlocked");
+
+      await program.methods
+        .resetFastClient()
+        .accounts({
+          globalConfig: globalConfigPda,
+          admin: admin.publicKey,
+          clientWallet: client.publicKey,
+        })
+        .rpc();
+      await consumeFastAs(client);
+    });
+  });
+
+  describe("sharded buckets", () => {
+    const SHARDS = 3;
+
+    function shardParentFor(wallet: PublicKey): PublicKey {
+      return PublicKey.findProgramAddressSync(
+        [seed("SHARD_PARENT_SEED"), wallet.toBuffer()],
+        program.programId
+      )[0];
+    }
+
+    function shardFor(wallet: PublicKey, index: number): PublicKey {
+      return PublicKey.findProgramAddressSync(
+        [seed("CLIENT_SHARD_SEED"), wallet.toBuffer(), Buffer.from([index])],
+        program.programId
+      )[0];
+    }
+
+    function shardAccounts(wallet: PublicKey, count = SHARDS) {
+      return Array.from({ length: count }, (_, i) => ({
+        pubkey: shardFor(wallet, i),
+        isSigner: false,
+        isWritable: true,
+      }));
+    }
+
+    async function newShardedClient(): Promise<Keypair> {
+      const client = await fundedWallet();
+      await program.methods
+        .registerSharded(SHARDS)
+        .accounts({ globalConfig: globalConfigPda, client: client.publicKey, payer: client.publicKey })
+        .remainingAccounts(shardAccounts(client.publicKey))
+        .signers([client])
+        .rpc();
+      return client;
+    }
+
+    async function consumeShardAs(client: Keypair, shard: number): Promise<string> {
+      return program.methods
+        .consumeSharded(shard)
+        .accounts({ globalConfig: globalConfigPda, client: client.publicKey })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    async function aggregate(client: Keypair) {
+      await program.methods
+        .aggregateShards()
+        .accounts({ globalConfig: globalConfigPda, shardParent: shardParentFor(client.publicKey) })
+        .remainingAccounts(shardAccounts(client.publicKey).map((a) => ({ ...a, isWritable: false })))
+        .rpc();
+      return program.account.shardParent.fetch(shardParentFor(client.publicKey));
+    }
+
+    before(async () => {
+      await setConfig(10, 120, 10);
+    });
+
+    it("Creates the parent and every shard", async () => {
+      const client = await newShardedClient();
+      const parent = await program.account.shardParent.fetch(shardParentFor(client.publicKey));
+      assert.equal(parent.shardCount, SHARDS);
+      for (let i = 0; i < SHARDS; i++) {
+        const shard = await program.account.clientBucket.fetch(shardFor(client.publicKey, i));
+        assert.isTrue(shard.owner.equals(client.publicKey));
+      }
+    });
+
+    it("Keeps aggregate enforcement within shard_count of the true limit", async () => {
+      const client = await newShardedClient();
+      let accepted = 0;
+      for (let shard = 0; shard < SHARDS; shard++) {
+        for (;;) {
+          try {
+            await consumeShardAs(client, shard);
+            accepted++;
+          } catch (e: any) {
+            assert.include(e.message, "RateLimitExceeded");
+            break;
+          }
+        }
+      }
+      assert.isAbove(accepted, 10 - SHARDS);
+      assert.isAtMost(accepted, 10);
+
+      const before = await program.account.globalStats.fetch(globalStatsPda);
+      const parent = await aggregate(client);
+      assert.equal(parent.windowRequests.toNumber(), accepted);
+      assert.equal(parent.totalRequests.toNumber(), accepted);
+      assert.isAbove(parent.lastAggregatedAt.toNumber(), 0);
+
+      // The shards' requests reach the program-wide total once, at aggregation.
+      const after = await program.account.globalStats.fetch(globalStatsPda);
+      assert.equal(after.totalRequests.toNumber() - before.totalRequests.toNumber(), accepted);
+      await aggregate(client);
+      const again = await program.account.globalStats.fetch(globalStatsPda);
+      assert.equal(again.totalRequests.toNumber(), after.totalRequests.toNumber());
+    });
+
+    it("Rejects a shard index past shard_count", async () => {
+      const client = await newShardedClient();
+      const { error } = await expectRejection(
+        program.methods
+          .consumeSharded(SHARDS)
+          .accounts({
+            globalConfig: globalConfigPda,
+            shardParent: shardParentFor(client.publicKey),
+            shardBucket: shardFor(client.publicKey, SHARDS),
+            client: client.publicKey,
+          })
+          .signers([client])
+          .rpc()
+      );
+      assert.match(error.message, /InvalidShard|AccountNotInitialized/);
+    });
+
+    it("aggregate_shards requires every shard", async () => {
+      const client = await newShardedClient();
+      const { error } = await expectRejection(
+        program.methods
+          .aggregateShards()
+          .accounts({ globalConfig: globalConfigPda, shardParent: shardParentFor(client.publicKey) })
+          .remainingAccounts(shardAccounts(client.publicKey, SHARDS - 1))
+          .rpc()
+      );
+      assert.include(error.message, "InvalidShardCount");
+    });
+
+    it("A sharded client cannot also register a regular bucket", async () => {
+      const client = await newShardedClient();
+      const { error } = await expectRejection(
+        program.methods
+          .registerClient()
+          .accounts({ globalConfig: globalConfigPda, client: client.publicKey, payer: client.publicKey })
+          .signers([client])
+          .rpc()
+      );
+      assert.include(error.message, "BucketKindConflict");
+    });
+  });
+
+  describe("account layout migration", () => {
+    async function migrate(account: PublicKey) {
+      return program.methods.migrateAccount().accounts({ account, payer: admin.publicKey }).rpc();
+    }
+
+    it("migrate_account refuses a bucket already at the current version", async () => {
+      const client = await newClient();
+      const { error } = await expectRejection(migrate(bucketFor(client.publicKey)));
+      assert.include(error.message, "AlreadyMigrated");
+    });
+
+    it("migrate_account refuses the current config", async () => {
+      const { error } = await expectRejection(migrate(globalConfigPda));
+      assert.include(error.message, "AlreadyMigrated");
+    });
+
+    it("migrate_account refuses accounts that are neither a bucket nor the config", async () => {
+      const { error } = await expectRejection(migrate(globalStatsPda));
+      assert.include(error.message, "InvalidAccountLayout");
+    });
+
+    it("top_up_rent leaves rent-exempt accounts exactly as funded", async () => {
+      const client = await newClient();
+      for (const account of [bucketFor(client.publicKey), globalConfigPda]) {
+        const before = await provider.connection.getAccountInfo(account, "confirmed");
+        const sig = await program.methods
+          .topUpRent()
+          .accounts({ account, payer: admin.publicKey })
+          .rpc({ commitment: "confirmed" });
+        const after = await provider.connection.getAccountInfo(account, "confirmed");
+        assert.equal(eventsNamed(await eventsIn(sig), "RentToppedUp")[0].data.lamports.toNumber(), 0);
+        assert.equal(after!.lamports, before!.lamports);
+        assert.isAtLeast(
+          after!.lamports,
+          await provider.connection.getMinimumBalanceForRentExemption(after!.data.length)
+        );
+      }
+    });
+
+    it("top_up_rent refuses accounts the program does not own", async () => {
+      const { error } = await expectRejection(
+        program.methods.topUpRent().accounts({ account: admin.publicKey, payer: admin.publicKey }).rpc()
+      );
+      assert.include(error.message, "ConstraintOwner");
+    });
+
+    it("New accounts are written at the current version", async () => {
+      const client = await newClient();
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.version, 7);
+      const gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.equal(gc.version, 11);
+    });
+
+    it("New buckets start with an empty label", async () => {
+      const client = await newClient();
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.deepEqual(Array.from(bucket.label), new Array(32).fill(0));
+    });
+  });
+
+  describe("gate_check", () => {
+    function gate(client: Keypair) {
+      return program.methods
+        .gateCheck()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          client: client.publicKey,
+        })
+        .signers([client]);
+    }
+
+    before(async () => {
+      await setConfig(3, 60, 3);
+    });
+
+    it("Allows and reports the capacity left", async () => {
+      const client = await newClient();
+      const result = await gate(client).view();
+      assert.isTrue(result.allowed);
+      assert.equal(result.remaining.toNumber(), 2);
+      assert.equal(result.retryAfter.toNumber(), 0);
+    });
+
+    it("Denies over the limit without failing and keeps the violation", async () => {
+      const client = await newClient();
+      for (let i = 0; i < 3; i++) {
+        await gate(client).rpc({ commitment: "confirmed" });
+      }
+
+      const result = await gate(client).view();
+      assert.isFalse(result.allowed);
+      assert.equal(result.remaining.toNumber(), 0);
+      assert.isAbove(result.retryAfter.toNumber(), 0);
+      assert.isAtMost(result.retryAfter.toNumber(), 60);
+
+      const sig = await gate(client).rpc({ commitment: "confirmed" });
+      const rejected = eventsNamed(await eventsIn(sig), "ConsumeRejected");
+      assert.lengthOf(rejected, 1);
+      assert.equal(rejected[0].data.reasonCode, 1);
+
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.requestCount.toNumber(), 3);
+      assert.equal(bucket.violations.toNumber(), 1);
+    });
+
+    it("Still fails for a blocked client", async () => {
+      const client = await newClient();
+      await program.methods
+        .blockClient()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          admin: admin.publicKey,
+          clientWallet: client.publicKey,
+        })
+        .rpc();
+      const { error } = await expectRejection(gate(client).rpc());
+      assert.include(error.message, "ClientBlocked");
+    });
+  });
+
+  describe("verify_consumed_in_tx", () => {
+    function consumeIx(client: Keypair) {
+      return program.methods
+        .consumeRequest()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          client: client.publicKey,
+        })
+        .instruction();
+    }
+
+    function verifyIx(client: Keypair) {
+      return program.methods
+        .verifyConsumedInTx(client.publicKey)
+        .accounts({ instructions: SYSVAR_INSTRUCTIONS_PUBKEY })
+        .instruction();
+    }
+
+    async function send(signers: Keypair[], ...ixs: Promise<anchor.web3.TransactionInstruction>[]) {
+      const tx = new Transaction().add(...(await Promise.all(ixs)));
+      return provider.sendAndConfirm(tx, signers);
+    }
+
+    before(async () => {
+      await setConfig(10, 120, 15);
+    });
+
+    it("Passes when the client consumed earlier in the transaction", async () => {
+      const client = await newClient();
+      await send([client], consumeIx(client), verifyIx(client));
+    });
+
+    it("Fails when the consume comes after the check", async () => {
+      const client = await newClient();
+      const { error } = await expectRejection(send([client], verifyIx(client), consumeIx(client)));
+      assert.include((error.logs ?? []).join("\n"), "ConsumeNotFound");
+    });
+
+    it("Only counts consumes by the client being checked", async () => {
+      const client = await newClient();
+      const other = await newClient();
+      const { error } = await expectRejection(send([other], consumeIx(other), verifyIx(client)));
+      assert.include((error.logs ?? []).join("\n"), "ConsumeNotFound");
+
+      // Any one matching consume among several is enough
+      await send([other, client], consumeIx(other), consumeIx(client), verifyIx(client));
+    });
+  });
+
+  describe("dynamic limits", () => {
+    let feeder: Keypair;
+
+    function setFeeder(key: PublicKey, floor: number, ceiling: number) {
+      return program.methods
+        .setLimitFeeder(key, new anchor.BN(floor), new anchor.BN(ceiling))
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function push(signer: Keypair, newMax: number, validForSeconds: number) {
+      const validUntil = Math.floor(Date.now() / 1000) + validForSeconds;
+      return program.methods
+        .pushDynamicLimit(new anchor.BN(newMax), new anchor.BN(validUntil))
+        .accounts({ globalConfig: globalConfigPda, limitFeeder: signer.publicKey })
+        .signers([signer])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    async function consumeTimes(client: Keypair, times: number) {
+      for (let i = 0; i < times; i++) {
+        await consumeAs(client);
+      }
+    }
+
+    before(async () => {
+      await setConfig(2, 60, 2);
+      feeder = await fundedWallet();
+      await setFeeder(feeder.publicKey, 1, 4);
+    });
+
+    after(async () => {
+      await setFeeder(PublicKey.default, 0, 0);
+    });
+
+    it("Applies a feeder push to consumes", async () => {
+      await push(feeder, 3, 60);
+      const client = await newClient();
+      await consumeTimes(client, 3);
+      const { error } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "RateLimitExceeded");
+    });
+
+    it("Clamps a push to the admin's bounds", async () => {
+      const sig = await push(feeder, 100, 60);
+      const set = eventsNamed(await eventsIn(sig), "DynamicLimitSet");
+      assert.lengthOf(set, 1);
+      assert.equal(set[0].data.requestedMax.toNumber(), 100);
+      assert.equal(set[0].data.appliedMax.toNumber(), 4);
+
+      const client = await newClient();
+      await consumeTimes(client, 4);
+      const { error } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "RateLimitExceeded");
+    });
+
+    it("Reverts to the base config once the override expires", async () => {
+      await push(feeder, 4, 2);
+      await sleep(3500);
+      const client = await newClient();
+      await consumeTimes(client, 2);
+      const { error } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "RateLimitExceeded");
+    });
+
+    it("Refuses pushes from anyone but the feeder", async () => {
+      const other = await fundedWallet();
+      const { error } = await expectRejection(push(other, 3, 60));
+      assert.include(error.message, "Unauthorized");
+    });
+
+    it("Refuses pushes once the admin revokes the feeder", async () => {
+      await push(feeder, 4, 60);
+      await setFeeder(PublicKey.default, 0, 0);
+      const { error } = await expectRejection(push(feeder, 4, 60));
+      assert.include(error.message, "Unauthorized");
+
+      // Revoking also dropped the live override
+      const client = await newClient();
+      await consumeTimes(client, 2);
+      await expectRejection(consumeAs(client));
+      await setFeeder(feeder.publicKey, 1, 4);
+    });
+  });
+
+  describe("admin recovery", () => {
+    function begin(newAdmin: PublicKey, authority?: Keypair) {
+      const call = program.methods.beginAdminRecovery(newAdmin).accounts({
+        globalConfig: globalConfigPda,
+        authority: authority?.publicKey ?? admin.publicKey,
+        program: program.programId,
+        programData: programDataPda,
+      });
+      return (authority ? call.signers([authority]) : call).rpc({ commitment: "confirmed" });
+    }
+
+    function cancel(signer?: Keypair) {
+      const call = program.methods
+        .cancelAdminRecovery()
+        .accounts({ globalConfig: globalConfigPda, admin: signer?.publicKey ?? admin.publicKey });
+      return (signer ? call.signers([signer]) : call).rpc({ commitment: "confirmed" });
+    }
+
+    function finalize() {
+      return program.methods
+        .finalizeAdminRecovery()
+        .accounts({ globalConfig: globalConfigPda })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    it("Only the upgrade authority can begin a recovery", async () => {
+      const intruder = await fundedWallet();
+      const { error } = await expectRejection(begin(intruder.publicKey, intruder));
+      assert.include(error.message, "NotUpgradeAuthority");
+    });
+
+    it("Starts a timelocked recovery and the current admin can cancel it", async () => {
+      const successor = Keypair.generate();
+      const sig = await begin(successor.publicKey);
+      const started = eventsNamed(await eventsIn(sig), "AdminRecoveryStarted");
+      assert.lengthOf(started, 1);
+      assert.isTrue(started[0].data.newAdmin.equals(successor.publicKey));
+
+      let gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.isTrue(gc.recoveryAdmin.equals(successor.publicKey));
+      assert.equal(gc.recoveryEta.toNumber(), started[0].data.eta.toNumber());
+      assert.isTrue(gc.admin.equals(admin.publicKey));
+
+      // Nobody but the admin can cancel
+      const other = await fundedWallet();
+      const { error } = await expectRejection(cancel(other));
+      assert.include(error.message, "Unauthorized");
+
+      const cancelSig = await cancel();
+      assert.lengthOf(eventsNamed(await eventsIn(cancelSig), "AdminRecoveryCancelled"), 1);
+      gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.equal(gc.recoveryEta.toNumber(), 0);
+
+      const { error: again } = await expectRejection(cancel());
+      assert.include(again.message, "NoRecoveryPending");
+    });
+
+    it("Refuses to finalize before the timelock expires", async () => {
+      await begin(Keypair.generate().publicKey);
+      const { error } = await expectRejection(finalize());
+      assert.include(error.message, "RecoveryTimelockActive");
+      await cancel();
+    });
+
+    // The default build waits 7 days; build with --features short-admin-recovery
+    // to run this one
+    it("Hands over the admin role once the timelock expires", async function () {
+      const successor = await fundedWallet();
+      await begin(successor.publicKey);
+      const eta = (await program.account.globalConfig.fetch(globalConfigPda)).recoveryEta.toNumber();
+      if (eta - Date.now() / 1000 > 60) {
+        await cancel();
+        this.skip();
+      }
+      await sleep(Math.max(eta * 1000 - Date.now(), 0) + 2000);
+
+      const sig = await finalize();
+      const finalized = eventsNamed(await eventsIn(sig), "AdminRecoveryFinalized");
+      assert.lengthOf(finalized, 1);
+      assert.isTrue(finalized[0].data.oldAdmin.equals(admin.publicKey));
+      const gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.isTrue(gc.admin.equals(successor.publicKey));
+      assert.equal(gc.recoveryEta.toNumber(), 0);
+
+      // Hand the role back for the rest of the suites
+      await program.methods
+        .transferAdmin(admin.publicKey)
+        .accounts({ globalConfig: globalConfigPda, admin: successor.publicKey })
+        .signers([successor])
+        .rpc();
+    });
+  });
+
+  describe("scheduled pauses", () => {
+    async function chainNow(): Promise<number> {
+      const slot = await provider.connection.getSlot("confirmed");
+      return (await provider.connection.getBlockTime(slot))!;
+    }
+
+    function schedule(start: number, end: number) {
+      return program.methods
+        .schedulePause(new anchor.BN(start), new anchor.BN(end))
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function clearSchedule() {
+      return program.methods
+        .clearSchedule()
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    before(async () => {
+      await setConfig(100, 60, 100);
+    });
+
+    after(async () => {
+      await clearSchedule();
+    });
+
+    it("Pauses inside the window and resumes after it on its own", async () => {
+      const client = await newClient();
+      const now = await chainNow();
+      await schedule(now + 3, now + 7);
+      await consumeAs(client);
+
+      await sleep(4000);
+      const { error } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "ProgramPaused");
+      const { error: registerError } = await expectRejection(newClient());
+      assert.include(registerError.message, "ProgramPaused");
+
+      await sleep(5000);
+      await consumeAs(client);
+      const gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.isFalse(isPaused(gc));
+    });
+
+    it("clear_schedule cancels a window in progress", async () => {
+      const client = await newClient();
+      const now = await chainNow();
+      await schedule(now - 1, now + 60);
+      await expectRejection(consumeAs(client));
+
+      await clearSchedule();
+      await consumeAs(client);
+    });
+
+    it("Refuses inverted, past and overlong windows", async () => {
+      const now = await chainNow();
+      for (const [start, end] of [
+        [now + 10, now + 10],
+        [now - 100, now - 50],
+        [now + 10, now + 10 + 24 * 60 * 60 + 1],
+      ]) {
+        const { error } = await expectRejection(schedule(start, end));
+        assert.include(error.message, "InvalidSchedule");
+      }
+    });
+  });
+
+  describe("time-of-day limits", () => {
+    const [schedulePda] = PublicKey.findProgramAddressSync(
+      [seed("SCHEDULE_SEED")],
+      program.programId
+    );
+    const DAY = 24 * 60 * 60;
+
+    async function secondOfDay(): Promise<number> {
+      const slot = await provider.connection.getSlot("confirmed");
+      return (await provider.connection.getBlockTime(slot))! % DAY;
+    }
+
+    function setSchedule(entries: [number, number][]) {
+      return program.methods
+        .setSchedule(
+          entries.map(([start, max]) => ({ startSecondOfDay: start, maxRequests: new anchor.BN(max) }))
+        )
+        .accounts({
+          globalConfig: globalConfigPda,
+          schedule: schedulePda,
+          admin: admin.publicKey,
+          payer: admin.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function consumeScheduled(client: Keypair, schedule: PublicKey | null = schedulePda) {
+      return program.methods
+        .consumeRequest()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          client: client.publicKey,
+          schedule,
+        })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    async function expectLimit(client: Keypair, max: number) {
+      for (let i = 0; i < max; i++) {
+        await consumeScheduled(client);
+      }
+      const { error } = await expectRejection(consumeScheduled(client));
+      assert.include(error.message, "RateLimitExceeded");
+    }
+
+    before(async () => {
+      await setConfig(100, 60, 100);
+    });
+
+    after(async () => {
+      await setSchedule([]);
+    });
+
+    it("Takes max_requests from the entry that started last today", async function () {
+      const now = await secondOfDay();
+      // Needs room on both sides of the current second
+      if (now < 120 || now > DAY - 120) this.skip();
+      await setSchedule([
+        [0, 50],
+        [now - 60, 2],
+        [now + 60, 50],
+      ]);
+      await expectLimit(await newClient(), 2);
+    });
+
+    it("Carries the previous day's last entry past midnight", async function () {
+      const now = await secondOfDay();
+      if (now > DAY - 240) this.skip();
+      // Both entries start later today, so yesterday's last one is running
+      await setSchedule([
+        [now + 120, 50],
+        [now + 180, 3],
+      ]);
+      await expectLimit(await newClient(), 3);
+    });
+
+    it("Needs the schedule account while a schedule is set", async () => {
+      await setSchedule([[0, 2]]);
+      const { error } = await expectRejection(consumeScheduled(await newClient(), null));
+      assert.include(error.message, "ScheduleAccountMissing");
+    });
+
+    it("Falls back to the config once the schedule is cleared", async () => {
+      await setSchedule([[0, 2]]);
+      await setSchedule([]);
+      const client = await newClient();
+      for (let i = 0; i < 5; i++) {
+        await consumeScheduled(client, null);
+      }
+      const gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.equal(gc.flags & (1 << 5), 0);
+    });
+
+    it("Refuses unsorted, out-of-range and empty-limit entries", async () => {
+      for (const entries of [
+        [[600, 5], [300, 5]],
+        [[300, 5], [300, 6]],
+        [[DAY, 5]],
+        [[0, 0]],
+      ] as [number, number][][]) {
+        const { error } = await expectRejection(setSchedule(entries));
+        assert.include(error.message, "InvalidLimitSchedule");
+      }
+    });
+  });
+
+  describe("circuit breaker", () => {
+    function setBreaker(threshold: number, windowSeconds: number) {
+      return program.methods
+        .setCircuitBreaker(new anchor.BN(threshold), new anchor.BN(windowSeconds))
+        .accounts({ globalConfig: globalConfigPda, globalStats: globalStatsPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    // The breaker's count and trip are in the stats, which consumes pass while it is on
+    function gate(client: Keypair) {
+      return program.methods
+        .gateCheck()
+        .accounts({
+          globalConfig: globalConfigPda,
+          globalStats: globalStatsPda,
+          clientBucket: bucketFor(client.publicKey),
+          client: client.publicKey,
+        })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function consumeWithStats(client: Keypair, globalStats: PublicKey | null) {
+      return program.methods
+        .consumeRequest()
+        .accounts({
+          globalConfig: globalConfigPda,
+          globalStats,
+          clientBucket: bucketFor(client.publicKey),
+          client: client.publicKey,
+        })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    before(async () => {
+      await setConfig(1, 60, 1);
+    });
+
+    after(async () => {
+      await setBreaker(0, 0);
+    });
+
+    it("Refuses consumes once denials pass the threshold", async () => {
+      await setBreaker(2, 60);
+      const client = await newClient();
+      const bystander = await newClient();
+      await gate(client);
+      // Two denials sit at the threshold
+      await gate(client);
+      await gate(client);
+      await consumeWithStats(bystander, globalStatsPda);
+
+      const sig = await gate(client);
+      const tripped = eventsNamed(await eventsIn(sig), "CircuitTripped");
+      assert.lengthOf(tripped, 1);
+      assert.equal(tripped[0].data.rejections.toNumber(), 3);
+      // The config is not touched; the trip is the count left in the stats
+      assert.isFalse(isPaused(await program.account.globalConfig.fetch(globalConfigPda)));
+      const stats = await program.account.globalStats.fetch(globalStatsPda);
+      assert.equal(stats.rejectionCount.toNumber(), 3);
+
+      // Refused before the bystander's own limit is looked at
+      const { error } = await expectRejection(consumeWithStats(bystander, globalStatsPda));
+      assert.include(error.message, "CircuitBreakerTripped");
+      // Leaving the stats out does not get around it
+      const { error: missing } = await expectRejection(consumeWithStats(bystander, null));
+      assert.include(missing.message, "StatsAccountMissing");
+    });
+
+    it("Stays tripped until the admin resets it", async () => {
+      await setBreaker(2, 60);
+      const stats = await program.account.globalStats.fetch(globalStatsPda);
+      assert.equal(stats.rejectionCount.toNumber(), 0);
+
+      const client = await newClient();
+      await consumeWithStats(client, globalStatsPda);
+    });
+
+    it("A zero threshold turns it off", async () => {
+      await setBreaker(0, 60);
+      const client = await newClient();
+      for (let i = 0; i < 5; i++) {
+        await gate(client);
+      }
+      const stats = await program.account.globalStats.fetch(globalStatsPda);
+      assert.equal(stats.rejectionCount.toNumber(), 0);
+      // Nor do consumes need the stats any more
+      await consumeWithStats(await newClient(), null);
+    });
+  });
+
+  describe("registration policy", () => {
+    const FEE = 5_000;
+    let treasury: Keypair;
+
+    function setPolicy(fee: number, cooldownSeconds: number, treasuryKey: PublicKey) {
+      return program.methods
+        .setRegistrationPolicy(new anchor.BN(fee), new anchor.BN(cooldownSeconds), treasuryKey)
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    // The client only signs; the payer covers rent and the fee
+    function register(client: Keypair, payer: Keypair, treasuryKey: PublicKey | null = treasury.publicKey) {
+      return program.methods
+        .registerClient()
+        .accounts({ globalConfig: globalConfigPda, client: client.publicKey, payer: payer.publicKey, treasury: treasuryKey })
+        .signers([client, payer])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    before(async () => {
+      // Funded so the fee transfers keep it rent-exempt
+      treasury = await fundedWallet();
+      await setPolicy(FEE, 60, treasury.publicKey);
+    });
+
+    after(async () => {
+      await setPolicy(0, 0, PublicKey.default);
+    });
+
+    it("Sends the fee to the treasury", async () => {
+      const payer = await fundedWallet();
+      const before = await provider.connection.getBalance(treasury.publicKey, "confirmed");
+      await register(Keypair.generate(), payer);
+      const after = await provider.connection.getBalance(treasury.publicKey, "confirmed");
+      assert.equal(after - before, FEE);
+    });
+
+    it("Holds the same payer to the cooldown, but not other payers", async () => {
+      const payer = await fundedWallet();
+      await register(Keypair.generate(), payer);
+      const { error } = await expectRejection(register(Keypair.generate(), payer));
+      assert.include(error.message, "RegistrationCooldown");
+
+      await register(Keypair.generate(), await fundedWallet());
+    });
+
+    it("Needs the treasury while a fee is set", async () => {
+      const { error } = await expectRejection(register(Keypair.generate(), await fundedWallet(), null));
+      assert.include(error.message, "InvalidTreasury");
+      const { error: wrong } = await expectRejection(
+        register(Keypair.generate(), await fundedWallet(), Keypair.generate().publicKey)
+      );
+      assert.include(wrong.message, "InvalidTreasury");
+    });
+
+    it("Refuses a fee without a treasury", async () => {
+      const { error } = await expectRejection(setPolicy(FEE, 0, PublicKey.default));
+      assert.include(error.message, "InvalidTreasury");
+    });
+  });
+
+  describe("transfer_bucket", () => {
+    function transfer(owner: Keypair, newWallet: Keypair) {
+      return program.methods
+        .transferBucket()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(owner.publicKey),
+          newBucket: bucketFor(newWallet.publicKey),
+          owner: owner.publicKey,
+          newWallet: newWallet.publicKey,
+          payer: owner.publicKey,
+        })
+        .signers([owner, newWallet])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    before(async () => {
+      await setConfig(100, 60, 100);
+    });
+
+    it("Carries the bucket's fields to the new wallet and closes the old one", async () => {
+      const owner = await newClient();
+      for (let i = 0; i < 3; i++) {
+        await consumeAs(owner);
+      }
+      const original = await program.account.clientBucket.fetch(bucketFor(owner.publicKey));
+
+      const successor = Keypair.generate();
+      const sig = await transfer(owner, successor);
+      const moved = await program.account.clientBucket.fetch(bucketFor(successor.publicKey));
+      assert.isTrue(moved.owner.equals(successor.publicKey));
+      assert.equal(moved.totalRequests.toNumber(), 3);
+      assert.equal(moved.requestCount.toNumber(), original.requestCount.toNumber());
+      assert.equal(moved.windowStart.toNumber(), original.windowStart.toNumber());
+      assert.equal(moved.violations.toNumber(), original.violations.toNumber());
+      assert.lengthOf(eventsNamed(await eventsIn(sig), "BucketTransferred"), 1);
+
+      assert.isNull(await provider.connection.getAccountInfo(bucketFor(owner.publicKey), "confirmed"));
+      await consumeAs(successor);
+      const after = await program.account.clientBucket.fetch(bucketFor(successor.publicKey));
+      assert.equal(after.totalRequests.toNumber(), 4);
+    });
+
+    it("Refuses a blocked owner", async () => {
+      const owner = await newClient();
+      await program.methods
+        .blockClient()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(owner.publicKey),
+          admin: admin.publicKey,
+          clientWallet: owner.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      const { error } = await expectRejection(transfer(owner, Keypair.generate()));
+      assert.include(error.message, "ClientBlocked");
+    });
+
+    it("Refuses a new wallet that already has a bucket", async () => {
+      const owner = await newClient();
+      const taken = await newClient();
+      await expectRejection(transfer(owner, taken));
+      const bucket = await program.account.clientBucket.fetch(bucketFor(owner.publicKey));
+      assert.isTrue(bucket.owner.equals(owner.publicKey));
+    });
+  });
+
+  describe("client notes", () => {
+    const noteFor = (wallet: PublicKey) =>
+      PublicKey.findProgramAddressSync([seed("CLIENT_NOTE_SEED"), wallet.toBuffer()], program.programId)[0];
+
+    function setNote(wallet: PublicKey, note: string) {
+      return program.methods
+        .setClientNote(note)
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey, payer: admin.publicKey, clientWallet: wallet })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function clearNote(wallet: PublicKey) {
+      return program.methods
+        .clearClientNote()
+        .accounts({ globalConfig: globalConfigPda, clientNote: noteFor(wallet), admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    before(async () => {
+      await setConfig(2, 60, 2);
+    });
+
+    it("Sets, overwrites and clears a note", async () => {
+      const client = await newClient();
+      await setNote(client.publicKey, "contacted 2024-05-01 re abuse");
+      let entry = await program.account.clientNote.fetch(noteFor(client.publicKey));
+      assert.equal(entry.note, "contacted 2024-05-01 re abuse");
+      assert.isTrue(entry.updatedBy.equals(admin.publicKey));
+
+      await setNote(client.publicKey, "resolved");
+      entry = await program.account.clientNote.fetch(noteFor(client.publicKey));
+      assert.equal(entry.note, "resolved");
+
+      await clearNote(client.publicKey);
+      assert.isNull(await provider.connection.getAccountInfo(noteFor(client.publicKey), "confirmed"));
+    });
+
+    it("Refuses notes over 64 bytes", async () => {
+      const client = await newClient();
+      const { error } = await expectRejection(setNote(client.publicKey, "x".repeat(65)));
+      assert.include(error.message, "InvalidNote");
+      // Multi-byte characters count by their bytes
+      const { error: wide } = await expectRejection(setNote(client.publicKey, "€".repeat(22)));
+      assert.include(wide.message, "InvalidNote");
+    });
+
+    it("Leaves the consume path alone", async () => {
+      const noted = await newClient();
+      const plain = await newClient();
+      await setNote(noted.publicKey, "watch this one");
+      for (const client of [noted, plain]) {
+        await consumeAs(client);
+        await consumeAs(client);
+        const { error } = await expectRejection(consumeAs(client));
+        assert.include(error.message, "RateLimitExceeded");
+      }
+      const a = await program.account.clientBucket.fetch(bucketFor(noted.publicKey));
+      const b = await program.account.clientBucket.fetch(bucketFor(plain.publicKey));
+      assert.equal(a.requestCount.toNumber(), b.requestCount.toNumber());
+      assert.equal(a.flags, b.flags);
+    });
+  });
+
+  describe("bucket labels", () => {
+    function setLabel(client: Keypair, label: string) {
+      return program.methods
+        .setLabel(label)
+        .accounts({ globalConfig: globalConfigPda, clientBucket: bucketFor(client.publicKey), owner: client.publicKey })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function quota(client: Keypair) {
+      return program.methods
+        .getQuota()
+        .accounts({ globalConfig: globalConfigPda, clientBucket: bucketFor(client.publicKey), schedule: null })
+        .view();
+    }
+
+    before(async () => {
+      await setConfig(3, 60, 3);
+    });
+
+    it("Sets a label and reads it back through get_quota", async () => {
+      const client = await newClient();
+      await setLabel(client, "indexer-eu");
+      await consumeAs(client);
+      const result = await quota(client);
+      assert.equal(result.label, "indexer-eu");
+      assert.isTrue(result.owner.equals(client.publicKey));
+      assert.equal(result.remaining.toNumber(), 2);
+      assert.equal(result.totalRequests.toNumber(), 1);
+
+      // Padding is trimmed, and an empty label clears it
+      await setLabel(client, "eu");
+      assert.equal((await quota(client)).label, "eu");
+      await setLabel(client, "");
+      assert.equal((await quota(client)).label, "");
+    });
+
+    it("Refuses labels over 32 bytes and other wallets' buckets", async () => {
+      const client = await newClient();
+      const { error } = await expectRejection(setLabel(client, "x".repeat(33)));
+      assert.include(error.message, "InvalidLabel");
+      const { error: wide } = await expectRejection(setLabel(client, "€".repeat(11)));
+      assert.include(wide.message, "InvalidLabel");
+
+      const other = await fundedWallet();
+      const { error: foreign } = await expectRejection(
+        program.methods
+          .setLabel("mine now")
+          .accounts({ globalConfig: globalConfigPda, clientBucket: bucketFor(client.publicKey), owner: other.publicKey })
+          .signers([other])
+          .rpc()
+      );
+      assert.include(foreign.message, "ConstraintSeeds");
+    });
+
+    it("Carries the label in rejection events", async () => {
+      const client = await newClient();
+      await setLabel(client, "bot-7");
+      for (let i = 0; i < 3; i++) await consumeAs(client);
+      const { events } = await expectRejection(consumeAs(client));
+      assert.equal(eventsNamed(events, "ConsumeRejected")[0].data.label, "bot-7");
+    });
+
+    it("Survives an admin reset", async () => {
+      const client = await newClient();
+      await setLabel(client, "keep me");
+      await consumeAs(client);
+      await program.methods
+        .resetClient()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          admin: admin.publicKey,
+          clientWallet: client.publicKey,
+        })
+        .rpc();
+      const result = await quota(client);
+      assert.equal(result.label, "keep me");
+      assert.equal(result.remaining.toNumber(), 3);
+    });
+  });
+
+  describe("bucket event tags", () => {
+    function tag(text: string): number[] {
+      const bytes = Array.from(Buffer.from(text));
+      return bytes.concat(new Array(16 - bytes.length).fill(0));
+    }
+
+    function setEventTag(client: Keypair, eventTag: number[]) {
+      return program.methods
+        .setEventTag(eventTag)
+        .accounts({ globalConfig: globalConfigPda, clientBucket: bucketFor(client.publicKey), owner: client.publicKey })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function setPolicy(setOnce: boolean) {
+      return program.methods
+        .setEventTagPolicy(setOnce)
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    before(async () => {
+      await setConfig(3, 60, 3);
+    });
+
+    after(async () => {
+      await setPolicy(false);
+    });
+
+    it("Copies the tag into consume, rejection and block events", async () => {
+      const client = await newClient();
+      await setEventTag(client, tag("tenant-a"));
+      const consumed = eventsNamed(await eventsIn(await consumeAs(client)), "BucketConsumed");
+      assert.deepEqual(consumed[0].data.eventTag, tag("tenant-a"));
+      assert.equal(consumed[0].data.requestCount.toNumber(), 1);
+
+      // Changing it mid-stream shows up in the very next event
+      await setEventTag(client, tag("tenant-b"));
+      for (let i = 0; i < 2; i++) await consumeAs(client);
+      const { events } = await expectRejection(consumeAs(client));
+      assert.deepEqual(eventsNamed(events, "ConsumeRejected")[0].data.eventTag, tag("tenant-b"));
+
+      const sig = await program.methods
+        .blockClient()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          admin: admin.publicKey,
+          clientWallet: client.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+      assert.deepEqual(eventsNamed(await eventsIn(sig), "BucketBlocked")[0].data.eventTag, tag("tenant-b"));
+    });
+
+    it("Leaves untagged consumes without a BucketConsumed event", async () => {
+      const client = await newClient();
+      assert.isEmpty(eventsNamed(await eventsIn(await consumeAs(client)), "BucketConsumed"));
+    });
+
+    it("Holds tags to the first one set under the set-once policy", async () => {
+      await setPolicy(true);
+      const client = await newClient();
+      await setEventTag(client, tag("first"));
+      const { error } = await expectRejection(setEventTag(client, tag("second")));
+      assert.include(error.message, "EventTagLocked");
+      const { error: cleared } = await expectRejection(setEventTag(client, tag("")));
+      assert.include(cleared.message, "EventTagLocked");
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.deepEqual(bucket.eventTag, tag("first"));
+
+      await setPolicy(false);
+      await setEventTag(client, tag("second"));
+    });
+  });
+
+  describe("stale-bucket collection", () => {
+    const STALE_AFTER = 2;
+    const BOUNTY_BPS = 1_000;
+
+    function setGcPolicy(staleAfterSeconds: number, bountyBps: number) {
+      return program.methods
+        .setGcPolicy(new anchor.BN(staleAfterSeconds), bountyBps)
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function collect(client: PublicKey, cranker: Keypair) {
+      return program.methods
+        .gcBucket()
+        .accounts({ globalConfig: globalConfigPda, clientBucket: bucketFor(client), owner: client, cranker: cranker.publicKey })
+        .signers([cranker])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    const balance = (key: PublicKey) => provider.connection.getBalance(key, "confirmed");
+
+    before(async () => {
+      await setGcPolicy(STALE_AFTER, BOUNTY_BPS);
+    });
+
+    after(async () => {
+      await setGcPolicy(0, 0);
+    });
+
+    it("Leaves a fresh bucket alone", async () => {
+      const client = await newClient();
+      const { error } = await expectRejection(collect(client.publicKey, await fundedWallet()));
+      assert.include(error.message, "BucketNotStale");
+    });
+
+    it("Splits a stale bucket's rent between cranker and owner, and allows re-registering", async () => {
+      const client = await newClient();
+      const blocked = await newClient();
+      await program.methods
+        .blockClient()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(blocked.publicKey),
+          admin: admin.publicKey,
+          clientWallet: blocked.publicKey,
+        })
+        .rpc();
+      await sleep((STALE_AFTER + 1) * 1000);
+
+      const cranker = await fundedWallet();
+      const rent = await balance(bucketFor(client.publicKey));
+      const ownerBefore = await balance(client.publicKey);
+      const crankerBefore = await balance(cranker.publicKey);
+      const { totalClients } = await program.account.globalStats.fetch(globalStatsPda);
+      await collect(client.publicKey, cranker);
+
+      const bounty = Math.floor((rent * BOUNTY_BPS) / 10_000);
+      assert.equal((await balance(cranker.publicKey)) - crankerBefore, bounty);
+      assert.equal((await balance(client.publicKey)) - ownerBefore, rent - bounty);
+      assert.isNull(await provider.connection.getAccountInfo(bucketFor(client.publicKey), "confirmed"));
+      const stats = await program.account.globalStats.fetch(globalStatsPda);
+      assert.equal(stats.totalClients.toNumber(), totalClients.toNumber() - 1);
+
+      // The block outlives any idle time
+      const { error } = await expectRejection(collect(blocked.publicKey, cranker));
+      assert.include(error.message, "ClientBlocked");
+
+      await program.methods
+        .registerClient()
+        .accounts({ globalConfig: globalConfigPda, client: client.publicKey, payer: client.publicKey })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.totalRequests.toNumber(), 0);
+    });
+
+    it("Refuses a bounty over 100%", async () => {
+      const { error } = await expectRejection(setGcPolicy(STALE_AFTER, 10_001));
+      assert.include(error.message, "InvalidConfig");
+    });
+  });
+
+  describe("sweeping stray lamports", () => {
+    const STRAY = 100_000;
+    let treasury: Keypair;
+
+    function setTreasury(key: PublicKey) {
+      return program.methods
+        .setRegistrationPolicy(new anchor.BN(0), new anchor.BN(0), key)
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function sweep(accounts: PublicKey[]) {
+      return program.methods
+        .sweepExcessLamports()
+        .accounts({ globalConfig: globalConfigPda, treasury: treasury.publicKey, admin: admin.publicKey })
+        .remainingAccounts(accounts.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })))
+        .rpc({ commitment: "confirmed" });
+    }
+
+    async function excess(key: PublicKey): Promise<number> {
+      const info = await provider.connection.getAccountInfo(key, "confirmed");
+      const minimum = await provider.connection.getMinimumBalanceForRentExemption(info!.data.length);
+      return info!.lamports - minimum;
+    }
+
+    before(async () => {
+      treasury = await fundedWallet();
+      await setTreasury(treasury.publicKey);
+    });
+
+    after(async () => {
+      await setTreasury(PublicKey.default);
+    });
+
+    it("Moves only what sits above rent exemption to the treasury", async () => {
+      const client = await newClient();
+      const bucket = bucketFor(client.publicKey);
+      for (const target of [globalConfigPda, bucket]) {
+        await provider.sendAndConfirm(
+          new Transaction().add(
+            SystemProgram.transfer({ fromPubkey: admin.publicKey, toPubkey: target, lamports: STRAY })
+          ),
+          [],
+          { commitment: "confirmed" }
+        );
+      }
+      const expected = (await excess(globalConfigPda)) + (await excess(bucket));
+      assert.isAtLeast(expected, 2 * STRAY);
+
+      const before = await provider.connection.getBalance(treasury.publicKey, "confirmed");
+      await sweep([globalConfigPda, bucket]);
+      const after = await provider.connection.getBalance(treasury.publicKey, "confirmed");
+      assert.equal(after - before, expected);
+      assert.equal(await excess(globalConfigPda), 0);
+      assert.equal(await excess(bucket), 0);
+
+      // The accounts still load, and a second sweep finds nothing
+      assert.isTrue((await program.account.clientBucket.fetch(bucket)).owner.equals(client.publicKey));
+      await sweep([globalConfigPda, bucket]);
+      assert.equal(await provider.connection.getBalance(treasury.publicKey, "confirmed"), after);
+    });
+
+    it("Refuses accounts the program does not own", async () => {
+      const { error } = await expectRejection(sweep([(await fundedWallet()).publicKey]));
+      assert.include(error.message, "SweepTargetNotOwned");
+    });
+
+    it("Is admin-only", async () => {
+      const outsider = await fundedWallet();
+      const { error } = await expectRejection(
+        program.methods
+          .sweepExcessLamports()
+          .accounts({ globalConfig: globalConfigPda, treasury: treasury.publicKey, admin: outsider.publicKey })
+          .remainingAccounts([{ pubkey: globalConfigPda, isSigner: false, isWritable: true }])
+          .signers([outsider])
+          .rpc()
+      );
+      assert.include(error.message, "Unauthorized");
+    });
+  });
+
+  describe("estimate_config_impact", () => {
+    function estimate(maxRequests: number, buckets: PublicKey[]) {
+      return program.methods
+        .estimateConfigImpact({
+          maxRequests: new anchor.BN(maxRequests),
+          windowSeconds: new anchor.BN(60),
+          burstLimit: new anchor.BN(Math.max(maxRequests, 1)),
+        })
+        .accounts({ globalConfig: globalConfigPda })
+        .remainingAccounts(buckets.map((pubkey) => ({ pubkey, isSigner: false, isWritable: false })))
+        .view();
+    }
+
+    let buckets: PublicKey[] = [];
+
+    before(async () => {
+      await setConfig(10, 60, 10);
+      for (const uses of [2, 5, 8]) {
+        const client = await newClient();
+        for (let i = 0; i < uses; i++) await consumeAs(client);
+        buckets.push(bucketFor(client.publicKey));
+      }
+    });
+
+    it("Counts the buckets whose last window exceeds the proposed max", async () => {
+      let impact = await estimate(4, buckets);
+      assert.equal(impact.evaluated.toNumber(), 3);
+      assert.equal(impact.wouldReject.toNumber(), 2);
+
+      impact = await estimate(5, buckets);
+      assert.equal(impact.wouldReject.toNumber(), 1);
+      impact = await estimate(8, buckets);
+      assert.equal(impact.wouldReject.toNumber(), 0);
+    });
+
+    it("Changes nothing on the buckets or the config", async () => {
+      const before = await Promise.all(buckets.map((b) => program.account.clientBucket.fetch(b)));
+      await estimate(1, buckets);
+      const after = await Promise.all(buckets.map((b) => program.account.clientBucket.fetch(b)));
+      assert.deepEqual(
+        after.map((b) => b.requestCount.toNumber()),
+        before.map((b) => b.requestCount.toNumber())
+      );
+      const gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.equal(gc.maxRequests.toNumber(), 10);
+    });
+
+    it("Refuses an invalid proposal and accounts that are not buckets", async () => {
+      const { error } = await expectRejection(estimate(0, buckets));
+      assert.include(error.message, "InvalidConfig");
+      const { error: notBucket } = await expectRejection(estimate(4, [globalConfigPda]));
+      assert.include(notBucket.message, "AccountDiscriminatorMismatch");
+    });
+  });
+
+  describe("priority consumes", () => {
+    const BURST = 1;
+    const WINDOW = 0;
+
+    function consumePriority(client: Keypair, priority: boolean) {
+      return program.methods
+        .consumePriority(priority)
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          client: client.publicKey,
+        })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    async function sourceOf(sig: string): Promise<number> {
+      return eventsNamed(await eventsIn(sig), "PriorityConsumed")[0].data.source;
+    }
+
+    before(async () => {
+      await setConfig(3, 6, 6);
+    });
+
+    after(async () => {
+      await setConfig(10, 120, 15);
+    });
+
+    it("Pays from banked credit first and falls back to the window", async () => {
+      const client = await newClient();
+      // Let the registration window close idle, banking 3 credits
+      await sleep(6500);
+
+      assert.equal(await sourceOf(await consumePriority(client, true)), BURST);
+      assert.equal(await sourceOf(await consumePriority(client, true)), BURST);
+      let bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.burstCredits.toNumber(), 1);
+      assert.equal(bucket.priorityCount.toNumber(), 2);
+      assert.equal(bucket.requestCount.toNumber(), 2);
+
+      // Unflagged, the same instruction draws on the window allowance
+      assert.equal(await sourceOf(await consumePriority(client, false)), WINDOW);
+      assert.equal(await sourceOf(await consumePriority(client, true)), BURST);
+      // Credits are gone, so priority falls back to the allowance
+      assert.equal(await sourceOf(await consumePriority(client, true)), WINDOW);
+
+      bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.burstCredits.toNumber(), 0);
+      assert.equal(bucket.priorityCount.toNumber(), 3);
+      assert.equal(bucket.requestCount.toNumber(), 5);
+    });
+
+    it("Mixed traffic adds up across both pools", async () => {
+      const client = await newClient();
+      await sleep(6500);
+
+      await consumePriority(client, true);
+      await consumeAs(client);
+      await consumePriority(client, true);
+      await consumeAs(client);
+      await consumeAs(client);
+      let bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      // 2 priority units on credit and 3 steady units fill the window allowance
+      assert.equal(bucket.priorityCount.toNumber(), 2);
+      assert.equal(bucket.requestCount.toNumber(), 5);
+      assert.equal(bucket.totalRequests.toNumber(), 5);
+
+      // The last credit goes above max; the one after reaches nothing
+      await consumeAs(client);
+      const { error } = await expectRejection(consumePriority(client, true));
+      assert.include(error.message, "RateLimitExceeded");
+      bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.burstCredits.toNumber(), 0);
+      assert.equal(bucket.requestCount.toNumber(), 6);
+      assert.equal(bucket.totalRequests.toNumber(), 6);
+    });
+  });
+
+  describe("bulk discounts", () => {
+    const tier = (minAmount: number, discountBps: number) => ({ minAmount: new anchor.BN(minAmount), discountBps });
+
+    function setTiers(tiers: { minAmount: anchor.BN; discountBps: number }[]) {
+      return program.methods
+        .setDiscountTiers(tiers)
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc();
+    }
+
+    // Consumes `amount` on a fresh client and returns what its window was charged
+    // and what its lifetime total recorded
+    async function chargeFor(amount: number): Promise<[number, number]> {
+      const client = await newClient();
+      await program.methods
+        .consumeAmount(new anchor.BN(amount))
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          client: client.publicKey,
+        })
+        .signers([client])
+        .rpc();
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      return [bucket.requestCount.toNumber(), bucket.totalRequests.toNumber()];
+    }
+
+    before(async () => {
+      await setConfig(200, 120, 200);
+      await setTiers([tier(10, 500), tier(100, 1_000)]);
+    });
+
+    after(async () => {
+      await setTiers([]);
+      await setConfig(10, 120, 15);
+    });
+
+    it("Charges each tier from its min_amount and totals the real amount", async () => {
+      assert.deepEqual(await chargeFor(9), [9, 9]);
+      // 5% of 10 rounds down to nothing
+      assert.deepEqual(await chargeFor(10), [10, 10]);
+      assert.deepEqual(await chargeFor(99), [95, 99]);
+      assert.deepEqual(await chargeFor(100), [90, 100]);
+      assert.deepEqual(await chargeFor(200), [180, 200]);
+    });
+
+    it("Leaves single consumes at full price", async () => {
+      const client = await newClient();
+      await consumeAs(client);
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.requestCount.toNumber(), 1);
+    });
+
+    it("Refuses unsorted tiers and free batches", async () => {
+      const { error } = await expectRejection(setTiers([tier(100, 1_000), tier(10, 500)]));
+      assert.include(error.message, "InvalidDiscountTiers");
+      const { error: free } = await expectRejection(setTiers([tier(10, 10_000)]));
+      assert.include(free.message, "InvalidDiscountTiers");
+      const gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.equal(gc.discountTierCount, 2);
+    });
+  });
+
+  describe("appeals", () => {
+    const BOND = 1_000_000;
+    let treasury: Keypair;
+
+    const appealFor = (wallet: PublicKey) =>
+      PublicKey.findProgramAddressSync([seed("APPEAL_SEED"), wallet.toBuffer()], program.programId)[0];
+
+    // The treasury comes first, since a bond needs one
+    async function setPolicies(treasuryKey: PublicKey, bond: number, cooldown: number) {
+      await program.methods
+        .setRegistrationPolicy(new anchor.BN(0), new anchor.BN(0), treasuryKey)
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc();
+      await program.methods
+        .setAppealPolicy(new anchor.BN(bond), new anchor.BN(cooldown))
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc();
+    }
+
+    function block(client: Keypair) {
+      return program.methods
+        .blockClient()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          admin: admin.publicKey,
+          clientWallet: client.publicKey,
+        })
+        .rpc();
+    }
+
+    function fileAppeal(client: Keypair) {
+      return program.methods
+        .fileAppeal()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          appeal: appealFor(client.publicKey),
+          client: client.publicKey,
+        })
+        .signers([client])
+        .rpc();
+    }
+
+    function approve(client: Keypair) {
+      return program.methods
+        .approveAppeal()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          appeal: appealFor(client.publicKey),
+          admin: admin.publicKey,
+          clientWallet: client.publicKey,
+        })
+        .rpc();
+    }
+
+    function deny(client: Keypair) {
+      return program.methods
+        .denyAppeal()
+        .accounts({
+          globalConfig: globalConfigPda,
+          appeal: appealFor(client.publicKey),
+          treasury: treasury.publicKey,
+          admin: admin.publicKey,
+          clientWallet: client.publicKey,
+        })
+        .rpc();
+    }
+
+    before(async () => {
+      treasury = await fundedWallet();
+      await setPolicies(treasury.publicKey, BOND, 2);
+    });
+
+    after(async () => {
+      await setPolicies(PublicKey.default, 0, 0);
+    });
+
+    it("Only a blocked client can appeal", async () => {
+      const client = await newClient();
+      const { error } = await expectRejection(fileAppeal(client));
+      assert.include(error.message, "ClientNotBlocked");
+    });
+
+    it("Approval unblocks the client and refunds the bond", async () => {
+      const client = await newClient();
+      await block(client);
+      const before = await provider.connection.getBalance(client.publicKey);
+
+      await fileAppeal(client);
+      const appeal = await program.account.appeal.fetch(appealFor(client.publicKey));
+      assert.equal(appeal.bondLamports.toNumber(), BOND);
+      assert.isAtMost(await provider.connection.getBalance(client.publicKey), before - BOND);
+      const { error } = await expectRejection(fileAppeal(client));
+      assert.include(error.message, "AppealAlreadyOpen");
+
+      await approve(client);
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.isFalse(isBlocked(bucket));
+      assert.isNull(await provider.connection.getAccountInfo(appealFor(client.publicKey)));
+      // Bond and rent both come back
+      assert.equal(await provider.connection.getBalance(client.publicKey), before);
+    });
+
+    it("Denial forfeits the bond and holds off the next appeal", async () => {
+      const client = await newClient();
+      await block(client);
+      await fileAppeal(client);
+
+      const before = await provider.connection.getBalance(treasury.publicKey);
+      await deny(client);
+      assert.equal(await provider.connection.getBalance(treasury.publicKey), before + BOND);
+      assert.isTrue(isBlocked(await program.account.clientBucket.fetch(bucketFor(client.publicKey))));
+      const appeal = await program.account.appeal.fetch(appealFor(client.publicKey));
+      assert.equal(appeal.filedAt.toNumber(), 0);
+      assert.isAbove(appeal.deniedAt.toNumber(), 0);
+
+      const { error } = await expectRejection(fileAppeal(client));
+      assert.include(error.message, "AppealCooldown");
+      const { error: closed } = await expectRejection(deny(client));
+      assert.include(closed.message, "NoAppealOpen");
+
+      await sleep(2500);
+      await fileAppeal(client);
+      assert.equal((await program.account.appeal.fetch(appealFor(client.publicKey))).bondLamports.toNumber(), BOND);
+    });
+
+    it("The stray-lamport sweep leaves an open bond alone", async () => {
+      const client = await newClient();
+      await block(client);
+      await fileAppeal(client);
+      const appeal = appealFor(client.publicKey);
+      const held = await provider.connection.getBalance(appeal);
+
+      await program.methods
+        .sweepExcessLamports()
+        .accounts({ globalConfig: globalConfigPda, treasury: treasury.publicKey, admin: admin.publicKey })
+        .remainingAccounts([{ pubkey: appeal, isSigner: false, isWritable: true }])
+        .rpc();
+      assert.equal(await provider.connection.getBalance(appeal), held);
+    });
+  });
+
+  describe("quota offers", () => {
+    const offerFor = (wallet: PublicKey) =>
+      PublicKey.findProgramAddressSync([seed("OFFER_SEED"), wallet.toBuffer()], program.programId)[0];
+
+    function offer(seller: Keypair, amount: number, price: number) {
+      return program.methods
+        .offerQuota(new anchor.BN(amount), new anchor.BN(price))
+        .accounts({
+          globalConfig: globalConfigPda,
+          sellerBucket: bucketFor(seller.publicKey),
+          offer: offerFor(seller.publicKey),
+          seller: seller.publicKey,
+        })
+        .signers([seller])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function accept(seller: Keypair, buyer: Keypair) {
+      return program.methods
+        .acceptOffer()
+        .accounts({
+          globalConfig: globalConfigPda,
+          offer: offerFor(seller.publicKey),
+          sellerBucket: bucketFor(seller.publicKey),
+          buyerBucket: bucketFor(buyer.publicKey),
+          seller: seller.publicKey,
+          buyer: buyer.publicKey,
+        })
+        .signers([buyer])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function cancel(seller: Keypair) {
+      return program.methods
+        .cancelOffer()
+        .accounts({
+          globalConfig: globalConfigPda,
+          offer: offerFor(seller.publicKey),
+          sellerBucket: bucketFor(seller.publicKey),
+          seller: seller.publicKey,
+        })
+        .signers([seller])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    // Long enough for the happy path to fit in one window
+    before(async () => {
+      await setConfig(3, 10, 3);
+    });
+
+    after(async () => {
+      await setConfig(10, 120, 15);
+    });
+
+    it("Escrows the seller's headroom and pays them for it", async () => {
+      const seller = await newClient();
+      const buyer = await newClient();
+      await offer(seller, 2, 5_000);
+      let sellerBucket = await program.account.clientBucket.fetch(bucketFor(seller.publicKey));
+      assert.equal(sellerBucket.requestCount.toNumber(), 2);
+      assert.equal(sellerBucket.escrowedCount.toNumber(), 2);
+      // Only one unit is left to the seller while the offer is out
+      await consumeAs(seller);
+      const { error } = await expectRejection(consumeAs(seller));
+      assert.include(error.message, "RateLimitExceeded");
+
+      for (let i = 0; i < 3; i++) await consumeAs(buyer);
+      const sellerBefore = await provider.connection.getBalance(seller.publicKey);
+      const events = await eventsIn(await accept(seller, buyer));
+      const accepted = eventsNamed(events, "OfferAccepted")[0].data;
+      assert.equal(accepted.amount.toNumber(), 2);
+
+      // The price plus the offer's rent
+      const sellerAfter = await provider.connection.getBalance(seller.publicKey);
+      assert.isAtLeast(sellerAfter - sellerBefore, 5_000);
+      assert.isNull(await provider.connection.getAccountInfo(offerFor(seller.publicKey)));
+
+      const buyerBucket = await program.account.clientBucket.fetch(bucketFor(buyer.publicKey));
+      assert.equal(buyerBucket.bonusRequests.toNumber(), 2);
+      await consumeAs(buyer);
+      await consumeAs(buyer);
+      const over = await expectRejection(consumeAs(buyer));
+      assert.include(over.error.message, "RateLimitExceeded");
+    });
+
+    it("Cancelling releases the escrow within the window", async () => {
+      const seller = await newClient();
+      await offer(seller, 3, 1);
+      const events = await eventsIn(await cancel(seller));
+      assert.equal(eventsNamed(events, "OfferCancelled")[0].data.released.toNumber(), 3);
+      const bucket = await program.account.clientBucket.fetch(bucketFor(seller.publicKey));
+      assert.equal(bucket.requestCount.toNumber(), 0);
+      assert.equal(bucket.escrowedCount.toNumber(), 0);
+      await consumeAs(seller);
+    });
+
+    it("Offers lapse with the seller's window", async () => {
+      const seller = await newClient();
+      const buyer = await newClient();
+      await offer(seller, 1, 1);
+      await sleep(10500);
+
+      // Nothing has rolled the seller over, and the offer is still refused
+      const { error } = await expectRejection(accept(seller, buyer));
+      assert.include(error.message, "OfferExpired");
+      const events = await eventsIn(await cancel(seller));
+      assert.equal(eventsNamed(events, "OfferCancelled")[0].data.released.toNumber(), 0);
+    });
+
+    it("Refuses buying from yourself", async () => {
+      const seller = await newClient();
+      await offer(seller, 1, 1);
+      const { error } = await expectRejection(accept(seller, seller));
+      assert.include(error.message, "SelfTrade");
+      await cancel(seller);
+    });
+  });
+
+  describe("freezes", () => {
+    function setFrozen(client: Keypair, frozen: boolean) {
+      const method = frozen ? program.methods.freezeClient() : program.methods.thawClient();
+      return method
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          admin: admin.publicKey,
+          clientWallet: client.publicKey,
+        })
+        .rpc();
+    }
+
+    function block(client: Keypair) {
+      return program.methods
+        .blockClient()
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          admin: admin.publicKey,
+          clientWallet: client.publicKey,
+        })
+        .rpc();
+    }
+
+    function closeBucket(client: Keypair) {
+      return program.methods
+        .closeBucket()
+        .accounts({ clientBucket: bucketFor(client.publicKey), owner: client.publicKey })
+        .signers([client])
+        .rpc();
+    }
+
+    const blockedClients = async () =>
+      (await program.account.globalStats.fetch(globalStatsPda)).blockedClients.toNumber();
+
+    it("Refuses consumes without a block's record, and thaws intact", async () => {
+      const client = await newClient();
+      await consumeAs(client);
+      await consumeAs(client);
+      const blockedBefore = await blockedClients();
+
+      await setFrozen(client, true);
+      const { error, events } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "ClientFrozen");
+      assert.isEmpty(eventsNamed(events, "ConsumeRejected"));
+      let bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.isFalse(isBlocked(bucket));
+      assert.equal(bucket.violations.toNumber(), 0);
+      assert.equal(await blockedClients(), blockedBefore);
+
+      await setFrozen(client, false);
+      await consumeAs(client);
+      bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.requestCount.toNumber(), 3);
+      assert.equal(bucket.totalRequests.toNumber(), 3);
+    });
+
+    it("A block is counted and says so; a freeze is neither", async () => {
+      const frozen = await newClient();
+      const blocked = await newClient();
+      const blockedBefore = await blockedClients();
+      await setFrozen(frozen, true);
+      await block(blocked);
+      assert.equal(await blockedClients(), blockedBefore + 1);
+
+      const { error } = await expectRejection(consumeAs(blocked));
+      assert.include(error.message, "ClientBlocked");
+      const bucket = await program.account.clientBucket.fetch(bucketFor(blocked.publicKey));
+      assert.equal(bucket.violations.toNumber(), 1);
+    });
+
+    it("Frozen clients can close their bucket, blocked ones cannot", async () => {
+      const frozen = await newClient();
+      const blocked = await newClient();
+      await setFrozen(frozen, true);
+      await block(blocked);
+
+      const before = await provider.connection.getBalance(frozen.publicKey);
+      await closeBucket(frozen);
+      assert.isNull(await provider.connection.getAccountInfo(bucketFor(frozen.publicKey)));
+      assert.isAbove(await provider.connection.getBalance(frozen.publicKey), before);
+
+      const { error } = await expectRejection(closeBucket(blocked));
+      assert.include(error.message, "ClientBlocked");
+      assert.isNotNull(await provider.connection.getAccountInfo(bucketFor(blocked.publicKey)));
+    });
+  });
+
+  describe("pause reasons", () => {
+    const INCIDENT = 2;
+
+    function pause(reason: number, autoUnpauseAt = 0) {
+      return program.methods
+        .pause(reason, new anchor.BN(autoUnpauseAt))
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function togglePause() {
+      return program.methods
+        .togglePause()
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    // PauseInfo from the "Program return:" log line: paused_at (i64 LE), reason (u8)
+    function pauseInfoIn(logs: string[]) {
+      const prefix = `Program return: ${program.programId.toBase58()} `;
+      const line = logs.find((l) => l.startsWith(prefix));
+      assert.isDefined(line, "expected return data in the logs");
+      const data = Buffer.from(line!.slice(prefix.length), "base64");
+      return { pausedAt: Number(data.readBigInt64LE(0)), reason: data.readUInt8(8) };
+    }
+
+    it("Records the start and reason, and clears them on unpause", async () => {
+      const client = await newClient();
+      const events = await eventsIn(await pause(INCIDENT));
+      const changed = eventsNamed(events, "PauseChanged")[0].data;
+      assert.isTrue(changed.paused);
+      assert.equal(changed.reason, INCIDENT);
+
+      let gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.isTrue(isPaused(gc));
+      assert.equal(gc.pauseReason, INCIDENT);
+      assert.isAbove(gc.pausedAt.toNumber(), 0);
+      assert.equal(changed.pausedAt.toNumber(), gc.pausedAt.toNumber());
+
+      // A rejected consume carries the same record back
+      const { error } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "ProgramPaused");
+      assert.deepEqual(pauseInfoIn(error.logs ?? []), { pausedAt: gc.pausedAt.toNumber(), reason: INCIDENT });
+
+      await togglePause();
+      gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.isFalse(isPaused(gc));
+      assert.equal(gc.pausedAt.toNumber(), 0);
+      assert.equal(gc.pauseReason, 0);
+      await consumeAs(client);
+    });
+
+    it("Refuses unknown reasons", async () => {
+      const { error } = await expectRejection(pause(9));
+      assert.include(error.message, "InvalidConfig");
+    });
+
+    async function chainTime(): Promise<number> {
+      return (await provider.connection.getBlockTime(await provider.connection.getSlot()))!;
+    }
+
+    it("Lapses at auto_unpause_at with no admin transaction", async () => {
+      const client = await newClient();
+      const deadline = (await chainTime()) + 3;
+      await pause(INCIDENT, deadline);
+      let gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.equal(gc.autoUnpauseAt.toNumber(), deadline);
+      const { error } = await expectRejection(consumeAs(client));
+      assert.include(error.message, "ProgramPaused");
+
+      while ((await chainTime()) < deadline + 1) await sleep(500);
+      // Consumes go through after the deadline but leave the flag to the admin
+      const events = await eventsIn(await consumeAs(client));
+      assert.isEmpty(eventsNamed(events, "PauseChanged"));
+      gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.isTrue(isPaused(gc));
+      assert.equal(gc.autoUnpauseAt.toNumber(), deadline);
+      await newClient();
+
+      // A lapsed pause counts as over, so toggling pauses afresh and then clears
+      await togglePause();
+      await togglePause();
+      gc = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.isFalse(isPaused(gc));
+      assert.equal(gc.autoUnpauseAt.toNumber(), 0);
+    });
+
+    it("Refuses deadlines in the past or too far ahead", async () => {
+      const now = await chainTime();
+      for (const deadline of [now - 10, now + 8 * 24 * 60 * 60]) {
+        const { error } = await expectRejection(pause(INCIDENT, deadline));
+        assert.include(error.message, "InvalidSchedule");
+      }
+    });
+  });
+
+  describe("sponsor pool", () => {
+    const [sponsorPoolPda] = PublicKey.findProgramAddressSync([seed("SPONSOR_POOL_SEED")], program.programId);
+    const balance = (key: PublicKey) => provider.connection.getBalance(key, "confirmed");
+    const BUCKET_FLAG_SPONSORED = 1 << 3;
+
+    function fund(lamports: number) {
+      return program.methods
+        .fundSponsorPool(new anchor.BN(lamports))
+        .accounts({ globalConfig: globalConfigPda, sponsorPool: sponsorPoolPda, admin: admin.publicKey, payer: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function withdraw() {
+      return program.methods
+        .withdrawSponsorPool()
+        .accounts({ globalConfig: globalConfigPda, sponsorPool: sponsorPoolPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function registerSponsored(client: Keypair) {
+      return program.methods
+        .registerSponsored()
+        .accounts({ globalConfig: globalConfigPda, sponsorPool: sponsorPoolPda, client: client.publicKey })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    async function poolRent(): Promise<number> {
+      const info = await provider.connection.getAccountInfo(sponsorPoolPda, "confirmed");
+      return provider.connection.getMinimumBalanceForRentExemption(info!.data.length);
+    }
+
+    it("Funds the pool", async () => {
+      const before = (await provider.connection.getAccountInfo(sponsorPoolPda)) ? await balance(sponsorPoolPda) : 0;
+      const events = await eventsIn(await fund(0.1 * LAMPORTS_PER_SOL));
+      const after = await balance(sponsorPoolPda);
+      assert.isAtLeast(after - before, 0.1 * LAMPORTS_PER_SOL);
+      const funded = eventsNamed(events, "SponsorPoolFunded")[0].data;
+      assert.equal(funded.available.toNumber(), after - (await poolRent()));
+    });
+
+    it("Registers a client with no SOL, debiting exactly the bucket's rent", async () => {
+      const client = Keypair.generate();
+      const rent = await provider.connection.getMinimumBalanceForRentExemption(276);
+      const before = await balance(sponsorPoolPda);
+      const events = await eventsIn(await registerSponsored(client));
+      assert.equal(before - (await balance(sponsorPoolPda)), rent);
+      assert.equal(await balance(bucketFor(client.publicKey)), rent);
+      assert.equal(eventsNamed(events, "ClientSponsored")[0].data.lamports.toNumber(), rent);
+
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.isTrue(bucket.owner.equals(client.publicKey));
+      assert.equal(bucket.flags & BUCKET_FLAG_SPONSORED, BUCKET_FLAG_SPONSORED);
+      await consumeAs(client);
+
+      // Only once per wallet
+      const { error } = await expectRejection(registerSponsored(client));
+      assert.isTrue((error.logs ?? []).some((line: string) => line.includes("already in use")));
+    });
+
+    it("Returns a sponsored bucket's rent to the pool when it closes", async () => {
+      const client = Keypair.generate();
+      await registerSponsored(client);
+      const before = await balance(sponsorPoolPda);
+      const rent = await balance(bucketFor(client.publicKey));
+      await program.methods
+        .closeBucket()
+        .accounts({ clientBucket: bucketFor(client.publicKey), sponsorPool: sponsorPoolPda, owner: client.publicKey })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+      assert.equal((await balance(sponsorPoolPda)) - before, rent);
+      assert.equal(await balance(client.publicKey), 0);
+    });
+
+    it("Withdraws what is left and then refuses to sponsor", async () => {
+      const events = await eventsIn(await withdraw());
+      const withdrawn = eventsNamed(events, "SponsorPoolWithdrawn")[0].data.lamports.toNumber();
+      assert.isAbove(withdrawn, 0);
+      assert.equal(await balance(sponsorPoolPda), await poolRent());
+
+      const { error } = await expectRejection(registerSponsored(Keypair.generate()));
+      assert.include(error.message, "SponsorPoolEmpty");
+    });
+
+    it("Keeps fund and withdraw to the admin", async () => {
+      const outsider = await fundedWallet();
+      const { error } = await expectRejection(
+        program.methods
+          .withdrawSponsorPool()
+          .accounts({ globalConfig: globalConfigPda, sponsorPool: sponsorPoolPda, admin: outsider.publicKey })
+          .signers([outsider])
+          .rpc()
+      );
+      assert.include(error.message, "Unauthorized");
+    });
+  });
+
+  describe("consume_v2", () => {
+    type Args = { amount?: number; deadline?: number; nonce?: number; priority?: boolean };
+
+    // Omitted fields take ConsumeArgs::default()
+    function consumeV2(client: Keypair, args: Args = {}) {
+      return program.methods
+        .consumeV2({
+          amount: new anchor.BN(args.amount ?? 1),
+          deadline: args.deadline === undefined ? null : new anchor.BN(args.deadline),
+          nonce: args.nonce === undefined ? null : new anchor.BN(args.nonce),
+          priority: args.priority ?? false,
+        })
+        .accounts({
+          globalConfig: globalConfigPda,
+          clientBucket: bucketFor(client.publicKey),
+          client: client.publicKey,
+        })
+        .signers([client])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    const bucketOf = (client: Keypair) => program.account.clientBucket.fetch(bucketFor(client.publicKey));
+
+    async function chainTime(): Promise<number> {
+      return (await provider.connection.getBlockTime(await provider.connection.getSlot()))!;
+    }
+
+    before(async () => {
+      await setConfig(4, 20, 6);
+    });
+
+    after(async () => {
+      await setConfig(10, 120, 15);
+    });
+
+    it("Defaults to consume_request and shares the bucket with v1", async () => {
+      const client = await newClient();
+      await consumeAs(client);
+      await consumeV2(client);
+      await consumeAs(client);
+      const bucket = await bucketOf(client);
+      assert.equal(bucket.requestCount.toNumber(), 3);
+      assert.equal(bucket.totalRequests.toNumber(), 3);
+      assert.equal(bucket.lastNonce.toNumber(), 0);
+
+      await consumeV2(client);
+      // The fifth consume is over max_requests whichever instruction makes it
+      let rejected = await expectRejection(consumeV2(client));
+      assert.include(rejected.error.message, "RateLimitExceeded");
+      rejected = await expectRejection(consumeAs(client));
+      assert.include(rejected.error.message, "RateLimitExceeded");
+    });
+
+    it("Consumes an amount, capped at burst_limit", async () => {
+      const client = await newClient();
+      await consumeV2(client, { amount: 3 });
+      assert.equal((await bucketOf(client)).requestCount.toNumber(), 3);
+
+      let rejected = await expectRejection(consumeV2(client, { amount: 7 }));
+      assert.include(rejected.error.message, "AmountExceedsCap");
+      rejected = await expectRejection(consumeV2(client, { amount: 0 }));
+      assert.include(rejected.error.message, "InvalidAmount");
+    });
+
+    it("Refuses a consume past its deadline", async () => {
+      const client = await newClient();
+      const now = await chainTime();
+      const { error } = await expectRejection(consumeV2(client, { deadline: now - 5 }));
+      assert.include(error.message, "DeadlineExceeded");
+      assert.equal((await bucketOf(client)).requestCount.toNumber(), 0);
+
+      await consumeV2(client, { deadline: now + 60 });
+      assert.equal((await bucketOf(client)).requestCount.toNumber(), 1);
+    });
+
+    it("Takes each nonce once, out of order within 64 of the highest", async () => {
+      const client = await newClient();
+      await consumeV2(client, { nonce: 5 });
+      let rejected = await expectRejection(consumeV2(client, { nonce: 5 }));
+      assert.include(rejected.error.message, "NonceAlreadyUsed");
+      await consumeV2(client, { nonce: 4 });
+      let bucket = await bucketOf(client);
+      assert.equal(bucket.lastNonce.toNumber(), 5);
+      assert.equal(bucket.nonceBitmap.toString(2), "11");
+
+      // 70 slides the window past 4 and 5, which are now too old to tell apart
+      await consumeV2(client, { nonce: 70 });
+      rejected = await expectRejection(consumeV2(client, { nonce: 6 }));
+      assert.include(rejected.error.message, "NonceReused");
+      bucket = await bucketOf(client);
+      assert.equal(bucket.lastNonce.toNumber(), 70);
+      assert.equal(bucket.requestCount.toNumber(), 3);
+
+      // A consume refused by the limit does not use up its nonce
+      await consumeV2(client);
+      rejected = await expectRejection(consumeV2(client, { nonce: 69 }));
+      assert.include(rejected.error.message, "RateLimitExceeded");
+      assert.equal((await bucketOf(client)).nonceBitmap.toString(2), "1");
+    });
+
+    it("Pays priority consumes with burst credit, one unit at a time", async () => {
+      const client = await newClient();
+      // Let the registration window close idle, banking a window's worth of credits
+      await sleep(20500);
+      const events = await eventsIn(await consumeV2(client, { priority: true }));
+      assert.equal(eventsNamed(events, "PriorityConsumed")[0].data.source, 1);
+      const bucket = await bucketOf(client);
+      assert.equal(bucket.priorityCount.toNumber(), 1);
+      assert.equal(bucket.burstCredits.toNumber(), 3);
+
+      const { error } = await expectRejection(consumeV2(client, { priority: true, amount: 2 }));
+      assert.include(error.message, "InvalidAmount");
+    });
+
+    it("Combines every option in one consume", async () => {
+      const client = await newClient();
+      const deadline = (await chainTime()) + 60;
+      await consumeV2(client, { amount: 2, deadline, nonce: 1 });
+      await consumeV2(client, { deadline, nonce: 2, priority: true });
+      const bucket = await bucketOf(client);
+      assert.equal(bucket.requestCount.toNumber(), 3);
+      assert.equal(bucket.lastNonce.toNumber(), 2);
+
+      // Every check still applies: a fresh nonce cannot rescue a late consume
+      const { error } = await expectRejection(consumeV2(client, { deadline: deadline - 120, nonce: 3 }));
+      assert.include(error.message, "DeadlineExceeded");
+      assert.equal((await bucketOf(client)).lastNonce.toNumber(), 2);
+    });
+  });
+
+  describe("deployment info", () => {
+    const FEATURE_REGISTRATION_FEE = 1;
+
+    function deploymentInfo() {
+      return program.methods.getDeploymentInfo().accounts({ globalConfig: globalConfigPda }).view();
+    }
+
+    function setFee(fee: number, treasury: PublicKey) {
+      return program.methods
+        .setRegistrationPolicy(new anchor.BN(fee), new anchor.BN(0), treasury)
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    it("Reports the crate's version, the fixed-window algorithm and the config epoch", async () => {
+      const manifest = fs.readFileSync("programs/solana-rate-limiter/Cargo.toml", "utf8");
+      const [, ...version] = manifest.match(/^version = "(\d+)\.(\d+)\.(\d+)/m)!;
+      const info = await deploymentInfo();
+      assert.deepEqual(info.programVersion, version.map(Number));
+      assert.equal(info.limitAlgorithm, 0);
+      const config = await program.account.globalConfig.fetch(globalConfigPda);
+      assert.equal(info.configEpoch.toNumber(), config.configEpoch.toNumber());
+    });
+
+    it("Sets the fee bit only while a registration fee is charged", async () => {
+      assert.equal((await deploymentInfo()).featuresBitmask.toNumber() & FEATURE_REGISTRATION_FEE, 0);
+      await setFee(5_000, Keypair.generate().publicKey);
+      try {
+        const info = await deploymentInfo();
+        assert.equal(info.featuresBitmask.toNumber() & FEATURE_REGISTRATION_FEE, FEATURE_REGISTRATION_FEE);
+      } finally {
+        await setFee(0, PublicKey.default);
+      }
+      assert.equal((await deploymentInfo()).featuresBitmask.toNumber() & FEATURE_REGISTRATION_FEE, 0);
+    });
+  });
+
+  describe("registration preview", () => {
+    const FEE = 5_000;
+    let treasury: Keypair;
+    let original: { maxRequests: anchor.BN; windowSeconds: anchor.BN; burstLimit: anchor.BN };
+
+    function preview(client: PublicKey, payer: PublicKey) {
+      return program.methods
+        .previewRegistration()
+        .accounts({ globalConfig: globalConfigPda, schedule: null, client, payer })
+        .view();
+    }
+
+    function setPolicy(fee: number, cooldownSeconds: number, treasuryKey: PublicKey) {
+      return program.methods
+        .setRegistrationPolicy(new anchor.BN(fee), new anchor.BN(cooldownSeconds), treasuryKey)
+        .accounts({ globalConfig: globalConfigPda, admin: admin.publicKey })
+        .rpc({ commitment: "confirmed" });
+    }
+
+    function register(client: Keypair, payer: Keypair) {
+      return program.methods
+        .registerClient()
+        .accounts({ globalConfig: globalConfigPda, client: client.publicKey, payer: payer.publicKey, treasury: treasury.publicKey })
+        .signers([client, payer])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    before(async () => {
+      original = await program.account.globalConfig.fetch(globalConfigPda);
+      treasury = await fundedWallet();
+      await setConfig(7, 60, 14);
+      await setPolicy(FEE, 60, treasury.publicKey);
+    });
+
+    after(async () => {
+      await setPolicy(0, 0, PublicKey.default);
+      await setConfig(original.maxRequests.toNumber(), original.windowSeconds.toNumber(), original.burstLimit.toNumber());
+    });
+
+    it("Quotes the limits, fee and rent a registration then charges", async () => {
+      const client = Keypair.generate();
+      const payer = await fundedWallet();
+      const quote = await preview(client.publicKey, payer.publicKey);
+      assert.equal(quote.maxRequests.toNumber(), 7);
+      assert.equal(quote.windowSeconds.toNumber(), 60);
+      assert.equal(quote.burstLimit.toNumber(), 14);
+      assert.equal(quote.registrationFeeLamports.toNumber(), FEE);
+      assert.isTrue(quote.registrationOpen);
+      assert.isFalse(quote.requiresAllowlist);
+
+      // The admin pays the transaction fee, so the payer loses exactly what was quoted
+      const before = await provider.connection.getBalance(payer.publicKey, "confirmed");
+      await register(client, payer);
+      const after = await provider.connection.getBalance(payer.publicKey, "confirmed");
+      assert.equal(before - after, quote.registrationFeeLamports.toNumber() + quote.rentLamports.toNumber());
+      const bucket = await program.account.clientBucket.fetch(bucketFor(client.publicKey));
+      assert.equal(bucket.owner.toBase58(), client.publicKey.toBase58());
+    });
+
+    it("Reports registration closed while the payer cools down or the client is taken", async () => {
+      const client = Keypair.generate();
+      const payer = await fundedWallet();
+      await register(client, payer);
+
+      const next = Keypair.generate();
+      assert.isFalse((await preview(next.publicKey, payer.publicKey)).registrationOpen);
+      const { error } = await expectRejection(register(next, payer));
+      assert.include(error.message, "RegistrationCooldown");
+
+      const otherPayer = await fundedWallet();
+      assert.isFalse((await preview(client.publicKey, otherPayer.publicKey)).registrationOpen);
+      assert.isTrue((await preview(next.publicKey, otherPayer.publicKey)).registrationOpen);
+    });
+
+    it("Follows a config change without anyone re-registering", async () => {
+      await setConfig(3, 30, 3);
+      const quote = await preview(Keypair.generate().publicKey, admin.publicKey);
+      assert.equal(quote.maxRequests.toNumber(), 3);
+      assert.equal(quote.windowSeconds.toNumber(), 30);
+      assert.equal(quote.burstLimit.toNumber(), 3);
+      await setConfig(7, 60, 14);
+    });
+
+    it("Flags a program-owned client as needing an allowlisted caller", async () => {
+      // The config account is owned by the limiter itself, which is enough here
+      const quote = await preview(globalConfigPda, admin.publicKey);
+      assert.isTrue(quote.requiresAllowlist);
+    });
+  });
 });